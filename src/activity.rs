@@ -0,0 +1,201 @@
+//! Button activity shared between `sd watch` and `sd idle`.
+//!
+//! A running watch records when it started, when a key was last pressed and
+//! a periodic heartbeat in a small JSON file. `sd idle` reads that file to
+//! report how long the deck has gone untouched; a stale heartbeat means no
+//! tracker is running.
+//!
+//! Location: `~/.local/share/sd/activity.json`
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, trace};
+
+use crate::error::{Result, SdError};
+
+/// How often a watch refreshes its heartbeat while idle.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A heartbeat older than this means the tracker is gone.
+pub const STALE_AFTER: Duration = Duration::from_secs(10);
+
+/// Activity recorded by a running tracker.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivityRecord {
+    /// Process id of the tracker.
+    pub pid: u32,
+    /// When the tracker started.
+    pub started_at: DateTime<Utc>,
+    /// Last key press, if any since the tracker started.
+    pub last_press_at: Option<DateTime<Utc>>,
+    /// Last time the tracker wrote this record.
+    pub heartbeat_at: DateTime<Utc>,
+}
+
+impl ActivityRecord {
+    /// Whether the tracker's heartbeat is recent enough to trust at `now`.
+    #[must_use]
+    pub fn is_live(&self, now: DateTime<Utc>) -> bool {
+        // A heartbeat from the future (clock skew) counts as fresh
+        (now - self.heartbeat_at)
+            .to_std()
+            .ok()
+            .is_none_or(|age| age <= STALE_AFTER)
+    }
+
+    /// Seconds since the last press, or since the tracker started if nothing
+    /// has been pressed yet.
+    #[must_use]
+    pub fn idle_seconds(&self, now: DateTime<Utc>) -> u64 {
+        let since = self.last_press_at.unwrap_or(self.started_at);
+        (now - since).to_std().map_or(0, |d| d.as_secs())
+    }
+}
+
+/// Idle time reported by `sd idle`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IdleStatus {
+    /// Seconds since the last key press (or tracker start).
+    pub idle_seconds: u64,
+    /// Last key press, if one was seen.
+    pub last_press_at: Option<DateTime<Utc>>,
+}
+
+/// Writes activity for a running watch.
+#[derive(Debug)]
+pub struct ActivityTracker {
+    path: PathBuf,
+    record: ActivityRecord,
+    last_write: Option<Instant>,
+}
+
+impl ActivityTracker {
+    /// Start tracking into `path`.
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let now = Utc::now();
+        Self {
+            path: path.into(),
+            record: ActivityRecord {
+                pid: std::process::id(),
+                started_at: now,
+                last_press_at: None,
+                heartbeat_at: now,
+            },
+            last_write: None,
+        }
+    }
+
+    /// Start tracking at the standard location.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data directory can't be determined.
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::new(default_activity_path()?))
+    }
+
+    /// Record a key press and write it out immediately.
+    pub fn pressed(&mut self) {
+        self.record.last_press_at = Some(Utc::now());
+        self.write();
+    }
+
+    /// Refresh the heartbeat if it's due.
+    pub fn tick(&mut self) {
+        if self
+            .last_write
+            .is_none_or(|at| at.elapsed() >= HEARTBEAT_INTERVAL)
+        {
+            self.write();
+        }
+    }
+
+    /// Write the record; failures are logged, never fatal to the watch.
+    fn write(&mut self) {
+        self.record.heartbeat_at = Utc::now();
+        self.last_write = Some(Instant::now());
+        if let Err(e) = write_record(&self.path, &self.record) {
+            debug!(error = %e, path = %self.path.display(), "Failed to write activity");
+        } else {
+            trace!(path = %self.path.display(), "Activity heartbeat");
+        }
+    }
+}
+
+fn write_record(path: &Path, record: &ActivityRecord) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string(record)
+        .map_err(|e| SdError::Other(format!("Failed to serialize activity: {e}")))?;
+    // Write then rename so readers never see a partial record
+    let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Read the idle time recorded at `path`.
+///
+/// # Errors
+///
+/// Returns [`SdError::NoActivityTracker`] if no tracker has written a recent
+/// heartbeat.
+pub fn read_idle(path: &Path, now: DateTime<Utc>) -> Result<IdleStatus> {
+    let record = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str::<ActivityRecord>(&text).ok())
+        .filter(|record| record.is_live(now))
+        .ok_or(SdError::NoActivityTracker)?;
+    Ok(IdleStatus {
+        idle_seconds: record.idle_seconds(now),
+        last_press_at: record.last_press_at,
+    })
+}
+
+/// Returns the activity file path.
+///
+/// Location: `~/.local/share/sd/activity.json`
+///
+/// # Errors
+///
+/// Returns an error if the data directory can't be determined.
+pub fn default_activity_path() -> Result<PathBuf> {
+    let data_dir = dirs::data_local_dir()
+        .ok_or_else(|| SdError::Other("Could not determine local data directory".to_string()))?;
+    Ok(data_dir.join("sd").join("activity.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_from_tracker() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("activity.json");
+        assert!(matches!(
+            read_idle(&path, Utc::now()),
+            Err(SdError::NoActivityTracker)
+        ));
+
+        let mut tracker = ActivityTracker::new(&path);
+        tracker.tick();
+        let status = read_idle(&path, Utc::now()).unwrap();
+        assert!(status.last_press_at.is_none());
+
+        tracker.pressed();
+        let later = Utc::now() + chrono::Duration::seconds(5);
+        let status = read_idle(&path, later).unwrap();
+        assert!(status.last_press_at.is_some());
+        assert!((4..=6).contains(&status.idle_seconds));
+
+        // Heartbeat gone stale: the tracker is presumed dead
+        let much_later = Utc::now() + chrono::Duration::seconds(60);
+        assert!(read_idle(&path, much_later).is_err());
+    }
+}
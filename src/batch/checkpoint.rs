@@ -0,0 +1,296 @@
+//! Resumable batches for `set-keys --checkpoint`.
+//!
+//! A checkpoint records which keys a batch has written, so re-running the
+//! same command after an interruption skips them. The file is a small JSON
+//! object naming the batch's source directory, device and key layout, and
+//! listing completed key indices:
+//!
+//! ```text
+//! {"source":"/home/me/layout","serial":"CL12345","layout":"8x4","completed":[0,1,2,5]}
+//! ```
+//!
+//! It is rewritten after every successful key and removed once the batch
+//! finishes without errors. A missing file starts a fresh batch; a file
+//! recorded for another directory, device or layout is refused, since
+//! skipping its keys would leave the wrong images in place.
+
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{debug, instrument};
+
+/// Errors that can occur while reading or writing a checkpoint.
+#[derive(Debug, Error)]
+pub enum CheckpointError {
+    /// Failed to read the checkpoint file.
+    #[error("failed to read checkpoint {0}: {1}")]
+    ReadError(PathBuf, #[source] io::Error),
+
+    /// The file exists but isn't a checkpoint.
+    #[error("checkpoint {0} is not valid: {1}")]
+    ParseError(PathBuf, #[source] serde_json::Error),
+
+    /// Failed to write or remove the checkpoint file.
+    #[error("failed to write checkpoint {0}: {1}")]
+    WriteError(PathBuf, #[source] io::Error),
+
+    /// The file belongs to a different batch.
+    #[error(
+        "checkpoint {path} was recorded for {field} {recorded}, not {current}; \
+         delete it to start the batch over"
+    )]
+    Mismatch {
+        path: PathBuf,
+        field: &'static str,
+        recorded: String,
+        current: String,
+    },
+}
+
+/// The batch a checkpoint belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointScope {
+    /// Canonical source directory.
+    pub source: PathBuf,
+    /// Device serial; empty when planning without a device.
+    pub serial: String,
+    /// Key layout as `COLSxROWS`.
+    pub layout: String,
+}
+
+impl CheckpointScope {
+    /// Scope for a batch from `dir` onto the device `serial` with a
+    /// `cols` x `rows` key grid.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `dir` can't be resolved.
+    pub fn new(dir: &Path, serial: &str, cols: u8, rows: u8) -> io::Result<Self> {
+        Ok(Self {
+            source: std::fs::canonicalize(dir)?,
+            serial: serial.to_string(),
+            layout: format!("{cols}x{rows}"),
+        })
+    }
+
+    /// The first field where a recorded scope differs from this one.
+    ///
+    /// The serial is only compared when both are known.
+    fn mismatch(&self, recorded: &Self) -> Option<(&'static str, String, String)> {
+        if recorded.source != self.source {
+            Some((
+                "source",
+                recorded.source.display().to_string(),
+                self.source.display().to_string(),
+            ))
+        } else if !recorded.serial.is_empty()
+            && !self.serial.is_empty()
+            && recorded.serial != self.serial
+        {
+            Some(("device", recorded.serial.clone(), self.serial.clone()))
+        } else if recorded.layout != self.layout {
+            Some(("layout", recorded.layout.clone(), self.layout.clone()))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckpointFile {
+    #[serde(flatten)]
+    scope: CheckpointScope,
+    completed: BTreeSet<u8>,
+}
+
+/// Keys already written by an earlier run of the same batch.
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: PathBuf,
+    file: CheckpointFile,
+}
+
+impl Checkpoint {
+    /// Open the checkpoint at `path` for the batch `scope`, starting empty
+    /// if it doesn't exist.
+    ///
+    /// The file is written straight away, so an unwritable path fails
+    /// before any key is touched.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read, parsed or written, or
+    /// was recorded for a different batch.
+    #[instrument]
+    pub fn open(path: &Path, scope: CheckpointScope) -> Result<Self, CheckpointError> {
+        let checkpoint = Self::read(path, scope)?;
+        checkpoint.save()?;
+        Ok(checkpoint)
+    }
+
+    /// Read the checkpoint at `path` without writing it (for dry runs).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or parsed, or was
+    /// recorded for a different batch.
+    #[instrument]
+    pub fn read(path: &Path, scope: CheckpointScope) -> Result<Self, CheckpointError> {
+        let file = match std::fs::read_to_string(path) {
+            Ok(text) => {
+                let file: CheckpointFile = serde_json::from_str(&text)
+                    .map_err(|e| CheckpointError::ParseError(path.to_path_buf(), e))?;
+                if let Some((field, recorded, current)) = scope.mismatch(&file.scope) {
+                    return Err(CheckpointError::Mismatch {
+                        path: path.to_path_buf(),
+                        field,
+                        recorded,
+                        current,
+                    });
+                }
+                CheckpointFile {
+                    scope,
+                    completed: file.completed,
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => CheckpointFile {
+                scope,
+                completed: BTreeSet::new(),
+            },
+            Err(e) => return Err(CheckpointError::ReadError(path.to_path_buf(), e)),
+        };
+        let checkpoint = Self {
+            path: path.to_path_buf(),
+            file,
+        };
+        debug!(completed = checkpoint.completed(), "Checkpoint opened");
+        Ok(checkpoint)
+    }
+
+    /// Number of keys recorded as written.
+    #[must_use]
+    pub fn completed(&self) -> usize {
+        self.file.completed.len()
+    }
+
+    /// Returns true if `key` was written by an earlier run.
+    #[must_use]
+    pub fn is_done(&self, key: u8) -> bool {
+        self.file.completed.contains(&key)
+    }
+
+    /// Record `key` as written and save the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written.
+    pub fn record(&mut self, key: u8) -> Result<(), CheckpointError> {
+        if self.file.completed.insert(key) {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// The batch is done: remove the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but can't be removed.
+    pub fn finish(self) -> Result<(), CheckpointError> {
+        match std::fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(CheckpointError::WriteError(self.path, e)),
+        }
+    }
+
+    fn save(&self) -> Result<(), CheckpointError> {
+        let failed = |e| CheckpointError::WriteError(self.path.clone(), e);
+        let json = serde_json::to_string(&self.file)
+            .map_err(io::Error::other)
+            .map_err(failed)?;
+        // Write then rename so an interruption never leaves a partial file
+        let tmp = self
+            .path
+            .with_extension(format!("{}.tmp", std::process::id()));
+        std::fs::write(&tmp, json).map_err(failed)?;
+        std::fs::rename(&tmp, &self.path).map_err(failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(serial: &str, layout: &str) -> CheckpointScope {
+        CheckpointScope {
+            source: PathBuf::from("/layouts/main"),
+            serial: serial.to_string(),
+            layout: layout.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_resumes_completed_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("layout.checkpoint");
+
+        let mut checkpoint = Checkpoint::open(&path, scope("CL1", "8x4")).unwrap();
+        assert!(path.exists());
+        checkpoint.record(3).unwrap();
+        checkpoint.record(0).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            r#"{"source":"/layouts/main","serial":"CL1","layout":"8x4","completed":[0,3]}"#
+        );
+
+        // A second run sees the first run's keys
+        let checkpoint = Checkpoint::open(&path, scope("CL1", "8x4")).unwrap();
+        assert_eq!(checkpoint.completed(), 2);
+        assert!(checkpoint.is_done(3) && !checkpoint.is_done(1));
+
+        checkpoint.finish().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_rejects_invalid_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.json");
+        std::fs::write(&path, "not json").unwrap();
+        assert!(matches!(
+            Checkpoint::open(&path, scope("CL1", "8x4")),
+            Err(CheckpointError::ParseError(..))
+        ));
+    }
+
+    #[test]
+    fn test_refuses_other_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("layout.checkpoint");
+        Checkpoint::open(&path, scope("CL1", "8x4"))
+            .unwrap()
+            .record(1)
+            .unwrap();
+
+        let field = |current| match Checkpoint::read(&path, current) {
+            Err(CheckpointError::Mismatch { field, .. }) => Some(field),
+            _ => None,
+        };
+        assert_eq!(field(scope("CL2", "8x4")), Some("device"));
+        assert_eq!(field(scope("CL1", "5x3")), Some("layout"));
+        let mut moved = scope("CL1", "8x4");
+        moved.source = PathBuf::from("/layouts/other");
+        assert_eq!(field(moved), Some("source"));
+
+        // Planning without a device can't check the serial
+        assert_eq!(field(scope("", "8x4")), None);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            r#"{"source":"/layouts/main","serial":"CL1","layout":"8x4","completed":[1]}"#
+        );
+    }
+}
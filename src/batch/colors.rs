@@ -0,0 +1,174 @@
+//! Generated per-key colors for `fill-all --randomize` and `rainbow`.
+//!
+//! Every key gets a distinct color, which makes these fills a quick visual
+//! check that each key on the deck responds. Random fills are reproducible:
+//! the seed is reported and can be passed back with `--seed`.
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// How to color each key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "scheme", rename_all = "lowercase")]
+pub enum ColorScheme {
+    /// Distinct random hues, reproducible from `seed`.
+    Random { seed: u64 },
+    /// A hue gradient running left to right across the grid.
+    Rainbow,
+}
+
+impl ColorScheme {
+    /// Random colors from `seed`, or from the clock if none is given.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)] // Low bits of the clock are plenty
+    pub fn random(seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64)
+        });
+        Self::Random { seed }
+    }
+
+    /// The seed that reproduces this fill, if it's random.
+    #[must_use]
+    pub const fn seed(&self) -> Option<u64> {
+        match self {
+            Self::Random { seed } => Some(*seed),
+            Self::Rainbow => None,
+        }
+    }
+
+    /// One color per key of a `cols`×`rows` grid, in key order.
+    #[must_use]
+    pub fn colors(&self, cols: u8, rows: u8) -> Vec<(u8, u8, u8)> {
+        let count = usize::from(cols) * usize::from(rows);
+        match self {
+            Self::Random { seed } => random_colors(count, *seed),
+            Self::Rainbow => rainbow_colors(cols, rows),
+        }
+    }
+}
+
+impl fmt::Display for ColorScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Random { seed } => write!(f, "random colors (seed {seed})"),
+            Self::Rainbow => write!(f, "a rainbow"),
+        }
+    }
+}
+
+/// Evenly spaced hues from a random start, shuffled, so no two keys match.
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+fn random_colors(count: usize, seed: u64) -> Vec<(u8, u8, u8)> {
+    let mut rng = SplitMix64(seed);
+    let offset = rng.next_f64();
+    let mut hues: Vec<f64> = (0..count)
+        .map(|i| (offset + i as f64 / count as f64).fract())
+        .collect();
+    // Fisher-Yates
+    for i in (1..hues.len()).rev() {
+        let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+        hues.swap(i, j);
+    }
+    hues.into_iter()
+        .map(|hue| {
+            let saturation = 0.65 + 0.35 * rng.next_f64();
+            let value = 0.75 + 0.25 * rng.next_f64();
+            hsv_to_rgb(hue, saturation, value)
+        })
+        .collect()
+}
+
+/// Hues from red to violet by column, stepping down each column's rows.
+#[allow(clippy::cast_precision_loss)]
+fn rainbow_colors(cols: u8, rows: u8) -> Vec<(u8, u8, u8)> {
+    let count = f64::from(cols) * f64::from(rows);
+    (0..rows)
+        .flat_map(|row| (0..cols).map(move |col| (row, col)))
+        .map(|(row, col)| {
+            let position = f64::from(col) * f64::from(rows) + f64::from(row);
+            // Stop short of wrapping back around to red
+            hsv_to_rgb(position / count * (300.0 / 360.0), 1.0, 1.0)
+        })
+        .collect()
+}
+
+/// Convert hue, saturation and value (all 0.0-1.0) to RGB.
+#[must_use]
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::many_single_char_names
+)]
+pub fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> (u8, u8, u8) {
+    let h = hue.rem_euclid(1.0) * 6.0;
+    let c = value * saturation;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let m = value - c;
+    let (r, g, b) = match h as u8 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let channel = |v: f64| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    (channel(r), channel(g), channel(b))
+}
+
+/// Small, seedable generator; quality is plenty for picking colors.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    const fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_hsv_to_rgb() {
+        assert_eq!(hsv_to_rgb(0.0, 1.0, 1.0), (255, 0, 0));
+        assert_eq!(hsv_to_rgb(1.0 / 3.0, 1.0, 1.0), (0, 255, 0));
+        assert_eq!(hsv_to_rgb(2.0 / 3.0, 1.0, 1.0), (0, 0, 255));
+        assert_eq!(hsv_to_rgb(0.5, 0.0, 1.0), (255, 255, 255));
+        assert_eq!(hsv_to_rgb(0.25, 1.0, 0.0), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_random_is_distinct_and_reproducible() {
+        let scheme = ColorScheme::random(Some(42));
+        let colors = scheme.colors(8, 4);
+        assert_eq!(colors.len(), 32);
+        assert_eq!(colors.iter().collect::<HashSet<_>>().len(), 32);
+        assert_eq!(colors, ColorScheme::random(Some(42)).colors(8, 4));
+        assert_ne!(colors, ColorScheme::random(Some(43)).colors(8, 4));
+    }
+
+    #[test]
+    fn test_rainbow_runs_left_to_right() {
+        let colors = ColorScheme::Rainbow.colors(5, 3);
+        assert_eq!(colors.len(), 15);
+        assert_eq!(colors.iter().collect::<HashSet<_>>().len(), 15);
+        // Top-left key starts at red
+        assert_eq!(colors[0], (255, 0, 0));
+        assert_eq!(ColorScheme::Rainbow.seed(), None);
+    }
+}
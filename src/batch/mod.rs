@@ -1,8 +1,16 @@
 //! Batch operations for Stream Deck key management.
 //!
 //! This module provides functionality for batch operations like setting multiple keys
-//! from a directory of images.
+//! from a directory of images or filling keys from a palette file.
 
+mod checkpoint;
+mod colors;
+mod pacing;
+mod palette;
 mod scanner;
 
-pub use scanner::{ScanResult, scan_directory};
+pub use checkpoint::{Checkpoint, CheckpointError, CheckpointScope};
+pub use colors::ColorScheme;
+pub use pacing::WritePacer;
+pub use palette::{PaletteEntry, PaletteError, load_palette, parse_palette, parse_palette_from};
+pub use scanner::{DuplicateKey, ScanResult, scan_directory};
@@ -0,0 +1,74 @@
+//! Chunked key writes for `--batch-size` and `--batch-delay`.
+//!
+//! Some hubs and older firmware drop writes, or the whole device, when a
+//! batch sends many key images back to back. Pausing after every few writes
+//! gives them time to catch up. By default nothing is paced.
+
+use std::time::Duration;
+
+use tracing::debug;
+
+/// Pauses between chunks of key writes in a batch loop.
+#[derive(Debug, Clone)]
+pub struct WritePacer {
+    batch_size: Option<usize>,
+    delay: Duration,
+    written: usize,
+}
+
+impl WritePacer {
+    /// Pause for `delay` after every `batch_size` writes; `None` (or 0)
+    /// writes everything back to back.
+    #[must_use]
+    pub fn new(batch_size: Option<usize>, delay: Duration) -> Self {
+        Self {
+            batch_size: batch_size.filter(|size| *size > 0),
+            delay,
+            written: 0,
+        }
+    }
+
+    /// Call before each key write; sleeps when the previous chunk is full.
+    pub fn before_write(&mut self) {
+        if let Some(size) = self.batch_size {
+            if self.written > 0 && self.written % size == 0 {
+                debug!(
+                    written = self.written,
+                    delay_ms = self.delay.as_millis(),
+                    "Pausing between write chunks"
+                );
+                std::thread::sleep(self.delay);
+            }
+        }
+        self.written += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_before_write_sleeps_after_full_chunk() {
+        let mut pacer = WritePacer::new(Some(2), Duration::from_millis(20));
+        let start = Instant::now();
+        pacer.before_write();
+        pacer.before_write();
+        assert!(start.elapsed() < Duration::from_millis(20));
+        pacer.before_write();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_unpaced_never_sleeps() {
+        for size in [None, Some(0)] {
+            let mut pacer = WritePacer::new(size, Duration::from_secs(60));
+            let start = Instant::now();
+            for _ in 0..8 {
+                pacer.before_write();
+            }
+            assert!(start.elapsed() < Duration::from_secs(1));
+        }
+    }
+}
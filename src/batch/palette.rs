@@ -0,0 +1,222 @@
+//! Palette files for batch color fills.
+//!
+//! A palette assigns colors to keys, one per line. Lines are either
+//! `index: color` or a bare color, which takes the key after the previous
+//! entry (starting at the first key, 0 unless `--key-base` says otherwise),
+//! so a plain list fills keys 0..N in order:
+//!
+//! ```text
+//! # Warm row
+//! ff0000
+//! ff8000
+//! 8: #00ff00
+//! 0000ff        # key 9
+//! ```
+//!
+//! Blank lines are ignored and `# ` starts a comment. Color strings are not
+//! validated here; callers check them with the CLI's color parser.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use thiserror::Error;
+use tracing::{debug, instrument};
+
+/// A single key assignment from a palette file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PaletteEntry {
+    /// Key index (0-based).
+    pub key: u8,
+    /// Color as written in the palette.
+    pub color: String,
+    /// 1-based line number in the palette file.
+    pub line: usize,
+}
+
+/// Errors that can occur while reading a palette.
+#[derive(Debug, Error)]
+pub enum PaletteError {
+    /// Failed to read the palette file.
+    #[error("failed to read palette {0}: {1}")]
+    ReadError(PathBuf, #[source] io::Error),
+
+    /// The key index before `:` is not a number in 0-255.
+    #[error("line {line}: invalid key index '{value}'")]
+    InvalidIndex { line: usize, value: String },
+
+    /// An `index:` line has no color after the colon.
+    #[error("line {line}: missing color after '{key}:'")]
+    MissingColor { line: usize, key: u8 },
+
+    /// An ordered entry would follow key 255.
+    #[error("line {line}: no key index left after 255")]
+    IndexOverflow { line: usize },
+
+    /// The same key is assigned twice.
+    #[error("line {line}: key {key} already assigned on line {first_line}")]
+    DuplicateKey {
+        line: usize,
+        key: u8,
+        first_line: usize,
+    },
+}
+
+/// Read and parse a palette file whose keys are numbered from `first_key`.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be read or is malformed.
+#[instrument]
+pub fn load_palette(path: &Path, first_key: u8) -> Result<Vec<PaletteEntry>, PaletteError> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| PaletteError::ReadError(path.to_path_buf(), e))?;
+    let entries = parse_palette_from(&text, first_key)?;
+    debug!(entries = entries.len(), "Palette loaded");
+    Ok(entries)
+}
+
+/// Parse palette text into key assignments, in file order.
+///
+/// # Errors
+///
+/// Returns an error for bad indices, missing colors or duplicate keys.
+pub fn parse_palette(text: &str) -> Result<Vec<PaletteEntry>, PaletteError> {
+    parse_palette_from(text, 0)
+}
+
+/// Parse palette text whose bare colors start at `first_key`.
+///
+/// # Errors
+///
+/// Returns an error for bad indices, missing colors or duplicate keys.
+pub fn parse_palette_from(text: &str, first_key: u8) -> Result<Vec<PaletteEntry>, PaletteError> {
+    let mut entries = Vec::new();
+    let mut seen: HashMap<u8, usize> = HashMap::new();
+    let mut next_key: Option<u8> = Some(first_key);
+
+    for (idx, raw) in text.lines().enumerate() {
+        let line = idx + 1;
+        let content = strip_comment(raw).trim();
+        if content.is_empty() {
+            continue;
+        }
+
+        let (key, color) = match content.split_once(':') {
+            Some((index, color)) => {
+                let index = index.trim();
+                let key = index
+                    .parse::<u8>()
+                    .map_err(|_| PaletteError::InvalidIndex {
+                        line,
+                        value: index.to_string(),
+                    })?;
+                let color = color.trim();
+                if color.is_empty() {
+                    return Err(PaletteError::MissingColor { line, key });
+                }
+                (key, color)
+            }
+            None => (
+                next_key.ok_or(PaletteError::IndexOverflow { line })?,
+                content,
+            ),
+        };
+
+        if let Some(&first_line) = seen.get(&key) {
+            return Err(PaletteError::DuplicateKey {
+                line,
+                key,
+                first_line,
+            });
+        }
+        seen.insert(key, line);
+        next_key = key.checked_add(1);
+
+        entries.push(PaletteEntry {
+            key,
+            color: color.to_string(),
+            line,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Strip a `#` comment: one at the start of the line or preceded by
+/// whitespace, and followed by whitespace or the end of the line (so
+/// `#ff0000` stays a color).
+fn strip_comment(line: &str) -> &str {
+    let bytes = line.as_bytes();
+    for (i, _) in line.match_indices('#') {
+        let at_boundary = i == 0 || bytes[i - 1].is_ascii_whitespace();
+        let followed_by_space = bytes.get(i + 1).is_none_or(u8::is_ascii_whitespace);
+        if at_boundary && followed_by_space {
+            return &line[..i];
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys_and_colors(entries: &[PaletteEntry]) -> Vec<(u8, &str)> {
+        entries.iter().map(|e| (e.key, e.color.as_str())).collect()
+    }
+
+    #[test]
+    fn test_parse_ordered_list() {
+        let entries = parse_palette("ff0000\n#00ff00\n\n0000ff\n").unwrap();
+        assert_eq!(
+            keys_and_colors(&entries),
+            vec![(0, "ff0000"), (1, "#00ff00"), (2, "0000ff")]
+        );
+        assert_eq!(entries[2].line, 4);
+    }
+
+    #[test]
+    fn test_parse_indexed_and_mixed() {
+        let text = "# Palette\n5: ff0000\n00ff00   # follows key 5\n 0 : #0000ff\n";
+        let entries = parse_palette(text).unwrap();
+        assert_eq!(
+            keys_and_colors(&entries),
+            vec![(5, "ff0000"), (6, "00ff00"), (0, "#0000ff")]
+        );
+    }
+
+    #[test]
+    fn test_parse_from_first_key() {
+        let entries = parse_palette_from("ff0000\n00ff00\n8: 0000ff\n", 1).unwrap();
+        assert_eq!(
+            keys_and_colors(&entries),
+            vec![(1, "ff0000"), (2, "00ff00"), (8, "0000ff")]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            parse_palette("x: ff0000"),
+            Err(PaletteError::InvalidIndex { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_palette("ff0000\n3:"),
+            Err(PaletteError::MissingColor { line: 2, key: 3 })
+        ));
+        assert!(matches!(
+            parse_palette("ff0000\n0: 00ff00"),
+            Err(PaletteError::DuplicateKey {
+                line: 2,
+                key: 0,
+                first_line: 1
+            })
+        ));
+        assert!(matches!(
+            parse_palette("255: ff0000\n00ff00"),
+            Err(PaletteError::IndexOverflow { line: 2 })
+        ));
+    }
+}
@@ -2,6 +2,7 @@
 //!
 //! Scans directories for images matching a pattern and maps them to Stream Deck key indices.
 
+use std::collections::BTreeMap;
 use std::io;
 use std::path::{Path, PathBuf};
 
@@ -18,6 +19,8 @@ pub struct ScanResult {
     pub unmatched: Vec<PathBuf>,
     /// Files that matched but had errors (path, reason).
     pub invalid: Vec<(PathBuf, String)>,
+    /// Keys matched by more than one file (sorted by key index).
+    pub duplicates: Vec<DuplicateKey>,
 }
 
 impl ScanResult {
@@ -35,6 +38,30 @@ impl ScanResult {
     pub fn has_invalid(&self) -> bool {
         !self.invalid.is_empty()
     }
+
+    /// Returns true if any key was matched by more than one file.
+    pub fn has_duplicates(&self) -> bool {
+        !self.duplicates.is_empty()
+    }
+}
+
+/// A key index claimed by several files (e.g. `key-0.png` and `key-00.png`).
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateKey {
+    /// Key index (0-based).
+    pub key: u8,
+    /// Every matching file, in filename order. The last one is the file kept
+    /// in [`ScanResult::mappings`].
+    pub paths: Vec<PathBuf>,
+}
+
+impl DuplicateKey {
+    /// The file used for this key.
+    pub fn kept(&self) -> &Path {
+        self.paths
+            .last()
+            .expect("duplicates have at least two paths")
+    }
 }
 
 /// A mapping from a key index to an image file.
@@ -86,6 +113,8 @@ pub enum ScanError {
 /// - `mappings`: Files that matched and have valid key indices (sorted by key).
 /// - `unmatched`: Files that didn't match the pattern.
 /// - `invalid`: Files that matched but had errors (e.g., key index out of range).
+/// - `duplicates`: Keys matched by several files; the last file in filename
+///   order is the one kept in `mappings`.
 ///
 /// # Example
 ///
@@ -111,7 +140,7 @@ pub fn scan_directory(dir: &Path, pattern: &str, key_count: u8) -> Result<ScanRe
     let mut mappings = Vec::new();
     let mut unmatched = Vec::new();
     let mut invalid = Vec::new();
-    let mut seen_keys: std::collections::HashMap<u8, PathBuf> = std::collections::HashMap::new();
+    let mut seen_keys: BTreeMap<u8, Vec<PathBuf>> = BTreeMap::new();
 
     // Read directory entries
     let dir_entries =
@@ -137,7 +166,7 @@ pub fn scan_directory(dir: &Path, pattern: &str, key_count: u8) -> Result<ScanRe
         match extract_key_index(&path, pattern) {
             Some(key) if key < key_count => {
                 // Check for duplicate key indices
-                if let Some(prev_path) = seen_keys.get(&key) {
+                if let Some(prev_path) = seen_keys.get(&key).and_then(|paths| paths.last()) {
                     warn!(
                         key = %key,
                         prev_path = %prev_path.display(),
@@ -159,7 +188,7 @@ pub fn scan_directory(dir: &Path, pattern: &str, key_count: u8) -> Result<ScanRe
                     "Matched key file"
                 );
 
-                seen_keys.insert(key, path.clone());
+                seen_keys.entry(key).or_default().push(path.clone());
                 mappings.push(KeyMapping {
                     key,
                     path,
@@ -188,10 +217,17 @@ pub fn scan_directory(dir: &Path, pattern: &str, key_count: u8) -> Result<ScanRe
     // Sort mappings by key index for consistent ordering
     mappings.sort_by_key(|m| m.key);
 
+    let duplicates: Vec<DuplicateKey> = seen_keys
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(key, paths)| DuplicateKey { key, paths })
+        .collect();
+
     info!(
         matched = %mappings.len(),
         unmatched = %unmatched.len(),
         invalid = %invalid.len(),
+        duplicates = %duplicates.len(),
         "Directory scan complete"
     );
 
@@ -199,6 +235,7 @@ pub fn scan_directory(dir: &Path, pattern: &str, key_count: u8) -> Result<ScanRe
         mappings,
         unmatched,
         invalid,
+        duplicates,
     })
 }
 
@@ -318,6 +355,57 @@ mod tests {
         assert!(result.invalid[0].1.contains("out of range"));
     }
 
+    #[test]
+    fn test_scan_directory_duplicates() {
+        let tmp = TempDir::new().unwrap();
+        create_test_file(tmp.path(), "key-1.png");
+        create_test_file(tmp.path(), "key-2.png");
+        create_test_file(tmp.path(), "key-01.png");
+        create_test_file(tmp.path(), "key-001.png");
+
+        let result = scan_directory(tmp.path(), "key-{index}.png", 32).unwrap();
+
+        assert!(result.has_duplicates());
+        assert_eq!(result.duplicates.len(), 1);
+        let dup = &result.duplicates[0];
+        assert_eq!(dup.key, 1);
+        assert_eq!(dup.paths.len(), 3);
+        // Kept file is the last in filename order, and only it is mapped
+        assert_eq!(dup.kept(), tmp.path().join("key-1.png"));
+        assert_eq!(result.mappings.len(), 2);
+        assert_eq!(result.mappings[0].path, tmp.path().join("key-1.png"));
+    }
+
+    #[test]
+    #[allow(clippy::literal_string_with_formatting_args)]
+    fn test_scan_directory_padded_and_unpadded_collide() {
+        let tmp = TempDir::new().unwrap();
+        create_test_file(tmp.path(), "key-5.png");
+        create_test_file(tmp.path(), "key-05.png");
+        create_test_file(tmp.path(), "key-06.png");
+
+        let result = scan_directory(tmp.path(), "key-{index:02d}.png", 32).unwrap();
+
+        assert_eq!(result.duplicates.len(), 1);
+        assert_eq!(result.duplicates[0].key, 5);
+        assert_eq!(
+            result.duplicates[0].paths,
+            vec![tmp.path().join("key-05.png"), tmp.path().join("key-5.png")]
+        );
+        assert_eq!(result.mappings.len(), 2);
+    }
+
+    #[test]
+    fn test_scan_directory_no_duplicates() {
+        let tmp = TempDir::new().unwrap();
+        create_test_file(tmp.path(), "key-0.png");
+        create_test_file(tmp.path(), "key-10.png");
+
+        let result = scan_directory(tmp.path(), "key-{index}.png", 32).unwrap();
+
+        assert!(!result.has_duplicates());
+    }
+
     #[test]
     fn test_scan_directory_empty() {
         let tmp = TempDir::new().unwrap();
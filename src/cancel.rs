@@ -0,0 +1,108 @@
+//! Cooperative cancellation for long-running operations.
+//!
+//! Batch and multi-key uploads check a [`CancelToken`] between keys so a
+//! caller (Ctrl+C in the CLI, or a future server/daemon control channel) can
+//! stop them without leaving a half-written key behind.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tracing::{debug, warn};
+
+use crate::error::{Result, SdError};
+
+/// Exit code used when a second Ctrl+C aborts immediately (128 + SIGINT).
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Shared flag that asks an in-progress operation to stop.
+///
+/// Cloning is cheap; all clones observe the same cancellation.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Create a token that is not cancelled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation.
+    pub fn cancel(&self) {
+        debug!("Cancellation requested");
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true once cancellation has been requested.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Return [`SdError::Cancelled`] if cancellation has been requested.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the token is cancelled.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(SdError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Create a token cancelled by the first Ctrl+C.
+    ///
+    /// The first interrupt lets the operation stop between keys and report
+    /// partial results; a second one exits immediately.
+    #[must_use]
+    pub fn on_ctrl_c() -> Self {
+        let token = Self::new();
+        let watcher = token.clone();
+        std::thread::spawn(move || {
+            let Ok(runtime) = tokio::runtime::Builder::new_current_thread()
+                .enable_io()
+                .build()
+            else {
+                warn!("Failed to start Ctrl+C watcher; interrupts will abort immediately");
+                return;
+            };
+            runtime.block_on(async {
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    warn!("Interrupted; stopping after the current key (Ctrl+C again to abort)");
+                    watcher.cancel();
+                }
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    std::process::exit(INTERRUPTED_EXIT_CODE);
+                }
+            });
+        });
+        token
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_cancellation() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+        assert!(clone.check().is_ok());
+
+        token.cancel();
+        assert!(clone.is_cancelled());
+        assert!(matches!(clone.check(), Err(SdError::Cancelled)));
+    }
+
+    #[test]
+    fn test_cancel_from_another_thread() {
+        let token = CancelToken::new();
+        let remote = token.clone();
+        std::thread::spawn(move || remote.cancel()).join().unwrap();
+        assert!(token.is_cancelled());
+    }
+}
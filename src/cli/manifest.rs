@@ -0,0 +1,170 @@
+//! Machine-readable command list for `sd --list-commands`.
+//!
+//! Walks the clap definition, so the manifest always matches the real CLI.
+//! Each runnable command is listed with its full invocation and arguments:
+//!
+//! ```text
+//! [{"command":"sd set-key","about":"...","args":[
+//!    {"name":"key","positional":true,"kind":"value","required":true,...},
+//!    {"name":"resize","long":"resize","kind":"value","default":"fit",
+//!     "possible_values":["fit","fill","stretch"],...}]}, ...]
+//! ```
+//!
+//! Global options are listed once, on the root `sd` entry.
+
+use clap::{Arg, ArgAction, Command, CommandFactory};
+use serde::Serialize;
+
+use super::Cli;
+
+/// One runnable command.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandInfo {
+    /// Full invocation, e.g. `sd snapshot show`.
+    pub command: String,
+    /// One-line description.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub about: Option<String>,
+    /// Other names the command answers to.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// Arguments and options, in definition order.
+    pub args: Vec<ArgInfo>,
+}
+
+/// One argument or option of a command.
+#[derive(Debug, Clone, Serialize)]
+pub struct ArgInfo {
+    /// Argument name, as clap knows it.
+    pub name: String,
+    /// `--long` spelling, without dashes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub long: Option<String>,
+    /// `-s` spelling, without the dash.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short: Option<char>,
+    /// True for arguments given by position rather than by flag.
+    pub positional: bool,
+    /// `flag` (no value), `count` (repeatable flag), `value` or `list`.
+    pub kind: &'static str,
+    /// Placeholder shown in help, e.g. `FILE`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_name: Option<String>,
+    /// Accepted values, for enumerated options.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub possible_values: Vec<String>,
+    /// Value used when the argument is omitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    /// Whether the argument must be given.
+    pub required: bool,
+    /// Whether the option applies to every command.
+    pub global: bool,
+    /// Environment variable that can supply the value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<String>,
+    /// Help text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub help: Option<String>,
+}
+
+/// Every visible command of the CLI, root first.
+#[must_use]
+pub fn command_manifest() -> Vec<CommandInfo> {
+    let root = Cli::command();
+    let mut commands = Vec::new();
+    collect(&root, root.get_name(), &mut commands);
+    commands
+}
+
+fn collect(command: &Command, path: &str, commands: &mut Vec<CommandInfo>) {
+    // A group that needs a subcommand can't run on its own
+    if !command.is_subcommand_required_set() {
+        commands.push(CommandInfo {
+            command: path.to_string(),
+            about: command.get_about().map(ToString::to_string),
+            aliases: command.get_visible_aliases().map(str::to_string).collect(),
+            args: command
+                .get_arguments()
+                .filter(|arg| !arg.is_hide_set())
+                .filter_map(arg_info)
+                .collect(),
+        });
+    }
+    for sub in command.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        collect(sub, &format!("{path} {}", sub.get_name()), commands);
+    }
+}
+
+fn arg_info(arg: &Arg) -> Option<ArgInfo> {
+    let kind = match arg.get_action() {
+        ArgAction::SetTrue | ArgAction::SetFalse => "flag",
+        ArgAction::Count => "count",
+        ArgAction::Append => "list",
+        ArgAction::Set => "value",
+        // --help and --version
+        _ => return None,
+    };
+    let defaults: Vec<String> = arg
+        .get_default_values()
+        .iter()
+        .map(|value| value.to_string_lossy().into_owned())
+        .collect();
+    Some(ArgInfo {
+        name: arg.get_id().as_str().to_string(),
+        long: arg.get_long().map(str::to_string),
+        short: arg.get_short(),
+        positional: arg.is_positional(),
+        kind,
+        value_name: arg
+            .get_value_names()
+            .and_then(|names| names.first())
+            .map(ToString::to_string)
+            .filter(|_| kind != "flag" && kind != "count"),
+        possible_values: arg
+            .get_possible_values()
+            .iter()
+            .filter(|value| !value.is_hide_set())
+            .map(|value| value.get_name().to_string())
+            .collect(),
+        // Flags default to false, which says nothing
+        default: (!defaults.is_empty() && kind != "flag" && kind != "count")
+            .then(|| defaults.join(",")),
+        required: arg.is_required_set(),
+        global: arg.is_global_set(),
+        env: arg.get_env().map(|env| env.to_string_lossy().into_owned()),
+        help: arg.get_help().map(ToString::to_string),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_describes_commands_and_args() {
+        let manifest = command_manifest();
+        assert_eq!(manifest[0].command, "sd");
+        assert!(
+            manifest[0]
+                .args
+                .iter()
+                .any(|arg| arg.name == "robot" && arg.global)
+        );
+
+        let set_key = manifest.iter().find(|c| c.command == "sd set-key").unwrap();
+        let key = set_key.args.iter().find(|arg| arg.name == "key").unwrap();
+        assert!(key.positional && key.required);
+        let resize = set_key
+            .args
+            .iter()
+            .find(|arg| arg.name == "resize")
+            .unwrap();
+        assert_eq!(resize.default.as_deref(), Some("fit"));
+        assert!(resize.possible_values.contains(&"stretch".to_string()));
+
+        // Groups are listed by their runnable subcommands
+        assert!(manifest.iter().any(|c| c.command == "sd snapshot show"));
+        assert!(!manifest.iter().any(|c| c.command == "sd snapshot"));
+    }
+}
@@ -1,10 +1,25 @@
 //! CLI argument definitions and command dispatch.
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::time::Duration;
 
 use clap::{Parser, Subcommand, ValueEnum};
 
+use crate::batch::WritePacer;
+use crate::device::{DeviceModel, HexBytes, RawReportKind};
+use crate::error::Result;
+use crate::key_numbering::{KeyNumbering, KeyOrder, LayoutRotation};
+use crate::output::{JsonVersion, OutputTemplates, TimeFormat};
+use crate::snapshot::ArchiveCompression;
+
+mod manifest;
+
+pub use manifest::{ArgInfo, CommandInfo, command_manifest};
+
+/// Narrowest `--width` accepted; panels can't fit their borders below it.
+pub const MIN_OUTPUT_WIDTH: u16 = 20;
+
 /// Stream Deck CLI - Cross-platform control for Elgato Stream Deck devices.
 ///
 /// Robot Mode: Use --robot or --json for machine-parseable output optimized for AI agents.
@@ -39,14 +54,140 @@ pub struct Cli {
     #[arg(long, global = true, env = "NO_COLOR")]
     pub no_color: bool,
 
+    /// Wrap human output at COLS columns instead of the terminal width
+    /// (default: $COLUMNS, then the detected width)
+    #[arg(
+        long,
+        global = true,
+        visible_alias = "wrap",
+        value_name = "COLS",
+        value_parser = clap::value_parser!(u16).range(i64::from(MIN_OUTPUT_WIDTH)..)
+    )]
+    pub width: Option<u16>,
+
+    /// Tag this run's output with TEXT: a `label` field on robot-mode
+    /// objects, a `[TEXT]` prefix on human messages
+    #[arg(long, global = true, value_name = "TEXT", env = "SD_LABEL")]
+    pub label: Option<String>,
+
+    /// Draw the key layout with the device's physical gaps between keys
+    #[arg(
+        long,
+        global = true,
+        visible_alias = "key-gap-indicator",
+        env = "SD_PREVIEW_GAPS"
+    )]
+    pub gaps: bool,
+
+    /// Pretty-print structured output (overrides --format=json-compact)
+    #[arg(long, global = true, conflicts_with = "compact")]
+    pub pretty: bool,
+
+    /// Compact single-line structured output, whatever the format
+    #[arg(long, global = true)]
+    pub compact: bool,
+
+    /// Print only the value at this JSON Pointer (e.g. /device/serial); JSON output only
+    #[arg(
+        long,
+        global = true,
+        value_name = "POINTER",
+        visible_alias = "json-pointer"
+    )]
+    pub query: Option<String>,
+
+    /// Pin robot output to a documented JSON shape (v1, v2, v3, v4, latest) for older integrations
+    #[arg(
+        long,
+        global = true,
+        value_name = "VERSION",
+        default_value = "latest",
+        visible_alias = "compat",
+        env = "SD_JSON_COMPAT"
+    )]
+    pub json_compat: JsonVersion,
+
+    /// Timestamp style for human output: default, relative, or a strftime pattern
+    #[arg(
+        long,
+        global = true,
+        value_name = "FORMAT",
+        default_value = "default",
+        env = "SD_TIME_FORMAT"
+    )]
+    pub time_format: TimeFormat,
+
     /// Preview what would happen without making changes
-    #[arg(long, short = 'n', global = true)]
+    #[arg(long, short = 'n', global = true, env = "SD_DRY_RUN")]
     pub dry_run: bool,
 
+    /// Make changes even when dry-run is the default (SD_DRY_RUN or `default_dry_run` in settings)
+    #[arg(
+        long,
+        global = true,
+        visible_alias = "apply",
+        overrides_with = "dry_run"
+    )]
+    pub no_dry_run: bool,
+
+    /// Dry-run default from the settings file (`default_dry_run`).
+    #[arg(skip)]
+    pub default_dry_run: bool,
+
+    /// Offline model from the settings file (`default_model`).
+    #[arg(skip)]
+    pub default_model: Option<DeviceModel>,
+
+    /// Human-mode message templates from the settings file (`output.templates`).
+    #[arg(skip)]
+    pub output_templates: OutputTemplates,
+
+    /// Context profiles from the settings file (`contexts`).
+    #[arg(skip)]
+    pub contexts: BTreeMap<String, PathBuf>,
+
+    /// Also write a JSON report of this run (command, device, results, timing, errors) to PATH
+    #[arg(long, global = true, value_name = "PATH", env = "SD_REPORT")]
+    pub report: Option<PathBuf>,
+
+    /// On failure, explain the error: likely causes, fixes and docs
+    #[arg(long, global = true)]
+    pub explain: bool,
+
+    /// In batch results, list only the keys that failed (summary counts
+    /// still cover every key)
+    #[arg(long, global = true, env = "SD_ONLY_ERRORS")]
+    pub only_errors: bool,
+
+    /// Also append output as JSON Lines to FILE, alongside the normal output
+    ///
+    /// Messages, errors, events and results are written to both. Output a
+    /// command prints directly (such as dry-run previews) only goes to stdout.
+    #[arg(long, global = true, value_name = "FILE", env = "SD_ALSO_JSON")]
+    pub also_json: Option<PathBuf>,
+
+    /// In robot mode, finish with a `{"event":"complete",...}` line giving the
+    /// command, its duration and whether it succeeded
+    #[arg(long, global = true, env = "SD_EMIT_COMPLETE")]
+    pub emit_complete: bool,
+
+    /// Bypass the on-disk cache of resized key images
+    #[arg(long, global = true, env = "SD_NO_CACHE")]
+    pub no_cache: bool,
+
+    /// Don't warn when images will be resized (the resize still happens)
+    #[arg(long, global = true, env = "SD_NO_RESIZE_WARNING")]
+    pub no_resize_warning: bool,
+
     /// Target device by serial number (required if multiple devices connected)
     #[arg(long, short = 's', global = true, env = "SD_SERIAL")]
     pub serial: Option<String>,
 
+    /// Pin the target device across invocations: read the serial from PATH,
+    /// or pick a device and record its serial there on first use
+    #[arg(long, global = true, value_name = "PATH", env = "SD_SERIAL_FILE")]
+    pub serial_file: Option<PathBuf>,
+
     /// Retry N times on connection failure (default: 0 = no retry)
     #[arg(long, global = true, default_value = "0", env = "SD_RETRY")]
     pub retry: u32,
@@ -68,10 +209,185 @@ pub struct Cli {
     #[arg(long, global = true, default_value = "1.5", env = "SD_RETRY_BACKOFF")]
     pub retry_backoff: f32,
 
+    /// Randomize each retry delay by up to PERCENT either way (default: 0)
+    ///
+    /// When several sd processes lose their devices at once (a shared USB
+    /// hub resetting, say), jitter keeps them from retrying in lockstep.
+    #[arg(
+        long,
+        global = true,
+        default_value = "0",
+        value_name = "PERCENT",
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        env = "SD_RETRY_JITTER"
+    )]
+    pub retry_jitter: u8,
+
+    /// Verify each key image after writing it, retrying on mismatch, where
+    /// the device supports it
+    ///
+    /// No current Stream Deck model can read key images back, so for now
+    /// this only warns. The counts of verified, mismatched and unverified
+    /// writes go in the --report file and the --emit-complete event.
+    #[arg(long, global = true, env = "SD_VERIFY_WRITES")]
+    pub verify_writes: bool,
+
+    /// Write batch keys in chunks of N, pausing --batch-delay between chunks.
+    ///
+    /// For hardware that drops writes or reconnects mid-batch (often seen
+    /// behind unpowered hubs or with older firmware): if set-keys, fill-keys,
+    /// clear-keys or apply leave some keys unchanged or the device resets,
+    /// try --batch-size 8. Default: no chunking.
+    #[arg(long, global = true, value_name = "N", env = "SD_BATCH_SIZE")]
+    pub batch_size: Option<usize>,
+
+    /// Pause between --batch-size chunks in milliseconds (default: 250)
+    #[arg(long, global = true, default_value = "250", env = "SD_BATCH_DELAY")]
+    pub batch_delay: u64,
+
+    /// Number keys from 0 or 1, in arguments and output (default: 0)
+    #[arg(
+        long,
+        global = true,
+        value_name = "0|1",
+        default_value = "0",
+        value_parser = clap::value_parser!(u8).range(0..=1),
+        env = "SD_KEY_BASE"
+    )]
+    pub key_base: u8,
+
+    /// Order keys are counted in, in arguments and output.
+    ///
+    /// row-major counts left to right, then down (the device's own order);
+    /// column-major counts down each column; serpentine runs left to right,
+    /// then back right to left on the next row. Config files, palettes and
+    /// set-keys file names always use the device's own order from 0.
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value = "row-major",
+        env = "SD_KEY_ORDER"
+    )]
+    pub key_order: KeyOrder,
+
+    /// Degrees the deck is mounted turned clockwise (0, 90, 180 or 270).
+    ///
+    /// Keys are remapped on the device, so key 0 is the key that is top-left
+    /// as mounted in arguments, output, configs and button events alike, and
+    /// images are turned to stay upright. 90 and 270 need a square key grid.
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        value_name = "DEGREES",
+        default_value = "0",
+        env = "SD_ROTATE_LAYOUT"
+    )]
+    pub rotate_layout: LayoutRotation,
+
+    /// With --rotate-layout, upload images as they are instead of turning them
+    #[arg(long, global = true, env = "SD_NO_ROTATE_IMAGES")]
+    pub no_rotate_images: bool,
+
+    /// `--key-base`/`--key-order` laid out for the target device.
+    #[arg(skip)]
+    pub key_numbering: KeyNumbering,
+
+    /// Print every command and its arguments as JSON, for building tool manifests
+    #[arg(long)]
+    pub list_commands: bool,
+
+    /// Write a HID capture of the device to FILE, to attach to a bug report
+    ///
+    /// Records every Elgato HID interface (vendor/product IDs, release,
+    /// usage, serial, path), the device's report descriptor and report sizes,
+    /// and a few feature and input report reads with their timing. Nothing
+    /// is written to the device, and nothing is redacted: the file includes
+    /// serial numbers. Pick a device with --serial if several are connected.
+    #[arg(long, hide = true, value_name = "FILE")]
+    pub dump_hid: Option<PathBuf>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
 
+impl Commands {
+    /// Returns true if the command takes or reports key numbers.
+    pub const fn uses_keys(&self) -> bool {
+        matches!(
+            self,
+            Self::Info(_)
+                | Self::SetKey(_)
+                | Self::SetKeys(_)
+                | Self::ClearKey(_)
+                | Self::FillKey(_)
+                | Self::OverlayKey(_)
+                | Self::FillKeys(_)
+                | Self::FillPalette(_)
+                | Self::FillFromImage(_)
+                | Self::Rainbow(_)
+                | Self::FillAll(_)
+                | Self::ClearKeys(_)
+                | Self::Watch(_)
+                | Self::Read(_)
+                | Self::State(_)
+                | Self::Serve(_)
+        )
+    }
+
+    /// The model the command was told to plan for, if any.
+    pub const fn assumed_model(&self) -> Option<DeviceModel> {
+        match self {
+            Self::SetKeys(args) => args.assume_model,
+            _ => None,
+        }
+    }
+
+    /// Translate key arguments from the user's numbering to native indices.
+    ///
+    /// Palette files are translated when they are read, by `fill-palette`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a key or range isn't on the device.
+    pub fn renumber_keys(&mut self, numbering: &KeyNumbering) -> Result<()> {
+        if numbering.is_native() {
+            return Ok(());
+        }
+        match self {
+            Self::SetKey(args) => args.key = numbering.to_native(args.key)?,
+            Self::ClearKey(args) => args.key = numbering.to_native(args.key)?,
+            Self::FillKey(args) => args.key = numbering.to_native(args.key)?,
+            Self::OverlayKey(args) => args.key = numbering.to_native(args.key)?,
+            Self::FillKeys(FillKeysArgs { range, keys, .. })
+            | Self::ClearKeys(ClearKeysArgs { range, keys, .. }) => {
+                // A user range needn't be contiguous natively, so it becomes a key list
+                *keys = match range.take() {
+                    Some(range) => numbering.range_to_native(&range)?,
+                    None => keys
+                        .iter()
+                        .map(|key| numbering.to_native(*key))
+                        .collect::<Result<_>>()?,
+                };
+            }
+            Self::SetKeys(args) if args.key_range.is_some() || args.start_key > 0 => {
+                let range = args.key_range.take().unwrap_or_else(|| "-".to_string());
+                let start_key = std::mem::take(&mut args.start_key);
+                args.native_keys = Some(
+                    numbering
+                        .range_to_native(&range)?
+                        .into_iter()
+                        .filter(|native| numbering.to_user(*native) >= start_key)
+                        .collect(),
+                );
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
 /// Output format selection.
 #[derive(Debug, Clone, Copy, Default, ValueEnum)]
 pub enum OutputFormat {
@@ -91,8 +407,17 @@ impl Cli {
     }
 
     /// Returns true if output should be compact JSON.
+    ///
+    /// `--pretty`/`--compact` win over the density implied by `--format`,
+    /// so `--format=json-compact` stays a shorthand for `--format=json --compact`.
     pub const fn use_compact_json(&self) -> bool {
-        matches!(self.format, OutputFormat::JsonCompact)
+        if self.pretty {
+            false
+        } else if self.compact {
+            true
+        } else {
+            matches!(self.format, OutputFormat::JsonCompact)
+        }
     }
 
     /// Returns true if retry is enabled.
@@ -104,8 +429,49 @@ impl Cli {
     ///
     /// When dry-run is enabled, commands should show what would happen
     /// without actually making changes to the device.
+    ///
+    /// `--no-dry-run` beats `--dry-run`/`SD_DRY_RUN`, which beat the
+    /// settings file default.
     pub const fn is_dry_run(&self) -> bool {
-        self.dry_run
+        !self.no_dry_run && (self.dry_run || self.default_dry_run)
+    }
+
+    /// Returns true if dry-run mode is enabled globally or by a command's
+    /// own `--dry-run` flag.
+    ///
+    /// Every command that writes to the device resolves dry-run through
+    /// this or [`Self::is_dry_run`], so the settings default applies to all
+    /// of them.
+    pub const fn is_dry_run_with(&self, command_dry_run: bool) -> bool {
+        command_dry_run || self.is_dry_run()
+    }
+
+    /// Model to assume when no device is connected.
+    ///
+    /// The settings file's `default_model`, falling back to the XL.
+    pub const fn offline_model(&self) -> DeviceModel {
+        self.default_model.unwrap_or(DeviceModel::Xl)
+    }
+
+    /// Returns true if "will be resized" warnings should be reported.
+    pub const fn warn_on_resize(&self) -> bool {
+        !self.no_resize_warning
+    }
+
+    /// Column width for human output: `--width`, then `COLUMNS`, else `None`
+    /// to use the detected terminal width.
+    ///
+    /// A `COLUMNS` narrower than [`MIN_OUTPUT_WIDTH`] or not a number is
+    /// ignored rather than rejected, since the shell sets it, not the user.
+    #[must_use]
+    pub fn output_width(&self) -> Option<usize> {
+        self.width.map(usize::from).or_else(|| {
+            std::env::var("COLUMNS")
+                .ok()
+                .and_then(|cols| cols.trim().parse::<u16>().ok())
+                .filter(|cols| *cols >= MIN_OUTPUT_WIDTH)
+                .map(usize::from)
+        })
     }
 
     /// Build connection options from CLI flags.
@@ -121,6 +487,7 @@ impl Cli {
                 retry_delay: Duration::ZERO,
                 backoff_factor: 1.0,
                 max_delay: Duration::ZERO,
+                jitter: 0,
             }
         } else {
             ConnectionOptions {
@@ -128,9 +495,25 @@ impl Cli {
                 retry_delay: Duration::from_millis(self.retry_delay),
                 backoff_factor: self.retry_backoff,
                 max_delay: Duration::from_millis(self.retry_max_delay),
+                jitter: self.retry_jitter,
             }
         }
     }
+
+    /// Pacing for batch key writes (`--batch-size`, `--batch-delay`).
+    pub fn write_pacer(&self) -> WritePacer {
+        WritePacer::new(self.batch_size, Duration::from_millis(self.batch_delay))
+    }
+
+    /// Returns true if `--key-base` or `--key-order` changes key numbers.
+    pub fn custom_key_numbering(&self) -> bool {
+        self.key_base != 0 || self.key_order != KeyOrder::RowMajor
+    }
+
+    /// The number the user would type for native key index `key`.
+    pub fn user_key(&self, key: u8) -> u8 {
+        self.key_numbering.to_user(key)
+    }
 }
 
 /// Re-export ConnectionOptions from device module for convenience.
@@ -157,21 +540,40 @@ pub enum Commands {
     #[command(visible_alias = "batch")]
     SetKeys(SetKeysArgs),
 
+    /// Spread one image across the whole deck
+    SetCanvas(SetCanvasArgs),
+
     /// Clear a key (set to black)
     ClearKey(ClearKeyArgs),
 
     /// Clear all keys
     ClearAll(ClearAllArgs),
 
+    /// Clear all keys, set the default brightness and forget tracked state
+    Reset(ResetArgs),
+
     /// Fill a key with a solid color
     FillKey(FillKeyArgs),
 
+    /// Draw a small image (e.g. a badge) over part of a key's current image
+    OverlayKey(OverlayKeyArgs),
+
     /// Fill all keys with a solid color
     FillAll(FillAllArgs),
 
     /// Fill multiple specific keys with a solid color
     FillKeys(FillKeysArgs),
 
+    /// Fill keys with colors from a palette file
+    FillPalette(FillPaletteArgs),
+
+    /// Fill each key with the color of its part of an image
+    FillFromImage(FillFromImageArgs),
+
+    /// Fill the deck with a rainbow gradient (a quick check that every key works)
+    #[command(hide = true)]
+    Rainbow(RainbowArgs),
+
     /// Clear multiple specific keys (set to black)
     ClearKeys(ClearKeysArgs),
 
@@ -182,6 +584,12 @@ pub enum Commands {
     /// Read current button states once
     Read(ReadArgs),
 
+    /// Play back button events recorded with `watch --record`
+    Replay(ReplayArgs),
+
+    /// Print seconds since the last button press (needs a running `sd watch`)
+    Idle(IdleArgs),
+
     // === Configuration ===
     /// Initialize configuration directory
     Init(InitArgs),
@@ -195,6 +603,18 @@ pub enum Commands {
     /// Apply a declarative configuration to the device
     Apply(ApplyArgs),
 
+    /// List the configs in the profile library
+    Profiles(ProfilesArgs),
+
+    /// Apply a config from the profile library by name
+    ApplyProfile(ApplyProfileArgs),
+
+    /// Rewrite a declarative config that uses outdated syntax
+    UpgradeConfig(UpgradeConfigArgs),
+
+    /// Switch a running `sd serve --stdio` to the profile for a context
+    Context(ContextArgs),
+
     // === Snapshots ===
     /// Save current device state as a named snapshot
     Save(SaveArgs),
@@ -208,25 +628,42 @@ pub enum Commands {
     /// Manage snapshots (show, delete)
     Snapshot(SnapshotCommand),
 
+    /// Show the device state tracked by this session (not read from hardware)
+    State(StateArgs),
+
+    /// Manage the key image cache
+    Cache(CacheCommand),
+
     // === Web Interface ===
     /// Start local web server for GUI control
     Serve(ServeArgs),
 
     // === Utilities ===
     /// Show version and build information
-    Version,
+    Version(VersionArgs),
 
     /// Generate shell completions
     Completions(CompletionsArgs),
+
+    /// Run a built-in demo of wiring key presses to device actions
+    Demo(DemoCommand),
+
+    /// Send a raw HID report to the device (protocol debugging)
+    #[command(hide = true)]
+    Raw(RawArgs),
 }
 
 // === Argument Structs ===
 
 #[derive(Parser, Debug)]
 pub struct ListArgs {
-    /// Show extended device information
+    /// Show extended device information (opens each device to read firmware)
     #[arg(long, short = 'l')]
     pub long: bool,
+
+    /// Give up on slow devices after this many milliseconds
+    #[arg(long, value_name = "MS", default_value = "2000")]
+    pub timeout: u64,
 }
 
 #[derive(Parser, Debug)]
@@ -234,6 +671,99 @@ pub struct InfoArgs {
     /// Show all available fields
     #[arg(long, short = 'a')]
     pub all: bool,
+
+    /// Keep redrawing the panel with live key state (Ctrl+C to exit)
+    #[arg(long, short = 'w')]
+    pub watch: bool,
+
+    /// Polling interval for --watch in milliseconds
+    #[arg(long, default_value = "250", requires = "watch")]
+    pub interval: u64,
+
+    /// Print only this field (repeat for several, e.g. --field serial --field model)
+    #[arg(long, value_enum, value_name = "FIELD", conflicts_with_all = ["all", "watch"])]
+    pub field: Vec<InfoField>,
+}
+
+/// Single fields `info --field` can print.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InfoField {
+    /// Device serial number
+    Serial,
+    /// Firmware version
+    Firmware,
+    /// Number of keys
+    Keys,
+    /// Model name as accepted by --model (e.g. xl, mk2)
+    Model,
+}
+
+impl InfoField {
+    /// Name used as the JSON key in robot mode.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Serial => "serial",
+            Self::Firmware => "firmware",
+            Self::Keys => "keys",
+            Self::Model => "model",
+        }
+    }
+}
+
+/// What a key shows once a `--for` display ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AfterTimeout {
+    /// Put back what the key showed before
+    #[default]
+    Restore,
+    /// Clear the key to black
+    Clear,
+}
+
+/// Parse a `serve --token`, which must not be blank: an empty token would
+/// let an empty bearer header through.
+///
+/// # Errors
+///
+/// Returns a message for clap if the token is empty or only whitespace.
+pub fn parse_token(s: &str) -> std::result::Result<String, String> {
+    match s.trim() {
+        "" => Err("token can't be empty".to_string()),
+        token => Ok(token.to_string()),
+    }
+}
+
+/// Parse a duration such as `5s`, `500ms`, `2m` or `1h`; a bare number is seconds.
+///
+/// # Errors
+///
+/// Returns a message for clap if the value isn't a positive duration.
+pub fn parse_duration(s: &str) -> std::result::Result<Duration, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{s}': expected e.g. 5s, 500ms or 2m"))?;
+    let seconds = match unit.trim() {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        other => {
+            return Err(format!(
+                "unknown duration unit '{other}': use ms, s, m or h"
+            ));
+        }
+    };
+    Duration::try_from_secs_f64(seconds)
+        .ok()
+        .filter(|d| !d.is_zero())
+        .ok_or_else(|| format!("invalid duration '{s}': must be greater than zero"))
 }
 
 #[derive(Parser, Debug)]
@@ -242,20 +772,48 @@ pub struct BrightnessArgs {
     pub level: u8,
 }
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 pub struct SetKeyArgs {
     /// Key index (0-based, left-to-right, top-to-bottom)
     pub key: u8,
 
-    /// Path to image file (PNG, JPEG, BMP, GIF)
+    /// Path to image file (PNG, JPEG, BMP, GIF, SVG), or icon:NAME to use a
+    /// named icon from the desktop icon theme (e.g. icon:firefox)
     pub image: PathBuf,
 
     /// Resize strategy if image doesn't match key size
     #[arg(long, default_value = "fit")]
     pub resize: ResizeStrategy,
+
+    /// Render at this pixel size instead of the device's key size (e.g. 72x72)
+    #[arg(long, value_name = "WxH")]
+    pub key_size: Option<KeySize>,
+
+    /// Sharpen after resizing, from 0 (off) to 100, to crisp up icons
+    /// downscaled for small keys
+    #[arg(long, value_name = "AMOUNT", default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub sharpen: u8,
+
+    /// With --dry-run, report the image's approximate color on the LCD
+    #[arg(long)]
+    pub simulate_display: bool,
+
+    /// Show it only for DURATION (e.g. 5s, 500ms, 2m), then put the key back
+    #[arg(
+        long = "for",
+        value_name = "DURATION",
+        visible_alias = "key-timeout",
+        value_parser = parse_duration
+    )]
+    pub for_duration: Option<Duration>,
+
+    /// After --for: restore what the key showed before (as tracked this
+    /// session; cleared if unknown), or clear it
+    #[arg(long, value_enum, default_value = "restore", requires = "for_duration")]
+    pub then: AfterTimeout,
 }
 
-use crate::image_ops::ResizeStrategy;
+use crate::image_ops::{ColorSpace, KeySize, OverlayPosition, PaletteMode, ResizeStrategy};
 
 /// Arguments for batch key setting from a directory.
 ///
@@ -273,6 +831,9 @@ use crate::image_ops::ResizeStrategy;
 ///
 /// # Preview changes first
 /// sd set-keys ~/layout/ --dry-run
+///
+/// # Resume an interrupted run where it stopped
+/// sd set-keys ~/layout/ --checkpoint layout.checkpoint
 /// ```
 #[derive(Parser, Debug)]
 #[allow(clippy::struct_excessive_bools)] // CLI flags naturally use multiple bools
@@ -294,8 +855,8 @@ pub struct SetKeysArgs {
     #[arg(long, default_value = "0")]
     pub start_key: u8,
 
-    /// Only process keys in this range (e.g., "0-7" for first row)
-    #[arg(long)]
+    /// Only process keys in this range (e.g., "0-7" for first row, "8-" or "-7")
+    #[arg(long, allow_hyphen_values = true)]
     pub key_range: Option<String>,
 
     /// Dry run - show what would happen without applying
@@ -310,9 +871,76 @@ pub struct SetKeysArgs {
     #[arg(long)]
     pub skip_unchanged: bool,
 
+    /// Record written keys in FILE and skip them when re-run with the same
+    /// FILE; removed once every key succeeds. A FILE recorded for another
+    /// directory, device or key layout is refused
+    #[arg(long, value_name = "FILE")]
+    pub checkpoint: Option<PathBuf>,
+
     /// Resize strategy for images
     #[arg(long, default_value = "fit")]
     pub resize: ResizeStrategy,
+
+    /// Sharpen after resizing, from 0 (off) to 100, to crisp up icons
+    /// downscaled for small keys
+    #[arg(long, value_name = "AMOUNT", default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub sharpen: u8,
+
+    /// When several files map to one key (e.g. key-1.png and key-01.png),
+    /// use the last in filename order with a warning instead of failing
+    #[arg(long)]
+    pub allow_duplicates: bool,
+
+    /// Plan a dry run for this model without opening a device (key count and
+    /// size come from the model spec)
+    #[arg(long, value_enum, value_name = "MODEL")]
+    pub assume_model: Option<DeviceModel>,
+
+    /// Native keys allowed by `--key-range` and `--start-key` when they were
+    /// given in custom key numbering (see [`Commands::renumber_keys`]).
+    #[arg(skip)]
+    pub native_keys: Option<Vec<u8>>,
+}
+
+impl SetKeysArgs {
+    /// Returns true if `--key-range` and `--start-key` allow native `key`.
+    pub fn in_scope(&self, key: u8) -> bool {
+        if let Some(keys) = &self.native_keys {
+            return keys.contains(&key);
+        }
+        self.key_range
+            .as_deref()
+            .is_none_or(|range| crate::key_numbering::key_in_range(key, range))
+            && key >= self.start_key
+    }
+}
+
+/// Arguments for treating the deck as one large canvas.
+///
+/// # Examples
+///
+/// ```bash
+/// # Spread a wallpaper across every key
+/// sd set-canvas ~/art/banner.png
+///
+/// # Override the model's default bezel gap
+/// sd set-canvas ~/art/banner.png --gap 16
+///
+/// # Naive grid slice (no gap compensation)
+/// sd set-canvas ~/art/banner.png --gap 0
+///
+/// # Show tile sizes and per-key crop boxes without touching the device
+/// sd --dry-run --json set-canvas ~/art/banner.png
+/// ```
+#[derive(Parser, Debug)]
+pub struct SetCanvasArgs {
+    /// Path to the image to slice across the deck
+    pub image: PathBuf,
+
+    /// Pixels of the image hidden between adjacent keys.
+    /// Defaults to the device model's bezel width; use 0 for a naive grid slice.
+    #[arg(long, value_name = "PX")]
+    pub gap: Option<u32>,
 }
 
 #[derive(Parser, Debug)]
@@ -324,21 +952,141 @@ pub struct ClearKeyArgs {
 #[derive(Parser, Debug)]
 pub struct ClearAllArgs {}
 
+/// Brightness `sd reset` sets unless `--brightness` is given.
+pub const DEFAULT_RESET_BRIGHTNESS: u8 = 70;
+
+/// Arguments for the reset command.
+///
+/// # Examples
+///
+/// ```bash
+/// # Start from scratch: black keys, 70% brightness, nothing tracked
+/// sd reset --force
+///
+/// # Start from scratch at full brightness
+/// sd reset --force --brightness 100
+/// ```
+#[derive(Parser, Debug)]
+pub struct ResetArgs {
+    /// Brightness level to set (0-100)
+    #[arg(long, short = 'b', value_name = "LEVEL", default_value_t = DEFAULT_RESET_BRIGHTNESS)]
+    pub brightness: u8,
+
+    /// Reset without confirmation prompt
+    #[arg(long)]
+    pub force: bool,
+}
+
 #[derive(Parser, Debug)]
 pub struct FillKeyArgs {
     /// Key index
     pub key: u8,
 
-    /// Color in hex format (e.g., "ff0000" for red, "#00ff00" for green)
+    /// Color in hex format (e.g., "ff0000" for red, "#00ff00" for green).
+    /// With --over, 8 digits add alpha (e.g., "ff000080" for half-transparent red)
     pub color: String,
+
+    /// With --dry-run, report the approximate color as shown on the LCD
+    #[arg(long)]
+    pub simulate_display: bool,
+
+    /// How COLOR is interpreted: srgb (sent as given), linear, or device
+    /// (corrected for the model's panel to look closer to the sRGB value)
+    #[arg(long, value_enum, default_value_t = ColorSpace::Srgb)]
+    pub color_space: ColorSpace,
+
+    /// Blend the color over the key's current image instead of replacing it.
+    ///
+    /// The current image is the one this session tracked for the key (see
+    /// `sd state`); the tint itself isn't tracked, so restoring the key
+    /// removes it.
+    #[arg(long)]
+    pub over: bool,
+
+    /// Show it only for DURATION (e.g. 5s, 500ms, 2m), then put the key back
+    #[arg(
+        long = "for",
+        value_name = "DURATION",
+        visible_alias = "key-timeout",
+        value_parser = parse_duration
+    )]
+    pub for_duration: Option<Duration>,
+
+    /// After --for: restore what the key showed before (as tracked this
+    /// session; cleared if unknown), or clear it
+    #[arg(long, value_enum, default_value = "restore", requires = "for_duration")]
+    pub then: AfterTimeout,
+}
+
+/// Arguments for overlay-key command.
+///
+/// # Examples
+///
+/// ```bash
+/// # Notification dot in the bottom-right corner, 30% of the key's size
+/// sd overlay-key 0 badge.png
+///
+/// # Larger badge at the top
+/// sd overlay-key 0 badge.png --position top --scale 0.5
+/// ```
+#[derive(Parser, Debug)]
+pub struct OverlayKeyArgs {
+    /// Key index
+    pub key: u8,
+
+    /// Image to draw over the key (transparency is kept)
+    pub image: PathBuf,
+
+    /// Where on the key to draw the overlay
+    #[arg(long, value_enum, default_value_t = OverlayPosition::BottomRight)]
+    pub position: OverlayPosition,
+
+    /// Overlay size as a fraction of the key (greater than 0, at most 1)
+    #[arg(long, default_value_t = 0.3)]
+    pub scale: f32,
 }
 
+/// Arguments for fill-all command.
+///
+/// # Examples
+///
+/// ```bash
+/// # Fill every key red
+/// sd fill-all ff0000
+///
+/// # A different random color on every key; repeat it later with --seed
+/// sd fill-all --randomize
+/// sd fill-all --randomize --seed 1234
+/// ```
 #[derive(Parser, Debug)]
 pub struct FillAllArgs {
     /// Color in hex format
-    pub color: String,
+    #[arg(required_unless_present = "randomize")]
+    pub color: Option<String>,
+
+    /// Fill each key with a different random color instead
+    #[arg(long, conflicts_with = "color")]
+    pub randomize: bool,
+
+    /// Seed for --randomize, to reproduce an earlier fill
+    #[arg(long, requires = "randomize")]
+    pub seed: Option<u64>,
+
+    /// How COLOR is interpreted: srgb (sent as given), linear, or device
+    /// (corrected for the model's panel to look closer to the sRGB value)
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ColorSpace::Srgb,
+        conflicts_with = "randomize"
+    )]
+    pub color_space: ColorSpace,
 }
 
+/// Arguments for the rainbow command.
+#[derive(Parser, Debug)]
+pub struct RainbowArgs {}
+
 /// Arguments for batch fill-keys command.
 ///
 /// Fill multiple keys with a solid color in one operation.
@@ -364,8 +1112,8 @@ pub struct FillKeysArgs {
     #[arg(long, conflicts_with_all = ["range", "keys"])]
     pub all: bool,
 
-    /// Range of keys to fill (e.g., "0-7" for first row)
-    #[arg(long, short = 'r')]
+    /// Range of keys to fill (e.g., "0-7" for first row, "8-" to the last key, "-7")
+    #[arg(long, short = 'r', allow_hyphen_values = true)]
     pub range: Option<String>,
 
     /// Specific key indices to fill (space-separated)
@@ -375,21 +1123,88 @@ pub struct FillKeysArgs {
     /// Continue filling other keys if one fails
     #[arg(long, short = 'c')]
     pub continue_on_error: bool,
+
+    /// How COLOR is interpreted: srgb (sent as given), linear, or device
+    /// (corrected for the model's panel to look closer to the sRGB value)
+    #[arg(long, value_enum, default_value_t = ColorSpace::Srgb)]
+    pub color_space: ColorSpace,
 }
 
-/// Arguments for batch clear-keys command.
+/// Arguments for fill-palette command.
 ///
-/// Clear multiple keys (set to black) in one operation.
+/// Each palette line is `index: color` or a bare color; bare colors take the
+/// key after the previous entry, so a plain list fills keys 0..N in order.
+/// Blank lines are ignored and `# ` starts a comment.
 ///
 /// # Examples
 ///
 /// ```bash
-/// # Clear all keys
-/// sd clear-keys --all
+/// # Fill keys from a palette
+/// sd fill-palette warm.txt
 ///
-/// # Clear first row (keys 0-7)
+/// # Preview the key-to-color mapping
+/// sd fill-palette warm.txt --dry-run
+/// ```
+#[derive(Parser, Debug)]
+pub struct FillPaletteArgs {
+    /// Palette file (one color per line, optionally prefixed with "index:")
+    #[arg(value_name = "PALETTE")]
+    pub palette: PathBuf,
+
+    /// Continue filling other keys if one fails
+    #[arg(long, short = 'c')]
+    pub continue_on_error: bool,
+}
+
+/// Arguments for fill-from-image command.
+///
+/// The image is divided into the deck's grid of rows and columns, and each
+/// key is filled with one color summarizing its cell: an abstract, color-only
+/// version of `set-canvas`.
+///
+/// # Examples
+///
+/// ```bash
+/// # Fill keys with the average color of each region
+/// sd fill-from-image sunset.png
+///
+/// # Use each region's most common color instead
+/// sd fill-from-image sunset.png --mode dominant
+///
+/// # Preview the computed colors
+/// sd fill-from-image sunset.png --dry-run
+/// ```
+#[derive(Parser, Debug)]
+pub struct FillFromImageArgs {
+    /// Source image
+    #[arg(value_name = "SOURCE")]
+    pub image: PathBuf,
+
+    /// How each region is reduced to one color
+    #[arg(long, value_enum, default_value_t = PaletteMode::Average)]
+    pub mode: PaletteMode,
+
+    /// Continue filling other keys if one fails
+    #[arg(long, short = 'c')]
+    pub continue_on_error: bool,
+}
+
+/// Arguments for batch clear-keys command.
+///
+/// Clear multiple keys (set to black) in one operation.
+///
+/// # Examples
+///
+/// ```bash
+/// # Clear all keys
+/// sd clear-keys --all
+///
+/// # Clear first row (keys 0-7)
 /// sd clear-keys --range 0-7
 ///
+/// # Clear from key 8 to the last key
+/// sd clear-keys --range 8-
+///
 /// # Clear specific keys
 /// sd clear-keys --keys 0 5 10 15
 /// ```
@@ -399,8 +1214,8 @@ pub struct ClearKeysArgs {
     #[arg(long, conflicts_with_all = ["range", "keys"])]
     pub all: bool,
 
-    /// Range of keys to clear (e.g., "0-7" for first row)
-    #[arg(long, short = 'r')]
+    /// Range of keys to clear (e.g., "0-7" for first row, "8-" to the last key, "-7")
+    #[arg(long, short = 'r', allow_hyphen_values = true)]
     pub range: Option<String>,
 
     /// Specific key indices to clear (space-separated)
@@ -431,6 +1246,15 @@ pub struct ClearKeysArgs {
 ///
 /// # Limit reconnection attempts
 /// sd watch --reconnect --max-reconnect-attempts 5
+///
+/// # Several watchers on one hub: spread reconnects by up to 25%
+/// sd watch --reconnect --reconnect-jitter 25
+///
+/// # Flash keys orange for 200ms when pressed
+/// sd watch --feedback --feedback-color ff8800 --feedback-ms 200
+///
+/// # Record a long session, keeping one press in ten
+/// sd watch --record session.jsonl --sample-rate 10
 /// ```
 #[derive(Parser, Debug)]
 pub struct WatchArgs {
@@ -438,7 +1262,7 @@ pub struct WatchArgs {
     #[arg(long)]
     pub once: bool,
 
-    /// Timeout in seconds (0 = no timeout)
+    /// Timeout in seconds (0 = no timeout); with --once, exits 124 if nothing is pressed
     #[arg(long, short = 't', default_value = "0")]
     pub timeout: u64,
 
@@ -453,10 +1277,131 @@ pub struct WatchArgs {
     /// Maximum number of reconnection attempts (0 = unlimited)
     #[arg(long, default_value = "0")]
     pub max_reconnect_attempts: u32,
+
+    /// Randomize each reconnect delay by up to PERCENT either way (default: 0)
+    ///
+    /// For several watchers on a shared hub: after the hub resets, jitter
+    /// spreads their reconnects out instead of all hitting it at once.
+    #[arg(
+        long,
+        default_value = "0",
+        value_name = "PERCENT",
+        value_parser = clap::value_parser!(u8).range(0..=100)
+    )]
+    pub reconnect_jitter: u8,
+
+    /// Flash each key when pressed, then restore it
+    ///
+    /// Keys are restored to what sd set in this session. Keys it hasn't
+    /// touched aren't flashed, with a warning, since what they show can't
+    /// be restored.
+    #[arg(long)]
+    pub feedback: bool,
+
+    /// Flash color for --feedback (hex, e.g. ff8800)
+    #[arg(
+        long,
+        value_name = "COLOR",
+        default_value = "ffffff",
+        requires = "feedback"
+    )]
+    pub feedback_color: String,
+
+    /// How long the --feedback flash lasts in milliseconds
+    #[arg(long, value_name = "MS", default_value = "150", requires = "feedback")]
+    pub feedback_ms: u64,
+
+    /// Also write every press and release to FILE (JSON Lines) for `sd replay`
+    #[arg(long, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+
+    /// Record only every Nth press (with its release) to keep long captures
+    /// small (default: 1, every press)
+    ///
+    /// Recorded timestamps stay real, so replaying a sampled recording
+    /// shows long pauses where presses were dropped.
+    #[arg(
+        long,
+        value_name = "N",
+        default_value = "1",
+        value_parser = clap::value_parser!(u32).range(1..),
+        requires = "record"
+    )]
+    pub sample_rate: u32,
+}
+
+/// Arguments for the replay command.
+///
+/// Feeds a recording through a simulated device's input queue and reports
+/// the events exactly as `sd watch` would, at the recorded timing. No
+/// hardware is touched.
+///
+/// # Examples
+///
+/// ```bash
+/// # Capture a session, then play it back
+/// sd watch --record session.jsonl
+/// sd replay session.jsonl
+///
+/// # Fast-forward, as JSON for a test harness
+/// sd replay session.jsonl --speed 10 --robot
+/// ```
+#[derive(Parser, Debug)]
+pub struct ReplayArgs {
+    /// Recording to play (JSON Lines from `watch --record` or `watch --robot`)
+    #[arg(value_name = "FILE")]
+    pub recording: PathBuf,
+
+    /// Playback speed multiplier (2 plays twice as fast)
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.0)]
+    pub speed: f64,
+
+    /// Model of the simulated device (default: xl)
+    #[arg(long, value_enum, value_name = "MODEL")]
+    pub model: Option<DeviceModel>,
 }
 
 #[derive(Parser, Debug)]
-pub struct ReadArgs {}
+pub struct ReadArgs {
+    /// Keep polling until two consecutive readings agree, for up to this many
+    /// milliseconds (the first read right after connecting can be stale)
+    #[arg(long, value_name = "MS")]
+    pub settle: Option<u64>,
+}
+
+/// Arguments for the state command.
+///
+/// State is tracked per process: a one-shot `sd state` shows what this run
+/// changed (nothing), so the command is mostly useful through long-running
+/// sessions such as `sd serve --stdio` (methods `state` and `reset-state`).
+#[derive(Parser, Debug)]
+pub struct StateArgs {
+    #[command(subcommand)]
+    pub command: Option<StateSubcommand>,
+}
+
+/// State subcommands.
+#[derive(Subcommand, Debug)]
+pub enum StateSubcommand {
+    /// Forget tracked changes (the device itself is not changed)
+    Reset,
+}
+
+/// Arguments for the idle command.
+///
+/// Idle time comes from a running `sd watch`, which records each press.
+///
+/// # Examples
+///
+/// ```bash
+/// # In one terminal
+/// sd watch
+///
+/// # Elsewhere: lock the screen after 5 minutes away
+/// [ "$(sd idle)" -ge 300 ] && loginctl lock-session
+/// ```
+#[derive(Parser, Debug)]
+pub struct IdleArgs {}
 
 #[derive(Parser, Debug)]
 pub struct InitArgs {
@@ -470,6 +1415,41 @@ pub struct ConfigArgs {
     /// Show configuration file path
     #[arg(long)]
     pub path: bool,
+
+    #[command(subcommand)]
+    pub command: Option<ConfigSubcommand>,
+}
+
+/// Config subcommands.
+#[derive(Subcommand, Debug)]
+pub enum ConfigSubcommand {
+    /// Show which action each key gets from a declarative config
+    Keys(ConfigKeysArgs),
+}
+
+/// Arguments for `config keys`.
+///
+/// Expands every selector in a config against the device layout and shows
+/// the winning action per key (most specific selector wins, as in `apply`).
+///
+/// # Examples
+///
+/// ```bash
+/// # Resolve against the connected device
+/// sd config keys ~/.config/sd/profiles/work.yaml
+///
+/// # Resolve for a model that isn't plugged in
+/// sd config keys work.yaml --model mini
+/// ```
+#[derive(Parser, Debug)]
+pub struct ConfigKeysArgs {
+    /// Path to configuration file (YAML or TOML)
+    #[arg(value_name = "CONFIG")]
+    pub config: PathBuf,
+
+    /// Resolve for this model instead of the connected device
+    #[arg(long, value_enum, value_name = "MODEL")]
+    pub model: Option<DeviceModel>,
 }
 
 /// Arguments for the validate command.
@@ -485,18 +1465,38 @@ pub struct ConfigArgs {
 /// # Strict mode (warnings become errors)
 /// sd validate config.toml --strict
 ///
+/// # Catch misspelled fields such as `brightnes: 80`
+/// sd validate config.yaml --strict-fields
+///
 /// # JSON output for scripts
 /// sd validate config.yaml --robot
+///
+/// # Validate every .yaml/.yml/.toml file in a directory (e.g. in CI)
+/// sd validate profiles/ --all --strict
 /// ```
 #[derive(Parser, Debug)]
 pub struct ValidateArgs {
-    /// Path to configuration file to validate
+    /// Path to configuration file to validate, or a directory of them
     #[arg(value_name = "CONFIG")]
     pub config: PathBuf,
 
+    /// Validate every .yaml, .yml and .toml file in the CONFIG directory
+    /// (implied when CONFIG is a directory)
+    #[arg(long)]
+    pub all: bool,
+
     /// Treat warnings as errors
     #[arg(long)]
     pub strict: bool,
+
+    /// Report every key no selector covers (uses an XL layout if no device is connected)
+    #[arg(long, visible_alias = "count-keys")]
+    pub require_complete: bool,
+
+    /// Report fields the config format doesn't define (e.g. a `brightnes`
+    /// typo) as errors instead of ignoring them
+    #[arg(long)]
+    pub strict_fields: bool,
 }
 
 /// Arguments for the apply command.
@@ -520,6 +1520,22 @@ pub struct ValidateArgs {
 ///
 /// # Show diff of changes
 /// sd apply config.yaml --diff
+///
+/// # Notify when done
+/// sd apply config.yaml --after 'notify-send sd "$SD_APPLY_KEYS_SET keys set"'
+///
+/// # Render the layout to PNGs without a device
+/// sd apply config.yaml --export-images out/ --no-upload --model mk2
+///
+/// # Make the device match the config exactly (clear uncovered keys)
+/// sd apply config.yaml --replace
+///
+/// # Check a layout in CI without hardware
+/// sd apply config.yaml --mock xl --dump-state state.json
+///
+/// # Keep a way back: restore the backup if the result isn't right
+/// sd apply config.yaml --snapshot-on-apply before-redesign
+/// sd restore before-redesign
 /// ```
 #[derive(Parser, Debug)]
 pub struct ApplyArgs {
@@ -542,6 +1558,192 @@ pub struct ApplyArgs {
     /// Show diff of what would change (implies --dry-run unless --apply-diff)
     #[arg(long)]
     pub diff: bool,
+
+    /// Also write each key's rendered image to DIR as key-{index}.png
+    #[arg(long, value_name = "DIR", visible_alias = "output-images-dir")]
+    pub export_images: Option<PathBuf>,
+
+    /// Only export images; don't touch the device (requires --export-images)
+    #[arg(long, requires = "export_images")]
+    pub no_upload: bool,
+
+    /// Device model to render for when no device is connected (default: xl)
+    #[arg(long, value_enum, value_name = "MODEL")]
+    pub model: Option<DeviceModel>,
+
+    /// Clear every key the config doesn't cover, so the device matches it exactly
+    ///
+    /// By default apply merges: keys no selector matches keep whatever they
+    /// showed before. With --replace those keys are cleared to black. A
+    /// `default` selector covers every key, so nothing extra is cleared.
+    #[arg(long)]
+    pub replace: bool,
+
+    /// Apply to a simulated device of this model instead of real hardware
+    #[arg(long, value_enum, value_name = "MODEL", conflicts_with = "no_upload")]
+    pub mock: Option<DeviceModel>,
+
+    /// Write the mock device's resulting key state to FILE as JSON (requires --mock)
+    #[arg(long, value_name = "FILE", requires = "mock")]
+    pub dump_state: Option<PathBuf>,
+
+    /// Save the current state as snapshot NAME before applying, so `sd restore
+    /// NAME` undoes the apply (default name: pre-apply-<UTC timestamp>)
+    #[arg(
+        long,
+        value_name = "NAME",
+        num_args = 0..=1,
+        conflicts_with_all = ["mock", "no_upload"]
+    )]
+    pub snapshot_on_apply: Option<Option<String>>,
+
+    /// Refuse configs with fields the format doesn't define (e.g. a
+    /// `brightnes` typo) instead of ignoring them
+    #[arg(long)]
+    pub strict_fields: bool,
+
+    /// Shell command to run once every key is applied, overriding the
+    /// config's `on_apply`
+    ///
+    /// Skipped when a key fails, the apply is interrupted or uses --mock.
+    /// The result is in SD_APPLY_KEYS_SET, SD_APPLY_TOTAL, SD_APPLY_SERIAL and
+    /// friends, or substitute {keys_set}, {total}, {name}, ... (shell-quoted)
+    #[arg(long, value_name = "COMMAND")]
+    pub after: Option<String>,
+
+    /// Fail the apply if the hook fails (by default it only warns)
+    #[arg(long)]
+    pub strict_hooks: bool,
+
+    /// Report each key as soon as it's applied (in robot mode, one JSON line
+    /// per key), before the final summary
+    #[arg(long)]
+    pub stream: bool,
+
+    /// Decode and resize each distinct image once, reusing it for every key
+    /// that shows it (faster for configs repeating the same icon)
+    #[arg(long)]
+    pub dedupe_images: bool,
+}
+
+/// Arguments for the profiles command.
+///
+/// The profile library is a directory of declarative configs
+/// (`~/.config/sd/profiles/` by default), applied by file name with
+/// `sd apply-profile`.
+///
+/// # Examples
+///
+/// ```bash
+/// # List profiles with their names and key counts
+/// sd profiles
+///
+/// # Use another library
+/// sd profiles --profile-dir ~/decks
+/// ```
+#[derive(Parser, Debug)]
+pub struct ProfilesArgs {
+    /// Profile library directory (default: ~/.config/sd/profiles)
+    #[arg(long, value_name = "DIR", env = "SD_PROFILE_DIR")]
+    pub profile_dir: Option<PathBuf>,
+}
+
+/// Arguments for the apply-profile command.
+///
+/// # Examples
+///
+/// ```bash
+/// # Apply ~/.config/sd/profiles/work.yaml
+/// sd apply-profile work
+///
+/// # Preview it first
+/// sd apply-profile work --dry-run
+/// ```
+#[derive(Parser, Debug)]
+#[allow(clippy::struct_excessive_bools)] // CLI flags naturally use multiple bools
+pub struct ApplyProfileArgs {
+    /// Profile file name, with or without its .yaml/.toml extension
+    #[arg(value_name = "NAME")]
+    pub name: String,
+
+    /// Profile library directory (default: ~/.config/sd/profiles)
+    #[arg(long, value_name = "DIR", env = "SD_PROFILE_DIR")]
+    pub profile_dir: Option<PathBuf>,
+
+    /// Preview changes without applying them
+    #[arg(long, short = 'n')]
+    pub dry_run: bool,
+
+    /// Apply even if validation produces warnings
+    #[arg(long)]
+    pub force: bool,
+
+    /// Skip brightness setting from the profile
+    #[arg(long)]
+    pub no_brightness: bool,
+
+    /// Show diff of what would change
+    #[arg(long)]
+    pub diff: bool,
+
+    /// Clear every key the profile doesn't cover
+    #[arg(long)]
+    pub replace: bool,
+}
+
+/// Arguments for the upgrade-config command.
+///
+/// Rewrites loose selectors (`row0`, `0..7`, `*`) and renamed fields (`path`,
+/// `fill`, `serial`) to the current schema, listing each change. The file is
+/// re-serialized, so comments and fields sd doesn't know are not kept.
+///
+/// # Examples
+///
+/// ```bash
+/// # Preview the rewritten file
+/// sd upgrade-config old.yaml --dry-run
+///
+/// # Rewrite in place
+/// sd upgrade-config old.yaml
+///
+/// # Write the upgraded config elsewhere (the extension picks the format)
+/// sd upgrade-config old.yaml --output new.toml
+/// ```
+#[derive(Parser, Debug)]
+pub struct UpgradeConfigArgs {
+    /// Path to configuration file to upgrade
+    #[arg(value_name = "CONFIG")]
+    pub config: PathBuf,
+
+    /// Print the rewritten config instead of saving it
+    #[arg(long, short = 'n')]
+    pub dry_run: bool,
+
+    /// Write the upgraded config to FILE instead of over CONFIG
+    #[arg(long, short = 'o', value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+/// Arguments for the context command.
+///
+/// Context names map to profile configs in the settings file's `contexts`
+/// section. A running `sd serve --stdio` applies the profile when the context
+/// changes; focus tracking is left to whatever calls `sd context`.
+///
+/// # Examples
+///
+/// ```bash
+/// # ~/.config/sd/config.yaml
+/// #   contexts:
+/// #     firefox: profiles/browser.yaml
+///
+/// # From a window-focus hook
+/// sd context firefox
+/// ```
+#[derive(Parser, Debug)]
+pub struct ContextArgs {
+    /// Context name, as listed under `contexts` in the settings file
+    pub name: String,
 }
 
 /// Arguments for the save command.
@@ -616,12 +1818,31 @@ pub struct RestoreArgs {
 ///
 /// # Show detailed info
 /// sd snapshots --long
+///
+/// # Third page of 20, most recently updated first
+/// sd snapshots --limit 20 --offset 40
 /// ```
 #[derive(Parser, Debug)]
 pub struct SnapshotsArgs {
     /// Show detailed snapshot information
     #[arg(long, short = 'l')]
     pub long: bool,
+
+    /// Show at most N snapshots
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub limit: Option<u32>,
+
+    /// Skip the first N snapshots
+    #[arg(long, value_name = "N")]
+    pub offset: Option<u32>,
+}
+
+impl SnapshotsArgs {
+    /// Returns true if only a page of snapshots was asked for.
+    #[must_use]
+    pub const fn is_paginated(&self) -> bool {
+        self.limit.is_some() || self.offset.is_some()
+    }
 }
 
 /// Snapshot management subcommands.
@@ -637,6 +1858,10 @@ pub struct SnapshotsArgs {
 ///
 /// # Force delete without confirmation
 /// sd snapshot delete old-layout --force
+///
+/// # Share a snapshot with its images, then load it elsewhere
+/// sd snapshot export work-mode work.sdsnap --compression zstd
+/// sd snapshot import work.sdsnap
 /// ```
 #[derive(Parser, Debug)]
 pub struct SnapshotCommand {
@@ -652,6 +1877,18 @@ pub enum SnapshotSubcommand {
 
     /// Delete a snapshot
     Delete(SnapshotDeleteArgs),
+
+    /// Change a snapshot's description, brightness or name (keys are kept)
+    Edit(SnapshotEditArgs),
+
+    /// Compare two snapshots key by key
+    Compare(SnapshotCompareArgs),
+
+    /// Write a snapshot and its cached images to an archive file
+    Export(SnapshotExportArgs),
+
+    /// Load a snapshot from an archive written by `snapshot export`
+    Import(SnapshotImportArgs),
 }
 
 /// Arguments for snapshot show command.
@@ -660,6 +1897,10 @@ pub struct SnapshotShowArgs {
     /// Name of the snapshot to show
     #[arg(value_name = "NAME")]
     pub name: String,
+
+    /// Write each image key's cached image to DIR as key-{index}.png
+    #[arg(long, value_name = "DIR")]
+    pub images: Option<PathBuf>,
 }
 
 /// Arguments for snapshot delete command.
@@ -674,6 +1915,149 @@ pub struct SnapshotDeleteArgs {
     pub force: bool,
 }
 
+/// Arguments for snapshot edit command.
+///
+/// # Examples
+///
+/// ```bash
+/// # Fix a typo in the description
+/// sd snapshot edit work-mode --description "Work layout"
+///
+/// # Dim it and give it a clearer name
+/// sd snapshot edit work-mode --brightness 40 --rename work-evening
+/// ```
+#[derive(Parser, Debug)]
+#[command(group(
+    clap::ArgGroup::new("changes")
+        .required(true)
+        .multiple(true)
+        .args(["description", "brightness", "rename"])
+))]
+pub struct SnapshotEditArgs {
+    /// Name of the snapshot to edit
+    #[arg(value_name = "NAME")]
+    pub name: String,
+
+    /// New description ("" clears it)
+    #[arg(long, short = 'd')]
+    pub description: Option<String>,
+
+    /// New brightness level (0-100) restored with the snapshot
+    #[arg(long)]
+    pub brightness: Option<u8>,
+
+    /// New name (alphanumeric, hyphens, underscores)
+    #[arg(long, value_name = "NEW")]
+    pub rename: Option<String>,
+}
+
+/// Arguments for snapshot compare command.
+#[derive(Parser, Debug)]
+pub struct SnapshotCompareArgs {
+    /// Name of the first snapshot
+    #[arg(value_name = "A")]
+    pub a: String,
+
+    /// Name of the second snapshot
+    #[arg(value_name = "B")]
+    pub b: String,
+}
+
+/// Arguments for snapshot export command.
+#[derive(Parser, Debug)]
+pub struct SnapshotExportArgs {
+    /// Name of the snapshot to export
+    #[arg(value_name = "NAME")]
+    pub name: String,
+
+    /// Archive file to write
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+
+    /// Archive compression: deflate opens with any zip tool, zstd is smaller
+    /// for image-heavy snapshots, none stores images as-is
+    #[arg(long, value_enum, default_value = "deflate")]
+    pub compression: ArchiveCompression,
+
+    /// Re-encode cached images as PNG instead of copying them unchanged
+    #[arg(long)]
+    pub reencode: bool,
+}
+
+/// Arguments for snapshot import command.
+#[derive(Parser, Debug)]
+pub struct SnapshotImportArgs {
+    /// Archive file written by `sd snapshot export`
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+
+    /// Save under this name instead of the archived one
+    #[arg(long, value_name = "NAME")]
+    pub name: Option<String>,
+
+    /// Overwrite an existing snapshot with the same name
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// Key image cache subcommands.
+///
+/// # Examples
+///
+/// ```bash
+/// # Remove all cached key images
+/// sd cache clean
+/// ```
+#[derive(Parser, Debug)]
+pub struct CacheCommand {
+    #[command(subcommand)]
+    pub command: CacheSubcommand,
+}
+
+/// Cache subcommands.
+#[derive(Subcommand, Debug)]
+pub enum CacheSubcommand {
+    /// Remove all cached key images
+    Clean,
+}
+
+/// Built-in demos.
+///
+/// Demos are small examples of composing sd's building blocks, meant to be
+/// read and copied rather than relied on; their behavior may change.
+///
+/// # Examples
+///
+/// ```bash
+/// # Press the first key to dim, the second to brighten; Ctrl+C restores
+/// sd demo dimmer
+/// ```
+#[derive(Parser, Debug)]
+pub struct DemoCommand {
+    #[command(subcommand)]
+    pub command: DemoSubcommand,
+}
+
+/// Demo subcommands.
+#[derive(Subcommand, Debug)]
+pub enum DemoSubcommand {
+    /// The first key dims and the second brightens the display until Ctrl+C
+    Dimmer(DimmerArgs),
+}
+
+/// Arguments for `demo dimmer`.
+#[derive(Parser, Debug)]
+pub struct DimmerArgs {
+    /// Brightness change per press, in percent
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        default_value_t = 10,
+        value_parser = clap::value_parser!(u8).range(1..=100)
+    )]
+    pub step: u8,
+}
+
 #[derive(Parser, Debug)]
 pub struct ServeArgs {
     /// Port to listen on
@@ -687,6 +2071,87 @@ pub struct ServeArgs {
     /// Open browser automatically
     #[arg(long)]
     pub open: bool,
+
+    /// Speak JSON-RPC 2.0 on stdin/stdout, one message per line, instead of HTTP
+    #[arg(long, conflicts_with = "open")]
+    pub stdio: bool,
+
+    /// Serve /healthz and /metrics over HTTP on --bind and --port
+    ///
+    /// With --stdio, the counters cover the JSON-RPC commands served.
+    #[arg(long)]
+    pub metrics: bool,
+
+    /// Require this secret from clients
+    ///
+    /// HTTP requests must send `Authorization: Bearer <TOKEN>` (WebSocket
+    /// upgrades may use `?access_token=<TOKEN>` instead); JSON-RPC clients
+    /// must call `auth` with it before anything else. Recommended whenever
+    /// --bind isn't a loopback address.
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        env = "SD_SERVE_TOKEN",
+        hide_env_values = true,
+        value_parser = parse_token
+    )]
+    pub token: Option<String>,
+}
+
+/// Arguments for the version command.
+///
+/// # Examples
+///
+/// ```bash
+/// # Show build information
+/// sd version
+///
+/// # Also ask the release feed whether a newer version exists
+/// sd version --check
+/// ```
+#[derive(Parser, Debug)]
+pub struct VersionArgs {
+    /// Check the release feed for a newer version (short timeout, never fails)
+    #[arg(long)]
+    pub check: bool,
+
+    /// Never touch the network; --check then only reports the current version
+    #[arg(long, env = "SD_OFFLINE")]
+    pub offline: bool,
+}
+
+/// Arguments for the hidden raw command.
+///
+/// Sends bytes straight to the device with no validation. Only for bringing
+/// up unsupported models or diagnosing protocol issues; a wrong report can
+/// leave the device in a bad state until it is replugged.
+///
+/// # Examples
+///
+/// ```bash
+/// # Set brightness to 100% on an MK.2/XL (feature report 0x03, command 0x08)
+/// sd raw --report "03 08 64" --i-know-what-im-doing
+///
+/// # Same, reading 32 bytes of feature report 0x03 back afterwards
+/// sd --robot raw --report "03 08 64" --read 32 --i-know-what-im-doing
+/// ```
+#[derive(Parser, Debug)]
+pub struct RawArgs {
+    /// Report bytes in hex, report ID first (e.g. "03 08 64")
+    #[arg(long, value_name = "HEX")]
+    pub report: HexBytes,
+
+    /// Report type to send
+    #[arg(long, value_enum, default_value = "feature")]
+    pub kind: RawReportKind,
+
+    /// Afterwards, read this many bytes of the feature report with the same ID
+    #[arg(long, value_name = "LEN")]
+    pub read: Option<usize>,
+
+    /// Required confirmation that you accept the risk
+    #[arg(long = "i-know-what-im-doing")]
+    pub confirm: bool,
 }
 
 #[derive(Parser, Debug)]
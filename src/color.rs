@@ -0,0 +1,73 @@
+//! Hex color parsing shared by every command and config field that takes a color.
+//!
+//! Accepted forms, each with or without a leading `#`:
+//!
+//! - `f00`: shorthand, each digit doubled (`ff0000`)
+//! - `ff0000`: red, green and blue
+//! - `ff000080`: the same plus alpha, from `00` (transparent) to `ff` (opaque)
+//!
+//! Most commands paint solid colors and reject translucent ones; alpha only
+//! matters where something shows through (`fill-key --over`).
+
+/// The accepted hex forms, for error messages.
+pub const HEX_FORMS: &str = "3, 6 or 8 hex digits (e.g., f00, ff0000 or ff000080)";
+
+/// Parse a hex color into `(r, g, b, alpha)`; forms without alpha are opaque.
+///
+/// Returns `None` if `s` isn't one of the forms in the module docs.
+#[must_use]
+pub fn parse_hex(s: &str) -> Option<(u8, u8, u8, u8)> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    match hex.len() {
+        3 => {
+            let digit = |i: usize| u8::from_str_radix(&hex[i..=i], 16).ok().map(|d| d * 17);
+            Some((digit(0)?, digit(1)?, digit(2)?, u8::MAX))
+        }
+        6 => Some((channel(0)?, channel(2)?, channel(4)?, u8::MAX)),
+        8 => Some((channel(0)?, channel(2)?, channel(4)?, channel(6)?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex_forms() {
+        for prefix in ["", "#"] {
+            let parse = |hex: &str| parse_hex(&format!("{prefix}{hex}"));
+            assert_eq!(parse("f00"), Some((255, 0, 0, 255)));
+            assert_eq!(parse("1aF"), Some((17, 170, 255, 255)));
+            assert_eq!(parse("ff5500"), Some((255, 85, 0, 255)));
+            assert_eq!(parse("FF5500"), Some((255, 85, 0, 255)));
+            assert_eq!(parse("ff000080"), Some((255, 0, 0, 128)));
+            assert_eq!(parse("00000000"), Some((0, 0, 0, 0)));
+        }
+    }
+
+    #[test]
+    fn test_parse_hex_rejects_other_lengths_and_digits() {
+        for bad in [
+            "",
+            "#",
+            "f0",
+            "ff00",
+            "ff000",
+            "ff0000f",
+            "ff0000800",
+            "ggg",
+            "gg0000",
+        ] {
+            assert_eq!(parse_hex(bad), None, "{bad}");
+        }
+        // Only a single leading '#'
+        assert_eq!(parse_hex("##ff0000"), None);
+        // Multi-byte characters never reach the slicing
+        assert_eq!(parse_hex("ff00é"), None);
+    }
+}
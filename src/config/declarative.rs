@@ -4,12 +4,13 @@
 //! Unlike the [`loader`](super::loader) module which handles Elgato's
 //! `.streamDeckProfile` format, this module handles our declarative config format.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info, instrument, trace, warn};
 
+use crate::device::DeviceModel;
 use crate::error::{Result, SdError};
 
 use super::{KeyConfig, KeySelector};
@@ -58,6 +59,7 @@ impl ConfigFormat {
 /// ```yaml
 /// name: My Profile
 /// brightness: 75
+/// on_apply: logger -t sd applied {name}, {keys_set} keys set
 /// keys:
 ///   "0":
 ///     image: ~/icons/chrome.png
@@ -68,7 +70,23 @@ impl ConfigFormat {
 ///   "default":
 ///     clear: true
 /// ```
+///
+/// # Model Variants
+///
+/// A key entry can carry `variants` keyed by device model. When the profile
+/// is applied, the variant for the connected model replaces the entry;
+/// other models get the base config:
+///
+/// ```yaml
+/// keys:
+///   "0":
+///     image: ~/icons/logo-72.png
+///     variants:
+///       xl:
+///         image: ~/icons/logo-96.png
+/// ```
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(from = "RawProfileConfig", into = "RawProfileConfig")]
 pub struct ProfileConfig {
     /// Optional profile name for identification.
     #[serde(default)]
@@ -87,12 +105,89 @@ pub struct ProfileConfig {
     #[serde(default)]
     pub brightness: Option<u8>,
 
+    /// Shell command to run after the profile is applied (see [`crate::hook`]).
+    ///
+    /// `apply --after` overrides it.
+    #[serde(default)]
+    pub on_apply: Option<String>,
+
     /// Key configurations mapped by selector.
     ///
     /// Keys are [`KeySelector`] strings (e.g., "0", "8-15", "row-0", "default").
     /// Values are [`KeyConfig`] entries specifying how to configure each key.
-    #[serde(default)]
     pub keys: HashMap<String, KeyConfig>,
+
+    /// Per-model replacements for key entries, by selector then model name.
+    ///
+    /// Model names are as accepted by [`DeviceModel::parse_name`]; see
+    /// [`ProfileConfig::for_model`] for how they're resolved.
+    pub variants: HashMap<String, BTreeMap<String, KeyConfig>>,
+}
+
+/// On-disk shape of a profile, with `variants` nested in each key entry.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct RawProfileConfig {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    device: Option<String>,
+    #[serde(default)]
+    brightness: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    on_apply: Option<String>,
+    #[serde(default)]
+    keys: HashMap<String, RawKeyEntry>,
+}
+
+/// A key entry: the base config plus optional per-model variants.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct RawKeyEntry {
+    #[serde(flatten)]
+    config: KeyConfig,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    variants: BTreeMap<String, KeyConfig>,
+}
+
+impl From<RawProfileConfig> for ProfileConfig {
+    fn from(raw: RawProfileConfig) -> Self {
+        let mut keys = HashMap::with_capacity(raw.keys.len());
+        let mut variants = HashMap::new();
+        for (selector, entry) in raw.keys {
+            if !entry.variants.is_empty() {
+                variants.insert(selector.clone(), entry.variants);
+            }
+            keys.insert(selector, entry.config);
+        }
+        Self {
+            name: raw.name,
+            device: raw.device,
+            brightness: raw.brightness,
+            on_apply: raw.on_apply,
+            keys,
+            variants,
+        }
+    }
+}
+
+impl From<ProfileConfig> for RawProfileConfig {
+    fn from(config: ProfileConfig) -> Self {
+        let mut variants = config.variants;
+        let keys = config
+            .keys
+            .into_iter()
+            .map(|(selector, config)| {
+                let variants = variants.remove(&selector).unwrap_or_default();
+                (selector, RawKeyEntry { config, variants })
+            })
+            .collect();
+        Self {
+            name: config.name,
+            device: config.device,
+            brightness: config.brightness,
+            on_apply: config.on_apply,
+            keys,
+        }
+    }
 }
 
 impl ProfileConfig {
@@ -108,6 +203,7 @@ impl ProfileConfig {
     /// - Brightness is in range 0-100
     /// - All key selectors are valid
     /// - All key configs are valid
+    /// - Every variant names a known [`DeviceModel`] and has a valid config
     ///
     /// # Errors
     ///
@@ -136,10 +232,57 @@ impl ProfileConfig {
             })?;
         }
 
+        for (selector_str, variants) in &self.variants {
+            if !self.keys.contains_key(selector_str) {
+                return Err(SdError::ConfigInvalid(format!(
+                    "Variants for key '{selector_str}' have no base config"
+                )));
+            }
+            for (model, config) in variants {
+                if DeviceModel::parse_name(model).is_none() {
+                    return Err(SdError::ConfigInvalid(format!(
+                        "Unknown model '{model}' in variants for key '{selector_str}' \
+                         (expected e.g. mini, mk2, xl, plus, neo)"
+                    )));
+                }
+                config.validate().map_err(|e| {
+                    SdError::ConfigInvalid(format!(
+                        "Invalid '{model}' variant for key '{selector_str}': {e}"
+                    ))
+                })?;
+            }
+        }
+
         debug!(keys = self.keys.len(), "All key entries validated");
         Ok(())
     }
 
+    /// Resolve model variants for the device the profile is applied to.
+    ///
+    /// Each key entry with a variant for exactly `model` uses that variant;
+    /// every other entry keeps its base config. Models are matched exactly,
+    /// so an `xl` variant doesn't cover an XL V2. With no known model (no
+    /// device connected) the base configs are used throughout. The result
+    /// has no variants left.
+    #[must_use]
+    pub fn for_model(&self, model: Option<DeviceModel>) -> Self {
+        let mut resolved = self.clone();
+        let variants = std::mem::take(&mut resolved.variants);
+        let Some(model) = model else {
+            return resolved;
+        };
+        for (selector, variants) in variants {
+            let matching = variants
+                .into_iter()
+                .find(|(name, _)| DeviceModel::parse_name(name) == Some(model));
+            if let Some((name, config)) = matching {
+                trace!(selector = %selector, variant = %name, "Using model variant");
+                resolved.keys.insert(selector, config);
+            }
+        }
+        resolved
+    }
+
     /// Parse and validate key selectors.
     ///
     /// Returns a vector of (selector, config) pairs sorted by priority.
@@ -182,9 +325,42 @@ impl ProfileConfig {
 /// - Validation fails
 #[instrument(skip_all, fields(path = %path.as_ref().display()))]
 pub fn load_config<P: AsRef<Path>>(path: P) -> Result<ProfileConfig> {
-    let path = path.as_ref();
     info!("Loading configuration file");
+    let (content, format) = read_config_file(path.as_ref())?;
+
+    // Parse based on format
+    load_config_from_str(&content, format)
+}
 
+/// Load a profile configuration, rejecting fields the format doesn't define
+/// (`--strict-fields`).
+///
+/// [`load_config`] ignores unknown fields so older versions of `sd` can read
+/// newer configs; this catches typos such as `brightnes: 80` instead.
+///
+/// # Errors
+///
+/// Returns an error for anything [`load_config`] rejects, or
+/// [`SdError::ConfigInvalid`] listing every unknown field.
+#[instrument(skip_all, fields(path = %path.as_ref().display()))]
+pub fn load_config_strict<P: AsRef<Path>>(path: P) -> Result<ProfileConfig> {
+    info!("Loading configuration file (strict fields)");
+    let (content, format) = read_config_file(path.as_ref())?;
+    let config = load_config_from_str(&content, format)?;
+
+    let unknown = unknown_fields(&content, format)?;
+    if !unknown.is_empty() {
+        let list: Vec<String> = unknown.iter().map(ToString::to_string).collect();
+        return Err(SdError::ConfigInvalid(format!(
+            "Unknown field(s): {}",
+            list.join(", ")
+        )));
+    }
+    Ok(config)
+}
+
+/// Read a config file and detect its format from the extension.
+fn read_config_file(path: &Path) -> Result<(String, ConfigFormat)> {
     // Detect format from extension
     let format = ConfigFormat::from_extension(path).ok_or_else(|| {
         SdError::ConfigParse(format!(
@@ -205,9 +381,7 @@ pub fn load_config<P: AsRef<Path>>(path: P) -> Result<ProfileConfig> {
         }
     })?;
     debug!(bytes = content.len(), "Read config file");
-
-    // Parse based on format
-    load_config_from_str(&content, format)
+    Ok((content, format))
 }
 
 /// Load a profile configuration from a string with a specified format.
@@ -228,8 +402,8 @@ pub fn load_config_from_str(content: &str, format: ConfigFormat) -> Result<Profi
         }
     };
 
-    // Handle warnings for unknown fields (serde ignores them by default)
-    // This is a forward-compatibility feature
+    // Unknown fields are ignored for forward compatibility; --strict-fields
+    // reports them through unknown_fields
     trace!(name = ?config.name, "Parsed config structure");
 
     // Validate the configuration
@@ -246,6 +420,125 @@ pub fn load_config_from_str(content: &str, format: ConfigFormat) -> Result<Profi
     Ok(config)
 }
 
+/// A field in a config file that the format doesn't define.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnknownField {
+    /// Where the field is, e.g. `key[0]` or `key[0].variants.xl`; empty at
+    /// the top level.
+    pub location: String,
+    /// The field name as written.
+    pub field: String,
+}
+
+impl std::fmt::Display for UnknownField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.location.is_empty() {
+            write!(f, "'{}'", self.field)
+        } else {
+            write!(f, "'{}' in {}", self.field, self.location)
+        }
+    }
+}
+
+const PROFILE_FIELDS: &[&str] = &["name", "device", "brightness", "on_apply", "keys"];
+
+/// Find every field `content` sets that the config format doesn't define.
+///
+/// A second pass over the document, since key entries are flattened and
+/// untagged and so can't use serde's `deny_unknown_fields`. Each key entry
+/// is checked against the fields of the kind it parses as, so `color` next
+/// to `image` is reported too.
+///
+/// # Errors
+///
+/// Returns an error if `content` isn't valid YAML or TOML.
+pub fn unknown_fields(content: &str, format: ConfigFormat) -> Result<Vec<UnknownField>> {
+    use serde_yaml::Value;
+
+    let document: Value = match format {
+        ConfigFormat::Yaml => {
+            serde_yaml::from_str(content).map_err(|e| SdError::ConfigParse(format!("YAML: {e}")))?
+        }
+        ConfigFormat::Toml => {
+            toml::from_str(content).map_err(|e| SdError::ConfigParse(format!("TOML: {e}")))?
+        }
+    };
+
+    let mut unknown = Vec::new();
+    let Value::Mapping(top) = &document else {
+        return Ok(unknown);
+    };
+    check_fields(top, PROFILE_FIELDS, "", &mut unknown);
+
+    let Some(Value::Mapping(keys)) = top.get("keys") else {
+        return Ok(unknown);
+    };
+    for (selector, entry) in keys {
+        let Value::Mapping(entry) = entry else {
+            continue;
+        };
+        let location = format!("key[{}]", field_name(selector));
+        let mut allowed = key_config_fields(entry).to_vec();
+        allowed.push("variants");
+        check_fields(entry, &allowed, &location, &mut unknown);
+
+        let Some(Value::Mapping(variants)) = entry.get("variants") else {
+            continue;
+        };
+        for (model, variant) in variants {
+            if let Value::Mapping(variant) = variant {
+                let location = format!("{location}.variants.{}", field_name(model));
+                check_fields(variant, key_config_fields(variant), &location, &mut unknown);
+            }
+        }
+    }
+
+    debug!(count = unknown.len(), "Checked config for unknown fields");
+    Ok(unknown)
+}
+
+/// Fields of the [`KeyConfig`] kind `entry` parses as, tried in the same
+/// order serde tries them.
+fn key_config_fields(entry: &serde_yaml::Mapping) -> &'static [&'static str] {
+    const KINDS: &[(&str, &[&str])] = &[
+        ("image", &["image", "data", "label"]),
+        ("data", &["data", "label"]),
+        ("pattern", &["pattern", "missing"]),
+        ("color", &["color"]),
+        ("clear", &["clear"]),
+    ];
+    KINDS
+        .iter()
+        .find(|(tag, _)| entry.contains_key(*tag))
+        .map_or(&[], |&(_, fields)| fields)
+}
+
+fn check_fields(
+    map: &serde_yaml::Mapping,
+    allowed: &[&str],
+    location: &str,
+    unknown: &mut Vec<UnknownField>,
+) {
+    for key in map.keys() {
+        let field = field_name(key);
+        if !allowed.contains(&field.as_str()) {
+            unknown.push(UnknownField {
+                location: location.to_string(),
+                field,
+            });
+        }
+    }
+}
+
+fn field_name(key: &serde_yaml::Value) -> String {
+    match key {
+        serde_yaml::Value::String(s) => s.clone(),
+        serde_yaml::Value::Number(n) => n.to_string(),
+        serde_yaml::Value::Bool(b) => b.to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
 /// Save a profile configuration to a file.
 ///
 /// Automatically detects the format from the file extension.
@@ -270,14 +563,7 @@ pub fn save_config<P: AsRef<Path>>(config: &ProfileConfig, path: P) -> Result<()
     })?;
     debug!(format = ?format, "Using config format");
 
-    // Serialize to string
-    let content = match format {
-        ConfigFormat::Yaml => {
-            serde_yaml::to_string(config).map_err(|e| SdError::ConfigParse(format!("YAML: {e}")))?
-        }
-        ConfigFormat::Toml => toml::to_string_pretty(config)
-            .map_err(|e| SdError::ConfigParse(format!("TOML: {e}")))?,
-    };
+    let content = config_to_string(config, format)?;
 
     // Write to file
     std::fs::write(path, content)?;
@@ -289,6 +575,22 @@ pub fn save_config<P: AsRef<Path>>(config: &ProfileConfig, path: P) -> Result<()
     Ok(())
 }
 
+/// Serialize a profile configuration in `format`, as [`save_config`] writes it.
+///
+/// # Errors
+///
+/// Returns an error if serialization fails.
+pub fn config_to_string(config: &ProfileConfig, format: ConfigFormat) -> Result<String> {
+    match format {
+        ConfigFormat::Yaml => {
+            serde_yaml::to_string(config).map_err(|e| SdError::ConfigParse(format!("YAML: {e}")))
+        }
+        ConfigFormat::Toml => {
+            toml::to_string_pretty(config).map_err(|e| SdError::ConfigParse(format!("TOML: {e}")))
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -497,6 +799,74 @@ keys:
         assert!(matches!(parsed[3].0, KeySelector::Default));
     }
 
+    #[test]
+    fn test_model_variants_resolve_per_model() {
+        fn is_color(config: &KeyConfig, name: &str) -> bool {
+            matches!(config, KeyConfig::Color { color: ColorSpec::Hex(c) } if c == name)
+        }
+
+        let yaml = r#"
+keys:
+  "0":
+    color: red
+    variants:
+      xl:
+        color: blue
+      Stream Deck Mini:
+        clear: true
+  "1":
+    color: green
+"#;
+        let config = load_config_from_str(yaml, ConfigFormat::Yaml).unwrap();
+        assert!(is_color(&config.keys["0"], "red"));
+        assert_eq!(config.variants["0"].len(), 2);
+
+        let xl = config.for_model(Some(DeviceModel::Xl));
+        assert!(is_color(&xl.keys["0"], "blue"));
+        assert!(xl.variants.is_empty());
+        let mini = config.for_model(Some(DeviceModel::Mini));
+        assert!(matches!(mini.keys["0"], KeyConfig::Clear { clear: true }));
+
+        // No variant for the model (exact matches only) or no model: the base
+        for model in [Some(DeviceModel::XlV2), Some(DeviceModel::Mk2), None] {
+            let resolved = config.for_model(model);
+            assert!(is_color(&resolved.keys["0"], "red"), "{model:?}");
+            assert_eq!(resolved.keys.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_model_variants_roundtrip_toml() {
+        let toml_str = r#"
+[keys."0"]
+color = "red"
+
+[keys."0".variants.mk2]
+color = "blue"
+"#;
+        let config = load_config_from_str(toml_str, ConfigFormat::Toml).unwrap();
+        let saved = toml::to_string_pretty(&config).unwrap();
+        let parsed = load_config_from_str(&saved, ConfigFormat::Toml).unwrap();
+        assert_eq!(parsed.variants["0"].keys().collect::<Vec<_>>(), ["mk2"]);
+    }
+
+    #[test]
+    fn test_validate_unknown_variant_model() {
+        let yaml = r#"
+keys:
+  "0":
+    color: red
+    variants:
+      xxl:
+        color: blue
+"#;
+        let err = load_config_from_str(yaml, ConfigFormat::Yaml).unwrap_err();
+        assert!(
+            matches!(&err, SdError::ConfigInvalid(msg) if msg.contains("'xxl'")),
+            "{err}"
+        );
+    }
+
     #[test]
     fn test_key_config_image_yaml() {
         let yaml = r#"
@@ -509,7 +879,7 @@ keys:
         let key_config = config.keys.get("0").unwrap();
 
         match key_config {
-            KeyConfig::Image { image, label } => {
+            KeyConfig::Image { image, label, .. } => {
                 assert_eq!(image, &PathBuf::from("/path/to/image.png"));
                 assert_eq!(label, &Some("My Label".to_string()));
             }
@@ -644,6 +1014,48 @@ another_unknown:
         assert_eq!(config.name, Some("Test".to_string()));
     }
 
+    #[test]
+    fn test_unknown_fields_found_when_strict() {
+        let yaml = r##"
+name: Test
+brightnes: 80
+keys:
+  "0":
+    image: a.png
+    lable: Play
+    color: "#ff0000"
+    variants:
+      xl:
+        color: "#00ff00"
+        colour: "#00ff00"
+  "1-2":
+    clear: true
+"##;
+        // Lenient by default
+        assert!(load_config_from_str(yaml, ConfigFormat::Yaml).is_ok());
+
+        let mut unknown: Vec<String> = unknown_fields(yaml, ConfigFormat::Yaml)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        unknown.sort();
+        assert_eq!(
+            unknown,
+            [
+                "'brightnes'",
+                "'color' in key[0]",
+                "'colour' in key[0].variants.xl",
+                "'lable' in key[0]",
+            ]
+        );
+
+        let toml = "name = \"Test\"\n[keys.0]\ncolor = \"red\"\nmissing = \"skip\"\n";
+        let unknown = unknown_fields(toml, ConfigFormat::Toml).unwrap();
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].field, "missing");
+    }
+
     #[test]
     fn test_roundtrip_yaml() {
         let mut keys = HashMap::new();
@@ -658,7 +1070,9 @@ another_unknown:
             name: Some("Test".to_string()),
             device: None,
             brightness: Some(80),
+            on_apply: None,
             keys,
+            variants: HashMap::new(),
         };
 
         // Serialize to YAML
@@ -2,8 +2,21 @@
 //!
 //! This module provides the [`KeyConfig`] enum which represents different
 //! ways to configure a Stream Deck key: image, pattern, color, or clear.
+//!
+//! An image can live in the config itself, as base64 `data` instead of an
+//! `image` path, so the config is one self-contained file:
+//!
+//! ```yaml
+//! keys:
+//!   "0":
+//!     data: iVBORw0KGgoAAAANSUhEUgAA...
+//! ```
+//!
+//! Base64 is a third larger than the file it encodes, and the whole config
+//! is read on every load, so inline data suits small icons; keep large or
+//! shared images as files.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 use tracing::{debug, trace, warn};
@@ -13,17 +26,22 @@ use crate::error::{Result, SdError};
 /// Configuration for a single key or key group.
 ///
 /// Each key in a Stream Deck profile can be configured in one of several ways:
-/// - An image from a file path
+/// - An image from a file path, or inline base64 data
 /// - A pattern for batch key assignment (using `{index}` placeholder)
 /// - A solid color fill
 /// - Cleared (set to black)
 #[derive(Debug, Clone, Deserialize, Serialize)]
-#[serde(untagged)]
+#[serde(untagged, from = "RawKeyConfig")]
 pub enum KeyConfig {
-    /// Static image from file path.
+    /// Static image from a file path or inline data.
     Image {
-        /// Path to the image file (absolute, relative, or with ~ expansion).
+        /// Path to the image file (absolute, relative, or with ~ expansion);
+        /// empty when the image is inline `data`.
+        #[serde(default, skip_serializing_if = "is_empty_path")]
         image: PathBuf,
+        /// Base64 image data (or a `data:` URI), in place of `image`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        data: Option<String>,
         /// Optional text label overlay (future enhancement).
         #[serde(default)]
         label: Option<String>,
@@ -54,6 +72,56 @@ pub enum KeyConfig {
     },
 }
 
+/// A key config as written. An entry with `data` but no `image` is an
+/// image too, which the untagged [`KeyConfig`] alone can't tell from a typo.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawKeyConfig {
+    Image {
+        image: PathBuf,
+        #[serde(default)]
+        data: Option<String>,
+        #[serde(default)]
+        label: Option<String>,
+    },
+    Inline {
+        data: String,
+        #[serde(default)]
+        label: Option<String>,
+    },
+    Pattern {
+        pattern: String,
+        #[serde(default)]
+        missing: MissingBehavior,
+    },
+    Color {
+        color: ColorSpec,
+    },
+    Clear {
+        clear: bool,
+    },
+}
+
+impl From<RawKeyConfig> for KeyConfig {
+    fn from(raw: RawKeyConfig) -> Self {
+        match raw {
+            RawKeyConfig::Image { image, data, label } => Self::Image { image, data, label },
+            RawKeyConfig::Inline { data, label } => Self::Image {
+                image: PathBuf::new(),
+                data: Some(data),
+                label,
+            },
+            RawKeyConfig::Pattern { pattern, missing } => Self::Pattern { pattern, missing },
+            RawKeyConfig::Color { color } => Self::Color { color },
+            RawKeyConfig::Clear { clear } => Self::Clear { clear },
+        }
+    }
+}
+
+fn is_empty_path(path: &Path) -> bool {
+    path.as_os_str().is_empty()
+}
+
 /// How to handle missing pattern files.
 #[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
@@ -70,13 +138,14 @@ pub enum MissingBehavior {
 /// Color specification supporting multiple input formats.
 ///
 /// Colors can be specified as:
-/// - Hex strings: `"#FF5500"` or `"FF5500"`
+/// - Hex strings: `"#FF5500"`, `"FF5500"`, shorthand `"#F50"`, or with
+///   alpha `"#FF550080"` (see [`crate::color`])
 /// - RGB arrays: `[255, 85, 0]`
 /// - Named colors: `"red"`, `"blue"`, etc.
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(untagged)]
 pub enum ColorSpec {
-    /// Hex format: "#FF5500" or "FF5500".
+    /// Hex format: "#FF5500", "FF5500", "F50" or "FF550080".
     Hex(String),
     /// RGB array: [255, 85, 0].
     Rgb([u8; 3]),
@@ -87,11 +156,37 @@ impl ColorSpec {
     ///
     /// # Errors
     ///
-    /// Returns an error if the color specification is invalid.
+    /// Returns an error if the color specification is invalid or
+    /// translucent (keys are painted solid).
     pub fn to_rgb(&self) -> Result<(u8, u8, u8)> {
+        let (r, g, b, alpha) = self.to_rgba()?;
+        if alpha < u8::MAX {
+            return Err(SdError::ConfigParse(format!(
+                "Color '{}' is translucent; key colors must be opaque",
+                self.source()
+            )));
+        }
+        Ok((r, g, b))
+    }
+
+    /// Parse color specification to RGB values plus alpha (255 unless the
+    /// hex form gives one).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the color specification is invalid.
+    pub fn to_rgba(&self) -> Result<(u8, u8, u8, u8)> {
         match self {
             Self::Hex(hex) => parse_hex_color(hex),
-            Self::Rgb([r, g, b]) => Ok((*r, *g, *b)),
+            Self::Rgb([r, g, b]) => Ok((*r, *g, *b, u8::MAX)),
+        }
+    }
+
+    /// The color as written, for messages.
+    fn source(&self) -> String {
+        match self {
+            Self::Hex(hex) => hex.clone(),
+            Self::Rgb(rgb) => format!("{rgb:?}"),
         }
     }
 
@@ -106,34 +201,28 @@ impl ColorSpec {
     }
 }
 
-/// Parse a hex color string to RGB values.
+/// Parse a named or hex color string to RGB values plus alpha.
 ///
-/// Supports both `#RRGGBB` and `RRGGBB` formats.
-fn parse_hex_color(hex: &str) -> Result<(u8, u8, u8)> {
+/// Hex colors may be `#RGB`, `#RRGGBB` or `#RRGGBBAA`, with or without `#`.
+fn parse_hex_color(hex: &str) -> Result<(u8, u8, u8, u8)> {
     trace!(hex = %hex, "Parsing hex color");
-    let hex = hex.trim_start_matches('#');
 
-    // Check for named colors first
-    if let Some(rgb) = named_color_to_rgb(hex) {
-        debug!(name = %hex, r = rgb.0, g = rgb.1, b = rgb.2, "Resolved named color");
-        return Ok(rgb);
+    // Hex first: the name lookup warns about anything it doesn't know
+    if let Some(rgba) = crate::color::parse_hex(hex) {
+        debug!(hex = %hex, r = rgba.0, g = rgba.1, b = rgba.2, a = rgba.3, "Parsed hex color");
+        return Ok(rgba);
     }
 
-    if hex.len() != 6 {
-        return Err(SdError::ConfigParse(format!(
-            "Invalid hex color '{hex}': expected 6 hex digits"
-        )));
+    let name = hex.trim_start_matches('#');
+    if let Some((r, g, b)) = named_color_to_rgb(name) {
+        debug!(name = %name, r, g, b, "Resolved named color");
+        return Ok((r, g, b, u8::MAX));
     }
 
-    let r = u8::from_str_radix(&hex[0..2], 16)
-        .map_err(|_| SdError::ConfigParse(format!("Invalid red component in '{hex}'")))?;
-    let g = u8::from_str_radix(&hex[2..4], 16)
-        .map_err(|_| SdError::ConfigParse(format!("Invalid green component in '{hex}'")))?;
-    let b = u8::from_str_radix(&hex[4..6], 16)
-        .map_err(|_| SdError::ConfigParse(format!("Invalid blue component in '{hex}'")))?;
-
-    debug!(hex = %hex, r, g, b, "Parsed hex color");
-    Ok((r, g, b))
+    Err(SdError::ConfigParse(format!(
+        "Invalid hex color '{hex}': expected a color name or {}",
+        crate::color::HEX_FORMS
+    )))
 }
 
 /// Convert a named color to RGB values.
@@ -161,6 +250,18 @@ fn named_color_to_rgb(name: &str) -> Option<(u8, u8, u8)> {
     Some(rgb)
 }
 
+/// Placeholders a key pattern can use for the key number.
+const INDEX_PLACEHOLDERS: [&str; 3] = ["{index}", "{index:02d}", "{index:03d}"];
+
+/// Whether `pattern` marks where the key number goes with `{index}`
+/// (or its zero-padded `{index:02d}` / `{index:03d}` forms).
+#[must_use]
+pub fn has_index_placeholder(pattern: &str) -> bool {
+    INDEX_PLACEHOLDERS
+        .iter()
+        .any(|placeholder| pattern.contains(placeholder))
+}
+
 impl KeyConfig {
     /// Validate the configuration.
     ///
@@ -168,21 +269,34 @@ impl KeyConfig {
     ///
     /// Returns an error if:
     /// - Image path is empty
+    /// - Both an image path and inline data are given
+    /// - Inline data isn't base64 of an image sd can decode
     /// - Pattern does not contain `{index}` placeholder
     /// - Color specification is invalid
     /// - `clear: false` is specified (should omit the key instead)
     pub fn validate(&self) -> Result<()> {
         trace!(config = ?self, "Validating key config");
         match self {
-            Self::Image { image, .. } => {
+            Self::Image { image, data, .. } => match data {
+                Some(_) if !is_empty_path(image) => Err(SdError::ConfigInvalid(
+                    "Give either image (a file) or data (inline base64), not both".to_string(),
+                )),
+                Some(data) => {
+                    // Decode it now, so a broken image fails validation
+                    // rather than half way through an apply
+                    let uri = crate::data_uri::from_base64(data)?;
+                    // The size only matters for SVGs, which are rasterized
+                    crate::image_ops::open_for_size(Path::new(&uri), 72, 72)?;
+                    Ok(())
+                }
                 // Path validation happens during resolution
-                if image.as_os_str().is_empty() {
-                    return Err(SdError::ConfigInvalid("Empty image path".to_string()));
+                None if is_empty_path(image) => {
+                    Err(SdError::ConfigInvalid("Empty image path".to_string()))
                 }
-                Ok(())
-            }
+                None => Ok(()),
+            },
             Self::Pattern { pattern, .. } => {
-                if !pattern.contains("{index}") {
+                if !has_index_placeholder(pattern) {
                     return Err(SdError::ConfigInvalid(
                         "Pattern must contain {index} placeholder".to_string(),
                     ));
@@ -208,8 +322,11 @@ impl KeyConfig {
     #[must_use]
     pub fn description(&self) -> String {
         match self {
-            Self::Image { image, label } => {
-                let mut desc = format!("image: {}", image.display());
+            Self::Image { image, data, label } => {
+                let mut desc = match data {
+                    Some(data) => format!("image: inline data ({} bytes of base64)", data.len()),
+                    None => format!("image: {}", image.display()),
+                };
                 if let Some(l) = label {
                     desc.push_str(&format!(" (label: {l})"));
                 }
@@ -249,7 +366,7 @@ mod tests {
         let config: KeyConfig = serde_yaml::from_str(yaml).unwrap();
 
         match config {
-            KeyConfig::Image { image, label } => {
+            KeyConfig::Image { image, label, .. } => {
                 assert_eq!(image.to_str().unwrap(), "~/icons/test.png");
                 assert!(label.is_none());
             }
@@ -329,6 +446,18 @@ missing: skip
         }
     }
 
+    #[test]
+    fn test_parse_color_hex_shorthand_and_alpha() {
+        for hex in ["#F50", "f50", "#FF5500FF", "ff5500ff"] {
+            let color = ColorSpec::Hex(hex.to_string());
+            assert_eq!(color.to_rgb().unwrap(), (255, 85, 0), "{hex}");
+        }
+        let translucent = ColorSpec::Hex("#FF550080".to_string());
+        assert_eq!(translucent.to_rgba().unwrap(), (255, 85, 0, 128));
+        assert!(translucent.to_rgb().is_err());
+        assert_eq!(ColorSpec::Rgb([1, 2, 3]).to_rgba().unwrap(), (1, 2, 3, 255));
+    }
+
     #[test]
     fn test_parse_color_rgb_array() {
         let yaml = r#"color: [255, 85, 0]"#;
@@ -367,11 +496,41 @@ missing: skip
     fn test_validate_empty_image() {
         let config = KeyConfig::Image {
             image: PathBuf::from(""),
+            data: None,
             label: None,
         };
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_parse_inline_image() {
+        // A 1x1 PNG
+        let png = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNk+M9QDwADhgGAWjR9awAAAABJRU5ErkJggg==";
+        let config: KeyConfig = serde_yaml::from_str(&format!("data: {png}\nlabel: Dot")).unwrap();
+        match &config {
+            KeyConfig::Image { image, data, label } => {
+                assert!(image.as_os_str().is_empty());
+                assert_eq!(data.as_deref(), Some(png));
+                assert_eq!(label.as_deref(), Some("Dot"));
+            }
+            _ => panic!("Expected Image config"),
+        }
+        config.validate().unwrap();
+        // Written back without the empty path
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        assert!(!yaml.contains("image"), "{yaml}");
+        let reparsed: KeyConfig = serde_yaml::from_str(&yaml).unwrap();
+        assert!(matches!(reparsed, KeyConfig::Image { data: Some(_), .. }));
+
+        let both: KeyConfig =
+            serde_yaml::from_str(&format!("image: dot.png\ndata: {png}")).unwrap();
+        let err = both.validate().unwrap_err().to_string();
+        assert!(err.contains("not both"), "{err}");
+
+        let broken: KeyConfig = serde_yaml::from_str("data: aGVsbG8=").unwrap();
+        assert!(broken.validate().is_err());
+    }
+
     #[test]
     fn test_validate_pattern_no_placeholder() {
         let config = KeyConfig::Pattern {
@@ -381,6 +540,15 @@ missing: skip
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_has_index_placeholder() {
+        assert!(has_index_placeholder("key-{index}.png"));
+        assert!(has_index_placeholder("icon-{index:02d}.png"));
+        assert!(has_index_placeholder("{index:03d}.jpg"));
+        assert!(!has_index_placeholder("key-index.png"));
+        assert!(!has_index_placeholder("key-{idx}.png"));
+    }
+
     #[test]
     fn test_validate_invalid_color() {
         // This tests that invalid hex colors fail
@@ -407,6 +575,7 @@ missing: skip
     fn test_description() {
         let img = KeyConfig::Image {
             image: PathBuf::from("test.png"),
+            data: None,
             label: Some("Test".to_string()),
         };
         assert!(img.description().contains("test.png"));
@@ -11,6 +11,8 @@ mod loader;
 mod path;
 mod schema;
 mod selector;
+pub mod settings;
+pub mod upgrade;
 
 // Re-export schema types for use by other modules
 #[allow(unused_imports)] // Types are for future use
@@ -28,11 +30,14 @@ pub use loader::ProfileLoader;
 
 // Re-export path helpers for declarative config support
 #[allow(unused_imports)] // Types are for future use
-pub use path::{PathResolver, home_dir, resolve_path, validate_image_path};
+pub use path::{
+    PathResolver, default_profiles_dir, find_profile, home_dir, profile_files, resolve_path,
+    validate_image_path,
+};
 
 // Re-export key config types for declarative YAML/TOML configuration
-pub use key_config::{ColorSpec, KeyConfig, MissingBehavior, ResolvedKey};
+pub use key_config::{ColorSpec, KeyConfig, MissingBehavior, ResolvedKey, has_index_placeholder};
 
 // Re-export key selector types for targeting keys in config
 #[allow(unused_imports)] // Used by validate/apply commands (future beads)
-pub use selector::KeySelector;
+pub use selector::{KeySelector, uncovered_keys};
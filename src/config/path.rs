@@ -1,20 +1,29 @@
 //! Path resolution helpers for declarative configuration files.
 //!
-//! Supports absolute paths, paths relative to the config file, and "~" home
-//! directory expansion.
+//! Supports absolute paths, paths relative to the config file, "~" home
+//! directory expansion, and `${VAR}` environment variable expansion.
+//!
+//! Also finds profiles in the profile library (`~/.config/sd/profiles/`),
+//! where configs can be applied by file name instead of full path.
 
 use std::path::{Path, PathBuf};
 
 use tracing::{debug, trace, warn};
 
+use super::declarative::ConfigFormat;
 use crate::error::{Result, SdError};
 
 /// Resolve a path from a config file.
 ///
 /// Resolution rules:
-/// 1. Absolute paths: used as-is
-/// 2. Paths starting with `~`: expanded to home directory
-/// 3. Relative paths: resolved relative to the config file's directory
+/// 1. `${VAR}` references: expanded from the environment first
+/// 2. Absolute paths: used as-is
+/// 3. Paths starting with `~`: expanded to home directory
+/// 4. Relative paths: resolved relative to the config file's directory
+///
+/// # Errors
+///
+/// Returns [`SdError::UndefinedVariable`] if a referenced variable is unset.
 pub fn resolve_path(path: &Path, config_dir: &Path) -> Result<PathBuf> {
     trace!(
         path = %path.display(),
@@ -22,6 +31,8 @@ pub fn resolve_path(path: &Path, config_dir: &Path) -> Result<PathBuf> {
         "Resolving path"
     );
 
+    let expanded = expand_env_vars(path)?;
+    let path = expanded.as_path();
     let path_str = path.to_string_lossy();
 
     // Home directory expansion
@@ -58,6 +69,46 @@ pub fn resolve_path(path: &Path, config_dir: &Path) -> Result<PathBuf> {
     Ok(resolved)
 }
 
+/// Expand `${VAR}` references in a path from the process environment.
+///
+/// # Errors
+///
+/// Returns [`SdError::UndefinedVariable`] if a referenced variable is unset.
+pub fn expand_env_vars(path: &Path) -> Result<PathBuf> {
+    expand_vars_with(path, |name| std::env::var(name).ok())
+}
+
+/// Expand `${VAR}` references using `lookup` (split out for testing).
+///
+/// Only the braced form is recognized so that literal `$` in file names
+/// keeps working; an unterminated `${` is left as-is.
+fn expand_vars_with(path: &Path, lookup: impl Fn(&str) -> Option<String>) -> Result<PathBuf> {
+    let original = path.to_string_lossy();
+    if !original.contains("${") {
+        return Ok(path.to_path_buf());
+    }
+
+    let mut expanded = String::with_capacity(original.len());
+    let mut rest = original.as_ref();
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 2..start + 2 + len];
+        let value = lookup(name).ok_or_else(|| SdError::UndefinedVariable {
+            name: name.to_string(),
+            path: original.to_string(),
+        })?;
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&value);
+        rest = &rest[start + 3 + len..];
+    }
+    expanded.push_str(rest);
+
+    debug!(original = %original, expanded = %expanded, "Expanded environment variables");
+    Ok(PathBuf::from(expanded))
+}
+
 /// Resolve the user's home directory (cross-platform).
 pub fn home_dir() -> Result<PathBuf> {
     dirs::home_dir()
@@ -84,7 +135,7 @@ pub fn validate_image_path(path: &Path) -> Result<()> {
         .map(|e| e.to_lowercase());
 
     match ext.as_deref() {
-        Some("png" | "jpg" | "jpeg" | "gif" | "bmp" | "webp") => Ok(()),
+        Some("png" | "jpg" | "jpeg" | "gif" | "bmp" | "webp" | "ico" | "svg" | "svgz") => Ok(()),
         Some(other) => Err(SdError::ImageFormat(format!(
             "Unsupported image format: .{other}"
         ))),
@@ -94,6 +145,72 @@ pub fn validate_image_path(path: &Path) -> Result<()> {
     }
 }
 
+/// Returns the default profile library directory.
+///
+/// Location: `~/.config/sd/profiles/`
+pub fn default_profiles_dir() -> Result<PathBuf> {
+    let config_dir = dirs::config_dir().ok_or_else(|| {
+        SdError::ConfigInvalid("Could not determine config directory".to_string())
+    })?;
+    Ok(config_dir.join("sd").join("profiles"))
+}
+
+/// The `.yaml`/`.yml`/`.toml` files in a profile directory, by file name.
+///
+/// A directory that doesn't exist yet holds no profiles.
+pub fn profile_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            debug!(dir = %dir.display(), "No profile directory");
+            return Ok(Vec::new());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && ConfigFormat::from_extension(path).is_some())
+        .collect();
+    files.sort();
+    trace!(dir = %dir.display(), count = files.len(), "Found profiles");
+    Ok(files)
+}
+
+/// Find the profile called `name` in `dir`.
+///
+/// `name` is a file name with or without its extension, so `work` finds
+/// `work.yaml` or `work.toml`.
+///
+/// # Errors
+///
+/// Returns [`SdError::ConfigNotFound`] if no file matches, or
+/// [`SdError::ConfigInvalid`] if several do (e.g. `work.yaml` and
+/// `work.toml`).
+pub fn find_profile(dir: &Path, name: &str) -> Result<PathBuf> {
+    let matches: Vec<PathBuf> = profile_files(dir)?
+        .into_iter()
+        .filter(|path| {
+            path.file_name().is_some_and(|file| file == name)
+                || path.file_stem().is_some_and(|stem| stem == name)
+        })
+        .collect();
+    match matches.as_slice() {
+        [] => Err(SdError::ConfigNotFound {
+            path: dir.join(name).display().to_string(),
+        }),
+        [path] => Ok(path.clone()),
+        _ => Err(SdError::ConfigInvalid(format!(
+            "Profile '{name}' is ambiguous: {}. Use the full file name",
+            matches
+                .iter()
+                .filter_map(|path| path.file_name())
+                .map(|file| file.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
 /// Path resolution context for a config file.
 pub struct PathResolver {
     config_dir: PathBuf,
@@ -201,6 +318,62 @@ mod tests {
         assert_eq!(resolved, home);
     }
 
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "ICON_DIR" => Some("/opt/icons".to_string()),
+            "THEME" => Some("dark".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand_defined_vars() {
+        let expanded = expand_vars_with(Path::new("${ICON_DIR}/${THEME}/chrome.png"), lookup);
+        assert_eq!(
+            expanded.unwrap(),
+            PathBuf::from("/opt/icons/dark/chrome.png")
+        );
+
+        // No references: unchanged
+        let expanded = expand_vars_with(Path::new("icons/$literal.png"), lookup);
+        assert_eq!(expanded.unwrap(), PathBuf::from("icons/$literal.png"));
+    }
+
+    #[test]
+    fn test_expand_undefined_var() {
+        let result = expand_vars_with(Path::new("${MISSING_DIR}/chrome.png"), lookup);
+        match result {
+            Err(SdError::UndefinedVariable { name, path }) => {
+                assert_eq!(name, "MISSING_DIR");
+                assert_eq!(path, "${MISSING_DIR}/chrome.png");
+            }
+            other => panic!("Expected UndefinedVariable, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_expand_partially_defined_vars() {
+        // One defined, one not: the undefined one is reported
+        let result = expand_vars_with(Path::new("${ICON_DIR}/${VARIANT}/chrome.png"), lookup);
+        assert!(
+            matches!(result, Err(SdError::UndefinedVariable { ref name, .. }) if name == "VARIANT")
+        );
+
+        // Unterminated reference is kept literally after the expanded prefix
+        let expanded = expand_vars_with(Path::new("${ICON_DIR}/${oops.png"), lookup);
+        assert_eq!(expanded.unwrap(), PathBuf::from("/opt/icons/${oops.png"));
+    }
+
+    #[test]
+    fn test_resolve_path_expands_vars_before_joining() {
+        let config_dir = Path::new("/home/user/.config/sd");
+        let path = Path::new("${SD_TEST_UNSET_VAR_FOR_RESOLVE}/x.png");
+        assert!(matches!(
+            resolve_path(path, config_dir),
+            Err(SdError::UndefinedVariable { .. })
+        ));
+    }
+
     #[test]
     fn test_validate_existing_image() {
         let temp = TempDir::new().unwrap();
@@ -247,7 +420,7 @@ mod tests {
     fn test_supported_formats() {
         let temp = TempDir::new().unwrap();
 
-        for ext in ["png", "jpg", "jpeg", "gif", "bmp", "webp"] {
+        for ext in ["png", "jpg", "jpeg", "gif", "bmp", "webp", "ico", "svg"] {
             let path = temp.path().join(format!("test.{ext}"));
             File::create(&path).unwrap();
             assert!(validate_image_path(&path).is_ok(), "Should support .{ext}");
@@ -266,6 +439,49 @@ mod tests {
         assert!(resolved.starts_with(temp.path()));
     }
 
+    #[test]
+    fn test_find_profile_by_name() {
+        let temp = TempDir::new().unwrap();
+        for file in [
+            "work.yaml",
+            "stream.toml",
+            "notes.txt",
+            "dup.yml",
+            "dup.toml",
+        ] {
+            File::create(temp.path().join(file)).unwrap();
+        }
+
+        let names: Vec<_> = profile_files(temp.path())
+            .unwrap()
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["dup.toml", "dup.yml", "stream.toml", "work.yaml"]);
+
+        assert_eq!(
+            find_profile(temp.path(), "work").unwrap(),
+            temp.path().join("work.yaml")
+        );
+        assert_eq!(
+            find_profile(temp.path(), "stream.toml").unwrap(),
+            temp.path().join("stream.toml")
+        );
+        assert!(matches!(
+            find_profile(temp.path(), "notes"),
+            Err(SdError::ConfigNotFound { .. })
+        ));
+        assert!(matches!(
+            find_profile(temp.path(), "dup"),
+            Err(SdError::ConfigInvalid(_))
+        ));
+        assert!(
+            profile_files(&temp.path().join("missing"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
     #[test]
     fn test_path_resolver_image() {
         let temp = TempDir::new().unwrap();
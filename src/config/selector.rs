@@ -242,6 +242,29 @@ impl KeySelector {
     }
 }
 
+/// Return the key indices of `device` that no selector covers, in order.
+///
+/// A `default` selector covers every key. Selectors that don't resolve on this
+/// device (e.g. out of range) contribute nothing; callers report those separately.
+#[must_use]
+pub fn uncovered_keys<'a>(
+    selectors: impl IntoIterator<Item = &'a KeySelector>,
+    device: &DeviceInfo,
+) -> Vec<u8> {
+    let mut covered = vec![false; usize::from(device.key_count)];
+    for selector in selectors {
+        if matches!(selector, KeySelector::Default) {
+            return Vec::new();
+        }
+        for key in selector.resolve(device).unwrap_or_default() {
+            covered[usize::from(key)] = true;
+        }
+    }
+    (0..device.key_count)
+        .filter(|&key| !covered[usize::from(key)])
+        .collect()
+}
+
 impl FromStr for KeySelector {
     type Err = SdError;
 
@@ -557,6 +580,20 @@ mod tests {
         assert_eq!(selector, KeySelector::Range { start: 8, end: 15 });
     }
 
+    #[test]
+    fn test_uncovered_keys() {
+        let device = mini_device();
+        let selectors = [KeySelector::Row(0), KeySelector::Single(4)];
+        assert_eq!(uncovered_keys(&selectors, &device), vec![3, 5]);
+
+        // Out-of-range selectors are ignored rather than panicking
+        let selectors = [KeySelector::Range { start: 0, end: 40 }];
+        assert_eq!(uncovered_keys(&selectors, &device), vec![0, 1, 2, 3, 4, 5]);
+
+        let selectors = [KeySelector::Single(0), KeySelector::Default];
+        assert!(uncovered_keys(&selectors, &device).is_empty());
+    }
+
     #[test]
     fn test_hash() {
         use std::collections::HashSet;
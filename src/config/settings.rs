@@ -0,0 +1,195 @@
+//! User settings that change CLI defaults.
+//!
+//! Settings live in `~/.config/sd/config.yaml` and are optional; a missing
+//! file means built-in defaults. Command-line flags always win over settings.
+//!
+//! ```yaml
+//! # Preview every command until --no-dry-run (or --apply) is given
+//! default_dry_run: true
+//!
+//! # Key size and layout to assume when no device is connected (default: XL)
+//! default_model: StreamDeckMK2
+//!
+//! # Rephrase human-mode confirmations (see `output::template`)
+//! output:
+//!   templates:
+//!     key_filled: "key {key} is now {color}"
+//!
+//! # Profiles for `sd context NAME`, relative to this file (see `context`)
+//! contexts:
+//!   firefox: profiles/browser.yaml
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::device::DeviceModel;
+use crate::error::{Result, SdError};
+use crate::output::OutputTemplates;
+
+/// Defaults loaded from the user's settings file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Treat every command as `--dry-run` unless `--no-dry-run` is given.
+    pub default_dry_run: bool,
+
+    /// Model to render and validate for when no device is connected.
+    #[serde(
+        deserialize_with = "deserialize_model",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub default_model: Option<DeviceModel>,
+
+    /// Human output customization.
+    #[serde(skip_serializing_if = "OutputSettings::is_default")]
+    pub output: OutputSettings,
+
+    /// Profile config for each context name pushed with `sd context`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub contexts: BTreeMap<String, PathBuf>,
+}
+
+/// The `output` section of the settings file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputSettings {
+    /// Message templates for human mode.
+    pub templates: OutputTemplates,
+}
+
+impl OutputSettings {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Settings {
+    /// Load settings from `path`. A missing file yields the defaults.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but can't be read or parsed.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            debug!(path = %path.display(), "No settings file, using defaults");
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(path)?;
+        // An empty or comment-only file is null rather than an empty map
+        let mut settings = serde_yaml::from_str::<Option<Self>>(&text)
+            .map(Option::unwrap_or_default)
+            .map_err(|e| SdError::ConfigParse(format!("{}: {e}", path.display())))?;
+        if let Some(dir) = path.parent() {
+            for profile in settings.contexts.values_mut() {
+                if profile.is_relative() {
+                    *profile = dir.join(&*profile);
+                }
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Load settings from the standard location.
+    ///
+    /// Problems are logged and fall back to the defaults so a broken
+    /// settings file never stops the CLI from running.
+    #[must_use]
+    pub fn load_default() -> Self {
+        let Some(path) = default_settings_path() else {
+            return Self::default();
+        };
+        Self::load(&path).unwrap_or_else(|e| {
+            warn!(error = %e, "Ignoring unreadable settings file");
+            Self::default()
+        })
+    }
+}
+
+/// Accept any name [`DeviceModel::parse_name`] understands.
+fn deserialize_model<'de, D>(deserializer: D) -> std::result::Result<Option<DeviceModel>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let Some(name) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    DeviceModel::parse_name(&name)
+        .map(Some)
+        .ok_or_else(|| serde::de::Error::custom(format!("unknown device model '{name}'")))
+}
+
+/// Returns the settings file path.
+///
+/// Location: `~/.config/sd/config.yaml`
+#[must_use]
+pub fn default_settings_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("sd").join("config.yaml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_file_uses_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = Settings::load(&dir.path().join("config.yaml")).unwrap();
+        assert_eq!(settings, Settings::default());
+        assert!(!settings.default_dry_run);
+    }
+
+    #[test]
+    fn test_load_default_dry_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+
+        std::fs::write(&path, "default_dry_run: true\n").unwrap();
+        assert!(Settings::load(&path).unwrap().default_dry_run);
+
+        std::fs::write(&path, "# nothing set\n").unwrap();
+        assert!(!Settings::load(&path).unwrap().default_dry_run);
+
+        std::fs::write(&path, "default_model: StreamDeckMK2\n").unwrap();
+        assert_eq!(
+            Settings::load(&path).unwrap().default_model,
+            Some(DeviceModel::Mk2)
+        );
+
+        std::fs::write(
+            &path,
+            "output:\n  templates:\n    key_filled: \"{key} = {color}\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            Settings::load(&path)
+                .unwrap()
+                .output
+                .templates
+                .key_filled
+                .as_deref(),
+            Some("{key} = {color}")
+        );
+
+        std::fs::write(
+            &path,
+            "contexts:\n  firefox: browser.yaml\n  code: /abs/editor.yaml\n",
+        )
+        .unwrap();
+        let contexts = Settings::load(&path).unwrap().contexts;
+        assert_eq!(contexts["firefox"], dir.path().join("browser.yaml"));
+        assert_eq!(contexts["code"], PathBuf::from("/abs/editor.yaml"));
+
+        std::fs::write(&path, "default_model: toaster\n").unwrap();
+        assert!(Settings::load(&path).is_err());
+
+        std::fs::write(&path, "default_dry_run: [\n").unwrap();
+        assert!(matches!(
+            Settings::load(&path),
+            Err(SdError::ConfigParse(_))
+        ));
+    }
+}
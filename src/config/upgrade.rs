@@ -0,0 +1,300 @@
+//! Rewriting declarative configs that use outdated syntax.
+//!
+//! Older configs may spell selectors loosely (`row0`, `column_2`, `0..7`,
+//! `*`) or use field names that have since been renamed (`path` for `image`,
+//! `fill` for `color`). [`upgrade_str`] rewrites them to the current schema
+//! and reports every change, so `sd upgrade-config` can save the result with
+//! [`save_config`](super::declarative::save_config).
+//!
+//! Upgrades work on the parsed document rather than the text, so the
+//! rewritten file keeps no comments and no fields sd doesn't know.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tracing::{debug, trace};
+
+use crate::error::{Result, SdError};
+
+use super::KeySelector;
+use super::declarative::{ConfigFormat, ProfileConfig};
+
+/// Top-level fields that were renamed: (old, current).
+const PROFILE_RENAMES: [(&str, &str); 2] = [("serial", "device"), ("profile", "name")];
+
+/// Key entry fields that were renamed: (old, current).
+const KEY_RENAMES: [(&str, &str); 5] = [
+    ("path", "image"),
+    ("icon", "image"),
+    ("file", "image"),
+    ("fill", "color"),
+    ("on_missing", "missing"),
+];
+
+/// One rewrite made to bring a config up to date.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigChange {
+    /// Where in the config the change was made (e.g. `keys.row0`).
+    pub location: String,
+    /// What was changed.
+    pub message: String,
+}
+
+/// The result of upgrading a config.
+#[derive(Debug, Clone)]
+pub struct Upgrade {
+    /// The config in the current schema, validated.
+    pub config: ProfileConfig,
+    /// Every change made, top-level fields first, then keys by selector;
+    /// empty if the config was already current.
+    pub changes: Vec<ConfigChange>,
+}
+
+impl Upgrade {
+    /// Returns true if nothing needed rewriting.
+    #[must_use]
+    pub fn is_current(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Upgrade config text in `format` to the current schema.
+///
+/// # Errors
+///
+/// Returns an error if the text doesn't parse, or if the upgraded config
+/// still isn't valid (for example two selectors that mean the same keys).
+pub fn upgrade_str(content: &str, format: ConfigFormat) -> Result<Upgrade> {
+    let mut document: Value = match format {
+        ConfigFormat::Yaml => {
+            serde_yaml::from_str(content).map_err(|e| SdError::ConfigParse(format!("YAML: {e}")))?
+        }
+        ConfigFormat::Toml => {
+            toml::from_str(content).map_err(|e| SdError::ConfigParse(format!("TOML: {e}")))?
+        }
+    };
+    // An empty YAML file is a valid, empty profile
+    if document.is_null() {
+        document = Value::Object(Map::new());
+    }
+    let Value::Object(profile) = &mut document else {
+        return Err(SdError::ConfigParse(
+            "Config must be a mapping of profile fields".to_string(),
+        ));
+    };
+
+    let mut changes = Vec::new();
+    for (old, new) in PROFILE_RENAMES {
+        rename_field(profile, old, new, "", &mut changes);
+    }
+    if let Some(Value::Object(keys)) = profile.get_mut("keys") {
+        upgrade_keys(keys, &mut changes)?;
+    }
+    debug!(changes = changes.len(), "Upgraded config document");
+
+    let config: ProfileConfig = serde_json::from_value(document)
+        .map_err(|e| SdError::ConfigParse(format!("Upgraded config: {e}")))?;
+    config.validate()?;
+    Ok(Upgrade { config, changes })
+}
+
+/// Rename loose selectors and upgrade every key entry.
+fn upgrade_keys(keys: &mut Map<String, Value>, changes: &mut Vec<ConfigChange>) -> Result<()> {
+    let selectors: Vec<String> = keys.keys().cloned().collect();
+    for selector in selectors {
+        let location = format!("keys.{selector}");
+        let current = match canonical_selector(&selector) {
+            Some(canonical) if canonical != selector => {
+                if keys.contains_key(&canonical) {
+                    return Err(SdError::ConfigInvalid(format!(
+                        "Selector '{selector}' is the old form of '{canonical}', which is also \
+                         set; merge the two entries by hand"
+                    )));
+                }
+                let entry = keys.remove(&selector).unwrap_or(Value::Null);
+                keys.insert(canonical.clone(), entry);
+                changes.push(ConfigChange {
+                    location: location.clone(),
+                    message: format!("renamed selector '{selector}' to '{canonical}'"),
+                });
+                canonical
+            }
+            _ => selector,
+        };
+
+        let Some(Value::Object(entry)) = keys.get_mut(&current) else {
+            continue;
+        };
+        upgrade_entry(entry, &location, changes);
+        if let Some(Value::Object(variants)) = entry.get_mut("variants") {
+            for (model, variant) in variants.iter_mut() {
+                if let Value::Object(variant) = variant {
+                    upgrade_entry(variant, &format!("{location}.variants.{model}"), changes);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Upgrade the fields of one key entry.
+fn upgrade_entry(entry: &mut Map<String, Value>, location: &str, changes: &mut Vec<ConfigChange>) {
+    for (old, new) in KEY_RENAMES {
+        rename_field(entry, old, new, location, changes);
+    }
+    if entry.contains_key("blank") && !entry.contains_key("clear") {
+        let blank = entry.remove("blank").unwrap_or(Value::Null);
+        entry.insert("clear".to_string(), blank);
+        changes.push(ConfigChange {
+            location: location.to_string(),
+            message: "replaced 'blank' with 'clear'".to_string(),
+        });
+    }
+}
+
+/// Move `old` to `new` in `map`, or drop `old` if `new` is already set.
+fn rename_field(
+    map: &mut Map<String, Value>,
+    old: &str,
+    new: &str,
+    location: &str,
+    changes: &mut Vec<ConfigChange>,
+) {
+    let Some(value) = map.remove(old) else {
+        return;
+    };
+    let message = if map.contains_key(new) {
+        format!("dropped '{old}' because '{new}' is already set")
+    } else {
+        map.insert(new.to_string(), value);
+        format!("renamed '{old}' to '{new}'")
+    };
+    trace!(location, %message, "Upgraded field");
+    changes.push(ConfigChange {
+        location: if location.is_empty() {
+            old.to_string()
+        } else {
+            format!("{location}.{old}")
+        },
+        message,
+    });
+}
+
+/// The current spelling of a selector, or `None` if it isn't one in any form.
+///
+/// Accepts `*` and `all` for `default`; `row0`, `row_0` and `row 0` for
+/// `row-0`; `col0`, `column-0` and `column_0` for `col-0`; and `0..7`,
+/// `0..=7` and `0 - 7` for `0-7`. Case and surrounding spaces are ignored.
+#[must_use]
+pub fn canonical_selector(selector: &str) -> Option<String> {
+    let lower = selector.trim().to_ascii_lowercase();
+    let canonical = match lower.as_str() {
+        "*" | "all" => "default".to_string(),
+        s => {
+            let line = |prefix: &str, rest: &str| {
+                let n = rest.trim_start_matches(['-', '_', ' ', ':']).trim();
+                n.parse::<u8>().ok().map(|n| format!("{prefix}-{n}"))
+            };
+            if let Some(rest) = s.strip_prefix("column") {
+                line("col", rest)?
+            } else if let Some(rest) = s.strip_prefix("col") {
+                line("col", rest)?
+            } else if let Some(rest) = s.strip_prefix("row") {
+                line("row", rest)?
+            } else if let Some((start, end)) = s.split_once("..") {
+                let end = end.strip_prefix('=').unwrap_or(end);
+                format!("{}-{}", start.trim(), end.trim())
+            } else if let Some((start, end)) = s.split_once('-') {
+                format!("{}-{}", start.trim(), end.trim())
+            } else {
+                s.to_string()
+            }
+        }
+    };
+    KeySelector::parse(&canonical).ok().map(|_| canonical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KeyConfig;
+
+    #[test]
+    fn test_canonical_selector() {
+        for (old, new) in [
+            ("0", "0"),
+            (" 3 ", "3"),
+            ("*", "default"),
+            ("ALL", "default"),
+            ("row0", "row-0"),
+            ("Row_2", "row-2"),
+            ("column-4", "col-4"),
+            ("col 1", "col-1"),
+            ("0..7", "0-7"),
+            ("8..=15", "8-15"),
+            ("1 - 3", "1-3"),
+        ] {
+            assert_eq!(canonical_selector(old).as_deref(), Some(new), "{old}");
+        }
+        for bad in ["rowx", "7..2", "key-1", ""] {
+            assert_eq!(canonical_selector(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_upgrade_old_yaml() {
+        let yaml = r##"
+profile: Old
+serial: ABC123
+keys:
+  row0:
+    fill: "#ff0000"
+  "0":
+    path: ~/icons/a.png
+  "*":
+    blank: true
+  "1..3":
+    color: blue
+    variants:
+      xl:
+        icon: ~/icons/b.png
+"##;
+        let upgrade = upgrade_str(yaml, ConfigFormat::Yaml).unwrap();
+        let config = &upgrade.config;
+        assert_eq!(config.name.as_deref(), Some("Old"));
+        assert_eq!(config.device.as_deref(), Some("ABC123"));
+        assert!(matches!(config.keys["row-0"], KeyConfig::Color { .. }));
+        assert!(matches!(config.keys["0"], KeyConfig::Image { .. }));
+        assert!(matches!(
+            config.keys["default"],
+            KeyConfig::Clear { clear: true }
+        ));
+        assert!(matches!(
+            config.variants["1-3"]["xl"],
+            KeyConfig::Image { .. }
+        ));
+        // 2 top-level renames, 3 selectors, 4 key fields
+        assert_eq!(upgrade.changes.len(), 9, "{:?}", upgrade.changes);
+    }
+
+    #[test]
+    fn test_current_config_is_unchanged() {
+        let toml_str = r#"
+name = "Current"
+
+[keys."0"]
+color = "red"
+"#;
+        let upgrade = upgrade_str(toml_str, ConfigFormat::Toml).unwrap();
+        assert!(upgrade.is_current());
+        assert_eq!(upgrade.config.keys.len(), 1);
+    }
+
+    #[test]
+    fn test_upgrade_rejects_colliding_selectors() {
+        let yaml = "keys:\n  row0:\n    color: red\n  row-0:\n    color: blue\n";
+        assert!(matches!(
+            upgrade_str(yaml, ConfigFormat::Yaml),
+            Err(SdError::ConfigInvalid(_))
+        ));
+    }
+}
@@ -0,0 +1,131 @@
+//! Context switches pushed from `sd context` to a running `sd serve --stdio`.
+//!
+//! The settings file maps context names (usually the focused application) to
+//! profile configs:
+//!
+//! ```yaml
+//! contexts:
+//!   firefox: profiles/browser.yaml
+//!   code: profiles/editor.yaml
+//! ```
+//!
+//! Whatever watches window focus runs `sd context firefox`, which records the
+//! name in a small JSON file. The server polls that file and applies the
+//! mapped profile when it changes, so sd never has to detect windows itself.
+//!
+//! Location: `~/.local/share/sd/context.json`
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::error::{Result, SdError};
+
+/// How often the server checks for a new context.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The last context pushed with `sd context`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextRecord {
+    /// Context name, a key of the settings file's `contexts`.
+    pub name: String,
+    /// When it was pushed; a repeat push of the same name re-applies it.
+    pub set_at: DateTime<Utc>,
+}
+
+/// Record `name` as the current context at `path`.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be written.
+pub fn push(path: &Path, name: &str) -> Result<ContextRecord> {
+    let record = ContextRecord {
+        name: name.to_string(),
+        set_at: Utc::now(),
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string(&record)
+        .map_err(|e| SdError::Other(format!("Failed to serialize context: {e}")))?;
+    // Write then rename so the server never reads a partial record
+    let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(record)
+}
+
+/// Read the current context, if one has been pushed and the file is valid.
+#[must_use]
+pub fn read(path: &Path) -> Option<ContextRecord> {
+    let text = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&text)
+        .inspect_err(|e| debug!(error = %e, path = %path.display(), "Ignoring bad context file"))
+        .ok()
+}
+
+/// Notices new pushes to a context file.
+#[derive(Debug)]
+pub struct ContextWatcher {
+    path: PathBuf,
+    last: Option<ContextRecord>,
+}
+
+impl ContextWatcher {
+    /// Watch `path`; whatever it holds now counts as already seen.
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let last = read(&path);
+        Self { path, last }
+    }
+
+    /// Returns the context pushed since the last poll, if any.
+    pub fn poll(&mut self) -> Option<ContextRecord> {
+        let current = read(&self.path)?;
+        if self.last.as_ref() == Some(&current) {
+            return None;
+        }
+        self.last = Some(current.clone());
+        Some(current)
+    }
+}
+
+/// Returns the context file path.
+///
+/// Location: `~/.local/share/sd/context.json`
+///
+/// # Errors
+///
+/// Returns an error if the data directory can't be determined.
+pub fn default_context_path() -> Result<PathBuf> {
+    let data_dir = dirs::data_local_dir()
+        .ok_or_else(|| SdError::Other("Could not determine local data directory".to_string()))?;
+    Ok(data_dir.join("sd").join("context.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watcher_sees_each_push_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("context.json");
+        push(&path, "startup").unwrap();
+
+        let mut watcher = ContextWatcher::new(&path);
+        assert_eq!(watcher.poll(), None);
+
+        let pushed = push(&path, "firefox").unwrap();
+        assert_eq!(watcher.poll(), Some(pushed.clone()));
+        assert_eq!(watcher.poll(), None);
+        assert_eq!(read(&path), Some(pushed));
+
+        std::fs::write(&path, "not json").unwrap();
+        assert_eq!(watcher.poll(), None);
+    }
+}
@@ -0,0 +1,235 @@
+//! `data:` URIs as image sources.
+//!
+//! Programs that render key images in memory can pass them inline instead of
+//! writing a temporary file:
+//!
+//! ```text
+//! sd set-key 0 "data:image/png;base64,iVBORw0KGgo..."
+//! ```
+//!
+//! Only base64 payloads are accepted, and the media type must be an image
+//! format sd can decode. Whitespace inside the payload is ignored, so wrapped
+//! base64 works too.
+//!
+//! Configs can carry images inline the same way, as `data:` on a key; there
+//! the bare base64 is enough and the format is recognized from the bytes
+//! (see [`from_base64`]).
+
+use std::path::Path;
+
+use base64::Engine;
+
+use crate::error::{Result, SdError};
+
+/// Prefix that marks an image argument as a data URI.
+pub const DATA_SCHEME: &str = "data:";
+
+/// How data URI sources are named in output, in place of a file path.
+pub const SOURCE_NAME: &str = "data-uri";
+
+/// Media types accepted in a data URI, with the format name reported for each.
+const MEDIA_TYPES: [(&str, &str); 9] = [
+    ("image/png", "png"),
+    ("image/jpeg", "jpeg"),
+    ("image/jpg", "jpeg"),
+    ("image/gif", "gif"),
+    ("image/bmp", "bmp"),
+    ("image/webp", "webp"),
+    ("image/x-icon", "ico"),
+    ("image/vnd.microsoft.icon", "ico"),
+    ("image/svg+xml", "svg"),
+];
+
+/// A decoded `data:` URI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataUri {
+    /// Format name for the media type (`png`, `svg`, ...).
+    pub format: &'static str,
+    /// The decoded payload.
+    pub bytes: Vec<u8>,
+}
+
+/// Returns the URI if `path` is a `data:` URI rather than a file path.
+#[must_use]
+pub fn as_data_uri(path: &Path) -> Option<&str> {
+    path.to_str().filter(|s| s.starts_with(DATA_SCHEME))
+}
+
+/// Name an image source for output: [`SOURCE_NAME`] for data URIs (which
+/// can be megabytes long), the path otherwise.
+#[must_use]
+pub fn display_source(path: &Path) -> String {
+    if as_data_uri(path).is_some() {
+        SOURCE_NAME.to_string()
+    } else {
+        path.display().to_string()
+    }
+}
+
+/// Parse and decode a `data:<media type>;base64,<payload>` URI.
+///
+/// # Errors
+///
+/// Returns [`SdError::ImageFormat`] if the URI is malformed, not base64, has
+/// an unsupported media type, or the payload isn't valid base64.
+pub fn parse(uri: &str) -> Result<DataUri> {
+    let rest = uri
+        .strip_prefix(DATA_SCHEME)
+        .ok_or_else(|| invalid("missing the data: prefix"))?;
+    let (header, payload) = rest
+        .split_once(',')
+        .ok_or_else(|| invalid("missing the ',' before the payload"))?;
+
+    let mut params = header.split(';');
+    let media_type = params.next().unwrap_or_default().trim();
+    let is_base64 = params.any(|p| p.trim().eq_ignore_ascii_case("base64"));
+    let format = MEDIA_TYPES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(media_type))
+        .map(|(_, format)| *format)
+        .ok_or_else(|| {
+            let shown = if media_type.is_empty() {
+                "no media type"
+            } else {
+                media_type
+            };
+            invalid(&format!(
+                "{shown} is not a supported image type (use e.g. image/png or image/svg+xml)"
+            ))
+        })?;
+    if !is_base64 {
+        return Err(invalid(
+            "only base64 payloads are supported (data:image/png;base64,...)",
+        ));
+    }
+
+    let compact: String = payload
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect();
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(compact)
+        .map_err(|e| invalid(&format!("payload isn't valid base64: {e}")))?;
+    if bytes.is_empty() {
+        return Err(invalid("the payload is empty"));
+    }
+    Ok(DataUri { format, bytes })
+}
+
+/// The `data:` URI for an inline config image (`data:` on a key).
+///
+/// `payload` is plain base64, whose format is recognized from the decoded
+/// bytes, or a whole `data:` URI, which is checked and kept as is.
+///
+/// # Errors
+///
+/// Returns [`SdError::ImageFormat`] if the payload isn't valid base64 or
+/// isn't an image format sd can decode.
+pub fn from_base64(payload: &str) -> Result<String> {
+    let payload = payload.trim();
+    if payload.starts_with(DATA_SCHEME) {
+        parse(payload)?;
+        return Ok(payload.to_string());
+    }
+
+    let invalid =
+        |reason: &str| SdError::ImageFormat(format!("Invalid inline image data: {reason}"));
+    let compact: String = payload
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect();
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(&compact)
+        .map_err(|e| invalid(&format!("not valid base64: {e}")))?;
+    if bytes.is_empty() {
+        return Err(invalid("it's empty"));
+    }
+    let media_type = sniff_media_type(&bytes)
+        .ok_or_else(|| invalid("not a PNG, JPEG, GIF, BMP, WebP, ICO or SVG image"))?;
+    Ok(format!("{DATA_SCHEME}{media_type};base64,{compact}"))
+}
+
+/// Recognize an image's media type from its first bytes.
+fn sniff_media_type(bytes: &[u8]) -> Option<&'static str> {
+    use image::ImageFormat;
+
+    if let Ok(format) = image::guess_format(bytes) {
+        return match format {
+            ImageFormat::Png => Some("image/png"),
+            ImageFormat::Jpeg => Some("image/jpeg"),
+            ImageFormat::Gif => Some("image/gif"),
+            ImageFormat::Bmp => Some("image/bmp"),
+            ImageFormat::WebP => Some("image/webp"),
+            ImageFormat::Ico => Some("image/x-icon"),
+            _ => None,
+        };
+    }
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]);
+    head.contains("<svg").then_some("image/svg+xml")
+}
+
+fn invalid(reason: &str) -> SdError {
+    SdError::ImageFormat(format!("Invalid data URI: {reason}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_base64_image() {
+        let data = parse("data:image/png;base64,aGVs\nbG8=").unwrap();
+        assert_eq!(data.format, "png");
+        assert_eq!(data.bytes, b"hello");
+        assert_eq!(
+            parse("data:IMAGE/SVG+XML;base64,PHN2Zy8+").unwrap().format,
+            "svg"
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_uris() {
+        for uri in [
+            "data:image/png;base64",
+            "data:text/plain;base64,aGVsbG8=",
+            "data:;base64,aGVsbG8=",
+            "data:image/png,hello",
+            "data:image/png;base64,not base64!",
+            "data:image/png;base64,",
+        ] {
+            assert!(matches!(parse(uri), Err(SdError::ImageFormat(_))), "{uri}");
+        }
+    }
+
+    #[test]
+    fn test_from_base64_recognizes_format() {
+        // "\x89PNG\r\n\x1a\n" and "<svg/>"
+        assert_eq!(
+            from_base64("iVBORw0K\n  GgoAAA==").unwrap(),
+            "data:image/png;base64,iVBORw0KGgoAAA=="
+        );
+        assert_eq!(
+            from_base64("PHN2Zy8+").unwrap(),
+            "data:image/svg+xml;base64,PHN2Zy8+"
+        );
+        let uri = "data:image/png;base64,aGVsbG8=";
+        assert_eq!(from_base64(uri).unwrap(), uri);
+
+        for payload in ["", "not base64!", "aGVsbG8="] {
+            assert!(
+                matches!(from_base64(payload), Err(SdError::ImageFormat(_))),
+                "{payload}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_display_source() {
+        let uri = PathBuf::from("data:image/png;base64,aGVsbG8=");
+        assert_eq!(as_data_uri(&uri), uri.to_str());
+        assert_eq!(display_source(&uri), SOURCE_NAME);
+        assert_eq!(display_source(Path::new("key.png")), "key.png");
+        assert_eq!(as_data_uri(Path::new("data/key.png")), None);
+    }
+}
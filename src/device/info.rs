@@ -1,6 +1,13 @@
 //! Device information types for Stream Deck devices.
 
-use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, SdError};
+use crate::image_ops::KeySize;
 
 /// Information about a connected Stream Deck device.
 #[derive(Debug, Clone, Serialize)]
@@ -25,8 +32,159 @@ pub struct DeviceInfo {
     pub kind: String,
 }
 
-/// Supported Stream Deck device models.
+impl DeviceInfo {
+    /// Returns the native key image size for this device.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)] // Key dimensions are always small
+    pub const fn key_size(&self) -> KeySize {
+        KeySize::new(self.key_width as u32, self.key_height as u32)
+    }
+
+    /// Describe a device of `model` that isn't connected (no serial or firmware).
+    ///
+    /// Used when validating or previewing against an assumed model.
+    #[must_use]
+    pub fn for_model(model: DeviceModel) -> Self {
+        let (cols, rows) = model.layout();
+        let (width, height) = model.key_dimensions();
+        Self {
+            serial: String::new(),
+            product_name: model.display_name().to_string(),
+            firmware_version: String::new(),
+            key_count: model.key_count(),
+            key_width: width as usize,
+            key_height: height as usize,
+            rows,
+            cols,
+            kind: format!("{model:?}"),
+        }
+    }
+
+    /// Returns the known device model, if the kind is recognized.
+    #[must_use]
+    pub fn model(&self) -> Option<DeviceModel> {
+        DeviceModel::from_kind(&self.kind)
+    }
+
+    /// Returns the physical key size, gap and DPI, if the model is known
+    /// and has key displays.
+    #[must_use]
+    pub fn physical(&self) -> Option<PhysicalDimensions> {
+        self.model().and_then(DeviceModel::physical)
+    }
+}
+
+/// A connected Elgato device that isn't a Stream Deck model sd supports.
+///
+/// Listed so users can see why their device is ignored, and report its IDs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnknownDevice {
+    /// Device serial number (may be empty)
+    pub serial: String,
+    /// Product name reported over USB, or "Unknown Elgato device"
+    pub product_name: String,
+    /// Always "Unknown", matching [`DeviceInfo::kind`] for known models
+    pub kind: &'static str,
+    /// USB vendor ID
+    pub vendor_id: u16,
+    /// USB product ID
+    pub product_id: u16,
+    /// Always false; supported devices in the same list omit it
+    pub supported: bool,
+}
+
+impl UnknownDevice {
+    /// Describe an unsupported device from its USB descriptor.
+    #[must_use]
+    pub fn new(
+        serial: Option<&str>,
+        product_name: Option<&str>,
+        vendor_id: u16,
+        product_id: u16,
+    ) -> Self {
+        Self {
+            serial: serial.unwrap_or_default().to_string(),
+            product_name: product_name
+                .filter(|name| !name.trim().is_empty())
+                .unwrap_or("Unknown Elgato device")
+                .to_string(),
+            kind: "Unknown",
+            vendor_id,
+            product_id,
+            supported: false,
+        }
+    }
+
+    /// The USB ID as `lsusb` prints it, e.g. `0fd9:00ff`.
+    #[must_use]
+    pub fn usb_id(&self) -> String {
+        format!("{:04x}:{:04x}", self.vendor_id, self.product_id)
+    }
+
+    /// The error for trying to operate on this device.
+    #[must_use]
+    pub fn to_error(&self) -> SdError {
+        SdError::UnsupportedDevice {
+            product: self.product_name.clone(),
+            serial: self.serial.clone(),
+            vendor_id: self.vendor_id,
+            product_id: self.product_id,
+        }
+    }
+}
+
+/// Where a reported brightness level came from.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BrightnessSource {
+    /// Read back from the device.
+    Device,
+    /// The last level this sd process set (see `sd state`).
+    Tracked,
+}
+
+/// The display brightness shown by `info`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BrightnessReading {
+    /// Brightness level (0-100).
+    #[serde(rename = "current_brightness")]
+    pub level: u8,
+    /// Where `level` came from; only reported in verbose mode.
+    #[serde(
+        rename = "current_brightness_source",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub source: Option<BrightnessSource>,
+}
+
+/// How `--verify-writes` went for one device session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct WriteVerification {
+    /// Whether the device can read key images back.
+    pub supported: bool,
+    /// Key images written.
+    pub writes: usize,
+    /// Writes that read back as written, after at most one retry.
+    pub verified: usize,
+    /// Writes that still read back wrong after a retry.
+    pub mismatched: usize,
+    /// Writes that couldn't be checked.
+    pub unverified: usize,
+}
+
+/// Physical size of a model's key displays.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PhysicalDimensions {
+    /// Edge length of a (square) key display in millimetres.
+    pub physical_key_mm: f32,
+    /// Bezel between adjacent key displays in millimetres.
+    pub gap_mm: f32,
+    /// Pixel density of the key displays.
+    pub dpi: f32,
+}
+
+/// Supported Stream Deck device models.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
 #[allow(dead_code)]
 pub enum DeviceModel {
     /// Stream Deck Mini (6 keys, 3x2)
@@ -89,6 +247,125 @@ impl DeviceModel {
         }
     }
 
+    /// Parse a model from a device kind identifier (e.g. `"Xl"`, `"Mk2"`).
+    #[must_use]
+    pub fn from_kind(kind: &str) -> Option<Self> {
+        let model = match kind {
+            "Mini" => Self::Mini,
+            "MiniMk2" => Self::MiniMk2,
+            "Original" => Self::Original,
+            "OriginalV2" => Self::OriginalV2,
+            "Mk2" => Self::Mk2,
+            "Xl" => Self::Xl,
+            "XlV2" => Self::XlV2,
+            "Pedal" => Self::Pedal,
+            "Plus" => Self::Plus,
+            "Neo" => Self::Neo,
+            _ => return None,
+        };
+        Some(model)
+    }
+
+    /// Parse a model name leniently, as written in settings files.
+    ///
+    /// Case, spaces, `-`, `_`, `.` and a `StreamDeck` prefix are ignored, so
+    /// `mk2`, `Mk2`, `StreamDeckMK2` and `Stream Deck MK.2` all parse.
+    #[must_use]
+    pub fn parse_name(name: &str) -> Option<Self> {
+        let normalized: String = name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '+')
+            .collect::<String>()
+            .to_ascii_lowercase();
+        let normalized = normalized.strip_prefix("streamdeck").unwrap_or(&normalized);
+        let model = match normalized {
+            "mini" => Self::Mini,
+            "minimk2" => Self::MiniMk2,
+            "original" | "" => Self::Original,
+            "originalv2" => Self::OriginalV2,
+            "mk2" => Self::Mk2,
+            "xl" => Self::Xl,
+            "xlv2" => Self::XlV2,
+            "pedal" => Self::Pedal,
+            "plus" | "+" => Self::Plus,
+            "neo" => Self::Neo,
+            _ => return None,
+        };
+        Some(model)
+    }
+
+    /// Returns the bezel width between adjacent keys relative to the key width.
+    ///
+    /// Approximate values measured from the physical devices; used to make
+    /// canvas images look continuous across the gaps.
+    #[must_use]
+    pub const fn gap_ratio(self) -> f32 {
+        match self {
+            Self::Mini | Self::MiniMk2 => 0.35,
+            Self::Original | Self::OriginalV2 | Self::Mk2 | Self::Neo => 0.33,
+            Self::Xl | Self::XlV2 => 0.25,
+            Self::Plus => 0.2,
+            Self::Pedal => 0.0, // No display
+        }
+    }
+
+    /// Returns the visible edge length of one key display in millimetres.
+    ///
+    /// Approximate values from Elgato's published dimensions; `0.0` for the
+    /// Pedal, which has no displays.
+    #[must_use]
+    pub const fn physical_key_mm(self) -> f32 {
+        match self {
+            Self::Mini | Self::MiniMk2 => 14.0,
+            Self::Original | Self::OriginalV2 | Self::Mk2 | Self::Neo => 14.5,
+            Self::Xl | Self::XlV2 => 14.4,
+            Self::Plus => 19.6,
+            Self::Pedal => 0.0, // No display
+        }
+    }
+
+    /// Returns the bezel between adjacent keys in millimetres.
+    ///
+    /// Derived from [`Self::gap_ratio`] so the physical and pixel gaps agree.
+    #[must_use]
+    pub fn gap_mm(self) -> f32 {
+        (self.physical_key_mm() * self.gap_ratio() * 10.0).round() / 10.0
+    }
+
+    /// Returns the pixel density of the key displays (0 for the Pedal).
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)] // Small pixel values
+    pub fn dpi(self) -> f32 {
+        let mm = self.physical_key_mm();
+        if mm <= 0.0 {
+            return 0.0;
+        }
+        let (width, _) = self.key_dimensions();
+        (width as f32 * 25.4 / mm).round()
+    }
+
+    /// Returns physical key dimensions, or `None` for models without displays.
+    #[must_use]
+    pub fn physical(self) -> Option<PhysicalDimensions> {
+        (self.physical_key_mm() > 0.0).then(|| PhysicalDimensions {
+            physical_key_mm: self.physical_key_mm(),
+            gap_mm: self.gap_mm(),
+            dpi: self.dpi(),
+        })
+    }
+
+    /// Returns the default canvas gap in pixels for this model.
+    #[must_use]
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )] // Small, non-negative pixel values
+    pub fn default_gap(self) -> u32 {
+        let (width, _) = self.key_dimensions();
+        (width as f32 * self.gap_ratio()).round() as u32
+    }
+
     /// Returns a human-readable name for this device model.
     #[must_use]
     pub const fn display_name(self) -> &'static str {
@@ -107,8 +384,62 @@ impl DeviceModel {
     }
 }
 
+/// HID report type for `sd raw`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RawReportKind {
+    /// Feature report (brightness, reset, firmware queries).
+    Feature,
+    /// Output report (image data).
+    Output,
+}
+
+/// Raw bytes parsed from hex, e.g. `"03 08 64"`, `"0x03,0x08,0x64"` or `"030864"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexBytes(pub Vec<u8>);
+
+impl FromStr for HexBytes {
+    type Err = SdError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |detail: &str| SdError::Other(format!("Invalid hex bytes '{s}': {detail}"));
+
+        let mut bytes = Vec::new();
+        for token in s.split(|c: char| c.is_whitespace() || c == ',' || c == ':') {
+            let token = token.trim_start_matches("0x").trim_start_matches("0X");
+            if token.is_empty() {
+                continue;
+            }
+            if token.len() % 2 != 0 {
+                return Err(invalid("odd number of hex digits"));
+            }
+            for pair in token.as_bytes().chunks(2) {
+                let pair = std::str::from_utf8(pair).map_err(|_| invalid("not hex"))?;
+                bytes.push(u8::from_str_radix(pair, 16).map_err(|_| invalid("not hex"))?);
+            }
+        }
+
+        if bytes.is_empty() {
+            return Err(invalid("no bytes"));
+        }
+        Ok(Self(bytes))
+    }
+}
+
+impl fmt::Display for HexBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
 /// Button press/release event.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ButtonEvent {
     /// Key index (0-based)
     pub key: u8,
@@ -130,6 +461,8 @@ pub struct ConnectionOptions {
     pub backoff_factor: f32,
     /// Maximum delay cap (default: 10000ms).
     pub max_delay: std::time::Duration,
+    /// Randomize each delay by up to this percentage either way (default: 0).
+    pub jitter: u8,
 }
 
 impl Default for ConnectionOptions {
@@ -140,14 +473,219 @@ impl Default for ConnectionOptions {
             retry_delay: Duration::from_millis(1000),
             backoff_factor: 1.5,
             max_delay: Duration::from_millis(10000),
+            jitter: 0,
         }
     }
 }
 
+/// Spread `delay` randomly by up to `percent` either way (`--retry-jitter`,
+/// `watch --reconnect-jitter`).
+///
+/// Several `sd` processes that lose their devices together, as when a
+/// shared USB hub resets, would otherwise retry in lockstep; jitter spreads
+/// their attempts out. Zero returns `delay` unchanged.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn jittered(delay: std::time::Duration, percent: u8) -> std::time::Duration {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static CALLS: AtomicU64 = AtomicU64::new(0);
+
+    if percent == 0 || delay.is_zero() {
+        return delay;
+    }
+    // Clock, process and call count, so processes and retries all differ
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    let mut z = u64::from(nanos)
+        ^ (u64::from(std::process::id()) << 32)
+        ^ CALLS
+            .fetch_add(1, Ordering::Relaxed)
+            .wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    // SplitMix64 finalizer
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    let unit = (z >> 11) as f64 / (1u64 << 53) as f64;
+
+    let band = f64::from(percent.min(100)) / 100.0;
+    delay.mul_f64(band.mul_add(unit.mul_add(2.0, -1.0), 1.0))
+}
+
+/// One HID interface of an Elgato device, as enumerated (`--dump-hid`).
+#[derive(Debug, Clone, Serialize)]
+pub struct HidInterface {
+    /// USB vendor ID
+    pub vendor_id: u16,
+    /// USB product ID
+    pub product_id: u16,
+    /// Device serial number (may be empty)
+    pub serial: String,
+    /// Product name reported over USB
+    pub product_name: Option<String>,
+    /// Manufacturer reported over USB
+    pub manufacturer: Option<String>,
+    /// Device release number (bcdDevice)
+    pub release_number: u16,
+    /// USB interface number, or -1 if the platform doesn't say
+    pub interface_number: i32,
+    /// HID usage page of the top-level collection
+    pub usage_page: u16,
+    /// HID usage of the top-level collection
+    pub usage: u16,
+    /// Platform path the interface was opened by
+    pub path: String,
+    /// Whether sd recognizes the device as a Stream Deck model
+    pub supported: bool,
+}
+
+/// Size of one report, as declared by a report descriptor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HidReportSize {
+    /// `input`, `output` or `feature`
+    pub kind: &'static str,
+    /// Report ID (0 if the descriptor doesn't use IDs)
+    pub report_id: u8,
+    /// Payload size in bytes, not counting the report ID
+    pub bytes: u32,
+}
+
+/// One request made during a HID capture, and what came back.
+#[derive(Debug, Clone, Serialize)]
+pub struct HidRoundTrip {
+    /// What was asked for, e.g. `get_feature_report 0x05`
+    pub request: String,
+    /// Bytes received, as hex
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+    /// Why the request failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Time the request took, in microseconds
+    pub micros: u64,
+}
+
+/// Raw HID details of a device, for bug reports (`--dump-hid`).
+#[derive(Debug, Clone, Serialize)]
+pub struct HidDump {
+    /// Every Elgato HID interface on the bus
+    pub interfaces: Vec<HidInterface>,
+    /// The probed device as sd models it, if it's a supported model
+    pub device: Option<DeviceInfo>,
+    /// Why the probed interface couldn't be opened
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_error: Option<String>,
+    /// Raw report descriptor, as hex
+    pub report_descriptor: Option<String>,
+    /// Why the report descriptor couldn't be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub descriptor_error: Option<String>,
+    /// Report sizes declared by the descriptor
+    pub report_sizes: Vec<HidReportSize>,
+    /// Sample requests and their responses
+    pub round_trips: Vec<HidRoundTrip>,
+}
+
+/// Sizes of the reports declared by a HID report descriptor.
+///
+/// Only what's needed to size reports is interpreted: Report Size, Report
+/// Count and Report ID, summed per Input, Output and Feature item. Sizes are
+/// listed inputs first, then outputs, then features, each by report ID.
+#[must_use]
+pub fn hid_report_sizes(descriptor: &[u8]) -> Vec<HidReportSize> {
+    const INPUT: u8 = 0x80;
+    const OUTPUT: u8 = 0x90;
+    const FEATURE: u8 = 0xb0;
+
+    let mut bits: std::collections::BTreeMap<(u8, u8), u32> = std::collections::BTreeMap::new();
+    let (mut report_size, mut report_count, mut report_id) = (0u32, 0u32, 0u8);
+    let mut i = 0;
+    while let Some(&prefix) = descriptor.get(i) {
+        // Long items carry their data length in the next byte; none size reports
+        if prefix == 0xfe {
+            i += 3 + descriptor.get(i + 1).copied().map_or(0, usize::from);
+            continue;
+        }
+        let len = match prefix & 0x03 {
+            3 => 4,
+            n => usize::from(n),
+        };
+        let Some(data) = descriptor.get(i + 1..i + 1 + len) else {
+            break;
+        };
+        let value = data
+            .iter()
+            .rev()
+            .fold(0u32, |acc, &byte| (acc << 8) | u32::from(byte));
+        match prefix & 0xfc {
+            0x74 => report_size = value,
+            0x94 => report_count = value,
+            0x84 => report_id = u8::try_from(value).unwrap_or(u8::MAX),
+            tag @ (INPUT | OUTPUT | FEATURE) => {
+                let total = bits.entry((tag, report_id)).or_default();
+                *total = total.saturating_add(report_size.saturating_mul(report_count));
+            }
+            _ => {}
+        }
+        i += 1 + len;
+    }
+
+    bits.into_iter()
+        .map(|((tag, report_id), bits)| HidReportSize {
+            kind: match tag {
+                INPUT => "input",
+                OUTPUT => "output",
+                _ => "feature",
+            },
+            report_id,
+            bytes: bits.div_ceil(8),
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_hid_report_sizes() {
+        let descriptor = [
+            0x06, 0x00, 0xff, // Usage Page (vendor)
+            0x09, 0x01, // Usage
+            0xa1, 0x01, // Collection (application)
+            0x85, 0x01, 0x75, 0x08, 0x95, 0x3f, 0x81, 0x02, // Input 1: 63 bytes
+            0x85, 0x02, 0x96, 0xff, 0x03, 0x91, 0x02, // Output 2: 1023 bytes
+            0x85, 0x03, 0x95, 0x1f, 0xb1, 0x04, // Feature 3: 31 bytes
+            0x85, 0x03, 0x75, 0x01, 0x95, 0x04, 0xb1, 0x02, // ...plus 4 bits
+            0xc0, // End Collection
+        ];
+        let sizes: Vec<(&str, u8, u32)> = hid_report_sizes(&descriptor)
+            .into_iter()
+            .map(|size| (size.kind, size.report_id, size.bytes))
+            .collect();
+        assert_eq!(
+            sizes,
+            vec![("input", 1, 63), ("output", 2, 1023), ("feature", 3, 32)]
+        );
+        assert!(hid_report_sizes(&[0x85]).is_empty());
+    }
+
+    #[test]
+    fn test_jittered_stays_within_band() {
+        use std::time::Duration;
+
+        let delay = Duration::from_millis(1000);
+        assert_eq!(jittered(delay, 0), delay);
+        let delays: Vec<Duration> = (0..50).map(|_| jittered(delay, 20)).collect();
+        assert!(
+            delays
+                .iter()
+                .all(|d| (Duration::from_millis(800)..=Duration::from_millis(1200)).contains(d))
+        );
+        assert!(delays.iter().any(|d| *d != delays[0]));
+    }
+
     #[test]
     fn test_device_model_key_count() {
         assert_eq!(DeviceModel::Mini.key_count(), 6);
@@ -155,6 +693,89 @@ mod tests {
         assert_eq!(DeviceModel::Xl.key_count(), 32);
     }
 
+    #[test]
+    fn test_device_model_from_kind() {
+        assert_eq!(DeviceModel::from_kind("Xl"), Some(DeviceModel::Xl));
+        assert_eq!(
+            DeviceModel::from_kind("MiniMk2"),
+            Some(DeviceModel::MiniMk2)
+        );
+        assert_eq!(DeviceModel::from_kind("Unknown"), None);
+    }
+
+    #[test]
+    fn test_unknown_device_reports_raw_ids() {
+        let device = UnknownDevice::new(Some("ABC"), Some(" "), 0x0fd9, 0x00ff);
+        assert_eq!(device.product_name, "Unknown Elgato device");
+        assert_eq!(device.usb_id(), "0fd9:00ff");
+
+        let json = serde_json::to_value(&device).unwrap();
+        assert_eq!(json["kind"], "Unknown");
+        assert_eq!(json["vendor_id"], 0x0fd9);
+        assert_eq!(json["product_id"], 0x00ff);
+        assert_eq!(json["supported"], false);
+
+        let err = device.to_error();
+        assert_eq!(err.code(), "unsupported_device");
+        assert!(err.to_string().contains("0fd9:00ff"), "{err}");
+    }
+
+    #[test]
+    fn test_device_model_default_gap() {
+        assert_eq!(DeviceModel::Xl.default_gap(), 24);
+        assert_eq!(DeviceModel::Mk2.default_gap(), 24);
+        assert_eq!(DeviceModel::Pedal.default_gap(), 0);
+    }
+
+    #[test]
+    fn test_device_model_parse_name() {
+        assert_eq!(DeviceModel::parse_name("mk2"), Some(DeviceModel::Mk2));
+        assert_eq!(
+            DeviceModel::parse_name("StreamDeckMK2"),
+            Some(DeviceModel::Mk2)
+        );
+        assert_eq!(
+            DeviceModel::parse_name("Stream Deck XL V2"),
+            Some(DeviceModel::XlV2)
+        );
+        assert_eq!(
+            DeviceModel::parse_name("mini-mk2"),
+            Some(DeviceModel::MiniMk2)
+        );
+        assert_eq!(
+            DeviceModel::parse_name("Stream Deck +"),
+            Some(DeviceModel::Plus)
+        );
+        assert_eq!(DeviceModel::parse_name("StreamDeckFoo"), None);
+    }
+
+    #[test]
+    fn test_hex_bytes_parse() {
+        let expected = HexBytes(vec![0x03, 0x08, 0x64]);
+        assert_eq!("03 08 64".parse::<HexBytes>().unwrap(), expected);
+        assert_eq!("0x03,0x08,0x64".parse::<HexBytes>().unwrap(), expected);
+        assert_eq!("030864".parse::<HexBytes>().unwrap(), expected);
+        assert_eq!(expected.to_string(), "03 08 64");
+
+        assert!("".parse::<HexBytes>().is_err());
+        assert!("0308 6".parse::<HexBytes>().is_err());
+        assert!("zz".parse::<HexBytes>().is_err());
+    }
+
+    #[test]
+    fn test_device_model_physical() {
+        let xl = DeviceModel::Xl.physical().unwrap();
+        assert!((xl.physical_key_mm - 14.4).abs() < f32::EPSILON);
+        assert!((xl.gap_mm - 3.6).abs() < 0.01);
+        assert!((xl.dpi - 169.0).abs() < f32::EPSILON);
+
+        let mk2 = DeviceModel::Mk2.physical().unwrap();
+        assert!((mk2.gap_mm - 4.8).abs() < 0.01);
+        assert!((mk2.dpi - 126.0).abs() < f32::EPSILON);
+
+        assert!(DeviceModel::Pedal.physical().is_none());
+    }
+
     #[test]
     fn test_device_model_dimensions() {
         assert_eq!(DeviceModel::Mini.key_dimensions(), (72, 72));
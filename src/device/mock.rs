@@ -27,10 +27,13 @@ use std::path::Path;
 use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use std::sync::{Arc, Mutex};
 
+use image::DynamicImage;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use tracing::{debug, trace};
 
 use super::DeviceOperations;
-use super::info::{DeviceInfo, DeviceModel};
+use super::info::{DeviceInfo, DeviceModel, WriteVerification};
 use crate::error::{Result, SdError};
 use crate::image_ops::ResizeStrategy;
 
@@ -44,6 +47,12 @@ pub enum Operation {
         key: u8,
         path: String,
     },
+    /// A pre-rendered image; `digest` is the SHA256 of its pixel bytes.
+    SetKeyRendered {
+        key: u8,
+        path: String,
+        digest: String,
+    },
     ClearKey {
         key: u8,
     },
@@ -78,6 +87,50 @@ pub enum KeyState {
     Color { r: u8, g: u8, b: u8 },
 }
 
+/// Serializable snapshot of what a mock device is displaying.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MockStateDump {
+    /// Product name of the simulated model.
+    pub product: String,
+    /// Number of keys.
+    pub key_count: u8,
+    /// Current brightness (0-100).
+    pub brightness: u8,
+    /// Every key, in index order.
+    pub keys: Vec<MockKeyDump>,
+}
+
+/// One key in a [`MockStateDump`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MockKeyDump {
+    /// Key index (0-based).
+    pub key: u8,
+    /// `clear`, `image` or `color`.
+    pub state: &'static str,
+    /// Image path, for image keys.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// Hex color (`#rrggbb`), for color keys.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+impl MockKeyDump {
+    fn new(key: u8, state: &KeyState) -> Self {
+        let (name, image, color) = match state {
+            KeyState::Clear => ("clear", None, None),
+            KeyState::Image(path) => ("image", Some(path.clone()), None),
+            KeyState::Color { r, g, b } => ("color", None, Some(format!("#{r:02x}{g:02x}{b:02x}"))),
+        };
+        Self {
+            key,
+            state: name,
+            image,
+            color,
+        }
+    }
+}
+
 /// Configuration for mock behavior.
 #[derive(Debug, Clone, Default)]
 pub struct MockConfig {
@@ -87,6 +140,10 @@ pub struct MockConfig {
     pub failing_keys: Vec<u8>,
     /// Initial connection state.
     pub connected: bool,
+    /// Whether written key images can be read back (`--verify-writes`).
+    pub can_verify_writes: bool,
+    /// Keys whose image writes land wrong (read back as cleared).
+    pub corrupt_keys: Vec<u8>,
 }
 
 impl MockConfig {
@@ -110,11 +167,14 @@ pub struct MockDevice {
     keys: Mutex<Vec<KeyState>>,
     button_states: Mutex<Vec<bool>>,
     input_queue: Mutex<VecDeque<(u8, bool)>>,
+    readings: Mutex<VecDeque<Option<Vec<u8>>>>,
     operation_log: Mutex<Vec<Operation>>,
     error_injection: Mutex<Option<SdError>>,
     config: MockConfig,
     op_count: Mutex<usize>,
     connected: AtomicBool,
+    verify_writes: bool,
+    verification: Mutex<WriteVerification>,
 }
 
 impl MockDevice {
@@ -146,11 +206,14 @@ impl MockDevice {
             keys: Mutex::new(keys),
             button_states: Mutex::new(button_states),
             input_queue: Mutex::new(VecDeque::new()),
+            readings: Mutex::new(VecDeque::new()),
             operation_log: Mutex::new(Vec::new()),
             error_injection: Mutex::new(None),
             config: MockConfig::connected(),
             op_count: Mutex::new(0),
             connected: AtomicBool::new(true),
+            verify_writes: false,
+            verification: Mutex::new(WriteVerification::default()),
         }
     }
 
@@ -182,6 +245,15 @@ impl MockDevice {
         self
     }
 
+    /// Verify key image writes by reading them back (`--verify-writes`),
+    /// rewriting once on mismatch. Like a real deck, a mock without
+    /// [`with_read_back`](MockDeviceBuilder::with_read_back) only counts them.
+    #[must_use]
+    pub fn with_verify_writes(mut self, verify: bool) -> Self {
+        self.verify_writes = verify;
+        self
+    }
+
     /// Inject an error for the next operation.
     pub fn inject_error(&self, error: SdError) {
         *self.error_injection.lock().unwrap() = Some(error);
@@ -221,6 +293,43 @@ impl MockDevice {
         queue.push_back((key, false));
     }
 
+    /// Script what a button read reports, in queue order: the keys held down
+    /// at that read, or `None` for a read that times out with no data.
+    pub fn queue_reading(&self, pressed: Option<&[u8]>) {
+        self.readings
+            .lock()
+            .unwrap()
+            .push_back(pressed.map(<[u8]>::to_vec));
+    }
+
+    /// Take the next button reading: a scripted one if queued (`None` for
+    /// no report), else the current states after applying queued events.
+    fn next_reading(&self) -> Option<Vec<bool>> {
+        self.record_op(Operation::ReadButtonStates);
+
+        let mut states = self.button_states.lock().unwrap();
+
+        // A scripted reading replaces the state wholesale
+        if let Some(reading) = self.readings.lock().unwrap().pop_front() {
+            let pressed = reading?;
+            for (key, state) in states.iter_mut().enumerate() {
+                *state = pressed.iter().any(|&p| usize::from(p) == key);
+            }
+            return Some(states.clone());
+        }
+
+        // Process any queued events first
+        let mut queue = self.input_queue.lock().unwrap();
+
+        while let Some((key, pressed)) = queue.pop_front() {
+            if (key as usize) < states.len() {
+                states[key as usize] = pressed;
+            }
+        }
+
+        Some(states.clone())
+    }
+
     /// Set a button's current state.
     pub fn set_button_state(&self, key: u8, pressed: bool) {
         let mut states = self.button_states.lock().unwrap();
@@ -335,6 +444,21 @@ impl MockDevice {
         self.brightness.load(Ordering::SeqCst)
     }
 
+    /// Snapshot the displayed state of every key.
+    #[must_use]
+    pub fn state_dump(&self) -> MockStateDump {
+        let keys = self.keys.lock().unwrap();
+        MockStateDump {
+            product: self.info.product_name.clone(),
+            key_count: self.info.key_count,
+            brightness: self.get_brightness(),
+            keys: (0u8..)
+                .zip(keys.iter())
+                .map(|(key, state)| MockKeyDump::new(key, state))
+                .collect(),
+        }
+    }
+
     /// Clear the operation log for fresh assertions.
     pub fn clear_operations(&self) {
         self.operation_log.lock().unwrap().clear();
@@ -375,6 +499,43 @@ impl MockDevice {
         Ok(())
     }
 
+    /// Write a key image, verifying it when `--verify-writes` is on.
+    fn write_key(&self, key: u8, state: KeyState) -> Result<()> {
+        let slot = usize::from(key);
+        let write = || {
+            self.keys.lock().unwrap()[slot] = if self.config.corrupt_keys.contains(&key) {
+                KeyState::Clear
+            } else {
+                state.clone()
+            };
+        };
+        write();
+        if !self.verify_writes {
+            return Ok(());
+        }
+
+        let mut stats = self.verification.lock().unwrap();
+        stats.writes += 1;
+        if !self.config.can_verify_writes {
+            stats.unverified += 1;
+            return Ok(());
+        }
+        let landed = || self.keys.lock().unwrap()[slot] == state;
+        if !landed() {
+            debug!(key, "Key image didn't read back as written; retrying");
+            write();
+        }
+        if landed() {
+            stats.verified += 1;
+            Ok(())
+        } else {
+            stats.mismatched += 1;
+            Err(SdError::DeviceCommunication(format!(
+                "Key {key} didn't read back as written"
+            )))
+        }
+    }
+
     fn check_key(&self, key: u8) -> Result<()> {
         if self.config.failing_keys.contains(&key) {
             return Err(SdError::DeviceCommunication(format!(
@@ -408,6 +569,15 @@ impl DeviceOperations for MockDevice {
         Ok(())
     }
 
+    fn can_read_brightness(&self) -> bool {
+        true
+    }
+
+    fn read_brightness(&self) -> Result<u8> {
+        self.check_error()?;
+        Ok(self.get_brightness())
+    }
+
     fn set_key_image(&self, key: u8, path: &Path, _resize: ResizeStrategy) -> Result<()> {
         self.check_error()?;
         self.check_key(key)?;
@@ -417,10 +587,20 @@ impl DeviceOperations for MockDevice {
             path: path.display().to_string(),
         });
 
-        let mut keys = self.keys.lock().unwrap();
-        keys[key as usize] = KeyState::Image(path.display().to_string());
+        self.write_key(key, KeyState::Image(path.display().to_string()))
+    }
 
-        Ok(())
+    fn set_key_rendered(&self, key: u8, image: &DynamicImage, source: &Path) -> Result<()> {
+        self.check_error()?;
+        self.check_key(key)?;
+
+        self.record_op(Operation::SetKeyRendered {
+            key,
+            path: source.display().to_string(),
+            digest: hex::encode(Sha256::digest(image.as_bytes())),
+        });
+
+        self.write_key(key, KeyState::Image(source.display().to_string()))
     }
 
     fn clear_key(&self, key: u8) -> Result<()> {
@@ -454,10 +634,7 @@ impl DeviceOperations for MockDevice {
         let (r, g, b) = color;
         self.record_op(Operation::FillKeyColor { key, r, g, b });
 
-        let mut keys = self.keys.lock().unwrap();
-        keys[key as usize] = KeyState::Color { r, g, b };
-
-        Ok(())
+        self.write_key(key, KeyState::Color { r, g, b })
     }
 
     fn fill_all_keys_color(&self, color: (u8, u8, u8)) -> Result<()> {
@@ -466,28 +643,29 @@ impl DeviceOperations for MockDevice {
         let (r, g, b) = color;
         self.record_op(Operation::FillAllKeysColor { r, g, b });
 
-        let mut keys = self.keys.lock().unwrap();
-        for key in keys.iter_mut() {
-            *key = KeyState::Color { r, g, b };
+        for key in 0..self.info.key_count {
+            self.write_key(key, KeyState::Color { r, g, b })?;
         }
 
         Ok(())
     }
 
-    fn read_button_states(&self) -> Vec<bool> {
-        self.record_op(Operation::ReadButtonStates);
-
-        // Process any queued events first
-        let mut states = self.button_states.lock().unwrap();
-        let mut queue = self.input_queue.lock().unwrap();
+    fn write_verification(&self) -> Option<WriteVerification> {
+        self.verify_writes.then(|| WriteVerification {
+            supported: self.config.can_verify_writes,
+            ..*self.verification.lock().unwrap()
+        })
+    }
 
-        while let Some((key, pressed)) = queue.pop_front() {
-            if (key as usize) < states.len() {
-                states[key as usize] = pressed;
-            }
-        }
+    fn read_button_states(&self) -> Vec<bool> {
+        // A scripted silent read means no report: all keys released
+        self.next_reading()
+            .unwrap_or_else(|| vec![false; self.button_states.lock().unwrap().len()])
+    }
 
-        states.clone()
+    fn poll_button_states(&self) -> Result<Option<Vec<bool>>> {
+        self.check_error()?;
+        Ok(self.next_reading())
     }
 
     fn watch_buttons(&self, json_output: bool, once: bool, timeout_secs: u64) -> Result<()> {
@@ -551,6 +729,21 @@ impl MockDeviceBuilder {
         self
     }
 
+    /// Set keys whose image writes land wrong.
+    #[must_use]
+    pub fn with_corrupt_keys(mut self, keys: Vec<u8>) -> Self {
+        self.config.corrupt_keys = keys;
+        self
+    }
+
+    /// Model a device that can read key images back, which no real
+    /// Stream Deck can.
+    #[must_use]
+    pub fn with_read_back(mut self) -> Self {
+        self.config.can_verify_writes = true;
+        self
+    }
+
     /// Create device in disconnected state.
     #[must_use]
     pub fn disconnected(mut self) -> Self {
@@ -606,6 +799,14 @@ mod tests {
         mock.assert_operations(&[Operation::SetBrightness { level: 50 }]);
     }
 
+    #[test]
+    fn test_read_brightness() {
+        let mock = MockDevice::xl();
+        assert!(mock.can_read_brightness());
+        mock.set_brightness(35).unwrap();
+        assert_eq!(mock.read_brightness().unwrap(), 35);
+    }
+
     #[test]
     fn test_set_key_image() {
         let mock = MockDevice::xl();
@@ -619,6 +820,31 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_state_dump() {
+        let mock = MockDevice::mini();
+        mock.set_brightness(40).unwrap();
+        mock.fill_key_color(0, (255, 0, 0)).unwrap();
+        mock.set_key_image(1, Path::new("/icons/a.png"), ResizeStrategy::Fit)
+            .unwrap();
+
+        let dump = mock.state_dump();
+        assert_eq!(dump.key_count, 6);
+        assert_eq!(dump.brightness, 40);
+        assert_eq!(dump.keys.len(), 6);
+
+        let json = serde_json::to_value(&dump).unwrap();
+        assert_eq!(
+            json["keys"][0],
+            serde_json::json!({"key": 0, "state": "color", "color": "#ff0000"})
+        );
+        assert_eq!(json["keys"][1]["image"], "/icons/a.png");
+        assert_eq!(
+            json["keys"][5],
+            serde_json::json!({"key": 5, "state": "clear"})
+        );
+    }
+
     #[test]
     fn test_clear_key() {
         let mock = MockDevice::xl();
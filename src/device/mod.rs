@@ -7,16 +7,25 @@ mod info;
 pub mod mock;
 mod real;
 
-pub use info::{ButtonEvent, ConnectionOptions, DeviceInfo, DeviceModel};
+pub use info::{
+    BrightnessReading, BrightnessSource, ButtonEvent, ConnectionOptions, DeviceInfo, DeviceModel,
+    HexBytes, HidDump, HidInterface, HidReportSize, HidRoundTrip, PhysicalDimensions,
+    RawReportKind, UnknownDevice, WriteVerification, hid_report_sizes, jittered,
+};
 pub use real::{
-    Device, clear_all_keys, clear_key, fill_all_keys_color, fill_key_color, get_device_info,
-    list_devices, open_device, open_device_with_retry, read_button_states, set_brightness,
-    set_key_image, watch_buttons,
+    Device, DeviceListing, capture_hid, clear_all_keys, clear_key, fill_all_keys_color,
+    fill_key_color, get_device_info, list_devices, list_devices_within, open_device,
+    open_device_with_retry, poll_button_states, read_button_states, send_raw_report,
+    set_brightness, set_key_image, set_key_image_with_size, set_key_images, set_key_rendered,
+    watch_buttons,
 };
 
 use std::path::Path;
+use std::time::{Duration, Instant};
+
+use image::DynamicImage;
 
-use crate::error::Result;
+use crate::error::{Result, SdError};
 use crate::image_ops::ResizeStrategy;
 
 /// Core device operations trait.
@@ -50,6 +59,27 @@ pub trait DeviceOperations {
     /// if there's a communication failure.
     fn set_brightness(&self, level: u8) -> Result<()>;
 
+    /// Whether [`read_brightness`](Self::read_brightness) can query the device.
+    ///
+    /// Stream Deck firmware has no brightness read-back, so real devices
+    /// report `false` and callers fall back to the session's tracked value.
+    fn can_read_brightness(&self) -> bool {
+        false
+    }
+
+    /// Read the current display brightness (0-100) from the device.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend can't read brightness (see
+    /// [`can_read_brightness`](Self::can_read_brightness)) or there's a
+    /// communication failure.
+    fn read_brightness(&self) -> Result<u8> {
+        Err(SdError::DeviceCommunication(
+            "This device doesn't report its brightness".to_string(),
+        ))
+    }
+
     /// Set a key's image from a file path.
     ///
     /// The image will be loaded, converted, and resized to match
@@ -62,6 +92,31 @@ pub trait DeviceOperations {
     /// a communication failure.
     fn set_key_image(&self, key: u8, path: &Path, resize: ResizeStrategy) -> Result<()>;
 
+    /// Render an image file the way [`set_key_image`](Self::set_key_image)
+    /// would, without uploading it.
+    ///
+    /// Pair with [`set_key_rendered`](Self::set_key_rendered) to decode and
+    /// resize a shared icon once for several keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file doesn't exist or the image format
+    /// is unsupported.
+    fn render_key_image(&self, path: &Path, resize: ResizeStrategy) -> Result<DynamicImage> {
+        crate::image_cache::load_for_key(path, self.info().key_size(), resize)
+    }
+
+    /// Upload an image already rendered by
+    /// [`render_key_image`](Self::render_key_image).
+    ///
+    /// `source` is the file it was rendered from, kept for bookkeeping.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key index is out of range or
+    /// there's a communication failure.
+    fn set_key_rendered(&self, key: u8, image: &DynamicImage, source: &Path) -> Result<()>;
+
     /// Clear a single key (set to black).
     ///
     /// # Errors
@@ -97,12 +152,29 @@ pub trait DeviceOperations {
     /// Returns an error if there's a communication failure.
     fn fill_all_keys_color(&self, color: (u8, u8, u8)) -> Result<()>;
 
+    /// Verification stats for the key images written so far, or `None`
+    /// unless `--verify-writes` was asked for.
+    fn write_verification(&self) -> Option<WriteVerification>;
+
     /// Read button states (non-blocking with timeout).
     ///
     /// Returns a vector of booleans where each index corresponds
     /// to a key, and the value indicates if the key is pressed.
     fn read_button_states(&self) -> Vec<bool>;
 
+    /// Read button states once, telling "no report" apart from "all released".
+    ///
+    /// Returns `Ok(None)` if no button report arrived before the timeout.
+    /// The device only reports when a button changes, so `None` means
+    /// nothing changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the read itself fails (e.g. the device is gone).
+    fn poll_button_states(&self) -> Result<Option<Vec<bool>>> {
+        Ok(Some(self.read_button_states()))
+    }
+
     /// Watch for button presses and invoke callback.
     ///
     /// This is a blocking operation that will loop until:
@@ -152,3 +224,113 @@ pub fn open_boxed_device_with_retry(
 ) -> Result<BoxedDevice> {
     Ok(Box::new(open_device_with_retry(serial, opts)?))
 }
+
+/// Result of [`settle_button_states`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettledStates {
+    /// Latest reading.
+    pub states: Vec<bool>,
+    /// Reads made, including the first.
+    pub polls: u32,
+    /// Whether two consecutive reads agreed before the window closed.
+    pub settled: bool,
+}
+
+/// Poll button states until two consecutive readings agree, for up to `window`.
+///
+/// The device only reports changes, so a poll with no report counts as
+/// agreeing with the reading before it; an idle deck settles at once.
+///
+/// # Errors
+///
+/// Returns an error if a poll fails.
+pub fn settle_button_states(
+    device: &dyn DeviceOperations,
+    window: Duration,
+) -> Result<SettledStates> {
+    let deadline = Instant::now() + window;
+    let mut states = device.read_button_states();
+    let mut polls = 1;
+    let settled = loop {
+        let next = device.poll_button_states()?;
+        polls += 1;
+        match next {
+            None => break true,
+            Some(next) if next == states => break true,
+            Some(next) => states = next,
+        }
+        if Instant::now() >= deadline {
+            break false;
+        }
+    };
+    Ok(SettledStates {
+        states,
+        polls,
+        settled,
+    })
+}
+
+/// Brightness control from two keys, as run by `demo dimmer`.
+///
+/// Each new press of `dim_key` or `brighten_key` moves the brightness by
+/// `step` percent, clamped to 0-100. Holding a key doesn't repeat.
+#[derive(Debug, Clone)]
+pub struct Dimmer {
+    dim_key: u8,
+    brighten_key: u8,
+    step: u8,
+    level: u8,
+    last_states: Vec<bool>,
+}
+
+impl Dimmer {
+    /// Start at `level`; keys already down in `states` count as held.
+    #[must_use]
+    pub const fn new(
+        dim_key: u8,
+        brighten_key: u8,
+        step: u8,
+        level: u8,
+        states: Vec<bool>,
+    ) -> Self {
+        Self {
+            dim_key,
+            brighten_key,
+            step,
+            level,
+            last_states: states,
+        }
+    }
+
+    /// The brightness last set (or the starting level).
+    #[must_use]
+    pub const fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// Read the buttons once and apply any new press.
+    ///
+    /// Returns the new level when the brightness changed.
+    pub fn poll(&mut self, device: &dyn DeviceOperations) -> Result<Option<u8>> {
+        let states = device.read_button_states();
+        let pressed = |key: u8| {
+            let key = usize::from(key);
+            states.get(key).copied().unwrap_or(false)
+                && !self.last_states.get(key).copied().unwrap_or(false)
+        };
+        let next = if pressed(self.dim_key) {
+            self.level.saturating_sub(self.step)
+        } else if pressed(self.brighten_key) {
+            self.level.saturating_add(self.step).min(100)
+        } else {
+            self.level
+        };
+        self.last_states = states;
+        if next == self.level {
+            return Ok(None);
+        }
+        device.set_brightness(next)?;
+        self.level = next;
+        Ok(Some(next))
+    }
+}
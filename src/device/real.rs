@@ -4,6 +4,7 @@
 //! the concrete device implementation.
 
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
 
 use elgato_streamdeck::info::Kind;
@@ -11,14 +12,134 @@ use elgato_streamdeck::{StreamDeck, StreamDeckInput};
 use tracing::{debug, error, info, trace, warn};
 
 use super::DeviceOperations;
-use super::info::{ButtonEvent, ConnectionOptions, DeviceInfo};
+use super::info::{
+    ButtonEvent, ConnectionOptions, DeviceInfo, HexBytes, HidDump, HidInterface, HidRoundTrip,
+    RawReportKind, UnknownDevice, WriteVerification, hid_report_sizes, jittered,
+};
+use crate::cancel::CancelToken;
 use crate::error::{Result, SdError};
-use crate::image_ops::ResizeStrategy;
+use crate::image_ops::{KeySize, ResizeStrategy};
+use crate::key_numbering::LayoutRotation;
+
+/// Elgato's USB vendor ID, shared by every Stream Deck model.
+const ELGATO_VENDOR_ID: u16 = 0x0fd9;
 
 /// Real Stream Deck device wrapper.
 pub struct Device {
     inner: StreamDeck,
     info: DeviceInfo,
+    rotation: LayoutRotation,
+    rotate_images: bool,
+    sharpen: u8,
+    /// `--verify-writes` was asked for.
+    verify_writes: bool,
+    /// Key images sent, for the verification stats.
+    image_writes: AtomicUsize,
+}
+
+impl Device {
+    /// Remap keys for a deck mounted turned by `rotation` (`--rotate-layout`).
+    ///
+    /// Key 0 becomes the key that is top-left as mounted, for writes and
+    /// button reads alike. With `rotate_images`, uploaded images are turned
+    /// to match so they appear upright.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device's key grid can't be turned that way.
+    pub fn with_layout_rotation(
+        mut self,
+        rotation: LayoutRotation,
+        rotate_images: bool,
+    ) -> Result<Self> {
+        rotation.check(self.info.cols, self.info.rows)?;
+        if rotation != LayoutRotation::None {
+            debug!(
+                degrees = rotation.degrees(),
+                rotate_images, "Rotating key layout"
+            );
+        }
+        self.rotation = rotation;
+        self.rotate_images = rotate_images;
+        Ok(self)
+    }
+
+    /// Sharpen image files by `amount` (0-100) after resizing (`--sharpen`).
+    pub fn with_sharpen(mut self, amount: u8) -> Self {
+        self.sharpen = amount;
+        self
+    }
+
+    /// Whether written key images can be checked (`--verify-writes`).
+    ///
+    /// No Stream Deck model acknowledges an image write or sends a key's
+    /// image back over HID, so there is nothing to compare against and this
+    /// is false for every model.
+    #[must_use]
+    #[allow(clippy::unused_self)] // A method so per-model support can be added
+    pub const fn can_verify_writes(&self) -> bool {
+        false
+    }
+
+    /// Verify key image writes where the device supports it (`--verify-writes`).
+    ///
+    /// On devices that can't (see [`can_verify_writes`](Self::can_verify_writes))
+    /// writes stay unverified and are only counted; see
+    /// [`write_verification`](DeviceOperations::write_verification).
+    #[must_use]
+    pub fn with_verify_writes(mut self, verify: bool) -> Self {
+        if verify && !self.can_verify_writes() {
+            debug!(
+                product = %self.info.product_name,
+                "Write verification isn't supported by this device; writes are unverified"
+            );
+        }
+        self.verify_writes = verify;
+        self
+    }
+
+    /// Send one key image (not flushed), counting it for the verification stats.
+    fn write_key_image(&self, physical_key: u8, img: image::DynamicImage) -> Result<()> {
+        self.inner
+            .set_button_image(physical_key, img)
+            .map_err(|e| SdError::DeviceCommunication(e.to_string()))?;
+        self.image_writes.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// The device's own index for a key numbered in the mounted layout.
+    const fn physical_key(&self, key: u8) -> u8 {
+        self.rotation
+            .physical_key(key, self.info.cols, self.info.rows)
+    }
+
+    /// Reorder button states from the device's order to the mounted layout.
+    fn mounted_states(&self, states: Vec<bool>) -> Vec<bool> {
+        if self.rotation == LayoutRotation::None {
+            return states;
+        }
+        (0..self.info.key_count)
+            .map(|key| {
+                states
+                    .get(usize::from(self.physical_key(key)))
+                    .copied()
+                    .unwrap_or(false)
+            })
+            .collect()
+    }
+
+    /// Turn an image so it appears upright on the mounted deck.
+    fn upright(&self, img: image::DynamicImage) -> image::DynamicImage {
+        if !self.rotate_images {
+            return img;
+        }
+        match self.rotation {
+            LayoutRotation::None => img,
+            LayoutRotation::Quarter => img.rotate270(),
+            LayoutRotation::Half => img.rotate180(),
+            LayoutRotation::ThreeQuarters => img.rotate90(),
+        }
+    }
 }
 
 impl DeviceOperations for Device {
@@ -40,6 +161,14 @@ impl DeviceOperations for Device {
         set_key_image(self, key, path, resize)
     }
 
+    fn render_key_image(&self, path: &Path, resize: ResizeStrategy) -> Result<image::DynamicImage> {
+        render_key_image(self, path, resize, self.info.key_size())
+    }
+
+    fn set_key_rendered(&self, key: u8, image: &image::DynamicImage, _source: &Path) -> Result<()> {
+        set_key_rendered(self, key, image)
+    }
+
     fn clear_key(&self, key: u8) -> Result<()> {
         clear_key(self, key)
     }
@@ -56,39 +185,186 @@ impl DeviceOperations for Device {
         fill_all_keys_color(self, color)
     }
 
+    fn write_verification(&self) -> Option<WriteVerification> {
+        let writes = self.image_writes.load(Ordering::Relaxed);
+        self.verify_writes.then_some(WriteVerification {
+            supported: self.can_verify_writes(),
+            writes,
+            unverified: writes,
+            ..WriteVerification::default()
+        })
+    }
+
     fn read_button_states(&self) -> Vec<bool> {
         read_button_states(self)
     }
 
+    fn poll_button_states(&self) -> Result<Option<Vec<bool>>> {
+        poll_button_states(self)
+    }
+
     fn watch_buttons(&self, json_output: bool, once: bool, timeout_secs: u64) -> Result<()> {
         watch_buttons(self, json_output, once, timeout_secs)
     }
 }
 
-/// List all connected Stream Deck devices.
+/// Devices found by [`list_devices_within`].
+#[derive(Debug, Clone, Default)]
+pub struct DeviceListing {
+    /// Devices sorted by serial.
+    pub devices: Vec<DeviceInfo>,
+    /// Serials whose firmware query didn't finish in time (listed without it).
+    pub timed_out: Vec<String>,
+    /// Elgato devices that aren't a supported Stream Deck model.
+    pub unsupported: Vec<UnknownDevice>,
+}
+
+/// List all connected Stream Deck devices, sorted by serial.
 pub fn list_devices() -> Result<Vec<DeviceInfo>> {
+    let mut devices = enumerate_devices()?;
+    sort_devices(&mut devices);
+    Ok(devices)
+}
+
+/// List devices, giving up after `timeout`.
+///
+/// Enumeration runs on a worker thread so a wedged HID stack can't hang the
+/// caller. With `query_firmware`, each device is opened on its own thread to
+/// read its firmware version; devices that don't answer before the deadline
+/// are still listed, without firmware, and reported in `timed_out`.
+///
+/// # Errors
+///
+/// Returns an error if the HID layer fails or enumeration itself times out.
+pub fn list_devices_within(timeout: Duration, query_firmware: bool) -> Result<DeviceListing> {
+    let deadline = Instant::now() + timeout;
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || tx.send(enumerate_all()).ok());
+    let (mut devices, unsupported) = rx.recv_timeout(timeout).map_err(|_| {
+        SdError::DeviceCommunication(format!(
+            "Device enumeration timed out after {} ms",
+            timeout.as_millis()
+        ))
+    })??;
+    sort_devices(&mut devices);
+
+    let mut timed_out = Vec::new();
+    if query_firmware && !devices.is_empty() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        for (index, device) in devices.iter().enumerate() {
+            let tx = tx.clone();
+            let serial = device.serial.clone();
+            std::thread::spawn(move || {
+                let firmware = open_device(Some(&serial)).map(|d| d.info.firmware_version);
+                tx.send((index, firmware)).ok();
+            });
+        }
+        drop(tx);
+
+        let mut answered = vec![false; devices.len()];
+        while answered.contains(&false) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let Ok((index, firmware)) = rx.recv_timeout(remaining) else {
+                break;
+            };
+            answered[index] = true;
+            match firmware {
+                Ok(version) => devices[index].firmware_version = version,
+                Err(e) => {
+                    debug!(serial = %devices[index].serial, error = %e, "Firmware query failed")
+                }
+            }
+        }
+
+        for (device, _) in devices.iter().zip(&answered).filter(|(_, done)| !**done) {
+            warn!(serial = %device.serial, "Device query timed out");
+            timed_out.push(device.serial.clone());
+        }
+    }
+
+    Ok(DeviceListing {
+        devices,
+        timed_out,
+        unsupported,
+    })
+}
+
+/// Keep `list` output stable across runs; HID enumeration order isn't.
+fn sort_devices(devices: &mut [DeviceInfo]) {
+    devices.sort_by(|a, b| a.serial.cmp(&b.serial).then_with(|| a.kind.cmp(&b.kind)));
+}
+
+fn enumerate_devices() -> Result<Vec<DeviceInfo>> {
+    enumerate_all().map(|(devices, _)| devices)
+}
+
+/// Supported devices, plus any other Elgato devices on the bus.
+fn enumerate_all() -> Result<(Vec<DeviceInfo>, Vec<UnknownDevice>)> {
     let hid =
         elgato_streamdeck::new_hidapi().map_err(|e| SdError::DeviceCommunication(e.to_string()))?;
 
     let devices = elgato_streamdeck::list_devices(&hid);
+    let unsupported = unknown_devices(&hid, &devices);
 
-    let mut result = Vec::new();
-    for (kind, serial) in devices {
-        let image_format = kind.key_image_format();
-        result.push(DeviceInfo {
-            serial,
-            product_name: kind_to_name(kind),
-            firmware_version: String::new(), // Need to open device to get this
-            key_count: kind.key_count(),
-            key_width: image_format.size.0,
-            key_height: image_format.size.1,
-            rows: kind.row_count(),
-            cols: kind.column_count(),
-            kind: format!("{kind:?}"),
-        });
+    // Firmware needs the device opened, so it's left empty here
+    let result = devices
+        .into_iter()
+        .map(|(kind, serial)| model_info(kind, serial, String::new()))
+        .collect();
+
+    Ok((result, unsupported))
+}
+
+/// Describe a device of model `kind`.
+fn model_info(kind: Kind, serial: String, firmware_version: String) -> DeviceInfo {
+    let image_format = kind.key_image_format();
+    DeviceInfo {
+        serial,
+        product_name: kind_to_name(kind),
+        firmware_version,
+        key_count: kind.key_count(),
+        key_width: image_format.size.0,
+        key_height: image_format.size.1,
+        rows: kind.row_count(),
+        cols: kind.column_count(),
+        kind: format!("{kind:?}"),
     }
+}
 
-    Ok(result)
+/// Elgato HID devices that `elgato-streamdeck` didn't recognize as a model.
+///
+/// Each device exposes several HID interfaces, so entries are deduplicated
+/// by product ID and serial.
+fn unknown_devices(hid: &hidapi::HidApi, known: &[(Kind, String)]) -> Vec<UnknownDevice> {
+    let mut unknown: Vec<UnknownDevice> = Vec::new();
+    for entry in hid.device_list() {
+        if entry.vendor_id() != ELGATO_VENDOR_ID {
+            continue;
+        }
+        let serial = entry.serial_number().unwrap_or_default();
+        if known.iter().any(|(_, s)| s == serial) {
+            continue;
+        }
+        let device = UnknownDevice::new(
+            entry.serial_number(),
+            entry.product_string(),
+            entry.vendor_id(),
+            entry.product_id(),
+        );
+        if !unknown
+            .iter()
+            .any(|u| u.product_id == device.product_id && u.serial == device.serial)
+        {
+            debug!(usb_id = %device.usb_id(), serial = %device.serial, "Unsupported Elgato device");
+            unknown.push(device);
+        }
+    }
+    unknown.sort_by(|a, b| {
+        a.serial
+            .cmp(&b.serial)
+            .then(a.product_id.cmp(&b.product_id))
+    });
+    unknown
 }
 
 /// Open a Stream Deck device, optionally by serial number.
@@ -98,8 +374,17 @@ pub fn open_device(serial: Option<&str>) -> Result<Device> {
 
     let devices = elgato_streamdeck::list_devices(&hid);
 
+    // An unsupported device that was asked for (or is all there is) gets a
+    // clear error rather than "not found"
+    let unsupported_error = || {
+        unknown_devices(&hid, &devices)
+            .iter()
+            .find(|u| serial.is_none_or(|serial| u.serial == serial))
+            .map(UnknownDevice::to_error)
+    };
+
     if devices.is_empty() {
-        return Err(SdError::NoDevicesFound);
+        return Err(unsupported_error().unwrap_or(SdError::NoDevicesFound));
     }
 
     // Find the target device
@@ -108,8 +393,10 @@ pub fn open_device(serial: Option<&str>) -> Result<Device> {
             .iter()
             .find(|(_, s)| s == serial)
             .cloned()
-            .ok_or_else(|| SdError::DeviceNotFound {
-                serial: serial.to_string(),
+            .ok_or_else(|| {
+                unsupported_error().unwrap_or_else(|| SdError::DeviceNotFound {
+                    serial: serial.to_string(),
+                })
             })?
     } else if devices.len() == 1 {
         devices[0].clone()
@@ -130,20 +417,17 @@ pub fn open_device(serial: Option<&str>) -> Result<Device> {
         .firmware_version()
         .unwrap_or_else(|_| "unknown".to_string());
 
-    let image_format = kind.key_image_format();
-    let info = DeviceInfo {
-        serial: target_serial,
-        product_name: kind_to_name(kind),
-        firmware_version: firmware,
-        key_count: kind.key_count(),
-        key_width: image_format.size.0,
-        key_height: image_format.size.1,
-        rows: kind.row_count(),
-        cols: kind.column_count(),
-        kind: format!("{kind:?}"),
-    };
-
-    Ok(Device { inner, info })
+    let info = model_info(kind, target_serial, firmware);
+
+    Ok(Device {
+        inner,
+        info,
+        rotation: LayoutRotation::None,
+        rotate_images: false,
+        sharpen: 0,
+        verify_writes: false,
+        image_writes: AtomicUsize::new(0),
+    })
 }
 
 /// Open a Stream Deck device with retry/backoff options.
@@ -168,7 +452,7 @@ pub fn open_device_with_retry(serial: Option<&str>, opts: &ConnectionOptions) ->
                     delay_ms = delay.as_millis(),
                     "Connection failed, retrying"
                 );
-                std::thread::sleep(delay);
+                std::thread::sleep(jittered(delay, opts.jitter));
                 let next_delay_secs =
                     (delay.as_secs_f32() * opts.backoff_factor).min(opts.max_delay.as_secs_f32());
                 delay = Duration::from_secs_f32(next_delay_secs);
@@ -196,8 +480,232 @@ pub fn set_brightness(device: &Device, level: u8) -> Result<()> {
         .map_err(|e| SdError::DeviceCommunication(e.to_string()))
 }
 
+/// Send a raw HID report, bypassing all validation (`sd raw`).
+///
+/// For protocol debugging only: the bytes go to the device as-is, the first
+/// byte being the report ID. If `read_len` is set, the feature report with
+/// the same ID is read back afterwards and returned.
+pub fn send_raw_report(
+    device: &Device,
+    kind: RawReportKind,
+    report: &[u8],
+    read_len: Option<usize>,
+) -> Result<Option<Vec<u8>>> {
+    warn!(
+        serial = %device.info.serial,
+        ?kind,
+        bytes = %HexBytes(report.to_vec()),
+        "Sending raw HID report"
+    );
+
+    match kind {
+        RawReportKind::Feature => device
+            .inner
+            .send_feature_report(report)
+            .map_err(|e| SdError::DeviceCommunication(e.to_string()))?,
+        RawReportKind::Output => {
+            device
+                .inner
+                .write_data(report)
+                .map_err(|e| SdError::DeviceCommunication(e.to_string()))?;
+        }
+    }
+
+    let Some(len) = read_len else {
+        return Ok(None);
+    };
+    let report_id = report.first().copied().unwrap_or(0);
+    let response = device
+        .inner
+        .get_feature_report(report_id, len)
+        .map_err(|e| SdError::DeviceCommunication(e.to_string()))?;
+    debug!(report_id, bytes = %HexBytes(response.clone()), "Raw HID response");
+    Ok(Some(response))
+}
+
+/// Feature reports read during a HID capture: the serial and firmware
+/// reports of both protocol generations, and their neighbours.
+const CAPTURE_FEATURE_REPORTS: [u8; 5] = [0x03, 0x04, 0x05, 0x06, 0x07];
+
+/// Record the raw HID details of a device, for bug reports (`--dump-hid`).
+///
+/// Captures every Elgato interface on the bus (IDs, release, usage, path),
+/// then opens one device's interface directly, supported model or not, and
+/// reads its report descriptor, a few feature reports (serial and firmware,
+/// for both protocol generations) and one input report. Nothing is written to
+/// the device, so a capture never changes what it shows.
+///
+/// # Errors
+///
+/// Returns an error if the HID layer fails, no Elgato device is connected,
+/// or several are and `serial` doesn't pick one.
+pub fn capture_hid(serial: Option<&str>) -> Result<HidDump> {
+    let hid =
+        elgato_streamdeck::new_hidapi().map_err(|e| SdError::DeviceCommunication(e.to_string()))?;
+    let known = elgato_streamdeck::list_devices(&hid);
+
+    let mut entries: Vec<&hidapi::DeviceInfo> = hid
+        .device_list()
+        .filter(|entry| entry.vendor_id() == ELGATO_VENDOR_ID)
+        .collect();
+    entries.sort_by(|a, b| {
+        (a.serial_number(), a.interface_number()).cmp(&(b.serial_number(), b.interface_number()))
+    });
+    let interfaces: Vec<HidInterface> = entries
+        .iter()
+        .map(|entry| {
+            let serial = entry.serial_number().unwrap_or_default().to_string();
+            HidInterface {
+                vendor_id: entry.vendor_id(),
+                product_id: entry.product_id(),
+                supported: known.iter().any(|(_, s)| *s == serial),
+                serial,
+                product_name: entry.product_string().map(str::to_string),
+                manufacturer: entry.manufacturer_string().map(str::to_string),
+                release_number: entry.release_number(),
+                interface_number: entry.interface_number(),
+                usage_page: entry.usage_page(),
+                usage: entry.usage(),
+                path: entry.path().to_string_lossy().into_owned(),
+            }
+        })
+        .collect();
+
+    let mut serials: Vec<&str> = entries
+        .iter()
+        .map(|entry| entry.serial_number().unwrap_or_default())
+        .collect();
+    serials.dedup();
+    let target = match serial {
+        Some(serial) => entries
+            .iter()
+            .find(|entry| entry.serial_number() == Some(serial))
+            .ok_or_else(|| SdError::DeviceNotFound {
+                serial: serial.to_string(),
+            })?,
+        None if serials.len() > 1 => {
+            return Err(SdError::MultipleDevices {
+                serials: serials.iter().map(|s| (*s).to_string()).collect(),
+            });
+        }
+        None => entries.first().ok_or(SdError::NoDevicesFound)?,
+    };
+    let target_serial = target.serial_number().unwrap_or_default();
+    debug!(
+        serial = target_serial,
+        product_id = target.product_id(),
+        "Capturing HID details"
+    );
+
+    let mut dump = HidDump {
+        interfaces,
+        device: known
+            .iter()
+            .find(|(_, s)| s == target_serial)
+            .map(|(kind, s)| model_info(*kind, s.clone(), String::new())),
+        open_error: None,
+        report_descriptor: None,
+        descriptor_error: None,
+        report_sizes: Vec::new(),
+        round_trips: Vec::new(),
+    };
+
+    let handle = match hid.open_path(target.path()) {
+        Ok(handle) => handle,
+        Err(e) => {
+            warn!(error = %e, "Couldn't open device for HID capture");
+            dump.open_error = Some(e.to_string());
+            return Ok(dump);
+        }
+    };
+
+    let mut descriptor = vec![0u8; 4096];
+    match handle.get_report_descriptor(&mut descriptor) {
+        Ok(len) => {
+            descriptor.truncate(len);
+            dump.report_sizes = hid_report_sizes(&descriptor);
+            dump.report_descriptor = Some(HexBytes(descriptor).to_string());
+        }
+        Err(e) => dump.descriptor_error = Some(e.to_string()),
+    }
+
+    // Buffers fit the declared report (plus its ID byte), or a generous default
+    let report_len = |kind: &str, report_id: u8, default: usize| {
+        dump.report_sizes
+            .iter()
+            .find(|size| size.kind == kind && size.report_id == report_id)
+            .and_then(|size| usize::try_from(size.bytes).ok())
+            .map_or(default, |bytes| bytes + 1)
+    };
+    let mut round_trips = Vec::new();
+    for report_id in CAPTURE_FEATURE_REPORTS {
+        let mut buf = vec![0u8; report_len("feature", report_id, 32)];
+        buf[0] = report_id;
+        let started = Instant::now();
+        let result = handle.get_feature_report(&mut buf);
+        round_trips.push(round_trip(
+            format!("get_feature_report 0x{report_id:02x}"),
+            started,
+            result.map(|len| buf[..len].to_vec()),
+        ));
+    }
+    let input_id = dump
+        .report_sizes
+        .iter()
+        .find(|size| size.kind == "input")
+        .map_or(0, |size| size.report_id);
+    let mut buf = vec![0u8; report_len("input", input_id, 1024)];
+    let started = Instant::now();
+    let result = handle.read_timeout(&mut buf, 100);
+    round_trips.push(round_trip(
+        "read_input_report (100 ms timeout)".to_string(),
+        started,
+        result.map(|len| buf[..len].to_vec()),
+    ));
+    dump.round_trips = round_trips;
+
+    Ok(dump)
+}
+
+/// Record one capture request and its outcome.
+fn round_trip(
+    request: String,
+    started: Instant,
+    result: hidapi::HidResult<Vec<u8>>,
+) -> HidRoundTrip {
+    let micros = u64::try_from(started.elapsed().as_micros()).unwrap_or(u64::MAX);
+    trace!(%request, micros, "HID capture request");
+    match result {
+        Ok(bytes) => HidRoundTrip {
+            request,
+            response: Some(HexBytes(bytes).to_string()),
+            error: None,
+            micros,
+        },
+        Err(e) => HidRoundTrip {
+            request,
+            response: None,
+            error: Some(e.to_string()),
+            micros,
+        },
+    }
+}
+
 /// Set a key's image from a file.
 pub fn set_key_image(device: &Device, key: u8, path: &Path, resize: ResizeStrategy) -> Result<()> {
+    set_key_image_with_size(device, key, path, resize, device.info.key_size())
+}
+
+/// Set a key's image from a file, rendering at an explicit key size.
+///
+/// Used by `--key-size` to override the device-derived dimensions.
+pub fn set_key_image_with_size(
+    device: &Device,
+    key: u8,
+    path: &Path,
+    resize: ResizeStrategy,
+    size: KeySize,
+) -> Result<()> {
     if key >= device.info.key_count {
         return Err(SdError::InvalidKeyIndex {
             index: key,
@@ -206,19 +714,51 @@ pub fn set_key_image(device: &Device, key: u8, path: &Path, resize: ResizeStrate
         });
     }
 
-    let resized = crate::image_ops::load_and_resize(
-        path,
-        device.info.key_width as u32,
-        device.info.key_height as u32,
-        resize,
-    )?;
+    if size != device.info.key_size() {
+        debug!(
+            requested = %size,
+            native = %device.info.key_size(),
+            "Rendering at overridden key size"
+        );
+    }
+
+    let resized = render_key_image(device, path, resize, size)?;
+    device.write_key_image(device.physical_key(key), device.upright(resized))?;
 
+    // Flush changes to device
     device
         .inner
-        .set_button_image(key, resized)
-        .map_err(|e| SdError::DeviceCommunication(e.to_string()))?;
+        .flush()
+        .map_err(|e| SdError::DeviceCommunication(e.to_string()))
+}
 
-    // Flush changes to device
+/// Load and resize an image for this device's keys, sharpened if configured.
+fn render_key_image(
+    device: &Device,
+    path: &Path,
+    resize: ResizeStrategy,
+    size: KeySize,
+) -> Result<image::DynamicImage> {
+    let resized = crate::image_cache::load_for_key(path, size, resize)?;
+    if device.sharpen > 0 {
+        return Ok(crate::image_ops::sharpen(&resized, device.sharpen));
+    }
+    Ok(resized)
+}
+
+/// Upload an image already rendered for this device's keys.
+///
+/// Lets `apply --dedupe-images` render a repeated icon once.
+pub fn set_key_rendered(device: &Device, key: u8, image: &image::DynamicImage) -> Result<()> {
+    if key >= device.info.key_count {
+        return Err(SdError::InvalidKeyIndex {
+            index: key,
+            max: device.info.key_count,
+            max_idx: device.info.key_count - 1,
+        });
+    }
+
+    device.write_key_image(device.physical_key(key), device.upright(image.clone()))?;
     device
         .inner
         .flush()
@@ -237,7 +777,7 @@ pub fn clear_key(device: &Device, key: u8) -> Result<()> {
 
     device
         .inner
-        .clear_button_image(key)
+        .clear_button_image(device.physical_key(key))
         .map_err(|e| SdError::DeviceCommunication(e.to_string()))?;
 
     device
@@ -275,10 +815,10 @@ pub fn fill_key_color(device: &Device, key: u8, color: (u8, u8, u8)) -> Result<(
         *pixel = image::Rgb([color.0, color.1, color.2]);
     }
 
-    device
-        .inner
-        .set_button_image(key, image::DynamicImage::ImageRgb8(img))
-        .map_err(|e| SdError::DeviceCommunication(e.to_string()))?;
+    device.write_key_image(
+        device.physical_key(key),
+        image::DynamicImage::ImageRgb8(img),
+    )?;
 
     device
         .inner
@@ -297,10 +837,44 @@ pub fn fill_all_keys_color(device: &Device, color: (u8, u8, u8)) -> Result<()> {
             *pixel = image::Rgb([color.0, color.1, color.2]);
         }
 
-        device
-            .inner
-            .set_button_image(key, image::DynamicImage::ImageRgb8(img))
-            .map_err(|e| SdError::DeviceCommunication(e.to_string()))?;
+        device.write_key_image(key, image::DynamicImage::ImageRgb8(img))?;
+    }
+
+    // Flush all changes at once
+    device
+        .inner
+        .flush()
+        .map_err(|e| SdError::DeviceCommunication(e.to_string()))
+}
+
+/// Upload pre-rendered images to several keys with a single flush.
+///
+/// Stops between keys if `cancel` is triggered, flushing the keys already
+/// written and returning [`SdError::Cancelled`].
+pub fn set_key_images(
+    device: &Device,
+    images: Vec<(u8, image::DynamicImage)>,
+    cancel: &CancelToken,
+) -> Result<()> {
+    for (key, img) in images {
+        if cancel.is_cancelled() {
+            // Show what was uploaded so far, then report the cancellation
+            device
+                .inner
+                .flush()
+                .map_err(|e| SdError::DeviceCommunication(e.to_string()))?;
+            return Err(SdError::Cancelled);
+        }
+
+        if key >= device.info.key_count {
+            return Err(SdError::InvalidKeyIndex {
+                index: key,
+                max: device.info.key_count,
+                max_idx: device.info.key_count - 1,
+            });
+        }
+
+        device.write_key_image(device.physical_key(key), device.upright(img))?;
     }
 
     // Flush all changes at once
@@ -338,7 +912,7 @@ pub fn watch_buttons(
         if let Ok(StreamDeckInput::ButtonStateChange(states)) =
             device.inner.read_input(read_timeout)
         {
-            for (key, pressed) in states.iter().enumerate() {
+            for (key, pressed) in device.mounted_states(states).iter().enumerate() {
                 if *pressed {
                     #[allow(clippy::cast_possible_truncation)] // Key count is always < 256
                     let event = ButtonEvent {
@@ -366,23 +940,33 @@ pub fn watch_buttons(
 
 /// Read current button states once.
 pub fn read_button_states(device: &Device) -> Vec<bool> {
-    let read_timeout = Some(Duration::from_millis(100));
     let default = || vec![false; device.info.key_count as usize];
 
-    device
-        .inner
-        .read_input(read_timeout)
+    poll_button_states(device)
         .ok()
-        .and_then(|input| {
-            if let StreamDeckInput::ButtonStateChange(states) = input {
-                Some(states)
-            } else {
-                None
-            }
-        })
+        .flatten()
         .unwrap_or_else(default)
 }
 
+/// Read button states once, returning `None` when no button report arrives
+/// within the read timeout.
+///
+/// # Errors
+///
+/// Returns an error if the HID read fails.
+pub fn poll_button_states(device: &Device) -> Result<Option<Vec<bool>>> {
+    let read_timeout = Some(Duration::from_millis(100));
+
+    let input = device
+        .inner
+        .read_input(read_timeout)
+        .map_err(|e| SdError::DeviceCommunication(e.to_string()))?;
+    Ok(match input {
+        StreamDeckInput::ButtonStateChange(states) => Some(device.mounted_states(states)),
+        _ => None,
+    })
+}
+
 /// Convert device kind to human-readable name.
 #[allow(clippy::missing_const_for_fn)] // Returns String which requires allocation
 fn kind_to_name(kind: Kind) -> String {
@@ -13,9 +13,22 @@ pub enum SdError {
     #[error("Device not found: {serial}")]
     DeviceNotFound { serial: String },
 
+    #[error("Pinned device {serial} (from {path}) is not connected")]
+    PinnedDeviceNotConnected { serial: String, path: String },
+
     #[error("Multiple devices found, specify --serial: {serials:?}")]
     MultipleDevices { serials: Vec<String> },
 
+    #[error(
+        "Unsupported Elgato device '{product}' (USB ID {vendor_id:04x}:{product_id:04x}, serial {serial})"
+    )]
+    UnsupportedDevice {
+        product: String,
+        serial: String,
+        vendor_id: u16,
+        product_id: u16,
+    },
+
     #[error("Failed to open device '{serial}': {reason}")]
     DeviceOpenFailed { serial: String, reason: String },
 
@@ -42,10 +55,16 @@ pub enum SdError {
     #[error("Unsupported image format: {0}")]
     ImageFormat(String),
 
+    #[error("Icon '{name}' not found in icon themes: {themes}")]
+    IconNotFound { name: String, themes: String },
+
     // Key errors
     #[error("Invalid key index {index}: device has {max} keys (0-{max_idx})")]
     InvalidKeyIndex { index: u8, max: u8, max_idx: u8 },
 
+    #[error("Pattern '{pattern}' has no {{index}} placeholder, so it matches no files")]
+    InvalidPattern { pattern: String },
+
     // Configuration errors
     #[error("Configuration file not found: {path}")]
     ConfigNotFound { path: String },
@@ -56,6 +75,9 @@ pub enum SdError {
     #[error("Invalid configuration: {0}")]
     ConfigInvalid(String),
 
+    #[error("Undefined environment variable '{name}' in path: {path}")]
+    UndefinedVariable { name: String, path: String },
+
     #[error("Invalid brightness value {value}: must be 0-100")]
     InvalidBrightness { value: u8 },
 
@@ -63,10 +85,19 @@ pub enum SdError {
     #[error("Web server failed to start on {addr}: {reason}")]
     WebServerFailed { addr: String, reason: String },
 
+    #[error("No activity tracker is running")]
+    NoActivityTracker,
+
     // General errors
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
+    #[error("Operation cancelled")]
+    Cancelled,
+
+    #[error("No button pressed within {timeout_secs}s")]
+    WatchTimedOut { timeout_secs: u64 },
+
     #[error("{0}")]
     Other(String),
 }
@@ -79,6 +110,7 @@ impl SdError {
             self,
             Self::NoDevicesFound
                 | Self::DeviceNotFound { .. }
+                | Self::PinnedDeviceNotConnected { .. }
                 | Self::DeviceOpenFailed { .. }
                 | Self::DeviceCommunication(_)
         )
@@ -93,6 +125,7 @@ impl SdError {
                 | Self::ImageProcessing(_)
                 | Self::ImageNotFound { .. }
                 | Self::ImageFormat(_)
+                | Self::IconNotFound { .. }
         )
     }
 
@@ -101,7 +134,10 @@ impl SdError {
     pub const fn is_config_error(&self) -> bool {
         matches!(
             self,
-            Self::ConfigNotFound { .. } | Self::ConfigParse(_) | Self::ConfigInvalid(_)
+            Self::ConfigNotFound { .. }
+                | Self::ConfigParse(_)
+                | Self::ConfigInvalid(_)
+                | Self::UndefinedVariable { .. }
         )
     }
 
@@ -117,32 +153,212 @@ impl SdError {
             self,
             Self::NoDevicesFound
                 | Self::DeviceNotFound { .. }
+                | Self::PinnedDeviceNotConnected { .. }
                 | Self::MultipleDevices { .. }
                 | Self::InvalidKeyIndex { .. }
+                | Self::InvalidPattern { .. }
                 | Self::InvalidBrightness { .. }
                 | Self::ImageNotFound { .. }
                 | Self::ImageFormat(_)
+                | Self::IconNotFound { .. }
                 | Self::ConfigNotFound { .. }
                 | Self::ConfigInvalid(_)
+                | Self::UndefinedVariable { .. }
+                | Self::NoActivityTracker
         )
     }
 
+    /// Process exit code for the error: 1, except 124 when `watch --once
+    /// --timeout` saw no press (matching `timeout(1)`).
+    pub const fn exit_code(&self) -> i32 {
+        match self {
+            Self::WatchTimedOut { .. } => 124,
+            _ => 1,
+        }
+    }
+
     /// Returns a suggestion for how to fix the error.
     pub const fn suggestion(&self) -> Option<&'static str> {
         match self {
             Self::NoDevicesFound => Some("Ensure Stream Deck is connected via USB"),
             Self::MultipleDevices { .. } => Some("Use --serial to specify which device"),
+            Self::PinnedDeviceNotConnected { .. } => {
+                Some("Reconnect the device, or delete the serial file to pin another one")
+            }
             Self::InvalidBrightness { .. } => Some("Use a value between 0 and 100"),
+            Self::InvalidPattern { .. } => Some(
+                "Include {index} where the key number goes, e.g. --pattern \"key-{index}.png\" \
+                 or --pattern \"icon-{index:02d}.png\" for zero-padded names",
+            ),
             Self::ConfigNotFound { .. } => Some("Run: sd init"),
             Self::ImageFormat { .. } | Self::ImageFormat(_) => {
-                Some("Use a supported image format: png, jpg, jpeg, gif, bmp, webp")
+                Some("Use a supported image format: png, jpg, jpeg, gif, bmp, webp, ico, svg")
             }
             Self::ConfigInvalid { .. } | Self::ConfigInvalid(_) => {
                 Some("Check configuration values for validity")
             }
+            Self::UndefinedVariable { .. } => {
+                Some("Export the variable before running sd, e.g. ICON_DIR=~/icons sd apply ...")
+            }
+            Self::NoActivityTracker => Some("Start one in another terminal: sd watch"),
+            Self::UnsupportedDevice { .. } => {
+                Some("Include the USB ID from `sd list` when reporting the model")
+            }
+            Self::IconNotFound { .. } => {
+                Some("Use the icon name without extension, or set SD_ICON_THEME")
+            }
             _ => None,
         }
     }
+
+    /// Returns an extended explanation of the error kind (`--explain`):
+    /// likely causes, common fixes and where to read more.
+    pub const fn explanation(&self) -> Option<&'static str> {
+        match self {
+            Self::NoDevicesFound => Some(
+                "sd found no Stream Deck on any USB HID interface. Common causes: the \
+                 device is unplugged or on an unpowered hub, the Elgato app or another \
+                 program holds it exclusively, or (on Linux) your user lacks permission \
+                 to open /dev/hidraw* until a udev rule for vendor 0fd9 is installed. \
+                 Run `sd list` after replugging to confirm it is visible.",
+            ),
+            Self::DeviceNotFound { .. } => Some(
+                "A serial was given with --serial or SD_SERIAL, but no connected device \
+                 has it. Serials are case-sensitive; copy one exactly from `sd list`. \
+                 An environment variable left over from another machine is a common \
+                 culprit.",
+            ),
+            Self::PinnedDeviceNotConnected { .. } => Some(
+                "--serial-file pins the device whose serial is stored in that file. The \
+                 pinned device is not connected right now. Reconnect it, or delete the \
+                 file so the next run picks (and pins) whichever device is present.",
+            ),
+            Self::MultipleDevices { .. } => Some(
+                "With more than one Stream Deck connected, sd won't guess which to \
+                 change. Select one with --serial <SERIAL> (or SD_SERIAL), or pin one \
+                 across runs with --serial-file <PATH>. `sd list` shows every serial.",
+            ),
+            Self::UnsupportedDevice { .. } => Some(
+                "The device is an Elgato product, but not a Stream Deck model sd knows \
+                 the key layout and image format for, so it can't be driven safely. \
+                 `sd list` shows its USB vendor and product IDs; newer models are \
+                 added as the underlying driver learns them.",
+            ),
+            Self::DeviceOpenFailed { .. } => Some(
+                "The device was found but could not be opened. Usually another process \
+                 (the Elgato app, a running `sd watch`, another automation) has it open, \
+                 or the OS denied access. Close the other program or fix permissions, \
+                 then retry; --retry can ride out brief contention.",
+            ),
+            Self::DeviceCommunication(_) => Some(
+                "A USB transfer to the device failed mid-command. This happens when the \
+                 device is unplugged, the hub resets, or the system suspends. Replug \
+                 and rerun; `sd watch --reconnect` survives these automatically.",
+            ),
+            Self::InvalidImageDimensions { .. } => Some(
+                "The image doesn't have the exact pixel size this operation needs. Most \
+                 commands resize automatically; this error comes from paths that require \
+                 pre-sized images. `sd info` shows the device's key size.",
+            ),
+            Self::ImageProcessing(_) | Self::ImageFormat(_) => Some(
+                "The image could not be decoded or converted. Check that the file is a \
+                 real png, jpg, gif, bmp, webp or ico (not just renamed) and isn't \
+                 truncated; re-exporting it from an image editor usually fixes this.",
+            ),
+            Self::ImageNotFound { .. } => Some(
+                "The image path doesn't exist. Relative paths resolve from the current \
+                 directory on the command line, but from the config file's directory in \
+                 `sd apply` configs (see docs/DECLARATIVE_CONFIG.md#path-resolution).",
+            ),
+            Self::IconNotFound { .. } => Some(
+                "`icon:NAME` looks NAME up in the freedesktop icon theme: the desktop's \
+                 theme (or SD_ICON_THEME), the themes it inherits from, then hicolor and \
+                 /usr/share/pixmaps. NAME is the file name without extension, as used \
+                 by .desktop files (e.g. `firefox`, `utilities-terminal`).",
+            ),
+            Self::InvalidKeyIndex { .. } => Some(
+                "Keys are numbered from 0, left to right and top to bottom, so the last \
+                 key is one less than the key count. Different models have different \
+                 counts; `sd info` shows this device's layout.",
+            ),
+            Self::InvalidPattern { .. } => Some(
+                "set-keys maps files to keys by the number in their name. The pattern \
+                 marks where that number sits with {index} (0, 1, 2...), {index:02d} \
+                 (00, 01...) or {index:03d}; the rest of the pattern must match the file \
+                 name exactly. Without a placeholder no file can match.",
+            ),
+            Self::ConfigNotFound { .. } => Some(
+                "The configuration file doesn't exist at that path. `sd init` creates the \
+                 default layout under ~/.config/sd/; otherwise pass the path to an \
+                 existing file.",
+            ),
+            Self::ConfigParse(_) | Self::ConfigInvalid(_) => Some(
+                "The configuration was read but is malformed or has invalid values: a \
+                 bad selector, brightness above 100, a pattern without {index}, or an \
+                 unknown color. `sd validate <FILE>` lists every problem at once (see \
+                 docs/DECLARATIVE_CONFIG.md#validation-rules).",
+            ),
+            Self::UndefinedVariable { .. } => Some(
+                "Config paths may use ${VAR} placeholders, which are expanded from the \
+                 environment when the config is applied. The named variable isn't set \
+                 in this shell; export it or replace the placeholder with a path.",
+            ),
+            Self::InvalidBrightness { .. } => {
+                Some("Brightness is a percentage from 0 (off) to 100 (full).")
+            }
+            Self::WebServerFailed { .. } => Some(
+                "The server couldn't bind its address, usually because the port is \
+                 already in use or binding needs privileges. Choose another --port or \
+                 stop the other process.",
+            ),
+            Self::NoActivityTracker => Some(
+                "`sd idle` reports time since the last key press as recorded by a \
+                 running `sd watch`. No watch has recorded activity recently, so there \
+                 is nothing to report. Keep `sd watch` running in the background.",
+            ),
+            Self::Cancelled => Some(
+                "The operation was interrupted (Ctrl+C). Batch commands stop between \
+                 keys, so keys already written keep their new content.",
+            ),
+            Self::WatchTimedOut { .. } => Some(
+                "`watch --once --timeout` waits for a single press and gives up when the \
+                 timeout passes, exiting with 124 like timeout(1) so scripts can tell \
+                 \"nobody pressed\" apart from a failure.",
+            ),
+            Self::Io(_) | Self::Other(_) => None,
+        }
+    }
+
+    /// Returns a stable, machine-readable code for the error kind.
+    pub const fn code(&self) -> &'static str {
+        match self {
+            Self::NoDevicesFound => "no_devices_found",
+            Self::DeviceNotFound { .. } => "device_not_found",
+            Self::PinnedDeviceNotConnected { .. } => "pinned_device_not_connected",
+            Self::MultipleDevices { .. } => "multiple_devices",
+            Self::UnsupportedDevice { .. } => "unsupported_device",
+            Self::DeviceOpenFailed { .. } => "device_open_failed",
+            Self::DeviceCommunication(_) => "device_communication",
+            Self::InvalidImageDimensions { .. } => "invalid_image_dimensions",
+            Self::ImageProcessing(_) => "image_processing",
+            Self::ImageNotFound { .. } => "image_not_found",
+            Self::ImageFormat(_) => "image_format",
+            Self::IconNotFound { .. } => "icon_not_found",
+            Self::InvalidKeyIndex { .. } => "invalid_key_index",
+            Self::InvalidPattern { .. } => "invalid_pattern",
+            Self::ConfigNotFound { .. } => "config_not_found",
+            Self::ConfigParse(_) => "config_parse",
+            Self::ConfigInvalid(_) => "config_invalid",
+            Self::UndefinedVariable { .. } => "undefined_variable",
+            Self::InvalidBrightness { .. } => "invalid_brightness",
+            Self::WebServerFailed { .. } => "web_server_failed",
+            Self::NoActivityTracker => "no_activity_tracker",
+            Self::Io(_) => "io",
+            Self::Cancelled => "cancelled",
+            Self::WatchTimedOut { .. } => "watch_timed_out",
+            Self::Other(_) => "other",
+        }
+    }
 }
 
 /// Convenience type alias for Results using `SdError`.
@@ -188,9 +404,23 @@ mod tests {
             .is_connection_error()
         );
         assert!(SdError::DeviceCommunication("hid".to_string()).is_connection_error());
+        assert!(
+            SdError::PinnedDeviceNotConnected {
+                serial: "abc".to_string(),
+                path: "/tmp/sd-serial".to_string()
+            }
+            .is_connection_error()
+        );
         assert!(!SdError::InvalidBrightness { value: 101 }.is_connection_error());
     }
 
+    #[test]
+    fn test_exit_code_distinguishes_watch_timeout() {
+        assert_eq!(SdError::WatchTimedOut { timeout_secs: 5 }.exit_code(), 124);
+        assert_eq!(SdError::NoDevicesFound.exit_code(), 1);
+        assert_eq!(SdError::Cancelled.exit_code(), 1);
+    }
+
     #[test]
     fn test_image_error_classification() {
         assert!(
@@ -229,6 +459,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_recoverable_errors_have_explanations() {
+        let errors = [
+            SdError::NoDevicesFound,
+            SdError::MultipleDevices {
+                serials: vec!["A".to_string(), "B".to_string()],
+            },
+            SdError::InvalidKeyIndex {
+                index: 40,
+                max: 32,
+                max_idx: 31,
+            },
+            SdError::ConfigInvalid("bad".to_string()),
+            SdError::NoActivityTracker,
+        ];
+        for err in &errors {
+            assert!(err.is_user_recoverable());
+            assert!(err.explanation().is_some_and(|e| e.len() > 40), "{err:?}");
+        }
+        assert!(
+            SdError::MultipleDevices { serials: vec![] }
+                .explanation()
+                .unwrap()
+                .contains("--serial")
+        );
+        assert!(SdError::Other("x".to_string()).explanation().is_none());
+    }
+
     #[test]
     fn test_retryable_matches_connection_errors() {
         let err = SdError::DeviceCommunication("hid".to_string());
@@ -0,0 +1,304 @@
+//! Post-apply hooks: `on_apply` in a config, or `apply --after`.
+//!
+//! The command runs through the shell (`sh -c`, or `cmd /V:ON /C` on
+//! Windows) once an apply has written every key to a device. Applies where
+//! a key failed, interrupted applies, `--mock` applies and dry runs run
+//! nothing (see [`skip_reason`]), so there are no failures to report. It
+//! sees the result in its environment:
+//!
+//! ```text
+//! SD_APPLY_CONFIG     path of the config file
+//! SD_APPLY_NAME       profile name, empty if the config has none
+//! SD_APPLY_SERIAL     serial of the device written to
+//! SD_APPLY_KEYS_SET   keys written (also counting keys --replace cleared)
+//! SD_APPLY_TOTAL      keys attempted
+//! ```
+//!
+//! The same values can be written into the command as `{config}`, `{name}`,
+//! `{serial}`, `{keys_set}` and `{total}`. Each stays one harmless argument,
+//! even for a profile named `a; rm -rf ~`; write placeholders bare rather
+//! than inside quotes:
+//!
+//! ```yaml
+//! on_apply: logger -t sd applied {name}: {keys_set} of {total} keys
+//! ```
+//!
+//! On Unix the values are shell-quoted into the command. cmd.exe expands
+//! `%VAR%` even inside quotes, so on Windows a placeholder becomes a
+//! delayed `!SD_APPLY_*!` reference instead, expanded only after the
+//! command is parsed; a literal `!` in a Windows hook must be written `^!`.
+//!
+//! The hook's output goes to stderr, keeping stdout clean for `--robot`.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use serde::Serialize;
+use tracing::{debug, instrument, warn};
+
+use crate::output::template;
+
+/// What an apply did, for the hook.
+#[derive(Debug, Clone)]
+pub struct ApplySummary<'a> {
+    /// Config file that was applied.
+    pub config: &'a Path,
+    /// Profile name from the config.
+    pub name: Option<&'a str>,
+    /// Serial of the device written to.
+    pub serial: &'a str,
+    /// Keys written successfully.
+    pub keys_set: usize,
+    /// Keys that failed.
+    pub errors: usize,
+    /// Keys attempted.
+    pub total: usize,
+}
+
+impl ApplySummary<'_> {
+    /// The summary as `(placeholder, value)` pairs, also used for the
+    /// `SD_APPLY_*` variables. `errors` isn't one: the hook only runs when
+    /// it is zero.
+    fn values(&self) -> [(&'static str, String); 5] {
+        [
+            ("config", self.config.display().to_string()),
+            ("name", self.name.unwrap_or_default().to_string()),
+            ("serial", self.serial.to_string()),
+            ("keys_set", self.keys_set.to_string()),
+            ("total", self.total.to_string()),
+        ]
+    }
+}
+
+/// Why an apply's hook didn't run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookSkip {
+    /// The apply was interrupted.
+    Cancelled,
+    /// One or more keys failed.
+    KeysFailed,
+    /// The apply wrote to a `--mock` device.
+    Mock,
+}
+
+impl HookSkip {
+    /// Reason for messages.
+    #[must_use]
+    pub const fn describe(self) -> &'static str {
+        match self {
+            Self::Cancelled => "the apply was interrupted",
+            Self::KeysFailed => "some keys failed",
+            Self::Mock => "the apply used a mock device",
+        }
+    }
+}
+
+/// Why the hook for `summary` shouldn't run, or `None` if it should.
+#[must_use]
+pub const fn skip_reason(
+    summary: &ApplySummary<'_>,
+    mock: bool,
+    cancelled: bool,
+) -> Option<HookSkip> {
+    if cancelled {
+        Some(HookSkip::Cancelled)
+    } else if summary.errors > 0 {
+        Some(HookSkip::KeysFailed)
+    } else if mock {
+        Some(HookSkip::Mock)
+    } else {
+        None
+    }
+}
+
+/// How a hook run went.
+#[derive(Debug, Clone, Serialize)]
+pub struct HookOutcome {
+    /// The command as run, after substitution.
+    pub command: String,
+    /// Exit code, if the command ran and exited normally.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// True if the command exited with status 0.
+    pub ok: bool,
+    /// Why the command couldn't be started.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Substitute the summary into `template`, each value as one shell word.
+#[must_use]
+pub fn render_command(template: &str, summary: &ApplySummary<'_>) -> String {
+    let values = summary.values();
+    let quoted: Vec<(&str, String)> = values
+        .iter()
+        .map(|(name, value)| (*name, shell_word(name, value)))
+        .collect();
+    let pairs: Vec<(&str, &str)> = quoted
+        .iter()
+        .map(|(name, value)| (*name, value.as_str()))
+        .collect();
+    template::render(template, &pairs)
+}
+
+/// Run the hook `template` for `summary` and wait for it.
+///
+/// Never fails: a command that can't start or exits non-zero is reported in
+/// the [`HookOutcome`], for the caller to warn about or treat as an error.
+#[instrument(skip(summary))]
+pub fn run_apply_hook(template: &str, summary: &ApplySummary<'_>) -> HookOutcome {
+    let command = render_command(template, summary);
+    let mut shell = shell_command(&command);
+    for (name, value) in summary.values() {
+        shell.env(env_name(name), value);
+    }
+    shell.stdin(Stdio::null()).stdout(std::io::stderr());
+
+    match shell.status() {
+        Ok(status) => {
+            debug!(%command, %status, "Apply hook finished");
+            HookOutcome {
+                command,
+                exit_code: status.code(),
+                ok: status.success(),
+                error: None,
+            }
+        }
+        Err(e) => {
+            warn!(%command, error = %e, "Apply hook failed to start");
+            HookOutcome {
+                command,
+                exit_code: None,
+                ok: false,
+                error: Some(e.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    // Delayed expansion, for the `!SD_APPLY_*!` placeholders
+    let mut shell = Command::new("cmd");
+    shell.arg("/V:ON").arg("/C").arg(command);
+    shell
+}
+
+/// The environment variable holding the `name` placeholder's value.
+fn env_name(name: &str) -> String {
+    format!("SD_APPLY_{}", name.to_ascii_uppercase())
+}
+
+/// Quote `value` as one shell word; plain numbers and names stay bare.
+#[cfg(not(windows))]
+fn shell_word(_name: &str, value: &str) -> String {
+    let plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:".contains(c));
+    if plain {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+/// Refer to the `name` placeholder's variable, so cmd.exe never parses the
+/// value itself.
+#[cfg(windows)]
+fn shell_word(name: &str, _value: &str) -> String {
+    format!("\"!{}!\"", env_name(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(name: Option<&str>) -> ApplySummary<'_> {
+        ApplySummary {
+            config: Path::new("work.yaml"),
+            name,
+            serial: "AL12",
+            keys_set: 14,
+            errors: 1,
+            total: 15,
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_render_command_quotes_values() {
+        assert_eq!(
+            render_command("echo {name} {keys_set}/{total}", &summary(Some("Work"))),
+            "echo Work 14/15"
+        );
+        // Failures never reach a hook, so they aren't placeholders
+        assert_eq!(
+            render_command("echo {errors} {ok}", &summary(None)),
+            "echo {errors} {ok}"
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_render_command_refers_to_the_environment() {
+        assert_eq!(
+            render_command("echo {name} 100%", &summary(Some("%PATH% & calc"))),
+            "echo \"!SD_APPLY_NAME!\" 100%"
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_render_command_keeps_values_one_word() {
+        assert_eq!(
+            render_command("echo {name}", &summary(Some("it's; rm -rf ~"))),
+            r"echo 'it'\''s; rm -rf ~'"
+        );
+        assert_eq!(render_command("echo {name}", &summary(None)), "echo ''");
+    }
+
+    #[test]
+    fn test_skip_reason_needs_a_clean_real_apply() {
+        let failed = summary(None);
+        assert_eq!(
+            skip_reason(&failed, false, false),
+            Some(HookSkip::KeysFailed)
+        );
+        assert_eq!(skip_reason(&failed, false, true), Some(HookSkip::Cancelled));
+
+        let clean = ApplySummary {
+            keys_set: 15,
+            errors: 0,
+            ..failed
+        };
+        assert_eq!(skip_reason(&clean, true, false), Some(HookSkip::Mock));
+        assert_eq!(skip_reason(&clean, false, false), None);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_run_apply_hook_sets_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let template = format!(
+            "printf '%s %s' \"$SD_APPLY_KEYS_SET\" \"${{SD_APPLY_ERRORS-unset}}\" > {}",
+            out.display()
+        );
+        let outcome = run_apply_hook(&template, &summary(Some("Work")));
+        assert!(outcome.ok, "{outcome:?}");
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "14 unset");
+
+        let failed = run_apply_hook("exit 3", &summary(None));
+        assert!(!failed.ok);
+        assert_eq!(failed.exit_code, Some(3));
+    }
+}
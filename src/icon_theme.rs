@@ -0,0 +1,360 @@
+//! Freedesktop icon theme lookup for `icon:NAME` images.
+//!
+//! `sd set-key 0 icon:firefox` resolves `firefox` the way desktop launchers
+//! do: the user's icon theme first, then the themes it inherits from, then
+//! `hicolor` and finally `/usr/share/pixmaps`. Scalable (SVG) icons win since
+//! they rasterize sharply at any key size; otherwise the smallest PNG at least
+//! as large as the key is used.
+//!
+//! The theme is taken from `SD_ICON_THEME`, else `gtk-icon-theme-name` in the
+//! GTK settings, else just `hicolor`.
+
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, trace};
+
+use crate::error::{Result, SdError};
+
+/// Prefix that marks an image argument as an icon name.
+pub const ICON_SCHEME: &str = "icon:";
+
+/// Theme every icon theme ultimately falls back to.
+pub const FALLBACK_THEME: &str = "hicolor";
+
+/// How deep to look below a theme directory (`48x48/apps/name.png`).
+const MAX_DEPTH: usize = 3;
+
+/// Returns the icon name if `path` uses the `icon:` scheme.
+#[must_use]
+pub fn icon_name(path: &Path) -> Option<&str> {
+    path.to_str()?
+        .strip_prefix(ICON_SCHEME)
+        .filter(|name| !name.is_empty())
+}
+
+/// Resolve an image argument: `icon:NAME` is looked up in the icon theme,
+/// anything else is returned unchanged.
+///
+/// # Errors
+///
+/// Returns [`SdError::IconNotFound`] if the named icon isn't in any searched
+/// theme.
+pub fn resolve_image(path: &Path, size: u32) -> Result<PathBuf> {
+    match icon_name(path) {
+        Some(name) => IconSearch::from_env().lookup(name, size),
+        None => Ok(path.to_path_buf()),
+    }
+}
+
+/// Where and in which themes to look for icons.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IconSearch {
+    /// Themes in lookup order, ending with `hicolor`.
+    pub themes: Vec<String>,
+    /// Directories holding themes (`~/.icons`, `$XDG_DATA_DIRS/icons`, ...).
+    pub base_dirs: Vec<PathBuf>,
+    /// Unthemed icon directories searched last.
+    pub pixmap_dirs: Vec<PathBuf>,
+}
+
+impl IconSearch {
+    /// Search `theme` and its inherited themes under `base_dirs`.
+    #[must_use]
+    pub fn new(theme: Option<&str>, base_dirs: Vec<PathBuf>, pixmap_dirs: Vec<PathBuf>) -> Self {
+        let mut themes = Vec::new();
+        let mut pending: Vec<String> = theme.map(str::to_string).into_iter().collect();
+        // Breadth-first over Inherits=, skipping cycles
+        while !pending.is_empty() {
+            let name = pending.remove(0);
+            if themes.contains(&name) || name == FALLBACK_THEME {
+                continue;
+            }
+            pending.extend(inherited_themes(&base_dirs, &name));
+            themes.push(name);
+        }
+        themes.push(FALLBACK_THEME.to_string());
+        Self {
+            themes,
+            base_dirs,
+            pixmap_dirs,
+        }
+    }
+
+    /// The user's theme and the standard XDG icon directories.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let theme = std::env::var("SD_ICON_THEME")
+            .ok()
+            .filter(|t| !t.is_empty())
+            .or_else(gtk_icon_theme);
+
+        let mut base_dirs = Vec::new();
+        if let Some(home) = dirs::home_dir() {
+            base_dirs.push(home.join(".icons"));
+        }
+        if let Some(data) = dirs::data_dir() {
+            base_dirs.push(data.join("icons"));
+        }
+        let data_dirs = std::env::var("XDG_DATA_DIRS")
+            .ok()
+            .filter(|d| !d.is_empty())
+            .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+        base_dirs.extend(
+            std::env::split_paths(&data_dirs)
+                .filter(|d| !d.as_os_str().is_empty())
+                .map(|d| d.join("icons")),
+        );
+
+        let search = Self::new(
+            theme.as_deref(),
+            base_dirs,
+            vec![PathBuf::from("/usr/share/pixmaps")],
+        );
+        debug!(themes = ?search.themes, "Icon theme search order");
+        search
+    }
+
+    /// Find the best file for icon `name` at `size` pixels.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SdError::IconNotFound`] listing the searched themes.
+    pub fn lookup(&self, name: &str, size: u32) -> Result<PathBuf> {
+        for theme in &self.themes {
+            let mut candidates = Vec::new();
+            for base in &self.base_dirs {
+                collect_icons(&base.join(theme), name, 0, &mut candidates);
+            }
+            if let Some(best) = best_candidate(candidates, size) {
+                debug!(name, theme = %theme, path = %best.display(), "Resolved icon");
+                return Ok(best);
+            }
+        }
+
+        let mut candidates = Vec::new();
+        for dir in &self.pixmap_dirs {
+            for ext in ["svg", "png"] {
+                let path = dir.join(format!("{name}.{ext}"));
+                if path.is_file() {
+                    candidates.push(Candidate::new(path, None));
+                }
+            }
+        }
+        best_candidate(candidates, size).ok_or_else(|| SdError::IconNotFound {
+            name: name.to_string(),
+            themes: self
+                .themes
+                .iter()
+                .map(String::as_str)
+                .chain((!self.pixmap_dirs.is_empty()).then_some("pixmaps"))
+                .collect::<Vec<_>>()
+                .join(", "),
+        })
+    }
+}
+
+/// An icon file and the nominal size of the directory it was found in.
+#[derive(Debug)]
+struct Candidate {
+    path: PathBuf,
+    scalable: bool,
+    size: Option<u32>,
+}
+
+impl Candidate {
+    fn new(path: PathBuf, size: Option<u32>) -> Self {
+        let scalable = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("svg"));
+        Self {
+            path,
+            scalable,
+            size,
+        }
+    }
+}
+
+/// Prefer SVG, then the smallest PNG at least `size`, then the largest PNG.
+fn best_candidate(mut candidates: Vec<Candidate>, size: u32) -> Option<PathBuf> {
+    candidates.sort_by_key(|c| {
+        let nominal = c.size.unwrap_or(0);
+        (
+            !c.scalable,
+            nominal < size,
+            if nominal < size {
+                u32::MAX - nominal
+            } else {
+                nominal
+            },
+            c.path.clone(),
+        )
+    });
+    candidates.into_iter().next().map(|c| c.path)
+}
+
+/// Walk a theme directory for `name.svg` / `name.png`, remembering the size
+/// encoded in the directory names (`48x48`, `48`, `48x48@2`).
+fn collect_icons(dir: &Path, name: &str, depth: usize, out: &mut Vec<Candidate>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if depth < MAX_DEPTH {
+                collect_icons(&path, name, depth + 1, out);
+            }
+            continue;
+        }
+        let matches = path.file_stem().and_then(|s| s.to_str()) == Some(name)
+            && path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| e.eq_ignore_ascii_case("svg") || e.eq_ignore_ascii_case("png"));
+        if matches {
+            trace!(path = %path.display(), "Icon candidate");
+            let size = path
+                .strip_prefix(dir.ancestors().nth(depth).unwrap_or(dir))
+                .ok()
+                .and_then(|rel| {
+                    rel.components()
+                        .find_map(|c| dir_size(c.as_os_str().to_str()?))
+                });
+            out.push(Candidate::new(path, size));
+        }
+    }
+}
+
+/// Nominal pixel size of an icon directory name, accounting for `@2` scales.
+fn dir_size(component: &str) -> Option<u32> {
+    let (dims, scale) = component
+        .split_once('@')
+        .map_or((component, "1"), |(d, s)| (d, s.trim_end_matches('x')));
+    let edge = dims.split_once('x').map_or(dims, |(w, _)| w);
+    Some(edge.parse::<u32>().ok()? * scale.parse::<u32>().ok()?)
+}
+
+/// Themes listed in `Inherits=` of the first `index.theme` found for `theme`.
+fn inherited_themes(base_dirs: &[PathBuf], theme: &str) -> Vec<String> {
+    base_dirs
+        .iter()
+        .find_map(|base| std::fs::read_to_string(base.join(theme).join("index.theme")).ok())
+        .and_then(|index| {
+            index
+                .lines()
+                .find_map(|line| line.trim().strip_prefix("Inherits=").map(str::to_string))
+        })
+        .map(|list| {
+            list.split(',')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// `gtk-icon-theme-name` from the GTK 4 or GTK 3 settings file.
+fn gtk_icon_theme() -> Option<String> {
+    let config = dirs::config_dir()?;
+    ["gtk-4.0", "gtk-3.0"].iter().find_map(|gtk| {
+        let text = std::fs::read_to_string(config.join(gtk).join("settings.ini")).ok()?;
+        text.lines().find_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            (key.trim() == "gtk-icon-theme-name")
+                .then(|| value.trim().trim_matches('"').to_string())
+                .filter(|v| !v.is_empty())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(path: &Path) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, b"").unwrap();
+    }
+
+    #[test]
+    fn test_icon_name() {
+        assert_eq!(icon_name(Path::new("icon:firefox")), Some("firefox"));
+        assert_eq!(icon_name(Path::new("icon:")), None);
+        assert_eq!(icon_name(Path::new("firefox.png")), None);
+    }
+
+    #[test]
+    fn test_dir_size() {
+        assert_eq!(dir_size("48x48"), Some(48));
+        assert_eq!(dir_size("64"), Some(64));
+        assert_eq!(dir_size("48x48@2"), Some(96));
+        assert_eq!(dir_size("48@2x"), Some(96));
+        assert_eq!(dir_size("scalable"), None);
+        assert_eq!(dir_size("apps"), None);
+    }
+
+    #[test]
+    fn test_lookup_follows_theme_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("icons");
+        std::fs::create_dir_all(base.join("Child")).unwrap();
+        std::fs::write(
+            base.join("Child/index.theme"),
+            "[Icon Theme]\nName=Child\nInherits=Parent,hicolor\n",
+        )
+        .unwrap();
+        touch(&base.join("Parent/48x48/apps/term.png"));
+        touch(&base.join("hicolor/scalable/apps/term.svg"));
+        touch(&base.join("hicolor/32x32/apps/editor.png"));
+        touch(&base.join("hicolor/128x128/apps/editor.png"));
+        touch(&base.join("hicolor/256x256/apps/editor.png"));
+
+        let search = IconSearch::new(Some("Child"), vec![base.clone()], vec![]);
+        assert_eq!(search.themes, ["Child", "Parent", "hicolor"]);
+
+        // The first theme with a match wins, even over an SVG further down
+        assert_eq!(
+            search.lookup("term", 72).unwrap(),
+            base.join("Parent/48x48/apps/term.png")
+        );
+        // Smallest PNG that covers the key
+        assert_eq!(
+            search.lookup("editor", 72).unwrap(),
+            base.join("hicolor/128x128/apps/editor.png")
+        );
+        assert_eq!(
+            search.lookup("editor", 512).unwrap(),
+            base.join("hicolor/256x256/apps/editor.png")
+        );
+
+        match search.lookup("missing", 72) {
+            Err(SdError::IconNotFound { name, themes }) => {
+                assert_eq!(name, "missing");
+                assert_eq!(themes, "Child, Parent, hicolor");
+            }
+            other => panic!("expected IconNotFound, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_lookup_prefers_svg_and_falls_back_to_pixmaps() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("icons");
+        let pixmaps = dir.path().join("pixmaps");
+        touch(&base.join("hicolor/256x256/apps/app.png"));
+        touch(&base.join("hicolor/scalable/apps/app.svg"));
+        touch(&pixmaps.join("legacy.png"));
+
+        let search = IconSearch::new(None, vec![base.clone()], vec![pixmaps.clone()]);
+        assert_eq!(
+            search.lookup("app", 72).unwrap(),
+            base.join("hicolor/scalable/apps/app.svg")
+        );
+        assert_eq!(
+            search.lookup("legacy", 72).unwrap(),
+            pixmaps.join("legacy.png")
+        );
+    }
+}
@@ -0,0 +1,353 @@
+//! Persistent cache of rendered key images.
+//!
+//! Resized key images are stored content-addressably, keyed by the SHA256 of
+//! the source bytes plus the target key size and resize strategy, so pushing
+//! the same icon again (even from a new invocation) skips decode and resize.
+//!
+//! # Directory Structure
+//!
+//! ```text
+//! ~/.local/share/sd/
+//! └── cache/
+//!     └── keys/
+//!         ├── aa/
+//!         │   └── aabbcc...123.png
+//!         └── bb/
+//!             └── bbccdd...456.png
+//! ```
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use image::{DynamicImage, ImageFormat};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::{debug, trace, warn};
+
+use crate::error::{Result, SdError};
+use crate::image_ops::{self, KeySize, ResizeStrategy};
+
+/// Whether [`load_for_key`] consults the cache (cleared by `--no-cache`).
+static CACHE_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Enable or disable the persistent cache for this process.
+pub fn set_enabled(enabled: bool) {
+    CACHE_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns true unless caching was disabled with `--no-cache`.
+#[must_use]
+pub fn is_enabled() -> bool {
+    CACHE_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Result of clearing the cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheCleanStats {
+    /// Number of cached images removed.
+    pub files: usize,
+    /// Total bytes freed.
+    pub bytes: u64,
+}
+
+/// On-disk store of rendered key images.
+#[derive(Debug, Clone)]
+pub struct KeyImageCache {
+    dir: PathBuf,
+}
+
+impl KeyImageCache {
+    /// Use the cache at the standard location.
+    ///
+    /// Location: `~/.local/share/sd/cache/keys/`
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::new(default_key_cache_dir()?))
+    }
+
+    /// Use a cache rooted at `dir` (created lazily on first write).
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Cache root directory.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Compute the cache key for source bytes rendered at `size` with `strategy`.
+    #[must_use]
+    pub fn cache_key(source: &[u8], size: KeySize, strategy: ResizeStrategy) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(source);
+        hasher.update(format!("|{size}|{strategy:?}").as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    /// Storage path for a cache key.
+    ///
+    /// Uses first 2 characters as subdirectory for distribution.
+    #[must_use]
+    pub fn entry_path(&self, key: &str) -> PathBuf {
+        let subdir = &key[0..2.min(key.len())];
+        self.dir.join(subdir).join(format!("{key}.png"))
+    }
+
+    /// Look up a cached image. Unreadable entries are treated as misses.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<DynamicImage> {
+        let path = self.entry_path(key);
+        if !path.exists() {
+            return None;
+        }
+        match image::open(&path) {
+            Ok(img) => Some(img),
+            Err(e) => {
+                debug!(path = %path.display(), error = %e, "Ignoring unreadable cache entry");
+                None
+            }
+        }
+    }
+
+    /// Store a rendered image under `key`.
+    ///
+    /// Writes to a temporary file first so concurrent invocations never
+    /// observe a half-written entry.
+    pub fn put(&self, key: &str, image: &DynamicImage) -> Result<()> {
+        let path = self.entry_path(key);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| SdError::Other(format!("Failed to create cache directory: {e}")))?;
+        }
+
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        image
+            .save_with_format(&tmp, ImageFormat::Png)
+            .map_err(|e| SdError::Other(format!("Failed to write cached image: {e}")))?;
+        std::fs::rename(&tmp, &path)
+            .map_err(|e| SdError::Other(format!("Failed to write cached image: {e}")))?;
+
+        trace!(key, "Cached key image");
+        Ok(())
+    }
+
+    /// Remove every cached image.
+    pub fn clean(&self) -> Result<CacheCleanStats> {
+        let mut stats = CacheCleanStats::default();
+        let Ok(subdirs) = std::fs::read_dir(&self.dir) else {
+            return Ok(stats);
+        };
+
+        for subdir in subdirs.flatten() {
+            let Ok(entries) = std::fs::read_dir(subdir.path()) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_none_or(|ext| ext != "png") {
+                    continue;
+                }
+                let size = entry.metadata().map_or(0, |m| m.len());
+                std::fs::remove_file(&path).map_err(|e| {
+                    SdError::Other(format!("Failed to remove {}: {e}", path.display()))
+                })?;
+                stats.files += 1;
+                stats.bytes += size;
+            }
+            // Leave non-empty directories alone; only tidy up ones we emptied
+            std::fs::remove_dir(subdir.path()).ok();
+        }
+
+        debug!(
+            files = stats.files,
+            bytes = stats.bytes,
+            "Key image cache cleaned"
+        );
+        Ok(stats)
+    }
+}
+
+/// Key images rendered during one run, shared by every key showing the same
+/// source (`apply --dedupe-images`).
+///
+/// Misses still go through [`load_for_key`], so the persistent cache is used
+/// for the first render of each image.
+#[derive(Debug, Default)]
+pub struct RenderedImages {
+    images: HashMap<(PathBuf, KeySize, ResizeStrategy), DynamicImage>,
+    reused: usize,
+}
+
+impl RenderedImages {
+    /// Create an empty set.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the image rendered for `path` at `size` with `strategy`,
+    /// calling `render` only the first time it's asked for.
+    pub fn get_or_render(
+        &mut self,
+        path: &Path,
+        size: KeySize,
+        strategy: ResizeStrategy,
+        render: impl FnOnce() -> Result<DynamicImage>,
+    ) -> Result<&DynamicImage> {
+        match self.images.entry((path.to_path_buf(), size, strategy)) {
+            Entry::Occupied(entry) => {
+                self.reused += 1;
+                trace!(path = %path.display(), "Reusing rendered key image");
+                Ok(entry.into_mut())
+            }
+            Entry::Vacant(entry) => Ok(entry.insert(render()?)),
+        }
+    }
+
+    /// Number of distinct images rendered.
+    #[must_use]
+    pub fn unique(&self) -> usize {
+        self.images.len()
+    }
+
+    /// Number of uploads that reused an already-rendered image.
+    #[must_use]
+    pub const fn reused(&self) -> usize {
+        self.reused
+    }
+}
+
+/// Returns the default key image cache directory.
+///
+/// Location: `~/.local/share/sd/cache/keys/`
+pub fn default_key_cache_dir() -> Result<PathBuf> {
+    let data_dir = dirs::data_local_dir()
+        .ok_or_else(|| SdError::Other("Could not determine local data directory".to_string()))?;
+    Ok(data_dir.join("sd").join("cache").join("keys"))
+}
+
+/// Render an image for a key, going through the persistent cache when enabled.
+///
+/// Cache failures never fail the upload; they fall back to a fresh render.
+pub fn load_for_key(path: &Path, size: KeySize, strategy: ResizeStrategy) -> Result<DynamicImage> {
+    if !is_enabled() {
+        return image_ops::process_for_key(path, size, strategy);
+    }
+    let Ok(cache) = KeyImageCache::open_default() else {
+        return image_ops::process_for_key(path, size, strategy);
+    };
+    load_with_cache(&cache, path, size, strategy)
+}
+
+/// Render an image for a key using a specific cache.
+pub fn load_with_cache(
+    cache: &KeyImageCache,
+    path: &Path,
+    size: KeySize,
+    strategy: ResizeStrategy,
+) -> Result<DynamicImage> {
+    let Ok(source) = std::fs::read(path) else {
+        // Let the normal path produce the proper not-found/format error
+        return image_ops::process_for_key(path, size, strategy);
+    };
+
+    let key = KeyImageCache::cache_key(&source, size, strategy);
+    if let Some(img) = cache.get(&key) {
+        debug!(path = %path.display(), key, "Key image cache hit");
+        return Ok(img);
+    }
+
+    let img = image_ops::process_for_key(path, size, strategy)?;
+    if let Err(e) = cache.put(&key, &img) {
+        warn!(error = %e, "Failed to cache key image");
+    }
+    Ok(img)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn test_cache_key_depends_on_size_and_strategy() {
+        let bytes = b"icon";
+        let size = KeySize::new(72, 72);
+        let key = KeyImageCache::cache_key(bytes, size, ResizeStrategy::Fit);
+
+        assert_eq!(key.len(), 64);
+        assert_eq!(
+            key,
+            KeyImageCache::cache_key(bytes, size, ResizeStrategy::Fit)
+        );
+        assert_ne!(
+            key,
+            KeyImageCache::cache_key(bytes, KeySize::new(96, 96), ResizeStrategy::Fit)
+        );
+        assert_ne!(
+            key,
+            KeyImageCache::cache_key(bytes, size, ResizeStrategy::Fill)
+        );
+        assert_ne!(
+            key,
+            KeyImageCache::cache_key(b"other", size, ResizeStrategy::Fit)
+        );
+    }
+
+    #[test]
+    fn test_load_with_cache_populates_and_hits() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("icon.png");
+        RgbImage::from_pixel(100, 80, Rgb([200, 10, 10]))
+            .save(&source)
+            .unwrap();
+
+        let cache = KeyImageCache::new(dir.path().join("cache"));
+        let size = KeySize::new(72, 72);
+        let first = load_with_cache(&cache, &source, size, ResizeStrategy::Fit).unwrap();
+
+        let key =
+            KeyImageCache::cache_key(&std::fs::read(&source).unwrap(), size, ResizeStrategy::Fit);
+        assert!(cache.entry_path(&key).exists());
+
+        let second = load_with_cache(&cache, &source, size, ResizeStrategy::Fit).unwrap();
+        assert_eq!(first.to_rgb8(), second.to_rgb8());
+
+        let stats = cache.clean().unwrap();
+        assert_eq!(stats.files, 1);
+        assert!(stats.bytes > 0);
+        assert!(!cache.entry_path(&key).exists());
+    }
+
+    #[test]
+    fn test_rendered_images_render_once_per_source() {
+        let size = KeySize::new(72, 72);
+        let solid = |c| DynamicImage::ImageRgb8(RgbImage::from_pixel(72, 72, Rgb([c, c, c])));
+        let mut rendered = RenderedImages::new();
+
+        let first = rendered
+            .get_or_render(Path::new("a.png"), size, ResizeStrategy::Fit, || {
+                Ok(solid(1))
+            })
+            .unwrap()
+            .clone();
+        let again = rendered
+            .get_or_render(Path::new("a.png"), size, ResizeStrategy::Fit, || {
+                panic!("already rendered")
+            })
+            .unwrap();
+        assert_eq!(first.as_bytes(), again.as_bytes());
+
+        // A different strategy is a different image
+        rendered
+            .get_or_render(Path::new("a.png"), size, ResizeStrategy::Fill, || {
+                Ok(solid(2))
+            })
+            .unwrap();
+        assert_eq!(rendered.unique(), 2);
+        assert_eq!(rendered.reused(), 1);
+    }
+}
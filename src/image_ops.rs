@@ -1,14 +1,20 @@
 //! Image processing operations.
 
+use std::fmt;
 use std::path::Path;
+use std::str::FromStr;
 
 use clap::ValueEnum;
 use image::{DynamicImage, GenericImageView};
+use serde::Serialize;
 
+use crate::data_uri;
+use crate::device::DeviceModel;
 use crate::error::{Result, SdError};
 
 /// Strategy for resizing images to match key dimensions.
-#[derive(Debug, Clone, Copy, Default, ValueEnum, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, ValueEnum, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ResizeStrategy {
     /// Fit within key, maintain aspect ratio (may have black bars).
     #[default]
@@ -19,6 +25,70 @@ pub enum ResizeStrategy {
     Stretch,
 }
 
+/// Target pixel dimensions for a rendered key image.
+///
+/// Parsed from `WxH` strings (e.g. `72x72`, `96X96`) for the `--key-size`
+/// override. Both dimensions must be non-zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct KeySize {
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+}
+
+impl KeySize {
+    /// Create a key size from explicit dimensions.
+    #[must_use]
+    pub const fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+}
+
+impl FromStr for KeySize {
+    type Err = SdError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            SdError::ImageProcessing(format!("Invalid key size '{s}': expected WxH (e.g. 72x72)"))
+        };
+
+        let (w, h) = s.trim().split_once(['x', 'X']).ok_or_else(invalid)?;
+        let width: u32 = w.trim().parse().map_err(|_| invalid())?;
+        let height: u32 = h.trim().parse().map_err(|_| invalid())?;
+
+        if width == 0 || height == 0 {
+            return Err(SdError::ImageProcessing(format!(
+                "Invalid key size '{s}': dimensions must be greater than zero"
+            )));
+        }
+
+        Ok(Self { width, height })
+    }
+}
+
+impl fmt::Display for KeySize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// Load an image and render it for a key of the given size.
+///
+/// Thin wrapper over [`load_and_resize`] used when the target size may come
+/// from a `--key-size` override rather than the connected device.
+///
+/// # Errors
+///
+/// Returns an error if the image cannot be loaded.
+pub fn process_for_key(
+    path: &Path,
+    size: KeySize,
+    strategy: ResizeStrategy,
+) -> Result<DynamicImage> {
+    load_and_resize(path, size.width, size.height, strategy)
+}
+
 /// Load an image and resize it according to the specified strategy.
 ///
 /// # Arguments
@@ -37,47 +107,829 @@ pub fn load_and_resize(
     height: u32,
     strategy: ResizeStrategy,
 ) -> Result<DynamicImage> {
-    if !path.exists() {
+    if !path.exists() && data_uri::as_data_uri(path).is_none() {
         return Err(SdError::ImageNotFound {
             path: path.display().to_string(),
         });
     }
 
-    let img = image::open(path).map_err(|e| SdError::ImageProcessing(e.to_string()))?;
+    let img = open_for_size(path, width, height)?;
 
-    let filter = image::imageops::FilterType::Lanczos3;
+    Ok(apply_geometry(
+        &img,
+        resize_geometry(img.dimensions(), width, height, strategy),
+    ))
+}
+
+/// Black borders added around an image, in pixels per edge.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Padding {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+}
+
+/// How a source image is framed onto a key for a given resize strategy.
+///
+/// The source is first scaled to `scaled_width`×`scaled_height`; `fill`
+/// then keeps only `crop` of the scaled image, while `fit` centers it with
+/// `padding`. Shared by the real resize and the dry-run report so both agree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ResizeGeometry {
+    pub scaled_width: u32,
+    pub scaled_height: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crop: Option<CropBox>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub padding: Option<Padding>,
+}
 
-    let resized = match strategy {
+/// Compute the scale, crop, and padding used to render `source` at
+/// `width`×`height` with `strategy`.
+///
+/// Scaling rounds the same way as `image`'s `resize`/`resize_to_fill`, so
+/// results are pixel-identical to the previous direct calls.
+#[must_use]
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)] // Pixel dimensions are small and positive
+pub fn resize_geometry(
+    source: (u32, u32),
+    width: u32,
+    height: u32,
+    strategy: ResizeStrategy,
+) -> ResizeGeometry {
+    let (src_w, src_h) = (source.0.max(1), source.1.max(1));
+    let scaled = |fill: bool| {
+        let wratio = f64::from(width) / f64::from(src_w);
+        let hratio = f64::from(height) / f64::from(src_h);
+        let ratio = if fill {
+            wratio.max(hratio)
+        } else {
+            wratio.min(hratio)
+        };
+        let w = (f64::from(src_w) * ratio).round().max(1.0) as u32;
+        let h = (f64::from(src_h) * ratio).round().max(1.0) as u32;
+        (w, h)
+    };
+
+    match strategy {
         ResizeStrategy::Fit => {
-            // resize() maintains aspect ratio and fits within bounds
-            // We might need to pad it to fill the area if we want strict output size,
-            // but usually returning a smaller image is fine for display drivers
-            // that center it, OR we should pad with black.
-            // The elgato crate expects exact dimensions?
-            // "The image will be loaded, converted, and resized to match the device's key dimensions."
-            // If we return a smaller image, set_button_image might fail or behave oddly.
-            // Let's create a black canvas and paste the resized image on top.
-            let resized = img.resize(width, height, filter).to_rgb8();
-            let mut canvas = image::RgbImage::new(width, height);
-            // Default is black (0,0,0)
-
-            // Center the image
-            let (rw, rh) = resized.dimensions();
-            let x = (width - rw) / 2;
-            let y = (height - rh) / 2;
-
-            image::imageops::overlay(&mut canvas, &resized, x.into(), y.into());
-            image::DynamicImage::ImageRgb8(canvas)
+            let (w, h) = scaled(false);
+            let (w, h) = (w.min(width), h.min(height));
+            let left = (width - w) / 2;
+            let top = (height - h) / 2;
+            ResizeGeometry {
+                scaled_width: w,
+                scaled_height: h,
+                crop: None,
+                padding: Some(Padding {
+                    top,
+                    right: width - w - left,
+                    bottom: height - h - top,
+                    left,
+                }),
+            }
         }
         ResizeStrategy::Fill => {
-            // resize_to_fill() maintains aspect ratio and fills bounds (cropping)
-            img.resize_to_fill(width, height, filter)
+            let (w, h) = scaled(true);
+            let (w, h) = (w.max(width), h.max(height));
+            let crop = if u64::from(width) * u64::from(h) > u64::from(w) * u64::from(height) {
+                CropBox {
+                    x: 0,
+                    y: (h - height) / 2,
+                    width,
+                    height,
+                }
+            } else {
+                CropBox {
+                    x: (w - width) / 2,
+                    y: 0,
+                    width,
+                    height,
+                }
+            };
+            ResizeGeometry {
+                scaled_width: w,
+                scaled_height: h,
+                crop: Some(crop),
+                padding: None,
+            }
+        }
+        ResizeStrategy::Stretch => ResizeGeometry {
+            scaled_width: width,
+            scaled_height: height,
+            crop: None,
+            padding: None,
+        },
+    }
+}
+
+/// Render an image using precomputed [`ResizeGeometry`].
+#[must_use]
+pub fn apply_geometry(img: &DynamicImage, geometry: ResizeGeometry) -> DynamicImage {
+    let filter = image::imageops::FilterType::Lanczos3;
+    let scaled = img.resize_exact(geometry.scaled_width, geometry.scaled_height, filter);
+
+    if let Some(crop) = geometry.crop {
+        return scaled.crop_imm(crop.x, crop.y, crop.width, crop.height);
+    }
+    if let Some(pad) = geometry.padding {
+        // Center on a black canvas so the output is always the exact key size
+        let width = pad.left + geometry.scaled_width + pad.right;
+        let height = pad.top + geometry.scaled_height + pad.bottom;
+        let mut canvas = image::RgbImage::new(width, height);
+        image::imageops::overlay(
+            &mut canvas,
+            &scaled.to_rgb8(),
+            pad.left.into(),
+            pad.top.into(),
+        );
+        return DynamicImage::ImageRgb8(canvas);
+    }
+    scaled
+}
+
+/// Open an image, picking the best frame of multi-size `.ico` files.
+///
+/// For icons this decodes the smallest embedded frame that is at least
+/// `width`×`height` (or the largest frame if none is big enough), so keys are
+/// downscaled from a sharp source instead of whatever frame the decoder picks.
+/// SVGs are rasterized to fit `width`×`height`. Other formats are opened
+/// normally. A `data:` URI in place of the path is decoded from memory.
+///
+/// # Errors
+///
+/// Returns an error if the image cannot be read or decoded.
+pub fn open_for_size(path: &Path, width: u32, height: u32) -> Result<DynamicImage> {
+    if let Some(uri) = data_uri::as_data_uri(path) {
+        let data = data_uri::parse(uri)?;
+        return decode_for_size(&data.bytes, data.format, width, height).map_err(|e| match e {
+            SdError::ImageProcessing(reason) => SdError::ImageProcessing(format!(
+                "Couldn't decode the data URI's {} image: {reason}",
+                data.format
+            )),
+            other => other,
+        });
+    }
+
+    let bytes = std::fs::read(path).map_err(|e| SdError::ImageProcessing(e.to_string()))?;
+    let format = if has_extension(path, &["ico"]) {
+        "ico"
+    } else if has_extension(path, &["svg", "svgz"]) {
+        "svg"
+    } else {
+        ""
+    };
+    decode_for_size(&bytes, format, width, height)
+}
+
+/// Decode image bytes whose format (`ico`, `svg`, or anything else to
+/// sniff) is known from the file extension or media type.
+fn decode_for_size(bytes: &[u8], format: &str, width: u32, height: u32) -> Result<DynamicImage> {
+    if format == "ico" || bytes.starts_with(&ICO_MAGIC) {
+        return decode_ico_frame(bytes, width, height);
+    }
+    if format == "svg" {
+        return rasterize_svg(bytes, width, height);
+    }
+    image::load_from_memory(bytes).map_err(|e| SdError::ImageProcessing(e.to_string()))
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| extensions.iter().any(|x| e.eq_ignore_ascii_case(x)))
+}
+
+/// Rasterize an SVG (or gzipped `.svgz`) to fit within `width`×`height`,
+/// keeping its aspect ratio.
+///
+/// # Errors
+///
+/// Returns an error if the document can't be parsed.
+pub fn rasterize_svg(bytes: &[u8], width: u32, height: u32) -> Result<DynamicImage> {
+    use resvg::{tiny_skia, usvg};
+
+    let tree = usvg::Tree::from_data(bytes, &usvg::Options::default())
+        .map_err(|e| SdError::ImageFormat(format!("Invalid SVG: {e}")))?;
+    let size = tree.size();
+    #[allow(clippy::cast_precision_loss)]
+    let scale = (width as f32 / size.width()).min(height as f32 / size.height());
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let (w, h) = (
+        ((size.width() * scale).round() as u32).max(1),
+        ((size.height() * scale).round() as u32).max(1),
+    );
+    let mut pixmap = tiny_skia::Pixmap::new(w, h)
+        .ok_or_else(|| SdError::ImageProcessing(format!("Cannot rasterize SVG at {w}x{h}")))?;
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    // Round-trip through PNG to un-premultiply the alpha
+    let png = pixmap
+        .encode_png()
+        .map_err(|e| SdError::ImageProcessing(e.to_string()))?;
+    image::load_from_memory(&png).map_err(|e| SdError::ImageProcessing(e.to_string()))
+}
+
+/// `.ico` header: reserved (0) followed by type 1 (icon), little-endian.
+const ICO_MAGIC: [u8; 4] = [0, 0, 1, 0];
+const ICO_HEADER_LEN: usize = 6;
+const ICO_ENTRY_LEN: usize = 16;
+
+/// One image in an `.ico` directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IcoFrame {
+    pub width: u32,
+    pub height: u32,
+    offset: usize,
+    len: usize,
+}
+
+/// List the frames in an `.ico` file's directory.
+///
+/// # Errors
+///
+/// Returns an error if the header or directory is malformed.
+pub fn ico_frames(bytes: &[u8]) -> Result<Vec<IcoFrame>> {
+    let malformed = |what: &str| SdError::ImageFormat(format!("Malformed .ico file: {what}"));
+    if bytes.len() < ICO_HEADER_LEN || !bytes.starts_with(&ICO_MAGIC) {
+        return Err(malformed("bad header"));
+    }
+
+    let count = usize::from(u16::from_le_bytes([bytes[4], bytes[5]]));
+    (0..count)
+        .map(|i| {
+            let start = ICO_HEADER_LEN + i * ICO_ENTRY_LEN;
+            let entry = bytes
+                .get(start..start + ICO_ENTRY_LEN)
+                .ok_or_else(|| malformed("truncated directory"))?;
+            let dim = |b: u8| if b == 0 { 256 } else { u32::from(b) };
+            let le32 = |o: usize| {
+                u32::from_le_bytes([entry[o], entry[o + 1], entry[o + 2], entry[o + 3]]) as usize
+            };
+            let frame = IcoFrame {
+                width: dim(entry[0]),
+                height: dim(entry[1]),
+                len: le32(8),
+                offset: le32(12),
+            };
+            if frame.offset.saturating_add(frame.len) > bytes.len() {
+                return Err(malformed("frame data out of bounds"));
+            }
+            Ok(frame)
+        })
+        .collect()
+}
+
+/// Choose the smallest frame covering `width`×`height`, else the largest.
+#[must_use]
+pub fn best_ico_frame(frames: &[IcoFrame], width: u32, height: u32) -> Option<IcoFrame> {
+    let area = |f: &IcoFrame| u64::from(f.width) * u64::from(f.height);
+    frames
+        .iter()
+        .filter(|f| f.width >= width && f.height >= height)
+        .min_by_key(|f| area(f))
+        .or_else(|| frames.iter().max_by_key(|f| area(f)))
+        .copied()
+}
+
+/// Decode the best-fitting frame of an `.ico` file.
+///
+/// The chosen frame is re-wrapped as a single-image icon so the `image`
+/// crate's decoder handles both PNG and BMP payloads.
+fn decode_ico_frame(bytes: &[u8], width: u32, height: u32) -> Result<DynamicImage> {
+    let frames = ico_frames(bytes)?;
+    let frame = best_ico_frame(&frames, width, height)
+        .ok_or_else(|| SdError::ImageFormat("Icon file contains no images".to_string()))?;
+
+    let data_offset = ICO_HEADER_LEN + ICO_ENTRY_LEN;
+    let dir_start = frames
+        .iter()
+        .position(|f| *f == frame)
+        .map_or(ICO_HEADER_LEN, |i| ICO_HEADER_LEN + i * ICO_ENTRY_LEN);
+
+    let mut single = Vec::with_capacity(data_offset + frame.len);
+    single.extend_from_slice(&ICO_MAGIC);
+    single.extend_from_slice(&1u16.to_le_bytes());
+    single.extend_from_slice(&bytes[dir_start..dir_start + 12]);
+    #[allow(clippy::cast_possible_truncation)] // Header is 22 bytes
+    single.extend_from_slice(&(data_offset as u32).to_le_bytes());
+    single.extend_from_slice(&bytes[frame.offset..frame.offset + frame.len]);
+
+    image::load_from_memory_with_format(&single, image::ImageFormat::Ico)
+        .map_err(|e| SdError::ImageProcessing(e.to_string()))
+}
+
+/// A rectangular region of a source canvas, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CropBox {
+    /// Left edge.
+    pub x: u32,
+    /// Top edge.
+    pub y: u32,
+    /// Width of the region.
+    pub width: u32,
+    /// Height of the region.
+    pub height: u32,
+}
+
+/// Placement of a single key within a full-deck canvas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CanvasTile {
+    /// Key index (row-major).
+    pub key: u8,
+    /// Row of the key on the device.
+    pub row: u8,
+    /// Column of the key on the device.
+    pub col: u8,
+    /// Region of the scaled canvas shown on this key.
+    pub crop: CropBox,
+}
+
+/// Geometry for treating the whole deck as one canvas.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CanvasLayout {
+    /// Width the source image is scaled to before slicing.
+    pub canvas_width: u32,
+    /// Height the source image is scaled to before slicing.
+    pub canvas_height: u32,
+    /// Size of each key tile.
+    pub key_size: KeySize,
+    /// Pixels skipped between adjacent keys.
+    pub gap: u32,
+    /// Per-key crop boxes in key index order.
+    pub tiles: Vec<CanvasTile>,
+}
+
+/// Compute the canvas size and per-key crop boxes for a `rows`×`cols` deck.
+///
+/// The canvas includes `gap` pixels between neighbouring keys. Those regions
+/// are never shown, so the image appears to continue behind the bezels.
+#[must_use]
+pub fn canvas_layout(rows: u8, cols: u8, key_size: KeySize, gap: u32) -> CanvasLayout {
+    let span = |count: u8, key: u32| {
+        let count = u32::from(count);
+        count * key + count.saturating_sub(1) * gap
+    };
+
+    let mut tiles = Vec::with_capacity(usize::from(rows) * usize::from(cols));
+    for row in 0..rows {
+        for col in 0..cols {
+            tiles.push(CanvasTile {
+                key: row * cols + col,
+                row,
+                col,
+                crop: CropBox {
+                    x: u32::from(col) * (key_size.width + gap),
+                    y: u32::from(row) * (key_size.height + gap),
+                    width: key_size.width,
+                    height: key_size.height,
+                },
+            });
         }
-        ResizeStrategy::Stretch => {
-            // resize_exact() ignores aspect ratio
-            img.resize_exact(width, height, filter)
+    }
+
+    CanvasLayout {
+        canvas_width: span(cols, key_size.width),
+        canvas_height: span(rows, key_size.height),
+        key_size,
+        gap,
+        tiles,
+    }
+}
+
+/// Slice one image into per-key tiles for a `rows`×`cols` deck.
+///
+/// The image is scaled to fill the canvas described by [`canvas_layout`]
+/// (cropping to preserve aspect ratio), then cut into one tile per key.
+/// Tiles are returned in key index order.
+#[must_use]
+pub fn slice_for_deck(
+    img: &DynamicImage,
+    rows: u8,
+    cols: u8,
+    key_size: KeySize,
+    gap: u32,
+) -> Vec<DynamicImage> {
+    let layout = canvas_layout(rows, cols, key_size, gap);
+    let canvas = img.resize_to_fill(
+        layout.canvas_width,
+        layout.canvas_height,
+        image::imageops::FilterType::Lanczos3,
+    );
+
+    layout
+        .tiles
+        .iter()
+        .map(|tile| canvas.crop_imm(tile.crop.x, tile.crop.y, tile.crop.width, tile.crop.height))
+        .collect()
+}
+
+/// Gamma of a typical monitor, which sRGB colors are chosen on.
+const SRGB_GAMMA: f32 = 2.2;
+
+/// Gamma of the Stream Deck LCD tone curve, steeper than a monitor's.
+const LCD_GAMMA: f32 = 2.5;
+
+/// Approximate how an sRGB color looks on the Stream Deck's LCD.
+///
+/// The panels have a steeper tone curve than a typical monitor, slightly
+/// muted saturation, and some backlight bleed in the blacks. This returns the
+/// sRGB color that looks roughly like the physical key on a normal screen.
+/// It is a fixed, deterministic approximation, not a calibrated profile.
+#[must_use]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // Clamped to 0..=255
+pub fn simulate_lcd(rgb: (u8, u8, u8)) -> (u8, u8, u8) {
+    const SATURATION: f32 = 0.85;
+    const BLACK_LEVEL: f32 = 0.003;
+
+    let panel = |c: u8| (f32::from(c) / 255.0).powf(LCD_GAMMA);
+    let (r, g, b) = (panel(rgb.0), panel(rgb.1), panel(rgb.2));
+    let luma = 0.0722f32.mul_add(b, 0.2126f32.mul_add(r, 0.7152 * g));
+
+    let encode = |c: f32| {
+        let muted = (c - luma).mul_add(SATURATION, luma).clamp(0.0, 1.0);
+        let lit = (1.0 - BLACK_LEVEL).mul_add(muted, BLACK_LEVEL);
+        (lit.powf(1.0 / SRGB_GAMMA) * 255.0)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    };
+    (encode(r), encode(g), encode(b))
+}
+
+/// How a fill color is interpreted before it's sent to a key.
+#[derive(Debug, Clone, Copy, Default, ValueEnum, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorSpace {
+    /// Send the sRGB value as given.
+    #[default]
+    Srgb,
+    /// The value is linear light; encode it with the sRGB transfer curve.
+    Linear,
+    /// Pre-correct for the model's panel so the key looks closer to the
+    /// requested sRGB color.
+    Device,
+}
+
+impl fmt::Display for ColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Srgb => write!(f, "srgb"),
+            Self::Linear => write!(f, "linear"),
+            Self::Device => write!(f, "device"),
         }
+    }
+}
+
+/// Tone curve gamma of a model's LCD, undone by [`ColorSpace::Device`].
+///
+/// Every display model currently uses the curve [`simulate_lcd`] assumes.
+/// Models without key displays, and unknown ones, get the sRGB gamma, which
+/// makes the correction a no-op.
+#[must_use]
+pub const fn panel_gamma(model: Option<DeviceModel>) -> f32 {
+    match model {
+        Some(
+            DeviceModel::Mini
+            | DeviceModel::MiniMk2
+            | DeviceModel::Original
+            | DeviceModel::OriginalV2
+            | DeviceModel::Mk2
+            | DeviceModel::Xl
+            | DeviceModel::XlV2
+            | DeviceModel::Plus
+            | DeviceModel::Neo,
+        ) => LCD_GAMMA,
+        Some(DeviceModel::Pedal) | None => SRGB_GAMMA,
+    }
+}
+
+/// The RGB value to upload for a fill color given in `space`.
+///
+/// For [`ColorSpace::Device`], channels are raised to `SRGB_GAMMA /
+/// panel_gamma(model)` so the panel's steeper curve lands near the intended
+/// brightness. Saturation and black level aren't corrected.
+#[must_use]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // Clamped to 0..=255
+pub fn to_device_rgb(
+    rgb: (u8, u8, u8),
+    space: ColorSpace,
+    model: Option<DeviceModel>,
+) -> (u8, u8, u8) {
+    let map = |f: &dyn Fn(f32) -> f32| {
+        let channel = |c: u8| (f(f32::from(c) / 255.0) * 255.0).round().clamp(0.0, 255.0) as u8;
+        (channel(rgb.0), channel(rgb.1), channel(rgb.2))
     };
+    match space {
+        ColorSpace::Srgb => rgb,
+        ColorSpace::Linear => map(&|c| {
+            if c <= 0.003_130_8 {
+                c * 12.92
+            } else {
+                1.055f32.mul_add(c.powf(1.0 / 2.4), -0.055)
+            }
+        }),
+        ColorSpace::Device => {
+            let exponent = SRGB_GAMMA / panel_gamma(model);
+            map(&|c| c.powf(exponent))
+        }
+    }
+}
+
+/// Average color of an image, used to summarize it for display simulation.
+#[must_use]
+#[allow(clippy::cast_possible_truncation)] // Averages of u8 channels fit in u8
+pub fn average_rgb(img: &DynamicImage) -> (u8, u8, u8) {
+    let rgb = img.to_rgb8();
+    let count = u64::from(rgb.width()) * u64::from(rgb.height());
+    if count == 0 {
+        return (0, 0, 0);
+    }
+    let (r, g, b) = rgb.pixels().fold((0u64, 0u64, 0u64), |(r, g, b), p| {
+        (
+            r + u64::from(p[0]),
+            g + u64::from(p[1]),
+            b + u64::from(p[2]),
+        )
+    });
+    ((r / count) as u8, (g / count) as u8, (b / count) as u8)
+}
+
+/// How to reduce a region of an image to one color.
+#[derive(Debug, Clone, Copy, Default, ValueEnum, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaletteMode {
+    /// Mean of every pixel; smooth, but mixes distinct areas into a blend.
+    #[default]
+    Average,
+    /// The most common color; keeps a cell's main color crisp.
+    Dominant,
+}
+
+impl fmt::Display for PaletteMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Average => write!(f, "average"),
+            Self::Dominant => write!(f, "dominant"),
+        }
+    }
+}
 
-    Ok(resized)
+/// Most common color of an image.
+///
+/// Pixels are grouped into buckets of similar color (16 levels per channel)
+/// and the average of the fullest bucket is returned, so noise and
+/// gradients don't split one visible color into many exact values. Ties go
+/// to the darker bucket.
+#[must_use]
+#[allow(clippy::cast_possible_truncation)] // Averages of u8 channels fit in u8
+pub fn dominant_rgb(img: &DynamicImage) -> (u8, u8, u8) {
+    let rgb = img.to_rgb8();
+    // Per bucket: pixel count and channel sums
+    let mut buckets = vec![(0u64, 0u64, 0u64, 0u64); 16 * 16 * 16];
+    for p in rgb.pixels() {
+        let index =
+            (usize::from(p[0] >> 4) << 8) | (usize::from(p[1] >> 4) << 4) | usize::from(p[2] >> 4);
+        let bucket = &mut buckets[index];
+        bucket.0 += 1;
+        bucket.1 += u64::from(p[0]);
+        bucket.2 += u64::from(p[1]);
+        bucket.3 += u64::from(p[2]);
+    }
+    let Some(&(count, r, g, b)) = buckets
+        .iter()
+        .rev()
+        .max_by_key(|(count, ..)| *count)
+        .filter(|(count, ..)| *count > 0)
+    else {
+        return (0, 0, 0);
+    };
+    ((r / count) as u8, (g / count) as u8, (b / count) as u8)
+}
+
+/// One color per cell of a `rows`×`cols` grid laid over the whole image,
+/// in row-major key order.
+///
+/// Cells split the image evenly without scaling it, so every source pixel
+/// counts towards exactly one key; an image smaller than the grid repeats
+/// edge pixels instead.
+#[must_use]
+pub fn grid_colors(img: &DynamicImage, rows: u8, cols: u8, mode: PaletteMode) -> Vec<(u8, u8, u8)> {
+    let (width, height) = img.dimensions();
+    // Start and length of cell `i` of `count` along an edge of `size` pixels
+    let span = |i: u8, count: u8, size: u32| {
+        let (i, count) = (u32::from(i), u32::from(count));
+        let start = (i * size / count).min(size.saturating_sub(1));
+        let end = ((i + 1) * size / count).max(start + 1);
+        (start, end - start)
+    };
+
+    let mut colors = Vec::with_capacity(usize::from(rows) * usize::from(cols));
+    for row in 0..rows {
+        for col in 0..cols {
+            if width == 0 || height == 0 {
+                colors.push((0, 0, 0));
+                continue;
+            }
+            let (x, cell_width) = span(col, cols, width);
+            let (y, cell_height) = span(row, rows, height);
+            let cell = img.crop_imm(x, y, cell_width, cell_height);
+            colors.push(match mode {
+                PaletteMode::Average => average_rgb(&cell),
+                PaletteMode::Dominant => dominant_rgb(&cell),
+            });
+        }
+    }
+    colors
+}
+
+/// Blend a translucent `color` (RGBA) over every pixel of `img`.
+///
+/// Alpha 255 paints the color solid; lower values let the image show
+/// through, e.g. `ff000080` tints a key half red.
+#[must_use]
+#[allow(clippy::cast_possible_truncation)] // Blends of u8 channels fit in u8
+pub fn overlay_color(img: &DynamicImage, color: (u8, u8, u8, u8)) -> DynamicImage {
+    let (r, g, b, alpha) = color;
+    let alpha = u16::from(alpha);
+    let blend = |over: u8, under: u8| {
+        ((u16::from(over) * alpha + u16::from(under) * (255 - alpha) + 127) / 255) as u8
+    };
+    let mut rgb = img.to_rgb8();
+    for pixel in rgb.pixels_mut() {
+        *pixel = image::Rgb([blend(r, pixel[0]), blend(g, pixel[1]), blend(b, pixel[2])]);
+    }
+    DynamicImage::ImageRgb8(rgb)
+}
+
+/// Blur radius for [`sharpen`], sized for the fine detail of a key icon.
+const SHARPEN_SIGMA: f32 = 1.0;
+
+/// Crisp up a downscaled image with an unsharp mask (`--sharpen`).
+///
+/// `amount` runs from 0 (unchanged) to 100, which adds back twice the
+/// detail a slight blur removes. Dimensions and alpha are kept.
+#[must_use]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // Clamped to 0-255
+pub fn sharpen(img: &DynamicImage, amount: u8) -> DynamicImage {
+    if amount == 0 {
+        return img.clone();
+    }
+    let strength = f32::from(amount.min(100)) / 50.0;
+    let mut rgba = img.to_rgba8();
+    let blurred = image::imageops::blur(&rgba, SHARPEN_SIGMA);
+    for (pixel, soft) in rgba.pixels_mut().zip(blurred.pixels()) {
+        for channel in 0..3 {
+            let original = f32::from(pixel[channel]);
+            let detail = original - f32::from(soft[channel]);
+            pixel[channel] = (original + detail * strength).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Where an overlay sits on a key.
+#[derive(Debug, Clone, Copy, Default, ValueEnum, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverlayPosition {
+    /// Top-left corner.
+    TopLeft,
+    /// Centered along the top edge.
+    Top,
+    /// Top-right corner.
+    TopRight,
+    /// Centered along the left edge.
+    Left,
+    /// Middle of the key.
+    Center,
+    /// Centered along the right edge.
+    Right,
+    /// Bottom-left corner.
+    BottomLeft,
+    /// Centered along the bottom edge.
+    Bottom,
+    /// Bottom-right corner, the usual spot for badges.
+    #[default]
+    BottomRight,
+}
+
+/// Compute where an overlay of `overlay` (width, height) lands on a key.
+///
+/// The overlay is scaled to fit within `scale` of the key's width and
+/// height, keeping its aspect ratio, and pinned to `position`.
+///
+/// # Errors
+///
+/// Returns an error if `scale` isn't greater than 0 and at most 1.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)] // Key and overlay sizes are a few hundred pixels
+pub fn overlay_box(
+    key: KeySize,
+    overlay: (u32, u32),
+    position: OverlayPosition,
+    scale: f32,
+) -> Result<CropBox> {
+    if !(scale > 0.0 && scale <= 1.0) {
+        return Err(SdError::ImageProcessing(format!(
+            "Invalid overlay scale {scale}: must be greater than 0 and at most 1"
+        )));
+    }
+    let (max_width, max_height) = (key.width as f32 * scale, key.height as f32 * scale);
+    let (width, height) = (overlay.0.max(1) as f32, overlay.1.max(1) as f32);
+    let fit = (max_width / width).min(max_height / height);
+    let width = ((width * fit).round() as u32).clamp(1, key.width);
+    let height = ((height * fit).round() as u32).clamp(1, key.height);
+
+    let (free_x, free_y) = (key.width - width, key.height - height);
+    let (x, y) = match position {
+        OverlayPosition::TopLeft => (0, 0),
+        OverlayPosition::Top => (free_x / 2, 0),
+        OverlayPosition::TopRight => (free_x, 0),
+        OverlayPosition::Left => (0, free_y / 2),
+        OverlayPosition::Center => (free_x / 2, free_y / 2),
+        OverlayPosition::Right => (free_x, free_y / 2),
+        OverlayPosition::BottomLeft => (0, free_y),
+        OverlayPosition::Bottom => (free_x / 2, free_y),
+        OverlayPosition::BottomRight => (free_x, free_y),
+    };
+    Ok(CropBox {
+        x,
+        y,
+        width,
+        height,
+    })
+}
+
+/// Draw `overlay` onto `base` in `region`, respecting the overlay's alpha.
+#[must_use]
+pub fn composite_overlay(
+    base: &DynamicImage,
+    overlay: &DynamicImage,
+    region: CropBox,
+) -> DynamicImage {
+    let badge = overlay.resize_exact(
+        region.width,
+        region.height,
+        image::imageops::FilterType::Lanczos3,
+    );
+    let mut canvas = base.to_rgba8();
+    image::imageops::overlay(
+        &mut canvas,
+        &badge.to_rgba8(),
+        i64::from(region.x),
+        i64::from(region.y),
+    );
+    DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(canvas).to_rgb8())
+}
+
+/// Minimum contrast ratio for key text: WCAG AA for large text, which is
+/// what a few characters on a key amount to.
+pub const MIN_TEXT_CONTRAST: f32 = 3.0;
+
+/// WCAG 2.x relative luminance of an sRGB color, from 0.0 (black) to 1.0 (white).
+#[must_use]
+pub fn relative_luminance(rgb: (u8, u8, u8)) -> f32 {
+    let linear = |c: u8| {
+        let c = f32::from(c) / 255.0;
+        if c <= 0.040_45 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.0722f32.mul_add(
+        linear(rgb.2),
+        0.2126f32.mul_add(linear(rgb.0), 0.7152 * linear(rgb.1)),
+    )
+}
+
+/// WCAG contrast ratio between two colors, from 1.0 (identical) to 21.0
+/// (black on white). Order doesn't matter.
+#[must_use]
+pub fn contrast_ratio(a: (u8, u8, u8), b: (u8, u8, u8)) -> f32 {
+    let (la, lb) = (relative_luminance(a), relative_luminance(b));
+    let (light, dark) = if la >= lb { (la, lb) } else { (lb, la) };
+    (light + 0.05) / (dark + 0.05)
+}
+
+/// Black or white, whichever is more legible on `background`.
+#[must_use]
+pub fn auto_text_color(background: (u8, u8, u8)) -> (u8, u8, u8) {
+    const BLACK: (u8, u8, u8) = (0, 0, 0);
+    const WHITE: (u8, u8, u8) = (255, 255, 255);
+    if contrast_ratio(BLACK, background) >= contrast_ratio(WHITE, background) {
+        BLACK
+    } else {
+        WHITE
+    }
 }
@@ -0,0 +1,364 @@
+//! User-facing key numbering for `--key-base` and `--key-order`.
+//!
+//! Devices number keys from 0, left to right and then top to bottom. Some
+//! people find it easier to count from 1, or to count down the columns:
+//!
+//! ```text
+//! sd --key-base 1 fill-key 1 red          # top-left key
+//! sd --key-order column-major clear-key 1  # second key of the first column
+//! ```
+//!
+//! Key arguments are translated to native indices before a command runs, and
+//! keys in its output are translated back. Palettes are typed in by hand, so
+//! `fill-palette` reads them in the user's numbering too. Config files and
+//! `set-keys` file names always use native numbering, so they work the same
+//! for everyone.
+//!
+//! A deck mounted upside down is a different matter: `--rotate-layout 180`
+//! remaps keys on the device itself (see [`LayoutRotation`]), so every key
+//! number, configs included, counts from the key that is top-left as mounted.
+
+use clap::ValueEnum;
+
+use crate::error::{Result, SdError};
+
+/// The order keys are counted in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum KeyOrder {
+    /// Left to right, then top to bottom (the device's own order)
+    #[default]
+    RowMajor,
+    /// Top to bottom, then left to right
+    ColumnMajor,
+    /// Row by row, alternating direction: left to right, then right to left
+    Serpentine,
+}
+
+/// How far a deck is turned clockwise from its normal orientation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LayoutRotation {
+    /// Mounted normally
+    #[default]
+    #[value(name = "0")]
+    None,
+    /// Turned a quarter clockwise (square grids only)
+    #[value(name = "90")]
+    Quarter,
+    /// Upside down
+    #[value(name = "180")]
+    Half,
+    /// Turned a quarter counter-clockwise (square grids only)
+    #[value(name = "270")]
+    ThreeQuarters,
+}
+
+impl LayoutRotation {
+    /// The rotation in degrees clockwise.
+    #[must_use]
+    pub const fn degrees(self) -> u16 {
+        match self {
+            Self::None => 0,
+            Self::Quarter => 90,
+            Self::Half => 180,
+            Self::ThreeQuarters => 270,
+        }
+    }
+
+    /// Check that a `cols`×`rows` grid can be turned this way.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for 90° or 270° on a grid that isn't square, since
+    /// the turned layout wouldn't match the key grid.
+    pub fn check(self, cols: u8, rows: u8) -> Result<()> {
+        match self {
+            Self::Quarter | Self::ThreeQuarters if cols != rows => Err(SdError::Other(format!(
+                "--rotate-layout {} needs a square key grid, but this device is {cols}x{rows}; \
+                 use 0 or 180",
+                self.degrees()
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// The device's own index for `key`, counted row-major from the key that
+    /// is top-left as mounted.
+    ///
+    /// Keys off the grid (and 90°/270° on a grid that isn't square) are
+    /// returned unchanged.
+    #[must_use]
+    pub const fn physical_key(self, key: u8, cols: u8, rows: u8) -> u8 {
+        let count = cols.saturating_mul(rows);
+        if key >= count {
+            return key;
+        }
+        let (row, col) = (key / cols, key % cols);
+        match self {
+            Self::Half => count - 1 - key,
+            Self::Quarter if cols == rows => (cols - 1 - col) * cols + row,
+            Self::ThreeQuarters if cols == rows => col * cols + (cols - 1 - row),
+            _ => key,
+        }
+    }
+}
+
+/// Maps between the numbering the user chose and native key indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyNumbering {
+    base: u8,
+    order: KeyOrder,
+    cols: u8,
+    rows: u8,
+}
+
+impl KeyNumbering {
+    /// Numbering for a `cols`×`rows` grid.
+    #[must_use]
+    pub const fn new(base: u8, order: KeyOrder, cols: u8, rows: u8) -> Self {
+        Self {
+            base,
+            order,
+            cols,
+            rows,
+        }
+    }
+
+    /// Returns true if user numbers are native indices (the default).
+    #[must_use]
+    pub fn is_native(&self) -> bool {
+        self.base == 0 && self.order == KeyOrder::RowMajor
+    }
+
+    /// The number of the first key (0 or 1).
+    #[must_use]
+    pub const fn base(&self) -> u8 {
+        self.base
+    }
+
+    const fn key_count(&self) -> u8 {
+        self.cols.saturating_mul(self.rows)
+    }
+
+    /// Translate a user key number to the native index.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key isn't on the grid.
+    pub fn to_native(&self, key: u8) -> Result<u8> {
+        let count = self.key_count();
+        let position = key
+            .checked_sub(self.base)
+            .filter(|position| *position < count);
+        match position {
+            Some(position) => Ok(self.reorder(position, false)),
+            None if self.base == 0 => Err(SdError::InvalidKeyIndex {
+                index: key,
+                max: count,
+                max_idx: count.saturating_sub(1),
+            }),
+            None => Err(SdError::Other(format!(
+                "Invalid key {key}: with --key-base {base} this device's keys are {base}-{last}",
+                base = self.base,
+                last = u16::from(self.base) + u16::from(count) - 1,
+            ))),
+        }
+    }
+
+    /// Translate a native index to the number the user would type.
+    #[must_use]
+    pub fn to_user(&self, native: u8) -> u8 {
+        if native >= self.key_count() {
+            return native.saturating_add(self.base);
+        }
+        self.reorder(native, true).saturating_add(self.base)
+    }
+
+    /// Translate a user range like `1-8` to native indices, in user order.
+    ///
+    /// Open bounds run to the first or last key, so with `--key-base 1` on a
+    /// 32-key deck `8-` is keys 8-32 and `-4` is keys 1-4.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the range is malformed or runs off the grid.
+    pub fn range_to_native(&self, range: &str) -> Result<Vec<u8>> {
+        let (start, end) = parse_range_bounds(range)?;
+        let last = self.base.saturating_add(self.key_count()).saturating_sub(1);
+        let (start, end) = (start.unwrap_or(self.base), end.unwrap_or(last));
+        if start > end {
+            return Err(SdError::Other(format!(
+                "Invalid range '{range}': start ({start}) must be <= end ({end})"
+            )));
+        }
+        (start..=end).map(|key| self.to_native(key)).collect()
+    }
+
+    /// Reorder a 0-based position between user order and row-major order.
+    const fn reorder(&self, position: u8, to_user: bool) -> u8 {
+        let (cols, rows) = (self.cols, self.rows);
+        match self.order {
+            KeyOrder::RowMajor => position,
+            KeyOrder::ColumnMajor if to_user => (position % cols) * rows + position / cols,
+            KeyOrder::ColumnMajor => (position % rows) * cols + position / rows,
+            // Mirroring every other row is its own inverse
+            KeyOrder::Serpentine => {
+                let (row, col) = (position / cols, position % cols);
+                if row % 2 == 0 {
+                    position
+                } else {
+                    row * cols + (cols - 1 - col)
+                }
+            }
+        }
+    }
+}
+
+impl Default for KeyNumbering {
+    fn default() -> Self {
+        Self::new(0, KeyOrder::RowMajor, 0, 0)
+    }
+}
+
+/// Split a key range like `0-7`, `8-` or `-7` into its bounds; a bound
+/// that is left off is `None`.
+///
+/// # Errors
+///
+/// Returns an error if there is no `-` or a bound isn't a number.
+pub fn parse_range_bounds(range: &str) -> Result<(Option<u8>, Option<u8>)> {
+    let (start, end) = range.trim().split_once('-').ok_or_else(|| {
+        SdError::Other(format!(
+            "Invalid range format '{range}': expected START-END (e.g., 0-7, 8- or -7)"
+        ))
+    })?;
+    let parse = |part: &str, which: &str| {
+        let part = part.trim();
+        if part.is_empty() {
+            return Ok(None);
+        }
+        part.parse::<u8>()
+            .map(Some)
+            .map_err(|_| SdError::Other(format!("Invalid range {which} '{part}': not a number")))
+    };
+    Ok((parse(start, "start")?, parse(end, "end")?))
+}
+
+/// Returns true if native `key` falls within `range` (bounds may be open).
+///
+/// A range that doesn't parse includes every key.
+#[must_use]
+pub fn key_in_range(key: u8, range: &str) -> bool {
+    parse_range_bounds(range).map_or(true, |(start, end)| {
+        start.is_none_or(|start| key >= start) && end.is_none_or(|end| key <= end)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orders_on_a_5x3_grid() {
+        let column = KeyNumbering::new(0, KeyOrder::ColumnMajor, 5, 3);
+        // Down the first column, then the top of the second
+        let natives: Vec<u8> = (0..4).map(|k| column.to_native(k).unwrap()).collect();
+        assert_eq!(natives, [0, 5, 10, 1]);
+
+        let snake = KeyNumbering::new(1, KeyOrder::Serpentine, 5, 3);
+        let natives: Vec<u8> = (5..=7).map(|k| snake.to_native(k).unwrap()).collect();
+        assert_eq!(natives, [4, 9, 8]);
+    }
+
+    #[test]
+    fn test_to_user_inverts_to_native() {
+        for order in [
+            KeyOrder::RowMajor,
+            KeyOrder::ColumnMajor,
+            KeyOrder::Serpentine,
+        ] {
+            for base in [0, 1] {
+                let numbering = KeyNumbering::new(base, order, 8, 4);
+                for key in base..base + 32 {
+                    let native = numbering.to_native(key).unwrap();
+                    assert!(native < 32);
+                    assert_eq!(numbering.to_user(native), key, "{order:?} base {base}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_keys_off_the_grid_are_rejected() {
+        let numbering = KeyNumbering::new(1, KeyOrder::RowMajor, 3, 2);
+        assert_eq!(numbering.to_native(1).unwrap(), 0);
+        assert_eq!(numbering.to_native(6).unwrap(), 5);
+        for key in [0, 7] {
+            assert!(numbering.to_native(key).is_err(), "{key}");
+        }
+        assert!(matches!(
+            KeyNumbering::new(0, KeyOrder::ColumnMajor, 3, 2).to_native(6),
+            Err(SdError::InvalidKeyIndex {
+                max: 6,
+                max_idx: 5,
+                ..
+            })
+        ));
+        assert_eq!(numbering.range_to_native("2-4").unwrap(), [1, 2, 3]);
+        assert!(numbering.range_to_native("0-3").is_err());
+        assert!(numbering.range_to_native("4-2").is_err());
+        assert!(KeyNumbering::default().is_native());
+    }
+
+    #[test]
+    fn test_open_ranges_resolve_against_the_key_count() {
+        let numbering = KeyNumbering::new(1, KeyOrder::RowMajor, 3, 2);
+        assert_eq!(numbering.range_to_native("4-").unwrap(), [3, 4, 5]);
+        assert_eq!(numbering.range_to_native("-2").unwrap(), [0, 1]);
+        assert_eq!(numbering.range_to_native("-").unwrap(), [0, 1, 2, 3, 4, 5]);
+        assert!(numbering.range_to_native("7-").is_err());
+        assert!(numbering.range_to_native("x-").is_err());
+
+        // Column-major keeps user order, so the range isn't contiguous natively
+        let column = KeyNumbering::new(1, KeyOrder::ColumnMajor, 3, 2);
+        assert_eq!(column.range_to_native("5-").unwrap(), [2, 5]);
+    }
+
+    #[test]
+    fn test_key_in_range() {
+        assert!(key_in_range(3, "0-7"));
+        assert!(key_in_range(7, "0-7"));
+        assert!(!key_in_range(8, "0-7"));
+        assert!(key_in_range(8, "8-"));
+        assert!(!key_in_range(7, "8-"));
+        assert!(key_in_range(0, "-7"));
+        assert!(!key_in_range(8, "-7"));
+        assert!(key_in_range(255, "-"));
+        // An unparseable range filters nothing
+        assert!(key_in_range(9, "first-row"));
+        assert!(key_in_range(9, "7"));
+    }
+
+    #[test]
+    fn test_layout_rotation() {
+        // Upside down, the mounted top-left key is the device's last
+        let half = LayoutRotation::Half;
+        assert_eq!(half.physical_key(0, 5, 3), 14);
+        assert_eq!(half.physical_key(14, 5, 3), 0);
+        assert_eq!(half.physical_key(6, 5, 3), 8);
+        assert!(half.check(5, 3).is_ok());
+
+        // On a 3x3 grid turned clockwise, the device's top-left is top-right
+        let quarter = LayoutRotation::Quarter;
+        assert_eq!(quarter.physical_key(2, 3, 3), 0);
+        assert_eq!(quarter.physical_key(0, 3, 3), 6);
+        for key in 0..9 {
+            let twice = quarter.physical_key(quarter.physical_key(key, 3, 3), 3, 3);
+            assert_eq!(twice, half.physical_key(key, 3, 3));
+            let back = LayoutRotation::ThreeQuarters.physical_key(key, 3, 3);
+            assert_eq!(quarter.physical_key(back, 3, 3), key);
+        }
+        assert!(quarter.check(8, 4).is_err());
+        assert!(LayoutRotation::ThreeQuarters.check(8, 4).is_err());
+        assert_eq!(LayoutRotation::None.physical_key(7, 8, 4), 7);
+    }
+}
@@ -5,22 +5,52 @@
 //!
 //! # Modules
 //!
+//! - `activity`: Button activity shared by `watch` and `idle`
 //! - `device`: Device abstraction layer for Stream Deck hardware
 //! - `error`: Error types with user-recoverable hints
 //! - `output`: Output mode abstraction (robot/human)
 //! - `batch`: Batch operations support
+//! - `cancel`: Cooperative cancellation for long operations
+//! - `color`: Hex color parsing (`f00`, `ff0000`, `ff000080`)
 //! - `config`: Configuration file handling
+//! - `context`: Context switches for `sd context` and `sd serve --stdio`
+//! - `data_uri`: Inline `data:` URI image sources
+//! - `hook`: Post-apply shell hooks (`on_apply`, `apply --after`)
+//! - `metrics`: Health and metrics endpoints for `sd serve --metrics`
+//! - `recording`: Button event recordings for `watch --record` and `replay`
+//! - `report`: Structured run reports (`--report`)
+//! - `rpc`: JSON-RPC 2.0 protocol for `sd serve --stdio`
+//! - `server`: HTTP side of `sd serve`: event WebSocket, auth and serving
 //! - `snapshot`: Device state snapshots
+//! - `key_numbering`: `--key-base` and `--key-order` key numbering
+//! - `image_cache`: Persistent cache of rendered key images
+//! - `icon_theme`: Freedesktop icon theme lookup for `icon:NAME`
+//! - `update`: Release checks for `version --check`
 #![forbid(unsafe_code)]
 
+pub mod activity;
 pub mod batch;
+pub mod cancel;
 pub mod cli;
+pub mod color;
 pub mod config;
+pub mod context;
+pub mod data_uri;
 pub mod device;
 pub mod error;
+pub mod hook;
+pub mod icon_theme;
+pub mod image_cache;
 pub mod image_ops;
+pub mod key_numbering;
 pub mod logging;
+pub mod metrics;
 pub mod output;
+pub mod recording;
+pub mod report;
+pub mod rpc;
+pub mod server;
 pub mod snapshot;
 pub mod state;
 pub mod theme;
+pub mod update;
@@ -3,34 +3,52 @@
 //! Provides both human-friendly and agent-friendly (robot mode) interfaces.
 #![forbid(unsafe_code)]
 
+mod activity;
 mod batch;
+mod cancel;
 mod cli;
+mod color;
 mod config;
+mod context;
+mod data_uri;
 mod device;
 mod error;
+mod hook;
+mod icon_theme;
+mod image_cache;
 mod image_ops;
+mod key_numbering;
 mod logging;
+mod metrics;
 mod output;
+mod recording;
+mod report;
+mod rpc;
+mod server;
 mod snapshot;
 mod state;
 mod theme;
+mod update;
 
 use std::io;
 
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use image::GenericImageView;
 use rich_rust::prelude::{Color, Console, Style, Text};
 use serde::Serialize;
 
+use cancel::CancelToken;
 use cli::{Cli, Commands};
 use device::DeviceOperations;
 use error::{Result, SdError};
 use output::{
     BatchKeyResult, BatchSummary, BrightnessDryRunDetails, ClearAllDryRunDetails,
     ClearKeyDryRunDetails, ClearKeysDryRunDetails, DeviceContext, DryRunResponse,
-    FillKeyDryRunDetails, ImageSourceInfo, Output, OutputMode, ProcessingInfo, SetKeyDryRunDetails,
-    ValidationError,
+    FillKeyDryRunDetails, ImageSourceInfo, KeyDisplayEvent, KeySetResult, Output, OutputMode,
+    ProcessingInfo, ResetDryRunDetails, SetCanvasDryRunDetails, SetKeyDryRunDetails,
+    SimulatedDisplay, ValidationError, sort_batch_results,
 };
+use report::RunReport;
 
 /// Build information embedded at compile time.
 mod build_info {
@@ -58,27 +76,119 @@ mod build_info {
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
 
     // Initialize structured logging based on CLI flags
     logging::init_logging(cli.use_json(), cli.verbose, cli.quiet);
+    cli.json_compat.pin();
+
+    let settings = config::settings::Settings::load_default();
+    cli.default_dry_run = settings.default_dry_run;
+    cli.default_model = settings.default_model;
+    cli.output_templates = settings.output.templates;
+    cli.contexts = settings.contexts;
 
     // Note: no-color handling is now managed by rich_rust through OutputMode
 
-    // Prepare output handler
+    image_cache::set_enabled(!cli.no_cache);
+
+    // Prepare output handler, reporting keys the way the user numbers them
+    cli.key_numbering = key_numbering(&cli);
     let output = OutputMode::from_cli(&cli).into_output();
+    let output = match &cli.also_json {
+        Some(path) => match json_log_output(&cli, path) {
+            Ok(log) => Box::new(output::TeeOutput::new(vec![output, log])),
+            Err(e) => {
+                output.error(&e);
+                std::process::exit(1);
+            }
+        },
+        None => output,
+    };
+    let output: Box<dyn Output> = if cli.key_numbering.is_native() {
+        output
+    } else {
+        Box::new(output::RenumberedOutput::new(output, cli.key_numbering))
+    };
 
     // Run the command
-    let result = run(&cli, output.as_ref());
+    let report = cli.report.as_ref().map(|_| {
+        RunReport::start(
+            matches.subcommand_name(),
+            std::env::args().skip(1).collect(),
+            cli.is_dry_run(),
+        )
+    });
+    let started = std::time::Instant::now();
+    let numbering = cli.key_numbering;
+    let result = cli
+        .command
+        .as_mut()
+        .map_or(Ok(()), |command| command.renumber_keys(&numbering))
+        .and_then(|()| run(&cli, output.as_ref()))
+        .and_then(|()| output::take_render_error().map_or(Ok(()), Err));
+    let duration = started.elapsed();
+
+    // Write the run report before any exit
+    if let (Some(path), Some(report)) = (&cli.report, report) {
+        if let Err(e) = report.finish(&result).write(path) {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to write run report");
+        }
+    }
 
     // Handle errors
-    if let Err(e) = result {
-        output.error(&e);
-        std::process::exit(1);
+    if let Err(e) = &result {
+        output.error(e);
+    }
+    if cli.emit_complete && cli.use_json() {
+        let event = CompleteEvent {
+            event: "complete",
+            command: matches.subcommand_name(),
+            duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+            ok: result.is_ok(),
+            label: cli.label.as_deref(),
+            write_verification: report::write_verification(),
+        };
+        // Always one line, so it can't be confused with the pretty result
+        println!(
+            "{}",
+            serde_json::to_string(&event).expect("complete event serializes")
+        );
     }
+    if let Err(e) = &result {
+        std::process::exit(e.exit_code());
+    }
+}
+
+/// Trailing robot-mode event for `--emit-complete`.
+#[derive(Serialize)]
+struct CompleteEvent<'a> {
+    event: &'static str,
+    command: Option<&'a str>,
+    duration_ms: u64,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<&'a str>,
+    /// How `--verify-writes` went, when it was asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    write_verification: Option<device::WriteVerification>,
 }
 
 fn run(cli: &Cli, output: &dyn Output) -> Result<()> {
+    if cli.list_commands {
+        output_json(cli, &cli::command_manifest());
+        return Ok(());
+    }
+    if let Some(path) = &cli.dump_hid {
+        return cmd_dump_hid(cli, path, output);
+    }
+    if cli.verify_writes && cli.command.is_some() && !cli.quiet {
+        output.warning(
+            "--verify-writes has no effect: Stream Deck devices can't read key images back, \
+             so writes are counted but can't be verified",
+        );
+    }
     match &cli.command {
         None => print_quick_start(cli),
         Some(Commands::List(args)) => cmd_list(cli, args, output),
@@ -86,25 +196,43 @@ fn run(cli: &Cli, output: &dyn Output) -> Result<()> {
         Some(Commands::Brightness(args)) => cmd_brightness(cli, args, output),
         Some(Commands::SetKey(args)) => cmd_set_key(cli, args, output),
         Some(Commands::SetKeys(args)) => cmd_set_keys(cli, args, output),
+        Some(Commands::SetCanvas(args)) => cmd_set_canvas(cli, args, output),
         Some(Commands::ClearKey(args)) => cmd_clear_key(cli, args, output),
         Some(Commands::ClearAll(args)) => cmd_clear_all(cli, args, output),
+        Some(Commands::Reset(args)) => cmd_reset(cli, args, output),
         Some(Commands::FillKey(args)) => cmd_fill_key(cli, args, output),
+        Some(Commands::OverlayKey(args)) => cmd_overlay_key(cli, args, output),
         Some(Commands::FillAll(args)) => cmd_fill_all(cli, args, output),
         Some(Commands::FillKeys(args)) => cmd_fill_keys(cli, args, output),
+        Some(Commands::FillPalette(args)) => cmd_fill_palette(cli, args, output),
+        Some(Commands::FillFromImage(args)) => cmd_fill_from_image(cli, args, output),
+        Some(Commands::Rainbow(_)) => cmd_fill_scheme(cli, batch::ColorScheme::Rainbow, output),
         Some(Commands::ClearKeys(args)) => cmd_clear_keys(cli, args, output),
         Some(Commands::Watch(args)) => cmd_watch(cli, args, output),
         Some(Commands::Read(args)) => cmd_read(cli, args, output),
+        Some(Commands::Replay(args)) => cmd_replay(cli, args, output),
+        Some(Commands::Idle(args)) => cmd_idle(cli, args),
         Some(Commands::Init(args)) => cmd_init(cli, args),
         Some(Commands::Config(args)) => cmd_config(cli, args),
         Some(Commands::Validate(args)) => cmd_validate(cli, args, output),
         Some(Commands::Apply(args)) => cmd_apply(cli, args, output),
+        Some(Commands::Profiles(args)) => cmd_profiles(cli, args),
+        Some(Commands::ApplyProfile(args)) => cmd_apply_profile(cli, args, output),
+        Some(Commands::UpgradeConfig(args)) => cmd_upgrade_config(cli, args, output),
+        Some(Commands::Context(args)) => cmd_context(cli, args, output),
         Some(Commands::Save(args)) => cmd_save(cli, args),
         Some(Commands::Restore(args)) => cmd_restore(cli, args),
         Some(Commands::Snapshots(args)) => cmd_snapshots(cli, args),
         Some(Commands::Snapshot(args)) => cmd_snapshot(cli, args),
+        Some(Commands::State(args)) => cmd_state(args, output),
+        Some(Commands::Cache(args)) => cmd_cache(cli, args),
         Some(Commands::Serve(args)) => cmd_serve(cli, args),
-        Some(Commands::Version) => cmd_version(cli, output),
+        Some(Commands::Version(args)) => cmd_version(cli, args, output),
+        Some(Commands::Raw(args)) => cmd_raw(cli, args, output),
         Some(Commands::Completions(args)) => cmd_completions(cli, args),
+        Some(Commands::Demo(args)) => match &args.command {
+            cli::DemoSubcommand::Dimmer(args) => cmd_demo_dimmer(cli, args, output),
+        },
     }
 }
 
@@ -130,6 +258,7 @@ fn print_robot_quick_start() {
             list_devices: "sd list --robot",
             device_info: "sd info --robot",
             current_state: "sd read --robot",
+            all_commands: "sd --list-commands",
         },
         display: RobotDisplay {
             set_brightness: "sd brightness <0-100>",
@@ -150,7 +279,7 @@ fn print_robot_quick_start() {
         output_modes: OutputModes {
             human: "--format=text (default)",
             robot: "--robot or --format=json",
-            compact: "--format=json-compact",
+            compact: "--compact or --format=json-compact",
         },
         multi_device: "Use --serial <SERIAL> when multiple devices connected",
         web_ui: "sd serve --port 8420",
@@ -246,6 +375,7 @@ struct RobotDiscovery {
     list_devices: &'static str,
     device_info: &'static str,
     current_state: &'static str,
+    all_commands: &'static str,
 }
 
 #[derive(Serialize)]
@@ -277,40 +407,360 @@ struct OutputModes {
     compact: &'static str,
 }
 
+/// The `--also-json` sink: robot output appended to `path` as JSON Lines.
+fn json_log_output(cli: &Cli, path: &std::path::Path) -> Result<Box<dyn Output>> {
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| {
+            SdError::Other(format!(
+                "Failed to open --also-json file {}: {e}",
+                path.display()
+            ))
+        })?;
+    Ok(Box::new(
+        output::RobotOutput::new(output::RobotFormat::JsonCompact)
+            .with_version(cli.json_compat)
+            .with_explain(cli.explain)
+            .with_only_errors(cli.only_errors)
+            .with_writer(Box::new(file)),
+    ))
+}
+
 // === Device Opening Helper ===
 
+/// Numbering for `--key-base`/`--key-order`, laid out like the target device.
+fn key_numbering(cli: &Cli) -> key_numbering::KeyNumbering {
+    let uses_keys = cli.command.as_ref().is_some_and(Commands::uses_keys);
+    if !cli.custom_key_numbering() || !uses_keys {
+        return key_numbering::KeyNumbering::default();
+    }
+    // The command's own model, else the target device's (enumerated, not
+    // opened), else the offline model for dry runs and `state`
+    let (cols, rows) = cli
+        .command
+        .as_ref()
+        .and_then(Commands::assumed_model)
+        .map(device::DeviceModel::layout)
+        .or_else(|| connected_layout(cli))
+        .unwrap_or_else(|| cli.offline_model().layout());
+    key_numbering::KeyNumbering::new(cli.key_base, cli.key_order, cols, rows)
+}
+
+/// Columns and rows of the device a command would open, found by
+/// enumeration so the device isn't opened twice.
+fn connected_layout(cli: &Cli) -> Option<(u8, u8)> {
+    let pinned = cli
+        .serial_file
+        .as_deref()
+        .and_then(|path| pinned_serial(cli, path).ok());
+    let serial = pinned.as_deref().or(cli.serial.as_deref());
+    let devices = device::list_devices().ok()?;
+    let mut targets = devices
+        .iter()
+        .filter(|info| serial.is_none_or(|serial| info.serial == serial));
+    match (targets.next(), targets.next()) {
+        (Some(info), None) => Some((info.cols, info.rows)),
+        _ => None,
+    }
+}
+
 /// Opens a Stream Deck device, using retry logic if enabled via CLI flags.
 fn open_device(cli: &Cli) -> Result<device::Device> {
-    if cli.retry_enabled() {
+    let pinned = cli
+        .serial_file
+        .as_deref()
+        .map(|path| pinned_serial(cli, path))
+        .transpose()?;
+    let serial = pinned.as_deref().or(cli.serial.as_deref());
+
+    let result = if cli.retry_enabled() {
         let opts = cli.connection_options();
         tracing::debug!(
             retry = opts.max_retries,
             delay_ms = opts.retry_delay.as_millis(),
             backoff = opts.backoff_factor,
+            jitter = opts.jitter,
             "Opening device with retry"
         );
-        device::open_device_with_retry(cli.serial.as_deref(), &opts)
+        device::open_device_with_retry(serial, &opts)
     } else {
-        device::open_device(cli.serial.as_deref())
+        device::open_device(serial)
+    }
+    .and_then(|device| device.with_layout_rotation(cli.rotate_layout, !cli.no_rotate_images))
+    .map(|device| device.with_verify_writes(cli.verify_writes));
+
+    if let Ok(device) = &result {
+        report::record_device(&device.info().serial);
+    }
+
+    match (result, &cli.serial_file) {
+        (Err(SdError::DeviceNotFound { serial }), Some(path)) => {
+            Err(SdError::PinnedDeviceNotConnected {
+                serial,
+                path: path.display().to_string(),
+            })
+        }
+        (result, _) => result,
+    }
+}
+
+/// Add a device's `--verify-writes` stats to the run report once its writes are done.
+fn record_write_verification(device: &dyn DeviceOperations) {
+    if let Some(stats) = device.write_verification() {
+        report::record_write_verification(stats);
+    }
+}
+
+/// Resolve the serial pinned by `--serial-file`.
+///
+/// An explicit `--serial` wins and re-pins the file. Otherwise the recorded
+/// serial is used; if there is none yet, the first connected device (by
+/// serial) is picked and recorded so later calls target the same device.
+fn pinned_serial(cli: &Cli, path: &std::path::Path) -> Result<String> {
+    if let Some(serial) = &cli.serial {
+        std::fs::write(path, format!("{serial}\n"))?;
+        return Ok(serial.clone());
+    }
+
+    match std::fs::read_to_string(path) {
+        Ok(contents) if !contents.trim().is_empty() => {
+            let serial = contents.trim().to_string();
+            tracing::debug!(serial, path = %path.display(), "Using pinned serial");
+            return Ok(serial);
+        }
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
     }
+
+    let serial = device::list_devices()?
+        .into_iter()
+        .map(|info| info.serial)
+        .min()
+        .ok_or(SdError::NoDevicesFound)?;
+    std::fs::write(path, format!("{serial}\n"))?;
+    tracing::info!(serial, path = %path.display(), "Pinned device serial");
+    Ok(serial)
 }
 
 // === Command Implementations ===
 
-fn cmd_list(cli: &Cli, _args: &cli::ListArgs, output: &dyn Output) -> Result<()> {
-    let _ = cli; // Will be used later for args.long formatting
-    let devices = device::list_devices()?;
-    output.device_list(&devices);
+fn cmd_list(cli: &Cli, args: &cli::ListArgs, output: &dyn Output) -> Result<()> {
+    let timeout = std::time::Duration::from_millis(args.timeout);
+    let listing = device::list_devices_within(timeout, args.long)?;
+    if cli.use_json() {
+        // Unsupported devices join the same list, marked `"supported": false`,
+        // except under V1, whose consumers expect every entry in one shape
+        let unsupported = match cli.json_compat {
+            output::JsonVersion::V1 => &[][..],
+            _ => listing.unsupported.as_slice(),
+        };
+        let entries: Vec<ListEntry> = listing
+            .devices
+            .iter()
+            .map(ListEntry::Supported)
+            .chain(unsupported.iter().map(ListEntry::Unsupported))
+            .collect();
+        if cli.json_compat < output::JsonVersion::V3 {
+            // A bare array has nowhere to report the timeouts
+            if !listing.timed_out.is_empty() {
+                tracing::warn!(
+                    serials = ?listing.timed_out,
+                    timeout_ms = args.timeout,
+                    "No response in time; firmware not shown"
+                );
+            }
+            output_json(cli, &entries);
+        } else {
+            output_json(
+                cli,
+                &output::DeviceList::new(&entries, &listing.devices)
+                    .with_timed_out(&listing.timed_out),
+            );
+        }
+        return Ok(());
+    }
+    output.device_list(&listing.devices);
+    for unknown in &listing.unsupported {
+        output.warning(&format!(
+            "{} (USB ID {}, serial {}) is an Elgato device sd doesn't support yet",
+            unknown.product_name,
+            unknown.usb_id(),
+            if unknown.serial.is_empty() {
+                "unknown"
+            } else {
+                &unknown.serial
+            }
+        ));
+    }
+    if !listing.timed_out.is_empty() {
+        output.warning(&format!(
+            "No response within {} ms from: {} (firmware not shown)",
+            args.timeout,
+            listing.timed_out.join(", ")
+        ));
+    }
     Ok(())
 }
 
-fn cmd_info(cli: &Cli, _args: &cli::InfoArgs, output: &dyn Output) -> Result<()> {
+/// One robot `list` entry, serialized in its own type's shape.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ListEntry<'a> {
+    Supported(&'a device::DeviceInfo),
+    Unsupported(&'a device::UnknownDevice),
+}
+
+fn cmd_info(cli: &Cli, args: &cli::InfoArgs, output: &dyn Output) -> Result<()> {
     let device = open_device(cli)?;
     let info = device::get_device_info(&device);
-    output.device_info(&info);
+    if args.watch {
+        let brightness = current_brightness(&device, cli.verbose > 0);
+        return watch_device_status(&device, &info, brightness, output, args.interval);
+    }
+    if !args.field.is_empty() {
+        return print_info_fields(cli, &info, &args.field);
+    }
+    let brightness = current_brightness(&device, cli.verbose > 0);
+    if !args.all {
+        output.device_info(&info, brightness.as_ref());
+        return Ok(());
+    }
+
+    let physical = info.physical();
+    if cli.use_json() {
+        output_json(
+            cli,
+            &DeviceInfoAll {
+                info: &info,
+                physical,
+                brightness: brightness.filter(|_| cli.json_compat >= output::JsonVersion::V4),
+            },
+        );
+    } else {
+        output.device_info(&info, brightness.as_ref());
+        if let Some(p) = physical {
+            output.info(&format!(
+                "Physical: {} mm keys, {} mm gaps, ~{} DPI",
+                p.physical_key_mm, p.gap_mm, p.dpi
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Print selected `info` fields: bare values one per line, or a small JSON
+/// object in robot mode.
+#[allow(clippy::unnecessary_wraps)] // Consistent return type
+fn print_info_fields(
+    cli: &Cli,
+    info: &device::DeviceInfo,
+    fields: &[cli::InfoField],
+) -> Result<()> {
+    use clap::ValueEnum;
+
+    let value = |field: cli::InfoField| -> serde_json::Value {
+        match field {
+            cli::InfoField::Serial => info.serial.clone().into(),
+            cli::InfoField::Firmware => info.firmware_version.clone().into(),
+            cli::InfoField::Keys => info.key_count.into(),
+            cli::InfoField::Model => info
+                .model()
+                .and_then(|model| model.to_possible_value())
+                .map_or_else(|| info.kind.clone(), |v| v.get_name().to_string())
+                .into(),
+        }
+    };
+
+    if cli.use_json() {
+        let projection: serde_json::Map<String, serde_json::Value> = fields
+            .iter()
+            .map(|field| (field.name().to_string(), value(*field)))
+            .collect();
+        output_json(cli, &projection);
+    } else {
+        for field in fields {
+            match value(*field) {
+                serde_json::Value::String(s) => println!("{s}"),
+                other => println!("{other}"),
+            }
+        }
+    }
     Ok(())
 }
 
+/// `info --all` output: device info plus physical dimensions.
+#[derive(Serialize)]
+struct DeviceInfoAll<'a> {
+    #[serde(flatten)]
+    info: &'a device::DeviceInfo,
+    #[serde(flatten)]
+    physical: Option<device::PhysicalDimensions>,
+    #[serde(flatten)]
+    brightness: Option<device::BrightnessReading>,
+}
+
+/// The device's brightness, read back where the backend supports it and
+/// otherwise the last level set in this session. `None` if neither is known.
+///
+/// The source is only filled in when `with_source` is set (verbose mode).
+fn current_brightness(
+    device: &dyn DeviceOperations,
+    with_source: bool,
+) -> Option<device::BrightnessReading> {
+    let read = device
+        .can_read_brightness()
+        .then(|| device.read_brightness())
+        .and_then(|result| {
+            result
+                .map_err(|e| tracing::debug!(error = %e, "Brightness read failed"))
+                .ok()
+        });
+    let (level, source) = match read {
+        Some(level) => (level, device::BrightnessSource::Device),
+        None => (
+            state::session_state().brightness?,
+            device::BrightnessSource::Tracked,
+        ),
+    };
+    Some(device::BrightnessReading {
+        level,
+        source: with_source.then_some(source),
+    })
+}
+
+/// Poll key state and redraw the device panel whenever it changes.
+///
+/// The panel is drawn once up front with all keys released; the device only
+/// reports changes, so an idle deck would otherwise show nothing. Runs until
+/// interrupted (Ctrl+C) or a read fails. Robot mode emits one JSON line per
+/// change.
+fn watch_device_status(
+    device: &device::Device,
+    info: &device::DeviceInfo,
+    brightness: Option<device::BrightnessReading>,
+    output: &dyn Output,
+    interval_ms: u64,
+) -> Result<()> {
+    let interval = std::time::Duration::from_millis(interval_ms.max(10));
+    let mut last_states = vec![false; usize::from(info.key_count)];
+    output.device_status(info, brightness.as_ref(), &last_states);
+
+    loop {
+        std::thread::sleep(interval);
+        // No report means nothing changed
+        match device::poll_button_states(device)? {
+            Some(states) if states != last_states => {
+                output.device_status(info, brightness.as_ref(), &states);
+                last_states = states;
+            }
+            _ => {}
+        }
+    }
+}
+
 fn cmd_brightness(cli: &Cli, args: &cli::BrightnessArgs, output: &dyn Output) -> Result<()> {
     // Validate brightness level
     if args.level > 100 {
@@ -375,24 +825,156 @@ fn cmd_brightness_dry_run(cli: &Cli, args: &cli::BrightnessArgs) -> Result<()> {
 }
 
 fn cmd_set_key(cli: &Cli, args: &cli::SetKeyArgs, output: &dyn Output) -> Result<()> {
+    let resolved = resolve_key_icon(cli, args)?;
+    let args = &*resolved;
+
     // Handle dry-run mode
     if cli.is_dry_run() {
         return cmd_set_key_dry_run(cli, args);
     }
 
-    let device = open_device(cli)?;
-    device::set_key_image(&device, args.key, &args.image, args.resize)?;
+    let device = open_device(cli)?.with_sharpen(args.sharpen);
+    // Catch Ctrl+C before a --for display goes up, so it always comes down
+    let cancel = args.for_duration.map(|_| CancelToken::on_ctrl_c());
+    let warnings = write_key_image(cli, &device, args)?;
 
-    // Track state change
-    state::record::set_key(args.key, args.image.clone());
+    if let (Some(duration), Some(cancel)) = (args.for_duration, &cancel) {
+        let shown = data_uri::display_source(&args.image);
+        return show_key_for(
+            cli, &device, args.key, &shown, &warnings, duration, args.then, cancel, output,
+        );
+    }
 
-    output.key_set(args.key, &args.image);
+    output.key_set(&KeySetResult::new(args.key, &args.image).with_warnings(warnings));
     Ok(())
 }
 
+/// Resolve a `set-key` image of `icon:NAME` to a file in the icon theme.
+fn resolve_key_icon<'a>(
+    cli: &Cli,
+    args: &'a cli::SetKeyArgs,
+) -> Result<std::borrow::Cow<'a, cli::SetKeyArgs>> {
+    if icon_theme::icon_name(&args.image).is_none() {
+        return Ok(std::borrow::Cow::Borrowed(args));
+    }
+    let size = args
+        .key_size
+        .unwrap_or_else(|| device::DeviceInfo::for_model(cli.offline_model()).key_size());
+    Ok(std::borrow::Cow::Owned(cli::SetKeyArgs {
+        image: icon_theme::resolve_image(&args.image, size.width.max(size.height))?,
+        ..args.clone()
+    }))
+}
+
+/// Upload `set-key`'s image and track it, unless `--for` only shows it for
+/// a while.
+///
+/// Shared by the command and the `set-key` RPC. Returns warnings about the
+/// key size and resizing.
+fn write_key_image(
+    cli: &Cli,
+    device: &device::Device,
+    args: &cli::SetKeyArgs,
+) -> Result<Vec<String>> {
+    let native_size = device::get_device_info(device).key_size();
+    let key_size = args.key_size.unwrap_or(native_size);
+    let mut warnings = Vec::new();
+    if key_size != native_size {
+        warnings.push(format!(
+            "--key-size {key_size} differs from the device's native key size {native_size}"
+        ));
+    }
+    device::set_key_image_with_size(device, args.key, &args.image, args.resize, key_size)?;
+    record_write_verification(device);
+    warnings.extend(resize_warning(cli, &args.image, key_size));
+
+    if args.for_duration.is_none() {
+        // Track state change
+        state::record::set_key(args.key, args.image.clone());
+    }
+    Ok(warnings)
+}
+
+/// Keep a temporary key display up for `--for`, then end it per `--then`.
+///
+/// The temporary content is never tracked, so `restore` puts back what the
+/// session state says the key showed before (clearing it if unknown). Ctrl+C
+/// ends the wait early and still puts the key back; callers install `cancel`
+/// before the write so an early Ctrl+C can't strand the display. Robot mode
+/// prints a `displayed` and an `ended` event, one JSON line each; `warnings`
+/// from the write go on the `displayed` event.
+#[allow(clippy::too_many_arguments)]
+fn show_key_for(
+    cli: &Cli,
+    device: &device::Device,
+    key: u8,
+    shown: &str,
+    warnings: &[String],
+    duration: std::time::Duration,
+    then: cli::AfterTimeout,
+    cancel: &CancelToken,
+    output: &dyn Output,
+) -> Result<()> {
+    let report = cli.use_json() || !cli.quiet;
+    if report {
+        output.key_display(&KeyDisplayEvent::Displayed {
+            for_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+            key,
+            shows: shown.to_string(),
+            warnings: warnings.to_vec(),
+        });
+    }
+
+    let deadline = std::time::Instant::now() + duration;
+    while !cancel.is_cancelled() {
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        std::thread::sleep(remaining.min(std::time::Duration::from_millis(100)));
+    }
+
+    match then {
+        cli::AfterTimeout::Restore => restore_key_from_state(device, key)?,
+        cli::AfterTimeout::Clear => {
+            device::clear_key(device, key)?;
+            state::record::clear_key(key);
+        }
+    }
+    if report {
+        output.key_display(&KeyDisplayEvent::Ended {
+            interrupted: cancel.is_cancelled(),
+            key,
+            then,
+        });
+    }
+    cancel.check()
+}
+
+/// Warning for an uploaded image whose size didn't match the key.
+///
+/// Returns `None` when the sizes match, the dimensions can't be read, or
+/// `--no-resize-warning` is set.
+fn resize_warning(cli: &Cli, path: &std::path::Path, size: image_ops::KeySize) -> Option<String> {
+    if !cli.warn_on_resize() {
+        return None;
+    }
+    let (w, h) = image::image_dimensions(path).ok()?;
+    (w != size.width || h != size.height)
+        .then(|| format!("Image was resized from {w}x{h} to {size}"))
+}
+
 /// Dry-run handler for set-key command.
 #[allow(clippy::unnecessary_wraps)] // Consistent return type
 fn cmd_set_key_dry_run(cli: &Cli, args: &cli::SetKeyArgs) -> Result<()> {
+    // A malformed inline image is an input error, not a plan
+    if data_uri::as_data_uri(&args.image).is_some() {
+        let size = args
+            .key_size
+            .unwrap_or_else(|| device::DeviceInfo::for_model(cli.offline_model()).key_size());
+        image_ops::open_for_size(&args.image, size.width, size.height)?;
+    }
+
     // Try to get device info for context
     let device_result = open_device(cli);
 
@@ -408,11 +990,12 @@ fn cmd_set_key_dry_run(cli: &Cli, args: &cli::SetKeyArgs) -> Result<()> {
             Err(_) => (DeviceContext::disconnected(cli.serial.clone()), None),
         };
 
-        // Calculate processing info
-        let target_dims = device_info
-            .as_ref()
-            .map(|i| (i.key_width as u32, i.key_height as u32))
-            .unwrap_or((96, 96)); // Default XL dimensions
+        // Calculate processing info (--key-size overrides the device size)
+        let target_dims = args
+            .key_size
+            .or_else(|| device_info.as_ref().map(device::DeviceInfo::key_size))
+            .unwrap_or_else(|| device::DeviceInfo::for_model(cli.offline_model()).key_size());
+        let target_dims = (target_dims.width, target_dims.height);
 
         let resize_needed = source_info
             .dimensions
@@ -422,9 +1005,18 @@ fn cmd_set_key_dry_run(cli: &Cli, args: &cli::SetKeyArgs) -> Result<()> {
         let processing = ProcessingInfo {
             resize_needed,
             target_dimensions: target_dims,
+            strategy: args.resize,
+            geometry: source_info.dimensions.map(|dims| {
+                image_ops::resize_geometry(dims, target_dims.0, target_dims.1, args.resize)
+            }),
+            sharpen: (args.sharpen > 0).then_some(args.sharpen),
         };
 
-        let details = SetKeyDryRunDetails::new(args.key, source_info.clone(), processing);
+        let mut details =
+            SetKeyDryRunDetails::new(cli.user_key(args.key), source_info.clone(), processing);
+        if let Some(simulated) = simulate_image_display(args) {
+            details = details.with_simulated_display(simulated);
+        }
 
         // Build response based on validation
         let mut errors = Vec::new();
@@ -459,7 +1051,7 @@ fn cmd_set_key_dry_run(cli: &Cli, args: &cli::SetKeyArgs) -> Result<()> {
         }
 
         // Add resize warning
-        if resize_needed {
+        if resize_needed && cli.warn_on_resize() {
             if let Some((w, h)) = source_info.dimensions {
                 warnings.push(format!(
                     "Image will be resized from {}x{} to {}x{}",
@@ -468,6 +1060,16 @@ fn cmd_set_key_dry_run(cli: &Cli, args: &cli::SetKeyArgs) -> Result<()> {
             }
         }
 
+        // Warn when the override doesn't match the connected device
+        if let (Some(size), Some(info)) = (args.key_size, &device_info) {
+            if size != info.key_size() {
+                warnings.push(format!(
+                    "--key-size {size} differs from the device's native key size {}",
+                    info.key_size()
+                ));
+            }
+        }
+
         // Add device warning if not connected
         if let Err(ref e) = device_result {
             warnings.push(format!("Device not connected: {e}"));
@@ -490,8 +1092,8 @@ fn cmd_set_key_dry_run(cli: &Cli, args: &cli::SetKeyArgs) -> Result<()> {
         // Human-readable dry-run output
         println!(
             "DRY RUN: Would set key {} to {}",
-            args.key,
-            args.image.display()
+            cli.user_key(args.key),
+            data_uri::display_source(&args.image)
         );
 
         if source_info.exists {
@@ -508,6 +1110,17 @@ fn cmd_set_key_dry_run(cli: &Cli, args: &cli::SetKeyArgs) -> Result<()> {
             println!("  WARNING: Image file not found!");
         }
 
+        if let Some(size) = args.key_size {
+            println!("  Key size: {size} (override)");
+        }
+        if args.sharpen > 0 {
+            println!("  Sharpen: {}", args.sharpen);
+        }
+
+        if let Some(simulated) = simulate_image_display(args) {
+            print_simulated_display(&simulated);
+        }
+
         match device_result {
             Ok(device) => {
                 let info = device::get_device_info(&device);
@@ -519,6 +1132,18 @@ fn cmd_set_key_dry_run(cli: &Cli, args: &cli::SetKeyArgs) -> Result<()> {
                         info.key_count - 1
                     );
                 }
+                if let Some(dims) = source_info.dimensions {
+                    let size = args.key_size.unwrap_or_else(|| info.key_size());
+                    print_framing(dims, size, args.resize);
+                }
+                if let Some(size) = args.key_size {
+                    if size != info.key_size() {
+                        println!(
+                            "  WARNING: Key size {size} differs from device key size {}",
+                            info.key_size()
+                        );
+                    }
+                }
             }
             Err(e) => {
                 println!("  Device: not connected ({})", e);
@@ -531,6 +1156,23 @@ fn cmd_set_key_dry_run(cli: &Cli, args: &cli::SetKeyArgs) -> Result<()> {
 
 /// Analyze an image source file without fully loading it.
 fn analyze_image_source(path: &std::path::Path) -> ImageSourceInfo {
+    if let Some(uri) = data_uri::as_data_uri(path) {
+        let data = data_uri::parse(uri).ok();
+        return ImageSourceInfo {
+            path: data_uri::SOURCE_NAME.to_string(),
+            exists: true,
+            readable: data.is_some(),
+            format: data.as_ref().map(|d| d.format.to_string()),
+            // SVGs have no pixel size of their own; they're rasterized to the key
+            dimensions: data
+                .as_ref()
+                .filter(|d| d.format != "svg")
+                .and_then(|d| image::load_from_memory(&d.bytes).ok())
+                .map(|img| img.dimensions()),
+            size_bytes: data.map(|d| d.bytes.len() as u64),
+        };
+    }
+
     let exists = path.exists();
     let metadata = std::fs::metadata(path).ok();
     let size_bytes = metadata.as_ref().map(|m| m.len());
@@ -558,72 +1200,301 @@ fn analyze_image_source(path: &std::path::Path) -> ImageSourceInfo {
     }
 }
 
-#[allow(clippy::too_many_lines)] // Batch operations are inherently complex
-fn cmd_set_keys(cli: &Cli, args: &cli::SetKeysArgs, output: &dyn Output) -> Result<()> {
-    // Open device to get key count
+fn cmd_set_canvas(cli: &Cli, args: &cli::SetCanvasArgs, output: &dyn Output) -> Result<()> {
+    // Handle dry-run mode
+    if cli.is_dry_run() {
+        return cmd_set_canvas_dry_run(cli, args);
+    }
+
+    if !args.image.exists() {
+        return Err(SdError::ImageNotFound {
+            path: args.image.display().to_string(),
+        });
+    }
+    let img = image::open(&args.image).map_err(|e| SdError::ImageProcessing(e.to_string()))?;
+
     let device = open_device(cli)?;
-    let device_info = device::get_device_info(&device);
+    let info = device::get_device_info(&device);
+    let gap = args
+        .gap
+        .unwrap_or_else(|| info.model().map_or(0, device::DeviceModel::default_gap));
+    let tiles = image_ops::slice_for_deck(&img, info.rows, info.cols, info.key_size(), gap);
+    let tile_count = tiles.len();
 
-    // Scan directory for matching files
-    let scan_result = batch::scan_directory(&args.dir, &args.pattern, device_info.key_count)
-        .map_err(|e| SdError::Other(e.to_string()))?;
+    let cancel = CancelToken::on_ctrl_c();
+    device::set_key_images(&device, (0..info.key_count).zip(tiles).collect(), &cancel)?;
+    record_write_verification(&device);
 
-    // Handle dry-run mode (check both global and local flag)
-    if cli.is_dry_run() || args.dry_run {
-        return cmd_set_keys_dry_run(cli, args, &device_info, &scan_result);
+    // Track state change (every key now shows part of the canvas image)
+    for key in 0..info.key_count {
+        state::record::set_key(key, args.image.clone());
     }
 
-    // Check if we have any files to process
-    if scan_result.mappings.is_empty() {
-        output.warning(&format!(
-            "No files matching pattern '{}' found in {}",
-            args.pattern,
-            args.dir.display()
-        ));
-        if !scan_result.unmatched.is_empty() {
-            output.info(&format!(
-                "{} files didn't match pattern",
-                scan_result.unmatched.len()
-            ));
+    output.success(&format!(
+        "Canvas {} applied across {tile_count} keys ({}x{} grid)",
+        args.image.display(),
+        info.cols,
+        info.rows
+    ));
+    Ok(())
+}
+
+/// Dry-run handler for set-canvas command.
+#[allow(clippy::unnecessary_wraps)] // Consistent return type
+fn cmd_set_canvas_dry_run(cli: &Cli, args: &cli::SetCanvasArgs) -> Result<()> {
+    let device_result = open_device(cli);
+    let source_info = analyze_image_source(&args.image);
+
+    // Fall back to XL geometry when no device is connected
+    let (device_ctx, rows, cols, key_size, model) = match &device_result {
+        Ok(device) => {
+            let info = device::get_device_info(device);
+            (
+                DeviceContext::from_info(&info),
+                info.rows,
+                info.cols,
+                info.key_size(),
+                info.model(),
+            )
         }
-        if !scan_result.invalid.is_empty() {
-            output.info(&format!(
-                "{} files had invalid key indices",
-                scan_result.invalid.len()
-            ));
+        Err(_) => {
+            let model = cli.offline_model();
+            let (cols, rows) = model.layout();
+            let (w, h) = model.key_dimensions();
+            (
+                DeviceContext::disconnected(cli.serial.clone()),
+                rows,
+                cols,
+                image_ops::KeySize::new(w, h),
+                Some(model),
+            )
         }
-        return Ok(());
-    }
-
-    // Apply images to keys
-    let mut results: Vec<BatchKeyResult> = Vec::new();
-    let mut success_count = 0;
-    let mut error_count = 0;
+    };
+    let gap = args
+        .gap
+        .unwrap_or_else(|| model.map_or(0, device::DeviceModel::default_gap));
+    let layout = image_ops::canvas_layout(rows, cols, key_size, gap);
 
-    for mapping in &scan_result.mappings {
-        // Check key range filter if specified
-        if let Some(ref range) = args.key_range {
-            if !key_in_range(mapping.key, range) {
-                continue;
+    if cli.use_json() {
+        let mut warnings = Vec::new();
+        if let Some((w, h)) = source_info.dimensions.filter(|_| cli.warn_on_resize()) {
+            if w != layout.canvas_width || h != layout.canvas_height {
+                warnings.push(format!(
+                    "Image will be scaled from {w}x{h} to fill a {}x{} canvas",
+                    layout.canvas_width, layout.canvas_height
+                ));
             }
         }
-
-        // Skip if key is below start_key
-        if mapping.key < args.start_key {
-            continue;
+        if let Err(ref e) = device_result {
+            warnings.push(format!("Device not connected: {e}"));
         }
 
-        let result = device::set_key_image(&device, mapping.key, &mapping.path, args.resize);
-
-        match result {
-            Ok(()) => {
-                success_count += 1;
-                // Track state change
-                state::record::set_key(mapping.key, mapping.path.clone());
-                results.push(BatchKeyResult::set_key_success(mapping.key, &mapping.path));
-            }
-            Err(e) => {
-                error_count += 1;
+        let exists = source_info.exists;
+        let details = SetCanvasDryRunDetails::new(source_info, rows, cols, layout);
+        let response = if exists {
+            DryRunResponse::success("set_canvas", details, device_ctx).with_warnings(warnings)
+        } else {
+            let errors = vec![ValidationError {
+                field: "image".to_string(),
+                error: format!("Image file not found: {}", args.image.display()),
+                suggestion: Some(
+                    "Check the file path. Use absolute paths or paths relative to current directory."
+                        .to_string(),
+                ),
+            }];
+            DryRunResponse::failure(
+                "set_canvas",
+                "Image file not found",
+                errors,
+                details,
+                device_ctx,
+            )
+            .with_warnings(warnings)
+        };
+
+        output_json(cli, &response);
+    } else {
+        println!(
+            "DRY RUN: Would spread {} across {} keys ({}x{} grid)",
+            args.image.display(),
+            layout.tiles.len(),
+            cols,
+            rows
+        );
+
+        if let Some((w, h)) = source_info.dimensions {
+            println!("  Image: {w}x{h}");
+        } else if !source_info.exists {
+            println!("  WARNING: Image file not found!");
+        }
+        println!(
+            "  Canvas: {}x{} (tile {}, gap {}px)",
+            layout.canvas_width, layout.canvas_height, layout.key_size, layout.gap
+        );
+        for tile in &layout.tiles {
+            println!(
+                "  Key {:>2}: crop {}x{} at ({}, {})",
+                tile.key, tile.crop.width, tile.crop.height, tile.crop.x, tile.crop.y
+            );
+        }
+
+        match device_result {
+            Ok(device) => {
+                let info = device::get_device_info(&device);
+                println!("  Device: {} (serial: {})", info.product_name, info.serial);
+            }
+            Err(e) => {
+                println!(
+                    "  Device: not connected ({e}), assuming {} layout",
+                    cli.offline_model().display_name()
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_lines)] // Batch operations are inherently complex
+fn cmd_set_keys(cli: &Cli, args: &cli::SetKeysArgs, output: &dyn Output) -> Result<()> {
+    // A pattern without a placeholder would scan fine and match nothing
+    if !config::has_index_placeholder(&args.pattern) {
+        return Err(SdError::InvalidPattern {
+            pattern: args.pattern.clone(),
+        });
+    }
+
+    // Handle dry-run mode (check both global and local flag)
+    if cli.is_dry_run_with(args.dry_run) {
+        // Without a device, plan against the assumed (or default) model's layout
+        let (device_info, connected) = match args.assume_model {
+            Some(model) => (device::DeviceInfo::for_model(model), false),
+            None => match open_device(cli) {
+                Ok(device) => (device::get_device_info(&device), true),
+                Err(e) => {
+                    tracing::debug!(error = %e, "No device for dry run, using default model");
+                    (device::DeviceInfo::for_model(cli.offline_model()), false)
+                }
+            },
+        };
+        let scan_result = batch::scan_directory(&args.dir, &args.pattern, device_info.key_count)
+            .map_err(|e| SdError::Other(e.to_string()))?;
+        return cmd_set_keys_dry_run(cli, args, &device_info, connected, &scan_result);
+    }
+
+    if args.assume_model.is_some() {
+        return Err(SdError::Other(
+            "--assume-model only plans a dry run; add --dry-run, or drop it to write to the \
+             connected device"
+                .to_string(),
+        ));
+    }
+
+    // Open device to get key count
+    let device = open_device(cli)?.with_sharpen(args.sharpen);
+    let device_info = device::get_device_info(&device);
+
+    // Scan directory for matching files
+    let scan_result = batch::scan_directory(&args.dir, &args.pattern, device_info.key_count)
+        .map_err(|e| SdError::Other(e.to_string()))?;
+
+    // Check if we have any files to process
+    if scan_result.mappings.is_empty() {
+        output.warning(&format!(
+            "No files matching pattern '{}' found in {}",
+            args.pattern,
+            args.dir.display()
+        ));
+        if !scan_result.unmatched.is_empty() {
+            output.info(&format!(
+                "{} files didn't match pattern",
+                scan_result.unmatched.len()
+            ));
+        }
+        if !scan_result.invalid.is_empty() {
+            output.info(&format!(
+                "{} files had invalid key indices",
+                scan_result.invalid.len()
+            ));
+        }
+        return Ok(());
+    }
+
+    // Several files for one key is ambiguous; only proceed if allowed
+    let duplicates = duplicates_in_scope(args, scan_result.duplicates.as_slice());
+    if !duplicates.is_empty() && !args.allow_duplicates {
+        for dup in &duplicates {
+            output.error(&SdError::Other(duplicate_key_message(dup, false)));
+        }
+        return Err(SdError::Other(format!(
+            "{} key(s) matched by multiple files. Rename the extra files or pass --allow-duplicates",
+            duplicates.len()
+        )));
+    }
+
+    let mut checkpoint = match args.checkpoint.as_deref() {
+        Some(path) => Some(
+            batch::Checkpoint::open(path, checkpoint_scope(args, &device_info)?)
+                .map_err(|e| SdError::Other(e.to_string()))?,
+        ),
+        None => None,
+    };
+    if let Some(checkpoint) = checkpoint.as_ref().filter(|c| c.completed() > 0) {
+        output.info(&format!(
+            "Resuming: {} key(s) already written per checkpoint",
+            checkpoint.completed()
+        ));
+    }
+
+    // Apply images to keys
+    let mut results: Vec<BatchKeyResult> = Vec::new();
+    let mut success_count = 0;
+    let mut error_count = 0;
+    let cancel = CancelToken::on_ctrl_c();
+    let mut pacer = cli.write_pacer();
+
+    for mapping in &scan_result.mappings {
+        // Stop between keys on Ctrl+C; remaining keys count as skipped
+        if cancel.is_cancelled() {
+            break;
+        }
+
+        // Skip keys outside --key-range or below --start-key
+        if !args.in_scope(mapping.key) {
+            continue;
+        }
+
+        // Written by an earlier, interrupted run
+        if checkpoint.as_ref().is_some_and(|c| c.is_done(mapping.key)) {
+            continue;
+        }
+
+        pacer.before_write();
+        let result = device::set_key_image(&device, mapping.key, &mapping.path, args.resize);
+
+        match result {
+            Ok(()) => {
+                success_count += 1;
+                if let Some(checkpoint) = checkpoint.as_mut() {
+                    if let Err(e) = checkpoint.record(mapping.key) {
+                        tracing::warn!(error = %e, key = mapping.key, "Failed to update checkpoint");
+                    }
+                }
+                // Track state change
+                state::record::set_key(mapping.key, mapping.path.clone());
+                let warnings = duplicates
+                    .iter()
+                    .filter(|dup| dup.key == mapping.key)
+                    .map(|dup| duplicate_key_message(dup, true))
+                    .chain(resize_warning(cli, &mapping.path, device_info.key_size()))
+                    .collect();
+                results.push(
+                    BatchKeyResult::set_key_success(mapping.key, &mapping.path)
+                        .with_warnings(warnings),
+                );
+            }
+            Err(e) => {
+                error_count += 1;
                 results.push(BatchKeyResult::set_key_failure(
                     mapping.key,
                     &mapping.path,
@@ -631,7 +1502,9 @@ fn cmd_set_keys(cli: &Cli, args: &cli::SetKeysArgs, output: &dyn Output) -> Resu
                 ));
 
                 if !args.continue_on_error {
+                    record_write_verification(&device);
                     // Output results so far before returning error
+                    sort_batch_results(&mut results);
                     let summary = BatchSummary::new(results.len(), success_count, error_count);
                     output.batch_set_keys(&results, &summary);
                     return Err(e);
@@ -640,7 +1513,10 @@ fn cmd_set_keys(cli: &Cli, args: &cli::SetKeysArgs, output: &dyn Output) -> Resu
         }
     }
 
+    record_write_verification(&device);
+
     // Output final results
+    sort_batch_results(&mut results);
     let skipped = scan_result.mappings.len() - success_count - error_count;
     let summary =
         BatchSummary::new(results.len(), success_count, error_count).with_skipped(skipped);
@@ -648,15 +1524,78 @@ fn cmd_set_keys(cli: &Cli, args: &cli::SetKeysArgs, output: &dyn Output) -> Resu
         output.batch_set_keys(&results, &summary);
     }
 
+    cancel.check()?;
+    // Keep the checkpoint while failed keys remain, so a re-run retries them
+    if let Some(checkpoint) = checkpoint.filter(|_| error_count == 0) {
+        checkpoint
+            .finish()
+            .map_err(|e| SdError::Other(e.to_string()))?;
+    }
     Ok(())
 }
 
+/// The batch a set-keys `--checkpoint` belongs to.
+fn checkpoint_scope(
+    args: &cli::SetKeysArgs,
+    device_info: &device::DeviceInfo,
+) -> Result<batch::CheckpointScope> {
+    batch::CheckpointScope::new(
+        &args.dir,
+        &device_info.serial,
+        device_info.cols,
+        device_info.rows,
+    )
+    .map_err(|e| SdError::Other(format!("Cannot resolve {}: {e}", args.dir.display())))
+}
+
+/// Duplicate key matches that fall within set-keys' `--key-range`/`--start-key`.
+fn duplicates_in_scope<'a>(
+    args: &cli::SetKeysArgs,
+    duplicates: &'a [batch::DuplicateKey],
+) -> Vec<&'a batch::DuplicateKey> {
+    duplicates
+        .iter()
+        .filter(|dup| args.in_scope(dup.key))
+        .collect()
+}
+
+/// Describe which files claim a key and, if `allowed`, which one is used.
+fn duplicate_key_message(dup: &batch::DuplicateKey, allowed: bool) -> String {
+    let names: Vec<String> = dup
+        .paths
+        .iter()
+        .map(|p| {
+            p.file_name().map_or_else(
+                || p.display().to_string(),
+                |n| n.to_string_lossy().into_owned(),
+            )
+        })
+        .collect();
+    let mut message = format!(
+        "Key {} matched by {} files ({})",
+        dup.key,
+        names.len(),
+        names.join(", ")
+    );
+    if allowed {
+        if let Some(kept) = names.last() {
+            message.push_str(&format!("; using {kept}"));
+        }
+    }
+    message
+}
+
 /// Dry-run details for set-keys batch command.
 #[derive(Serialize)]
 struct SetKeysDryRunDetails {
     directory: String,
     pattern: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sharpen: Option<u8>,
     operations: Vec<SetKeysDryRunOperation>,
+    /// Keys `--checkpoint` records as written, which the run would skip.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    resumed: Vec<u8>,
     summary: SetKeysDryRunSummary,
 }
 
@@ -687,30 +1626,32 @@ fn cmd_set_keys_dry_run(
     cli: &Cli,
     args: &cli::SetKeysArgs,
     device_info: &device::DeviceInfo,
+    connected: bool,
     scan_result: &batch::ScanResult,
 ) -> Result<()> {
+    // Keys an earlier, interrupted run already wrote
+    let checkpoint = match args.checkpoint.as_deref() {
+        Some(path) => Some(
+            batch::Checkpoint::read(path, checkpoint_scope(args, device_info)?)
+                .map_err(|e| SdError::Other(e.to_string()))?,
+        ),
+        None => None,
+    };
+    let resumed = |key: u8| checkpoint.as_ref().is_some_and(|c| c.is_done(key));
+
     if cli.use_json() {
         let mut operations = Vec::new();
         let mut errors = Vec::new();
         let mut warnings = Vec::new();
         let mut resize_count = 0;
 
-        let in_scope = |key: u8, range: Option<&String>, start_key: u8| {
-            if let Some(range_str) = range {
-                if !key_in_range(key, range_str) {
-                    return false;
-                }
-            }
-            key >= start_key
-        };
-
         for mapping in &scan_result.mappings {
-            if !in_scope(mapping.key, args.key_range.as_ref(), args.start_key) {
+            if !args.in_scope(mapping.key) || resumed(mapping.key) {
                 continue;
             }
 
             let mut op = SetKeysDryRunOperation {
-                key: mapping.key,
+                key: cli.user_key(mapping.key),
                 source: Some(mapping.path.display().to_string()),
                 would_succeed: true,
                 resize_needed: None,
@@ -735,7 +1676,7 @@ fn cmd_set_keys_dry_run(
                         field: format!("image[{}]", mapping.key),
                         error,
                         suggestion: Some(
-                            "Use a supported image format: png, jpg, jpeg, gif, bmp, webp"
+                            "Use a supported image format: png, jpg, jpeg, gif, bmp, webp, ico"
                                 .to_string(),
                         ),
                     });
@@ -774,29 +1715,53 @@ fn cmd_set_keys_dry_run(
             ));
         }
 
-        if resize_count > 0 {
+        let duplicates = duplicates_in_scope(args, scan_result.duplicates.as_slice());
+        for dup in &duplicates {
+            if args.allow_duplicates {
+                warnings.push(duplicate_key_message(dup, true));
+            } else {
+                errors.push(ValidationError {
+                    field: format!("image[{}]", dup.key),
+                    error: duplicate_key_message(dup, false),
+                    suggestion: Some(
+                        "Rename or remove the extra files, or pass --allow-duplicates".to_string(),
+                    ),
+                });
+            }
+        }
+
+        if resize_count > 0 && cli.warn_on_resize() {
             warnings.push(format!(
                 "{resize_count} images will be resized to {}x{}",
                 device_info.key_width, device_info.key_height
             ));
         }
 
+        let resumed_keys: Vec<u8> = (0..device_info.key_count)
+            .filter(|&key| args.in_scope(key) && resumed(key))
+            .collect();
         let total_keys = (0..device_info.key_count)
-            .filter(|key| in_scope(*key, args.key_range.as_ref(), args.start_key))
-            .count();
+            .filter(|key| args.in_scope(*key))
+            .count()
+            - resumed_keys.len();
         let matching_files = operations.len();
         let would_succeed = operations.iter().filter(|op| op.would_succeed).count();
         let would_fail = matching_files.saturating_sub(would_succeed);
         let unmatched = total_keys.saturating_sub(matching_files);
 
-        if has_any_matches && matching_files == 0 {
+        if has_any_matches && matching_files == 0 && resumed_keys.is_empty() {
             warnings.push("No matching files within the specified key range/start_key".to_string());
         }
 
         let details = SetKeysDryRunDetails {
             directory: args.dir.display().to_string(),
             pattern: args.pattern.clone(),
+            sharpen: (args.sharpen > 0).then_some(args.sharpen),
             operations,
+            resumed: resumed_keys
+                .into_iter()
+                .map(|key| cli.user_key(key))
+                .collect(),
             summary: SetKeysDryRunSummary {
                 total_keys,
                 matching_files,
@@ -806,12 +1771,22 @@ fn cmd_set_keys_dry_run(
             },
         };
 
-        let device = DeviceContext::from_info(device_info);
+        let device = if connected {
+            DeviceContext::from_info(device_info)
+        } else {
+            warnings.push(format!(
+                "Planned for {} without a device",
+                device_info.product_name
+            ));
+            DeviceContext::assumed(device_info)
+        };
         let response = if errors.is_empty() && would_fail == 0 {
             DryRunResponse::success("set_keys_batch", details, device).with_warnings(warnings)
         } else {
             let reason = if !has_any_matches {
                 "No matching files found"
+            } else if !duplicates.is_empty() && !args.allow_duplicates {
+                "Multiple files map to the same key"
             } else {
                 "One or more operations would fail"
             };
@@ -826,14 +1801,27 @@ fn cmd_set_keys_dry_run(
             scan_result.mappings.len(),
             args.dir.display()
         );
-        println!(
-            "  Device: {} ({})",
-            device_info.product_name, device_info.serial
-        );
+        if connected {
+            println!(
+                "  Device: {} ({})",
+                device_info.product_name, device_info.serial
+            );
+        } else {
+            println!(
+                "  Device: {} (assumed, not connected)",
+                device_info.product_name
+            );
+        }
         println!("  Pattern: {}", args.pattern);
+        if let Some(checkpoint) = checkpoint.as_ref().filter(|c| c.completed() > 0) {
+            println!(
+                "  Checkpoint: {} key(s) already written, would be skipped",
+                checkpoint.completed()
+            );
+        }
         println!();
 
-        for mapping in &scan_result.mappings {
+        for mapping in scan_result.mappings.iter().filter(|m| !resumed(m.key)) {
             println!(
                 "  Key {}: {} ({} bytes)",
                 mapping.key,
@@ -857,19 +1845,21 @@ fn cmd_set_keys_dry_run(
                 println!("    {}: {}", path.display(), reason);
             }
         }
-    }
-    Ok(())
-}
 
-/// Check if a key index is within the specified range (e.g., "0-7").
-fn key_in_range(key: u8, range: &str) -> bool {
-    if let Some((start, end)) = range.split_once('-') {
-        if let (Ok(start), Ok(end)) = (start.parse::<u8>(), end.parse::<u8>()) {
-            return key >= start && key <= end;
+        let duplicates = duplicates_in_scope(args, scan_result.duplicates.as_slice());
+        if !duplicates.is_empty() {
+            println!();
+            if args.allow_duplicates {
+                println!("  Duplicate keys (--allow-duplicates):");
+            } else {
+                println!("  Duplicate keys (would fail; pass --allow-duplicates):");
+            }
+            for dup in &duplicates {
+                println!("    {}", duplicate_key_message(dup, args.allow_duplicates));
+            }
         }
     }
-    // If range parsing fails, include all keys
-    true
+    Ok(())
 }
 
 fn cmd_clear_key(cli: &Cli, args: &cli::ClearKeyArgs, output: &dyn Output) -> Result<()> {
@@ -903,7 +1893,7 @@ fn cmd_clear_key_dry_run(cli: &Cli, args: &cli::ClearKeyArgs) -> Result<()> {
             Err(_) => (DeviceContext::disconnected(cli.serial.clone()), None),
         };
 
-        let details = ClearKeyDryRunDetails::new(args.key);
+        let details = ClearKeyDryRunDetails::new(cli.user_key(args.key));
 
         // Build response based on validation
         let mut errors = Vec::new();
@@ -946,7 +1936,10 @@ fn cmd_clear_key_dry_run(cli: &Cli, args: &cli::ClearKeyArgs) -> Result<()> {
         output_json(cli, &response);
     } else {
         // Human-readable dry-run output
-        println!("DRY RUN: Would clear key {} (set to black)", args.key);
+        println!(
+            "DRY RUN: Would clear key {} (set to black)",
+            cli.user_key(args.key)
+        );
 
         match device_result {
             Ok(device) => {
@@ -1035,269 +2028,466 @@ fn cmd_clear_all_dry_run(cli: &Cli) -> Result<()> {
     Ok(())
 }
 
-fn cmd_fill_key(cli: &Cli, args: &cli::FillKeyArgs, output: &dyn Output) -> Result<()> {
+fn cmd_reset(cli: &Cli, args: &cli::ResetArgs, output: &dyn Output) -> Result<()> {
+    if args.brightness > 100 {
+        return Err(SdError::InvalidBrightness {
+            value: args.brightness,
+        });
+    }
+
     // Handle dry-run mode
     if cli.is_dry_run() {
-        return cmd_fill_key_dry_run(cli, args);
+        return cmd_reset_dry_run(cli, args);
+    }
+
+    // Confirm if not forced and not in robot mode
+    if !args.force && !cli.use_json() {
+        let console = Console::new();
+        let warning = Color::parse("#FFA502").expect("valid color");
+        console.print_styled(
+            &format!(
+                "Clear every key and set brightness to {}%? The current layout will be lost.",
+                args.brightness
+            ),
+            Style::new().color(warning),
+        );
+        console.print("Use --force to skip this prompt");
+        return Ok(());
     }
 
     let device = open_device(cli)?;
-    let color = parse_color(&args.color)?;
-    device::fill_key_color(&device, args.key, color)?;
+    let info = device::get_device_info(&device);
+    device::clear_all_keys(&device)?;
+    device::set_brightness(&device, args.brightness)?;
 
-    // Track state change
-    let color_str = format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2);
-    state::record::fill_key(args.key, color_str.clone());
+    // Forget what was tracked, then record the baseline we just set
+    let forgotten = state::reset();
+    state::record::clear_all(info.key_count);
+    state::record::brightness(args.brightness);
+    tracing::debug!(forgotten, "Reset tracked state");
 
-    output.key_filled(args.key, &color_str);
+    if cli.use_json() {
+        output_json(
+            cli,
+            &serde_json::json!({
+                "reset": true,
+                "ok": true,
+                "keys_cleared": info.key_count,
+                "brightness": args.brightness,
+            }),
+        );
+    } else {
+        output.success(&format!(
+            "Reset device: cleared {} keys, brightness {}%",
+            info.key_count, args.brightness
+        ));
+    }
     Ok(())
 }
 
-/// Dry-run handler for fill-key command.
+/// Dry-run handler for reset command.
 #[allow(clippy::unnecessary_wraps)] // Consistent return type
-fn cmd_fill_key_dry_run(cli: &Cli, args: &cli::FillKeyArgs) -> Result<()> {
-    // Validate color first
-    let color = parse_color(&args.color)?;
-    let color_str = format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2);
-
-    // Try to get device info for context
+fn cmd_reset_dry_run(cli: &Cli, args: &cli::ResetArgs) -> Result<()> {
     let device_result = open_device(cli);
 
     if cli.use_json() {
-        let (device_ctx, device_info) = match &device_result {
+        let (device_ctx, key_count) = match &device_result {
             Ok(device) => {
                 let info = device::get_device_info(device);
-                (DeviceContext::from_info(&info), Some(info))
+                (DeviceContext::from_info(&info), info.key_count)
             }
-            Err(_) => (DeviceContext::disconnected(cli.serial.clone()), None),
+            Err(_) => (DeviceContext::disconnected(cli.serial.clone()), 0),
         };
 
-        let details = FillKeyDryRunDetails::new(args.key, color_str.clone(), color);
-
-        // Build response based on validation
-        let mut errors = Vec::new();
-        let mut warnings = Vec::new();
-
-        // Check key index if device connected
-        if let Some(ref info) = device_info {
-            if args.key >= info.key_count {
-                errors.push(ValidationError {
-                    field: "key".to_string(),
-                    error: format!(
-                        "Key index {} is out of range (device has {} keys, valid: 0-{})",
-                        args.key,
-                        info.key_count,
-                        info.key_count - 1
-                    ),
-                    suggestion: Some(format!("Use a key index from 0 to {}", info.key_count - 1)),
-                });
-            }
-        }
-
-        // Add device warning if not connected
+        let details = ResetDryRunDetails::new(key_count, args.brightness);
+        let mut response = DryRunResponse::success("reset", details, device_ctx);
         if let Err(ref e) = device_result {
-            warnings.push(format!("Device not connected: {e}"));
+            response = response.with_warnings(vec![format!("Device not connected: {e}")]);
         }
-
-        let response = if errors.is_empty() {
-            DryRunResponse::success("fill_key", details, device_ctx).with_warnings(warnings)
-        } else {
-            DryRunResponse::failure("fill_key", "Validation failed", errors, details, device_ctx)
-                .with_warnings(warnings)
-        };
-
         output_json(cli, &response);
     } else {
-        // Human-readable dry-run output
-        println!(
-            "DRY RUN: Would fill key {} with color {}",
-            args.key, color_str
-        );
-        println!("  RGB: ({}, {}, {})", color.0, color.1, color.2);
-
         match device_result {
             Ok(device) => {
                 let info = device::get_device_info(&device);
+                println!(
+                    "DRY RUN: Would clear all {} keys and set brightness to {}%",
+                    info.key_count, args.brightness
+                );
                 println!("  Device: {} (serial: {})", info.product_name, info.serial);
-                if args.key >= info.key_count {
-                    println!(
-                        "  WARNING: Key {} is out of range (max: {})",
-                        args.key,
-                        info.key_count - 1
-                    );
-                }
             }
             Err(e) => {
-                println!("  Device: not connected ({})", e);
+                println!(
+                    "DRY RUN: Would clear all keys and set brightness to {}%",
+                    args.brightness
+                );
+                println!("  Device: not connected ({e})");
             }
         }
+        println!("  Tracked state would be forgotten");
     }
 
     Ok(())
 }
 
-fn cmd_fill_all(cli: &Cli, args: &cli::FillAllArgs, output: &dyn Output) -> Result<()> {
-    let device = open_device(cli)?;
-    let info = device::get_device_info(&device);
-    let color = parse_color(&args.color)?;
-    device::fill_all_keys_color(&device, color)?;
+fn cmd_fill_key(cli: &Cli, args: &cli::FillKeyArgs, output: &dyn Output) -> Result<()> {
+    // Handle dry-run mode
+    if cli.is_dry_run() {
+        return cmd_fill_key_dry_run(cli, args);
+    }
 
-    // Track state change for all keys
-    let color_str = format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2);
-    for key in 0..info.key_count {
-        state::record::fill_key(key, color_str.clone());
+    let device = open_device(cli)?;
+    // Catch Ctrl+C before a --for display goes up, so it always comes down
+    let cancel = args.for_duration.map(|_| CancelToken::on_ctrl_c());
+    let color_str = fill_key(&device, args)?;
+    if let (Some(duration), Some(cancel)) = (args.for_duration, &cancel) {
+        return show_key_for(
+            cli,
+            &device,
+            args.key,
+            &color_str,
+            &[],
+            duration,
+            args.then,
+            cancel,
+            output,
+        );
     }
 
-    output.all_filled(&color_str);
+    output.key_filled(args.key, &color_str);
     Ok(())
 }
 
-fn cmd_fill_keys(cli: &Cli, args: &cli::FillKeysArgs, output: &dyn Output) -> Result<()> {
-    let device = open_device(cli)?;
-    let device_info = device::get_device_info(&device);
-    let color = parse_color(&args.color)?;
-    let color_str = format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2);
-
-    // Determine which keys to fill
-    let keys = resolve_key_selection(
-        args.all,
-        args.range.as_deref(),
-        &args.keys,
-        device_info.key_count,
-    )?;
-
-    if keys.is_empty() {
-        return Err(SdError::Other(
-            "No keys specified. Use --all, --range, or --keys".to_string(),
-        ));
+/// Fill a key as `fill-key` asks and track it, unless `--for` only shows it
+/// for a while.
+///
+/// Shared by the command and the `fill-key` RPC. A translucent `--over`
+/// color is blended over the key's tracked image, which stays tracked so
+/// restoring the key removes the tint. Returns the color as reported.
+fn fill_key(device: &device::Device, args: &cli::FillKeyArgs) -> Result<String> {
+    let (r, g, b, alpha) = fill_key_color_arg(args)?;
+    let upload = upload_rgb(device, (r, g, b), args.color_space);
+    if alpha < u8::MAX {
+        blend_key_color(device, args.key, (upload.0, upload.1, upload.2, alpha))?;
+        record_write_verification(device);
+        return Ok(format!("#{r:02x}{g:02x}{b:02x}{alpha:02x}"));
     }
+    device::fill_key_color(device, args.key, upload)?;
+    record_write_verification(device);
+    let color_str = format!("#{r:02x}{g:02x}{b:02x}");
 
-    // Fill keys with color
-    let mut results: Vec<BatchKeyResult> = Vec::new();
-    let mut success_count = 0;
-    let mut error_count = 0;
+    if args.for_duration.is_none() {
+        // Track state change
+        state::record::fill_key(args.key, color_str.clone());
+    }
+    Ok(color_str)
+}
 
-    for key in &keys {
-        match device::fill_key_color(&device, *key, color) {
-            Ok(()) => {
-                success_count += 1;
-                // Track state change
-                state::record::fill_key(*key, color_str.clone());
-                results.push(BatchKeyResult::fill_success(*key, &color_str));
-            }
-            Err(e) => {
-                error_count += 1;
-                results.push(BatchKeyResult::fill_failure(
-                    *key,
-                    &color_str,
-                    &e.to_string(),
-                ));
+/// Parse the `fill-key` color, allowing alpha only with `--over`.
+fn fill_key_color_arg(args: &cli::FillKeyArgs) -> Result<(u8, u8, u8, u8)> {
+    let rgba = parse_color_rgba(&args.color)?;
+    if rgba.3 < u8::MAX && !args.over {
+        return Err(SdError::Other(format!(
+            "Color '{}' is translucent; add --over to blend it over the key's current image",
+            args.color
+        )));
+    }
+    Ok(rgba)
+}
 
-                if !args.continue_on_error {
-                    // Output results so far before returning error
-                    let summary = BatchSummary::new(results.len(), success_count, error_count);
-                    output.batch_fill_keys(&color_str, &results, &summary);
-                    return Err(e);
-                }
-            }
-        }
+/// The RGB to send for a fill color given in `space` (`--color-space`).
+fn upload_rgb(
+    device: &device::Device,
+    rgb: (u8, u8, u8),
+    space: image_ops::ColorSpace,
+) -> (u8, u8, u8) {
+    let upload = image_ops::to_device_rgb(rgb, space, device.info().model());
+    if upload != rgb {
+        tracing::debug!(?rgb, ?upload, %space, "Color corrected for upload");
     }
+    upload
+}
 
-    // Output final results
-    let summary = BatchSummary::new(keys.len(), success_count, error_count);
-    if !cli.quiet {
-        output.batch_fill_keys(&color_str, &results, &summary);
+fn cmd_overlay_key(cli: &Cli, args: &cli::OverlayKeyArgs, output: &dyn Output) -> Result<()> {
+    if cli.is_dry_run() {
+        return cmd_overlay_key_dry_run(cli, args);
     }
 
+    let result = overlay_key(cli, &open_device(cli)?, args)?;
+    if cli.use_json() {
+        output_json(cli, &result);
+    } else {
+        output.success(&format!(
+            "Drew {} over key {} ({}x{} at {}, {})",
+            result.overlay,
+            result.key,
+            result.region.width,
+            result.region.height,
+            result.region.x,
+            result.region.y
+        ));
+    }
     Ok(())
 }
 
-fn cmd_clear_keys(cli: &Cli, args: &cli::ClearKeysArgs, output: &dyn Output) -> Result<()> {
-    // Handle dry-run mode
-    if cli.is_dry_run() {
-        return cmd_clear_keys_dry_run(cli, args);
-    }
+/// Result of overlay-key.
+#[derive(Serialize)]
+struct OverlayKeyResult {
+    key: u8,
+    overlay: String,
+    position: image_ops::OverlayPosition,
+    #[serde(rename = "box")]
+    region: image_ops::CropBox,
+    ok: bool,
+}
 
-    let device = open_device(cli)?;
-    let device_info = device::get_device_info(&device);
+/// Draw `overlay-key`'s image over the key's tracked image.
+///
+/// Shared by the command and the `overlay-key` RPC.
+fn overlay_key(
+    cli: &Cli,
+    device: &device::Device,
+    args: &cli::OverlayKeyArgs,
+) -> Result<OverlayKeyResult> {
+    let region = overlay_key_image(device, args.key, &args.image, args.position, args.scale)?;
+    record_write_verification(device);
+    Ok(OverlayKeyResult {
+        key: cli.user_key(args.key),
+        overlay: data_uri::display_source(&args.image),
+        position: args.position,
+        region,
+        ok: true,
+    })
+}
 
-    // Determine which keys to clear
-    let keys = resolve_key_selection(
-        args.all,
-        args.range.as_deref(),
-        &args.keys,
-        device_info.key_count,
-    )?;
+/// Dry-run details for overlay-key.
+#[derive(Serialize)]
+struct OverlayKeyDryRunDetails {
+    key: u8,
+    overlay: String,
+    position: image_ops::OverlayPosition,
+    scale: f32,
+    key_size: image_ops::KeySize,
+    /// Where the overlay would be drawn, in key pixels.
+    #[serde(rename = "box")]
+    region: image_ops::CropBox,
+    /// Whether this process knows the key's current image to draw on.
+    base_known: bool,
+}
 
-    if keys.is_empty() {
-        return Err(SdError::Other(
-            "No keys specified. Use --all, --range, or --keys".to_string(),
+/// Dry-run handler for overlay-key: reports the computed overlay box.
+fn cmd_overlay_key_dry_run(cli: &Cli, args: &cli::OverlayKeyArgs) -> Result<()> {
+    let device_info = open_device(cli).ok().map(|d| device::get_device_info(&d));
+    let size = device_info.as_ref().map_or_else(
+        || device::DeviceInfo::for_model(cli.offline_model()).key_size(),
+        device::DeviceInfo::key_size,
+    );
+    let overlay = image_ops::open_for_size(&args.image, size.width, size.height)?;
+    let region = image_ops::overlay_box(size, overlay.dimensions(), args.position, args.scale)?;
+    let base_known = state::session_state().keys.contains_key(&args.key);
+
+    let mut warnings = Vec::new();
+    if !base_known {
+        warnings.push(format!(
+            "Key {}'s current image isn't known in this process, so the overlay would fail",
+            cli.user_key(args.key)
         ));
     }
 
-    // If --all is specified, use the optimized clear_all function
-    if args.all {
-        device::clear_all_keys(&device)?;
-        // Track state change
-        state::record::clear_all(device_info.key_count);
-        let results: Vec<BatchKeyResult> = (0..device_info.key_count)
-            .map(|k| BatchKeyResult::clear_success(k))
-            .collect();
-        let summary = BatchSummary::new(
-            device_info.key_count as usize,
-            device_info.key_count as usize,
-            0,
+    if cli.use_json() {
+        let mut errors = Vec::new();
+        if let Some(info) = device_info.as_ref().filter(|i| args.key >= i.key_count) {
+            errors.push(ValidationError {
+                field: "key".to_string(),
+                error: format!(
+                    "Key index {} is out of range (device has {} keys, valid: 0-{})",
+                    args.key,
+                    info.key_count,
+                    info.key_count - 1
+                ),
+                suggestion: Some(format!("Use a key index from 0 to {}", info.key_count - 1)),
+            });
+        }
+        let ctx = device_info.as_ref().map_or_else(
+            || {
+                warnings.push("Device not connected".to_string());
+                DeviceContext::disconnected(cli.serial.clone())
+            },
+            DeviceContext::from_info,
         );
-        if !cli.quiet {
-            output.batch_clear_keys(&results, &summary);
+        let details = OverlayKeyDryRunDetails {
+            key: cli.user_key(args.key),
+            overlay: data_uri::display_source(&args.image),
+            position: args.position,
+            scale: args.scale,
+            key_size: size,
+            region,
+            base_known,
+        };
+        let response = if errors.is_empty() {
+            DryRunResponse::success("overlay_key", details, ctx)
+        } else {
+            DryRunResponse::failure("overlay_key", "Validation failed", errors, details, ctx)
+        };
+        output_json(cli, &response.with_warnings(warnings));
+    } else {
+        println!(
+            "DRY RUN: Would draw {} over key {}",
+            data_uri::display_source(&args.image),
+            cli.user_key(args.key)
+        );
+        println!(
+            "  Box: {}x{} at ({}, {}) on a {size} key",
+            region.width, region.height, region.x, region.y
+        );
+        for warning in &warnings {
+            println!("  WARNING: {warning}");
         }
-        return Ok(());
     }
+    Ok(())
+}
 
-    // Clear individual keys
-    let mut results: Vec<BatchKeyResult> = Vec::new();
-    let mut success_count = 0;
-    let mut error_count = 0;
+/// Blend a translucent color over `key`'s tracked image.
+///
+/// The tracked state keeps the underlying image, so restoring the key
+/// removes the tint.
+fn blend_key_color(device: &device::Device, key: u8, rgba: (u8, u8, u8, u8)) -> Result<()> {
+    let info = device.info();
+    if key >= info.key_count {
+        return Err(SdError::InvalidKeyIndex {
+            index: key,
+            max: info.key_count,
+            max_idx: info.key_count - 1,
+        });
+    }
+    let base = tracked_key_image(key, info.key_size())?.ok_or_else(|| {
+        SdError::Other(format!(
+            "Key {key}'s current image isn't known, so there's nothing to blend over; \
+             set it in this session first (e.g. in `sd serve --stdio`) or use an opaque color"
+        ))
+    })?;
+    let tinted = image_ops::overlay_color(&base, rgba);
+    device::set_key_images(device, vec![(key, tinted)], &CancelToken::new())
+}
 
-    for key in &keys {
-        match device::clear_key(&device, *key) {
-            Ok(()) => {
-                success_count += 1;
-                // Track state change
-                state::record::clear_key(*key);
-                results.push(BatchKeyResult::clear_success(*key));
-            }
-            Err(e) => {
-                error_count += 1;
-                results.push(BatchKeyResult::clear_failure(*key, &e.to_string()));
+/// Composite `image` over part of `key`'s tracked image.
+///
+/// Like `fill-key --over`, the tracked state keeps the underlying image, so
+/// a second overlay replaces the first instead of stacking on it.
+fn overlay_key_image(
+    device: &device::Device,
+    key: u8,
+    image: &std::path::Path,
+    position: image_ops::OverlayPosition,
+    scale: f32,
+) -> Result<image_ops::CropBox> {
+    let info = device.info();
+    if key >= info.key_count {
+        return Err(SdError::InvalidKeyIndex {
+            index: key,
+            max: info.key_count,
+            max_idx: info.key_count - 1,
+        });
+    }
+    let size = info.key_size();
+    let overlay = image_ops::open_for_size(image, size.width, size.height)?;
+    let region = image_ops::overlay_box(size, overlay.dimensions(), position, scale)?;
+    let base = tracked_key_image(key, size)?.ok_or_else(|| {
+        SdError::Other(format!(
+            "Key {key}'s current image isn't known, so there's nothing to draw the overlay on; \
+             set it in this session first (e.g. in `sd serve --stdio`)"
+        ))
+    })?;
+    let composite = image_ops::composite_overlay(&base, &overlay, region);
+    device::set_key_images(device, vec![(key, composite)], &CancelToken::new())?;
+    Ok(region)
+}
 
-                if args.continue_on_error {
-                    // Continue processing other keys
-                } else {
-                    // Output results so far before returning error
-                    let summary = BatchSummary::new(keys.len(), success_count, error_count);
-                    if !cli.quiet {
-                        output.batch_clear_keys(&results, &summary);
-                    }
-                    return Err(e);
-                }
-            }
+/// The image session state says `key` shows, rendered at `size`.
+///
+/// Stream Decks can't read key images back, so a key this session hasn't
+/// touched has no known image (`None`) and nothing to draw over.
+fn tracked_key_image(key: u8, size: image_ops::KeySize) -> Result<Option<image::DynamicImage>> {
+    let tracked = state::session_state().keys.get(&key).cloned();
+    let solid = |(r, g, b): (u8, u8, u8)| {
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            size.width,
+            size.height,
+            image::Rgb([r, g, b]),
+        ))
+    };
+    match tracked {
+        Some(state::KeyState::Image { path }) => {
+            image_cache::load_for_key(&path, size, image_ops::ResizeStrategy::Fit).map(Some)
         }
+        Some(state::KeyState::Color { hex }) => Ok(Some(solid(parse_color(&hex)?))),
+        Some(state::KeyState::Cleared) => Ok(Some(solid((0, 0, 0)))),
+        None => Ok(None),
     }
+}
 
-    let summary = BatchSummary::new(keys.len(), success_count, error_count);
-    if !cli.quiet {
-        output.batch_clear_keys(&results, &summary);
+/// Print how an image will be scaled, cropped, or padded (human dry-run).
+fn print_framing(
+    source: (u32, u32),
+    size: image_ops::KeySize,
+    strategy: image_ops::ResizeStrategy,
+) {
+    let geometry = image_ops::resize_geometry(source, size.width, size.height, strategy);
+    println!(
+        "  Scale: {}x{} -> {}x{} ({strategy:?})",
+        source.0, source.1, geometry.scaled_width, geometry.scaled_height
+    );
+    if let Some(crop) = geometry.crop {
+        println!(
+            "  Crop: {}x{} at ({}, {})",
+            crop.width, crop.height, crop.x, crop.y
+        );
+    }
+    if let Some(pad) = geometry
+        .padding
+        .filter(|p| *p != image_ops::Padding::default())
+    {
+        println!(
+            "  Padding: top {}, right {}, bottom {}, left {}",
+            pad.top, pad.right, pad.bottom, pad.left
+        );
     }
+}
 
-    Ok(())
+/// Simulate the on-device look of a set-key image (`--simulate-display`).
+///
+/// Uses the image's average color; returns `None` if the flag is off or the
+/// image can't be read.
+fn simulate_image_display(args: &cli::SetKeyArgs) -> Option<SimulatedDisplay> {
+    if !args.simulate_display {
+        return None;
+    }
+    let img = image::open(&args.image).ok()?;
+    Some(SimulatedDisplay::new(image_ops::average_rgb(&img)))
 }
 
-/// Dry-run handler for clear-keys (batch) command.
+/// Print a simulated display color in human dry-run output.
+fn print_simulated_display(simulated: &SimulatedDisplay) {
+    let (r, g, b) = simulated.rgb;
+    println!(
+        "  On device (approximate): {} ({r}, {g}, {b})",
+        simulated.hex
+    );
+}
+
+/// Dry-run handler for fill-key command.
 #[allow(clippy::unnecessary_wraps)] // Consistent return type
-fn cmd_clear_keys_dry_run(cli: &Cli, args: &cli::ClearKeysArgs) -> Result<()> {
+fn cmd_fill_key_dry_run(cli: &Cli, args: &cli::FillKeyArgs) -> Result<()> {
+    // Validate color first
+    let (r, g, b, _) = fill_key_color_arg(args)?;
+    let color = (r, g, b);
+    let color_str = format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2);
+
     // Try to get device info for context
     let device_result = open_device(cli);
+    let model = device_result
+        .as_ref()
+        .map_or(Some(cli.offline_model()), |device| device.info().model());
+    let upload = image_ops::to_device_rgb(color, args.color_space, model);
 
     if cli.use_json() {
         let (device_ctx, device_info) = match &device_result {
@@ -1308,117 +2498,80 @@ fn cmd_clear_keys_dry_run(cli: &Cli, args: &cli::ClearKeysArgs) -> Result<()> {
             Err(_) => (DeviceContext::disconnected(cli.serial.clone()), None),
         };
 
-        // We need key_count to resolve selection - use device info if available
-        let key_count = device_info.as_ref().map(|i| i.key_count).unwrap_or(32);
-
-        // Resolve key selection
-        let keys_result =
-            resolve_key_selection(args.all, args.range.as_deref(), &args.keys, key_count);
+        let mut details =
+            FillKeyDryRunDetails::new(cli.user_key(args.key), color_str.clone(), color);
+        if upload != color {
+            details = details.with_upload_rgb(upload);
+        }
+        if args.simulate_display {
+            details = details.with_simulated_display(SimulatedDisplay::new(upload));
+        }
 
+        // Build response based on validation
         let mut errors = Vec::new();
         let mut warnings = Vec::new();
 
+        // Check key index if device connected
+        if let Some(ref info) = device_info {
+            if args.key >= info.key_count {
+                errors.push(ValidationError {
+                    field: "key".to_string(),
+                    error: format!(
+                        "Key index {} is out of range (device has {} keys, valid: 0-{})",
+                        args.key,
+                        info.key_count,
+                        info.key_count - 1
+                    ),
+                    suggestion: Some(format!("Use a key index from 0 to {}", info.key_count - 1)),
+                });
+            }
+        }
+
         // Add device warning if not connected
         if let Err(ref e) = device_result {
             warnings.push(format!("Device not connected: {e}"));
-            warnings.push("Using default key count of 32 for validation".to_string());
         }
 
-        match keys_result {
-            Ok(keys) => {
-                if keys.is_empty() {
-                    errors.push(ValidationError {
-                        field: "keys".to_string(),
-                        error: "No keys specified".to_string(),
-                        suggestion: Some(
-                            "Use --all, --range, or --keys to specify which keys to clear"
-                                .to_string(),
-                        ),
-                    });
-                    let details = ClearKeysDryRunDetails::new(vec![]);
-                    let response = DryRunResponse::failure(
-                        "clear_keys",
-                        "No keys specified",
-                        errors,
-                        details,
-                        device_ctx,
-                    )
-                    .with_warnings(warnings);
-                    output_json(cli, &response);
-                } else {
-                    let details = ClearKeysDryRunDetails::new(keys);
-                    let response = DryRunResponse::success("clear_keys", details, device_ctx)
-                        .with_warnings(warnings);
-                    output_json(cli, &response);
-                }
-            }
-            Err(e) => {
-                errors.push(ValidationError {
-                    field: "keys".to_string(),
-                    error: e.to_string(),
-                    suggestion: Some("Check the key range or indices specified".to_string()),
-                });
-                let details = ClearKeysDryRunDetails::new(vec![]);
-                let response = DryRunResponse::failure(
-                    "clear_keys",
-                    "Invalid key selection",
-                    errors,
-                    details,
-                    device_ctx,
-                )
-                .with_warnings(warnings);
-                output_json(cli, &response);
-            }
-        }
+        let response = if errors.is_empty() {
+            DryRunResponse::success("fill_key", details, device_ctx).with_warnings(warnings)
+        } else {
+            DryRunResponse::failure("fill_key", "Validation failed", errors, details, device_ctx)
+                .with_warnings(warnings)
+        };
+
+        output_json(cli, &response);
     } else {
         // Human-readable dry-run output
+        println!(
+            "DRY RUN: Would fill key {} with color {}",
+            cli.user_key(args.key),
+            color_str
+        );
+        println!("  RGB: ({}, {}, {})", color.0, color.1, color.2);
+        if upload != color {
+            println!(
+                "  Uploaded as: ({}, {}, {}) (color space: {})",
+                upload.0, upload.1, upload.2, args.color_space
+            );
+        }
+        if args.simulate_display {
+            print_simulated_display(&SimulatedDisplay::new(upload));
+        }
+
         match device_result {
             Ok(device) => {
                 let info = device::get_device_info(&device);
-                let keys_result = resolve_key_selection(
-                    args.all,
-                    args.range.as_deref(),
-                    &args.keys,
-                    info.key_count,
-                );
-
-                match keys_result {
-                    Ok(keys) => {
-                        if keys.is_empty() {
-                            println!("DRY RUN: No keys specified");
-                            println!(
-                                "  Use --all, --range, or --keys to specify which keys to clear"
-                            );
-                        } else if args.all {
-                            println!(
-                                "DRY RUN: Would clear all {} keys (set to black)",
-                                info.key_count
-                            );
-                        } else {
-                            println!("DRY RUN: Would clear {} keys: {:?}", keys.len(), keys);
-                        }
-                        println!("  Device: {} (serial: {})", info.product_name, info.serial);
-                    }
-                    Err(e) => {
-                        println!("DRY RUN: Invalid key selection: {}", e);
-                        println!("  Device: {} (serial: {})", info.product_name, info.serial);
-                    }
+                println!("  Device: {} (serial: {})", info.product_name, info.serial);
+                if args.key >= info.key_count {
+                    println!(
+                        "  WARNING: Key {} is out of range (max: {})",
+                        args.key,
+                        info.key_count - 1
+                    );
                 }
             }
             Err(e) => {
-                println!("DRY RUN: Would clear keys (set to black)");
                 println!("  Device: not connected ({})", e);
-
-                // Try to show what would be cleared based on args
-                if args.all {
-                    println!("  Selection: all keys");
-                } else if let Some(ref range) = args.range {
-                    println!("  Selection: keys in range {}", range);
-                } else if !args.keys.is_empty() {
-                    println!("  Selection: keys {:?}", args.keys);
-                } else {
-                    println!("  Selection: none specified");
-                }
             }
         }
     }
@@ -1426,1452 +2579,5077 @@ fn cmd_clear_keys_dry_run(cli: &Cli, args: &cli::ClearKeysArgs) -> Result<()> {
     Ok(())
 }
 
-/// Resolves key selection from --all, --range, or --keys options.
-fn resolve_key_selection(
-    all: bool,
-    range: Option<&str>,
-    keys: &[u8],
-    key_count: u8,
-) -> Result<Vec<u8>> {
-    if all {
-        return Ok((0..key_count).collect());
+fn cmd_fill_all(cli: &Cli, args: &cli::FillAllArgs, output: &dyn Output) -> Result<()> {
+    if args.randomize {
+        return cmd_fill_scheme(cli, batch::ColorScheme::random(args.seed), output);
+    }
+    if cli.is_dry_run() {
+        return cmd_fill_all_dry_run(cli, args);
     }
 
-    if let Some(range_str) = range {
-        return parse_key_range(range_str, key_count);
+    let device = open_device(cli)?;
+    let info = device::get_device_info(&device);
+    let color = parse_color(args.color.as_deref().unwrap_or_default())?;
+    device::fill_all_keys_color(&device, upload_rgb(&device, color, args.color_space))?;
+    record_write_verification(&device);
+
+    // Track state change for all keys
+    let color_str = format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2);
+    for key in 0..info.key_count {
+        state::record::fill_key(key, color_str.clone());
     }
 
-    if !keys.is_empty() {
-        // Validate all keys are in range
-        for key in keys {
-            if *key >= key_count {
-                return Err(SdError::InvalidKeyIndex {
-                    index: *key,
-                    max: key_count,
-                    max_idx: key_count - 1,
-                });
-            }
+    output.all_filled(&color_str);
+    Ok(())
+}
+
+/// Dry-run details for fill-all command.
+#[derive(Serialize)]
+struct FillAllDryRunDetails {
+    color: String,
+    rgb: (u8, u8, u8),
+    #[serde(skip_serializing_if = "Option::is_none")]
+    upload_rgb: Option<(u8, u8, u8)>,
+    key_count: u8,
+}
+
+/// Dry-run handler for fill-all command.
+fn cmd_fill_all_dry_run(cli: &Cli, args: &cli::FillAllArgs) -> Result<()> {
+    let color = parse_color(args.color.as_deref().unwrap_or_default())?;
+    let color_str = format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2);
+    let device_info = open_device(cli).ok().map(|d| device::get_device_info(&d));
+    let layout = device_info
+        .clone()
+        .unwrap_or_else(|| device::DeviceInfo::for_model(cli.offline_model()));
+    let upload = image_ops::to_device_rgb(color, args.color_space, layout.model());
+
+    if cli.use_json() {
+        let mut warnings = Vec::new();
+        let ctx = device_info.as_ref().map_or_else(
+            || {
+                warnings.push("Device not connected".to_string());
+                DeviceContext::disconnected(cli.serial.clone())
+            },
+            DeviceContext::from_info,
+        );
+        let details = FillAllDryRunDetails {
+            color: color_str,
+            rgb: color,
+            upload_rgb: (upload != color).then_some(upload),
+            key_count: layout.key_count,
+        };
+        output_json(
+            cli,
+            &DryRunResponse::success("fill_all", details, ctx).with_warnings(warnings),
+        );
+    } else {
+        println!(
+            "DRY RUN: Would fill all {} keys with color {color_str}",
+            layout.key_count
+        );
+        if upload != color {
+            println!(
+                "  Uploaded as: ({}, {}, {}) (color space: {})",
+                upload.0, upload.1, upload.2, args.color_space
+            );
+        }
+        match &device_info {
+            Some(info) => println!("  Device: {} ({})", info.product_name, info.serial),
+            None => println!("  Device: not connected ({} layout)", layout.product_name),
         }
-        return Ok(keys.to_vec());
     }
 
-    Ok(vec![])
+    Ok(())
 }
 
-/// Parses a key range string like "0-7" into a vector of key indices.
-fn parse_key_range(range: &str, key_count: u8) -> Result<Vec<u8>> {
-    let parts: Vec<&str> = range.split('-').collect();
-    if parts.len() != 2 {
-        return Err(SdError::Other(format!(
-            "Invalid range format '{range}': expected START-END (e.g., 0-7)"
-        )));
+/// Fill every key with its own generated color (`fill-all --randomize`,
+/// `rainbow`).
+fn cmd_fill_scheme(cli: &Cli, scheme: batch::ColorScheme, output: &dyn Output) -> Result<()> {
+    if cli.is_dry_run() {
+        return cmd_fill_scheme_dry_run(cli, scheme);
     }
 
-    let start: u8 = parts[0]
-        .parse()
-        .map_err(|_| SdError::Other(format!("Invalid range start '{}': not a number", parts[0])))?;
+    let device = open_device(cli)?;
+    let info = device::get_device_info(&device);
+    let colors = scheme.colors(info.cols, info.rows);
 
-    let end: u8 = parts[1]
-        .parse()
-        .map_err(|_| SdError::Other(format!("Invalid range end '{}': not a number", parts[1])))?;
+    let mut results: Vec<BatchKeyResult> = Vec::new();
+    let mut success_count = 0;
+    let mut error_count = 0;
+    let cancel = CancelToken::on_ctrl_c();
+    let mut pacer = cli.write_pacer();
 
-    if start > end {
-        return Err(SdError::Other(format!(
-            "Invalid range '{range}': start ({start}) must be <= end ({end})"
-        )));
+    for (key, color) in (0..info.key_count).zip(colors) {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let color_str = format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2);
+        pacer.before_write();
+        match device::fill_key_color(&device, key, color) {
+            Ok(()) => {
+                success_count += 1;
+                state::record::fill_key(key, color_str.clone());
+                results.push(BatchKeyResult::fill_success(key, &color_str));
+            }
+            Err(e) => {
+                error_count += 1;
+                results.push(BatchKeyResult::fill_failure(
+                    key,
+                    &color_str,
+                    &e.to_string(),
+                ));
+                record_write_verification(&device);
+                // Output results so far before returning error
+                sort_batch_results(&mut results);
+                let summary = BatchSummary::new(results.len(), success_count, error_count);
+                output.batch_fill_scheme(&scheme, &results, &summary);
+                return Err(e);
+            }
+        }
     }
+    record_write_verification(&device);
 
-    if end >= key_count {
-        return Err(SdError::InvalidKeyIndex {
-            index: end,
-            max: key_count,
-            max_idx: key_count - 1,
-        });
+    sort_batch_results(&mut results);
+    let total = usize::from(info.key_count);
+    let summary = BatchSummary::new(total, success_count, error_count)
+        .with_skipped(total - success_count - error_count);
+    if !cli.quiet {
+        output.batch_fill_scheme(&scheme, &results, &summary);
     }
 
-    Ok((start..=end).collect())
+    cancel.check()
 }
 
-/// Maximum backoff delay for reconnection (30 seconds).
-const MAX_RECONNECT_DELAY_MS: u64 = 30_000;
-/// Backoff multiplier for exponential backoff.
-const RECONNECT_BACKOFF_FACTOR: f64 = 1.5;
+/// Dry-run details for a generated fill.
+#[derive(Serialize)]
+struct FillSchemeDryRunDetails {
+    #[serde(flatten)]
+    scheme: batch::ColorScheme,
+    operations: Vec<FillSchemeDryRunOperation>,
+}
 
-fn cmd_watch(cli: &Cli, args: &cli::WatchArgs, output: &dyn Output) -> Result<()> {
-    let mut device = open_device(cli)?;
-    let serial = cli.serial.clone();
+/// Per-key color a generated fill would use.
+#[derive(Serialize)]
+struct FillSchemeDryRunOperation {
+    key: u8,
+    color: String,
+}
 
-    if !cli.quiet && !cli.use_json() {
-        output.info("Watching for button presses (Ctrl+C to stop)...");
-        if args.reconnect {
-            output.info("Auto-reconnect enabled");
+/// Dry-run handler for generated fills: lists the color for every key.
+#[allow(clippy::unnecessary_wraps)] // Consistent return type
+fn cmd_fill_scheme_dry_run(cli: &Cli, scheme: batch::ColorScheme) -> Result<()> {
+    let device_info = open_device(cli).ok().map(|d| device::get_device_info(&d));
+    let layout = device_info
+        .clone()
+        .unwrap_or_else(|| device::DeviceInfo::for_model(cli.offline_model()));
+    let operations: Vec<FillSchemeDryRunOperation> = (0..layout.key_count)
+        .zip(scheme.colors(layout.cols, layout.rows))
+        .map(|(key, (r, g, b))| FillSchemeDryRunOperation {
+            key: cli.user_key(key),
+            color: format!("#{r:02x}{g:02x}{b:02x}"),
+        })
+        .collect();
+
+    if cli.use_json() {
+        let mut warnings = Vec::new();
+        let ctx = device_info.as_ref().map_or_else(
+            || {
+                warnings.push("Device not connected".to_string());
+                DeviceContext::disconnected(cli.serial.clone())
+            },
+            DeviceContext::from_info,
+        );
+        let action = match scheme {
+            batch::ColorScheme::Random { .. } => "fill_all",
+            batch::ColorScheme::Rainbow => "rainbow",
+        };
+        let details = FillSchemeDryRunDetails { scheme, operations };
+        output_json(
+            cli,
+            &DryRunResponse::success(action, details, ctx).with_warnings(warnings),
+        );
+    } else {
+        println!(
+            "DRY RUN: Would fill {} keys with {scheme}",
+            operations.len()
+        );
+        match &device_info {
+            Some(info) => println!("  Device: {} ({})", info.product_name, info.serial),
+            None => println!("  Device: not connected ({} layout)", layout.product_name),
+        }
+        println!();
+        for op in &operations {
+            println!("  Key {}: {}", op.key, op.color);
         }
     }
 
-    // Track reconnection state
-    let mut reconnect_attempts: u32 = 0;
-    let mut reconnect_delay = args.reconnect_delay;
+    Ok(())
+}
 
-    loop {
-        // Try to watch for events using the output trait
-        let result = watch_buttons_with_output(&device, output, args.once, args.timeout);
+fn cmd_fill_from_image(
+    cli: &Cli,
+    args: &cli::FillFromImageArgs,
+    output: &dyn Output,
+) -> Result<()> {
+    let img = image::open(&args.image).map_err(|e| SdError::ImageProcessing(e.to_string()))?;
 
-        match result {
-            Ok(()) => {
-                // Normal exit (timeout, --once, or clean shutdown)
-                return Ok(());
-            }
-            Err(ref e) if e.is_connection_error() && args.reconnect => {
-                reconnect_attempts += 1;
+    if cli.is_dry_run() {
+        return cmd_fill_from_image_dry_run(cli, args, &img);
+    }
 
-                // Check max attempts (0 = unlimited)
-                if args.max_reconnect_attempts > 0
-                    && reconnect_attempts > args.max_reconnect_attempts
-                {
-                    // Emit final disconnect event
-                    output.warning(&format!("Disconnected: {} (reconnecting: false)", e));
+    let device = open_device(cli)?;
+    let info = device::get_device_info(&device);
+    let colors = image_ops::grid_colors(&img, info.rows, info.cols, args.mode);
 
-                    if !cli.quiet && !cli.use_json() {
-                        output.warning(&format!(
-                            "Max reconnection attempts ({}) exceeded",
-                            args.max_reconnect_attempts
-                        ));
-                    }
-                    return Err(SdError::Other(format!(
-                        "Connection lost after {} reconnection attempts: {}",
-                        reconnect_attempts - 1,
-                        e
-                    )));
-                }
+    let mut results: Vec<BatchKeyResult> = Vec::new();
+    let mut success_count = 0;
+    let mut error_count = 0;
+    let cancel = CancelToken::on_ctrl_c();
+    let mut pacer = cli.write_pacer();
 
-                // Emit disconnect event
-                output.warning(&format!(
-                    "Connection lost ({}), reconnecting in {}ms (attempt {}{})...",
-                    e,
-                    reconnect_delay,
-                    reconnect_attempts,
-                    if args.max_reconnect_attempts > 0 {
-                        format!("/{}", args.max_reconnect_attempts)
-                    } else {
-                        String::new()
-                    }
+    for (key, color) in (0..info.key_count).zip(colors) {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let color_str = format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2);
+        pacer.before_write();
+        match device::fill_key_color(&device, key, color) {
+            Ok(()) => {
+                success_count += 1;
+                state::record::fill_key(key, color_str.clone());
+                results.push(BatchKeyResult::fill_success(key, &color_str));
+            }
+            Err(e) => {
+                error_count += 1;
+                results.push(BatchKeyResult::fill_failure(
+                    key,
+                    &color_str,
+                    &e.to_string(),
                 ));
 
-                // Wait before reconnecting
-                std::thread::sleep(std::time::Duration::from_millis(reconnect_delay));
-
-                // Try to reconnect
-                match device::open_device(serial.as_deref()) {
-                    Ok(new_device) => {
-                        device = new_device;
-
-                        // Emit reconnected event
-                        output.success(&format!(
-                            "Reconnected successfully (attempt {})",
-                            reconnect_attempts
-                        ));
-
-                        // Reset backoff on successful connection
-                        reconnect_delay = args.reconnect_delay;
-                        reconnect_attempts = 0;
-                    }
-                    Err(conn_err) => {
-                        tracing::debug!(error = %conn_err, "Reconnection attempt failed");
-
-                        // Increase backoff with cap
-                        #[allow(clippy::cast_possible_truncation)]
-                        #[allow(clippy::cast_sign_loss)]
-                        {
-                            reconnect_delay = ((reconnect_delay as f64 * RECONNECT_BACKOFF_FACTOR)
-                                as u64)
-                                .min(MAX_RECONNECT_DELAY_MS);
-                        }
-                        // Continue loop to try again
-                    }
+                if !args.continue_on_error {
+                    record_write_verification(&device);
+                    // Output results so far before returning error
+                    sort_batch_results(&mut results);
+                    let summary = BatchSummary::new(results.len(), success_count, error_count);
+                    output.batch_fill_from_image(&args.image, args.mode, &results, &summary);
+                    return Err(e);
                 }
             }
-            Err(e) => {
-                // Non-connection error or reconnect disabled
-                return Err(e);
-            }
         }
     }
+    record_write_verification(&device);
+
+    sort_batch_results(&mut results);
+    let total = usize::from(info.key_count);
+    let summary = BatchSummary::new(total, success_count, error_count)
+        .with_skipped(total - success_count - error_count);
+    if !cli.quiet {
+        output.batch_fill_from_image(&args.image, args.mode, &results, &summary);
+    }
+
+    cancel.check()
 }
 
-/// Watch for button presses using the Output trait.
-///
-/// This function provides the watch loop that uses the Output trait
-/// for all button event reporting, enabling both robot and human modes.
-fn watch_buttons_with_output(
-    device: &device::Device,
-    output: &dyn Output,
-    once: bool,
-    timeout_secs: u64,
+/// Dry-run details for fill-from-image command.
+#[derive(Serialize)]
+struct FillFromImageDryRunDetails {
+    source: String,
+    mode: image_ops::PaletteMode,
+    operations: Vec<FillSchemeDryRunOperation>,
+}
+
+/// Dry-run handler for fill-from-image command: lists the color for every key.
+#[allow(clippy::unnecessary_wraps)] // Consistent return type
+fn cmd_fill_from_image_dry_run(
+    cli: &Cli,
+    args: &cli::FillFromImageArgs,
+    img: &image::DynamicImage,
 ) -> Result<()> {
-    use std::time::{Duration, Instant};
+    let device_info = open_device(cli).ok().map(|d| device::get_device_info(&d));
+    let layout = device_info
+        .clone()
+        .unwrap_or_else(|| device::DeviceInfo::for_model(cli.offline_model()));
+    let operations: Vec<FillSchemeDryRunOperation> = (0..layout.key_count)
+        .zip(image_ops::grid_colors(
+            img,
+            layout.rows,
+            layout.cols,
+            args.mode,
+        ))
+        .map(|(key, (r, g, b))| FillSchemeDryRunOperation {
+            key: cli.user_key(key),
+            color: format!("#{r:02x}{g:02x}{b:02x}"),
+        })
+        .collect();
 
-    let start = Instant::now();
-    let timeout = if timeout_secs == 0 {
-        None
+    if cli.use_json() {
+        let mut warnings = Vec::new();
+        let ctx = device_info.as_ref().map_or_else(
+            || {
+                warnings.push("Device not connected".to_string());
+                DeviceContext::disconnected(cli.serial.clone())
+            },
+            DeviceContext::from_info,
+        );
+        let details = FillFromImageDryRunDetails {
+            source: args.image.display().to_string(),
+            mode: args.mode,
+            operations,
+        };
+        output_json(
+            cli,
+            &DryRunResponse::success("fill_from_image", details, ctx).with_warnings(warnings),
+        );
     } else {
-        Some(Duration::from_secs(timeout_secs))
-    };
+        println!(
+            "DRY RUN: Would fill {} keys with the {} colors of {}",
+            operations.len(),
+            args.mode,
+            args.image.display()
+        );
+        match &device_info {
+            Some(info) => println!("  Device: {} ({})", info.product_name, info.serial),
+            None => println!("  Device: not connected ({} layout)", layout.product_name),
+        }
+        println!();
+        for op in &operations {
+            println!("  Key {}: {}", op.key, op.color);
+        }
+    }
 
-    // We need to access device internals - use the existing watch function
-    // but intercept events. For now, delegate to read_button_states in a loop.
-    let mut last_states = vec![false; device.info().key_count as usize];
+    Ok(())
+}
 
-    loop {
-        // Check timeout
-        if let Some(t) = timeout {
-            if start.elapsed() >= t {
-                break;
-            }
-        }
+fn cmd_fill_keys(cli: &Cli, args: &cli::FillKeysArgs, output: &dyn Output) -> Result<()> {
+    if cli.is_dry_run() {
+        return cmd_fill_keys_dry_run(cli, args);
+    }
 
-        // Read current states
-        let states = device::read_button_states(device);
+    let device = open_device(cli)?;
+    let device_info = device::get_device_info(&device);
+    let color = parse_color(&args.color)?;
+    let color_str = format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2);
+    let upload = upload_rgb(&device, color, args.color_space);
 
-        // Detect changes
-        for (key, (&current, &previous)) in states.iter().zip(last_states.iter()).enumerate() {
-            if current && !previous {
-                // Button pressed
-                #[allow(clippy::cast_possible_truncation)]
-                let event = device::ButtonEvent {
-                    key: key as u8,
-                    pressed: true,
-                    timestamp_ms: start.elapsed().as_millis().min(u128::from(u64::MAX)) as u64,
-                };
-                output.button_event(&event);
+    // Determine which keys to fill
+    let keys = resolve_key_selection(
+        args.all,
+        args.range.as_deref(),
+        &args.keys,
+        device_info.key_count,
+    )?;
 
-                if once {
-                    return Ok(());
+    if keys.is_empty() {
+        return Err(SdError::Other(
+            "No keys specified. Use --all, --range, or --keys".to_string(),
+        ));
+    }
+
+    // Fill keys with color
+    let mut results: Vec<BatchKeyResult> = Vec::new();
+    let mut success_count = 0;
+    let mut error_count = 0;
+    let cancel = CancelToken::on_ctrl_c();
+    let mut pacer = cli.write_pacer();
+
+    for key in &keys {
+        if cancel.is_cancelled() {
+            break;
+        }
+        pacer.before_write();
+        match device::fill_key_color(&device, *key, upload) {
+            Ok(()) => {
+                success_count += 1;
+                // Track state change
+                state::record::fill_key(*key, color_str.clone());
+                results.push(BatchKeyResult::fill_success(*key, &color_str));
+            }
+            Err(e) => {
+                error_count += 1;
+                results.push(BatchKeyResult::fill_failure(
+                    *key,
+                    &color_str,
+                    &e.to_string(),
+                ));
+
+                if !args.continue_on_error {
+                    record_write_verification(&device);
+                    // Output results so far before returning error
+                    sort_batch_results(&mut results);
+                    let summary = BatchSummary::new(results.len(), success_count, error_count);
+                    output.batch_fill_keys(&color_str, &results, &summary);
+                    return Err(e);
                 }
-            } else if !current && previous {
-                // Button released
-                #[allow(clippy::cast_possible_truncation)]
-                let event = device::ButtonEvent {
-                    key: key as u8,
-                    pressed: false,
-                    timestamp_ms: start.elapsed().as_millis().min(u128::from(u64::MAX)) as u64,
-                };
-                output.button_event(&event);
             }
         }
+    }
 
-        last_states = states;
+    record_write_verification(&device);
 
-        // Small sleep to avoid busy-waiting
-        std::thread::sleep(Duration::from_millis(50));
+    // Output final results
+    sort_batch_results(&mut results);
+    let summary = BatchSummary::new(keys.len(), success_count, error_count);
+    if !cli.quiet {
+        output.batch_fill_keys(&color_str, &results, &summary);
     }
 
-    Ok(())
+    cancel.check()
 }
 
-/// Connection events emitted during watch with reconnect.
+/// Dry-run details for fill-keys command.
 #[derive(Serialize)]
-#[serde(tag = "event", rename_all = "snake_case")]
-enum WatchConnectionEvent {
-    Disconnected { reason: String, reconnecting: bool },
-    Reconnecting { attempt: u32, delay_ms: u64 },
-    Reconnected { attempt: u32 },
+struct FillKeysDryRunDetails {
+    color: String,
+    rgb: (u8, u8, u8),
+    #[serde(skip_serializing_if = "Option::is_none")]
+    upload_rgb: Option<(u8, u8, u8)>,
+    keys: Vec<u8>,
+    total_count: usize,
 }
 
-/// Emits a watch connection event in robot mode.
-fn emit_watch_event(cli: &Cli, event: WatchConnectionEvent) {
+/// Dry-run handler for fill-keys command.
+fn cmd_fill_keys_dry_run(cli: &Cli, args: &cli::FillKeysArgs) -> Result<()> {
+    let color = parse_color(&args.color)?;
+    let color_str = format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2);
+    let device_info = open_device(cli).ok().map(|d| device::get_device_info(&d));
+    let layout = device_info
+        .clone()
+        .unwrap_or_else(|| device::DeviceInfo::for_model(cli.offline_model()));
+    let upload = image_ops::to_device_rgb(color, args.color_space, layout.model());
+    let keys = resolve_key_selection(
+        args.all,
+        args.range.as_deref(),
+        &args.keys,
+        layout.key_count,
+    )?;
+    if keys.is_empty() {
+        return Err(SdError::Other(
+            "No keys specified. Use --all, --range, or --keys".to_string(),
+        ));
+    }
+    let keys: Vec<u8> = keys.into_iter().map(|key| cli.user_key(key)).collect();
+
     if cli.use_json() {
-        let json = if cli.use_compact_json() {
-            serde_json::to_string(&event).unwrap_or_default()
-        } else {
-            serde_json::to_string_pretty(&event).unwrap_or_default()
+        let mut warnings = Vec::new();
+        let ctx = device_info.as_ref().map_or_else(
+            || {
+                warnings.push("Device not connected".to_string());
+                DeviceContext::disconnected(cli.serial.clone())
+            },
+            DeviceContext::from_info,
+        );
+        let details = FillKeysDryRunDetails {
+            color: color_str,
+            rgb: color,
+            upload_rgb: (upload != color).then_some(upload),
+            total_count: keys.len(),
+            keys,
         };
-        println!("{json}");
+        output_json(
+            cli,
+            &DryRunResponse::success("fill_keys", details, ctx).with_warnings(warnings),
+        );
+    } else {
+        println!(
+            "DRY RUN: Would fill {} keys with color {color_str}",
+            keys.len()
+        );
+        match &device_info {
+            Some(info) => println!("  Device: {} ({})", info.product_name, info.serial),
+            None => println!("  Device: not connected ({} layout)", layout.product_name),
+        }
+        println!();
+        for key in &keys {
+            println!("  Key {key}: {color_str}");
+        }
     }
-}
 
-fn cmd_read(cli: &Cli, _args: &cli::ReadArgs, output: &dyn Output) -> Result<()> {
-    let device = open_device(cli)?;
-    let states = device::read_button_states(&device);
-    output.button_states(&states);
     Ok(())
 }
 
-#[allow(clippy::unnecessary_wraps)] // Will return errors when implemented
-fn cmd_init(cli: &Cli, args: &cli::InitArgs) -> Result<()> {
-    let _ = (cli, args); // TODO: implement
-    eprintln!("Config init not yet implemented");
-    Ok(())
-}
+fn cmd_fill_palette(cli: &Cli, args: &cli::FillPaletteArgs, output: &dyn Output) -> Result<()> {
+    let numbering = cli.key_numbering;
+    let mut entries = batch::load_palette(&args.palette, numbering.base())
+        .map_err(|e| SdError::ConfigInvalid(e.to_string()))?;
+    if !numbering.is_native() {
+        for entry in &mut entries {
+            entry.key = numbering.to_native(entry.key).map_err(|e| {
+                SdError::ConfigInvalid(format!(
+                    "{} line {}: {e}",
+                    args.palette.display(),
+                    entry.line
+                ))
+            })?;
+        }
+    }
 
-#[allow(clippy::unnecessary_wraps)] // Will return errors when implemented
-fn cmd_config(cli: &Cli, args: &cli::ConfigArgs) -> Result<()> {
-    let _ = (cli, args); // TODO: implement
-    eprintln!("Config show not yet implemented");
-    Ok(())
+    // Validate every color up front so a typo doesn't leave a half-filled deck
+    let mut colors = Vec::with_capacity(entries.len());
+    let mut invalid = Vec::new();
+    for entry in &entries {
+        match parse_color(&entry.color) {
+            Ok(color) => colors.push(Some(color)),
+            Err(e) => {
+                colors.push(None);
+                invalid.push(format!("line {}: {e}", entry.line));
+            }
+        }
+    }
+
+    if cli.is_dry_run() {
+        return cmd_fill_palette_dry_run(cli, args, &entries, &colors);
+    }
+
+    if entries.is_empty() {
+        return Err(SdError::ConfigInvalid(format!(
+            "Palette {} has no colors",
+            args.palette.display()
+        )));
+    }
+    if !invalid.is_empty() {
+        return Err(SdError::ConfigInvalid(format!(
+            "Invalid colors in palette {}: {}",
+            args.palette.display(),
+            invalid.join("; ")
+        )));
+    }
+
+    let device = open_device(cli)?;
+
+    let mut results: Vec<BatchKeyResult> = Vec::new();
+    let mut success_count = 0;
+    let mut error_count = 0;
+    let cancel = CancelToken::on_ctrl_c();
+    let mut pacer = cli.write_pacer();
+
+    for (entry, color) in entries.iter().zip(colors.into_iter().flatten()) {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let color_str = format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2);
+        pacer.before_write();
+        match device::fill_key_color(&device, entry.key, color) {
+            Ok(()) => {
+                success_count += 1;
+                // Track state change
+                state::record::fill_key(entry.key, color_str.clone());
+                results.push(BatchKeyResult::fill_success(entry.key, &color_str));
+            }
+            Err(e) => {
+                error_count += 1;
+                results.push(BatchKeyResult::fill_failure(
+                    entry.key,
+                    &color_str,
+                    &e.to_string(),
+                ));
+
+                if !args.continue_on_error {
+                    record_write_verification(&device);
+                    // Output results so far before returning error
+                    sort_batch_results(&mut results);
+                    let summary = BatchSummary::new(results.len(), success_count, error_count);
+                    output.batch_fill_palette(&args.palette, &results, &summary);
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    record_write_verification(&device);
+
+    // Output final results
+    sort_batch_results(&mut results);
+    let skipped = entries.len() - success_count - error_count;
+    let summary =
+        BatchSummary::new(entries.len(), success_count, error_count).with_skipped(skipped);
+    if !cli.quiet {
+        output.batch_fill_palette(&args.palette, &results, &summary);
+    }
+
+    cancel.check()
 }
 
-/// Validate a declarative configuration file without applying it.
-fn cmd_validate(_cli: &Cli, args: &cli::ValidateArgs, output: &dyn Output) -> Result<()> {
-    use config::declarative::{ConfigFormat, load_config};
-    use output::ValidationResult;
-    use tracing::{debug, info};
+/// Dry-run details for fill-palette command.
+#[derive(Serialize)]
+struct FillPaletteDryRunDetails {
+    palette: String,
+    operations: Vec<FillPaletteDryRunOperation>,
+}
 
-    info!(config = %args.config.display(), "Validating configuration file");
+/// Per-key dry-run operation for fill-palette.
+#[derive(Serialize)]
+struct FillPaletteDryRunOperation {
+    key: u8,
+    line: usize,
+    color: String,
+    would_succeed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
 
-    let mut result = ValidationResult::new(&args.config);
+/// Dry-run handler for fill-palette command: lists the key-to-color mapping.
+#[allow(clippy::unnecessary_wraps)] // Consistent return type
+fn cmd_fill_palette_dry_run(
+    cli: &Cli,
+    args: &cli::FillPaletteArgs,
+    entries: &[batch::PaletteEntry],
+    colors: &[Option<(u8, u8, u8)>],
+) -> Result<()> {
+    // Try to get device info for context (may fail if no device connected)
+    let device_info = open_device(cli).ok().map(|d| device::get_device_info(&d));
+
+    let mut errors = Vec::new();
+    let operations: Vec<FillPaletteDryRunOperation> = entries
+        .iter()
+        .zip(colors)
+        .map(|(entry, color)| {
+            let error = match (color, &device_info) {
+                (None, _) => Some(format!("Invalid color '{}'", entry.color)),
+                (Some(_), Some(info)) if entry.key >= info.key_count => Some(format!(
+                    "Key index {} is out of range (device has {} keys, valid: 0-{})",
+                    entry.key,
+                    info.key_count,
+                    info.key_count.saturating_sub(1)
+                )),
+                _ => None,
+            };
+            if let Some(ref e) = error {
+                errors.push(ValidationError {
+                    field: format!("palette[line {}]", entry.line),
+                    error: e.clone(),
+                    suggestion: color.is_none().then(|| format!("Use {}", color::HEX_FORMS)),
+                });
+            }
+            FillPaletteDryRunOperation {
+                key: cli.user_key(entry.key),
+                line: entry.line,
+                color: color.map_or_else(
+                    || entry.color.clone(),
+                    |(r, g, b)| format!("#{r:02x}{g:02x}{b:02x}"),
+                ),
+                would_succeed: error.is_none(),
+                error,
+            }
+        })
+        .collect();
 
-    // Phase 1: Check file exists
-    if !args.config.exists() {
-        result.add_error(
-            "config_file",
-            format!("File not found: {}", args.config.display()),
+    if cli.use_json() {
+        let mut warnings = Vec::new();
+        if entries.is_empty() {
+            warnings.push("Palette has no colors".to_string());
+        }
+        let ctx = device_info.as_ref().map_or_else(
+            || {
+                warnings.push("Device not connected".to_string());
+                DeviceContext::disconnected(cli.serial.clone())
+            },
+            DeviceContext::from_info,
         );
-        output.validation_result(&result);
-        return if args.strict || !result.is_valid() {
-            Err(SdError::ConfigNotFound {
-                path: args.config.display().to_string(),
-            })
+        let details = FillPaletteDryRunDetails {
+            palette: args.palette.display().to_string(),
+            operations,
+        };
+        let response = if errors.is_empty() {
+            DryRunResponse::success("fill_palette", details, ctx)
         } else {
-            Ok(())
+            DryRunResponse::failure(
+                "fill_palette",
+                "One or more palette entries would fail",
+                errors,
+                details,
+                ctx,
+            )
         };
-    }
-
-    // Phase 2: Detect format
-    let format = ConfigFormat::from_extension(&args.config);
-    if format.is_none() {
-        result.add_error(
-            "config_file",
-            "Unknown file extension. Expected .yaml, .yml, or .toml",
+        output_json(cli, &response.with_warnings(warnings));
+    } else {
+        println!(
+            "DRY RUN: Would fill {} keys from {}",
+            operations.len(),
+            args.palette.display()
         );
-        output.validation_result(&result);
-        return Err(SdError::ConfigParse("Unknown config format".to_string()));
+        match &device_info {
+            Some(info) => println!("  Device: {} ({})", info.product_name, info.serial),
+            None => println!("  Device: not connected"),
+        }
+        println!();
+        for op in &operations {
+            match &op.error {
+                None => println!("  Key {}: {}", op.key, op.color),
+                Some(e) => println!("  Key {}: {} (would fail: {e})", op.key, op.color),
+            }
+        }
     }
 
-    // Phase 3: Load and parse
-    let config = match load_config(&args.config) {
-        Ok(c) => c,
-        Err(e) => {
-            result.add_error("syntax", e.to_string());
-            output.validation_result(&result);
-            return Err(e);
-        }
-    };
+    Ok(())
+}
 
-    debug!(name = ?config.name, keys = config.keys.len(), "Config parsed successfully");
+fn cmd_clear_keys(cli: &Cli, args: &cli::ClearKeysArgs, output: &dyn Output) -> Result<()> {
+    // Handle dry-run mode
+    if cli.is_dry_run() {
+        return cmd_clear_keys_dry_run(cli, args);
+    }
 
-    // Set config name
-    result.config_name = config.name.clone();
+    let device = open_device(cli)?;
+    let device_info = device::get_device_info(&device);
 
-    // Set summary stats
-    result.summary.key_count = Some(config.keys.len());
-    result.summary.brightness = config.brightness;
+    // Determine which keys to clear
+    let keys = resolve_key_selection(
+        args.all,
+        args.range.as_deref(),
+        &args.keys,
+        device_info.key_count,
+    )?;
 
-    // Phase 4: Validate brightness
-    if let Some(brightness) = config.brightness {
-        if brightness > 100 {
-            result.add_error(
-                "brightness",
-                format!("Brightness {} exceeds maximum of 100", brightness),
-            );
-        }
+    if keys.is_empty() {
+        return Err(SdError::Other(
+            "No keys specified. Use --all, --range, or --keys".to_string(),
+        ));
     }
 
-    // Phase 5: Validate key configurations
-    for (selector_str, key_config) in &config.keys {
-        // Validate selector
-        match config::KeySelector::parse(selector_str) {
-            Ok(_) => {}
-            Err(e) => {
-                result.add_error(
-                    format!("key[{}]", selector_str),
-                    format!("Invalid selector: {}", e),
-                );
-            }
+    // If --all is specified, use the optimized clear_all function
+    if args.all {
+        device::clear_all_keys(&device)?;
+        // Track state change
+        state::record::clear_all(device_info.key_count);
+        let results: Vec<BatchKeyResult> = (0..device_info.key_count)
+            .map(|k| BatchKeyResult::clear_success(k))
+            .collect();
+        let summary = BatchSummary::new(
+            device_info.key_count as usize,
+            device_info.key_count as usize,
+            0,
+        );
+        if !cli.quiet {
+            output.batch_clear_keys(&results, &summary);
         }
+        return Ok(());
+    }
 
-        // Validate key config
-        if let Err(e) = key_config.validate() {
-            result.add_error(format!("key[{}]", selector_str), e.to_string());
+    // Clear individual keys
+    let mut results: Vec<BatchKeyResult> = Vec::new();
+    let mut success_count = 0;
+    let mut error_count = 0;
+    let cancel = CancelToken::on_ctrl_c();
+    let mut pacer = cli.write_pacer();
+
+    for key in &keys {
+        if cancel.is_cancelled() {
+            break;
         }
+        pacer.before_write();
+        match device::clear_key(&device, *key) {
+            Ok(()) => {
+                success_count += 1;
+                // Track state change
+                state::record::clear_key(*key);
+                results.push(BatchKeyResult::clear_success(*key));
+            }
+            Err(e) => {
+                error_count += 1;
+                results.push(BatchKeyResult::clear_failure(*key, &e.to_string()));
 
-        // Validate image paths exist (if image type)
-        match key_config {
-            config::KeyConfig::Image { image, .. } => {
-                let resolved = if image.starts_with("~") {
-                    if let Ok(home) = config::home_dir() {
-                        home.join(image.strip_prefix("~").unwrap_or(image))
-                    } else {
-                        image.clone()
-                    }
-                } else if image.is_relative() {
-                    args.config
-                        .parent()
-                        .map(|p| p.join(image))
-                        .unwrap_or_else(|| image.clone())
+                if args.continue_on_error {
+                    // Continue processing other keys
                 } else {
-                    image.clone()
-                };
-
-                if !resolved.exists() {
-                    result.add_error(
-                        format!("key[{}]", selector_str),
-                        format!("Image not found: {}", image.display()),
-                    );
-                }
-            }
-            config::KeyConfig::Pattern { pattern, .. } => {
-                if !pattern.contains("{index}") {
-                    result.add_error(
-                        format!("key[{}]", selector_str),
-                        "Pattern must contain {index} placeholder",
-                    );
+                    // Output results so far before returning error
+                    sort_batch_results(&mut results);
+                    let summary = BatchSummary::new(keys.len(), success_count, error_count);
+                    if !cli.quiet {
+                        output.batch_clear_keys(&results, &summary);
+                    }
+                    return Err(e);
                 }
             }
-            config::KeyConfig::Color { color } => {
-                if color.to_rgb().is_err() {
-                    result.add_error(
-                        format!("key[{}]", selector_str),
-                        format!("Invalid color: {:?}", color),
-                    );
-                }
+        }
+    }
+
+    sort_batch_results(&mut results);
+    let summary = BatchSummary::new(keys.len(), success_count, error_count);
+    if !cli.quiet {
+        output.batch_clear_keys(&results, &summary);
+    }
+
+    cancel.check()
+}
+
+/// Dry-run handler for clear-keys (batch) command.
+#[allow(clippy::unnecessary_wraps)] // Consistent return type
+fn cmd_clear_keys_dry_run(cli: &Cli, args: &cli::ClearKeysArgs) -> Result<()> {
+    // Try to get device info for context
+    let device_result = open_device(cli);
+
+    if cli.use_json() {
+        let (device_ctx, device_info) = match &device_result {
+            Ok(device) => {
+                let info = device::get_device_info(device);
+                (DeviceContext::from_info(&info), Some(info))
             }
-            config::KeyConfig::Clear { clear } => {
-                if !clear {
-                    result.add_warning(
-                        format!("key[{}]", selector_str),
-                        "clear: false is redundant; omit the key instead",
+            Err(_) => (DeviceContext::disconnected(cli.serial.clone()), None),
+        };
+
+        // We need key_count to resolve selection - use device info if available
+        let key_count = device_info.as_ref().map(|i| i.key_count).unwrap_or(32);
+
+        // Resolve key selection
+        let keys_result =
+            resolve_key_selection(args.all, args.range.as_deref(), &args.keys, key_count);
+
+        let mut errors = Vec::new();
+        let mut warnings = Vec::new();
+
+        // Add device warning if not connected
+        if let Err(ref e) = device_result {
+            warnings.push(format!("Device not connected: {e}"));
+            warnings.push("Using default key count of 32 for validation".to_string());
+        }
+
+        match keys_result {
+            Ok(keys) => {
+                if keys.is_empty() {
+                    errors.push(ValidationError {
+                        field: "keys".to_string(),
+                        error: "No keys specified".to_string(),
+                        suggestion: Some(
+                            "Use --all, --range, or --keys to specify which keys to clear"
+                                .to_string(),
+                        ),
+                    });
+                    let details = ClearKeysDryRunDetails::new(vec![]);
+                    let response = DryRunResponse::failure(
+                        "clear_keys",
+                        "No keys specified",
+                        errors,
+                        details,
+                        device_ctx,
+                    )
+                    .with_warnings(warnings);
+                    output_json(cli, &response);
+                } else {
+                    let keys = keys.into_iter().map(|key| cli.user_key(key)).collect();
+                    let details = ClearKeysDryRunDetails::new(keys);
+                    let response = DryRunResponse::success("clear_keys", details, device_ctx)
+                        .with_warnings(warnings);
+                    output_json(cli, &response);
+                }
+            }
+            Err(e) => {
+                errors.push(ValidationError {
+                    field: "keys".to_string(),
+                    error: e.to_string(),
+                    suggestion: Some("Check the key range or indices specified".to_string()),
+                });
+                let details = ClearKeysDryRunDetails::new(vec![]);
+                let response = DryRunResponse::failure(
+                    "clear_keys",
+                    "Invalid key selection",
+                    errors,
+                    details,
+                    device_ctx,
+                )
+                .with_warnings(warnings);
+                output_json(cli, &response);
+            }
+        }
+    } else {
+        // Human-readable dry-run output
+        match device_result {
+            Ok(device) => {
+                let info = device::get_device_info(&device);
+                let keys_result = resolve_key_selection(
+                    args.all,
+                    args.range.as_deref(),
+                    &args.keys,
+                    info.key_count,
+                );
+
+                match keys_result {
+                    Ok(keys) => {
+                        if keys.is_empty() {
+                            println!("DRY RUN: No keys specified");
+                            println!(
+                                "  Use --all, --range, or --keys to specify which keys to clear"
+                            );
+                        } else if args.all {
+                            println!(
+                                "DRY RUN: Would clear all {} keys (set to black)",
+                                info.key_count
+                            );
+                        } else {
+                            let keys: Vec<u8> = keys.iter().map(|key| cli.user_key(*key)).collect();
+                            println!("DRY RUN: Would clear {} keys: {:?}", keys.len(), keys);
+                        }
+                        println!("  Device: {} (serial: {})", info.product_name, info.serial);
+                    }
+                    Err(e) => {
+                        println!("DRY RUN: Invalid key selection: {}", e);
+                        println!("  Device: {} (serial: {})", info.product_name, info.serial);
+                    }
+                }
+            }
+            Err(e) => {
+                println!("DRY RUN: Would clear keys (set to black)");
+                println!("  Device: not connected ({})", e);
+
+                // Try to show what would be cleared based on args
+                if args.all {
+                    println!("  Selection: all keys");
+                } else if let Some(ref range) = args.range {
+                    println!("  Selection: keys in range {}", range);
+                } else if !args.keys.is_empty() {
+                    let keys: Vec<u8> = args.keys.iter().map(|key| cli.user_key(*key)).collect();
+                    println!("  Selection: keys {:?}", keys);
+                } else {
+                    println!("  Selection: none specified");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves key selection from --all, --range, or --keys options.
+fn resolve_key_selection(
+    all: bool,
+    range: Option<&str>,
+    keys: &[u8],
+    key_count: u8,
+) -> Result<Vec<u8>> {
+    if all {
+        return Ok((0..key_count).collect());
+    }
+
+    if let Some(range_str) = range {
+        return parse_key_range(range_str, key_count);
+    }
+
+    if !keys.is_empty() {
+        // Validate all keys are in range
+        for key in keys {
+            if *key >= key_count {
+                return Err(SdError::InvalidKeyIndex {
+                    index: *key,
+                    max: key_count,
+                    max_idx: key_count - 1,
+                });
+            }
+        }
+        return Ok(keys.to_vec());
+    }
+
+    Ok(vec![])
+}
+
+/// Parses a key range string like "0-7" into a vector of key indices.
+///
+/// Either bound may be left off: "8-" runs to the last key, "-7" starts at
+/// key 0, and "-" alone is every key.
+fn parse_key_range(range: &str, key_count: u8) -> Result<Vec<u8>> {
+    let (start, end) = key_numbering::parse_range_bounds(range)?;
+    let start = start.unwrap_or(0);
+    let end = end.unwrap_or_else(|| key_count.saturating_sub(1));
+
+    if start > end {
+        return Err(SdError::Other(format!(
+            "Invalid range '{range}': start ({start}) must be <= end ({end})"
+        )));
+    }
+
+    if end >= key_count {
+        return Err(SdError::InvalidKeyIndex {
+            index: end,
+            max: key_count,
+            max_idx: key_count - 1,
+        });
+    }
+
+    Ok((start..=end).collect())
+}
+
+/// Maximum backoff delay for reconnection (30 seconds).
+const MAX_RECONNECT_DELAY_MS: u64 = 30_000;
+/// Backoff multiplier for exponential backoff.
+const RECONNECT_BACKOFF_FACTOR: f64 = 1.5;
+
+/// How a watch loop ended without error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WatchOutcome {
+    /// `--once` saw a press.
+    Pressed,
+    /// `--timeout` elapsed.
+    TimedOut,
+}
+
+/// Press feedback for `watch --feedback`.
+#[derive(Debug, Clone, Copy)]
+struct PressFeedback {
+    color: (u8, u8, u8),
+    duration: std::time::Duration,
+}
+
+impl PressFeedback {
+    fn from_args(args: &cli::WatchArgs) -> Result<Option<Self>> {
+        if !args.feedback {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            color: parse_color(&args.feedback_color)?,
+            duration: std::time::Duration::from_millis(args.feedback_ms),
+        }))
+    }
+}
+
+/// Put a key back the way the session state says it should look.
+///
+/// Doesn't record anything: the flash never touched the tracked state.
+fn restore_key_from_state(device: &device::Device, key: u8) -> Result<()> {
+    let tracked = state::session_state().keys.get(&key).cloned();
+    match tracked {
+        Some(state::KeyState::Image { path }) => {
+            device::set_key_image(device, key, &path, image_ops::ResizeStrategy::Fit)
+        }
+        Some(state::KeyState::Color { hex }) => {
+            device::fill_key_color(device, key, parse_color(&hex)?)
+        }
+        Some(state::KeyState::Cleared) | None => device::clear_key(device, key),
+    }
+}
+
+/// Robot-mode result for `watch --once` when the timeout wins.
+#[derive(Serialize)]
+struct WatchTimedOut {
+    timed_out: bool,
+    timeout_secs: u64,
+}
+
+fn cmd_watch(cli: &Cli, args: &cli::WatchArgs, output: &dyn Output) -> Result<()> {
+    let feedback = PressFeedback::from_args(args)?;
+    let mut device = open_device(cli)?;
+    let serial = cli.serial.clone();
+
+    if !cli.quiet && !cli.use_json() {
+        output.info("Watching for button presses (Ctrl+C to stop)...");
+        if args.reconnect {
+            output.info("Auto-reconnect enabled");
+        }
+    }
+
+    // Track reconnection state
+    let mut reconnect_attempts: u32 = 0;
+    let mut reconnect_delay = args.reconnect_delay;
+
+    // Publish press activity for `sd idle`
+    let mut tracker = activity::ActivityTracker::open_default().ok();
+    let mut recorder = args
+        .record
+        .as_deref()
+        .map(|path| {
+            recording::Recorder::create(path)
+                .map(|recorder| recorder.with_sample_rate(args.sample_rate))
+        })
+        .transpose()?;
+
+    loop {
+        // Try to watch for events using the output trait
+        let result = watch_buttons_with_output(
+            &device,
+            output,
+            args.once,
+            args.timeout,
+            tracker.as_mut(),
+            feedback,
+            recorder.as_mut(),
+        );
+
+        match result {
+            Ok(WatchOutcome::TimedOut) if args.once => {
+                // Distinguish "no press" from "pressed, then exited"; main
+                // reports the error and exits with its code once the run
+                // report is written
+                if cli.use_json() {
+                    output_json(
+                        cli,
+                        &WatchTimedOut {
+                            timed_out: true,
+                            timeout_secs: args.timeout,
+                        },
+                    );
+                }
+                return Err(SdError::WatchTimedOut {
+                    timeout_secs: args.timeout,
+                });
+            }
+            Ok(_) => {
+                // Normal exit (timeout, --once, or clean shutdown)
+                return Ok(());
+            }
+            Err(ref e) if e.is_connection_error() && args.reconnect => {
+                reconnect_attempts += 1;
+
+                // Check max attempts (0 = unlimited)
+                if args.max_reconnect_attempts > 0
+                    && reconnect_attempts > args.max_reconnect_attempts
+                {
+                    // Emit final disconnect event
+                    output.warning(&format!("Disconnected: {} (reconnecting: false)", e));
+
+                    if !cli.quiet && !cli.use_json() {
+                        output.warning(&format!(
+                            "Max reconnection attempts ({}) exceeded",
+                            args.max_reconnect_attempts
+                        ));
+                    }
+                    return Err(SdError::Other(format!(
+                        "Connection lost after {} reconnection attempts: {}",
+                        reconnect_attempts - 1,
+                        e
+                    )));
+                }
+
+                // Jitter only the wait; the backoff itself stays deterministic
+                let wait = device::jittered(
+                    std::time::Duration::from_millis(reconnect_delay),
+                    args.reconnect_jitter,
+                );
+
+                // Emit disconnect event
+                output.warning(&format!(
+                    "Connection lost ({}), reconnecting in {}ms (attempt {}{})...",
+                    e,
+                    wait.as_millis(),
+                    reconnect_attempts,
+                    if args.max_reconnect_attempts > 0 {
+                        format!("/{}", args.max_reconnect_attempts)
+                    } else {
+                        String::new()
+                    }
+                ));
+
+                // Wait before reconnecting
+                std::thread::sleep(wait);
+
+                // Try to reconnect
+                match device::open_device(serial.as_deref())
+                    .and_then(|d| d.with_layout_rotation(cli.rotate_layout, !cli.no_rotate_images))
+                {
+                    Ok(new_device) => {
+                        device = new_device;
+
+                        // Emit reconnected event
+                        output.success(&format!(
+                            "Reconnected successfully (attempt {})",
+                            reconnect_attempts
+                        ));
+
+                        // Reset backoff on successful connection
+                        reconnect_delay = args.reconnect_delay;
+                        reconnect_attempts = 0;
+                    }
+                    Err(conn_err) => {
+                        tracing::debug!(error = %conn_err, "Reconnection attempt failed");
+
+                        // Increase backoff with cap
+                        #[allow(clippy::cast_possible_truncation)]
+                        #[allow(clippy::cast_sign_loss)]
+                        {
+                            reconnect_delay = ((reconnect_delay as f64 * RECONNECT_BACKOFF_FACTOR)
+                                as u64)
+                                .min(MAX_RECONNECT_DELAY_MS);
+                        }
+                        // Continue loop to try again
+                    }
+                }
+            }
+            Err(e) => {
+                // Non-connection error or reconnect disabled
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// Demo: key 0 dims and key 1 brightens the display (`sd demo dimmer`).
+///
+/// A deliberately small example of reacting to presses: poll the buttons as
+/// `watch` does, and set the brightness on each new press. Ctrl+C or a failed
+/// read stops it, and either way the original brightness is put back.
+fn cmd_demo_dimmer(cli: &Cli, args: &cli::DimmerArgs, output: &dyn Output) -> Result<()> {
+    const DIM_KEY: u8 = 0;
+    const BRIGHTEN_KEY: u8 = 1;
+
+    if cli.is_dry_run() {
+        return cmd_demo_dimmer_dry_run(cli, args, DIM_KEY, BRIGHTEN_KEY);
+    }
+
+    let device = open_device(cli)?;
+    let original = current_brightness(&device, false).map(|reading| reading.level);
+    let cancel = CancelToken::on_ctrl_c();
+
+    if !cli.quiet && !cli.use_json() {
+        output.info(&format!(
+            "Demo: press key {} to dim and key {} to brighten by {}% (Ctrl+C to stop)",
+            cli.user_key(DIM_KEY),
+            cli.user_key(BRIGHTEN_KEY),
+            args.step
+        ));
+    }
+
+    let mut dimmer = device::Dimmer::new(
+        DIM_KEY,
+        BRIGHTEN_KEY,
+        args.step,
+        original.unwrap_or(cli::DEFAULT_RESET_BRIGHTNESS),
+        device::read_button_states(&device),
+    );
+    let stopped = loop {
+        if cancel.is_cancelled() {
+            break Ok(());
+        }
+        match dimmer.poll(&device) {
+            Ok(Some(level)) => {
+                state::record::brightness(level);
+                output.brightness_set(level);
+            }
+            Ok(None) => {}
+            Err(e) => break Err(e),
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    };
+
+    // Put the brightness back even when a poll failed, then report the failure
+    match original {
+        Some(original) => {
+            let restored = device::set_brightness(&device, original)
+                .inspect(|()| state::record::brightness(original));
+            stopped?;
+            restored?;
+            output.success(&format!("Demo stopped; brightness restored to {original}%"));
+        }
+        None => {
+            stopped?;
+            output.warning(&format!(
+                "Demo stopped; the original brightness is unknown, so it stays at {}%",
+                dimmer.level()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Dry-run details for `demo dimmer`.
+#[derive(Serialize)]
+struct DimmerDryRunDetails {
+    dim_key: u8,
+    brighten_key: u8,
+    step: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    current_brightness: Option<u8>,
+}
+
+/// Dry-run handler for `demo dimmer`: reports the key bindings without
+/// touching the brightness.
+#[allow(clippy::unnecessary_wraps)] // Consistent return type
+fn cmd_demo_dimmer_dry_run(
+    cli: &Cli,
+    args: &cli::DimmerArgs,
+    dim_key: u8,
+    brighten_key: u8,
+) -> Result<()> {
+    let device = open_device(cli);
+    let current = device
+        .as_ref()
+        .ok()
+        .and_then(|device| current_brightness(device, false))
+        .map(|reading| reading.level);
+
+    if cli.use_json() {
+        let mut warnings = Vec::new();
+        let ctx = match &device {
+            Ok(device) => DeviceContext::from_info(&device::get_device_info(device)),
+            Err(e) => {
+                warnings.push(format!("Device not connected: {e}"));
+                DeviceContext::disconnected(cli.serial.clone())
+            }
+        };
+        let details = DimmerDryRunDetails {
+            dim_key: cli.user_key(dim_key),
+            brighten_key: cli.user_key(brighten_key),
+            step: args.step,
+            current_brightness: current,
+        };
+        output_json(
+            cli,
+            &DryRunResponse::success("demo_dimmer", details, ctx).with_warnings(warnings),
+        );
+    } else {
+        println!(
+            "DRY RUN: Would dim with key {} and brighten with key {} by {}% per press",
+            cli.user_key(dim_key),
+            cli.user_key(brighten_key),
+            args.step
+        );
+        match &device {
+            Ok(device) => {
+                let info = device::get_device_info(device);
+                println!("  Device: {} ({})", info.product_name, info.serial);
+            }
+            Err(e) => println!("  Device: not connected ({e})"),
+        }
+        if let Some(level) = current {
+            println!("  Current brightness: {level}%");
+        }
+    }
+
+    Ok(())
+}
+
+/// Play a recording through a mock device's input queue (`sd replay`).
+///
+/// Events are reported as `watch` reports them, after the same change
+/// detection, so a press of a key that is already down is dropped.
+fn cmd_replay(cli: &Cli, args: &cli::ReplayArgs, output: &dyn Output) -> Result<()> {
+    if !(args.speed.is_finite() && args.speed > 0.0) {
+        return Err(SdError::Other(format!(
+            "Invalid --speed {}: must be greater than 0",
+            args.speed
+        )));
+    }
+    let events = recording::read_recording(&args.recording)?;
+    let mock = device::mock::MockDevice::new(args.model.unwrap_or_else(|| cli.offline_model()));
+    let key_count = mock.info().key_count;
+    if let Some(event) = events.iter().find(|event| event.key >= key_count) {
+        return Err(SdError::InvalidKeyIndex {
+            index: event.key,
+            max: key_count,
+            max_idx: key_count.saturating_sub(1),
+        });
+    }
+
+    if !cli.quiet && !cli.use_json() {
+        output.info(&format!(
+            "Replaying {} events on a simulated {}...",
+            events.len(),
+            mock.info().product_name
+        ));
+    }
+
+    let mut last_states = vec![false; usize::from(key_count)];
+    let mut previous_ms = 0;
+    for event in &events {
+        std::thread::sleep(recording::replay_delay(
+            previous_ms,
+            event.timestamp_ms,
+            args.speed,
+        ));
+        previous_ms = event.timestamp_ms;
+
+        if event.pressed {
+            mock.queue_press(event.key);
+        } else {
+            mock.queue_release(event.key);
+        }
+        let states = mock.read_button_states();
+        for (key, (&current, &previous)) in (0..=u8::MAX).zip(states.iter().zip(&last_states)) {
+            if current != previous {
+                output.button_event(&device::ButtonEvent {
+                    key,
+                    pressed: current,
+                    timestamp_ms: event.timestamp_ms,
+                });
+            }
+        }
+        last_states = states;
+    }
+
+    if !cli.quiet && !cli.use_json() {
+        output.success(&format!("Replayed {}", args.recording.display()));
+    }
+    Ok(())
+}
+
+/// Watch for button presses using the Output trait.
+///
+/// This function provides the watch loop that uses the Output trait
+/// for all button event reporting, enabling both robot and human modes.
+fn watch_buttons_with_output(
+    device: &device::Device,
+    output: &dyn Output,
+    once: bool,
+    timeout_secs: u64,
+    mut tracker: Option<&mut activity::ActivityTracker>,
+    feedback: Option<PressFeedback>,
+    mut recorder: Option<&mut recording::Recorder>,
+) -> Result<WatchOutcome> {
+    use std::time::{Duration, Instant};
+
+    // Keys currently flashed by --feedback, with when to restore them
+    let mut flashing: Vec<(u8, Instant)> = Vec::new();
+    // Untracked keys already warned about, so each press doesn't warn again
+    let mut untracked: Vec<u8> = Vec::new();
+    let restore_due = |flashing: &mut Vec<(u8, Instant)>, wait: bool| {
+        flashing.retain(|&(key, until)| {
+            let now = Instant::now();
+            if until > now {
+                if !wait {
+                    return true;
+                }
+                std::thread::sleep(until - now);
+            }
+            if let Err(e) = restore_key_from_state(device, key) {
+                tracing::warn!(key, error = %e, "Failed to restore key after press feedback");
+            }
+            false
+        });
+    };
+
+    let start = Instant::now();
+    let timeout = if timeout_secs == 0 {
+        None
+    } else {
+        Some(Duration::from_secs(timeout_secs))
+    };
+
+    // We need to access device internals - use the existing watch function
+    // but intercept events. For now, delegate to read_button_states in a loop.
+    let mut last_states = vec![false; device.info().key_count as usize];
+
+    loop {
+        // Check timeout
+        if let Some(t) = timeout {
+            if start.elapsed() >= t {
+                restore_due(&mut flashing, true);
+                return Ok(WatchOutcome::TimedOut);
+            }
+        }
+
+        // Read current states
+        let states = device::read_button_states(device);
+
+        // Detect changes
+        for (key, (&current, &previous)) in states.iter().zip(last_states.iter()).enumerate() {
+            if current && !previous {
+                // Button pressed
+                #[allow(clippy::cast_possible_truncation)]
+                let event = device::ButtonEvent {
+                    key: key as u8,
+                    pressed: true,
+                    timestamp_ms: start.elapsed().as_millis().min(u128::from(u64::MAX)) as u64,
+                };
+                output.button_event(&event);
+                if let Some(recorder) = recorder.as_deref_mut() {
+                    recorder.record(&event)?;
+                }
+                if let Some(tracker) = tracker.as_deref_mut() {
+                    tracker.pressed();
+                }
+                let tracked = state::session_state().keys.contains_key(&event.key);
+                if let (Some(_), false) = (feedback, tracked) {
+                    // Nothing to restore it to; a flash would wipe the key
+                    if !untracked.contains(&event.key) {
+                        untracked.push(event.key);
+                        // Follows the press event, which carries the key number
+                        output.warning(
+                            "--feedback skipped for this key: sd hasn't set it this session, \
+                             so it couldn't be restored after the flash",
+                        );
+                    }
+                } else if let Some(feedback) = feedback {
+                    let key = event.key;
+                    match device::fill_key_color(device, key, feedback.color) {
+                        Ok(()) => {
+                            flashing.retain(|&(k, _)| k != key);
+                            flashing.push((key, Instant::now() + feedback.duration));
+                        }
+                        Err(e) => tracing::debug!(key, error = %e, "Press feedback failed"),
+                    }
+                }
+
+                if once {
+                    restore_due(&mut flashing, true);
+                    return Ok(WatchOutcome::Pressed);
+                }
+            } else if !current && previous {
+                // Button released
+                #[allow(clippy::cast_possible_truncation)]
+                let event = device::ButtonEvent {
+                    key: key as u8,
+                    pressed: false,
+                    timestamp_ms: start.elapsed().as_millis().min(u128::from(u64::MAX)) as u64,
+                };
+                output.button_event(&event);
+                if let Some(recorder) = recorder.as_deref_mut() {
+                    recorder.record(&event)?;
+                }
+            }
+        }
+
+        last_states = states;
+        restore_due(&mut flashing, false);
+        if let Some(tracker) = tracker.as_deref_mut() {
+            tracker.tick();
+        }
+
+        // Small sleep to avoid busy-waiting
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Connection events emitted during watch with reconnect.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum WatchConnectionEvent {
+    Disconnected { reason: String, reconnecting: bool },
+    Reconnecting { attempt: u32, delay_ms: u64 },
+    Reconnected { attempt: u32 },
+}
+
+/// Emits a watch connection event in robot mode.
+fn emit_watch_event(cli: &Cli, event: WatchConnectionEvent) {
+    if cli.use_json() {
+        let json = if cli.use_compact_json() {
+            serde_json::to_string(&event).unwrap_or_default()
+        } else {
+            serde_json::to_string_pretty(&event).unwrap_or_default()
+        };
+        println!("{json}");
+    }
+}
+
+fn cmd_read(cli: &Cli, args: &cli::ReadArgs, output: &dyn Output) -> Result<()> {
+    let device = open_device(cli)?;
+
+    // --settle: poll until two consecutive readings agree or time runs out
+    let states = match args.settle {
+        None => device::read_button_states(&device),
+        Some(settle_ms) => {
+            let settle =
+                device::settle_button_states(&device, std::time::Duration::from_millis(settle_ms))?;
+            let polls = settle.polls;
+            tracing::debug!(
+                polls,
+                settled = settle.settled,
+                "Button state settle finished"
+            );
+            match (settle.settled, cli.verbose > 0) {
+                (false, _) => output.warning(&format!(
+                    "Readings didn't settle within {settle_ms} ms after {polls} polls; \
+                     showing the last one"
+                )),
+                (true, true) => output.info(&format!("Readings settled after {polls} polls")),
+                (true, false) => {}
+            }
+            settle.states
+        }
+    };
+
+    output.button_states(&states);
+    Ok(())
+}
+
+/// Show (or forget) the state tracked by this process.
+#[allow(clippy::unnecessary_wraps)] // Consistent return type
+fn cmd_state(args: &cli::StateArgs, output: &dyn Output) -> Result<()> {
+    match args.command {
+        None => output.device_state(&state::session_state().report()),
+        Some(cli::StateSubcommand::Reset) => {
+            let keys = state::reset();
+            output.success(&format!(
+                "Forgot tracked state for {keys} keys (the device is unchanged)"
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn cmd_idle(cli: &Cli, _args: &cli::IdleArgs) -> Result<()> {
+    let path = activity::default_activity_path()?;
+    let status = activity::read_idle(&path, chrono::Utc::now())?;
+    if cli.use_json() {
+        output_json(cli, &status);
+    } else {
+        println!("{}", status.idle_seconds);
+    }
+    Ok(())
+}
+
+#[allow(clippy::unnecessary_wraps)] // Will return errors when implemented
+fn cmd_init(cli: &Cli, args: &cli::InitArgs) -> Result<()> {
+    let _ = (cli, args); // TODO: implement
+    eprintln!("Config init not yet implemented");
+    Ok(())
+}
+
+fn cmd_config(cli: &Cli, args: &cli::ConfigArgs) -> Result<()> {
+    if let Some(cli::ConfigSubcommand::Keys(keys_args)) = &args.command {
+        return cmd_config_keys(cli, keys_args);
+    }
+    eprintln!("Config show not yet implemented"); // TODO: implement
+    Ok(())
+}
+
+/// The action one key ends up with after selector resolution.
+#[derive(serde::Serialize)]
+struct ResolvedKeyAction {
+    key: u8,
+    /// Selector that won for this key.
+    selector: String,
+    /// `image`, `pattern`, `color`, `clear` or `skip`.
+    action: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+    /// Less specific selectors that also matched this key.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    overridden: Vec<String>,
+}
+
+/// Show the resolved key-to-action mapping of a declarative config.
+fn cmd_config_keys(cli: &Cli, args: &cli::ConfigKeysArgs) -> Result<()> {
+    use config::declarative::load_config;
+    use std::collections::BTreeMap;
+    use tracing::{debug, warn};
+
+    if !args.config.exists() {
+        return Err(SdError::ConfigNotFound {
+            path: args.config.display().to_string(),
+        });
+    }
+    let device_info = offline_device_info(cli, args.model);
+    let config = load_config(&args.config)?.for_model(device_info.model());
+
+    // Most specific first; ties broken by selector text so output is stable
+    let mut entries = config.parsed_keys()?;
+    entries.sort_by_cached_key(|(selector, _)| (selector.priority(), selector.to_string()));
+
+    let mut mapping: BTreeMap<u8, ResolvedKeyAction> = BTreeMap::new();
+    for (selector, key_config) in entries {
+        let keys = match selector.resolve(&device_info) {
+            Ok(keys) => keys,
+            Err(e) => {
+                warn!(selector = %selector, error = %e, "Selector matches no keys on this device");
+                continue;
+            }
+        };
+
+        for key in keys {
+            if let Some(winner) = mapping.get_mut(&key) {
+                winner.overridden.push(selector.to_string());
+                continue;
+            }
+            let image = key_image_path(key, key_config, &args.config)?
+                .map(|path| data_uri::display_source(&path));
+            let (action, color) = match key_config {
+                config::KeyConfig::Image { .. } => ("image", None),
+                config::KeyConfig::Pattern { .. } => ("pattern", None),
+                config::KeyConfig::Color { color } => {
+                    let (r, g, b) = color.to_rgb()?;
+                    ("color", Some(format!("#{r:02x}{g:02x}{b:02x}")))
+                }
+                config::KeyConfig::Clear { clear: true } => ("clear", None),
+                config::KeyConfig::Clear { clear: false } => ("skip", None),
+            };
+            mapping.insert(
+                key,
+                ResolvedKeyAction {
+                    key,
+                    selector: selector.to_string(),
+                    action,
+                    image,
+                    color,
+                    overridden: Vec::new(),
+                },
+            );
+        }
+    }
+    debug!(
+        keys = mapping.len(),
+        device_keys = device_info.key_count,
+        "Resolved config key mapping"
+    );
+
+    let resolved: Vec<ResolvedKeyAction> = mapping.into_values().collect();
+    if cli.use_json() {
+        output_json(cli, &resolved);
+        return Ok(());
+    }
+
+    println!(
+        "{} ({} keys): {} of {} keys configured",
+        device_info.product_name,
+        device_info.key_count,
+        resolved.len(),
+        device_info.key_count
+    );
+    if resolved.is_empty() {
+        return Ok(());
+    }
+    let selector_width = resolved
+        .iter()
+        .map(|r| r.selector.len())
+        .max()
+        .unwrap_or(0)
+        .max("SELECTOR".len());
+    println!(
+        "\n  {:>3}  {:<selector_width$}  {:<7}  TARGET",
+        "KEY", "SELECTOR", "ACTION"
+    );
+    for r in &resolved {
+        let target = r.image.as_deref().or(r.color.as_deref()).unwrap_or("-");
+        let mut line = format!(
+            "  {:>3}  {:<selector_width$}  {:<7}  {}",
+            r.key, r.selector, r.action, target
+        );
+        if !r.overridden.is_empty() {
+            line.push_str(&format!("  (overrides {})", r.overridden.join(", ")));
+        }
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// Validate a declarative configuration file without applying it.
+fn cmd_validate(cli: &Cli, args: &cli::ValidateArgs, output: &dyn Output) -> Result<()> {
+    if args.all || args.config.is_dir() {
+        return cmd_validate_all(cli, args, output);
+    }
+
+    let connected = device::list_devices()
+        .ok()
+        .and_then(|devices| devices.into_iter().next());
+    let (result, fatal) = validate_config_file(cli, args, &args.config, connected.as_ref());
+    output.validation_result(&result);
+    if let Some(e) = fatal {
+        return Err(e);
+    }
+
+    // Exit with error if not valid or if strict mode and warnings exist
+    if !result.is_valid() {
+        Err(SdError::ConfigInvalid(format!(
+            "{} error(s) found",
+            result.summary.error_count
+        )))
+    } else if args.strict && result.summary.warning_count > 0 {
+        Err(SdError::ConfigInvalid(format!(
+            "{} warning(s) found (strict mode)",
+            result.summary.warning_count
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+fn cmd_upgrade_config(cli: &Cli, args: &cli::UpgradeConfigArgs, output: &dyn Output) -> Result<()> {
+    use config::declarative::{ConfigFormat, config_to_string, save_config};
+    use config::upgrade::upgrade_str;
+    use tracing::info;
+
+    let format_of = |path: &std::path::Path| {
+        ConfigFormat::from_extension(path).ok_or_else(|| {
+            SdError::ConfigParse(format!(
+                "Unknown config format for '{}': expected .yaml, .yml, or .toml",
+                path.display()
+            ))
+        })
+    };
+    let format = format_of(&args.config)?;
+    let target = args.output.as_ref().unwrap_or(&args.config);
+    let target_format = format_of(target)?;
+    let content = std::fs::read_to_string(&args.config).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            SdError::ConfigNotFound {
+                path: args.config.display().to_string(),
+            }
+        } else {
+            SdError::Io(e)
+        }
+    })?;
+
+    let upgrade = upgrade_str(&content, format)?;
+    let dry_run = cli.is_dry_run_with(args.dry_run);
+    // Nothing to do in place; an --output copy is still written
+    let write = !dry_run && (!upgrade.is_current() || args.output.is_some());
+    if write {
+        save_config(&upgrade.config, target)?;
+        info!(
+            config = %args.config.display(),
+            target = %target.display(),
+            changes = upgrade.changes.len(),
+            "Upgraded configuration"
+        );
+    }
+
+    if cli.use_json() {
+        let mut response = serde_json::json!({
+            "config": args.config.display().to_string(),
+            "output": target.display().to_string(),
+            "current": upgrade.is_current(),
+            "changes": upgrade.changes,
+            "written": write,
+            "dry_run": dry_run,
+        });
+        if dry_run {
+            response["content"] = config_to_string(&upgrade.config, target_format)?.into();
+        }
+        output_json(cli, &response);
+        return Ok(());
+    }
+
+    for change in &upgrade.changes {
+        output.info(&format!("{}: {}", change.location, change.message));
+    }
+    if dry_run {
+        println!(
+            "DRY RUN: would write {} ({} change(s)):",
+            target.display(),
+            upgrade.changes.len()
+        );
+        print!("{}", config_to_string(&upgrade.config, target_format)?);
+    } else if write {
+        output.success(&format!(
+            "Upgraded {} ({} change(s)), wrote {}",
+            args.config.display(),
+            upgrade.changes.len(),
+            target.display()
+        ));
+    } else {
+        output.success(&format!(
+            "{} already uses the current syntax",
+            args.config.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Validate every config file in a directory (`validate --all`).
+fn cmd_validate_all(cli: &Cli, args: &cli::ValidateArgs, output: &dyn Output) -> Result<()> {
+    use config::declarative::ConfigFormat;
+
+    let dir = &args.config;
+    if !dir.is_dir() {
+        return Err(SdError::Other(format!(
+            "--all needs a directory of configs, but {} is not a directory",
+            dir.display()
+        )));
+    }
+    let mut files: Vec<std::path::PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && ConfigFormat::from_extension(path).is_some())
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        return Err(SdError::ConfigInvalid(format!(
+            "No .yaml, .yml or .toml files in {}",
+            dir.display()
+        )));
+    }
+
+    // One device lookup covers every file
+    let connected = device::list_devices()
+        .ok()
+        .and_then(|devices| devices.into_iter().next());
+    let results: Vec<output::ValidationResult> = files
+        .iter()
+        .map(|path| validate_config_file(cli, args, path, connected.as_ref()).0)
+        .collect();
+    let passed = |r: &output::ValidationResult| {
+        r.is_valid() && !(args.strict && r.summary.warning_count > 0)
+    };
+    let failed = results.iter().filter(|r| !passed(r)).count();
+
+    if cli.use_json() {
+        output_json(
+            cli,
+            &serde_json::json!({
+                "valid": failed == 0,
+                "directory": dir.display().to_string(),
+                "strict": args.strict,
+                "results": results,
+                "summary": {
+                    "files": results.len(),
+                    "passed": results.len() - failed,
+                    "failed": failed,
+                    "error_count": results.iter().map(|r| r.summary.error_count).sum::<usize>(),
+                    "warning_count": results.iter().map(|r| r.summary.warning_count).sum::<usize>(),
+                },
+            }),
+        );
+    } else {
+        for result in &results {
+            output.validation_result(result);
+        }
+        if failed == 0 {
+            output.success(&format!(
+                "All {} config files in {} are valid",
+                results.len(),
+                dir.display()
+            ));
+        } else {
+            let names: Vec<String> = results
+                .iter()
+                .filter(|r| !passed(r))
+                .map(|r| r.config_path.clone())
+                .collect();
+            output.warning(&format!(
+                "{failed} of {} config files failed: {}",
+                results.len(),
+                names.join(", ")
+            ));
+        }
+    }
+
+    if failed == 0 {
+        Ok(())
+    } else {
+        let mode = if args.strict { " (strict mode)" } else { "" };
+        Err(SdError::ConfigInvalid(format!(
+            "{failed} of {} config files failed validation{mode}",
+            results.len()
+        )))
+    }
+}
+
+/// Validate one config file against `connected` (if any).
+///
+/// Returns the result, plus the error to exit with when the file couldn't
+/// be read or parsed at all.
+fn validate_config_file(
+    cli: &Cli,
+    args: &cli::ValidateArgs,
+    path: &std::path::Path,
+    connected: Option<&device::DeviceInfo>,
+) -> (output::ValidationResult, Option<SdError>) {
+    use config::declarative::{ConfigFormat, load_config, unknown_fields};
+    use output::ValidationResult;
+    use tracing::{debug, info};
+
+    info!(config = %path.display(), "Validating configuration file");
+
+    let mut result = ValidationResult::new(path);
+
+    // Phase 1: Check file exists
+    if !path.exists() {
+        result.add_error("config_file", format!("File not found: {}", path.display()));
+        let e = SdError::ConfigNotFound {
+            path: path.display().to_string(),
+        };
+        return (result, Some(e));
+    }
+
+    // Phase 2: Detect format
+    let format = ConfigFormat::from_extension(path);
+    if format.is_none() {
+        result.add_error(
+            "config_file",
+            "Unknown file extension. Expected .yaml, .yml, or .toml",
+        );
+        let e = SdError::ConfigParse("Unknown config format".to_string());
+        return (result, Some(e));
+    }
+
+    // Phase 3: Load and parse
+    let config = match load_config(path) {
+        Ok(c) => c,
+        Err(e) => {
+            result.add_error("syntax", e.to_string());
+            return (result, Some(e));
+        }
+    };
+
+    // Phase 3b: Unknown fields, which parsing ignores
+    if args.strict_fields {
+        let unknown = std::fs::read_to_string(path)
+            .map_err(SdError::Io)
+            .and_then(|content| unknown_fields(&content, format.unwrap_or(ConfigFormat::Yaml)));
+        match unknown {
+            Ok(unknown) => {
+                for field in unknown {
+                    let location = if field.location.is_empty() {
+                        "config".to_string()
+                    } else {
+                        field.location
+                    };
+                    result.add_error(location, format!("Unknown field '{}'", field.field));
+                }
+            }
+            Err(e) => result.add_error("syntax", e.to_string()),
+        }
+    }
+
+    debug!(name = ?config.name, keys = config.keys.len(), "Config parsed successfully");
+
+    // Set config name
+    result.config_name = config.name.clone();
+
+    // Set summary stats
+    result.summary.key_count = Some(config.keys.len());
+    result.summary.brightness = config.brightness;
+
+    // Phase 4: Validate brightness
+    if let Some(brightness) = config.brightness {
+        if brightness > 100 {
+            result.add_error(
+                "brightness",
+                format!("Brightness {} exceeds maximum of 100", brightness),
+            );
+        }
+    }
+
+    // Phase 5: Validate key configurations
+    for (selector_str, key_config) in &config.keys {
+        // Validate selector
+        match config::KeySelector::parse(selector_str) {
+            Ok(_) => {}
+            Err(e) => {
+                result.add_error(
+                    format!("key[{}]", selector_str),
+                    format!("Invalid selector: {}", e),
+                );
+            }
+        }
+
+        // Validate key config
+        if let Err(e) = key_config.validate() {
+            result.add_error(format!("key[{}]", selector_str), e.to_string());
+        }
+
+        // Validate image paths exist (if image type)
+        match key_config {
+            // Inline data was checked by validate() above
+            config::KeyConfig::Image {
+                image, data: None, ..
+            } => {
+                let config_dir = path.parent().unwrap_or(std::path::Path::new(""));
+                match config::resolve_path(image, config_dir) {
+                    Ok(resolved) if !resolved.exists() => {
+                        result.add_error(
+                            format!("key[{}]", selector_str),
+                            format!("Image not found: {}", image.display()),
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        result.add_error(format!("key[{}]", selector_str), e.to_string());
+                    }
+                }
+            }
+            config::KeyConfig::Pattern { pattern, .. } => {
+                if !config::has_index_placeholder(pattern) {
+                    result.add_error(
+                        format!("key[{}]", selector_str),
+                        "Pattern must contain {index} placeholder",
+                    );
+                }
+            }
+            config::KeyConfig::Color { color } => {
+                if color.to_rgb().is_err() {
+                    result.add_error(
+                        format!("key[{}]", selector_str),
+                        format!("Invalid color: {:?}", color),
+                    );
+                }
+            }
+            config::KeyConfig::Clear { clear } => {
+                if !clear {
+                    result.add_warning(
+                        format!("key[{}]", selector_str),
+                        "clear: false is redundant; omit the key instead",
+                    );
+                }
+            }
+        }
+    }
+
+    // Phase 6: Device-specific validation (optional, if device connected)
+    match connected {
+        Some(device_info) => {
+            let key_count = device_info.key_count;
+
+            for (selector_str, _) in &config.keys {
+                if let Ok(selector) = config::KeySelector::parse(selector_str) {
+                    // Check if selector indices are valid for this device
+                    match &selector {
+                        config::KeySelector::Single(idx) => {
+                            if *idx >= key_count {
+                                result.add_error(
+                                    format!("key[{}]", selector_str),
+                                    format!(
+                                        "Key index {} out of range for {} (0-{})",
+                                        idx,
+                                        device_info.product_name,
+                                        key_count - 1
+                                    ),
+                                );
+                            }
+                        }
+                        config::KeySelector::Range { start, end } => {
+                            if *end >= key_count {
+                                result.add_error(
+                                    format!("key[{}]", selector_str),
+                                    format!(
+                                        "Range end {} out of range for {} (0-{})",
+                                        end,
+                                        device_info.product_name,
+                                        key_count - 1
+                                    ),
+                                );
+                            }
+                            if start > end {
+                                result.add_error(
+                                    format!("key[{}]", selector_str),
+                                    format!("Invalid range: start {} > end {}", start, end),
+                                );
+                            }
+                        }
+                        config::KeySelector::Row(row) => {
+                            if *row >= device_info.rows {
+                                result.add_error(
+                                    format!("key[{}]", selector_str),
+                                    format!(
+                                        "Row {} out of range for {} (0-{})",
+                                        row,
+                                        device_info.product_name,
+                                        device_info.rows - 1
+                                    ),
+                                );
+                            }
+                        }
+                        config::KeySelector::Column(col) => {
+                            if *col >= device_info.cols {
+                                result.add_error(
+                                    format!("key[{}]", selector_str),
+                                    format!(
+                                        "Column {} out of range for {} (0-{})",
+                                        col,
+                                        device_info.product_name,
+                                        device_info.cols - 1
+                                    ),
+                                );
+                            }
+                        }
+                        config::KeySelector::Default => {}
+                    }
+                }
+            }
+        }
+        None => {
+            result.add_warning(
+                "device",
+                "No device connected; skipping device-specific validation",
+            );
+        }
+    }
+
+    // Phase 7: Coverage check (--require-complete)
+    if args.require_complete {
+        let device_info = connected
+            .cloned()
+            .unwrap_or_else(|| device::DeviceInfo::for_model(cli.offline_model()));
+        let selectors: Vec<config::KeySelector> = config
+            .keys
+            .keys()
+            .filter_map(|s| config::KeySelector::parse(s).ok())
+            .collect();
+        let uncovered = config::uncovered_keys(&selectors, &device_info);
+
+        if !uncovered.is_empty() {
+            let list = uncovered
+                .iter()
+                .map(u8::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            result.add_error(
+                "keys",
+                format!(
+                    "{} of {} keys on {} are not covered by any selector: {list}",
+                    uncovered.len(),
+                    device_info.key_count,
+                    device_info.product_name
+                ),
+            );
+        }
+        result.uncovered_keys = Some(uncovered);
+    }
+
+    info!(
+        valid = result.is_valid(),
+        errors = result.summary.error_count,
+        warnings = result.summary.warning_count,
+        "Validation complete"
+    );
+
+    (result, None)
+}
+
+/// Apply a declarative configuration to the device.
+/// A config in the profile library, as `sd profiles` lists it.
+#[derive(Serialize)]
+struct ProfileListing {
+    /// File name without extension, as `sd apply-profile` takes it.
+    name: String,
+    path: std::path::PathBuf,
+    /// The config's own `name`, if it sets one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    profile_name: Option<String>,
+    /// Number of key entries (selectors) in the config.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keys: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    brightness: Option<u8>,
+    /// Why the file couldn't be loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+fn profile_dir(dir: Option<&std::path::Path>) -> Result<std::path::PathBuf> {
+    dir.map_or_else(config::default_profiles_dir, |dir| Ok(dir.to_path_buf()))
+}
+
+fn cmd_profiles(cli: &Cli, args: &cli::ProfilesArgs) -> Result<()> {
+    let dir = profile_dir(args.profile_dir.as_deref())?;
+    let profiles: Vec<ProfileListing> = config::profile_files(&dir)?
+        .into_iter()
+        .map(|path| {
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let (config, error) = match config::declarative::load_config(&path) {
+                Ok(config) => (Some(config), None),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            ProfileListing {
+                name,
+                path,
+                profile_name: config.as_ref().and_then(|c| c.name.clone()),
+                keys: config.as_ref().map(|c| c.keys.len()),
+                brightness: config.as_ref().and_then(|c| c.brightness),
+                error,
+            }
+        })
+        .collect();
+
+    if cli.use_json() {
+        output_json(cli, &profiles);
+    } else if profiles.is_empty() {
+        println!("No profiles in {}", dir.display());
+        println!("Add .yaml or .toml configs there to apply them with 'sd apply-profile <name>'");
+    } else {
+        let console = Console::new();
+        let success = Color::parse("#00D26A").expect("valid color");
+        let muted = Color::parse("#747D8C").expect("valid color");
+
+        for profile in &profiles {
+            let mut line = Text::new("");
+            line.append_styled(&profile.name, Style::new().color(success.clone()));
+            match (&profile.error, profile.keys) {
+                (Some(error), _) => line.append(&format!(": invalid ({error})")),
+                (None, Some(keys)) => line.append(&format!(
+                    ": {}{keys} key(s){}",
+                    profile
+                        .profile_name
+                        .as_ref()
+                        .map_or(String::new(), |name| format!("{name}, ")),
+                    profile
+                        .brightness
+                        .map_or(String::new(), |b| format!(", {b}% brightness"))
+                )),
+                (None, None) => {}
+            }
+            console.print_text(&line);
+        }
+        console.print_styled(&format!("In {}", dir.display()), Style::new().color(muted));
+    }
+    Ok(())
+}
+
+fn cmd_apply_profile(cli: &Cli, args: &cli::ApplyProfileArgs, output: &dyn Output) -> Result<()> {
+    let dir = profile_dir(args.profile_dir.as_deref())?;
+    let config = config::find_profile(&dir, &args.name)?;
+    tracing::debug!(profile = %args.name, path = %config.display(), "Resolved profile");
+    let apply = cli::ApplyArgs {
+        config,
+        dry_run: args.dry_run,
+        force: args.force,
+        no_brightness: args.no_brightness,
+        diff: args.diff,
+        export_images: None,
+        no_upload: false,
+        model: None,
+        replace: args.replace,
+        mock: None,
+        dump_state: None,
+        snapshot_on_apply: None,
+        strict_fields: false,
+        after: None,
+        strict_hooks: false,
+        stream: false,
+        dedupe_images: false,
+    };
+    cmd_apply(cli, &apply, output)
+}
+
+fn cmd_apply(cli: &Cli, args: &cli::ApplyArgs, output: &dyn Output) -> Result<()> {
+    use config::KeySelector;
+    use config::declarative::{load_config, load_config_strict};
+    use tracing::{debug, info, warn};
+
+    info!(config = %args.config.display(), "Applying configuration");
+
+    // Phase 1: Load and validate config
+    if !args.config.exists() {
+        return Err(SdError::ConfigNotFound {
+            path: args.config.display().to_string(),
+        });
+    }
+
+    let config = if args.strict_fields {
+        load_config_strict(&args.config)?
+    } else {
+        load_config(&args.config)?
+    };
+    debug!(
+        name = ?config.name,
+        keys = config.keys.len(),
+        brightness = ?config.brightness,
+        "Config loaded"
+    );
+
+    // Phase 2: Validate config (unless --force)
+    if !args.force {
+        // Run validation to check for errors
+        let mut has_errors = false;
+
+        // Check brightness
+        if let Some(brightness) = config.brightness {
+            if brightness > 100 {
+                output.error(&SdError::InvalidBrightness { value: brightness });
+                has_errors = true;
+            }
+        }
+
+        // Check key configs
+        for (selector_str, key_config) in &config.keys {
+            if let Err(e) = KeySelector::parse(selector_str) {
+                output.error(&SdError::ConfigParse(format!(
+                    "Invalid selector '{}': {}",
+                    selector_str, e
+                )));
+                has_errors = true;
+            }
+            if let Err(e) = key_config.validate() {
+                output.error(&SdError::ConfigParse(format!(
+                    "Invalid key config for '{}': {}",
+                    selector_str, e
+                )));
+                has_errors = true;
+            }
+        }
+
+        if has_errors {
+            return Err(SdError::ConfigInvalid(
+                "Config has errors. Use --force to apply anyway.".to_string(),
+            ));
+        }
+    }
+
+    // Phase 3: Handle dry-run mode
+    if cli.is_dry_run_with(args.dry_run) || args.diff {
+        return cmd_apply_dry_run(cli, args, &config, output);
+    }
+
+    // Phase 4: Open device (a mock with --mock, none when only exporting)
+    let mock = args
+        .mock
+        .map(|model| device::mock::MockDevice::new(model).with_verify_writes(cli.verify_writes));
+    let real = if mock.is_some() || args.no_upload {
+        None
+    } else {
+        Some(open_device(cli)?)
+    };
+    let device: Option<&dyn DeviceOperations> = match (&mock, &real) {
+        (Some(mock), _) => Some(mock),
+        (None, Some(real)) => Some(real),
+        (None, None) => None,
+    };
+    let device_info = match device {
+        Some(device) => device.info().clone(),
+        None => offline_device_info(cli, args.model),
+    };
+    let config = config.for_model(device_info.model());
+
+    if let Some(dir) = &args.export_images {
+        std::fs::create_dir_all(dir).map_err(|e| {
+            SdError::Other(format!(
+                "Failed to create export directory {}: {e}",
+                dir.display()
+            ))
+        })?;
+    }
+
+    // Phase 4b: Back up the current state first (--snapshot-on-apply)
+    let backup = match (&args.snapshot_on_apply, &real) {
+        (Some(name), Some(_)) => Some(snapshot_before_apply(
+            name.as_deref(),
+            &args.config,
+            &device_info,
+        )?),
+        _ => None,
+    };
+    if let Some(snap) = &backup {
+        info!(snapshot = %snap.name, keys = snap.keys.len(), "Saved pre-apply snapshot");
+    }
+
+    // Phase 5: Apply brightness (unless --no-brightness)
+    if let (false, Some(device)) = (args.no_brightness, device) {
+        if let Some(brightness) = config.brightness {
+            debug!(brightness, "Setting brightness");
+            device.set_brightness(brightness)?;
+            state::record::brightness(brightness);
+            output.brightness_set(brightness);
+        }
+    }
+
+    // Phase 6: Apply key configurations
+    let mut results: Vec<BatchKeyResult> = Vec::new();
+    let mut success_count = 0;
+    let mut error_count = 0;
+    let mut exported: Vec<String> = Vec::new();
+    let cancel = CancelToken::on_ctrl_c();
+    let mut pacer = cli.write_pacer();
+    let mut rendered = args.dedupe_images.then(image_cache::RenderedImages::new);
+
+    // Most specific selector first (ties by selector text); each key is set
+    // once, by the first selector that matches it
+    let mut entries = Vec::with_capacity(config.keys.len());
+    for (selector_str, key_config) in &config.keys {
+        match KeySelector::parse(selector_str) {
+            Ok(selector) => entries.push((selector, selector_str, key_config)),
+            Err(e) => warn!(selector = selector_str, error = %e, "Skipping invalid selector"),
+        }
+    }
+    entries.sort_by_cached_key(|(selector, ..)| (selector.priority(), selector.to_string()));
+    let mut claimed = std::collections::HashSet::new();
+
+    for (selector, selector_str, key_config) in entries {
+        let keys = match selector.resolve(&device_info) {
+            Ok(k) => k,
+            Err(e) => {
+                warn!(selector = selector_str, error = %e, "Failed to resolve selector");
+                continue;
+            }
+        };
+
+        for key in keys {
+            if cancel.is_cancelled() {
+                break;
+            }
+            if !claimed.insert(key) {
+                debug!(
+                    key,
+                    selector = selector_str,
+                    "Key already set by a more specific selector"
+                );
+                continue;
+            }
+            if device.is_some() {
+                pacer.before_write();
+            }
+            let result = match (device, &args.export_images) {
+                (Some(device), export_dir) => apply_key_config(
+                    device,
+                    &device_info,
+                    key,
+                    key_config,
+                    &args.config,
+                    rendered.as_mut(),
+                )
+                .and_then(|res| {
+                    if let Some(dir) = export_dir {
+                        let size = device_info.key_size();
+                        if let Some(path) =
+                            export_key_image(dir, key, key_config, &args.config, size)?
+                        {
+                            exported.push(path.display().to_string());
+                        }
+                    }
+                    Ok(res)
+                }),
+                (None, Some(dir)) => {
+                    let size = device_info.key_size();
+                    export_key_image(dir, key, key_config, &args.config, size).map(|path| {
+                        path.map_or_else(
+                            // clear: false leaves the key untouched
+                            || BatchKeyResult {
+                                key,
+                                path: None,
+                                color: None,
+                                ok: true,
+                                error: None,
+                                warnings: Vec::new(),
+                                selector: None,
+                            },
+                            |path| {
+                                let result = BatchKeyResult::set_key_success(key, &path);
+                                exported.push(path.display().to_string());
+                                result
+                            },
+                        )
+                    })
+                }
+                (None, None) => unreachable!("--no-upload requires --export-images"),
+            };
+            match result {
+                Ok(res) => {
+                    success_count += 1;
+                    results.push(res.with_selector(selector_str));
+                }
+                Err(e) => {
+                    error_count += 1;
+                    results.push(BatchKeyResult {
+                        key,
+                        path: None,
+                        color: None,
+                        ok: false,
+                        error: Some(e.to_string()),
+                        warnings: Vec::new(),
+                        selector: Some(selector_str.clone()),
+                    });
+                }
+            }
+            if let (true, Some(result)) = (args.stream, results.last()) {
+                output.key_progress("apply", result);
+            }
+        }
+    }
+
+    // Phase 6b: Clear keys the config doesn't cover (--replace)
+    let mut replaced: Vec<u8> = Vec::new();
+    if let (true, Some(device)) = (args.replace, device) {
+        for key in replace_clear_keys(&config, &device_info) {
+            if cancel.is_cancelled() {
+                break;
+            }
+            debug!(key, "Clearing key not covered by config");
+            match device.clear_key(key) {
+                Ok(()) => {
+                    state::record::clear_key(key);
+                    success_count += 1;
+                    replaced.push(key);
+                    results.push(BatchKeyResult::clear_success(key));
+                }
+                Err(e) => {
+                    error_count += 1;
+                    results.push(BatchKeyResult::clear_failure(key, &e.to_string()));
+                }
+            }
+            if let (true, Some(result)) = (args.stream, results.last()) {
+                output.key_progress("apply", result);
+            }
+        }
+    }
+
+    if let Some(device) = device {
+        record_write_verification(device);
+    }
+
+    // Phase 7: Write the mock's resulting state (--dump-state)
+    if let (Some(mock), Some(path)) = (&mock, &args.dump_state) {
+        let json = serde_json::to_string_pretty(&mock.state_dump())
+            .map_err(|e| SdError::Other(format!("Failed to serialize mock state: {e}")))?;
+        std::fs::write(path, json + "\n")
+            .map_err(|e| SdError::Other(format!("Failed to write {}: {e}", path.display())))?;
+        debug!(path = %path.display(), "Mock state written");
+    }
+
+    // Phase 7b: Run the post-apply hook (--after, else the config's on_apply)
+    // only once every key reached a real device
+    let apply_summary = hook::ApplySummary {
+        config: &args.config,
+        name: config.name.as_deref(),
+        serial: &device_info.serial,
+        keys_set: success_count,
+        errors: error_count,
+        total: results.len(),
+    };
+    let hook_template = args.after.as_deref().or(config.on_apply.as_deref());
+    let hook_skipped = hook_template
+        .and_then(|_| hook::skip_reason(&apply_summary, mock.is_some(), cancel.is_cancelled()));
+    let hook = hook_template
+        .filter(|_| hook_skipped.is_none())
+        .map(|template| hook::run_apply_hook(template, &apply_summary));
+    if let Some(reason) = hook_skipped {
+        debug!(?reason, "Skipping apply hook");
+    }
+
+    // Phase 8: Output results
+    sort_batch_results(&mut results);
+    let summary = BatchSummary::new(results.len(), success_count, error_count);
+
+    if cli.use_json() {
+        let mut response = serde_json::json!({
+            "command": "apply",
+            "config": args.config.display().to_string(),
+            "config_name": config.name,
+            "device": {
+                "serial": device_info.serial,
+                "product": device_info.product_name,
+            },
+            "results": output::visible_results(&results, cli.only_errors),
+            "summary": summary,
+        });
+        if args.replace {
+            response["replaced"] = serde_json::json!(replaced);
+        }
+        if let Some(snap) = &backup {
+            response["backup_snapshot"] = serde_json::json!({
+                "name": snap.name,
+                "keys_saved": snap.keys.len(),
+                "brightness": snap.brightness,
+                "restore": format!("sd restore {}", snap.name),
+            });
+        }
+        if mock.is_some() {
+            response["mock"] = serde_json::json!(true);
+            if let Some(path) = &args.dump_state {
+                response["state_file"] = serde_json::json!(path.display().to_string());
+            }
+        }
+        if let Some(dir) = &args.export_images {
+            response["uploaded"] = serde_json::json!(!args.no_upload);
+            response["export_dir"] = serde_json::json!(dir.display().to_string());
+            response["exported"] = serde_json::json!(exported);
+        }
+        if let Some(rendered) = &rendered {
+            response["dedupe"] = serde_json::json!({
+                "unique_images": rendered.unique(),
+                "reused": rendered.reused(),
+            });
+        }
+        if let Some(outcome) = &hook {
+            response["hook"] = serde_json::json!(outcome);
+        } else if let Some(reason) = hook_skipped {
+            response["hook"] = serde_json::json!({ "skipped": reason });
+        }
+        output_json(cli, &response);
+    } else {
+        if let Some(name) = &config.name {
+            output.info(&format!("Applied config: {}", name));
+        }
+        output.batch_set_keys(&results, &summary);
+        if cli.verbose > 0 {
+            for result in &results {
+                if let Some(selector) = &result.selector {
+                    output.info(&format!(
+                        "Key {} set by selector '{selector}'",
+                        cli.user_key(result.key)
+                    ));
+                }
+            }
+        }
+        if !replaced.is_empty() {
+            output.info(&format!(
+                "Cleared {} key(s) not in config: {}",
+                replaced.len(),
+                join_keys(&replaced)
+            ));
+        }
+        if let (Some(rendered), true) = (&rendered, cli.verbose > 0) {
+            output.info(&format!(
+                "Rendered {} distinct image(s), reused {} time(s)",
+                rendered.unique(),
+                rendered.reused()
+            ));
+        }
+        if let (Some(_), Some(path)) = (&mock, &args.dump_state) {
+            output.info(&format!("Wrote mock device state to {}", path.display()));
+        }
+        if let Some(snap) = &backup {
+            output.info(&format!(
+                "Saved the previous state as snapshot '{}' ({} keys); undo with `sd restore {}`",
+                snap.name,
+                snap.keys.len(),
+                snap.name
+            ));
+            if snap.keys.is_empty() && snap.brightness.is_none() {
+                output.warning(
+                    "Nothing was tracked before this apply, so the snapshot is empty and \
+                     restoring it changes nothing",
+                );
+            }
+        }
+        if let Some(dir) = &args.export_images {
+            output.info(&format!(
+                "Exported {} key image(s) to {}",
+                exported.len(),
+                dir.display()
+            ));
+        }
+        match &hook {
+            Some(outcome) if !outcome.ok => output.warning(&format!(
+                "Apply hook failed ({}): {}",
+                hook_failure(outcome),
+                outcome.command
+            )),
+            Some(outcome) if cli.verbose > 0 => {
+                output.info(&format!("Ran apply hook: {}", outcome.command));
+            }
+            None if cli.verbose > 0 => {
+                if let Some(reason) = hook_skipped {
+                    output.info(&format!("Skipped apply hook: {}", reason.describe()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    cancel.check()?;
+    if error_count > 0 {
+        return Err(SdError::Other(format!(
+            "{} key(s) failed to apply",
+            error_count
+        )));
+    }
+    match hook {
+        Some(outcome) if !outcome.ok && args.strict_hooks => Err(SdError::Other(format!(
+            "Apply hook failed ({}): {}",
+            hook_failure(&outcome),
+            outcome.command
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Why a hook failed, for messages: its exit code or start error.
+fn hook_failure(outcome: &hook::HookOutcome) -> String {
+    match (&outcome.error, outcome.exit_code) {
+        (Some(error), _) => error.clone(),
+        (None, Some(code)) => format!("exit code {code}"),
+        (None, None) => "killed by a signal".to_string(),
+    }
+}
+
+/// Apply a single key configuration to the device.
+///
+/// With `rendered` (`--dedupe-images`), images are rendered once per distinct
+/// source and the result is uploaded to every key that uses it.
+fn apply_key_config(
+    device: &dyn DeviceOperations,
+    device_info: &device::DeviceInfo,
+    key: u8,
+    key_config: &config::KeyConfig,
+    config_path: &std::path::Path,
+    rendered: Option<&mut image_cache::RenderedImages>,
+) -> Result<BatchKeyResult> {
+    match key_config {
+        config::KeyConfig::Image { .. } | config::KeyConfig::Pattern { .. } => {
+            let resolved = key_image_path(key, key_config, config_path)?
+                .expect("image and pattern configs have a path");
+            if !resolved.exists() && data_uri::as_data_uri(&resolved).is_none() {
+                return Err(SdError::ImageNotFound {
+                    path: resolved.display().to_string(),
+                });
+            }
+
+            let strategy = image_ops::ResizeStrategy::Fit;
+            if let Some(rendered) = rendered {
+                let image =
+                    rendered.get_or_render(&resolved, device_info.key_size(), strategy, || {
+                        device.render_key_image(&resolved, strategy)
+                    })?;
+                device.set_key_rendered(key, image, &resolved)?;
+            } else {
+                device.set_key_image(key, &resolved, strategy)?;
+            }
+            state::record::set_key(key, resolved.clone());
+            Ok(BatchKeyResult::set_key_success(key, &resolved))
+        }
+        config::KeyConfig::Color { color } => {
+            let (r, g, b) = color.to_rgb()?;
+            device.fill_key_color(key, (r, g, b))?;
+            let color_str = format!("#{:02x}{:02x}{:02x}", r, g, b);
+            state::record::fill_key(key, color_str.clone());
+            Ok(BatchKeyResult::fill_success(key, &color_str))
+        }
+        config::KeyConfig::Clear { clear } => {
+            if *clear {
+                device.clear_key(key)?;
+                state::record::clear_key(key);
+                Ok(BatchKeyResult::clear_success(key))
+            } else {
+                // clear: false means skip this key
+                Ok(BatchKeyResult {
+                    key,
+                    path: None,
+                    color: None,
+                    ok: true,
+                    error: None,
+                    warnings: Vec::new(),
+                    selector: None,
+                })
+            }
+        }
+    }
+}
+
+/// Resolve the image file a key config uploads, if it's an image or pattern.
+///
+/// Paths are relative to the config file, with `${VAR}`s expanded; patterns
+/// substitute `{index}` (also `{index:02d}`, `{index:03d}`). Inline image
+/// data comes back as a `data:` URI, which the image loaders accept as a path.
+fn key_image_path(
+    key: u8,
+    key_config: &config::KeyConfig,
+    config_path: &std::path::Path,
+) -> Result<Option<std::path::PathBuf>> {
+    let config_dir = config_path.parent().unwrap_or(std::path::Path::new(""));
+    match key_config {
+        config::KeyConfig::Image {
+            data: Some(data), ..
+        } => data_uri::from_base64(data).map(|uri| Some(std::path::PathBuf::from(uri))),
+        config::KeyConfig::Image { image, .. } => config::resolve_path(image, config_dir).map(Some),
+        config::KeyConfig::Pattern { pattern, .. } => {
+            let filename = pattern
+                .replace("{index}", &key.to_string())
+                .replace("{index:02d}", &format!("{:02}", key))
+                .replace("{index:03d}", &format!("{:03}", key));
+            config::resolve_path(std::path::Path::new(&filename), config_dir).map(Some)
+        }
+        config::KeyConfig::Color { .. } | config::KeyConfig::Clear { .. } => Ok(None),
+    }
+}
+
+/// Render a key config exactly as `apply` would upload it and save it as
+/// `key-{index}.png` in `dir` (the default `set-keys` pattern).
+///
+/// Colors are written as solid swatches and cleared keys as black. Returns
+/// `None` for `clear: false`, which leaves the key untouched.
+fn export_key_image(
+    dir: &std::path::Path,
+    key: u8,
+    key_config: &config::KeyConfig,
+    config_path: &std::path::Path,
+    size: image_ops::KeySize,
+) -> Result<Option<std::path::PathBuf>> {
+    let rendered = match key_config {
+        config::KeyConfig::Image { .. } | config::KeyConfig::Pattern { .. } => {
+            let source = key_image_path(key, key_config, config_path)?
+                .expect("image and pattern configs have a path");
+            image_ops::process_for_key(&source, size, image_ops::ResizeStrategy::Fit)?
+        }
+        config::KeyConfig::Color { color } => {
+            let (r, g, b) = color.to_rgb()?;
+            image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+                size.width,
+                size.height,
+                image::Rgb([r, g, b]),
+            ))
+        }
+        config::KeyConfig::Clear { clear: true } => {
+            image::DynamicImage::ImageRgb8(image::RgbImage::new(size.width, size.height))
+        }
+        config::KeyConfig::Clear { clear: false } => return Ok(None),
+    };
+
+    let path = dir.join(format!("key-{key}.png"));
+    rendered
+        .save_with_format(&path, image::ImageFormat::Png)
+        .map_err(|e| SdError::Other(format!("Failed to export {}: {e}", path.display())))?;
+    Ok(Some(path))
+}
+
+/// Geometry to render for when the device isn't used.
+///
+/// An explicit `--model` wins; otherwise a connected device is used if there
+/// is one, falling back to the settings' `default_model` (XL if unset).
+fn offline_device_info(cli: &Cli, model: Option<device::DeviceModel>) -> device::DeviceInfo {
+    if let Some(model) = model {
+        return device::DeviceInfo::for_model(model);
+    }
+    open_device(cli).map_or_else(
+        |_| device::DeviceInfo::for_model(cli.offline_model()),
+        |device| device::get_device_info(&device),
+    )
+}
+
+/// Keys `apply --replace` clears: those no selector in `config` covers.
+fn replace_clear_keys(
+    config: &config::declarative::ProfileConfig,
+    device_info: &device::DeviceInfo,
+) -> Vec<u8> {
+    let selectors: Vec<config::KeySelector> = config
+        .keys
+        .keys()
+        .filter_map(|s| config::KeySelector::parse(s).ok())
+        .collect();
+    config::uncovered_keys(&selectors, device_info)
+}
+
+/// Format key indices as a comma-separated list.
+fn join_keys(keys: &[u8]) -> String {
+    keys.iter()
+        .map(u8::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Dry-run handler for apply command.
+#[allow(clippy::unnecessary_wraps)]
+fn cmd_apply_dry_run(
+    cli: &Cli,
+    args: &cli::ApplyArgs,
+    config: &config::declarative::ProfileConfig,
+    output: &dyn Output,
+) -> Result<()> {
+    use config::KeySelector;
+
+    // Try to get device info
+    let device_result = open_device(cli);
+    let device_info = device_result.as_ref().ok().map(device::get_device_info);
+    let config = &config.for_model(device_info.as_ref().and_then(device::DeviceInfo::model));
+
+    let mut operations = Vec::new();
+    let mut warnings = Vec::new();
+
+    // Build operation list
+    for (selector_str, key_config) in &config.keys {
+        let selector = match KeySelector::parse(selector_str) {
+            Ok(s) => s,
+            Err(e) => {
+                warnings.push(format!("Invalid selector '{}': {}", selector_str, e));
+                continue;
+            }
+        };
+
+        let keys = if let Some(ref info) = device_info {
+            match selector.resolve(info) {
+                Ok(k) => k,
+                Err(e) => {
+                    warnings.push(format!("Cannot resolve '{}': {}", selector_str, e));
+                    continue;
+                }
+            }
+        } else {
+            // No device - show selector as-is
+            vec![]
+        };
+
+        let action = match key_config {
+            config::KeyConfig::Image { data: Some(_), .. } => "set image: inline data".to_string(),
+            config::KeyConfig::Image { image, .. } => format!("set image: {}", image.display()),
+            config::KeyConfig::Color { color } => format!("fill color: {:?}", color),
+            config::KeyConfig::Clear { clear } => {
+                if *clear {
+                    "clear".to_string()
+                } else {
+                    "skip".to_string()
+                }
+            }
+            config::KeyConfig::Pattern { pattern, .. } => format!("pattern: {}", pattern),
+        };
+
+        operations.push(serde_json::json!({
+            "selector": selector_str,
+            "keys": keys,
+            "action": action,
+        }));
+    }
+
+    // --replace clears whatever the selectors leave uncovered
+    let would_clear = args.replace.then(|| {
+        let info = device_info.clone().unwrap_or_else(|| {
+            device::DeviceInfo::for_model(args.model.unwrap_or(cli.offline_model()))
+        });
+        replace_clear_keys(config, &info)
+    });
+
+    if cli.use_json() {
+        let response = serde_json::json!({
+            "dry_run": true,
+            "command": "apply",
+            "config": args.config.display().to_string(),
+            "config_name": config.name,
+            "brightness": config.brightness,
+            "no_brightness": args.no_brightness,
+            "device": device_info.as_ref().map(|i| serde_json::json!({
+                "serial": i.serial,
+                "product": i.product_name,
+                "key_count": i.key_count,
+            })),
+            "operations": operations,
+            "replace": args.replace,
+            "would_clear": would_clear,
+            "warnings": warnings,
+        });
+        output_json(cli, &response);
+    } else {
+        println!("DRY RUN: Would apply configuration");
+        println!("  Config: {}", args.config.display());
+        if let Some(name) = &config.name {
+            println!("  Profile: {}", name);
+        }
+
+        if let Some(ref info) = device_info {
+            println!("  Device: {} (serial: {})", info.product_name, info.serial);
+        } else {
+            println!("  Device: not connected");
+        }
+
+        if let Some(brightness) = config.brightness {
+            if args.no_brightness {
+                println!(
+                    "  Brightness: {} (skipped with --no-brightness)",
+                    brightness
+                );
+            } else {
+                println!("  Brightness: would set to {}%", brightness);
+            }
+        }
+
+        println!("\n  Key operations:");
+        for op in &operations {
+            println!(
+                "    {} -> {}",
+                op["selector"].as_str().unwrap_or("?"),
+                op["action"].as_str().unwrap_or("?")
+            );
+        }
+
+        if let Some(keys) = &would_clear {
+            if keys.is_empty() {
+                println!("\n  Replace: every key is covered, nothing extra to clear");
+            } else {
+                println!("\n  Would clear (--replace): {}", join_keys(keys));
+            }
+        }
+
+        if !warnings.is_empty() {
+            println!("\n  Warnings:");
+            for w in &warnings {
+                println!("    - {}", w);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// === Snapshot Commands ===
+
+fn cmd_save(cli: &Cli, args: &cli::SaveArgs) -> Result<()> {
+    // Validate snapshot name
+    if !is_valid_snapshot_name(&args.name) {
+        return Err(SdError::Other(
+            "Snapshot name must be 1-64 characters, alphanumeric with hyphens/underscores"
+                .to_string(),
+        ));
+    }
+
+    // Get device info for snapshot metadata
+    let device = open_device(cli)?;
+    let device_info = device::get_device_info(&device);
+
+    // Open snapshot database
+    let mut db = snapshot::SnapshotDb::open_default()?;
+
+    // Check for existing snapshot
+    if db.snapshot_exists(&args.name)? && !args.force {
+        return Err(SdError::Other(format!(
+            "Snapshot '{}' already exists. Use --force to overwrite.",
+            args.name
+        )));
+    }
+
+    // Session state is all sd tracks, so --session-only saves the same keys
+    tracing::debug!(
+        session_only = args.session_only,
+        "Saving tracked session state"
+    );
+    let (id, snap) = save_session_snapshot(
+        &mut db,
+        &args.name,
+        args.description.clone(),
+        &device_info,
+        !args.no_brightness,
+    )?;
+    let brightness = snap.brightness;
+
+    // Output result
+    if cli.use_json() {
+        output_json(
+            cli,
+            &serde_json::json!({
+                "command": "save",
+                "ok": true,
+                "snapshot": {
+                    "id": id,
+                    "name": args.name,
+                    "device_model": device_info.product_name,
+                    "key_count": device_info.key_count,
+                    "brightness": brightness,
+                    "keys_saved": snap.keys.len(),
+                }
+            }),
+        );
+    } else if !cli.quiet {
+        println!(
+            "Saved snapshot '{}' ({} keys{})",
+            args.name,
+            snap.keys.len(),
+            brightness.map_or(String::new(), |b| format!(", brightness {b}%"))
+        );
+    }
+
+    Ok(())
+}
+
+/// Save the state before `apply` changes anything (`--snapshot-on-apply`).
+///
+/// Without a name the snapshot is called `pre-apply-<UTC timestamp>`.
+fn snapshot_before_apply(
+    name: Option<&str>,
+    config: &std::path::Path,
+    device_info: &device::DeviceInfo,
+) -> Result<snapshot::Snapshot> {
+    let name = name.map_or_else(
+        || format!("pre-apply-{}", chrono::Utc::now().format("%Y%m%d-%H%M%S")),
+        str::to_string,
+    );
+    if !is_valid_snapshot_name(&name) {
+        return Err(SdError::Other(format!(
+            "Invalid --snapshot-on-apply name '{name}': use 1-64 characters, alphanumeric \
+             with hyphens/underscores"
+        )));
+    }
+
+    let mut db = snapshot::SnapshotDb::open_default()?;
+    // Never overwrite: the existing snapshot may be someone's only way back
+    if db.snapshot_exists(&name)? {
+        return Err(SdError::Other(format!(
+            "Snapshot '{name}' already exists. Pick another --snapshot-on-apply name, \
+             or leave it out for a timestamped one"
+        )));
+    }
+    let description = format!("Before applying {}", config.display());
+    save_session_snapshot(&mut db, &name, Some(description), device_info, true)
+        .map(|(_, snap)| snap)
+}
+
+/// Save the session's tracked key state (and brightness) as snapshot `name`,
+/// replacing any snapshot of that name.
+fn save_session_snapshot(
+    db: &mut snapshot::SnapshotDb,
+    name: &str,
+    description: Option<String>,
+    device_info: &device::DeviceInfo,
+    include_brightness: bool,
+) -> Result<(i64, snapshot::Snapshot)> {
+    // We can only save what this session has tracked
+    let session = state::session_state();
+    let mut keys = Vec::new();
+    for (&key_index, session_key) in &session.keys {
+        let key_state = match session_key {
+            state::KeyState::Image { path } => {
+                // Hash the image for content-addressable storage
+                let hash = hash_image_file(path)?;
+                // Cache the image
+                cache_image(db, &hash, path)?;
+                snapshot::KeyState::Image {
+                    source_path: Some(path.clone()),
+                    image_hash: hash,
+                }
+            }
+            state::KeyState::Color { hex } => snapshot::KeyState::Color { hex: hex.clone() },
+            state::KeyState::Cleared => snapshot::KeyState::Clear,
+        };
+        keys.push(snapshot::SnapshotKey {
+            key_index,
+            state: key_state,
+        });
+    }
+
+    let mut snap = snapshot::Snapshot::new(
+        name.to_string(),
+        device_info.product_name.clone(),
+        device_info.key_count,
+        device_info.key_width as u32,
+        device_info.key_height as u32,
+    );
+    snap.brightness = session.brightness.filter(|_| include_brightness);
+    snap.description = description;
+    snap.device_serial = Some(device_info.serial.clone());
+    snap.keys = keys;
+
+    let id = db.save_snapshot(&snap)?;
+    Ok((id, snap))
+}
+
+fn cmd_restore(cli: &Cli, args: &cli::RestoreArgs) -> Result<()> {
+    // Open snapshot database
+    let db = snapshot::SnapshotDb::open_default()?;
+
+    // Load snapshot
+    let snap = db
+        .load_snapshot(&args.name)?
+        .ok_or_else(|| SdError::Other(format!("Snapshot '{}' not found", args.name)))?;
+
+    if cli.is_dry_run() {
+        return cmd_restore_dry_run(cli, args, &snap);
+    }
+
+    // Open device
+    let device = open_device(cli)?;
+    let device_info = device::get_device_info(&device);
+
+    // Check device compatibility
+    if snap.key_count != device_info.key_count {
+        return Err(SdError::Other(format!(
+            "Snapshot was saved for {} keys, but device has {} keys",
+            snap.key_count, device_info.key_count
+        )));
+    }
+
+    // Apply brightness if present and not skipped
+    if !args.no_brightness {
+        if let Some(brightness) = snap.brightness {
+            device::set_brightness(&device, brightness)?;
+            state::record::brightness(brightness);
+        }
+    }
+
+    // Apply keys
+    let mut applied_count = 0;
+    let mut error_count = 0;
+
+    for key in &snap.keys {
+        let result = match &key.state {
+            snapshot::KeyState::Image {
+                source_path,
+                image_hash,
+            } => {
+                // Try to load from cache first, then source path
+                apply_cached_image(&device, key.key_index, image_hash, source_path.as_ref())
+            }
+            snapshot::KeyState::Color { hex } => {
+                let color = parse_color(hex)?;
+                device::fill_key_color(&device, key.key_index, color).map(|()| {
+                    state::record::fill_key(key.key_index, hex.clone());
+                })
+            }
+            snapshot::KeyState::Clear => device::clear_key(&device, key.key_index).map(|()| {
+                state::record::clear_key(key.key_index);
+            }),
+        };
+
+        match result {
+            Ok(()) => applied_count += 1,
+            Err(e) => {
+                error_count += 1;
+                tracing::warn!(key = key.key_index, error = %e, "Failed to restore key");
+            }
+        }
+    }
+    record_write_verification(&device);
+
+    // Output result
+    if cli.use_json() {
+        output_json(
+            cli,
+            &serde_json::json!({
+                "command": "restore",
+                "ok": error_count == 0,
+                "snapshot": args.name,
+                "keys_applied": applied_count,
+                "keys_failed": error_count,
+                "brightness_applied": !args.no_brightness && snap.brightness.is_some(),
+            }),
+        );
+    } else if !cli.quiet {
+        if error_count == 0 {
+            println!("Restored snapshot '{}' ({} keys)", args.name, applied_count);
+        } else {
+            println!(
+                "Restored snapshot '{}' ({} keys, {} failed)",
+                args.name, applied_count, error_count
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Dry-run details for restore command.
+#[derive(Serialize)]
+struct RestoreDryRunDetails<'a> {
+    snapshot: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    brightness: Option<u8>,
+    keys: Vec<RestoreDryRunOperation<'a>>,
+}
+
+/// Per-key dry-run operation for restore.
+#[derive(Serialize)]
+struct RestoreDryRunOperation<'a> {
+    key: u8,
+    #[serde(flatten)]
+    state: &'a snapshot::KeyState,
+}
+
+/// Dry-run handler for restore command: lists what the snapshot would set.
+#[allow(clippy::unnecessary_wraps)] // Consistent return type
+fn cmd_restore_dry_run(
+    cli: &Cli,
+    args: &cli::RestoreArgs,
+    snap: &snapshot::Snapshot,
+) -> Result<()> {
+    let device_info = open_device(cli).ok().map(|d| device::get_device_info(&d));
+    let brightness = snap.brightness.filter(|_| !args.no_brightness);
+    let keys: Vec<RestoreDryRunOperation> = snap
+        .keys
+        .iter()
+        .map(|key| RestoreDryRunOperation {
+            key: cli.user_key(key.key_index),
+            state: &key.state,
+        })
+        .collect();
+    let mismatch = device_info
+        .as_ref()
+        .filter(|info| info.key_count != snap.key_count)
+        .map(|info| {
+            format!(
+                "Snapshot was saved for {} keys, but device has {} keys",
+                snap.key_count, info.key_count
+            )
+        });
+
+    if cli.use_json() {
+        let mut warnings = Vec::new();
+        let ctx = device_info.as_ref().map_or_else(
+            || {
+                warnings.push("Device not connected".to_string());
+                DeviceContext::disconnected(cli.serial.clone())
+            },
+            DeviceContext::from_info,
+        );
+        let details = RestoreDryRunDetails {
+            snapshot: &args.name,
+            brightness,
+            keys,
+        };
+        let response = match mismatch {
+            None => DryRunResponse::success("restore", details, ctx),
+            Some(error) => DryRunResponse::failure(
+                "restore",
+                "Snapshot does not fit the device",
+                vec![ValidationError {
+                    field: "snapshot".to_string(),
+                    error,
+                    suggestion: None,
+                }],
+                details,
+                ctx,
+            ),
+        };
+        output_json(cli, &response.with_warnings(warnings));
+    } else {
+        println!(
+            "DRY RUN: Would restore snapshot '{}' ({} keys)",
+            args.name,
+            keys.len()
+        );
+        match &device_info {
+            Some(info) => println!("  Device: {} ({})", info.product_name, info.serial),
+            None => println!("  Device: not connected"),
+        }
+        if let Some(error) = &mismatch {
+            println!("  WARNING: {error}");
+        }
+        if let Some(level) = brightness {
+            println!("  Brightness: {level}%");
+        }
+        println!();
+        for op in &keys {
+            match op.state {
+                snapshot::KeyState::Image { image_hash, .. } => {
+                    println!(
+                        "  Key {}: image {}",
+                        op.key,
+                        &image_hash[..12.min(image_hash.len())]
                     );
                 }
+                snapshot::KeyState::Color { hex } => println!("  Key {}: {hex}", op.key),
+                snapshot::KeyState::Clear => println!("  Key {}: clear", op.key),
             }
         }
     }
 
-    // Phase 6: Device-specific validation (optional, if device connected)
-    // Try to get device info, but don't fail if no device
-    match device::list_devices() {
-        Ok(devices) if !devices.is_empty() => {
-            let device_info = &devices[0];
-            let key_count = device_info.key_count;
+    Ok(())
+}
 
-            for (selector_str, _) in &config.keys {
-                if let Ok(selector) = config::KeySelector::parse(selector_str) {
-                    // Check if selector indices are valid for this device
-                    match &selector {
-                        config::KeySelector::Single(idx) => {
-                            if *idx >= key_count {
-                                result.add_error(
-                                    format!("key[{}]", selector_str),
-                                    format!(
-                                        "Key index {} out of range for {} (0-{})",
-                                        idx,
-                                        device_info.product_name,
-                                        key_count - 1
-                                    ),
-                                );
-                            }
-                        }
-                        config::KeySelector::Range { start, end } => {
-                            if *end >= key_count {
-                                result.add_error(
-                                    format!("key[{}]", selector_str),
-                                    format!(
-                                        "Range end {} out of range for {} (0-{})",
-                                        end,
-                                        device_info.product_name,
-                                        key_count - 1
-                                    ),
-                                );
-                            }
-                            if start > end {
-                                result.add_error(
-                                    format!("key[{}]", selector_str),
-                                    format!("Invalid range: start {} > end {}", start, end),
-                                );
-                            }
-                        }
-                        config::KeySelector::Row(row) => {
-                            if *row >= device_info.rows {
-                                result.add_error(
-                                    format!("key[{}]", selector_str),
-                                    format!(
-                                        "Row {} out of range for {} (0-{})",
-                                        row,
-                                        device_info.product_name,
-                                        device_info.rows - 1
-                                    ),
-                                );
-                            }
-                        }
-                        config::KeySelector::Column(col) => {
-                            if *col >= device_info.cols {
-                                result.add_error(
-                                    format!("key[{}]", selector_str),
-                                    format!(
-                                        "Column {} out of range for {} (0-{})",
-                                        col,
-                                        device_info.product_name,
-                                        device_info.cols - 1
-                                    ),
-                                );
-                            }
-                        }
-                        config::KeySelector::Default => {}
-                    }
+fn cmd_snapshots(cli: &Cli, args: &cli::SnapshotsArgs) -> Result<()> {
+    // Open snapshot database
+    let db = snapshot::SnapshotDb::open_default()?;
+
+    // List snapshots
+    let offset = args.offset.unwrap_or(0);
+    let snapshots = db.list_snapshots_page(args.limit, offset)?;
+    let total = if args.is_paginated() {
+        db.count_snapshots()?
+    } else {
+        u32::try_from(snapshots.len()).unwrap_or(u32::MAX)
+    };
+
+    if cli.use_json() {
+        if args.is_paginated() {
+            // Paginating UIs need the total to know how many pages exist
+            output_json(
+                cli,
+                &serde_json::json!({
+                    "snapshots": snapshots,
+                    "total": total,
+                    "limit": args.limit,
+                    "offset": offset,
+                }),
+            );
+        } else {
+            output_json(cli, &snapshots);
+        }
+    } else if total == 0 {
+        println!("No snapshots saved");
+        println!("Use 'sd save <name>' to save the current device state");
+    } else if snapshots.is_empty() {
+        println!("No snapshots after offset {offset} ({total} saved)");
+    } else {
+        let console = Console::new();
+        let success = Color::parse("#00D26A").expect("valid color");
+        let muted = Color::parse("#747D8C").expect("valid color");
+
+        for snap in &snapshots {
+            if args.long {
+                let mut line = Text::new("");
+                line.append_styled(&snap.name, Style::new().color(success.clone()));
+                line.append(&format!(
+                    ": {} ({} keys{})",
+                    snap.device_model,
+                    snap.key_count,
+                    snap.brightness
+                        .map_or(String::new(), |b| format!(", {b}% brightness"))
+                ));
+                console.print_text(&line);
+                if let Some(ref desc) = snap.description {
+                    console.print_styled(&format!("  {desc}"), Style::new().color(muted.clone()));
                 }
+                console.print(&format!(
+                    "  Created: {}",
+                    cli.time_format.format(&snap.created_at, "%Y-%m-%d %H:%M")
+                ));
+            } else {
+                console.print(&snap.name);
             }
         }
-        _ => {
-            result.add_warning(
-                "device",
-                "No device connected; skipping device-specific validation",
+        if args.is_paginated() {
+            let first = offset.saturating_add(1);
+            let last = offset.saturating_add(u32::try_from(snapshots.len()).unwrap_or(u32::MAX));
+            console.print_styled(
+                &format!("Showing {first}-{last} of {total}"),
+                Style::new().color(muted),
             );
         }
     }
 
-    info!(
-        valid = result.is_valid(),
-        errors = result.summary.error_count,
-        warnings = result.summary.warning_count,
-        "Validation complete"
-    );
-
-    output.validation_result(&result);
+    Ok(())
+}
 
-    // Exit with error if not valid or if strict mode and warnings exist
-    if !result.is_valid() {
-        Err(SdError::ConfigInvalid(format!(
-            "{} error(s) found",
-            result.summary.error_count
-        )))
-    } else if args.strict && result.summary.warning_count > 0 {
-        Err(SdError::ConfigInvalid(format!(
-            "{} warning(s) found (strict mode)",
-            result.summary.warning_count
-        )))
-    } else {
-        Ok(())
+fn cmd_snapshot(cli: &Cli, args: &cli::SnapshotCommand) -> Result<()> {
+    match &args.command {
+        cli::SnapshotSubcommand::Show(show_args) => cmd_snapshot_show(cli, show_args),
+        cli::SnapshotSubcommand::Delete(delete_args) => cmd_snapshot_delete(cli, delete_args),
+        cli::SnapshotSubcommand::Edit(edit_args) => cmd_snapshot_edit(cli, edit_args),
+        cli::SnapshotSubcommand::Compare(compare_args) => cmd_snapshot_compare(cli, compare_args),
+        cli::SnapshotSubcommand::Export(export_args) => cmd_snapshot_export(cli, export_args),
+        cli::SnapshotSubcommand::Import(import_args) => cmd_snapshot_import(cli, import_args),
     }
 }
 
-/// Apply a declarative configuration to the device.
-fn cmd_apply(cli: &Cli, args: &cli::ApplyArgs, output: &dyn Output) -> Result<()> {
-    use config::KeySelector;
-    use config::declarative::load_config;
-    use tracing::{debug, info, warn};
-
-    info!(config = %args.config.display(), "Applying configuration");
-
-    // Phase 1: Load and validate config
-    if !args.config.exists() {
-        return Err(SdError::ConfigNotFound {
-            path: args.config.display().to_string(),
-        });
-    }
+fn cmd_snapshot_show(cli: &Cli, args: &cli::SnapshotShowArgs) -> Result<()> {
+    // Open snapshot database
+    let db = snapshot::SnapshotDb::open_default()?;
 
-    let config = load_config(&args.config)?;
-    debug!(
-        name = ?config.name,
-        keys = config.keys.len(),
-        brightness = ?config.brightness,
-        "Config loaded"
-    );
+    // Load snapshot
+    let snap = db
+        .load_snapshot(&args.name)?
+        .ok_or_else(|| SdError::Other(format!("Snapshot '{}' not found", args.name)))?;
 
-    // Phase 2: Validate config (unless --force)
-    if !args.force {
-        // Run validation to check for errors
-        let mut has_errors = false;
+    let images = args
+        .images
+        .as_deref()
+        .map(|dir| export_snapshot_images(&db, &snap, dir))
+        .transpose()?;
 
-        // Check brightness
-        if let Some(brightness) = config.brightness {
-            if brightness > 100 {
-                output.error(&SdError::InvalidBrightness { value: brightness });
-                has_errors = true;
+    if cli.use_json() {
+        match images {
+            Some(images) => {
+                let mut response = serde_json::to_value(&snap)
+                    .map_err(|e| SdError::Other(format!("Failed to serialize snapshot: {e}")))?;
+                response["images"] = serde_json::json!(images);
+                output_json(cli, &response);
             }
+            None => output_json(cli, &snap),
         }
+    } else {
+        let console = Console::new();
+        let bold = Style::new().bold();
 
-        // Check key configs
-        for (selector_str, key_config) in &config.keys {
-            if let Err(e) = KeySelector::parse(selector_str) {
-                output.error(&SdError::ConfigParse(format!(
-                    "Invalid selector '{}': {}",
-                    selector_str, e
-                )));
-                has_errors = true;
-            }
-            if let Err(e) = key_config.validate() {
-                output.error(&SdError::ConfigParse(format!(
-                    "Invalid key config for '{}': {}",
-                    selector_str, e
-                )));
-                has_errors = true;
-            }
-        }
+        console.print_styled("Snapshot Details", Style::new().bold().underline());
+        console.print("");
 
-        if has_errors {
-            return Err(SdError::ConfigInvalid(
-                "Config has errors. Use --force to apply anyway.".to_string(),
-            ));
+        let mut line = Text::new("");
+        line.append_styled("Name", bold.clone());
+        line.append(&format!(": {}", snap.name));
+        console.print_text(&line);
+
+        if let Some(ref desc) = snap.description {
+            let mut line = Text::new("");
+            line.append_styled("Description", bold.clone());
+            line.append(&format!(": {desc}"));
+            console.print_text(&line);
         }
-    }
 
-    // Phase 3: Handle dry-run mode
-    if args.dry_run || args.diff {
-        return cmd_apply_dry_run(cli, args, &config, output);
-    }
+        let mut line = Text::new("");
+        line.append_styled("Device", bold.clone());
+        line.append(&format!(": {}", snap.device_model));
+        console.print_text(&line);
 
-    // Phase 4: Open device
-    let device = open_device(cli)?;
-    let device_info = device::get_device_info(&device);
+        if let Some(ref serial) = snap.device_serial {
+            let mut line = Text::new("");
+            line.append_styled("Serial", bold.clone());
+            line.append(&format!(": {serial}"));
+            console.print_text(&line);
+        }
 
-    // Phase 5: Apply brightness (unless --no-brightness)
-    if !args.no_brightness {
-        if let Some(brightness) = config.brightness {
-            debug!(brightness, "Setting brightness");
-            device::set_brightness(&device, brightness)?;
-            state::record::brightness(brightness);
-            output.brightness_set(brightness);
+        let mut line = Text::new("");
+        line.append_styled("Keys", bold.clone());
+        line.append(&format!(
+            ": {} ({}x{} px)",
+            snap.key_count, snap.key_width, snap.key_height
+        ));
+        console.print_text(&line);
+
+        if let Some(brightness) = snap.brightness {
+            let mut line = Text::new("");
+            line.append_styled("Brightness", bold.clone());
+            line.append(&format!(": {brightness}%"));
+            console.print_text(&line);
         }
-    }
 
-    // Phase 6: Apply key configurations
-    let mut results: Vec<BatchKeyResult> = Vec::new();
-    let mut success_count = 0;
-    let mut error_count = 0;
+        let mut line = Text::new("");
+        line.append_styled("Created", bold.clone());
+        line.append(&format!(
+            ": {}",
+            cli.time_format
+                .format(&snap.created_at, "%Y-%m-%d %H:%M:%S")
+        ));
+        console.print_text(&line);
 
-    // Process keys in selector priority order
-    for (selector_str, key_config) in &config.keys {
-        let selector = match KeySelector::parse(selector_str) {
-            Ok(s) => s,
-            Err(e) => {
-                warn!(selector = selector_str, error = %e, "Skipping invalid selector");
-                continue;
-            }
-        };
+        let mut line = Text::new("");
+        line.append_styled("Updated", bold.clone());
+        line.append(&format!(
+            ": {}",
+            cli.time_format
+                .format(&snap.updated_at, "%Y-%m-%d %H:%M:%S")
+        ));
+        console.print_text(&line);
 
-        let keys = match selector.resolve(&device_info) {
-            Ok(k) => k,
-            Err(e) => {
-                warn!(selector = selector_str, error = %e, "Failed to resolve selector");
-                continue;
+        if !snap.keys.is_empty() {
+            console.print("");
+            console.print_styled("Keys:", bold);
+            for key in &snap.keys {
+                console.print(&format!(
+                    "  Key {}: {}",
+                    key.key_index,
+                    describe_key_state(&key.state)
+                ));
             }
-        };
+        }
 
-        for key in keys {
-            let result = apply_key_config(&device, &device_info, key, key_config, &args.config);
-            match result {
-                Ok(res) => {
-                    success_count += 1;
-                    results.push(res);
-                }
-                Err(e) => {
-                    error_count += 1;
-                    results.push(BatchKeyResult {
-                        key,
-                        path: None,
-                        color: None,
-                        ok: false,
-                        error: Some(e.to_string()),
-                    });
-                }
+        if let Some(images) = &images {
+            console.print("");
+            console.print(&format!(
+                "Exported {} image(s) to {}",
+                images.exported.len(),
+                images.dir.display()
+            ));
+            for missing in &images.missing {
+                let original = missing
+                    .original_path
+                    .as_ref()
+                    .map(|p| format!(" (originally {})", p.display()))
+                    .unwrap_or_default();
+                console.print(&format!("  Key {}: no cached image{original}", missing.key));
             }
         }
     }
 
-    // Phase 7: Output results
-    let summary = BatchSummary::new(results.len(), success_count, error_count);
+    Ok(())
+}
 
-    if cli.use_json() {
-        output_json(
-            cli,
-            &serde_json::json!({
-                "command": "apply",
-                "config": args.config.display().to_string(),
-                "config_name": config.name,
-                "device": {
-                    "serial": device_info.serial,
-                    "product": device_info.product_name,
-                },
-                "results": results,
-                "summary": summary,
-            }),
-        );
-    } else {
-        if let Some(name) = &config.name {
-            output.info(&format!("Applied config: {}", name));
+/// One-line description of a saved key, as `snapshot show` lists it.
+fn describe_key_state(state: &snapshot::KeyState) -> String {
+    match state {
+        snapshot::KeyState::Image {
+            source_path,
+            image_hash,
+        } => {
+            let path_str = source_path
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "(cached)".to_string());
+            format!(
+                "image: {} [{}...]",
+                path_str,
+                &image_hash[..8.min(image_hash.len())]
+            )
         }
-        output.batch_set_keys(&results, &summary);
-    }
-
-    if error_count > 0 {
-        Err(SdError::Other(format!(
-            "{} key(s) failed to apply",
-            error_count
-        )))
-    } else {
-        Ok(())
+        snapshot::KeyState::Color { hex } => format!("color: {hex}"),
+        snapshot::KeyState::Clear => "cleared".to_string(),
     }
 }
 
-/// Apply a single key configuration to the device.
-fn apply_key_config(
-    device: &impl DeviceOperations,
-    _device_info: &device::DeviceInfo,
-    key: u8,
-    key_config: &config::KeyConfig,
-    config_path: &std::path::Path,
-) -> Result<BatchKeyResult> {
-    match key_config {
-        config::KeyConfig::Image { image, .. } => {
-            // Resolve image path relative to config file
-            let resolved = if image.starts_with("~") {
-                if let Ok(home) = config::home_dir() {
-                    home.join(image.strip_prefix("~").unwrap_or(image))
-                } else {
-                    image.clone()
-                }
-            } else if image.is_relative() {
-                config_path
-                    .parent()
-                    .map(|p| p.join(image))
-                    .unwrap_or_else(|| image.clone())
-            } else {
-                image.clone()
-            };
+fn cmd_snapshot_compare(cli: &Cli, args: &cli::SnapshotCompareArgs) -> Result<()> {
+    let db = snapshot::SnapshotDb::open_default()?;
+    let load = |name: &str| {
+        db.load_snapshot(name)?
+            .ok_or_else(|| SdError::Other(format!("Snapshot '{name}' not found")))
+    };
+    let (a, b) = (load(&args.a)?, load(&args.b)?);
+    let diff = snapshot::compare(&a, &b)?;
 
-            device.set_key_image(key, &resolved, image_ops::ResizeStrategy::Fit)?;
-            state::record::set_key(key, resolved.clone());
-            Ok(BatchKeyResult::set_key_success(key, &resolved))
-        }
-        config::KeyConfig::Color { color } => {
-            let (r, g, b) = color.to_rgb()?;
-            device.fill_key_color(key, (r, g, b))?;
-            let color_str = format!("#{:02x}{:02x}{:02x}", r, g, b);
-            state::record::fill_key(key, color_str.clone());
-            Ok(BatchKeyResult::fill_success(key, &color_str))
-        }
-        config::KeyConfig::Clear { clear } => {
-            if *clear {
-                device.clear_key(key)?;
-                state::record::clear_key(key);
-                Ok(BatchKeyResult::clear_success(key))
-            } else {
-                // clear: false means skip this key
-                Ok(BatchKeyResult {
-                    key,
-                    path: None,
-                    color: None,
-                    ok: true,
-                    error: None,
-                })
-            }
-        }
-        config::KeyConfig::Pattern { pattern, .. } => {
-            // Resolve pattern by substituting {index}
-            let filename = pattern
-                .replace("{index}", &key.to_string())
-                .replace("{index:02d}", &format!("{:02}", key))
-                .replace("{index:03d}", &format!("{:03}", key));
+    if cli.use_json() {
+        output_json(cli, &diff);
+        return Ok(());
+    }
 
-            let resolved = if filename.starts_with("~") {
-                if let Ok(home) = config::home_dir() {
-                    home.join(filename.strip_prefix("~").unwrap_or(&filename))
-                } else {
-                    std::path::PathBuf::from(&filename)
-                }
-            } else if std::path::Path::new(&filename).is_relative() {
-                config_path
-                    .parent()
-                    .map(|p| p.join(&filename))
-                    .unwrap_or_else(|| std::path::PathBuf::from(&filename))
-            } else {
-                std::path::PathBuf::from(&filename)
-            };
+    let console = Console::new();
+    let bold = Style::new().bold();
+    let muted = Color::parse("#747D8C").expect("valid color");
+    console.print_styled(
+        &format!("Comparing '{}' with '{}'", diff.a, diff.b),
+        Style::new().bold().underline(),
+    );
+    console.print("");
 
-            device.set_key_image(key, &resolved, image_ops::ResizeStrategy::Fit)?;
-            state::record::set_key(key, resolved.clone());
-            Ok(BatchKeyResult::set_key_success(key, &resolved))
+    let brightness = |b: Option<u8>| b.map_or_else(|| "not saved".to_string(), |b| format!("{b}%"));
+    let mut line = Text::new("");
+    line.append_styled("Brightness", bold.clone());
+    if diff.brightness_a == diff.brightness_b {
+        line.append(&format!(": {} (same)", brightness(diff.brightness_a)));
+    } else {
+        line.append(&format!(
+            ": {} → {}",
+            brightness(diff.brightness_a),
+            brightness(diff.brightness_b)
+        ));
+    }
+    console.print_text(&line);
+
+    if diff.different > 0 {
+        console.print("");
+        console.print_styled("Changed keys:", bold);
+        let state = |s: Option<&snapshot::KeyState>| {
+            s.map_or_else(|| "not saved".to_string(), describe_key_state)
+        };
+        for key in diff.keys.iter().filter(|k| !k.same) {
+            console.print(&format!(
+                "  Key {}: {} → {}",
+                key.key,
+                state(key.a.as_ref()),
+                state(key.b.as_ref())
+            ));
         }
     }
-}
 
-/// Dry-run handler for apply command.
-#[allow(clippy::unnecessary_wraps)]
-fn cmd_apply_dry_run(
-    cli: &Cli,
-    args: &cli::ApplyArgs,
-    config: &config::declarative::ProfileConfig,
-    output: &dyn Output,
-) -> Result<()> {
-    use config::KeySelector;
+    console.print("");
+    let same = diff.keys.len() - diff.different;
+    let summary = if diff.is_identical() {
+        "Snapshots are identical".to_string()
+    } else {
+        format!("{} key(s) differ, {same} the same", diff.different)
+    };
+    console.print_styled(&summary, Style::new().color(muted));
 
-    // Try to get device info
-    let device_result = open_device(cli);
-    let device_info = device_result.as_ref().ok().map(device::get_device_info);
+    Ok(())
+}
 
-    let mut operations = Vec::new();
-    let mut warnings = Vec::new();
+fn cmd_snapshot_export(cli: &Cli, args: &cli::SnapshotExportArgs) -> Result<()> {
+    let db = snapshot::SnapshotDb::open_default()?;
+    let mut snap = db
+        .load_snapshot(&args.name)?
+        .ok_or_else(|| SdError::Other(format!("Snapshot '{}' not found", args.name)))?;
+    snap.id = None;
 
-    // Build operation list
-    for (selector_str, key_config) in &config.keys {
-        let selector = match KeySelector::parse(selector_str) {
-            Ok(s) => s,
-            Err(e) => {
-                warnings.push(format!("Invalid selector '{}': {}", selector_str, e));
-                continue;
-            }
+    // One copy of each image, however many keys show it
+    let mut images = std::collections::BTreeMap::new();
+    let mut missing = Vec::new();
+    for key in &snap.keys {
+        let snapshot::KeyState::Image { image_hash, .. } = &key.state else {
+            continue;
         };
-
-        let keys = if let Some(ref info) = device_info {
-            match selector.resolve(info) {
-                Ok(k) => k,
-                Err(e) => {
-                    warnings.push(format!("Cannot resolve '{}': {}", selector_str, e));
-                    continue;
-                }
+        if images.contains_key(image_hash) {
+            continue;
+        }
+        let cached = snapshot::image_cache_path(image_hash)
+            .and_then(|path| std::fs::read(path).map_err(SdError::from));
+        match cached {
+            Ok(bytes) if args.reencode => {
+                images.insert(image_hash.clone(), snapshot::reencode_png(&bytes)?);
             }
-        } else {
-            // No device - show selector as-is
-            vec![]
-        };
-
-        let action = match key_config {
-            config::KeyConfig::Image { image, .. } => format!("set image: {}", image.display()),
-            config::KeyConfig::Color { color } => format!("fill color: {:?}", color),
-            config::KeyConfig::Clear { clear } => {
-                if *clear {
-                    "clear".to_string()
-                } else {
-                    "skip".to_string()
-                }
+            Ok(bytes) => {
+                images.insert(image_hash.clone(), bytes);
+            }
+            Err(e) => {
+                tracing::debug!(key = key.key_index, error = %e, "Cached image unavailable");
+                missing.push(key.key_index);
             }
-            config::KeyConfig::Pattern { pattern, .. } => format!("pattern: {}", pattern),
-        };
-
-        operations.push(serde_json::json!({
-            "selector": selector_str,
-            "keys": keys,
-            "action": action,
-        }));
-    }
-
-    if cli.use_json() {
-        let response = serde_json::json!({
-            "dry_run": true,
-            "command": "apply",
-            "config": args.config.display().to_string(),
-            "config_name": config.name,
-            "brightness": config.brightness,
-            "no_brightness": args.no_brightness,
-            "device": device_info.as_ref().map(|i| serde_json::json!({
-                "serial": i.serial,
-                "product": i.product_name,
-                "key_count": i.key_count,
-            })),
-            "operations": operations,
-            "warnings": warnings,
-        });
-        output_json(cli, &response);
-    } else {
-        println!("DRY RUN: Would apply configuration");
-        println!("  Config: {}", args.config.display());
-        if let Some(name) = &config.name {
-            println!("  Profile: {}", name);
         }
+    }
 
-        if let Some(ref info) = device_info {
-            println!("  Device: {} (serial: {})", info.product_name, info.serial);
-        } else {
-            println!("  Device: not connected");
-        }
+    let archive = snapshot::SnapshotArchive {
+        manifest: snapshot::ArchiveManifest {
+            format_version: snapshot::ARCHIVE_FORMAT_VERSION,
+            compression: args.compression,
+            reencoded: args.reencode,
+            snapshot: snap,
+        },
+        images,
+    };
+    let file = std::fs::File::create(&args.file)
+        .map_err(|e| SdError::Other(format!("Failed to create {}: {e}", args.file.display())))?;
+    archive.write(std::io::BufWriter::new(file))?;
+    let bytes = std::fs::metadata(&args.file).map_or(0, |m| m.len());
 
-        if let Some(brightness) = config.brightness {
-            if args.no_brightness {
-                println!(
-                    "  Brightness: {} (skipped with --no-brightness)",
-                    brightness
-                );
-            } else {
-                println!("  Brightness: would set to {}%", brightness);
-            }
+    if cli.use_json() {
+        output_json(
+            cli,
+            &serde_json::json!({
+                "command": "snapshot export",
+                "ok": true,
+                "name": args.name,
+                "file": args.file,
+                "compression": args.compression,
+                "reencoded": args.reencode,
+                "images": archive.images.len(),
+                "missing_images": missing,
+                "bytes": bytes,
+            }),
+        );
+    } else if !cli.quiet {
+        println!(
+            "Exported snapshot '{}' to {} ({} image(s), {:?}, {bytes} bytes)",
+            args.name,
+            args.file.display(),
+            archive.images.len(),
+            args.compression
+        );
+        for key in &missing {
+            println!("  Key {key}: no cached image; import will use its original path");
         }
+    }
+    Ok(())
+}
 
-        println!("\n  Key operations:");
-        for op in &operations {
-            println!(
-                "    {} -> {}",
-                op["selector"].as_str().unwrap_or("?"),
-                op["action"].as_str().unwrap_or("?")
-            );
-        }
+fn cmd_snapshot_import(cli: &Cli, args: &cli::SnapshotImportArgs) -> Result<()> {
+    let file = std::fs::File::open(&args.file)
+        .map_err(|e| SdError::Other(format!("Failed to open {}: {e}", args.file.display())))?;
+    let archive = snapshot::SnapshotArchive::read(std::io::BufReader::new(file))?;
+    let mut snap = archive.manifest.snapshot;
+    if let Some(name) = &args.name {
+        snap.name.clone_from(name);
+    }
+    if !is_valid_snapshot_name(&snap.name) {
+        return Err(SdError::Other(format!(
+            "Invalid snapshot name '{}': use 1-64 characters, alphanumeric with \
+             hyphens/underscores (pick one with --name)",
+            snap.name
+        )));
+    }
 
-        if !warnings.is_empty() {
-            println!("\n  Warnings:");
-            for w in &warnings {
-                println!("    - {}", w);
-            }
+    let mut db = snapshot::SnapshotDb::open_default()?;
+    if db.snapshot_exists(&snap.name)? && !args.force {
+        return Err(SdError::Other(format!(
+            "Snapshot '{}' already exists. Use --force to overwrite, or --name to import under \
+             another name.",
+            snap.name
+        )));
+    }
+
+    let mut installed = 0;
+    for (hash, bytes) in &archive.images {
+        let cache_path = snapshot::image_cache_path(hash)?;
+        if cache_path.exists() {
+            continue;
+        }
+        if let Some(parent) = cache_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| SdError::Other(format!("Failed to create cache directory: {e}")))?;
         }
+        std::fs::write(&cache_path, bytes)
+            .map_err(|e| SdError::Other(format!("Failed to cache image: {e}")))?;
+        let original_path = snap.keys.iter().find_map(|key| match &key.state {
+            snapshot::KeyState::Image {
+                source_path,
+                image_hash,
+            } if image_hash == hash => source_path.clone(),
+            _ => None,
+        });
+        db.save_image(&snapshot::CachedImage::new(
+            hash.clone(),
+            original_path,
+            0,
+            0,
+            "png".to_string(),
+            u64::try_from(bytes.len()).unwrap_or(u64::MAX),
+        ))?;
+        installed += 1;
     }
+    snap.id = None;
+    db.save_snapshot(&snap)?;
 
+    if cli.use_json() {
+        output_json(
+            cli,
+            &serde_json::json!({
+                "command": "snapshot import",
+                "ok": true,
+                "name": snap.name,
+                "file": args.file,
+                "compression": archive.manifest.compression,
+                "images": archive.images.len(),
+                "images_added": installed,
+            }),
+        );
+    } else if !cli.quiet {
+        println!(
+            "Imported snapshot '{}' ({} keys, {} image(s), {installed} new to the cache)",
+            snap.name,
+            snap.keys.len(),
+            archive.images.len()
+        );
+    }
     Ok(())
 }
 
-// === Snapshot Commands ===
+/// Images recovered from a snapshot by `snapshot show --images`.
+#[derive(Serialize)]
+struct SnapshotImageExport {
+    dir: std::path::PathBuf,
+    exported: Vec<ExportedKeyImage>,
+    /// Image keys whose cached image is gone or unreadable.
+    missing: Vec<MissingKeyImage>,
+}
 
-fn cmd_save(cli: &Cli, args: &cli::SaveArgs) -> Result<()> {
-    // Validate snapshot name
-    if !is_valid_snapshot_name(&args.name) {
-        return Err(SdError::Other(
-            "Snapshot name must be 1-64 characters, alphanumeric with hyphens/underscores"
-                .to_string(),
-        ));
+#[derive(Serialize)]
+struct ExportedKeyImage {
+    key: u8,
+    path: std::path::PathBuf,
+}
+
+#[derive(Serialize)]
+struct MissingKeyImage {
+    key: u8,
+    hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_path: Option<std::path::PathBuf>,
+    error: String,
+}
+
+/// Decode each image key's cached image and save it to `dir` as
+/// `key-{index}.png`. Cache misses are collected rather than failing.
+fn export_snapshot_images(
+    db: &snapshot::SnapshotDb,
+    snap: &snapshot::Snapshot,
+    dir: &std::path::Path,
+) -> Result<SnapshotImageExport> {
+    std::fs::create_dir_all(dir).map_err(|e| {
+        SdError::Other(format!(
+            "Failed to create export directory {}: {e}",
+            dir.display()
+        ))
+    })?;
+
+    let mut export = SnapshotImageExport {
+        dir: dir.to_path_buf(),
+        exported: Vec::new(),
+        missing: Vec::new(),
+    };
+
+    for key in &snap.keys {
+        let snapshot::KeyState::Image {
+            source_path,
+            image_hash,
+        } = &key.state
+        else {
+            continue;
+        };
+
+        let decoded = snapshot::image_cache_path(image_hash).and_then(|cache_path| {
+            if !cache_path.exists() {
+                return Err(SdError::Other("not in image cache".to_string()));
+            }
+            // Cached files keep their source encoding whatever the extension
+            image::ImageReader::open(&cache_path)
+                .and_then(image::ImageReader::with_guessed_format)
+                .map_err(|e| SdError::Other(format!("Failed to read cached image: {e}")))?
+                .decode()
+                .map_err(|e| SdError::Other(format!("Failed to decode cached image: {e}")))
+        });
+
+        let path = dir.join(format!("key-{}.png", key.key_index));
+        match decoded.and_then(|img| {
+            img.save_with_format(&path, image::ImageFormat::Png)
+                .map_err(|e| SdError::Other(format!("Failed to write {}: {e}", path.display())))
+        }) {
+            Ok(()) => export.exported.push(ExportedKeyImage {
+                key: key.key_index,
+                path,
+            }),
+            Err(e) => {
+                let original_path = db
+                    .load_image(image_hash)
+                    .ok()
+                    .flatten()
+                    .and_then(|cached| cached.original_path)
+                    .or_else(|| source_path.clone());
+                export.missing.push(MissingKeyImage {
+                    key: key.key_index,
+                    hash: image_hash.clone(),
+                    original_path,
+                    error: e.to_string(),
+                });
+            }
+        }
     }
 
-    // Get device info for snapshot metadata
-    let device = open_device(cli)?;
-    let device_info = device::get_device_info(&device);
+    Ok(export)
+}
 
+fn cmd_snapshot_delete(cli: &Cli, args: &cli::SnapshotDeleteArgs) -> Result<()> {
     // Open snapshot database
     let mut db = snapshot::SnapshotDb::open_default()?;
 
-    // Check for existing snapshot
-    if db.snapshot_exists(&args.name)? && !args.force {
+    // Check if snapshot exists
+    if !db.snapshot_exists(&args.name)? {
         return Err(SdError::Other(format!(
-            "Snapshot '{}' already exists. Use --force to overwrite.",
+            "Snapshot '{}' not found",
             args.name
         )));
     }
 
-    // Get current session state
-    let session = state::session_state();
+    // Confirm deletion if not forced and not in robot mode
+    if !args.force && !cli.use_json() {
+        let console = Console::new();
+        let warning = Color::parse("#FFA502").expect("valid color");
+        console.print_styled(
+            &format!("Delete snapshot '{}'? This cannot be undone.", args.name),
+            Style::new().color(warning),
+        );
+        console.print("Use --force to skip this prompt");
+        return Ok(());
+    }
 
-    // Build keys list from session state
-    let mut keys = Vec::new();
-    let key_indices: Vec<u8> = if args.session_only {
-        // Only keys modified in this session
-        session.keys.keys().copied().collect()
-    } else {
-        // All keys from session (we can only save what we've tracked)
-        session.keys.keys().copied().collect()
-    };
+    // Delete snapshot
+    let deleted = db.delete_snapshot(&args.name)?;
 
-    for key_index in key_indices {
-        if let Some(session_key) = session.keys.get(&key_index) {
-            let key_state = match session_key {
-                state::KeyState::Image { path } => {
-                    // Hash the image for content-addressable storage
-                    let hash = hash_image_file(path)?;
-                    // Cache the image
-                    cache_image(&db, &hash, path)?;
-                    snapshot::KeyState::Image {
-                        source_path: Some(path.clone()),
-                        image_hash: hash,
-                    }
-                }
-                state::KeyState::Color { hex } => snapshot::KeyState::Color { hex: hex.clone() },
-                state::KeyState::Cleared => snapshot::KeyState::Clear,
-            };
-            keys.push(snapshot::SnapshotKey {
-                key_index,
-                state: key_state,
-            });
+    if cli.use_json() {
+        output_json(
+            cli,
+            &serde_json::json!({
+                "command": "snapshot delete",
+                "ok": deleted,
+                "name": args.name,
+                "deleted": deleted,
+            }),
+        );
+    } else if !cli.quiet {
+        if deleted {
+            println!("Deleted snapshot '{}'", args.name);
+        } else {
+            println!("Snapshot '{}' not found", args.name);
         }
     }
 
-    // Get brightness from session or None
-    let brightness = if args.no_brightness {
-        None
-    } else {
-        session.brightness
+    // Cleanup orphaned images
+    let cleaned = db.cleanup_orphaned_images()?;
+    if cleaned > 0 && !cli.quiet && !cli.use_json() {
+        println!("Cleaned up {} orphaned cached images", cleaned);
+    }
+
+    Ok(())
+}
+
+/// One metadata field changed by `snapshot edit`.
+#[derive(Serialize)]
+struct SnapshotFieldChange {
+    field: &'static str,
+    from: serde_json::Value,
+    to: serde_json::Value,
+}
+
+fn cmd_snapshot_edit(cli: &Cli, args: &cli::SnapshotEditArgs) -> Result<()> {
+    if let Some(level) = args.brightness.filter(|level| *level > 100) {
+        return Err(SdError::InvalidBrightness { value: level });
+    }
+    if let Some(new_name) = args.rename.as_ref().filter(|n| !is_valid_snapshot_name(n)) {
+        return Err(SdError::Other(format!(
+            "Invalid snapshot name '{new_name}': use 1-64 characters, alphanumeric with \
+             hyphens/underscores"
+        )));
+    }
+
+    let mut db = snapshot::SnapshotDb::open_default()?;
+    let snap = db
+        .load_snapshot(&args.name)?
+        .ok_or_else(|| SdError::Other(format!("Snapshot '{}' not found", args.name)))?;
+
+    // Only fields that actually differ are written and reported
+    let description = args
+        .description
+        .clone()
+        .filter(|d| snap.description.as_deref().unwrap_or_default() != d.as_str());
+    let edit = snapshot::SnapshotEdit {
+        brightness: args.brightness.filter(|b| snap.brightness != Some(*b)),
+        rename: args.rename.clone().filter(|n| *n != snap.name),
+        description,
     };
+    if let Some(new_name) = &edit.rename {
+        if db.snapshot_exists(new_name)? {
+            return Err(SdError::Other(format!(
+                "Snapshot '{new_name}' already exists. Delete it first or pick another name."
+            )));
+        }
+    }
 
-    // Create snapshot
-    let mut snap = snapshot::Snapshot::new(
-        args.name.clone(),
-        device_info.product_name.clone(),
-        device_info.key_count,
-        device_info.key_width as u32,
-        device_info.key_height as u32,
-    );
-    snap.brightness = brightness;
-    snap.description = args.description.clone();
-    snap.device_serial = Some(device_info.serial.clone());
-    snap.keys = keys;
+    let mut changes = Vec::new();
+    if let Some(description) = &edit.description {
+        changes.push(SnapshotFieldChange {
+            field: "description",
+            from: serde_json::json!(snap.description),
+            to: serde_json::json!(Some(description).filter(|d| !d.is_empty())),
+        });
+    }
+    if let Some(brightness) = edit.brightness {
+        changes.push(SnapshotFieldChange {
+            field: "brightness",
+            from: serde_json::json!(snap.brightness),
+            to: serde_json::json!(brightness),
+        });
+    }
+    if let Some(new_name) = &edit.rename {
+        changes.push(SnapshotFieldChange {
+            field: "name",
+            from: serde_json::json!(snap.name),
+            to: serde_json::json!(new_name),
+        });
+    }
 
-    // Save to database
-    let id = db.save_snapshot(&snap)?;
+    if !edit.is_empty() {
+        db.edit_snapshot(&args.name, &edit)?;
+    }
+    let name = edit.rename.as_deref().unwrap_or(&args.name);
 
-    // Output result
     if cli.use_json() {
         output_json(
             cli,
             &serde_json::json!({
-                "command": "save",
+                "command": "snapshot edit",
                 "ok": true,
-                "snapshot": {
-                    "id": id,
-                    "name": args.name,
-                    "device_model": device_info.product_name,
-                    "key_count": device_info.key_count,
-                    "brightness": brightness,
-                    "keys_saved": snap.keys.len(),
-                }
+                "name": name,
+                "changes": changes,
             }),
         );
     } else if !cli.quiet {
-        println!(
-            "Saved snapshot '{}' ({} keys{})",
-            args.name,
-            snap.keys.len(),
-            brightness.map_or(String::new(), |b| format!(", brightness {b}%"))
-        );
+        if changes.is_empty() {
+            println!(
+                "Snapshot '{}' already has those values; nothing changed",
+                args.name
+            );
+        } else {
+            println!("Updated snapshot '{name}':");
+            let show = |value: &serde_json::Value| {
+                if value.is_null() {
+                    "(none)".to_string()
+                } else {
+                    value.to_string()
+                }
+            };
+            for change in &changes {
+                println!(
+                    "  {}: {} -> {}",
+                    change.field,
+                    show(&change.from),
+                    show(&change.to)
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Validates a snapshot name.
+fn is_valid_snapshot_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Computes SHA256 hash of an image file.
+fn hash_image_file(path: &std::path::Path) -> Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let data = std::fs::read(path)
+        .map_err(|e| SdError::Other(format!("Failed to read image {}: {e}", path.display())))?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(&data);
+    let result = hasher.finalize();
+    Ok(hex::encode(result))
+}
+
+/// Caches an image to content-addressable storage.
+fn cache_image(db: &snapshot::SnapshotDb, hash: &str, source_path: &std::path::Path) -> Result<()> {
+    // Get cache path
+    let cache_path = snapshot::image_cache_path(hash)?;
+
+    // Skip if already cached
+    if cache_path.exists() {
+        return Ok(());
+    }
+
+    // Create cache directory
+    if let Some(parent) = cache_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| SdError::Other(format!("Failed to create cache directory: {e}")))?;
     }
 
+    // Copy image to cache (could convert to webp in future)
+    std::fs::copy(source_path, &cache_path)
+        .map_err(|e| SdError::Other(format!("Failed to cache image: {e}")))?;
+
+    // Get file metadata for DB entry
+    let metadata = std::fs::metadata(&cache_path)
+        .map_err(|e| SdError::Other(format!("Failed to get cached image metadata: {e}")))?;
+
+    // Save to database
+    let cached = snapshot::CachedImage::new(
+        hash.to_string(),
+        Some(source_path.to_path_buf()),
+        0, // Width/height could be extracted from image
+        0,
+        "png".to_string(), // Format detection could be added
+        metadata.len(),
+    );
+    db.save_image(&cached)?;
+
     Ok(())
 }
 
-fn cmd_restore(cli: &Cli, args: &cli::RestoreArgs) -> Result<()> {
-    // Open snapshot database
-    let db = snapshot::SnapshotDb::open_default()?;
-
-    // Load snapshot
-    let snap = db
-        .load_snapshot(&args.name)?
-        .ok_or_else(|| SdError::Other(format!("Snapshot '{}' not found", args.name)))?;
-
-    // Open device
-    let device = open_device(cli)?;
-    let device_info = device::get_device_info(&device);
-
-    // Check device compatibility
-    if snap.key_count != device_info.key_count {
-        return Err(SdError::Other(format!(
-            "Snapshot was saved for {} keys, but device has {} keys",
-            snap.key_count, device_info.key_count
-        )));
+/// Applies a cached image to a key.
+fn apply_cached_image(
+    device: &device::Device,
+    key: u8,
+    hash: &str,
+    source_path: Option<&std::path::PathBuf>,
+) -> Result<()> {
+    // Try cache first
+    let cache_path = snapshot::image_cache_path(hash)?;
+    if cache_path.exists() {
+        device::set_key_image(device, key, &cache_path, image_ops::ResizeStrategy::Fit)?;
+        if let Some(path) = source_path {
+            state::record::set_key(key, path.clone());
+        }
+        return Ok(());
     }
 
-    // Apply brightness if present and not skipped
-    if !args.no_brightness {
-        if let Some(brightness) = snap.brightness {
-            device::set_brightness(&device, brightness)?;
-            state::record::brightness(brightness);
+    // Fall back to source path
+    if let Some(path) = source_path {
+        if path.exists() {
+            device::set_key_image(device, key, path, image_ops::ResizeStrategy::Fit)?;
+            state::record::set_key(key, path.clone());
+            return Ok(());
         }
     }
 
-    // Apply keys
-    let mut applied_count = 0;
-    let mut error_count = 0;
+    Err(SdError::Other(format!(
+        "Image not found in cache or at original path (hash: {hash})"
+    )))
+}
 
-    for key in &snap.keys {
-        let result = match &key.state {
-            snapshot::KeyState::Image {
-                source_path,
-                image_hash,
-            } => {
-                // Try to load from cache first, then source path
-                apply_cached_image(&device, key.key_index, image_hash, source_path.as_ref())
-            }
-            snapshot::KeyState::Color { hex } => {
-                let color = parse_color(hex)?;
-                device::fill_key_color(&device, key.key_index, color).map(|()| {
-                    state::record::fill_key(key.key_index, hex.clone());
-                })
-            }
-            snapshot::KeyState::Clear => device::clear_key(&device, key.key_index).map(|()| {
-                state::record::clear_key(key.key_index);
-            }),
-        };
+// === Cache Commands ===
 
-        match result {
-            Ok(()) => applied_count += 1,
-            Err(e) => {
-                error_count += 1;
-                tracing::warn!(key = key.key_index, error = %e, "Failed to restore key");
-            }
-        }
+fn cmd_cache(cli: &Cli, args: &cli::CacheCommand) -> Result<()> {
+    match &args.command {
+        cli::CacheSubcommand::Clean => cmd_cache_clean(cli),
     }
+}
+
+fn cmd_cache_clean(cli: &Cli) -> Result<()> {
+    let cache = image_cache::KeyImageCache::open_default()?;
+    let stats = cache.clean()?;
 
-    // Output result
     if cli.use_json() {
         output_json(
             cli,
             &serde_json::json!({
-                "command": "restore",
-                "ok": error_count == 0,
-                "snapshot": args.name,
-                "keys_applied": applied_count,
-                "keys_failed": error_count,
-                "brightness_applied": !args.no_brightness && snap.brightness.is_some(),
+                "command": "cache clean",
+                "ok": true,
+                "path": cache.dir().display().to_string(),
+                "removed": stats.files,
+                "bytes_freed": stats.bytes,
             }),
         );
     } else if !cli.quiet {
-        if error_count == 0 {
-            println!("Restored snapshot '{}' ({} keys)", args.name, applied_count);
-        } else {
-            println!(
-                "Restored snapshot '{}' ({} keys, {} failed)",
-                args.name, applied_count, error_count
-            );
-        }
+        println!(
+            "Removed {} cached key image(s) ({} bytes) from {}",
+            stats.files,
+            stats.bytes,
+            cache.dir().display()
+        );
     }
 
     Ok(())
 }
 
-fn cmd_snapshots(cli: &Cli, args: &cli::SnapshotsArgs) -> Result<()> {
-    // Open snapshot database
-    let db = snapshot::SnapshotDb::open_default()?;
+fn cmd_serve(cli: &Cli, args: &cli::ServeArgs) -> Result<()> {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    // Over stdio, HTTP only carries the metrics (and events) alongside
+    let serves_http = !args.stdio || args.metrics;
+    let loopback = args.bind == "localhost"
+        || args
+            .bind
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback());
+    if args.token.is_none() && !loopback && serves_http {
+        tracing::warn!(bind = %args.bind, "Serving on a non-loopback address without --token");
+        if !cli.quiet {
+            eprintln!(
+                "Warning: anyone who can reach {} can use this server; set --token to require a secret",
+                args.bind
+            );
+        }
+    }
 
-    // List snapshots
-    let snapshots = db.list_snapshots()?;
+    // Requests, context switches and the watch loop take turns with the device
+    let device_lock = Mutex::new(());
+    let done = AtomicBool::new(false);
+    let hub = Arc::new(server::EventHub::new());
+    let metrics = args
+        .metrics
+        .then(|| Arc::new(metrics::ServerMetrics::new()));
+    if serves_http {
+        start_http_server(cli, args, &hub, metrics.as_ref())?;
+    }
 
-    if cli.use_json() {
-        output_json(cli, &snapshots);
-    } else if snapshots.is_empty() {
-        println!("No snapshots saved");
-        println!("Use 'sd save <name>' to save the current device state");
-    } else {
-        let console = Console::new();
-        let success = Color::parse("#00D26A").expect("valid color");
-        let muted = Color::parse("#747D8C").expect("valid color");
+    std::thread::scope(|scope| {
+        if !args.stdio {
+            // Nothing else to do: watch until the server is killed
+            stream_button_events(cli, &hub, &device_lock, &done);
+            return Ok(());
+        }
+        if serves_http {
+            scope.spawn(|| stream_button_events(cli, &hub, &device_lock, &done));
+        }
+        let result = cmd_serve_stdio(cli, metrics.as_deref(), args.token.as_deref(), &device_lock);
+        done.store(true, Ordering::Relaxed);
+        result
+    })
+}
 
-        for snap in &snapshots {
-            if args.long {
-                let mut line = Text::new("");
-                line.append_styled(&snap.name, Style::new().color(success.clone()));
-                line.append(&format!(
-                    ": {} ({} keys{})",
-                    snap.device_model,
-                    snap.key_count,
-                    snap.brightness
-                        .map_or(String::new(), |b| format!(", {b}% brightness"))
-                ));
-                console.print_text(&line);
-                if let Some(ref desc) = snap.description {
-                    console.print_styled(&format!("  {desc}"), Style::new().color(muted.clone()));
-                }
-                console.print(&format!(
-                    "  Created: {}",
-                    snap.created_at.format("%Y-%m-%d %H:%M")
-                ));
-            } else {
-                console.print(&snap.name);
+/// Start the HTTP server on `--bind`/`--port` in the background: `/events`
+/// always, and with `--metrics` also `/healthz` and `/metrics`, fed by a
+/// poller tracking whether the device is connected.
+fn start_http_server(
+    cli: &Cli,
+    args: &cli::ServeArgs,
+    hub: &std::sync::Arc<server::EventHub>,
+    metrics: Option<&std::sync::Arc<metrics::ServerMetrics>>,
+) -> Result<()> {
+    use std::sync::Arc;
+
+    let addr = format!("{}:{}", args.bind, args.port);
+    let listener = server::bind(&addr)?;
+    let mut router = server::events_router(Arc::clone(hub));
+
+    if let Some(metrics) = metrics {
+        let serial = cli.serial.clone();
+        let device_connected = move || {
+            device::list_devices().is_ok_and(|devices| {
+                serial.as_deref().map_or(!devices.is_empty(), |serial| {
+                    devices.iter().any(|d| d.serial == serial)
+                })
+            })
+        };
+        metrics.record_device(device_connected());
+        let poller = Arc::clone(metrics);
+        std::thread::spawn(move || {
+            loop {
+                std::thread::sleep(metrics::DEVICE_POLL_INTERVAL);
+                poller.record_device(device_connected());
             }
-        }
+        });
+        router = router.merge(metrics::router(Arc::clone(metrics)));
     }
 
-    Ok(())
-}
-
-fn cmd_snapshot(cli: &Cli, args: &cli::SnapshotCommand) -> Result<()> {
-    match &args.command {
-        cli::SnapshotSubcommand::Show(show_args) => cmd_snapshot_show(cli, show_args),
-        cli::SnapshotSubcommand::Delete(delete_args) => cmd_snapshot_delete(cli, delete_args),
+    let router = server::with_token(router, args.token.as_deref().map(Arc::from));
+    std::thread::spawn(move || {
+        if let Err(e) = server::serve(listener, router) {
+            tracing::error!(error = %e, "HTTP server stopped");
+        }
+    });
+    tracing::info!(%addr, metrics = metrics.is_some(), "Serving HTTP");
+    if !cli.quiet {
+        eprintln!("Button events on ws://{addr}/events");
+        if metrics.is_some() {
+            eprintln!("Health and metrics on http://{addr}/healthz and http://{addr}/metrics");
+        }
     }
+    Ok(())
 }
 
-fn cmd_snapshot_show(cli: &Cli, args: &cli::SnapshotShowArgs) -> Result<()> {
-    // Open snapshot database
-    let db = snapshot::SnapshotDb::open_default()?;
-
-    // Load snapshot
-    let snap = db
-        .load_snapshot(&args.name)?
-        .ok_or_else(|| SdError::Other(format!("Snapshot '{}' not found", args.name)))?;
-
-    if cli.use_json() {
-        output_json(cli, &snap);
-    } else {
-        let console = Console::new();
-        let bold = Style::new().bold();
-
-        console.print_styled("Snapshot Details", Style::new().bold().underline());
-        console.print("");
-
-        let mut line = Text::new("");
-        line.append_styled("Name", bold.clone());
-        line.append(&format!(": {}", snap.name));
-        console.print_text(&line);
+/// The server's watch loop: publish button changes to `/events` clients,
+/// in the same JSON as `watch --robot`.
+///
+/// The device is opened when a client subscribes and kept open while any
+/// client is listening; each read takes `device_lock`, so requests that
+/// write to the device take turns with it. A failed read (e.g. the device
+/// was unplugged) or open publishes `disconnected`, and the device is
+/// reopened, with a `reconnected` event, once it's back.
+fn stream_button_events(
+    cli: &Cli,
+    hub: &server::EventHub,
+    device_lock: &std::sync::Mutex<()>,
+    done: &std::sync::atomic::AtomicBool,
+) {
+    use std::sync::PoisonError;
+    use std::sync::atomic::Ordering;
+    use std::time::{Duration, Instant};
 
-        if let Some(ref desc) = snap.description {
-            let mut line = Text::new("");
-            line.append_styled("Description", bold.clone());
-            line.append(&format!(": {desc}"));
-            console.print_text(&line);
-        }
+    const RETRY_DELAY: Duration = Duration::from_secs(1);
 
-        let mut line = Text::new("");
-        line.append_styled("Device", bold.clone());
-        line.append(&format!(": {}", snap.device_model));
-        console.print_text(&line);
+    let start = Instant::now();
+    let mut device: Option<device::Device> = None;
+    let mut last_states: Vec<bool> = Vec::new();
+    let mut failed_opens: u32 = 0;
 
-        if let Some(ref serial) = snap.device_serial {
-            let mut line = Text::new("");
-            line.append_styled("Serial", bold.clone());
-            line.append(&format!(": {serial}"));
-            console.print_text(&line);
+    while !done.load(Ordering::Relaxed) {
+        std::thread::sleep(Duration::from_millis(50));
+        if hub.subscribers() == 0 {
+            // Let go of the device while nobody is listening
+            device = None;
+            continue;
         }
 
-        let mut line = Text::new("");
-        line.append_styled("Keys", bold.clone());
-        line.append(&format!(
-            ": {} ({}x{} px)",
-            snap.key_count, snap.key_width, snap.key_height
-        ));
-        console.print_text(&line);
+        let states = {
+            let _turn = device_lock.lock().unwrap_or_else(PoisonError::into_inner);
+            if device.is_none() {
+                match open_device(cli) {
+                    Ok(opened) => {
+                        if failed_opens > 0 {
+                            hub.publish(&WatchConnectionEvent::Reconnected {
+                                attempt: failed_opens,
+                            });
+                        }
+                        failed_opens = 0;
+                        last_states = vec![false; usize::from(opened.info().key_count)];
+                        device = Some(opened);
+                    }
+                    Err(e) => {
+                        failed_opens += 1;
+                        if failed_opens == 1 {
+                            hub.publish(&WatchConnectionEvent::Disconnected {
+                                reason: e.to_string(),
+                                reconnecting: true,
+                            });
+                        }
+                    }
+                }
+            }
+            device.as_ref().map(device::poll_button_states)
+        };
+        let states = match states {
+            None => {
+                std::thread::sleep(RETRY_DELAY);
+                continue;
+            }
+            Some(Err(e)) => {
+                // Let go of the dead handle and reopen on the next pass
+                tracing::warn!(error = %e, "Button read failed; reconnecting");
+                device = None;
+                failed_opens = 1;
+                hub.publish(&WatchConnectionEvent::Disconnected {
+                    reason: e.to_string(),
+                    reconnecting: true,
+                });
+                continue;
+            }
+            // No report within the read timeout: nothing changed
+            Some(Ok(None)) => continue,
+            Some(Ok(Some(states))) => states,
+        };
 
-        if let Some(brightness) = snap.brightness {
-            let mut line = Text::new("");
-            line.append_styled("Brightness", bold.clone());
-            line.append(&format!(": {brightness}%"));
-            console.print_text(&line);
+        for (key, (&current, &previous)) in states.iter().zip(&last_states).enumerate() {
+            if current != previous {
+                #[allow(clippy::cast_possible_truncation)] // Key count is always < 256
+                let event = device::ButtonEvent {
+                    key: cli.user_key(key as u8),
+                    pressed: current,
+                    timestamp_ms: u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+                };
+                hub.publish(&event);
+            }
         }
+        last_states = states;
+    }
+}
 
-        let mut line = Text::new("");
-        line.append_styled("Created", bold.clone());
-        line.append(&format!(
-            ": {}",
-            snap.created_at.format("%Y-%m-%d %H:%M:%S")
-        ));
-        console.print_text(&line);
-
-        let mut line = Text::new("");
-        line.append_styled("Updated", bold.clone());
-        line.append(&format!(
-            ": {}",
-            snap.updated_at.format("%Y-%m-%d %H:%M:%S")
-        ));
-        console.print_text(&line);
+/// Serve JSON-RPC over stdin/stdout until stdin closes.
+///
+/// With `contexts` in the settings file, a background thread also applies
+/// the profile for each `sd context` push and announces it with a `context`
+/// notification. With `metrics`, every command is counted. With `token`, the
+/// client must call `auth` before anything else. Everything touching the
+/// device holds `device_lock`.
+fn cmd_serve_stdio(
+    cli: &Cli,
+    metrics: Option<&metrics::ServerMetrics>,
+    token: Option<&str>,
+    device_lock: &std::sync::Mutex<()>,
+) -> Result<()> {
+    use std::io::BufRead;
+    use std::sync::PoisonError;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    tracing::info!("Serving JSON-RPC on stdio");
+    let done = AtomicBool::new(false);
+    let mut authenticated = token.is_none();
+    let context_path = context::default_context_path()
+        .ok()
+        .filter(|_| !cli.contexts.is_empty());
+
+    std::thread::scope(|scope| {
+        if let Some(path) = &context_path {
+            scope.spawn(|| {
+                let mut watcher = context::ContextWatcher::new(path);
+                while !done.load(Ordering::Relaxed) {
+                    if let Some(record) = watcher.poll() {
+                        let _device = device_lock.lock().unwrap_or_else(PoisonError::into_inner);
+                        let params = context_notification(cli, &record.name);
+                        let note = rpc::RpcNotification::new("context", params);
+                        write_rpc_line(&serde_json::json!(note).to_string());
+                    }
+                    std::thread::sleep(context::POLL_INTERVAL);
+                }
+            });
+        }
 
-        if !snap.keys.is_empty() {
-            console.print("");
-            console.print_styled("Keys:", bold);
-            for key in &snap.keys {
-                let state_desc = match &key.state {
-                    snapshot::KeyState::Image {
-                        source_path,
-                        image_hash,
-                    } => {
-                        let path_str = source_path
-                            .as_ref()
-                            .map(|p| p.display().to_string())
-                            .unwrap_or_else(|| "(cached)".to_string());
-                        format!(
-                            "image: {} [{}...]",
-                            path_str,
-                            &image_hash[..8.min(image_hash.len())]
-                        )
+        let result = std::io::stdin()
+            .lock()
+            .lines()
+            .try_for_each(|line| -> Result<()> {
+                let line = line?;
+                if line.trim().is_empty() {
+                    return Ok(());
+                }
+                let _device = device_lock.lock().unwrap_or_else(PoisonError::into_inner);
+                let dispatch = |req: &rpc::RpcRequest| {
+                    if req.method == "auth" {
+                        return rpc_authenticate(token, req, &mut authenticated);
+                    }
+                    if !authenticated {
+                        return Err(rpc::RpcError::unauthorized());
                     }
-                    snapshot::KeyState::Color { hex } => format!("color: {hex}"),
-                    snapshot::KeyState::Clear => "cleared".to_string(),
+                    let result = rpc_dispatch(cli, req);
+                    if let Some(metrics) = metrics {
+                        metrics.record_command(result.is_ok());
+                    }
+                    result
                 };
-                console.print(&format!("  Key {}: {}", key.key_index, state_desc));
-            }
-        }
+                if let Some(response) = rpc::handle_message(&line, dispatch) {
+                    write_rpc_line(&response);
+                }
+                Ok(())
+            });
+        done.store(true, Ordering::Relaxed);
+        result
+    })
+}
+
+/// Write one message to the RPC stream without interleaving other threads.
+fn write_rpc_line(message: &str) {
+    use std::io::Write;
+
+    let mut stdout = std::io::stdout().lock();
+    if let Err(e) = writeln!(stdout, "{message}").and_then(|()| stdout.flush()) {
+        tracing::warn!(error = %e, "Failed to write RPC message");
     }
+}
 
-    Ok(())
+/// Params of the `context` notification sent after applying a context.
+fn context_notification(cli: &Cli, name: &str) -> serde_json::Value {
+    let profile = cli.contexts.get(name);
+    match apply_context_profile(cli, name) {
+        Ok(result) => serde_json::json!({
+            "name": name,
+            "profile": profile,
+            "ok": true,
+            "result": result,
+        }),
+        Err(e) => serde_json::json!({
+            "name": name,
+            "profile": profile,
+            "ok": false,
+            "error": rpc::RpcError::from(e),
+        }),
+    }
 }
 
-fn cmd_snapshot_delete(cli: &Cli, args: &cli::SnapshotDeleteArgs) -> Result<()> {
-    // Open snapshot database
-    let mut db = snapshot::SnapshotDb::open_default()?;
+/// The profile config mapped to context `name` in the settings file.
+fn context_profile<'a>(cli: &'a Cli, name: &str) -> Result<&'a std::path::Path> {
+    cli.contexts
+        .get(name)
+        .map(std::path::PathBuf::as_path)
+        .ok_or_else(|| {
+            if cli.contexts.is_empty() {
+                SdError::ConfigInvalid(
+                    "No contexts configured. Map context names to profiles under `contexts` \
+                     in ~/.config/sd/config.yaml"
+                        .to_string(),
+                )
+            } else {
+                let known: Vec<&str> = cli.contexts.keys().map(String::as_str).collect();
+                SdError::ConfigInvalid(format!(
+                    "Unknown context '{name}'. Configured contexts: {}",
+                    known.join(", ")
+                ))
+            }
+        })
+}
 
-    // Check if snapshot exists
-    if !db.snapshot_exists(&args.name)? {
-        return Err(SdError::Other(format!(
-            "Snapshot '{}' not found",
-            args.name
-        )));
+/// Apply a context's profile with a child `sd apply`, which keeps its
+/// output off the RPC stream. Returns the apply command's JSON result.
+fn apply_context_profile(cli: &Cli, name: &str) -> Result<serde_json::Value> {
+    let profile = context_profile(cli, name)?;
+    tracing::info!(context = name, profile = %profile.display(), "Applying context profile");
+
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command
+        .arg("apply")
+        .arg(profile)
+        .args(["--robot", "--compact"]);
+    if let Some(serial) = &cli.serial {
+        command.args(["--serial", serial]);
+    }
+    if let Some(path) = &cli.serial_file {
+        command.arg("--serial-file").arg(path);
+    }
+    if cli.is_dry_run() {
+        command.arg("--dry-run");
+    }
+    let child = command.output()?;
+
+    if child.status.success() {
+        // Warnings come first as their own documents; the result is last
+        let unreadable = |reason: String| {
+            SdError::Other(format!(
+                "Applying {} for context '{name}' printed unreadable output: {reason}",
+                profile.display()
+            ))
+        };
+        return json_documents(&child.stdout)
+            .map_err(|e| unreadable(e.to_string()))?
+            .into_iter()
+            .rev()
+            .find(|doc| doc["warning"] != true && doc["info"] != true)
+            .ok_or_else(|| unreadable("no apply result".to_string()));
     }
+    // The last robot-mode error on stderr says why; the rest is JSON logs
+    let stderr = String::from_utf8_lossy(&child.stderr);
+    let reason = json_documents(&child.stderr)
+        .ok()
+        .and_then(|docs| {
+            docs.into_iter()
+                .rev()
+                .find(|doc| doc["error"] == true)
+                .and_then(|error| error["message"].as_str().map(str::to_string))
+        })
+        .unwrap_or_else(|| stderr.trim().to_string());
+    Err(SdError::Other(format!(
+        "Applying {} for context '{name}' failed: {reason}",
+        profile.display()
+    )))
+}
 
-    // Confirm deletion if not forced and not in robot mode
-    if !args.force && !cli.use_json() {
-        let console = Console::new();
-        let warning = Color::parse("#FFA502").expect("valid color");
-        console.print_styled(
-            &format!("Delete snapshot '{}'? This cannot be undone.", args.name),
-            Style::new().color(warning),
-        );
-        console.print("Use --force to skip this prompt");
+/// Split robot-mode output into its JSON documents, pretty or compact.
+fn json_documents(bytes: &[u8]) -> serde_json::Result<Vec<serde_json::Value>> {
+    serde_json::Deserializer::from_slice(bytes)
+        .into_iter()
+        .collect()
+}
+
+/// Push a context for a running `sd serve --stdio` to apply.
+fn cmd_context(cli: &Cli, args: &cli::ContextArgs, output: &dyn Output) -> Result<()> {
+    let profile = context_profile(cli, &args.name)?;
+
+    if cli.is_dry_run() {
+        let details = serde_json::json!({ "context": args.name, "profile": profile });
+        if cli.use_json() {
+            let response = DryRunResponse::success(
+                "context",
+                details,
+                DeviceContext::disconnected(cli.serial.clone()),
+            );
+            output_json(cli, &response);
+        } else {
+            println!(
+                "DRY RUN: Would switch to context '{}' ({})",
+                args.name,
+                profile.display()
+            );
+        }
         return Ok(());
     }
 
-    // Delete snapshot
-    let deleted = db.delete_snapshot(&args.name)?;
-
+    let record = context::push(&context::default_context_path()?, &args.name)?;
     if cli.use_json() {
         output_json(
             cli,
             &serde_json::json!({
-                "command": "snapshot delete",
-                "ok": deleted,
-                "name": args.name,
-                "deleted": deleted,
+                "context": record.name,
+                "profile": profile,
+                "set_at": record.set_at,
             }),
         );
-    } else if !cli.quiet {
-        if deleted {
-            println!("Deleted snapshot '{}'", args.name);
-        } else {
-            println!("Snapshot '{}' not found", args.name);
-        }
+    } else {
+        output.success(&format!(
+            "Context '{}' sent; a running `sd serve --stdio` applies {}",
+            record.name,
+            profile.display()
+        ));
     }
+    Ok(())
+}
 
-    // Cleanup orphaned images
-    let cleaned = db.cleanup_orphaned_images()?;
-    if cleaned > 0 && !cli.quiet && !cli.use_json() {
-        println!("Cleaned up {} orphaned cached images", cleaned);
-    }
+#[derive(serde::Deserialize)]
+struct RpcKeyParams {
+    key: u8,
+}
 
-    Ok(())
+#[derive(serde::Deserialize)]
+struct RpcContextParams {
+    name: String,
 }
 
-/// Validates a snapshot name.
-fn is_valid_snapshot_name(name: &str) -> bool {
-    !name.is_empty()
-        && name.len() <= 64
-        && name
-            .chars()
-            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+#[derive(serde::Deserialize)]
+struct RpcBrightnessParams {
+    level: u8,
 }
 
-/// Computes SHA256 hash of an image file.
-fn hash_image_file(path: &std::path::Path) -> Result<String> {
-    use sha2::{Digest, Sha256};
+#[derive(serde::Deserialize)]
+struct RpcSetKeyParams {
+    key: u8,
+    image: std::path::PathBuf,
+}
 
-    let data = std::fs::read(path)
-        .map_err(|e| SdError::Other(format!("Failed to read image {}: {e}", path.display())))?;
+#[derive(serde::Deserialize)]
+struct RpcOverlayKeyParams {
+    key: u8,
+    image: std::path::PathBuf,
+    #[serde(default)]
+    position: Option<String>,
+    #[serde(default)]
+    scale: Option<f32>,
+}
 
-    let mut hasher = Sha256::new();
-    hasher.update(&data);
-    let result = hasher.finalize();
-    Ok(hex::encode(result))
+#[derive(serde::Deserialize)]
+struct RpcFillKeyParams {
+    key: u8,
+    color: String,
+    /// Blend a translucent `color` over the key's tracked image.
+    #[serde(default)]
+    over: bool,
 }
 
-/// Caches an image to content-addressable storage.
-fn cache_image(db: &snapshot::SnapshotDb, hash: &str, source_path: &std::path::Path) -> Result<()> {
-    // Get cache path
-    let cache_path = snapshot::image_cache_path(hash)?;
+/// Methods the server answers, reported by `hello`.
+///
+/// `auth` is handled by the serve loop; the rest by [`rpc_dispatch`].
+const RPC_METHODS: &[&str] = &[
+    "auth",
+    "hello",
+    "version",
+    "list",
+    "info",
+    "state",
+    "reset-state",
+    "brightness",
+    "set-key",
+    "fill-key",
+    "overlay-key",
+    "clear-key",
+    "clear-all",
+    "context",
+];
+
+#[derive(serde::Deserialize)]
+struct RpcAuthParams {
+    token: String,
+}
 
-    // Skip if already cached
-    if cache_path.exists() {
-        return Ok(());
+/// Handle `auth`: check the client's token against `--token`.
+///
+/// Without a server token any `auth` succeeds, so clients can always send it.
+fn rpc_authenticate(
+    token: Option<&str>,
+    request: &rpc::RpcRequest,
+    authenticated: &mut bool,
+) -> std::result::Result<serde_json::Value, rpc::RpcError> {
+    let params: RpcAuthParams = request.params()?;
+    if !token.is_none_or(|token| rpc::token_matches(token, &params.token)) {
+        tracing::warn!("Rejected RPC client with a wrong token");
+        return Err(rpc::RpcError::unauthorized());
     }
+    *authenticated = true;
+    Ok(serde_json::json!({ "authenticated": true }))
+}
 
-    // Create cache directory
-    if let Some(parent) = cache_path.parent() {
-        std::fs::create_dir_all(parent)
-            .map_err(|e| SdError::Other(format!("Failed to create cache directory: {e}")))?;
+/// Notifications `serve --stdio` may send, reported by `hello`.
+const RPC_NOTIFICATIONS: &[&str] = &["context"];
+
+/// Parse an RPC request's parameters as the arguments of its CLI command.
+///
+/// `argv` follows the command name, so calls get the command's defaults and
+/// validation.
+fn rpc_args<T: clap::Parser>(
+    request: &rpc::RpcRequest,
+    argv: Vec<std::ffi::OsString>,
+) -> std::result::Result<T, rpc::RpcError> {
+    T::try_parse_from(std::iter::once(request.method.clone().into()).chain(argv)).map_err(|e| {
+        let message = e.to_string();
+        let first_line = message.lines().next().unwrap_or_default();
+        rpc::RpcError::new(
+            rpc::INVALID_PARAMS,
+            first_line.trim_start_matches("error: ").to_string(),
+        )
+    })
+}
+
+/// Run one JSON-RPC request against the device.
+///
+/// Method names match the CLI commands and results match their robot output.
+/// Key writes go through the commands' own helpers, so they're tracked the
+/// same way, and keys are numbered per `--key-base`/`--key-order`.
+fn rpc_dispatch(
+    cli: &Cli,
+    request: &rpc::RpcRequest,
+) -> std::result::Result<serde_json::Value, rpc::RpcError> {
+    use serde_json::json;
+
+    fn to_value(value: &impl Serialize) -> std::result::Result<serde_json::Value, rpc::RpcError> {
+        serde_json::to_value(value)
+            .map_err(|e| rpc::RpcError::new(rpc::INTERNAL_ERROR, e.to_string()))
     }
 
-    // Copy image to cache (could convert to webp in future)
-    std::fs::copy(source_path, &cache_path)
-        .map_err(|e| SdError::Other(format!("Failed to cache image: {e}")))?;
+    match request.method.as_str() {
+        "hello" => {
+            let device = open_device(cli).ok().map(|d| device::get_device_info(&d));
+            to_value(&rpc::Hello::new(RPC_METHODS, RPC_NOTIFICATIONS, device))
+        }
+        "version" => Ok(json!({ "version": env!("CARGO_PKG_VERSION") })),
+        "list" => to_value(&device::list_devices()?),
+        "info" => to_value(&device::get_device_info(&open_device(cli)?)),
+        "state" => to_value(&state::session_state().report()),
+        "reset-state" => Ok(json!({ "reset": true, "forgotten_keys": state::reset() })),
+        "brightness" => {
+            let params: RpcBrightnessParams = request.params()?;
+            if params.level > 100 {
+                return Err(SdError::InvalidBrightness {
+                    value: params.level,
+                }
+                .into());
+            }
+            device::set_brightness(&open_device(cli)?, params.level)?;
+            state::record::brightness(params.level);
+            Ok(json!({ "brightness": params.level, "ok": true }))
+        }
+        "set-key" => {
+            let params: RpcSetKeyParams = request.params()?;
+            let key = cli.key_numbering.to_native(params.key)?;
+            let args: cli::SetKeyArgs = rpc_args(
+                request,
+                vec!["--".into(), key.to_string().into(), params.image.into()],
+            )?;
+            let args = resolve_key_icon(cli, &args)?;
+            let device = open_device(cli)?.with_sharpen(args.sharpen);
+            let warnings = write_key_image(cli, &device, &args)?;
+            to_value(&KeySetResult::new(cli.user_key(key), &args.image).with_warnings(warnings))
+        }
+        "fill-key" => {
+            let params: RpcFillKeyParams = request.params()?;
+            let key = cli.key_numbering.to_native(params.key)?;
+            let mut argv: Vec<std::ffi::OsString> =
+                vec!["--".into(), key.to_string().into(), params.color.into()];
+            if params.over {
+                argv.insert(0, "--over".into());
+            }
+            let args: cli::FillKeyArgs = rpc_args(request, argv)?;
+            let color = fill_key(&open_device(cli)?, &args)?;
+            Ok(json!({ "key": cli.user_key(key), "color": color, "ok": true }))
+        }
+        "overlay-key" => {
+            let params: RpcOverlayKeyParams = request.params()?;
+            let key = cli.key_numbering.to_native(params.key)?;
+            let mut argv: Vec<std::ffi::OsString> = Vec::new();
+            if let Some(position) = params.position {
+                argv.push(format!("--position={position}").into());
+            }
+            if let Some(scale) = params.scale {
+                argv.push(format!("--scale={scale}").into());
+            }
+            argv.extend(["--".into(), key.to_string().into(), params.image.into()]);
+            let args: cli::OverlayKeyArgs = rpc_args(request, argv)?;
+            to_value(&overlay_key(cli, &open_device(cli)?, &args)?)
+        }
+        "clear-key" => {
+            let params: RpcKeyParams = request.params()?;
+            let key = cli.key_numbering.to_native(params.key)?;
+            device::clear_key(&open_device(cli)?, key)?;
+            state::record::clear_key(key);
+            Ok(json!({ "key": cli.user_key(key), "cleared": true }))
+        }
+        "context" => {
+            let params: RpcContextParams = request.params()?;
+            let result = apply_context_profile(cli, &params.name)?;
+            Ok(json!({ "context": params.name, "ok": true, "result": result }))
+        }
+        "clear-all" => {
+            let device = open_device(cli)?;
+            device::clear_all_keys(&device)?;
+            state::record::clear_all(device.info().key_count);
+            Ok(json!({ "cleared": "all", "ok": true }))
+        }
+        other => Err(rpc::RpcError::method_not_found(other)),
+    }
+}
 
-    // Get file metadata for DB entry
-    let metadata = std::fs::metadata(&cache_path)
-        .map_err(|e| SdError::Other(format!("Failed to get cached image metadata: {e}")))?;
+#[allow(clippy::unnecessary_wraps)] // Consistent return type with other commands
+fn cmd_version(cli: &Cli, args: &cli::VersionArgs, output: &dyn Output) -> Result<()> {
+    if args.check {
+        return cmd_version_check(cli, args, output);
+    }
 
-    // Save to database
-    let cached = snapshot::CachedImage::new(
-        hash.to_string(),
-        Some(source_path.to_path_buf()),
-        0, // Width/height could be extracted from image
-        0,
-        "png".to_string(), // Format detection could be added
-        metadata.len(),
+    let git_sha = if build_info::git_dirty() == "true" {
+        format!("{} (dirty)", build_info::git_sha())
+    } else {
+        build_info::git_sha().to_string()
+    };
+    output.version_info(
+        build_info::VERSION,
+        Some(&git_sha),
+        Some(build_info::build_timestamp()),
     );
-    db.save_image(&cached)?;
+    Ok(())
+}
+
+/// Compare the running version with the latest release.
+#[allow(clippy::unnecessary_wraps)] // Consistent return type; check failures are reported, not raised
+fn cmd_version_check(cli: &Cli, args: &cli::VersionArgs, output: &dyn Output) -> Result<()> {
+    let check = if args.offline {
+        update::UpdateCheck::unavailable(build_info::VERSION, "offline mode (--offline)")
+    } else {
+        update::check_for_update(
+            build_info::VERSION,
+            &update::release_feed_url(),
+            update::CHECK_TIMEOUT,
+        )
+    };
 
+    if cli.use_json() {
+        output_json(cli, &check);
+        return Ok(());
+    }
+
+    match (&check.latest, &check.error) {
+        (Some(latest), _) if check.update_available => {
+            output.warning(&format!("Update available: {} -> {latest}", check.current));
+            if let Some(url) = &check.release_url {
+                output.info(&format!("Release: {url}"));
+            }
+        }
+        (Some(_), _) => output.success(&format!("sd {} is up to date", check.current)),
+        (None, Some(reason)) => output.warning(&format!(
+            "sd {}: could not check for updates ({reason})",
+            check.current
+        )),
+        (None, None) => {}
+    }
     Ok(())
 }
 
-/// Applies a cached image to a key.
-fn apply_cached_image(
-    device: &device::Device,
-    key: u8,
-    hash: &str,
-    source_path: Option<&std::path::PathBuf>,
-) -> Result<()> {
-    // Try cache first
-    let cache_path = snapshot::image_cache_path(hash)?;
-    if cache_path.exists() {
-        device::set_key_image(device, key, &cache_path, image_ops::ResizeStrategy::Fit)?;
-        if let Some(path) = source_path {
-            state::record::set_key(key, path.clone());
+/// Result of `sd raw`.
+#[derive(Serialize)]
+struct RawReportResult {
+    kind: device::RawReportKind,
+    /// Bytes sent, as space-separated hex.
+    sent: String,
+    sent_len: usize,
+    /// Bytes read back with `--read`, as space-separated hex.
+    response: Option<String>,
+}
+
+/// Send a raw HID report (hidden debugging command).
+fn cmd_raw(cli: &Cli, args: &cli::RawArgs, output: &dyn Output) -> Result<()> {
+    if !args.confirm {
+        return Err(SdError::Other(
+            "Refusing to send a raw HID report without --i-know-what-im-doing".to_string(),
+        ));
+    }
+
+    let sent = args.report.to_string();
+    if cli.is_dry_run() {
+        if cli.use_json() {
+            let device = open_device(cli).map_or_else(
+                |_| DeviceContext::disconnected(cli.serial.clone()),
+                |d| DeviceContext::from_info(d.info()),
+            );
+            let details = RawReportResult {
+                kind: args.kind,
+                sent,
+                sent_len: args.report.0.len(),
+                response: None,
+            };
+            output_json(cli, &DryRunResponse::success("raw_report", details, device));
+        } else {
+            println!("DRY RUN: Would send {:?} report: {sent}", args.kind);
         }
         return Ok(());
     }
 
-    // Fall back to source path
-    if let Some(path) = source_path {
-        if path.exists() {
-            device::set_key_image(device, key, path, image_ops::ResizeStrategy::Fit)?;
-            state::record::set_key(key, path.clone());
-            return Ok(());
+    let device = open_device(cli)?;
+    let response = device::send_raw_report(&device, args.kind, &args.report.0, args.read)?;
+    let result = RawReportResult {
+        kind: args.kind,
+        sent,
+        sent_len: args.report.0.len(),
+        response: response.map(|bytes| device::HexBytes(bytes).to_string()),
+    };
+
+    if cli.use_json() {
+        output_json(cli, &result);
+    } else {
+        output.success(&format!(
+            "Sent {} bytes ({:?} report): {}",
+            result.sent_len, result.kind, result.sent
+        ));
+        if let Some(response) = &result.response {
+            output.info(&format!("Response: {response}"));
         }
     }
+    Ok(())
+}
 
-    Err(SdError::Other(format!(
-        "Image not found in cache or at original path (hash: {hash})"
-    )))
+/// A `--dump-hid` capture file.
+#[derive(Serialize)]
+struct HidCaptureFile {
+    sd_version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    #[serde(flatten)]
+    dump: device::HidDump,
 }
 
-#[allow(clippy::unnecessary_wraps)] // Will return errors when implemented
-fn cmd_serve(cli: &Cli, args: &cli::ServeArgs) -> Result<()> {
-    let _ = (cli, args); // TODO: implement
-    eprintln!("Web server not yet implemented");
-    Ok(())
+/// Robot-mode result of `--dump-hid`.
+#[derive(Serialize)]
+struct HidCaptureResult {
+    command: &'static str,
+    path: String,
+    interfaces: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    serial: Option<String>,
 }
 
-#[allow(clippy::unnecessary_wraps)] // Consistent return type with other commands
-fn cmd_version(_cli: &Cli, output: &dyn Output) -> Result<()> {
-    let git_sha = if build_info::git_dirty() == "true" {
-        format!("{} (dirty)", build_info::git_sha())
-    } else {
-        build_info::git_sha().to_string()
+fn cmd_dump_hid(cli: &Cli, path: &std::path::Path, output: &dyn Output) -> Result<()> {
+    let dump = device::capture_hid(cli.serial.as_deref())?;
+    let capture = HidCaptureFile {
+        sd_version: build_info::VERSION,
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        dump,
     };
-    output.version_info(
-        build_info::VERSION,
-        Some(&git_sha),
-        Some(build_info::build_timestamp()),
-    );
+    let json = serde_json::to_string_pretty(&capture)
+        .map_err(|e| SdError::Other(format!("Failed to serialize HID capture: {e}")))?;
+    std::fs::write(path, json + "\n")
+        .map_err(|e| SdError::Other(format!("Failed to write {}: {e}", path.display())))?;
+
+    let result = HidCaptureResult {
+        command: "dump-hid",
+        path: path.display().to_string(),
+        interfaces: capture.dump.interfaces.len(),
+        serial: capture.dump.device.map(|info| info.serial),
+    };
+    if cli.use_json() {
+        output_json(cli, &result);
+    } else {
+        output.success(&format!(
+            "Wrote HID capture ({} interfaces) to {}",
+            result.interfaces, result.path
+        ));
+        if capture.dump.open_error.is_some() {
+            output.warning("The device couldn't be opened; only its enumeration was captured");
+        }
+    }
     Ok(())
 }
 
 #[allow(clippy::unnecessary_wraps)] // Consistent return type with other commands
 fn cmd_completions(_cli: &Cli, args: &cli::CompletionsArgs) -> Result<()> {
-    use clap::CommandFactory;
     clap_complete::generate(args.shell, &mut Cli::command(), "sd", &mut io::stdout());
     Ok(())
 }
 
 // === Utility Functions ===
 
+/// Parse an opaque hex color; translucent ones are rejected.
 fn parse_color(s: &str) -> Result<(u8, u8, u8)> {
-    let s = s.trim_start_matches('#');
-    if s.len() != 6 {
+    let (r, g, b, alpha) = parse_color_rgba(s)?;
+    if alpha < u8::MAX {
         return Err(SdError::Other(format!(
-            "Invalid color format '{s}': expected 6 hex digits (e.g., ff0000)"
+            "Color '{s}' is translucent; only fill-key --over can use alpha"
         )));
     }
-
-    let r = u8::from_str_radix(&s[0..2], 16)
-        .map_err(|_| SdError::Other(format!("Invalid red component in '{s}'")))?;
-    let g = u8::from_str_radix(&s[2..4], 16)
-        .map_err(|_| SdError::Other(format!("Invalid green component in '{s}'")))?;
-    let b = u8::from_str_radix(&s[4..6], 16)
-        .map_err(|_| SdError::Other(format!("Invalid blue component in '{s}'")))?;
-
     Ok((r, g, b))
 }
 
+/// Parse a hex color in any form [`color::parse_hex`] accepts, keeping alpha.
+fn parse_color_rgba(s: &str) -> Result<(u8, u8, u8, u8)> {
+    color::parse_hex(s).ok_or_else(|| {
+        SdError::Other(format!(
+            "Invalid color format '{s}': expected {}",
+            color::HEX_FORMS
+        ))
+    })
+}
+
 fn output_json<T: Serialize>(cli: &Cli, data: &T) {
-    let json = if cli.use_compact_json() {
-        serde_json::to_string(data).unwrap()
-    } else {
-        serde_json::to_string_pretty(data).unwrap()
-    };
-    println!("{json}");
+    match output::render_json_versioned(
+        data,
+        cli.json_compat,
+        cli.label.as_deref(),
+        cli.use_compact_json(),
+        cli.query.as_deref(),
+    ) {
+        Ok(json) => println!("{json}"),
+        Err(e) => output::record_render_error(e),
+    }
 }
@@ -0,0 +1,211 @@
+//! Health and metrics endpoints for `sd serve --metrics`.
+//!
+//! A long-running server can be monitored over HTTP:
+//!
+//! ```text
+//! GET /healthz   200 {"status":"ok","device_connected":true,...}, 503 if the device is gone
+//! GET /metrics   counters in the Prometheus text format
+//! ```
+//!
+//! [`ServerMetrics`] is shared between the request dispatch, which counts
+//! commands, and a device poller, which tracks the connection and counts
+//! reconnects. The routes are served by [`crate::server`], behind `--token`
+//! if one is set.
+
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use axum::Router;
+use axum::extract::State;
+use axum::http::{StatusCode, header};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use serde::Serialize;
+use tracing::info;
+
+/// How often the server checks whether the device is still connected.
+pub const DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+const DEVICE_UNKNOWN: u8 = 0;
+const DEVICE_DISCONNECTED: u8 = 1;
+const DEVICE_CONNECTED: u8 = 2;
+
+/// Counters for a running server, safe to update from any thread.
+#[derive(Debug)]
+pub struct ServerMetrics {
+    started: Instant,
+    commands: AtomicU64,
+    errors: AtomicU64,
+    reconnects: AtomicU64,
+    device: AtomicU8,
+}
+
+/// The `/healthz` response body.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Health {
+    /// Always `"ok"`: the server is answering.
+    pub status: &'static str,
+    /// Whether the device was connected at the last check.
+    pub device_connected: bool,
+    /// Seconds since the server started.
+    pub uptime_secs: u64,
+    /// Commands served so far.
+    pub commands: u64,
+}
+
+impl ServerMetrics {
+    /// Start counting from zero, with the device state not yet known.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            commands: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            reconnects: AtomicU64::new(0),
+            device: AtomicU8::new(DEVICE_UNKNOWN),
+        }
+    }
+
+    /// Count one served command, and whether it failed.
+    pub fn record_command(&self, ok: bool) {
+        self.commands.fetch_add(1, Ordering::Relaxed);
+        if !ok {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Record whether the device is connected; coming back after being
+    /// seen disconnected counts as a reconnect.
+    pub fn record_device(&self, connected: bool) {
+        let state = if connected {
+            DEVICE_CONNECTED
+        } else {
+            DEVICE_DISCONNECTED
+        };
+        let previous = self.device.swap(state, Ordering::Relaxed);
+        if previous != state && previous != DEVICE_UNKNOWN {
+            info!(connected, "Device connection changed");
+        }
+        if previous == DEVICE_DISCONNECTED && connected {
+            self.reconnects.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The current health report.
+    #[must_use]
+    pub fn health(&self) -> Health {
+        Health {
+            status: "ok",
+            device_connected: self.device.load(Ordering::Relaxed) == DEVICE_CONNECTED,
+            uptime_secs: self.started.elapsed().as_secs(),
+            commands: self.commands.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Render every metric in the Prometheus text exposition format.
+    #[must_use]
+    pub fn render(&self) -> String {
+        let health = self.health();
+        let metrics = [
+            (
+                "sd_commands_total",
+                "counter",
+                "Commands served.",
+                health.commands,
+            ),
+            (
+                "sd_command_errors_total",
+                "counter",
+                "Commands that failed.",
+                self.errors.load(Ordering::Relaxed),
+            ),
+            (
+                "sd_reconnects_total",
+                "counter",
+                "Times the device came back after disconnecting.",
+                self.reconnects.load(Ordering::Relaxed),
+            ),
+            (
+                "sd_device_connected",
+                "gauge",
+                "1 if the device was connected at the last check.",
+                u64::from(health.device_connected),
+            ),
+            (
+                "sd_uptime_seconds",
+                "gauge",
+                "Seconds since the server started.",
+                health.uptime_secs,
+            ),
+        ];
+        let mut text = String::new();
+        for (name, kind, help, value) in metrics {
+            let _ = writeln!(text, "# HELP {name} {help}");
+            let _ = writeln!(text, "# TYPE {name} {kind}");
+            let _ = writeln!(text, "{name} {value}");
+        }
+        text
+    }
+}
+
+impl Default for ServerMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Routes for `/healthz` and `/metrics`.
+pub fn router(metrics: Arc<ServerMetrics>) -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/metrics", get(metrics_text))
+        .with_state(metrics)
+}
+
+async fn healthz(State(metrics): State<Arc<ServerMetrics>>) -> impl IntoResponse {
+    let health = metrics.health();
+    let status = if health.device_connected {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, axum::Json(health))
+}
+
+async fn metrics_text(State(metrics): State<Arc<ServerMetrics>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_commands_and_reconnects() {
+        let metrics = ServerMetrics::new();
+        metrics.record_command(true);
+        metrics.record_command(false);
+        assert!(!metrics.health().device_connected);
+
+        // The first sighting isn't a reconnect; coming back is
+        metrics.record_device(true);
+        metrics.record_device(false);
+        metrics.record_device(true);
+        metrics.record_device(true);
+
+        let health = metrics.health();
+        assert!(health.device_connected);
+        assert_eq!(health.commands, 2);
+        let text = metrics.render();
+        assert!(text.contains("\nsd_commands_total 2\n"), "{text}");
+        assert!(text.contains("\nsd_command_errors_total 1\n"), "{text}");
+        assert!(text.contains("\nsd_reconnects_total 1\n"), "{text}");
+        assert!(text.contains("\nsd_device_connected 1\n"), "{text}");
+        assert!(text.contains("# TYPE sd_uptime_seconds gauge\n"), "{text}");
+    }
+}
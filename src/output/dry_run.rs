@@ -3,6 +3,7 @@
 use serde::Serialize;
 
 use crate::device::DeviceInfo;
+use crate::image_ops::{CanvasLayout, ResizeGeometry, ResizeStrategy};
 
 /// Common dry-run response wrapper.
 #[derive(Debug, Serialize)]
@@ -65,6 +66,16 @@ impl DeviceContext {
         }
     }
 
+    /// Build a disconnected context for a model assumed without hardware.
+    #[must_use]
+    pub fn assumed(info: &DeviceInfo) -> Self {
+        Self {
+            serial: None,
+            connected: false,
+            ..Self::from_info(info)
+        }
+    }
+
     /// Build a disconnected device context with optional serial.
     #[must_use]
     pub fn disconnected(serial: Option<String>) -> Self {
@@ -177,6 +188,9 @@ pub struct SetKeyDryRunDetails {
     pub source: ImageSourceInfo,
     /// Processing requirements.
     pub processing: ProcessingInfo,
+    /// Approximate on-device color (`--simulate-display`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simulated_display: Option<SimulatedDisplay>,
 }
 
 /// Information about the source image.
@@ -203,6 +217,14 @@ pub struct ProcessingInfo {
     pub resize_needed: bool,
     /// Target dimensions (width, height).
     pub target_dimensions: (u32, u32),
+    /// Resize strategy that will be used.
+    pub strategy: ResizeStrategy,
+    /// Scale, crop, and padding for the strategy (when source size is known).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geometry: Option<ResizeGeometry>,
+    /// Unsharp mask amount applied after resizing (`--sharpen`), if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sharpen: Option<u8>,
 }
 
 impl SetKeyDryRunDetails {
@@ -213,6 +235,43 @@ impl SetKeyDryRunDetails {
             key,
             source,
             processing,
+            simulated_display: None,
+        }
+    }
+
+    /// Attach a simulated on-device color.
+    #[must_use]
+    pub fn with_simulated_display(mut self, simulated: SimulatedDisplay) -> Self {
+        self.simulated_display = Some(simulated);
+        self
+    }
+}
+
+/// Dry-run details for set-canvas command.
+#[derive(Debug, Serialize)]
+pub struct SetCanvasDryRunDetails {
+    /// Source image information.
+    pub source: ImageSourceInfo,
+    /// Device grid rows.
+    pub rows: u8,
+    /// Device grid columns.
+    pub cols: u8,
+    /// Dimensions of each key tile (width, height).
+    pub tile_dimensions: (u32, u32),
+    /// Canvas size and per-key crop boxes.
+    pub layout: CanvasLayout,
+}
+
+impl SetCanvasDryRunDetails {
+    /// Create new set-canvas dry-run details.
+    #[must_use]
+    pub const fn new(source: ImageSourceInfo, rows: u8, cols: u8, layout: CanvasLayout) -> Self {
+        Self {
+            source,
+            rows,
+            cols,
+            tile_dimensions: (layout.key_size.width, layout.key_size.height),
+            layout,
         }
     }
 }
@@ -226,8 +285,14 @@ pub struct FillKeyDryRunDetails {
     pub color: String,
     /// RGB components.
     pub rgb: (u8, u8, u8),
+    /// RGB actually sent, when `--color-space` corrects the color.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upload_rgb: Option<(u8, u8, u8)>,
     /// Human-readable description.
     pub description: String,
+    /// Approximate on-device color (`--simulate-display`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simulated_display: Option<SimulatedDisplay>,
 }
 
 impl FillKeyDryRunDetails {
@@ -242,7 +307,50 @@ impl FillKeyDryRunDetails {
             key,
             color,
             rgb,
+            upload_rgb: None,
             description,
+            simulated_display: None,
+        }
+    }
+
+    /// Record the corrected RGB that would be sent instead of `rgb`.
+    #[must_use]
+    pub fn with_upload_rgb(mut self, upload_rgb: (u8, u8, u8)) -> Self {
+        self.upload_rgb = Some(upload_rgb);
+        self
+    }
+
+    /// Attach a simulated on-device color.
+    #[must_use]
+    pub fn with_simulated_display(mut self, simulated: SimulatedDisplay) -> Self {
+        self.simulated_display = Some(simulated);
+        self
+    }
+}
+
+/// Approximate appearance of a color on the physical key LCD.
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulatedDisplay {
+    /// Color sent to the device (for images, the average color).
+    pub input_rgb: (u8, u8, u8),
+    /// Approximate color as it appears on the LCD.
+    pub rgb: (u8, u8, u8),
+    /// Approximate color in hex format (with # prefix).
+    pub hex: String,
+    /// Reminder that this is not a calibrated measurement.
+    pub note: &'static str,
+}
+
+impl SimulatedDisplay {
+    /// Simulate how `input_rgb` looks on the device.
+    #[must_use]
+    pub fn new(input_rgb: (u8, u8, u8)) -> Self {
+        let rgb = crate::image_ops::simulate_lcd(input_rgb);
+        Self {
+            input_rgb,
+            rgb,
+            hex: format!("#{:02x}{:02x}{:02x}", rgb.0, rgb.1, rgb.2),
+            note: "Approximation of the device LCD; actual output varies by unit and brightness",
         }
     }
 }
@@ -287,6 +395,31 @@ impl ClearAllDryRunDetails {
     }
 }
 
+/// Dry-run details for reset command.
+#[derive(Debug, Serialize)]
+pub struct ResetDryRunDetails {
+    /// Total number of keys that would be cleared.
+    pub key_count: u8,
+    /// Brightness level that would be set (0-100).
+    pub brightness: u8,
+    /// Human-readable description.
+    pub description: String,
+}
+
+impl ResetDryRunDetails {
+    /// Create new reset dry-run details.
+    #[must_use]
+    pub fn new(key_count: u8, brightness: u8) -> Self {
+        Self {
+            key_count,
+            brightness,
+            description: format!(
+                "Would clear all {key_count} keys, set brightness to {brightness}% and forget tracked state"
+            ),
+        }
+    }
+}
+
 /// Dry-run details for clear-keys (batch) command.
 #[derive(Debug, Serialize)]
 pub struct ClearKeysDryRunDetails {
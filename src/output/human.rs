@@ -6,16 +6,29 @@ use std::path::Path;
 use rich_rust::prelude::*;
 use tracing::{debug, instrument, trace};
 
-use crate::device::{ButtonEvent, DeviceInfo};
+use crate::batch::ColorScheme;
+use crate::cli::AfterTimeout;
+use crate::device::{BrightnessReading, BrightnessSource, ButtonEvent, DeviceInfo, DeviceModel};
 use crate::error::SdError;
+use crate::image_ops::PaletteMode;
+use crate::state::{KeyState, StateReport};
 use crate::theme::SdTheme;
 
-use super::{BatchKeyResult, BatchSummary, Output, ValidationResult};
+use super::template::{self, OutputTemplates};
+use super::{
+    BatchKeyResult, BatchSummary, KeyDisplayEvent, KeySetResult, Output, ValidationResult,
+    visible_results,
+};
 
 /// Styled terminal output implementation for human users.
 pub struct HumanOutput {
     console: Console,
     theme: SdTheme,
+    explain: bool,
+    templates: OutputTemplates,
+    only_errors: bool,
+    key_gaps: bool,
+    label: Option<String>,
 }
 
 impl HumanOutput {
@@ -25,15 +38,80 @@ impl HumanOutput {
         Self {
             console,
             theme: SdTheme::default(),
+            explain: false,
+            templates: OutputTemplates::default(),
+            only_errors: false,
+            key_gaps: false,
+            label: None,
         }
     }
 
+    /// Prefix messages with `[label]` (`--label`).
+    #[must_use]
+    pub fn with_label(mut self, label: Option<String>) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Start a message line: the `--label` prefix, if any.
+    fn message_start(&self) -> Text {
+        let mut text = Text::new("");
+        if let Some(label) = &self.label {
+            text.append_styled(
+                &format!("[{label}] "),
+                Style::new().color(self.theme.muted.clone()),
+            );
+        }
+        text
+    }
+
+    /// Show an explanation under errors (`--explain`).
+    #[must_use]
+    pub fn with_explain(mut self, explain: bool) -> Self {
+        self.explain = explain;
+        self
+    }
+
+    /// List only failed keys in batch results (`--only-errors`).
+    #[must_use]
+    pub fn with_only_errors(mut self, only_errors: bool) -> Self {
+        self.only_errors = only_errors;
+        self
+    }
+
+    /// Draw key layouts with the device's physical gaps (`--gaps`).
+    #[must_use]
+    pub fn with_key_gaps(mut self, key_gaps: bool) -> Self {
+        self.key_gaps = key_gaps;
+        self
+    }
+
+    /// Use the user's message templates from the settings file.
+    #[must_use]
+    pub fn with_templates(mut self, templates: OutputTemplates) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    /// Print `template` with `values` substituted. Returns false (printing
+    /// nothing) when no template is set, so the caller uses its default.
+    fn print_template(&self, template: Option<&str>, values: &[(&str, &str)]) -> bool {
+        let Some(template) = template else {
+            return false;
+        };
+        self.console
+            .print_text(&Text::new(&template::render(template, values)));
+        true
+    }
+
     fn width(&self) -> usize {
         self.console.width()
     }
 
     /// Generate ASCII art key layout grid for device info display.
-    fn render_key_layout(&self, rows: u8, cols: u8) -> String {
+    ///
+    /// Keys whose entry in `pressed` is true are drawn filled.
+    fn render_key_layout(&self, rows: u8, cols: u8, pressed: &[bool]) -> String {
         let mut grid = String::new();
         let cell_width = 2;
 
@@ -52,7 +130,11 @@ impl HumanOutput {
             grid.push_str("  │");
             for c in 0..cols {
                 let key_num = r * cols + c;
-                grid.push_str(&format!("{key_num:>2}"));
+                if pressed.get(usize::from(key_num)).copied().unwrap_or(false) {
+                    grid.push_str("██");
+                } else {
+                    grid.push_str(&format!("{key_num:>2}"));
+                }
                 if c < cols - 1 {
                     grid.push('│');
                 }
@@ -85,6 +167,136 @@ impl HumanOutput {
         grid
     }
 
+    /// Generate a key layout with each key boxed on its own and blank
+    /// columns and rows for the bezels, scaled from the model's `gap_ratio`
+    /// so the grid is spaced like the device.
+    ///
+    /// Keys whose entry in `pressed` is true are drawn filled.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )] // Small, non-negative character counts
+    fn render_spaced_key_layout(
+        &self,
+        rows: u8,
+        cols: u8,
+        pressed: &[bool],
+        gap_ratio: f32,
+    ) -> String {
+        const BOX_WIDTH: usize = 6;
+        let gap_cols = (BOX_WIDTH as f32 * gap_ratio).round() as usize;
+        // Terminal cells are about twice as tall as they are wide
+        let gap_rows = gap_cols.div_ceil(2);
+        let gap = " ".repeat(gap_cols);
+
+        let mut grid = String::new();
+        for r in 0..rows {
+            if r > 0 {
+                grid.push_str(&"\n".repeat(gap_rows));
+            }
+            let mut lines = [String::from("  "), String::from("  "), String::from("  ")];
+            for c in 0..cols {
+                if c > 0 {
+                    for line in &mut lines {
+                        line.push_str(&gap);
+                    }
+                }
+                let key_num = r * cols + c;
+                let label = if pressed.get(usize::from(key_num)).copied().unwrap_or(false) {
+                    "████".to_string()
+                } else {
+                    format!("{key_num:^4}")
+                };
+                lines[0].push_str("┌────┐");
+                lines[1].push_str(&format!("│{label}│"));
+                lines[2].push_str("└────┘");
+            }
+            for line in lines {
+                grid.push_str(&line);
+                grid.push('\n');
+            }
+        }
+        grid
+    }
+
+    /// Build the device info panel body, marking any pressed keys in the layout.
+    fn device_info_text(
+        &self,
+        info: &DeviceInfo,
+        pressed: &[bool],
+        brightness: Option<&BrightnessReading>,
+    ) -> Text {
+        // Build specification display
+        let mut content = Text::new("\n");
+
+        // Serial
+        content.append_styled("  Serial      ", self.theme.label.clone());
+        content.append_styled(&info.serial, self.theme.device_serial.clone());
+        content.append("\n");
+
+        // Firmware
+        content.append_styled("  Firmware    ", self.theme.label.clone());
+        content.append_styled(&info.firmware_version, self.theme.value.clone());
+        content.append("\n");
+
+        // Keys
+        content.append_styled("  Keys        ", self.theme.label.clone());
+        content.append_styled(
+            &format!(
+                "{} ({} columns × {} rows)",
+                info.key_count, info.cols, info.rows
+            ),
+            self.theme.value.clone(),
+        );
+        content.append("\n");
+
+        // Key size
+        content.append_styled("  Key Size    ", self.theme.label.clone());
+        content.append_styled(
+            &format!("{}×{} pixels", info.key_width, info.key_height),
+            self.theme.value.clone(),
+        );
+        content.append("\n");
+
+        // Device type
+        content.append_styled("  Type        ", self.theme.label.clone());
+        content.append_styled(&info.kind, self.theme.value.clone());
+        content.append("\n");
+
+        // Brightness (read back, or the level this session last set)
+        if let Some(reading) = brightness {
+            content.append_styled("  Brightness  ", self.theme.label.clone());
+            content.append_styled(&format!("{}%", reading.level), self.theme.value.clone());
+            match reading.source {
+                Some(BrightnessSource::Device) => {
+                    content.append_styled(" (from device)", self.theme.muted.clone());
+                }
+                Some(BrightnessSource::Tracked) => {
+                    content.append_styled(" (tracked)", self.theme.muted.clone());
+                }
+                None => {}
+            }
+            content.append("\n");
+        }
+        content.append("\n");
+
+        // Key layout grid
+        content.append_styled("  Key Layout:\n", self.theme.label.clone());
+        let gap_ratio = info
+            .model()
+            .filter(|_| self.key_gaps)
+            .map(DeviceModel::gap_ratio);
+        let key_layout = match gap_ratio {
+            Some(ratio) => self.render_spaced_key_layout(info.rows, info.cols, pressed, ratio),
+            None => self.render_key_layout(info.rows, info.cols, pressed),
+        };
+        content.append_styled(&key_layout, self.theme.key_index.clone());
+        content.append("\n");
+
+        content
+    }
+
     /// Render a brightness bar using block characters.
     fn render_brightness_bar(&self, level: u8, width: usize) -> String {
         let filled = (usize::from(level) * width) / 100;
@@ -122,13 +334,33 @@ impl HumanOutput {
             .border_style(Style::new().color(self.theme.success.clone()))
             .box_style(self.theme.box_style)
     }
+
+    /// Print one line per key of a fill with per-key colors.
+    fn fill_results(&self, results: &[BatchKeyResult]) {
+        for result in visible_results(results, self.only_errors) {
+            let color = result.color.as_deref().unwrap_or("?");
+            if result.ok {
+                let mut text = Text::new(&format!("  Key {}: filled with ", result.key));
+                text.append_styled(color, self.theme.value.clone());
+                self.console.print_text(&text);
+            } else if let Some(ref err) = result.error {
+                let mut text = Text::new("");
+                text.append_styled(
+                    &format!("  Key {}: ", result.key),
+                    Style::new().color(self.theme.error.clone()),
+                );
+                text.append_styled(err, Style::new().color(self.theme.muted.clone()));
+                self.console.print_text(&text);
+            }
+        }
+    }
 }
 
 impl Output for HumanOutput {
     #[instrument(skip(self))]
     fn success(&self, message: &str) {
         debug!(message, "Outputting success");
-        let mut text = Text::new("");
+        let mut text = self.message_start();
         text.append_styled(
             "[OK] ",
             Style::new().bold().color(self.theme.success.clone()),
@@ -146,6 +378,12 @@ impl Output for HumanOutput {
         );
         let mut content = Text::new("\n");
         content.append("  ");
+        if let Some(label) = &self.label {
+            content.append_styled(
+                &format!("[{label}] "),
+                Style::new().color(self.theme.muted.clone()),
+            );
+        }
         content.append_styled(
             "[ERR] ",
             Style::new().bold().color(self.theme.error.clone()),
@@ -177,6 +415,16 @@ impl Output for HumanOutput {
             content.append("\n");
         }
 
+        if let Some(explanation) = error.explanation().filter(|_| self.explain) {
+            content.append("\n");
+            content.append_styled("  Explanation:\n", self.theme.label.clone());
+            content.append_styled(
+                &format!("  {explanation}"),
+                Style::new().color(self.theme.muted.clone()),
+            );
+            content.append("\n");
+        }
+
         content.append("\n");
 
         let panel = self.error_panel(&content, Some("Error"));
@@ -186,7 +434,7 @@ impl Output for HumanOutput {
     #[instrument(skip(self))]
     fn warning(&self, message: &str) {
         debug!(message, "Outputting warning");
-        let mut text = Text::new("");
+        let mut text = self.message_start();
         text.append_styled(
             "[WARN] ",
             Style::new().bold().color(self.theme.warning.clone()),
@@ -198,7 +446,7 @@ impl Output for HumanOutput {
     #[instrument(skip(self))]
     fn info(&self, message: &str) {
         debug!(message, "Outputting info");
-        let mut text = Text::new("");
+        let mut text = self.message_start();
         text.append_styled(
             "[INFO] ",
             Style::new().bold().color(self.theme.accent.clone()),
@@ -277,58 +525,111 @@ impl Output for HumanOutput {
         self.console.print_renderable(&panel);
     }
 
-    #[instrument(skip(self, info), fields(serial = %info.serial))]
-    fn device_info(&self, info: &DeviceInfo) {
+    #[instrument(skip(self, info, brightness), fields(serial = %info.serial))]
+    fn device_info(&self, info: &DeviceInfo, brightness: Option<&BrightnessReading>) {
         debug!("Outputting device info");
 
-        // Build specification display
-        let mut content = Text::new("\n");
-
-        // Serial
-        content.append_styled("  Serial      ", self.theme.label.clone());
-        content.append_styled(&info.serial, self.theme.device_serial.clone());
-        content.append("\n");
+        let content = self.device_info_text(info, &[], brightness);
+        let panel = Panel::from_rich_text(&content, self.width().saturating_sub(4))
+            .title(info.product_name.as_str())
+            .border_style(Style::new().color(self.theme.accent.clone()))
+            .box_style(self.theme.box_style);
 
-        // Firmware
-        content.append_styled("  Firmware    ", self.theme.label.clone());
-        content.append_styled(&info.firmware_version, self.theme.value.clone());
-        content.append("\n");
+        self.console.print_renderable(&panel);
+    }
 
-        // Keys
-        content.append_styled("  Keys        ", self.theme.label.clone());
+    #[instrument(skip(self, info, pressed), fields(serial = %info.serial))]
+    fn device_status(
+        &self,
+        info: &DeviceInfo,
+        brightness: Option<&BrightnessReading>,
+        pressed: &[bool],
+    ) {
+        trace!("Outputting device status");
+
+        let mut content = self.device_info_text(info, pressed, brightness);
+        content.append_styled("  Pressed     ", self.theme.label.clone());
+        let keys: Vec<String> = pressed
+            .iter()
+            .enumerate()
+            .filter(|(_, down)| **down)
+            .map(|(key, _)| key.to_string())
+            .collect();
+        if keys.is_empty() {
+            content.append_styled("none", self.theme.muted.clone());
+        } else {
+            content.append_styled(&keys.join(", "), self.theme.value.clone());
+        }
+        content.append("\n\n");
         content.append_styled(
-            &format!(
-                "{} ({} columns × {} rows)",
-                info.key_count, info.cols, info.rows
-            ),
-            self.theme.value.clone(),
+            "  Watching; press Ctrl+C to exit\n",
+            self.theme.muted.clone(),
         );
-        content.append("\n");
 
-        // Key size
-        content.append_styled("  Key Size    ", self.theme.label.clone());
-        content.append_styled(
-            &format!("{}×{} pixels", info.key_width, info.key_height),
-            self.theme.value.clone(),
-        );
-        content.append("\n");
+        let panel = Panel::from_rich_text(&content, self.width().saturating_sub(4))
+            .title(info.product_name.as_str())
+            .border_style(Style::new().color(self.theme.accent.clone()))
+            .box_style(self.theme.box_style);
 
-        // Device type
-        content.append_styled("  Type        ", self.theme.label.clone());
-        content.append_styled(&info.kind, self.theme.value.clone());
+        // Redraw in place on a terminal; piped output just gets each panel
+        // in turn rather than clear-screen escapes
+        let term = ::console::Term::stdout();
+        if term.is_term() {
+            let _ = term.clear_screen();
+        }
+        self.console.print_renderable(&panel);
+    }
+
+    fn device_state(&self, report: &StateReport) {
+        debug!(keys = report.keys.len(), "Outputting tracked device state");
+        let muted = Style::new().color(self.theme.muted.clone());
+
+        let mut content = Text::new("\n");
+        content.append_styled("  Brightness  ", self.theme.label.clone());
+        match report.brightness {
+            Some(level) => {
+                content.append_styled(&format!("{level}%"), self.theme.value.clone());
+            }
+            None => {
+                content.append_styled("unchanged", muted.clone());
+            }
+        }
         content.append("\n\n");
 
-        // Key layout grid
-        content.append_styled("  Key Layout:\n", self.theme.label.clone());
-        let key_layout = self.render_key_layout(info.rows, info.cols);
-        content.append_styled(&key_layout, self.theme.key_index.clone());
+        if report.keys.is_empty() {
+            content.append_styled("  No key changes tracked\n", muted.clone());
+        }
+        for entry in &report.keys {
+            content.append_styled(
+                &format!("  Key {:<3}  ", entry.key),
+                self.theme.key_index.clone(),
+            );
+            match &entry.state {
+                KeyState::Image { path } => {
+                    content.append_styled("image  ", self.theme.label.clone());
+                    content.append_styled(
+                        &crate::data_uri::display_source(path),
+                        self.theme.value.clone(),
+                    );
+                }
+                KeyState::Color { hex } => {
+                    content.append_styled("color  ", self.theme.label.clone());
+                    content.append_styled(hex, self.theme.value.clone());
+                }
+                KeyState::Cleared => {
+                    content.append_styled("cleared", self.theme.label.clone());
+                }
+            }
+            content.append("\n");
+        }
+
         content.append("\n");
+        content.append_styled(&format!("  {}\n", report.note), muted);
 
         let panel = Panel::from_rich_text(&content, self.width().saturating_sub(4))
-            .title(info.product_name.as_str())
+            .title("Tracked State")
             .border_style(Style::new().color(self.theme.accent.clone()))
             .box_style(self.theme.box_style);
-
         self.console.print_renderable(&panel);
     }
 
@@ -382,6 +683,12 @@ impl Output for HumanOutput {
     #[instrument(skip(self))]
     fn brightness_set(&self, level: u8) {
         debug!(level, "Outputting brightness set");
+        if self.print_template(
+            self.templates.brightness_set.as_deref(),
+            &[("level", &level.to_string())],
+        ) {
+            return;
+        }
 
         // Calculate bar width (leave room for label and percentage)
         // "  Brightness: " = 14 chars, " XXX%" = 5 chars, padding = 4
@@ -401,26 +708,42 @@ impl Output for HumanOutput {
         self.console.print_renderable(&panel);
     }
 
-    #[instrument(skip(self, image))]
-    fn key_set(&self, key: u8, image: &Path) {
-        let filename = image
+    #[instrument(skip(self, result), fields(key = result.key))]
+    fn key_set(&self, result: &KeySetResult) {
+        let filename = Path::new(&result.image)
             .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| image.display().to_string());
-        debug!(key, filename = %filename, "Outputting key set");
+            .map_or_else(|| result.image.clone(), |n| n.to_string_lossy().to_string());
+        debug!(filename = %filename, "Outputting key set");
 
-        let mut text = Text::new("");
-        text.append_styled("✓ ", Style::new().bold().color(self.theme.success.clone()));
-        text.append("Key ");
-        text.append_styled(&format!("{key}"), self.theme.key_index.clone());
-        text.append(" set to ");
-        text.append_styled(&filename, self.theme.value.clone());
-        self.console.print_text(&text);
+        let key = result.key.to_string();
+        let templated = self.print_template(
+            self.templates.key_set.as_deref(),
+            &[("key", &key), ("image", &result.image), ("file", &filename)],
+        );
+        if !templated {
+            let mut text = Text::new("");
+            text.append_styled("✓ ", Style::new().bold().color(self.theme.success.clone()));
+            text.append("Key ");
+            text.append_styled(&key, self.theme.key_index.clone());
+            text.append(" set to ");
+            text.append_styled(&filename, self.theme.value.clone());
+            self.console.print_text(&text);
+        }
+
+        for warning in &result.warnings {
+            self.warning(warning);
+        }
     }
 
     #[instrument(skip(self))]
     fn key_cleared(&self, key: u8) {
         debug!(key, "Outputting key cleared");
+        if self.print_template(
+            self.templates.key_cleared.as_deref(),
+            &[("key", &key.to_string())],
+        ) {
+            return;
+        }
 
         let mut text = Text::new("");
         text.append_styled("✓ ", Style::new().bold().color(self.theme.success.clone()));
@@ -433,6 +756,12 @@ impl Output for HumanOutput {
     #[instrument(skip(self))]
     fn key_filled(&self, key: u8, color: &str) {
         debug!(key, color, "Outputting key filled");
+        if self.print_template(
+            self.templates.key_filled.as_deref(),
+            &[("key", &key.to_string()), ("color", color)],
+        ) {
+            return;
+        }
 
         let mut text = Text::new("");
         text.append_styled("✓ ", Style::new().bold().color(self.theme.success.clone()));
@@ -443,9 +772,40 @@ impl Output for HumanOutput {
         self.console.print_text(&text);
     }
 
+    #[instrument(skip(self, event), fields(key = event.key()))]
+    fn key_display(&self, event: &KeyDisplayEvent) {
+        debug!("Outputting key display");
+        match event {
+            KeyDisplayEvent::Displayed {
+                for_ms,
+                key,
+                shows,
+                warnings,
+            } => {
+                for warning in warnings {
+                    self.warning(warning);
+                }
+                let duration = std::time::Duration::from_millis(*for_ms);
+                self.info(&format!(
+                    "Key {key} shows {shows} for {duration:?} (Ctrl+C to end early)"
+                ));
+            }
+            KeyDisplayEvent::Ended { key, then, .. } => {
+                let verb = match then {
+                    AfterTimeout::Restore => "Restored",
+                    AfterTimeout::Clear => "Cleared",
+                };
+                self.success(&format!("{verb} key {key}"));
+            }
+        }
+    }
+
     #[instrument(skip(self))]
     fn all_cleared(&self) {
         debug!("Outputting all cleared");
+        if self.print_template(self.templates.all_cleared.as_deref(), &[]) {
+            return;
+        }
 
         let mut text = Text::new("");
         text.append_styled("✓ ", Style::new().bold().color(self.theme.success.clone()));
@@ -456,6 +816,9 @@ impl Output for HumanOutput {
     #[instrument(skip(self))]
     fn all_filled(&self, color: &str) {
         debug!(color, "Outputting all filled");
+        if self.print_template(self.templates.all_filled.as_deref(), &[("color", color)]) {
+            return;
+        }
 
         let mut text = Text::new("");
         text.append_styled("✓ ", Style::new().bold().color(self.theme.success.clone()));
@@ -535,7 +898,7 @@ impl Output for HumanOutput {
         debug!("Outputting batch set-keys results");
 
         // Show per-key results
-        for result in results {
+        for result in visible_results(results, self.only_errors) {
             if result.ok {
                 if let Some(ref path) = result.path {
                     let filename = Path::new(path)
@@ -545,6 +908,12 @@ impl Output for HumanOutput {
                     self.console
                         .print(&format!("  Key {}: {}", result.key, filename));
                 }
+                for warning in &result.warnings {
+                    let mut text = Text::new("    ");
+                    text.append_styled("[WARN] ", Style::new().color(self.theme.warning.clone()));
+                    text.append(warning);
+                    self.console.print_text(&text);
+                }
             } else if let Some(ref err) = result.error {
                 let mut text = Text::new("");
                 text.append_styled(
@@ -567,12 +936,36 @@ impl Output for HumanOutput {
         }
     }
 
+    fn key_progress(&self, command: &str, result: &BatchKeyResult) {
+        trace!(command, key = result.key, "Outputting key progress");
+        if self.only_errors && result.ok {
+            return;
+        }
+        let mut text = self.message_start();
+        if result.ok {
+            text.append_styled(
+                &format!("  Key {}: done", result.key),
+                Style::new().color(self.theme.muted.clone()),
+            );
+        } else {
+            text.append_styled(
+                &format!("  Key {}: ", result.key),
+                Style::new().color(self.theme.error.clone()),
+            );
+            text.append_styled(
+                result.error.as_deref().unwrap_or("failed"),
+                Style::new().color(self.theme.muted.clone()),
+            );
+        }
+        self.console.print_text(&text);
+    }
+
     #[instrument(skip(self, results, summary), fields(total = summary.total, success = summary.success))]
     fn batch_fill_keys(&self, color: &str, results: &[BatchKeyResult], summary: &BatchSummary) {
         debug!(color, "Outputting batch fill-keys results");
 
         // Show per-key results
-        for result in results {
+        for result in visible_results(results, self.only_errors) {
             if result.ok {
                 self.console
                     .print(&format!("  Key {}: filled with {}", result.key, color));
@@ -603,7 +996,7 @@ impl Output for HumanOutput {
         debug!("Outputting batch clear-keys results");
 
         // Show per-key results
-        for result in results {
+        for result in visible_results(results, self.only_errors) {
             if result.ok {
                 self.console
                     .print(&format!("  Key {}: cleared", result.key));
@@ -629,6 +1022,79 @@ impl Output for HumanOutput {
         }
     }
 
+    #[instrument(skip(self, results, summary), fields(total = summary.total, success = summary.success))]
+    fn batch_fill_palette(
+        &self,
+        palette: &Path,
+        results: &[BatchKeyResult],
+        summary: &BatchSummary,
+    ) {
+        debug!(palette = %palette.display(), "Outputting batch fill-palette results");
+
+        // Show per-key results
+        self.fill_results(results);
+
+        // Show summary
+        let name = palette.file_name().map_or_else(
+            || palette.display().to_string(),
+            |n| n.to_string_lossy().to_string(),
+        );
+        if summary.failed == 0 {
+            self.success(&format!("Filled {} keys from {}", summary.success, name));
+        } else {
+            self.warning(&format!(
+                "Filled {} keys from {} ({} errors)",
+                summary.success, name, summary.failed
+            ));
+        }
+    }
+
+    fn batch_fill_scheme(
+        &self,
+        scheme: &ColorScheme,
+        results: &[BatchKeyResult],
+        summary: &BatchSummary,
+    ) {
+        debug!(%scheme, "Outputting generated fill results");
+
+        self.fill_results(results);
+        if summary.failed == 0 {
+            self.success(&format!("Filled {} keys with {scheme}", summary.success));
+        } else {
+            self.warning(&format!(
+                "Filled {} keys with {scheme} ({} errors)",
+                summary.success, summary.failed
+            ));
+        }
+    }
+
+    fn batch_fill_from_image(
+        &self,
+        source: &Path,
+        mode: PaletteMode,
+        results: &[BatchKeyResult],
+        summary: &BatchSummary,
+    ) {
+        debug!(source = %source.display(), %mode, "Outputting fill-from-image results");
+
+        self.fill_results(results);
+        let name = source.file_name().map_or_else(
+            || source.display().to_string(),
+            |n| n.to_string_lossy().to_string(),
+        );
+        if summary.failed == 0 {
+            self.success(&format!(
+                "Filled {} keys with the {mode} colors of {name}",
+                summary.success
+            ));
+        } else {
+            self.warning(&format!(
+                "Filled {} keys with the {mode} colors of {name} ({} errors)",
+                summary.success, summary.failed
+            ));
+        }
+    }
+
     #[instrument(skip(self, result), fields(valid = result.valid, errors = result.summary.error_count))]
     fn validation_result(&self, result: &ValidationResult) {
         debug!("Outputting validation result");
@@ -772,3 +1238,38 @@ impl Output for HumanOutput {
         self.console.print_renderable(&panel);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spaced_layout_uses_the_model_gap() {
+        let output = HumanOutput::new(Console::new());
+        let mut pressed = vec![false; 15];
+        pressed[6] = true;
+        // Mk2: 6-column boxes with a 0.33 gap round to 2 columns and 1 row
+        let grid = output.render_spaced_key_layout(3, 5, &pressed, DeviceModel::Mk2.gap_ratio());
+        let lines: Vec<&str> = grid.lines().collect();
+
+        assert_eq!(lines.len(), 3 * 3 + 2);
+        assert_eq!(lines[0], "  ┌────┐  ┌────┐  ┌────┐  ┌────┐  ┌────┐");
+        assert_eq!(lines[1], "  │ 0  │  │ 1  │  │ 2  │  │ 3  │  │ 4  │");
+        assert_eq!(lines[3], "");
+        assert_eq!(lines[5], "  │ 5  │  │████│  │ 7  │  │ 8  │  │ 9  │");
+        assert_eq!(lines[7], "");
+        assert_eq!(lines[10], "  └────┘  └────┘  └────┘  └────┘  └────┘");
+    }
+
+    #[test]
+    fn spaced_layout_without_gap_packs_the_boxes() {
+        let output = HumanOutput::new(Console::new());
+        let grid = output.render_spaced_key_layout(2, 2, &[], 0.0);
+
+        assert_eq!(
+            grid,
+            "  ┌────┐┌────┐\n  │ 0  ││ 1  │\n  └────┘└────┘\n\
+             \x20 ┌────┐┌────┐\n  │ 2  ││ 3  │\n  └────┘└────┘\n"
+        );
+    }
+}
@@ -6,21 +6,36 @@ use std::path::Path;
 use rich_rust::prelude::Console;
 use serde::Serialize;
 
-use crate::cli::Cli;
-use crate::device::{ButtonEvent, DeviceInfo};
+use crate::batch::ColorScheme;
+use crate::cli::{AfterTimeout, Cli};
+use crate::device::{BrightnessReading, ButtonEvent, DeviceInfo};
 use crate::error::SdError;
+use crate::image_ops::PaletteMode;
+use crate::state::StateReport;
 
 pub mod dry_run;
 pub mod human;
+pub mod renumber;
 pub mod robot;
+pub mod tee;
+pub mod template;
+pub mod time_format;
 
 pub use dry_run::{
     BrightnessDryRunDetails, ClearAllDryRunDetails, ClearKeyDryRunDetails, ClearKeysDryRunDetails,
     DeviceContext, DryRunResponse, FillKeyDryRunDetails, ImageSourceInfo, ProcessingInfo,
-    SetKeyDryRunDetails, ValidationError,
+    ResetDryRunDetails, SetCanvasDryRunDetails, SetKeyDryRunDetails, SimulatedDisplay,
+    ValidationError,
 };
 pub use human::HumanOutput;
-pub use robot::RobotOutput;
+pub use renumber::RenumberedOutput;
+pub use robot::{
+    DeviceList, JsonVersion, RobotOutput, record_render_error, render_json_versioned,
+    take_render_error,
+};
+pub use tee::TeeOutput;
+pub use template::OutputTemplates;
+pub use time_format::TimeFormat;
 
 // === Batch Operation Result Types ===
 
@@ -35,6 +50,23 @@ pub struct BatchKeyResult {
     pub ok: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// Non-fatal notices (e.g. the image was resized).
+    #[serde(skip_serializing_if = "omit_warnings")]
+    pub warnings: Vec<String>,
+    /// Config selector that won this key (`apply`).
+    #[serde(skip_serializing_if = "omit_selector")]
+    pub selector: Option<String>,
+}
+
+/// Per-key `warnings` are left out when empty or before [`JsonVersion::V2`].
+fn omit_warnings(warnings: &[String]) -> bool {
+    warnings.is_empty() || JsonVersion::omits(JsonVersion::V2)
+}
+
+/// The `apply` selector is left out when unset or before [`JsonVersion::V4`].
+#[allow(clippy::ref_option)] // serde passes the field by reference
+fn omit_selector(selector: &Option<String>) -> bool {
+    selector.is_none() || JsonVersion::omits(JsonVersion::V4)
 }
 
 impl BatchKeyResult {
@@ -43,10 +75,12 @@ impl BatchKeyResult {
     pub fn set_key_success(key: u8, path: &Path) -> Self {
         Self {
             key,
-            path: Some(path.display().to_string()),
+            path: Some(crate::data_uri::display_source(path)),
             color: None,
             ok: true,
             error: None,
+            warnings: Vec::new(),
+            selector: None,
         }
     }
 
@@ -55,10 +89,12 @@ impl BatchKeyResult {
     pub fn set_key_failure(key: u8, path: &Path, error: &str) -> Self {
         Self {
             key,
-            path: Some(path.display().to_string()),
+            path: Some(crate::data_uri::display_source(path)),
             color: None,
             ok: false,
             error: Some(error.to_string()),
+            warnings: Vec::new(),
+            selector: None,
         }
     }
 
@@ -71,6 +107,8 @@ impl BatchKeyResult {
             color: None,
             ok: true,
             error: None,
+            warnings: Vec::new(),
+            selector: None,
         }
     }
 
@@ -83,6 +121,8 @@ impl BatchKeyResult {
             color: None,
             ok: false,
             error: Some(error.to_string()),
+            warnings: Vec::new(),
+            selector: None,
         }
     }
 
@@ -95,6 +135,8 @@ impl BatchKeyResult {
             color: Some(color.to_string()),
             ok: true,
             error: None,
+            warnings: Vec::new(),
+            selector: None,
         }
     }
 
@@ -107,8 +149,116 @@ impl BatchKeyResult {
             color: Some(color.to_string()),
             ok: false,
             error: Some(error.to_string()),
+            warnings: Vec::new(),
+            selector: None,
+        }
+    }
+
+    /// Attach non-fatal warnings to this result.
+    #[must_use]
+    pub fn with_warnings(mut self, warnings: Vec<String>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Record the config selector that set this key.
+    #[must_use]
+    pub fn with_selector(mut self, selector: &str) -> Self {
+        self.selector = Some(selector.to_string());
+        self
+    }
+}
+
+/// Result of a single set-key operation.
+///
+/// Fields are in the order `set-key` has always printed them.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeySetResult {
+    pub image: String,
+    pub key: u8,
+    pub ok: bool,
+    /// Non-fatal notices (e.g. the image was resized).
+    #[serde(skip_serializing_if = "omit_warnings")]
+    pub warnings: Vec<String>,
+}
+
+impl KeySetResult {
+    /// Create a successful set-key result.
+    #[must_use]
+    pub fn new(key: u8, image: &Path) -> Self {
+        Self {
+            key,
+            image: crate::data_uri::display_source(image),
+            ok: true,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Attach non-fatal warnings to this result.
+    #[must_use]
+    pub fn with_warnings(mut self, warnings: Vec<String>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+}
+
+/// One step of a temporary key display (`set-key --for`, `fill-key --for`).
+///
+/// Robot mode prints each step as a one-line event tagged `event`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum KeyDisplayEvent {
+    /// The key shows `shows` for the next `for_ms` milliseconds.
+    Displayed {
+        for_ms: u64,
+        key: u8,
+        shows: String,
+        /// Non-fatal notices from the write.
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        warnings: Vec<String>,
+    },
+    /// The display ended (early with Ctrl+C) and the key was put back.
+    Ended {
+        interrupted: bool,
+        key: u8,
+        then: AfterTimeout,
+    },
+}
+
+impl KeyDisplayEvent {
+    /// The key being displayed on.
+    #[must_use]
+    pub const fn key(&self) -> u8 {
+        match self {
+            Self::Displayed { key, .. } | Self::Ended { key, .. } => *key,
         }
     }
+
+    /// This event for `key` instead.
+    #[must_use]
+    pub fn with_key(mut self, new_key: u8) -> Self {
+        match &mut self {
+            Self::Displayed { key, .. } | Self::Ended { key, .. } => *key = new_key,
+        }
+        self
+    }
+}
+
+/// Order batch results by key index.
+///
+/// Batches run in scan or selector order, which need not be monotonic;
+/// commands call this once before handing results to [`Output`] so both
+/// output modes list keys in a stable order. The sort is stable, so repeated
+/// operations on the same key keep their relative order.
+pub fn sort_batch_results(results: &mut [BatchKeyResult]) {
+    results.sort_by_key(|r| r.key);
+}
+
+/// The per-key results to list: all of them, or only failures with
+/// `--only-errors`. Summaries are built from the full results either way.
+#[must_use]
+pub fn visible_results(results: &[BatchKeyResult], only_errors: bool) -> Vec<&BatchKeyResult> {
+    results.iter().filter(|r| !(only_errors && r.ok)).collect()
 }
 
 /// Summary statistics for a batch operation.
@@ -190,6 +340,9 @@ pub struct ValidationResult {
     pub config_name: Option<String>,
     /// All issues found during validation
     pub issues: Vec<ValidationIssue>,
+    /// Key indices no selector covers (only with `--require-complete`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uncovered_keys: Option<Vec<u8>>,
     /// Summary statistics
     pub summary: ValidationSummary,
 }
@@ -214,6 +367,7 @@ impl ValidationResult {
             config_path: config_path.display().to_string(),
             config_name: None,
             issues: Vec::new(),
+            uncovered_keys: None,
             summary: ValidationSummary {
                 error_count: 0,
                 warning_count: 0,
@@ -293,13 +447,82 @@ pub enum RobotFormat {
     JsonCompact,
 }
 
+/// Serialize structured output for stdout, honoring `--query`.
+///
+/// Without a query the whole value is rendered (pretty or compact). With a
+/// JSON Pointer query only the selected value is rendered; strings are
+/// printed raw so they can be used directly in shell scripts.
+///
+/// # Errors
+///
+/// Returns an error if serialization fails or the pointer doesn't resolve.
+pub fn render_json<T: Serialize + ?Sized>(
+    data: &T,
+    compact: bool,
+    query: Option<&str>,
+) -> Result<String, SdError> {
+    let Some(pointer) = query else {
+        return serialize_json(data, compact);
+    };
+
+    let value = serde_json::to_value(data)
+        .map_err(|e| SdError::Other(format!("Failed to serialize output: {e}")))?;
+    let selected = value.pointer(pointer).ok_or_else(|| {
+        SdError::Other(format!(
+            "JSON pointer '{pointer}' does not match any value in the output \
+             (pointers start with '/', e.g. /device/serial)"
+        ))
+    })?;
+
+    match selected {
+        serde_json::Value::String(s) => Ok(s.clone()),
+        other => serialize_json(other, compact),
+    }
+}
+
+/// Serialize a value as pretty or compact JSON.
+fn serialize_json<T: Serialize + ?Sized>(data: &T, compact: bool) -> Result<String, SdError> {
+    let json = if compact {
+        serde_json::to_string(data)
+    } else {
+        serde_json::to_string_pretty(data)
+    };
+    json.map_err(|e| SdError::Other(format!("Failed to serialize output: {e}")))
+}
+
 /// Determines how command output is rendered.
 #[derive(Debug)]
 pub enum OutputMode {
     /// JSON output for AI agents and scripting.
-    Robot(RobotFormat),
+    Robot {
+        /// Pretty or compact JSON.
+        format: RobotFormat,
+        /// Optional JSON Pointer selecting a single field (`--query`).
+        query: Option<String>,
+        /// Pinned JSON shape (`--json-compat`).
+        version: JsonVersion,
+        /// Add error explanations (`--explain`).
+        explain: bool,
+        /// List only failed keys in batch results (`--only-errors`).
+        only_errors: bool,
+        /// Field added to every object emitted (`--label`).
+        label: Option<String>,
+    },
     /// Styled terminal output for human users.
-    Human(Console),
+    Human {
+        /// Configured console.
+        console: Console,
+        /// Add error explanations (`--explain`).
+        explain: bool,
+        /// Message templates from the settings file.
+        templates: OutputTemplates,
+        /// List only failed keys in batch results (`--only-errors`).
+        only_errors: bool,
+        /// Draw key layouts with the physical gaps (`--gaps`).
+        key_gaps: bool,
+        /// Prefix for every message (`--label`).
+        label: Option<String>,
+    },
 }
 
 impl OutputMode {
@@ -312,28 +535,73 @@ impl OutputMode {
             } else {
                 RobotFormat::Json
             };
-            Self::Robot(format)
+            Self::Robot {
+                format,
+                query: cli.query.clone(),
+                version: cli.json_compat,
+                explain: cli.explain,
+                only_errors: cli.only_errors,
+                label: cli.label.clone(),
+            }
         } else {
             let mut builder = Console::builder().safe_box(cli.no_color);
             if cli.no_color {
                 builder = builder.no_color();
             }
-            Self::Human(builder.build())
+            if let Some(width) = cli.output_width() {
+                builder = builder.width(width);
+            }
+            Self::Human {
+                console: builder.build(),
+                explain: cli.explain,
+                templates: cli.output_templates.clone(),
+                only_errors: cli.only_errors,
+                key_gaps: cli.gaps,
+                label: cli.label.clone(),
+            }
         }
     }
 
     /// Returns true if output should be JSON.
     #[must_use]
     pub const fn is_robot(&self) -> bool {
-        matches!(self, Self::Robot(_))
+        matches!(self, Self::Robot { .. })
     }
 
     /// Convert into the appropriate Output implementation.
     #[must_use]
     pub fn into_output(self) -> Box<dyn Output> {
         match self {
-            Self::Robot(format) => Box::new(RobotOutput::new(format)),
-            Self::Human(console) => Box::new(HumanOutput::new(console)),
+            Self::Robot {
+                format,
+                query,
+                version,
+                explain,
+                only_errors,
+                label,
+            } => Box::new(
+                RobotOutput::new(format)
+                    .with_query(query)
+                    .with_version(version)
+                    .with_explain(explain)
+                    .with_only_errors(only_errors)
+                    .with_label(label),
+            ),
+            Self::Human {
+                console,
+                explain,
+                templates,
+                only_errors,
+                key_gaps,
+                label,
+            } => Box::new(
+                HumanOutput::new(console)
+                    .with_explain(explain)
+                    .with_templates(templates)
+                    .with_only_errors(only_errors)
+                    .with_key_gaps(key_gaps)
+                    .with_label(label),
+            ),
         }
     }
 }
@@ -350,7 +618,17 @@ pub trait Output {
 
     // Device operations
     fn device_list(&self, devices: &[DeviceInfo]);
-    fn device_info(&self, info: &DeviceInfo);
+    /// Device details for `info`, with the current brightness when known.
+    fn device_info(&self, info: &DeviceInfo, brightness: Option<&BrightnessReading>);
+    /// Live device status: info plus currently pressed keys (`info --watch`).
+    fn device_status(
+        &self,
+        info: &DeviceInfo,
+        brightness: Option<&BrightnessReading>,
+        pressed: &[bool],
+    );
+    /// What the session believes the device shows (`sd state`).
+    fn device_state(&self, report: &StateReport);
 
     // Button events
     fn button_event(&self, event: &ButtonEvent);
@@ -358,9 +636,11 @@ pub trait Output {
 
     // Display operations
     fn brightness_set(&self, level: u8);
-    fn key_set(&self, key: u8, image: &Path);
+    fn key_set(&self, result: &KeySetResult);
     fn key_cleared(&self, key: u8);
     fn key_filled(&self, key: u8, color: &str);
+    /// A step of a temporary `--for` key display, as it happens.
+    fn key_display(&self, event: &KeyDisplayEvent);
     fn all_cleared(&self);
     fn all_filled(&self, color: &str);
 
@@ -372,6 +652,10 @@ pub trait Output {
     fn newline(&self);
 
     // Batch operations
+    /// One key's result as soon as it's done, for commands that stream
+    /// progress (`apply --stream`); the batch results still follow.
+    fn key_progress(&self, command: &str, result: &BatchKeyResult);
+
     /// Output results of a batch set-keys operation.
     fn batch_set_keys(&self, results: &[BatchKeyResult], summary: &BatchSummary);
 
@@ -381,6 +665,31 @@ pub trait Output {
     /// Output results of a batch clear-keys operation.
     fn batch_clear_keys(&self, results: &[BatchKeyResult], summary: &BatchSummary);
 
+    /// Output results of a fill-palette operation (per-key colors).
+    fn batch_fill_palette(
+        &self,
+        palette: &Path,
+        results: &[BatchKeyResult],
+        summary: &BatchSummary,
+    );
+
+    /// Output results of a generated fill (`fill-all --randomize`, `rainbow`).
+    fn batch_fill_scheme(
+        &self,
+        scheme: &ColorScheme,
+        results: &[BatchKeyResult],
+        summary: &BatchSummary,
+    );
+
+    /// Output results of a fill from an image's colors (`fill-from-image`).
+    fn batch_fill_from_image(
+        &self,
+        source: &Path,
+        mode: PaletteMode,
+        results: &[BatchKeyResult],
+        summary: &BatchSummary,
+    );
+
     // Validation output
     /// Output results of config validation.
     fn validation_result(&self, result: &ValidationResult);
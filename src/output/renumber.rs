@@ -0,0 +1,209 @@
+//! Key numbers in output for `--key-base` and `--key-order`.
+//!
+//! Commands work in native key indices; this wrapper translates every key
+//! an [`Output`] reports back to the numbering the user typed.
+
+use std::path::Path;
+
+use crate::batch::ColorScheme;
+use crate::device::{BrightnessReading, ButtonEvent, DeviceInfo};
+use crate::error::SdError;
+use crate::image_ops::PaletteMode;
+use crate::key_numbering::KeyNumbering;
+use crate::state::StateReport;
+
+use super::{
+    BatchKeyResult, BatchSummary, KeyDisplayEvent, KeySetResult, Output, ValidationResult,
+};
+
+/// Reports keys in the user's numbering, then delegates to `inner`.
+pub struct RenumberedOutput {
+    inner: Box<dyn Output>,
+    numbering: KeyNumbering,
+}
+
+impl RenumberedOutput {
+    /// Wrap `inner` so keys are reported with `numbering`.
+    #[must_use]
+    pub fn new(inner: Box<dyn Output>, numbering: KeyNumbering) -> Self {
+        Self { inner, numbering }
+    }
+
+    fn results(&self, results: &[BatchKeyResult]) -> Vec<BatchKeyResult> {
+        results
+            .iter()
+            .map(|result| BatchKeyResult {
+                key: self.numbering.to_user(result.key),
+                ..result.clone()
+            })
+            .collect()
+    }
+
+    /// Per-key flags in user order (the base doesn't apply to positions).
+    fn states(&self, states: &[bool]) -> Vec<bool> {
+        let mut reordered = states.to_vec();
+        for (native, state) in (0..=u8::MAX).zip(states) {
+            let position = self.numbering.to_user(native) - self.numbering.base();
+            if let Some(slot) = reordered.get_mut(usize::from(position)) {
+                *slot = *state;
+            }
+        }
+        reordered
+    }
+}
+
+impl Output for RenumberedOutput {
+    fn success(&self, message: &str) {
+        self.inner.success(message);
+    }
+
+    fn error(&self, error: &SdError) {
+        self.inner.error(error);
+    }
+
+    fn warning(&self, message: &str) {
+        self.inner.warning(message);
+    }
+
+    fn info(&self, message: &str) {
+        self.inner.info(message);
+    }
+
+    fn device_list(&self, devices: &[DeviceInfo]) {
+        self.inner.device_list(devices);
+    }
+
+    fn device_info(&self, info: &DeviceInfo, brightness: Option<&BrightnessReading>) {
+        self.inner.device_info(info, brightness);
+    }
+
+    fn device_status(
+        &self,
+        info: &DeviceInfo,
+        brightness: Option<&BrightnessReading>,
+        pressed: &[bool],
+    ) {
+        self.inner
+            .device_status(info, brightness, &self.states(pressed));
+    }
+
+    fn device_state(&self, report: &StateReport) {
+        let mut report = report.clone();
+        for entry in &mut report.keys {
+            entry.key = self.numbering.to_user(entry.key);
+        }
+        report.keys.sort_by_key(|entry| entry.key);
+        self.inner.device_state(&report);
+    }
+
+    fn button_event(&self, event: &ButtonEvent) {
+        self.inner.button_event(&ButtonEvent {
+            key: self.numbering.to_user(event.key),
+            ..event.clone()
+        });
+    }
+
+    fn button_states(&self, states: &[bool]) {
+        self.inner.button_states(&self.states(states));
+    }
+
+    fn brightness_set(&self, level: u8) {
+        self.inner.brightness_set(level);
+    }
+
+    fn key_set(&self, result: &KeySetResult) {
+        self.inner.key_set(&KeySetResult {
+            key: self.numbering.to_user(result.key),
+            ..result.clone()
+        });
+    }
+
+    fn key_cleared(&self, key: u8) {
+        self.inner.key_cleared(self.numbering.to_user(key));
+    }
+
+    fn key_filled(&self, key: u8, color: &str) {
+        self.inner.key_filled(self.numbering.to_user(key), color);
+    }
+
+    fn key_display(&self, event: &KeyDisplayEvent) {
+        let key = self.numbering.to_user(event.key());
+        self.inner.key_display(&event.clone().with_key(key));
+    }
+
+    fn all_cleared(&self) {
+        self.inner.all_cleared();
+    }
+
+    fn all_filled(&self, color: &str) {
+        self.inner.all_filled(color);
+    }
+
+    fn version_info(&self, version: &str, git_sha: Option<&str>, build_time: Option<&str>) {
+        self.inner.version_info(version, git_sha, build_time);
+    }
+
+    fn rule(&self, title: Option<&str>) {
+        self.inner.rule(title);
+    }
+
+    fn newline(&self) {
+        self.inner.newline();
+    }
+
+    fn batch_set_keys(&self, results: &[BatchKeyResult], summary: &BatchSummary) {
+        self.inner.batch_set_keys(&self.results(results), summary);
+    }
+
+    fn batch_fill_keys(&self, color: &str, results: &[BatchKeyResult], summary: &BatchSummary) {
+        self.inner
+            .batch_fill_keys(color, &self.results(results), summary);
+    }
+
+    fn key_progress(&self, command: &str, result: &BatchKeyResult) {
+        let result = BatchKeyResult {
+            key: self.numbering.to_user(result.key),
+            ..result.clone()
+        };
+        self.inner.key_progress(command, &result);
+    }
+
+    fn batch_clear_keys(&self, results: &[BatchKeyResult], summary: &BatchSummary) {
+        self.inner.batch_clear_keys(&self.results(results), summary);
+    }
+
+    fn batch_fill_palette(
+        &self,
+        palette: &Path,
+        results: &[BatchKeyResult],
+        summary: &BatchSummary,
+    ) {
+        self.inner
+            .batch_fill_palette(palette, &self.results(results), summary);
+    }
+
+    fn batch_fill_scheme(
+        &self,
+        scheme: &ColorScheme,
+        results: &[BatchKeyResult],
+        summary: &BatchSummary,
+    ) {
+        self.inner
+            .batch_fill_scheme(scheme, &self.results(results), summary);
+    }
+
+    fn batch_fill_from_image(
+        &self,
+        source: &Path,
+        mode: PaletteMode,
+        results: &[BatchKeyResult],
+        summary: &BatchSummary,
+    ) {
+        self.inner
+            .batch_fill_from_image(source, mode, &self.results(results), summary);
+    }
+
+    fn validation_result(&self, result: &ValidationResult) {
+        self.inner.validation_result(result);
+    }
+}
@@ -1,58 +1,367 @@
 //! Robot mode JSON output implementation.
+//!
+//! # JSON shape versions
+//!
+//! Robot output is versioned so integrations can pin a shape with
+//! `--json-compat <VERSION>` while new fields are added:
+//!
+//! - Within a version, fields are never renamed, removed or retyped.
+//! - Fields added to an existing output are listed on the [`JsonVersion`]
+//!   that adds them and skipped while an older version is pinned (see
+//!   [`JsonVersion::omits`]).
+//! - Output that only appears with a newer flag or command isn't versioned;
+//!   an integration pinned to an older shape never asks for it.
+//! - Output is serialized straight from its types, never re-ordered through
+//!   a `Value`: structs keep their field order and `json!` documents their
+//!   sorted keys, so pinned output is what the pinned version printed.
+//! - Without `--json-compat`, output uses [`JsonVersion::LATEST`].
 #![allow(dead_code)]
 
+use std::cell::Cell;
+use std::fmt;
+use std::io::Write;
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::{LazyLock, Mutex};
 
 use serde::Serialize;
-use tracing::{debug, instrument, trace};
+use serde_json::Value;
+use tracing::{debug, instrument, trace, warn};
 
-use crate::device::{ButtonEvent, DeviceInfo};
+use crate::batch::ColorScheme;
+use crate::device::{BrightnessReading, ButtonEvent, DeviceInfo};
 use crate::error::SdError;
+use crate::image_ops::PaletteMode;
+use crate::state::StateReport;
+
+use super::{
+    BatchKeyResult, BatchSummary, KeyDisplayEvent, KeySetResult, Output, RobotFormat,
+    ValidationResult, render_json, visible_results,
+};
+
+/// A documented robot-mode JSON shape.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum JsonVersion {
+    /// Original shape.
+    V1,
+    /// Adds `warnings` to per-key results (`set-key`, `set-keys`), and
+    /// unsupported Elgato devices to `list`.
+    V2,
+    /// `list` prints a [`DeviceList`] object instead of a bare array.
+    V3,
+    /// Adds `current_brightness` and `current_brightness_source` to `info`,
+    /// and `selector` to per-key `apply` results.
+    #[default]
+    V4,
+}
+
+thread_local! {
+    /// The version whose shape this thread is serializing.
+    static PINNED_VERSION: Cell<JsonVersion> = const { Cell::new(JsonVersion::LATEST) };
+}
 
-use super::{BatchKeyResult, BatchSummary, Output, RobotFormat, ValidationResult};
+impl JsonVersion {
+    /// The shape emitted when no version is pinned.
+    pub const LATEST: Self = Self::V4;
+
+    /// Pin this version for everything this thread serializes from now on
+    /// (`--json-compat`), including `json!` documents built outside [`Output`].
+    pub fn pin(self) {
+        PINNED_VERSION.with(|pinned| pinned.set(self));
+    }
+
+    /// Run `f` with this version pinned, then restore the previous pin.
+    pub fn pinned<R>(self, f: impl FnOnce() -> R) -> R {
+        let previous = PINNED_VERSION.with(|pinned| pinned.replace(self));
+        let result = f();
+        PINNED_VERSION.with(|pinned| pinned.set(previous));
+        result
+    }
+
+    /// Whether a field added in `added_in` is left out of what this thread
+    /// is serializing; for `skip_serializing_if` on versioned fields.
+    #[must_use]
+    pub fn omits(added_in: Self) -> bool {
+        PINNED_VERSION.with(|pinned| pinned.get() < added_in)
+    }
+
+    /// Serialize `data` in this version's shape.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization fails.
+    pub fn serialize<T: Serialize + ?Sized>(self, data: &T) -> Result<Value, SdError> {
+        self.pinned(|| serde_json::to_value(data))
+            .map_err(|e| SdError::Other(format!("Failed to serialize output: {e}")))
+    }
+}
+
+impl FromStr for JsonVersion {
+    type Err = SdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "v1" | "1" => Ok(Self::V1),
+            "v2" | "2" => Ok(Self::V2),
+            "v3" | "3" => Ok(Self::V3),
+            "v4" | "4" | "latest" => Ok(Self::V4),
+            other => Err(SdError::Other(format!(
+                "Unknown JSON compat version '{other}': expected v1, v2, v3, v4 or latest"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for JsonVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::V1 => write!(f, "v1"),
+            Self::V2 => write!(f, "v2"),
+            Self::V3 => write!(f, "v3"),
+            Self::V4 => write!(f, "v4"),
+        }
+    }
+}
+
+/// Robot `list` output: the devices plus the one a bare command would use.
+///
+/// Device entries are serialized unchanged; older `--json-compat` versions
+/// print them as a bare array instead.
+#[derive(Debug, Serialize)]
+pub struct DeviceList<'a, T: Serialize> {
+    /// Number of entries in `devices`.
+    pub count: usize,
+    /// Device entries, in the same shape as the bare-array output.
+    pub devices: &'a [T],
+    /// Serial a command without `--serial` opens; null when there is no
+    /// supported device or several (a bare command fails with `MultipleDevices`).
+    pub default: Option<&'a str>,
+    /// Serials of devices that didn't answer within `list --timeout`, so
+    /// their entries lack firmware. Omitted when every device answered.
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    pub timed_out: &'a [String],
+}
+
+impl<'a, T: Serialize> DeviceList<'a, T> {
+    /// Group `devices` for output; `supported` decides the default.
+    pub fn new(devices: &'a [T], supported: &'a [DeviceInfo]) -> Self {
+        let default = match supported {
+            [only] => Some(only.serial.as_str()),
+            _ => None,
+        };
+        Self {
+            count: devices.len(),
+            devices,
+            default,
+            timed_out: &[],
+        }
+    }
+
+    /// Report devices that didn't answer in time.
+    #[must_use]
+    pub const fn with_timed_out(mut self, timed_out: &'a [String]) -> Self {
+        self.timed_out = timed_out;
+        self
+    }
+}
+
+/// First output that failed to render during the current run.
+static RENDER_ERROR: LazyLock<Mutex<Option<SdError>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Record a failure to render output, keeping the first one.
+///
+/// Output methods can't fail, so the error is held until the command
+/// returns and [`take_render_error`] turns it into the run's result.
+pub fn record_render_error(error: SdError) {
+    debug!(error = %error, "Recording output render error");
+    RENDER_ERROR
+        .lock()
+        .expect("render error lock poisoned")
+        .get_or_insert(error);
+}
+
+/// Take the render failure recorded during the current run, if any.
+pub fn take_render_error() -> Option<SdError> {
+    RENDER_ERROR
+        .lock()
+        .expect("render error lock poisoned")
+        .take()
+}
+
+/// Render `data` in a pinned JSON shape, honoring `--label` and `--query`.
+///
+/// A `label` is added as the last field of object output; arrays are left
+/// as they are, so their shape doesn't depend on the flag.
+///
+/// # Errors
+///
+/// Returns an error if serialization fails or the pointer doesn't resolve.
+pub fn render_json_versioned<T: Serialize + ?Sized>(
+    data: &T,
+    version: JsonVersion,
+    label: Option<&str>,
+    compact: bool,
+    query: Option<&str>,
+) -> Result<String, SdError> {
+    #[derive(Serialize)]
+    struct Labeled<'a, T: Serialize + ?Sized> {
+        #[serde(flatten)]
+        data: &'a T,
+        label: &'a str,
+    }
+
+    version.pinned(|| match label {
+        Some(label) if version.serialize(data)?.is_object() => {
+            render_json(&Labeled { data, label }, compact, query)
+        }
+        _ => render_json(data, compact, query),
+    })
+}
 
 /// JSON output implementation for AI agents and scripting.
 ///
 /// IMPORTANT: This implementation must match existing JSON output.
 pub struct RobotOutput {
     format: RobotFormat,
+    query: Option<String>,
+    version: JsonVersion,
+    explain: bool,
+    only_errors: bool,
+    label: Option<String>,
+    writer: Option<Mutex<Box<dyn Write + Send>>>,
 }
 
 impl RobotOutput {
     #[instrument]
     pub fn new(format: RobotFormat) -> Self {
         debug!(?format, "Creating RobotOutput");
-        Self { format }
+        Self {
+            format,
+            query: None,
+            version: JsonVersion::LATEST,
+            explain: false,
+            only_errors: false,
+            label: None,
+            writer: None,
+        }
+    }
+
+    /// Write JSON Lines to `writer` instead of stdout and stderr (`--also-json`).
+    ///
+    /// Every value, errors included, is written compactly on its own line.
+    #[must_use]
+    pub fn with_writer(mut self, writer: Box<dyn Write + Send>) -> Self {
+        self.format = RobotFormat::JsonCompact;
+        self.writer = Some(Mutex::new(writer));
+        self
+    }
+
+    /// Write one line to the writer; returns false if there is none.
+    fn write_line(&self, line: &str) -> bool {
+        let Some(writer) = &self.writer else {
+            return false;
+        };
+        let mut writer = writer
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if let Err(e) = writeln!(writer, "{line}").and_then(|()| writer.flush()) {
+            warn!(error = %e, "Failed to write JSON output");
+        }
+        true
+    }
+
+    /// Add an `explanation` field to errors (`--explain`).
+    #[must_use]
+    pub fn with_explain(mut self, explain: bool) -> Self {
+        self.explain = explain;
+        self
+    }
+
+    /// Filter batch `results` to failed keys (`--only-errors`).
+    #[must_use]
+    pub fn with_only_errors(mut self, only_errors: bool) -> Self {
+        self.only_errors = only_errors;
+        self
+    }
+
+    /// Emit a pinned JSON shape (`--json-compat`).
+    #[must_use]
+    pub fn with_version(mut self, version: JsonVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Add a `label` field to every object emitted (`--label`).
+    #[must_use]
+    pub fn with_label(mut self, label: Option<String>) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Only print the value at this JSON Pointer (`--query`).
+    #[must_use]
+    pub fn with_query(mut self, query: Option<String>) -> Self {
+        self.query = query;
+        self
     }
 
     /// Output any serializable data as JSON to stdout.
     #[instrument(skip(self, data), fields(format = ?self.format))]
     fn output_json<T: Serialize + ?Sized>(&self, data: &T) {
-        let json = match self.format {
-            RobotFormat::Json => {
-                trace!("Serializing as pretty JSON");
-                serde_json::to_string_pretty(data).expect("serialization failed")
-            }
-            RobotFormat::JsonCompact => {
-                trace!("Serializing as compact JSON");
-                serde_json::to_string(data).expect("serialization failed")
-            }
-        };
-        trace!(json_len = json.len(), "JSON serialized");
-        println!("{json}");
+        let compact = matches!(self.format, RobotFormat::JsonCompact);
+        trace!(compact, query = ?self.query, version = %self.version, "Serializing JSON");
+        self.print_rendered(render_json_versioned(
+            data,
+            self.version,
+            self.label.as_deref(),
+            compact,
+            self.query.as_deref(),
+        ));
     }
 
     /// Output single-line JSON (for streaming events).
     #[instrument(skip(self, data))]
     fn output_json_line<T: Serialize>(&self, data: &T) {
-        let json = serde_json::to_string(data).expect("serialization failed");
-        trace!(json_len = json.len(), "JSON line serialized");
-        println!("{json}");
+        self.print_rendered(render_json_versioned(
+            data,
+            self.version,
+            self.label.as_deref(),
+            true,
+            self.query.as_deref(),
+        ));
+    }
+
+    /// Per-key results in this output's shape, without successes under
+    /// `--only-errors`.
+    fn results_json(&self, results: &[BatchKeyResult]) -> Value {
+        self.version
+            .pinned(|| serde_json::json!(visible_results(results, self.only_errors)))
+    }
+
+    /// Print rendered JSON, or record the failure for the command's result.
+    ///
+    /// A `--query` that doesn't resolve is a usage error: scripts must not
+    /// mistake empty output for a successful extraction.
+    fn print_rendered(&self, rendered: Result<String, SdError>) {
+        match rendered {
+            Ok(json) => {
+                trace!(json_len = json.len(), "JSON serialized");
+                if !self.write_line(&json) {
+                    println!("{json}");
+                }
+            }
+            Err(e) => record_render_error(e),
+        }
     }
 
     /// Output pretty JSON to stderr (matches existing error behavior).
     #[instrument(skip(self, data))]
     fn output_json_pretty_stderr<T: Serialize>(&self, data: &T) {
+        if self.writer.is_some() {
+            let json = serde_json::to_string(data).expect("serialization failed");
+            self.write_line(&json);
+            return;
+        }
         let json = serde_json::to_string_pretty(data).expect("serialization failed");
         trace!(json_len = json.len(), "JSON error serialized");
         eprintln!("{json}");
@@ -72,12 +381,16 @@ impl Output for RobotOutput {
     #[instrument(skip(self))]
     fn error(&self, error: &SdError) {
         debug!(error = %error, "Robot: error");
-        self.output_json_pretty_stderr(&serde_json::json!({
+        let mut json = serde_json::json!({
             "error": true,
             "message": error.to_string(),
             "suggestion": error.suggestion(),
             "recoverable": error.is_user_recoverable(),
-        }));
+        });
+        if self.explain {
+            json["explanation"] = serde_json::json!(error.explanation());
+        }
+        self.output_json_pretty_stderr(&json);
     }
 
     #[instrument(skip(self))]
@@ -101,13 +414,61 @@ impl Output for RobotOutput {
     #[instrument(skip(self, devices), fields(count = devices.len()))]
     fn device_list(&self, devices: &[DeviceInfo]) {
         debug!("Robot: device_list");
-        self.output_json(devices);
+        if self.version < JsonVersion::V3 {
+            self.output_json(devices);
+            return;
+        }
+        self.output_json(&DeviceList::new(devices, devices));
     }
 
-    #[instrument(skip(self, info), fields(serial = %info.serial))]
-    fn device_info(&self, info: &DeviceInfo) {
+    #[instrument(skip(self, info, brightness), fields(serial = %info.serial))]
+    fn device_info(&self, info: &DeviceInfo, brightness: Option<&BrightnessReading>) {
+        #[derive(Serialize)]
+        struct InfoWithBrightness<'a> {
+            #[serde(flatten)]
+            info: &'a DeviceInfo,
+            #[serde(flatten)]
+            brightness: Option<&'a BrightnessReading>,
+        }
+
         debug!("Robot: device_info");
-        self.output_json(info);
+        let brightness = brightness.filter(|_| self.version >= JsonVersion::V4);
+        self.output_json(&InfoWithBrightness { info, brightness });
+    }
+
+    #[instrument(skip(self, info, brightness, pressed), fields(serial = %info.serial))]
+    fn device_status(
+        &self,
+        info: &DeviceInfo,
+        brightness: Option<&BrightnessReading>,
+        pressed: &[bool],
+    ) {
+        #[derive(Serialize)]
+        struct DeviceStatus<'a> {
+            device: &'a DeviceInfo,
+            #[serde(flatten)]
+            brightness: Option<&'a BrightnessReading>,
+            pressed: Vec<usize>,
+        }
+
+        trace!("Robot: device_status");
+        let pressed: Vec<usize> = pressed
+            .iter()
+            .enumerate()
+            .filter(|(_, down)| **down)
+            .map(|(key, _)| key)
+            .collect();
+        let brightness = brightness.filter(|_| self.version >= JsonVersion::V4);
+        self.output_json_line(&DeviceStatus {
+            device: info,
+            brightness,
+            pressed,
+        });
+    }
+
+    fn device_state(&self, report: &StateReport) {
+        debug!(keys = report.keys.len(), "Robot: device_state");
+        self.output_json(report);
     }
 
     #[instrument(skip(self, event), fields(key = event.key, pressed = event.pressed))]
@@ -128,14 +489,10 @@ impl Output for RobotOutput {
         self.output_json(&serde_json::json!({ "brightness": level, "ok": true }));
     }
 
-    #[instrument(skip(self, image))]
-    fn key_set(&self, key: u8, image: &Path) {
-        debug!(key, image = %image.display(), "Robot: key_set");
-        self.output_json(&serde_json::json!({
-            "key": key,
-            "image": image.display().to_string(),
-            "ok": true
-        }));
+    #[instrument(skip(self, result), fields(key = result.key))]
+    fn key_set(&self, result: &KeySetResult) {
+        debug!(image = %result.image, warnings = result.warnings.len(), "Robot: key_set");
+        self.output_json(result);
     }
 
     #[instrument(skip(self))]
@@ -154,6 +511,12 @@ impl Output for RobotOutput {
         }));
     }
 
+    #[instrument(skip(self, event), fields(key = event.key()))]
+    fn key_display(&self, event: &KeyDisplayEvent) {
+        debug!("Robot: key_display");
+        self.output_json_line(event);
+    }
+
     #[instrument(skip(self))]
     fn all_cleared(&self) {
         debug!("Robot: all_cleared");
@@ -196,7 +559,7 @@ impl Output for RobotOutput {
         self.output_json(&serde_json::json!({
             "command": "set-keys",
             "ok": summary.is_success(),
-            "results": results,
+            "results": self.results_json(results),
             "summary": {
                 "total": summary.total,
                 "success": summary.success,
@@ -213,7 +576,7 @@ impl Output for RobotOutput {
             "command": "fill-keys",
             "color": color,
             "ok": summary.is_success(),
-            "results": results,
+            "results": self.results_json(results),
             "summary": {
                 "total": summary.total,
                 "filled": summary.success,
@@ -228,7 +591,7 @@ impl Output for RobotOutput {
         self.output_json(&serde_json::json!({
             "command": "clear-keys",
             "ok": summary.is_success(),
-            "results": results,
+            "results": self.results_json(results),
             "summary": {
                 "total": summary.total,
                 "cleared": summary.success,
@@ -237,6 +600,93 @@ impl Output for RobotOutput {
         }));
     }
 
+    #[instrument(skip(self, results, summary), fields(total = summary.total, success = summary.success))]
+    fn batch_fill_palette(
+        &self,
+        palette: &Path,
+        results: &[BatchKeyResult],
+        summary: &BatchSummary,
+    ) {
+        debug!(palette = %palette.display(), "Robot: batch_fill_palette");
+        self.output_json(&serde_json::json!({
+            "command": "fill-palette",
+            "palette": palette.display().to_string(),
+            "ok": summary.is_success(),
+            "results": self.results_json(results),
+            "summary": {
+                "total": summary.total,
+                "filled": summary.success,
+                "failed": summary.failed,
+                "skipped": summary.skipped,
+            }
+        }));
+    }
+
+    fn batch_fill_scheme(
+        &self,
+        scheme: &ColorScheme,
+        results: &[BatchKeyResult],
+        summary: &BatchSummary,
+    ) {
+        debug!(%scheme, "Robot: batch_fill_scheme");
+        let mut json = serde_json::json!({
+            "command": match scheme {
+                ColorScheme::Random { .. } => "fill-all",
+                ColorScheme::Rainbow => "rainbow",
+            },
+            "ok": summary.is_success(),
+            "results": self.results_json(results),
+            "summary": {
+                "total": summary.total,
+                "filled": summary.success,
+                "failed": summary.failed,
+                "skipped": summary.skipped,
+            }
+        });
+        if let (Value::Object(map), Value::Object(fields)) = (&mut json, serde_json::json!(scheme))
+        {
+            map.extend(fields);
+        }
+        self.output_json(&json);
+    }
+
+    fn key_progress(&self, command: &str, result: &BatchKeyResult) {
+        trace!(command, key = result.key, "Robot: key_progress");
+        if self.only_errors && result.ok {
+            return;
+        }
+        let mut json = serde_json::json!({ "event": "key", "command": command });
+        if let (Value::Object(map), Value::Object(fields)) =
+            (&mut json, self.version.pinned(|| serde_json::json!(result)))
+        {
+            map.extend(fields);
+        }
+        self.output_json_line(&json);
+    }
+
+    fn batch_fill_from_image(
+        &self,
+        source: &Path,
+        mode: PaletteMode,
+        results: &[BatchKeyResult],
+        summary: &BatchSummary,
+    ) {
+        debug!(source = %source.display(), %mode, "Robot: batch_fill_from_image");
+        self.output_json(&serde_json::json!({
+            "command": "fill-from-image",
+            "source": source.display().to_string(),
+            "mode": mode,
+            "ok": summary.is_success(),
+            "results": self.results_json(results),
+            "summary": {
+                "total": summary.total,
+                "filled": summary.success,
+                "failed": summary.failed,
+                "skipped": summary.skipped,
+            }
+        }));
+    }
+
     #[instrument(skip(self, result), fields(valid = result.valid, errors = result.summary.error_count))]
     fn validation_result(&self, result: &ValidationResult) {
         debug!("Robot: validation_result");
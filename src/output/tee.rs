@@ -0,0 +1,171 @@
+//! Output to several sinks at once, for `--also-json`.
+//!
+//! [`TeeOutput`] forwards every call, errors included, to each sink in
+//! turn, so a terminal can show human output while a file logs the same
+//! run as JSON.
+
+use std::path::Path;
+
+use crate::batch::ColorScheme;
+use crate::device::{BrightnessReading, ButtonEvent, DeviceInfo};
+use crate::error::SdError;
+use crate::image_ops::PaletteMode;
+use crate::state::StateReport;
+
+use super::{
+    BatchKeyResult, BatchSummary, KeyDisplayEvent, KeySetResult, Output, ValidationResult,
+};
+
+/// Fans each output call out to every sink, in order.
+pub struct TeeOutput {
+    sinks: Vec<Box<dyn Output>>,
+}
+
+impl TeeOutput {
+    /// Forward to `sinks`; the first is usually the terminal.
+    #[must_use]
+    pub fn new(sinks: Vec<Box<dyn Output>>) -> Self {
+        Self { sinks }
+    }
+
+    fn each(&self, call: impl Fn(&dyn Output)) {
+        for sink in &self.sinks {
+            call(sink.as_ref());
+        }
+    }
+}
+
+impl Output for TeeOutput {
+    fn success(&self, message: &str) {
+        self.each(|sink| sink.success(message));
+    }
+
+    fn error(&self, error: &SdError) {
+        self.each(|sink| sink.error(error));
+    }
+
+    fn warning(&self, message: &str) {
+        self.each(|sink| sink.warning(message));
+    }
+
+    fn info(&self, message: &str) {
+        self.each(|sink| sink.info(message));
+    }
+
+    fn device_list(&self, devices: &[DeviceInfo]) {
+        self.each(|sink| sink.device_list(devices));
+    }
+
+    fn device_info(&self, info: &DeviceInfo, brightness: Option<&BrightnessReading>) {
+        self.each(|sink| sink.device_info(info, brightness));
+    }
+
+    fn device_status(
+        &self,
+        info: &DeviceInfo,
+        brightness: Option<&BrightnessReading>,
+        pressed: &[bool],
+    ) {
+        self.each(|sink| sink.device_status(info, brightness, pressed));
+    }
+
+    fn device_state(&self, report: &StateReport) {
+        self.each(|sink| sink.device_state(report));
+    }
+
+    fn button_event(&self, event: &ButtonEvent) {
+        self.each(|sink| sink.button_event(event));
+    }
+
+    fn button_states(&self, states: &[bool]) {
+        self.each(|sink| sink.button_states(states));
+    }
+
+    fn brightness_set(&self, level: u8) {
+        self.each(|sink| sink.brightness_set(level));
+    }
+
+    fn key_set(&self, result: &KeySetResult) {
+        self.each(|sink| sink.key_set(result));
+    }
+
+    fn key_cleared(&self, key: u8) {
+        self.each(|sink| sink.key_cleared(key));
+    }
+
+    fn key_filled(&self, key: u8, color: &str) {
+        self.each(|sink| sink.key_filled(key, color));
+    }
+
+    fn key_display(&self, event: &KeyDisplayEvent) {
+        self.each(|sink| sink.key_display(event));
+    }
+
+    fn all_cleared(&self) {
+        self.each(|sink| sink.all_cleared());
+    }
+
+    fn all_filled(&self, color: &str) {
+        self.each(|sink| sink.all_filled(color));
+    }
+
+    fn version_info(&self, version: &str, git_sha: Option<&str>, build_time: Option<&str>) {
+        self.each(|sink| sink.version_info(version, git_sha, build_time));
+    }
+
+    fn rule(&self, title: Option<&str>) {
+        self.each(|sink| sink.rule(title));
+    }
+
+    fn newline(&self) {
+        self.each(|sink| sink.newline());
+    }
+
+    fn batch_set_keys(&self, results: &[BatchKeyResult], summary: &BatchSummary) {
+        self.each(|sink| sink.batch_set_keys(results, summary));
+    }
+
+    fn batch_fill_keys(&self, color: &str, results: &[BatchKeyResult], summary: &BatchSummary) {
+        self.each(|sink| sink.batch_fill_keys(color, results, summary));
+    }
+
+    fn key_progress(&self, command: &str, result: &BatchKeyResult) {
+        self.each(|sink| sink.key_progress(command, result));
+    }
+
+    fn batch_clear_keys(&self, results: &[BatchKeyResult], summary: &BatchSummary) {
+        self.each(|sink| sink.batch_clear_keys(results, summary));
+    }
+
+    fn batch_fill_palette(
+        &self,
+        palette: &Path,
+        results: &[BatchKeyResult],
+        summary: &BatchSummary,
+    ) {
+        self.each(|sink| sink.batch_fill_palette(palette, results, summary));
+    }
+
+    fn batch_fill_scheme(
+        &self,
+        scheme: &ColorScheme,
+        results: &[BatchKeyResult],
+        summary: &BatchSummary,
+    ) {
+        self.each(|sink| sink.batch_fill_scheme(scheme, results, summary));
+    }
+
+    fn batch_fill_from_image(
+        &self,
+        source: &Path,
+        mode: PaletteMode,
+        results: &[BatchKeyResult],
+        summary: &BatchSummary,
+    ) {
+        self.each(|sink| sink.batch_fill_from_image(source, mode, results, summary));
+    }
+
+    fn validation_result(&self, result: &ValidationResult) {
+        self.each(|sink| sink.validation_result(result));
+    }
+}
@@ -0,0 +1,107 @@
+//! User templates for human-mode messages.
+//!
+//! The settings file can rephrase the one-line confirmations printed after a
+//! command succeeds:
+//!
+//! ```yaml
+//! output:
+//!   templates:
+//!     key_set: "[sd] key {key} <- {file}"
+//!     key_filled: "[sd] key {key} = {color}"
+//! ```
+//!
+//! Templates are plain text with `{name}` placeholders; `{{` and `}}` print
+//! literal braces. Nothing is evaluated, and an unknown placeholder is printed
+//! as written. Messages without a template keep the built-in styling.
+
+use serde::{Deserialize, Serialize};
+
+/// Templates for human-mode confirmations; unset ones use the defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputTemplates {
+    /// `set-key`: `{key}`, `{image}` (path as given), `{file}` (file name).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_set: Option<String>,
+    /// `clear-key`: `{key}`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_cleared: Option<String>,
+    /// `fill-key`: `{key}`, `{color}`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_filled: Option<String>,
+    /// `clear-all`: no placeholders.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub all_cleared: Option<String>,
+    /// `fill-all`: `{color}`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub all_filled: Option<String>,
+    /// `brightness`: `{level}`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brightness_set: Option<String>,
+}
+
+/// Substitute `{name}` placeholders in `template` from `values`.
+///
+/// `{{` and `}}` produce literal braces; placeholders not in `values` (and
+/// unmatched braces) are copied through unchanged.
+#[must_use]
+pub fn render(template: &str, values: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(pos) = rest.find(['{', '}']) {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        if let Some(after) = rest.strip_prefix("{{") {
+            out.push('{');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("}}") {
+            out.push('}');
+            rest = after;
+        } else {
+            let placeholder = rest
+                .strip_prefix('{')
+                .and_then(|inner| inner.split_once('}'))
+                .and_then(|(name, after)| {
+                    values
+                        .iter()
+                        .find(|(key, _)| *key == name)
+                        .map(|(_, value)| (*value, after))
+                });
+            if let Some((value, after)) = placeholder {
+                out.push_str(value);
+                rest = after;
+            } else {
+                out.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let values = [("key", "3"), ("color", "#ff0000")];
+        assert_eq!(render("Key {key} = {color}", &values), "Key 3 = #ff0000");
+        assert_eq!(render("{key}{key}", &values), "33");
+        assert_eq!(render("{{key}} {key}", &values), "{key} 3");
+        assert_eq!(render("{unknown} {key", &values), "{unknown} {key");
+        assert_eq!(render("} {", &values), "} {");
+        assert_eq!(render("plain", &values), "plain");
+        assert_eq!(render("✓ {key}", &values), "✓ 3");
+    }
+
+    #[test]
+    fn test_deserialize_rejects_unknown_templates() {
+        let templates: OutputTemplates =
+            serde_yaml::from_str("key_set: \"{key} <- {file}\"\n").unwrap();
+        assert_eq!(templates.key_set.as_deref(), Some("{key} <- {file}"));
+        assert!(templates.key_filled.is_none());
+        assert!(serde_yaml::from_str::<OutputTemplates>("key_sett: x\n").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! Timestamp rendering for human output (`--time-format`).
+//!
+//! Robot mode always emits RFC3339; these formats only affect text output.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Utc};
+
+use crate::error::SdError;
+
+/// How timestamps are rendered in human output.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// The command's built-in pattern (e.g. `%Y-%m-%d %H:%M`).
+    #[default]
+    Default,
+    /// Relative to now, e.g. "2 hours ago".
+    Relative,
+    /// A custom strftime pattern, e.g. `%d.%m.%Y %H:%M`.
+    Custom(String),
+}
+
+impl TimeFormat {
+    /// Render a timestamp, using `default_pattern` for [`TimeFormat::Default`].
+    #[must_use]
+    pub fn format(&self, ts: &DateTime<Utc>, default_pattern: &str) -> String {
+        self.format_at(ts, default_pattern, Utc::now())
+    }
+
+    /// Render a timestamp relative to an explicit `now` (for tests).
+    #[must_use]
+    pub fn format_at(
+        &self,
+        ts: &DateTime<Utc>,
+        default_pattern: &str,
+        now: DateTime<Utc>,
+    ) -> String {
+        match self {
+            Self::Default => ts.format(default_pattern).to_string(),
+            Self::Relative => format_relative(ts, now),
+            Self::Custom(pattern) => ts.format(pattern).to_string(),
+        }
+    }
+}
+
+impl FromStr for TimeFormat {
+    type Err = SdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "" | "default" => Ok(Self::Default),
+            "relative" => Ok(Self::Relative),
+            pattern => {
+                if StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+                    return Err(SdError::Other(format!(
+                        "Invalid time format '{pattern}': expected 'default', 'relative', or a strftime pattern like '%d.%m.%Y %H:%M'"
+                    )));
+                }
+                Ok(Self::Custom(pattern.to_string()))
+            }
+        }
+    }
+}
+
+impl fmt::Display for TimeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::Relative => write!(f, "relative"),
+            Self::Custom(pattern) => write!(f, "{pattern}"),
+        }
+    }
+}
+
+/// Render a past timestamp as "N units ago".
+fn format_relative(ts: &DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let secs = (now - *ts).num_seconds();
+    if secs < 60 {
+        return "just now".to_string();
+    }
+
+    let (count, unit) = match secs {
+        s if s < 3_600 => (s / 60, "minute"),
+        s if s < 86_400 => (s / 3_600, "hour"),
+        s if s < 30 * 86_400 => (s / 86_400, "day"),
+        s if s < 365 * 86_400 => (s / (30 * 86_400), "month"),
+        s => (s / (365 * 86_400), "year"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{count} {unit}{plural} ago")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 6, 15, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse_time_format() {
+        assert_eq!(
+            "relative".parse::<TimeFormat>().unwrap(),
+            TimeFormat::Relative
+        );
+        assert_eq!(
+            "default".parse::<TimeFormat>().unwrap(),
+            TimeFormat::Default
+        );
+        assert_eq!(
+            "%d.%m.%Y".parse::<TimeFormat>().unwrap(),
+            TimeFormat::Custom("%d.%m.%Y".to_string())
+        );
+        assert!("%Q".parse::<TimeFormat>().is_err());
+    }
+
+    #[test]
+    fn test_relative_format() {
+        let fmt = TimeFormat::Relative;
+        let at = |d: Duration| fmt.format_at(&(now() - d), "", now());
+        assert_eq!(at(Duration::seconds(5)), "just now");
+        assert_eq!(at(Duration::minutes(1)), "1 minute ago");
+        assert_eq!(at(Duration::hours(2)), "2 hours ago");
+        assert_eq!(at(Duration::days(3)), "3 days ago");
+        assert_eq!(at(Duration::days(65)), "2 months ago");
+        assert_eq!(at(Duration::days(800)), "2 years ago");
+        // Clock skew: future timestamps are not reported as negative ages.
+        assert_eq!(at(Duration::minutes(-5)), "just now");
+    }
+
+    #[test]
+    fn test_default_and_custom_format() {
+        let ts = now();
+        assert_eq!(
+            TimeFormat::Default.format_at(&ts, "%Y-%m-%d %H:%M", now()),
+            "2025-06-15 12:00"
+        );
+        assert_eq!(
+            TimeFormat::Custom("%d.%m.%Y".to_string()).format_at(&ts, "%Y", now()),
+            "15.06.2025"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! Button event recordings for `watch --record` and `sd replay`.
+//!
+//! A recording is JSON Lines, one [`ButtonEvent`] per line, in the same
+//! shape `sd watch --robot` prints, so a captured robot stream replays too:
+//!
+//! ```text
+//! {"key":3,"pressed":true,"timestamp_ms":1520}
+//! {"key":3,"pressed":false,"timestamp_ms":1610}
+//! ```
+//!
+//! Keys are recorded in native numbering, whatever `--key-base` was in use.
+//! Timestamps count from the start of the recording, so they keep rising
+//! across `watch --reconnect` reconnects.
+//!
+//! `watch --sample-rate N` keeps only every Nth press, with its release, to
+//! keep long captures small. Timestamps stay real, so a sampled recording
+//! replays with long gaps where the dropped presses were: its timing shows
+//! when the kept presses happened, not how often keys were pressed.
+
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+use crate::device::ButtonEvent;
+use crate::error::{Result, SdError};
+
+/// Appends button events to a recording file as they happen.
+#[derive(Debug)]
+pub struct Recorder {
+    writer: BufWriter<File>,
+    started: Instant,
+    events: usize,
+    sample_rate: u32,
+    presses: u64,
+    /// Keys whose current press was kept, so their release is kept too.
+    held: BTreeSet<u8>,
+}
+
+impl Recorder {
+    /// Start a new recording at `path`, replacing any file already there.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be created.
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).map_err(|e| {
+            SdError::Other(format!(
+                "Failed to create recording {}: {e}",
+                path.display()
+            ))
+        })?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+            started: Instant::now(),
+            events: 0,
+            sample_rate: 1,
+            presses: 0,
+            held: BTreeSet::new(),
+        })
+    }
+
+    /// Keep only every `rate`th press and its release (`--sample-rate`).
+    ///
+    /// 1 (the default) and 0 keep everything.
+    #[must_use]
+    pub fn with_sample_rate(mut self, rate: u32) -> Self {
+        self.sample_rate = rate.max(1);
+        self
+    }
+
+    /// Write one event, stamped with the time since the recording started.
+    ///
+    /// Each line is flushed straight away so an interrupted watch keeps
+    /// everything up to the last press. Events that sampling drops are
+    /// skipped without error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written.
+    pub fn record(&mut self, event: &ButtonEvent) -> Result<()> {
+        if !self.sampled(event) {
+            return Ok(());
+        }
+        let event = ButtonEvent {
+            timestamp_ms: u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX),
+            ..event.clone()
+        };
+        let line = serde_json::to_string(&event)
+            .map_err(|e| SdError::Other(format!("Failed to serialize event: {e}")))?;
+        writeln!(self.writer, "{line}")?;
+        self.writer.flush()?;
+        self.events += 1;
+        Ok(())
+    }
+
+    /// Number of events recorded so far.
+    #[must_use]
+    pub const fn events(&self) -> usize {
+        self.events
+    }
+
+    /// Whether `event` survives sampling: the first of every `sample_rate`
+    /// presses, and the release of any kept press.
+    fn sampled(&mut self, event: &ButtonEvent) -> bool {
+        if !event.pressed {
+            return self.held.remove(&event.key);
+        }
+        let keep = self.presses % u64::from(self.sample_rate) == 0;
+        self.presses += 1;
+        if keep {
+            self.held.insert(event.key);
+        }
+        keep
+    }
+}
+
+/// Read every event from a recording.
+///
+/// Blank lines are skipped. Timestamps must not go backwards.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be read, a line isn't a button event,
+/// or the events are out of order.
+pub fn read_recording(path: &Path) -> Result<Vec<ButtonEvent>> {
+    let file = File::open(path)
+        .map_err(|e| SdError::Other(format!("Failed to open recording {}: {e}", path.display())))?;
+    let mut events: Vec<ButtonEvent> = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let number = index + 1;
+        let event: ButtonEvent = serde_json::from_str(&line).map_err(|e| {
+            SdError::Other(format!(
+                "{} line {number} is not a button event: {e}",
+                path.display()
+            ))
+        })?;
+        if let Some(previous) = events.last() {
+            if event.timestamp_ms < previous.timestamp_ms {
+                return Err(SdError::Other(format!(
+                    "{} line {number}: timestamp {} is earlier than the previous event ({})",
+                    path.display(),
+                    event.timestamp_ms,
+                    previous.timestamp_ms
+                )));
+            }
+        }
+        events.push(event);
+    }
+    debug!(path = %path.display(), events = events.len(), "Read recording");
+    Ok(events)
+}
+
+/// How long to wait between two recorded timestamps when replaying at
+/// `speed` (2.0 is twice as fast).
+#[must_use]
+pub fn replay_delay(previous_ms: u64, next_ms: u64, speed: f64) -> Duration {
+    let gap = Duration::from_millis(next_ms.saturating_sub(previous_ms));
+    gap.div_f64(speed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(key: u8, pressed: bool, timestamp_ms: u64) -> ButtonEvent {
+        ButtonEvent {
+            key,
+            pressed,
+            timestamp_ms,
+        }
+    }
+
+    #[test]
+    fn test_record_then_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        let mut recorder = Recorder::create(&path).unwrap();
+        recorder.record(&event(3, true, 500)).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        recorder.record(&event(3, false, 0)).unwrap();
+        assert_eq!(recorder.events(), 2);
+
+        let events = read_recording(&path).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].key, 3);
+        assert!(!events[1].pressed);
+        // Stamped by the recorder's clock, not the caller's
+        assert!(events[0].timestamp_ms < 500);
+        assert!(events[1].timestamp_ms >= events[0].timestamp_ms + 5);
+    }
+
+    #[test]
+    fn test_sample_rate_keeps_whole_presses() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sampled.jsonl");
+        let mut recorder = Recorder::create(&path).unwrap().with_sample_rate(3);
+        for key in 0..6 {
+            recorder.record(&event(key, true, 0)).unwrap();
+            recorder.record(&event(key, false, 0)).unwrap();
+        }
+        // A release with no recorded press is dropped too
+        recorder.record(&event(9, false, 0)).unwrap();
+
+        let keys: Vec<(u8, bool)> = read_recording(&path)
+            .unwrap()
+            .iter()
+            .map(|e| (e.key, e.pressed))
+            .collect();
+        assert_eq!(keys, [(0, true), (0, false), (3, true), (3, false)]);
+        assert_eq!(recorder.events(), 4);
+    }
+
+    #[test]
+    fn test_read_rejects_bad_recordings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        std::fs::write(
+            &path,
+            "{\"key\":1,\"pressed\":true,\"timestamp_ms\":50}\n\nnope\n",
+        )
+        .unwrap();
+        let err = read_recording(&path).unwrap_err().to_string();
+        assert!(err.contains("line 3"), "{err}");
+
+        std::fs::write(
+            &path,
+            "{\"key\":1,\"pressed\":true,\"timestamp_ms\":50}\n{\"key\":1,\"pressed\":false,\"timestamp_ms\":20}\n",
+        )
+        .unwrap();
+        assert!(read_recording(&path).is_err());
+    }
+
+    #[test]
+    fn test_replay_delay_scales_with_speed() {
+        assert_eq!(replay_delay(100, 300, 1.0), Duration::from_millis(200));
+        assert_eq!(replay_delay(100, 300, 4.0), Duration::from_millis(50));
+        assert_eq!(replay_delay(300, 300, 2.0), Duration::ZERO);
+    }
+}
@@ -0,0 +1,242 @@
+//! Run reports for `--report <file>`.
+//!
+//! A report is metadata about one invocation: what ran, against which device,
+//! how long it took, which keys changed and how it ended. It is written as
+//! JSON regardless of output mode, separately from the command's own output.
+
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::debug;
+
+use crate::device::WriteVerification;
+use crate::error::{Result, SdError};
+use crate::state::{self, SessionState};
+
+/// Flags whose values are secrets, kept out of the recorded arguments.
+const SECRET_FLAGS: &[&str] = &["--token"];
+
+/// Placeholder recorded in place of a secret flag's value.
+const REDACTED: &str = "<redacted>";
+
+/// Serial of the device the current run opened, if any.
+static DEVICE_SERIAL: LazyLock<Mutex<Option<String>>> = LazyLock::new(|| Mutex::new(None));
+
+/// `--verify-writes` stats for the devices the current run wrote to.
+static WRITE_VERIFICATION: LazyLock<Mutex<Option<WriteVerification>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+/// Record the serial of the device the current run is talking to.
+pub fn record_device(serial: &str) {
+    debug!(serial, "Recording device for run report");
+    *DEVICE_SERIAL.lock().expect("report lock poisoned") = Some(serial.to_string());
+}
+
+/// Add a device's `--verify-writes` stats to the current run's.
+pub fn record_write_verification(stats: WriteVerification) {
+    debug!(?stats, "Recording write verification for run report");
+    let mut total = WRITE_VERIFICATION.lock().expect("report lock poisoned");
+    let total = total.get_or_insert_with(WriteVerification::default);
+    total.supported = stats.supported;
+    total.writes += stats.writes;
+    total.verified += stats.verified;
+    total.mismatched += stats.mismatched;
+    total.unverified += stats.unverified;
+}
+
+/// `--verify-writes` stats recorded so far in the current run.
+pub fn write_verification() -> Option<WriteVerification> {
+    *WRITE_VERIFICATION.lock().expect("report lock poisoned")
+}
+
+/// Summary of a single CLI invocation.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    /// Subcommand name (`None` for the bare quick-start).
+    pub command: Option<String>,
+    /// Arguments, excluding the program name, with secret values redacted.
+    pub args: Vec<String>,
+    /// Whether `--dry-run` was set.
+    pub dry_run: bool,
+    /// Serial of the device the command opened, if any.
+    pub device_serial: Option<String>,
+    /// When the run started.
+    pub started_at: DateTime<Utc>,
+    /// When the run finished.
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Wall-clock duration in milliseconds.
+    pub duration_ms: Option<u64>,
+    /// Whether the command succeeded.
+    pub ok: bool,
+    /// Device changes made during the run.
+    pub results: SessionState,
+    /// How `--verify-writes` went, when it was asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_verification: Option<WriteVerification>,
+    /// Failure details, if the command failed.
+    pub error: Option<ReportError>,
+    #[serde(skip)]
+    started: Instant,
+}
+
+/// Error details included in a failed run's report.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportError {
+    /// Error message.
+    pub message: String,
+    /// Suggested fix, when one is known.
+    pub suggestion: Option<&'static str>,
+    /// Whether the user can fix this (bad input, missing device, ...).
+    pub recoverable: bool,
+}
+
+impl From<&SdError> for ReportError {
+    fn from(err: &SdError) -> Self {
+        Self {
+            message: err.to_string(),
+            suggestion: err.suggestion(),
+            recoverable: err.is_user_recoverable(),
+        }
+    }
+}
+
+impl RunReport {
+    /// Start a report for the given subcommand and arguments.
+    ///
+    /// Values of [`SECRET_FLAGS`] are replaced before they are stored.
+    #[must_use]
+    pub fn start(command: Option<&str>, args: Vec<String>, dry_run: bool) -> Self {
+        Self {
+            command: command.map(str::to_string),
+            args: redact_secrets(args),
+            dry_run,
+            device_serial: None,
+            started_at: Utc::now(),
+            finished_at: None,
+            duration_ms: None,
+            ok: false,
+            results: SessionState::new(),
+            write_verification: None,
+            error: None,
+            started: Instant::now(),
+        }
+    }
+
+    /// Complete the report with the run's outcome, timings, device and changes.
+    #[must_use]
+    pub fn finish(mut self, result: &Result<()>) -> Self {
+        self.finished_at = Some(Utc::now());
+        self.duration_ms =
+            Some(u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX));
+        self.ok = result.is_ok();
+        self.error = result.as_ref().err().map(ReportError::from);
+        self.device_serial = DEVICE_SERIAL.lock().expect("report lock poisoned").clone();
+        self.results = state::session_state().clone();
+        self.write_verification = write_verification();
+        self
+    }
+
+    /// Write the report as pretty JSON to `path`.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| SdError::Other(format!("Failed to serialize run report: {e}")))?;
+        std::fs::write(path, json + "\n")?;
+        debug!(path = %path.display(), "Wrote run report");
+        Ok(())
+    }
+}
+
+/// Replace the values of secret flags, given as `--flag value` or
+/// `--flag=value`.
+fn redact_secrets(mut args: Vec<String>) -> Vec<String> {
+    let mut redact_next = false;
+    for arg in &mut args {
+        if std::mem::take(&mut redact_next) {
+            *arg = REDACTED.to_string();
+        } else if SECRET_FLAGS.contains(&arg.as_str()) {
+            redact_next = true;
+        } else if let Some((flag, _)) = arg
+            .split_once('=')
+            .filter(|(flag, _)| SECRET_FLAGS.contains(flag))
+        {
+            *arg = format!("{flag}={REDACTED}");
+        }
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finish_records_failure() {
+        let report = RunReport::start(Some("set-key"), vec!["set-key".into(), "99".into()], false)
+            .finish(&Err(SdError::InvalidKeyIndex {
+                index: 99,
+                max: 32,
+                max_idx: 31,
+            }));
+
+        assert!(!report.ok);
+        assert!(report.finished_at.is_some());
+        assert!(report.duration_ms.is_some());
+        let error = report.error.expect("error recorded");
+        assert!(error.message.contains("99"));
+        assert!(error.recoverable);
+    }
+
+    #[test]
+    fn test_start_redacts_secret_flags() {
+        let args = [
+            "serve",
+            "--token",
+            "s3cret",
+            "--port",
+            "9000",
+            "--token=hunter2",
+        ];
+        let report = RunReport::start(
+            Some("serve"),
+            args.iter().map(ToString::to_string).collect(),
+            false,
+        );
+
+        assert_eq!(
+            report.args,
+            [
+                "serve",
+                "--token",
+                REDACTED,
+                "--port",
+                "9000",
+                "--token=<redacted>"
+            ]
+        );
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(!json.contains("s3cret") && !json.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_write_report_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.json");
+
+        RunReport::start(Some("list"), vec!["list".into()], true)
+            .finish(&Ok(()))
+            .write(&path)
+            .unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["command"], "list");
+        assert_eq!(json["ok"], true);
+        assert_eq!(json["dry_run"], true);
+        assert!(json["error"].is_null());
+        assert!(json["results"]["keys"].is_object());
+        assert!(json.get("started").is_none());
+    }
+}
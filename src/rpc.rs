@@ -0,0 +1,416 @@
+//! JSON-RPC 2.0 protocol for `sd serve`.
+//!
+//! The method is a command name (`brightness`, `fill-key`, ...) and the params
+//! are that command's arguments as a JSON object. This module handles the
+//! protocol itself: parsing, batches, notifications and error objects. The
+//! mapping from methods to device operations lives with the commands.
+//!
+//! ```text
+//! --> {"jsonrpc": "2.0", "method": "fill-key", "params": {"key": 0, "color": "red"}, "id": 1}
+//! <-- {"jsonrpc": "2.0", "result": {"key": 0, "color": "#ff0000"}, "id": 1}
+//! ```
+//!
+//! Clients should start with `hello`, which returns a [`Hello`]: the
+//! [`PROTOCOL_VERSION`], the methods and notifications the server supports,
+//! and the connected device. A client built for another protocol version
+//! should stop there rather than guess.
+//!
+//! A server started with `--token` answers nothing until the client sends
+//! `auth` with that token; other requests fail with [`UNAUTHORIZED`]:
+//!
+//! ```text
+//! --> {"jsonrpc": "2.0", "method": "auth", "params": {"token": "s3cret"}, "id": 0}
+//! <-- {"jsonrpc": "2.0", "result": {"authenticated": true}, "id": 0}
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, trace};
+
+use crate::device::DeviceInfo;
+use crate::error::SdError;
+
+/// Version of the `sd serve` protocol.
+///
+/// Bumped when a method's params or results change incompatibly; adding
+/// methods or result fields doesn't bump it.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Invalid JSON was received.
+pub const PARSE_ERROR: i64 = -32700;
+/// The JSON sent is not a valid request object.
+pub const INVALID_REQUEST: i64 = -32600;
+/// The method does not exist.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// Invalid method parameters.
+pub const INVALID_PARAMS: i64 = -32602;
+/// Internal JSON-RPC error.
+pub const INTERNAL_ERROR: i64 = -32603;
+/// A command ran and failed; `data.code` holds the [`SdError::code`].
+pub const COMMAND_ERROR: i64 = -32000;
+/// The server requires `auth` with its token first.
+pub const UNAUTHORIZED: i64 = -32001;
+
+/// A JSON-RPC request. Requests without an `id` are notifications.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcRequest {
+    /// Must be `"2.0"`.
+    pub jsonrpc: String,
+    /// Command name.
+    pub method: String,
+    /// Command arguments; omitted params are `null`.
+    #[serde(default)]
+    pub params: Value,
+    /// Request id, echoed in the response.
+    #[serde(default, deserialize_with = "deserialize_id")]
+    pub id: Option<Value>,
+}
+
+impl RpcRequest {
+    /// Deserialize `params` into a command's argument struct.
+    ///
+    /// Missing params are treated as an empty object.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `INVALID_PARAMS` error if the params don't match `T`.
+    pub fn params<T: serde::de::DeserializeOwned>(&self) -> Result<T, RpcError> {
+        let params = if self.params.is_null() {
+            Value::Object(serde_json::Map::new())
+        } else {
+            self.params.clone()
+        };
+        serde_json::from_value(params)
+            .map_err(|e| RpcError::new(INVALID_PARAMS, format!("Invalid params: {e}")))
+    }
+}
+
+/// Keep an explicit `"id": null` distinct from a missing id (a notification).
+fn deserialize_id<'de, D>(deserializer: D) -> Result<Option<Value>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Value::deserialize(deserializer).map(Some)
+}
+
+/// A JSON-RPC error object.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RpcError {
+    /// Protocol or application error code.
+    pub code: i64,
+    /// Human-readable message.
+    pub message: String,
+    /// Extra detail; for command errors the `sd` error code and suggestion.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    /// An error with no extra data.
+    #[must_use]
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    /// The method isn't one the server knows.
+    #[must_use]
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(METHOD_NOT_FOUND, format!("Method not found: {method}"))
+    }
+
+    /// The client hasn't authenticated, or sent the wrong token.
+    #[must_use]
+    pub fn unauthorized() -> Self {
+        Self::new(
+            UNAUTHORIZED,
+            "Unauthorized: send auth with the server's --token first",
+        )
+    }
+}
+
+/// Compare a client's token with the server's without leaking, through
+/// timing, how much of it was right. An empty token never matches.
+#[must_use]
+pub fn token_matches(expected: &str, given: &str) -> bool {
+    let (expected, given) = (expected.as_bytes(), given.as_bytes());
+    let mut diff = expected.len() ^ given.len();
+    for (i, byte) in expected.iter().enumerate() {
+        diff |= usize::from(byte ^ given.get(i).copied().unwrap_or(0));
+    }
+    diff == 0 && !expected.is_empty()
+}
+
+impl From<SdError> for RpcError {
+    fn from(err: SdError) -> Self {
+        Self {
+            code: COMMAND_ERROR,
+            message: err.to_string(),
+            data: Some(serde_json::json!({
+                "code": err.code(),
+                "suggestion": err.suggestion(),
+                "recoverable": err.is_user_recoverable(),
+            })),
+        }
+    }
+}
+
+/// A JSON-RPC response: exactly one of `result` or `error` is set.
+#[derive(Debug, Clone, Serialize)]
+pub struct RpcResponse {
+    /// Always `"2.0"`.
+    pub jsonrpc: &'static str,
+    /// Method result on success.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// Error object on failure.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+    /// Request id, or `null` if it couldn't be determined.
+    pub id: Value,
+}
+
+impl RpcResponse {
+    /// Build a response from a method's outcome.
+    #[must_use]
+    pub fn new(id: Value, outcome: Result<Value, RpcError>) -> Self {
+        let (result, error) = match outcome {
+            Ok(value) => (Some(value), None),
+            Err(e) => (None, Some(e)),
+        };
+        Self {
+            jsonrpc: "2.0",
+            result,
+            error,
+            id,
+        }
+    }
+}
+
+/// A server-initiated JSON-RPC notification (no id, no response expected).
+#[derive(Debug, Clone, Serialize)]
+pub struct RpcNotification {
+    /// Always `"2.0"`.
+    pub jsonrpc: &'static str,
+    /// Event name.
+    pub method: &'static str,
+    /// Event details.
+    pub params: Value,
+}
+
+impl RpcNotification {
+    /// A notification of `method` with `params`.
+    #[must_use]
+    pub const fn new(method: &'static str, params: Value) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            method,
+            params,
+        }
+    }
+}
+
+/// Capabilities handshake returned by the `hello` method.
+#[derive(Debug, Clone, Serialize)]
+pub struct Hello {
+    /// Always [`PROTOCOL_VERSION`].
+    pub protocol_version: u32,
+    /// Version of the `sd` binary serving.
+    pub version: &'static str,
+    /// Methods the server answers.
+    pub methods: &'static [&'static str],
+    /// Notifications the server may send unprompted.
+    pub notifications: &'static [&'static str],
+    /// The device requests will go to, or `None` if none is connected.
+    pub device: Option<DeviceInfo>,
+}
+
+impl Hello {
+    /// A handshake for a server answering `methods`.
+    #[must_use]
+    pub const fn new(
+        methods: &'static [&'static str],
+        notifications: &'static [&'static str],
+        device: Option<DeviceInfo>,
+    ) -> Self {
+        Self {
+            protocol_version: PROTOCOL_VERSION,
+            version: env!("CARGO_PKG_VERSION"),
+            methods,
+            notifications,
+            device,
+        }
+    }
+}
+
+/// Handle one incoming message (a request or a batch).
+///
+/// `dispatch` runs a single request. Returns the serialized response, or
+/// `None` when nothing should be sent back (only notifications).
+pub fn handle_message<F>(text: &str, mut dispatch: F) -> Option<String>
+where
+    F: FnMut(&RpcRequest) -> Result<Value, RpcError>,
+{
+    let response = match serde_json::from_str::<Value>(text) {
+        Err(e) => Some(serde_json::json!(RpcResponse::new(
+            Value::Null,
+            Err(RpcError::new(PARSE_ERROR, format!("Parse error: {e}"))),
+        ))),
+        Ok(Value::Array(items)) if items.is_empty() => Some(serde_json::json!(RpcResponse::new(
+            Value::Null,
+            Err(RpcError::new(INVALID_REQUEST, "Empty batch")),
+        ))),
+        Ok(Value::Array(items)) => {
+            debug!(count = items.len(), "RPC batch");
+            let responses: Vec<RpcResponse> = items
+                .into_iter()
+                .filter_map(|item| handle_value(item, &mut dispatch))
+                .collect();
+            // A batch of notifications gets no response at all
+            (!responses.is_empty()).then(|| serde_json::json!(responses))
+        }
+        Ok(value) => handle_value(value, &mut dispatch).map(|r| serde_json::json!(r)),
+    };
+    response.map(|r| r.to_string())
+}
+
+fn handle_value<F>(value: Value, dispatch: &mut F) -> Option<RpcResponse>
+where
+    F: FnMut(&RpcRequest) -> Result<Value, RpcError>,
+{
+    let request = match serde_json::from_value::<RpcRequest>(value) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        Ok(request) => {
+            return Some(RpcResponse::new(
+                request.id.unwrap_or(Value::Null),
+                Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\"")),
+            ));
+        }
+        Err(e) => {
+            return Some(RpcResponse::new(
+                Value::Null,
+                Err(RpcError::new(
+                    INVALID_REQUEST,
+                    format!("Invalid request: {e}"),
+                )),
+            ));
+        }
+    };
+
+    trace!(method = %request.method, id = ?request.id, "RPC request");
+    let outcome = dispatch(&request);
+    request.id.map(|id| RpcResponse::new(id, outcome))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn echo(request: &RpcRequest) -> Result<Value, RpcError> {
+        match request.method.as_str() {
+            "echo" => Ok(request.params.clone()),
+            "fail" => Err(SdError::NoDevicesFound.into()),
+            other => Err(RpcError::method_not_found(other)),
+        }
+    }
+
+    fn call(text: &str) -> Value {
+        serde_json::from_str(&handle_message(text, echo).expect("response")).unwrap()
+    }
+
+    #[test]
+    fn test_single_request() {
+        let resp = call(r#"{"jsonrpc": "2.0", "method": "echo", "params": {"a": 1}, "id": 7}"#);
+        assert_eq!(resp["result"]["a"], 1);
+        assert_eq!(resp["id"], 7);
+        assert!(resp.get("error").is_none());
+    }
+
+    #[test]
+    fn test_errors() {
+        let resp = call(r#"{"jsonrpc": "2.0", "method": "nope", "id": "x"}"#);
+        assert_eq!(resp["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(resp["id"], "x");
+
+        let resp = call(r#"{"jsonrpc": "2.0", "method": "fail", "id": 1}"#);
+        assert_eq!(resp["error"]["code"], COMMAND_ERROR);
+        assert_eq!(resp["error"]["data"]["code"], "no_devices_found");
+
+        assert_eq!(call("{not json")["error"]["code"], PARSE_ERROR);
+        assert_eq!(call("[]")["error"]["code"], INVALID_REQUEST);
+        assert_eq!(
+            call(r#"{"jsonrpc": "1.0", "method": "echo", "id": 1}"#)["error"]["code"],
+            INVALID_REQUEST
+        );
+    }
+
+    #[test]
+    fn test_batch_and_notifications() {
+        let resp = call(
+            r#"[
+                {"jsonrpc": "2.0", "method": "echo", "params": [1], "id": 1},
+                {"jsonrpc": "2.0", "method": "echo", "params": [2]},
+                {"foo": "bar"},
+                {"jsonrpc": "2.0", "method": "nope", "id": null}
+            ]"#,
+        );
+        let items = resp.as_array().unwrap();
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0]["result"], serde_json::json!([1]));
+        assert_eq!(items[1]["error"]["code"], INVALID_REQUEST);
+        assert_eq!(items[2]["error"]["code"], METHOD_NOT_FOUND);
+        assert!(items[2]["id"].is_null());
+
+        assert!(handle_message(r#"{"jsonrpc": "2.0", "method": "echo"}"#, echo).is_none());
+    }
+
+    #[test]
+    fn test_notification_shape() {
+        let note = RpcNotification::new("context", serde_json::json!({"name": "firefox"}));
+        let value = serde_json::to_value(note).unwrap();
+        assert_eq!(value["jsonrpc"], "2.0");
+        assert_eq!(value["params"]["name"], "firefox");
+        assert!(value.get("id").is_none());
+    }
+
+    #[test]
+    fn test_token_matches() {
+        assert!(token_matches("s3cret", "s3cret"));
+        for wrong in ["", "s3cre", "s3cret!", "S3cret", "x3cret"] {
+            assert!(!token_matches("s3cret", wrong), "{wrong}");
+        }
+        assert!(!token_matches("", ""));
+        assert_eq!(RpcError::unauthorized().code, UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_hello_shape() {
+        let hello = Hello::new(&["hello", "version"], &["context"], None);
+        let value = serde_json::to_value(hello).unwrap();
+        assert_eq!(value["protocol_version"], PROTOCOL_VERSION);
+        assert_eq!(value["methods"], serde_json::json!(["hello", "version"]));
+        assert_eq!(value["notifications"], serde_json::json!(["context"]));
+        assert!(value["device"].is_null());
+    }
+
+    #[test]
+    fn test_params_default_to_empty_object() {
+        #[derive(Deserialize)]
+        struct Args {
+            #[serde(default)]
+            level: u8,
+        }
+        let request: RpcRequest =
+            serde_json::from_str(r#"{"jsonrpc": "2.0", "method": "x", "id": 1}"#).unwrap();
+        assert_eq!(request.params::<Args>().unwrap().level, 0);
+
+        let request: RpcRequest = serde_json::from_str(
+            r#"{"jsonrpc": "2.0", "method": "x", "params": {"level": "hi"}, "id": 1}"#,
+        )
+        .unwrap();
+        assert_eq!(request.params::<Args>().unwrap_err().code, INVALID_PARAMS);
+    }
+}
@@ -0,0 +1,263 @@
+//! HTTP side of `sd serve`.
+//!
+//! ```text
+//! GET /events    WebSocket of button and connection events, as `watch --robot` prints them
+//! GET /healthz   with --metrics, see crate::metrics
+//! GET /metrics   with --metrics
+//! ```
+//!
+//! Events come from the server's single watch loop through an [`EventHub`],
+//! so any number of clients share one read of the device. Each WebSocket
+//! text message is one JSON event:
+//!
+//! ```text
+//! {"key":3,"pressed":true,"timestamp_ms":1520}
+//! {"event":"disconnected","reason":"No Stream Deck devices found","reconnecting":true}
+//! ```
+//!
+//! With `--token`, every route requires `Authorization: Bearer <token>` and
+//! answers 401 without it. Browsers can't set headers on a WebSocket, so an
+//! upgrade may send the token as `?access_token=<token>` instead.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::Router;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tracing::{debug, trace, warn};
+
+use crate::error::{Result, SdError};
+use crate::rpc::token_matches;
+
+/// Events buffered per client before a slow one starts missing them.
+const EVENT_BUFFER: usize = 256;
+
+/// Fans events from the watch loop out to every connected client.
+#[derive(Debug)]
+pub struct EventHub {
+    sender: broadcast::Sender<String>,
+}
+
+impl EventHub {
+    /// A hub with no clients yet.
+    #[must_use]
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        Self { sender }
+    }
+
+    /// Send `event` to every client as one JSON message.
+    pub fn publish<T: Serialize>(&self, event: &T) {
+        match serde_json::to_string(event) {
+            // Fails only when nobody is listening, which is fine
+            Ok(json) => {
+                trace!(%json, "Publishing event");
+                let _ = self.sender.send(json);
+            }
+            Err(e) => warn!(error = %e, "Failed to serialize event"),
+        }
+    }
+
+    /// Receive every event published from now on.
+    #[must_use]
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.sender.subscribe()
+    }
+
+    /// Number of clients listening; the watch loop idles at zero.
+    #[must_use]
+    pub fn subscribers(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+impl Default for EventHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The `/events` WebSocket route.
+pub fn events_router(hub: Arc<EventHub>) -> Router {
+    Router::new()
+        .route("/events", get(events_socket))
+        .with_state(hub)
+}
+
+async fn events_socket(ws: WebSocketUpgrade, State(hub): State<Arc<EventHub>>) -> Response {
+    let events = hub.subscribe();
+    ws.on_upgrade(move |socket| forward_events(socket, events))
+}
+
+async fn forward_events(mut socket: WebSocket, mut events: broadcast::Receiver<String>) {
+    debug!("Event client connected");
+    loop {
+        match events.recv().await {
+            Ok(json) => {
+                if socket.send(Message::Text(json.into())).await.is_err() {
+                    break;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!(skipped, "Event client fell behind; dropped events");
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+    debug!("Event client disconnected");
+}
+
+/// Require `token` as a bearer token on every route of `router`.
+pub fn with_token(router: Router, token: Option<Arc<str>>) -> Router {
+    match token {
+        Some(token) => router.route_layer(middleware::from_fn_with_state(token, require_token)),
+        None => router,
+    }
+}
+
+async fn require_token(State(token): State<Arc<str>>, request: Request, next: Next) -> Response {
+    let given = given_token(&request).unwrap_or_default();
+    if token_matches(&token, given.trim()) {
+        return next.run(request).await;
+    }
+    debug!(path = %request.uri().path(), "Rejected request without a valid token");
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        "Missing or invalid token\n",
+    )
+        .into_response()
+}
+
+/// The token a request sent: its bearer token, or for a WebSocket upgrade
+/// the `access_token` query parameter.
+fn given_token(request: &Request) -> Option<String> {
+    let headers = request.headers();
+    if let Some(bearer) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        return Some(bearer.to_string());
+    }
+    let upgrade = headers
+        .get(header::UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+    if !upgrade {
+        return None;
+    }
+    Query::<HashMap<String, String>>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|Query(mut params)| params.remove("access_token"))
+}
+
+/// Bind the listener, so a busy port is reported before serving.
+///
+/// # Errors
+///
+/// Returns [`SdError::WebServerFailed`] if the address can't be bound.
+pub fn bind(addr: &str) -> Result<std::net::TcpListener> {
+    std::net::TcpListener::bind(addr).map_err(|e| SdError::WebServerFailed {
+        addr: addr.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+/// Serve `router` on `listener` until the process exits.
+///
+/// # Errors
+///
+/// Returns [`SdError::WebServerFailed`] if the server can't start or stops
+/// with an error.
+pub fn serve(listener: std::net::TcpListener, router: Router) -> Result<()> {
+    let addr = listener
+        .local_addr()
+        .map(|addr| addr.to_string())
+        .unwrap_or_default();
+    let failed = |reason: String| SdError::WebServerFailed {
+        addr: addr.clone(),
+        reason,
+    };
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| failed(e.to_string()))?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| failed(e.to_string()))?;
+    debug!(%addr, "Serving HTTP");
+    runtime.block_on(async {
+        let listener =
+            tokio::net::TcpListener::from_std(listener).map_err(|e| failed(e.to_string()))?;
+        axum::serve(listener, router)
+            .await
+            .map_err(|e| failed(e.to_string()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hub_reaches_every_subscriber() {
+        let hub = EventHub::new();
+        // Publishing with nobody listening is not an error
+        hub.publish(&serde_json::json!({"key": 0}));
+
+        let mut first = hub.subscribe();
+        let mut second = hub.subscribe();
+        assert_eq!(hub.subscribers(), 2);
+        hub.publish(&serde_json::json!({"key": 3, "pressed": true}));
+        for receiver in [&mut first, &mut second] {
+            assert_eq!(receiver.try_recv().unwrap(), r#"{"key":3,"pressed":true}"#);
+            assert!(receiver.try_recv().is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_token_from_header_or_websocket_query() {
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let router = with_token(
+            Router::new().route("/events", get(|| async { "ok" })),
+            Some(Arc::from("s3cret")),
+        );
+        let status = |request: axum::http::request::Builder| {
+            let router = router.clone();
+            async move {
+                let request = request.body(Body::empty()).unwrap();
+                router.oneshot(request).await.unwrap().status()
+            }
+        };
+        let get = |uri: &str| Request::builder().uri(uri);
+
+        assert_eq!(status(get("/events")).await, StatusCode::UNAUTHORIZED);
+        let bearer = get("/events").header(header::AUTHORIZATION, "Bearer s3cret");
+        assert_eq!(status(bearer).await, StatusCode::OK);
+        let wrong = get("/events").header(header::AUTHORIZATION, "Bearer guess");
+        assert_eq!(status(wrong).await, StatusCode::UNAUTHORIZED);
+
+        // The query parameter is only for WebSocket upgrades
+        let upgrade = get("/events?access_token=s3cret").header(header::UPGRADE, "websocket");
+        assert_eq!(status(upgrade).await, StatusCode::OK);
+        let plain = get("/events?access_token=s3cret");
+        assert_eq!(status(plain).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_bind_reports_busy_port() {
+        let listener = bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        assert!(matches!(bind(&addr), Err(SdError::WebServerFailed { .. })));
+    }
+}
@@ -0,0 +1,236 @@
+//! Portable snapshot archives for `sd snapshot export` and `import`.
+//!
+//! An archive is a zip file holding the snapshot and its cached images, so
+//! a layout can move to another machine without the original image files:
+//!
+//! ```text
+//! manifest.json      {"format_version":1,"compression":"zstd","reencoded":false,"snapshot":{...}}
+//! images/<hash>      one entry per distinct image, named by content hash
+//! ```
+//!
+//! The manifest records the codec the entries were written with, so import
+//! reports it without guessing. Deflate (the default) opens with any zip
+//! tool; zstd packs image-heavy snapshots smaller but needs a recent reader;
+//! `none` stores entries as-is.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, Write};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use super::schema::Snapshot;
+use crate::error::{Result, SdError};
+
+/// Archive layout version written to the manifest.
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const IMAGE_PREFIX: &str = "images/";
+
+/// How archive entries are compressed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveCompression {
+    /// Store entries uncompressed.
+    None,
+    /// Deflate: readable by every zip tool.
+    #[default]
+    Deflate,
+    /// Zstandard: smaller, but needs a recent zip reader.
+    Zstd,
+}
+
+impl ArchiveCompression {
+    const fn method(self) -> CompressionMethod {
+        match self {
+            Self::None => CompressionMethod::Stored,
+            Self::Deflate => CompressionMethod::Deflated,
+            Self::Zstd => CompressionMethod::Zstd,
+        }
+    }
+}
+
+/// The `manifest.json` entry of an archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    /// Archive layout version.
+    pub format_version: u32,
+    /// Codec the entries were written with.
+    pub compression: ArchiveCompression,
+    /// True if images were re-encoded as PNG rather than copied from the cache.
+    pub reencoded: bool,
+    /// The snapshot itself.
+    pub snapshot: Snapshot,
+}
+
+/// A snapshot with the bytes of every image it shows.
+#[derive(Debug, Clone)]
+pub struct SnapshotArchive {
+    /// Manifest, including the snapshot.
+    pub manifest: ArchiveManifest,
+    /// Image bytes by content hash.
+    pub images: BTreeMap<String, Vec<u8>>,
+}
+
+impl SnapshotArchive {
+    /// Write the archive, compressing every entry with the manifest's codec.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    #[instrument(skip_all, fields(name = %self.manifest.snapshot.name))]
+    pub fn write<W: Write + Seek>(&self, writer: W) -> Result<W> {
+        let failed = |e: &dyn std::fmt::Display| {
+            SdError::Other(format!("Failed to write snapshot archive: {e}"))
+        };
+        let options =
+            SimpleFileOptions::default().compression_method(self.manifest.compression.method());
+        let mut zip = ZipWriter::new(writer);
+
+        let manifest = serde_json::to_vec_pretty(&self.manifest).map_err(|e| failed(&e))?;
+        zip.start_file(MANIFEST_ENTRY, options)
+            .map_err(|e| failed(&e))?;
+        zip.write_all(&manifest).map_err(|e| failed(&e))?;
+
+        for (hash, bytes) in &self.images {
+            zip.start_file(format!("{IMAGE_PREFIX}{hash}"), options)
+                .map_err(|e| failed(&e))?;
+            zip.write_all(bytes).map_err(|e| failed(&e))?;
+        }
+
+        debug!(
+            images = self.images.len(),
+            compression = ?self.manifest.compression,
+            "Snapshot archive written"
+        );
+        zip.finish().map_err(|e| failed(&e))
+    }
+
+    /// Read an archive written by [`SnapshotArchive::write`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file isn't a snapshot archive, was written by
+    /// a newer version, or uses a codec this build can't read.
+    #[instrument(skip_all)]
+    pub fn read<R: Read + Seek>(reader: R) -> Result<Self> {
+        let invalid =
+            |e: &dyn std::fmt::Display| SdError::Other(format!("Invalid snapshot archive: {e}"));
+        let mut zip = ZipArchive::new(reader).map_err(|e| invalid(&e))?;
+
+        let manifest: ArchiveManifest = {
+            let entry = zip.by_name(MANIFEST_ENTRY).map_err(|e| invalid(&e))?;
+            serde_json::from_reader(entry).map_err(|e| invalid(&e))?
+        };
+        if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+            return Err(SdError::Other(format!(
+                "Snapshot archive format {} is newer than this sd supports ({ARCHIVE_FORMAT_VERSION}); \
+                 upgrade sd to import it",
+                manifest.format_version
+            )));
+        }
+        debug!(
+            name = %manifest.snapshot.name,
+            compression = ?manifest.compression,
+            "Reading snapshot archive"
+        );
+
+        let mut images = BTreeMap::new();
+        for index in 0..zip.len() {
+            let mut entry = zip.by_index(index).map_err(|e| invalid(&e))?;
+            let Some(hash) = entry.name().strip_prefix(IMAGE_PREFIX).map(str::to_string) else {
+                continue;
+            };
+            // The hash becomes a cache path on import, so it must be a plain hash
+            if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(invalid(&format!("unexpected entry '{}'", entry.name())));
+            }
+            if entry.compression() != manifest.compression.method() {
+                debug!(
+                    entry = %entry.name(),
+                    method = ?entry.compression(),
+                    "Entry compression differs from manifest"
+                );
+            }
+            let mut bytes = Vec::new();
+            entry
+                .read_to_end(&mut bytes)
+                .map_err(|e| invalid(&format!("{hash}: {e}")))?;
+            images.insert(hash, bytes);
+        }
+
+        Ok(Self { manifest, images })
+    }
+}
+
+/// Re-encode image bytes as PNG (`snapshot export --reencode`).
+///
+/// # Errors
+///
+/// Returns an error if the bytes aren't a decodable image.
+pub fn reencode_png(bytes: &[u8]) -> Result<Vec<u8>> {
+    let img = image::load_from_memory(bytes)
+        .map_err(|e| SdError::ImageProcessing(format!("Failed to decode cached image: {e}")))?;
+    let mut png = std::io::Cursor::new(Vec::new());
+    img.write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|e| SdError::ImageProcessing(format!("Failed to encode PNG: {e}")))?;
+    Ok(png.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::snapshot::SnapshotKey;
+
+    fn archive(compression: ArchiveCompression) -> SnapshotArchive {
+        let mut snapshot = Snapshot::new("work".to_string(), "Mk2".to_string(), 15, 72, 72);
+        snapshot.add_key(SnapshotKey::image(0, None, "abc".to_string()));
+        SnapshotArchive {
+            manifest: ArchiveManifest {
+                format_version: ARCHIVE_FORMAT_VERSION,
+                compression,
+                reencoded: false,
+                snapshot,
+            },
+            images: BTreeMap::from([("abc".to_string(), vec![7; 4096])]),
+        }
+    }
+
+    #[test]
+    fn test_round_trip_each_compression() {
+        let mut sizes = BTreeMap::new();
+        for compression in [
+            ArchiveCompression::None,
+            ArchiveCompression::Deflate,
+            ArchiveCompression::Zstd,
+        ] {
+            let bytes = archive(compression)
+                .write(Cursor::new(Vec::new()))
+                .unwrap()
+                .into_inner();
+            sizes.insert(format!("{compression:?}"), bytes.len());
+
+            let read = SnapshotArchive::read(Cursor::new(bytes)).unwrap();
+            assert_eq!(read.manifest.compression, compression);
+            assert_eq!(read.manifest.snapshot.name, "work");
+            assert_eq!(read.images["abc"], vec![7; 4096]);
+        }
+        assert!(sizes["Deflate"] < sizes["None"], "{sizes:?}");
+        assert!(sizes["Zstd"] < sizes["None"], "{sizes:?}");
+    }
+
+    #[test]
+    fn test_rejects_newer_format() {
+        let mut newer = archive(ArchiveCompression::Deflate);
+        newer.manifest.format_version = ARCHIVE_FORMAT_VERSION + 1;
+        let bytes = newer.write(Cursor::new(Vec::new())).unwrap().into_inner();
+        let err = SnapshotArchive::read(Cursor::new(bytes)).unwrap_err();
+        assert!(err.to_string().contains("newer"), "{err}");
+    }
+}
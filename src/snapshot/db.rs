@@ -4,12 +4,13 @@
 //! content-addressable image caching.
 
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use chrono::{DateTime, Utc};
-use rusqlite::{Connection, params};
-use tracing::{debug, info, instrument, trace};
+use rusqlite::{Connection, ErrorCode, OptionalExtension, TransactionBehavior, params};
+use tracing::{debug, info, instrument, trace, warn};
 
-use super::schema::{CachedImage, KeyState, Snapshot, SnapshotKey, SnapshotSummary};
+use super::schema::{CachedImage, KeyState, Snapshot, SnapshotEdit, SnapshotKey, SnapshotSummary};
 use crate::error::{Result, SdError};
 
 /// SQLite schema for snapshot storage.
@@ -62,7 +63,21 @@ CREATE INDEX IF NOT EXISTS idx_snapshot_keys_hash ON snapshot_keys(image_hash);
 CREATE INDEX IF NOT EXISTS idx_images_accessed ON images(last_accessed_at);
 "#;
 
+/// How long `SQLite` waits on a locked database before returning `SQLITE_BUSY`.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum attempts for a write that keeps failing with `SQLITE_BUSY`.
+const MAX_BUSY_RETRIES: u32 = 5;
+
+/// Initial delay between busy retries (doubled on each attempt).
+const BUSY_RETRY_DELAY: Duration = Duration::from_millis(50);
+
 /// Database wrapper for snapshot storage.
+///
+/// The on-disk database runs in WAL mode with a busy timeout so that a
+/// long-running process (e.g. the daemon) and one-shot CLI invocations can
+/// share it. Write operations retry on `SQLITE_BUSY` instead of failing with
+/// "database is locked".
 pub struct SnapshotDb {
     conn: Connection,
 }
@@ -97,6 +112,7 @@ impl SnapshotDb {
             .map_err(|e| SdError::Other(format!("Failed to open database: {e}")))?;
 
         let db = Self { conn };
+        db.init_concurrency()?;
         db.init_schema()?;
         info!(path = %path.display(), "Snapshot database ready");
         Ok(db)
@@ -113,6 +129,21 @@ impl SnapshotDb {
         Ok(db)
     }
 
+    /// Configures the connection for concurrent access (busy timeout + WAL).
+    fn init_concurrency(&self) -> Result<()> {
+        self.conn
+            .busy_timeout(BUSY_TIMEOUT)
+            .map_err(|e| SdError::Other(format!("Failed to set busy timeout: {e}")))?;
+
+        let mode: String = retry_on_busy(|| {
+            self.conn
+                .query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))
+        })
+        .map_err(|e| SdError::Other(format!("Failed to enable WAL mode: {e}")))?;
+        debug!(journal_mode = %mode, "Configured journal mode");
+        Ok(())
+    }
+
     /// Initializes the database schema.
     fn init_schema(&self) -> Result<()> {
         // Enable foreign keys
@@ -120,8 +151,7 @@ impl SnapshotDb {
             .execute("PRAGMA foreign_keys = ON", [])
             .map_err(|e| SdError::Other(format!("Failed to enable foreign keys: {e}")))?;
 
-        self.conn
-            .execute_batch(SCHEMA_SQL)
+        retry_on_busy(|| self.conn.execute_batch(SCHEMA_SQL))
             .map_err(|e| SdError::Other(format!("Failed to initialize schema: {e}")))?;
         Ok(())
     }
@@ -131,112 +161,11 @@ impl SnapshotDb {
     /// If a snapshot with the same name exists, it will be updated.
     #[instrument(skip(self, snapshot), fields(name = %snapshot.name))]
     pub fn save_snapshot(&mut self, snapshot: &Snapshot) -> Result<i64> {
-        let tx = self
-            .conn
-            .transaction()
-            .map_err(|e| SdError::Other(format!("Failed to start transaction: {e}")))?;
-
         let now = Utc::now().to_rfc3339();
 
-        // Check if snapshot exists
-        let existing_id: Option<i64> = tx
-            .query_row(
-                "SELECT id FROM snapshots WHERE name = ?1",
-                params![snapshot.name],
-                |row| row.get(0),
-            )
-            .ok();
-
-        let snapshot_id = if let Some(id) = existing_id {
-            // Update existing snapshot
-            debug!(id, "Updating existing snapshot");
-            tx.execute(
-                "UPDATE snapshots SET
-                    description = ?1,
-                    device_model = ?2,
-                    device_serial = ?3,
-                    key_count = ?4,
-                    key_width = ?5,
-                    key_height = ?6,
-                    brightness = ?7,
-                    updated_at = ?8
-                 WHERE id = ?9",
-                params![
-                    snapshot.description,
-                    snapshot.device_model,
-                    snapshot.device_serial,
-                    snapshot.key_count,
-                    snapshot.key_width,
-                    snapshot.key_height,
-                    snapshot.brightness,
-                    now,
-                    id,
-                ],
-            )
-            .map_err(|e| SdError::Other(format!("Failed to update snapshot: {e}")))?;
-
-            // Delete existing keys
-            tx.execute(
-                "DELETE FROM snapshot_keys WHERE snapshot_id = ?1",
-                params![id],
-            )
-            .map_err(|e| SdError::Other(format!("Failed to delete old keys: {e}")))?;
-
-            id
-        } else {
-            // Insert new snapshot
-            debug!("Inserting new snapshot");
-            tx.execute(
-                "INSERT INTO snapshots (name, description, device_model, device_serial, key_count, key_width, key_height, brightness, created_at, updated_at)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-                params![
-                    snapshot.name,
-                    snapshot.description,
-                    snapshot.device_model,
-                    snapshot.device_serial,
-                    snapshot.key_count,
-                    snapshot.key_width,
-                    snapshot.key_height,
-                    snapshot.brightness,
-                    now,
-                    now,
-                ],
-            )
-            .map_err(|e| SdError::Other(format!("Failed to insert snapshot: {e}")))?;
-
-            tx.last_insert_rowid()
-        };
-
-        // Insert keys
-        for key in &snapshot.keys {
-            let (state_type, source_path, image_hash, color_hex) = match &key.state {
-                KeyState::Image {
-                    source_path,
-                    image_hash,
-                } => (
-                    "image",
-                    source_path.as_ref().map(|p| p.display().to_string()),
-                    Some(image_hash.clone()),
-                    None,
-                ),
-                KeyState::Color { hex } => ("color", None, None, Some(hex.clone())),
-                KeyState::Clear => ("clear", None, None, None),
-            };
-
-            trace!(
-                key_index = key.key_index,
-                state_type, "Inserting snapshot key"
-            );
-            tx.execute(
-                "INSERT INTO snapshot_keys (snapshot_id, key_index, state_type, source_path, image_hash, color_hex, created_at)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-                params![snapshot_id, key.key_index, state_type, source_path, image_hash, color_hex, now],
-            )
-            .map_err(|e| SdError::Other(format!("Failed to insert snapshot key: {e}")))?;
-        }
-
-        tx.commit()
-            .map_err(|e| SdError::Other(format!("Failed to commit transaction: {e}")))?;
+        // The whole transaction is retried if another writer holds the lock.
+        let snapshot_id = retry_on_busy(|| write_snapshot(&mut self.conn, snapshot, &now))
+            .map_err(|e| SdError::Other(format!("Failed to save snapshot: {e}")))?;
 
         info!(name = %snapshot.name, id = snapshot_id, keys = snapshot.keys.len(), "Snapshot saved");
         Ok(snapshot_id)
@@ -347,16 +276,30 @@ impl SnapshotDb {
     /// Lists all snapshots with summary information.
     #[instrument(skip(self))]
     pub fn list_snapshots(&self) -> Result<Vec<SnapshotSummary>> {
+        self.list_snapshots_page(None, 0)
+    }
+
+    /// Lists one page of snapshots, most recently updated first.
+    ///
+    /// Skips `offset` snapshots and returns at most `limit` (all if `None`).
+    #[instrument(skip(self))]
+    pub fn list_snapshots_page(
+        &self,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<SnapshotSummary>> {
         let mut stmt = self
             .conn
             .prepare(
                 "SELECT id, name, description, device_model, key_count, brightness, created_at, updated_at
-                 FROM snapshots ORDER BY updated_at DESC",
+                 FROM snapshots ORDER BY updated_at DESC, id DESC LIMIT ?1 OFFSET ?2",
             )
             .map_err(|e| SdError::Other(format!("Failed to prepare statement: {e}")))?;
 
+        // A negative LIMIT means no limit in SQLite
+        let limit = limit.map_or(-1, i64::from);
         let summaries: Vec<SnapshotSummary> = stmt
-            .query_map([], |row| {
+            .query_map(params![limit, offset], |row| {
                 let created_at: String = row.get(6)?;
                 let updated_at: String = row.get(7)?;
 
@@ -383,15 +326,24 @@ impl SnapshotDb {
         Ok(summaries)
     }
 
+    /// Returns how many snapshots are saved.
+    #[instrument(skip(self))]
+    pub fn count_snapshots(&self) -> Result<u32> {
+        self.conn
+            .query_row("SELECT COUNT(*) FROM snapshots", [], |row| row.get(0))
+            .map_err(|e| SdError::Other(format!("Failed to count snapshots: {e}")))
+    }
+
     /// Deletes a snapshot by name.
     ///
     /// Returns true if a snapshot was deleted, false if not found.
     #[instrument(skip(self))]
     pub fn delete_snapshot(&mut self, name: &str) -> Result<bool> {
-        let deleted = self
-            .conn
-            .execute("DELETE FROM snapshots WHERE name = ?1", params![name])
-            .map_err(|e| SdError::Other(format!("Failed to delete snapshot: {e}")))?;
+        let deleted = retry_on_busy(|| {
+            self.conn
+                .execute("DELETE FROM snapshots WHERE name = ?1", params![name])
+        })
+        .map_err(|e| SdError::Other(format!("Failed to delete snapshot: {e}")))?;
 
         if deleted > 0 {
             info!(name, "Snapshot deleted");
@@ -402,6 +354,24 @@ impl SnapshotDb {
         }
     }
 
+    /// Updates a snapshot's metadata (description, brightness, name) without
+    /// touching its keys.
+    ///
+    /// Returns true if the snapshot was updated, false if not found.
+    #[instrument(skip(self, edit))]
+    pub fn edit_snapshot(&mut self, name: &str, edit: &SnapshotEdit) -> Result<bool> {
+        let now = Utc::now().to_rfc3339();
+        let edited = retry_on_busy(|| edit_snapshot(&mut self.conn, name, edit, &now))
+            .map_err(|e| SdError::Other(format!("Failed to edit snapshot: {e}")))?;
+
+        if edited {
+            info!(name, renamed = ?edit.rename, "Snapshot edited");
+        } else {
+            debug!(name, "Snapshot not found for editing");
+        }
+        Ok(edited)
+    }
+
     /// Checks if a snapshot exists by name.
     #[instrument(skip(self))]
     pub fn snapshot_exists(&self, name: &str) -> Result<bool> {
@@ -422,8 +392,8 @@ impl SnapshotDb {
     #[instrument(skip(self, image), fields(hash = %image.hash))]
     pub fn save_image(&self, image: &CachedImage) -> Result<()> {
         let now = Utc::now().to_rfc3339();
-        self.conn
-            .execute(
+        retry_on_busy(|| {
+            self.conn.execute(
                 "INSERT OR REPLACE INTO images (hash, original_path, width, height, format, size_bytes, created_at, last_accessed_at, access_count)
                  VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
                 params![
@@ -438,7 +408,8 @@ impl SnapshotDb {
                     image.access_count,
                 ],
             )
-            .map_err(|e| SdError::Other(format!("Failed to save image: {e}")))?;
+        })
+        .map_err(|e| SdError::Other(format!("Failed to save image: {e}")))?;
 
         trace!(hash = %image.hash, "Image metadata saved");
         Ok(())
@@ -516,13 +487,13 @@ impl SnapshotDb {
     /// Deletes orphaned images not referenced by any snapshot.
     #[instrument(skip(self))]
     pub fn cleanup_orphaned_images(&mut self) -> Result<usize> {
-        let deleted = self
-            .conn
-            .execute(
+        let deleted = retry_on_busy(|| {
+            self.conn.execute(
                 "DELETE FROM images WHERE hash NOT IN (SELECT DISTINCT image_hash FROM snapshot_keys WHERE image_hash IS NOT NULL)",
                 [],
             )
-            .map_err(|e| SdError::Other(format!("Failed to cleanup images: {e}")))?;
+        })
+        .map_err(|e| SdError::Other(format!("Failed to cleanup images: {e}")))?;
 
         if deleted > 0 {
             info!(deleted, "Orphaned images cleaned up");
@@ -531,6 +502,194 @@ impl SnapshotDb {
     }
 }
 
+/// Writes a snapshot and its keys in a single transaction.
+///
+/// The transaction takes the write lock up front (`BEGIN IMMEDIATE`) so a
+/// concurrent writer waits on the busy handler instead of failing when it
+/// tries to upgrade a read lock mid-transaction.
+fn write_snapshot(conn: &mut Connection, snapshot: &Snapshot, now: &str) -> rusqlite::Result<i64> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    // Check if snapshot exists
+    let existing_id: Option<i64> = tx
+        .query_row(
+            "SELECT id FROM snapshots WHERE name = ?1",
+            params![snapshot.name],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    let snapshot_id = if let Some(id) = existing_id {
+        // Update existing snapshot
+        debug!(id, "Updating existing snapshot");
+        tx.execute(
+            "UPDATE snapshots SET
+                description = ?1,
+                device_model = ?2,
+                device_serial = ?3,
+                key_count = ?4,
+                key_width = ?5,
+                key_height = ?6,
+                brightness = ?7,
+                updated_at = ?8
+             WHERE id = ?9",
+            params![
+                snapshot.description,
+                snapshot.device_model,
+                snapshot.device_serial,
+                snapshot.key_count,
+                snapshot.key_width,
+                snapshot.key_height,
+                snapshot.brightness,
+                now,
+                id,
+            ],
+        )?;
+
+        // Delete existing keys
+        tx.execute(
+            "DELETE FROM snapshot_keys WHERE snapshot_id = ?1",
+            params![id],
+        )?;
+
+        id
+    } else {
+        // Insert new snapshot
+        debug!("Inserting new snapshot");
+        tx.execute(
+            "INSERT INTO snapshots (name, description, device_model, device_serial, key_count, key_width, key_height, brightness, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                snapshot.name,
+                snapshot.description,
+                snapshot.device_model,
+                snapshot.device_serial,
+                snapshot.key_count,
+                snapshot.key_width,
+                snapshot.key_height,
+                snapshot.brightness,
+                now,
+                now,
+            ],
+        )?;
+
+        tx.last_insert_rowid()
+    };
+
+    // Insert keys
+    for key in &snapshot.keys {
+        let (state_type, source_path, image_hash, color_hex) = match &key.state {
+            KeyState::Image {
+                source_path,
+                image_hash,
+            } => (
+                "image",
+                source_path.as_ref().map(|p| p.display().to_string()),
+                Some(image_hash.clone()),
+                None,
+            ),
+            KeyState::Color { hex } => ("color", None, None, Some(hex.clone())),
+            KeyState::Clear => ("clear", None, None, None),
+        };
+
+        trace!(
+            key_index = key.key_index,
+            state_type, "Inserting snapshot key"
+        );
+        tx.execute(
+            "INSERT INTO snapshot_keys (snapshot_id, key_index, state_type, source_path, image_hash, color_hex, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![snapshot_id, key.key_index, state_type, source_path, image_hash, color_hex, now],
+        )?;
+    }
+
+    tx.commit()?;
+    Ok(snapshot_id)
+}
+
+/// Applies a metadata edit with one `UPDATE` per changed field, in a single
+/// immediate transaction so a concurrent save can't interleave.
+fn edit_snapshot(
+    conn: &mut Connection,
+    name: &str,
+    edit: &SnapshotEdit,
+    now: &str,
+) -> rusqlite::Result<bool> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let Some(id) = tx
+        .query_row(
+            "SELECT id FROM snapshots WHERE name = ?1",
+            params![name],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+    else {
+        return Ok(false);
+    };
+
+    if let Some(description) = &edit.description {
+        let description = Some(description.as_str()).filter(|d| !d.is_empty());
+        tx.execute(
+            "UPDATE snapshots SET description = ?1 WHERE id = ?2",
+            params![description, id],
+        )?;
+    }
+    if let Some(brightness) = edit.brightness {
+        tx.execute(
+            "UPDATE snapshots SET brightness = ?1 WHERE id = ?2",
+            params![brightness, id],
+        )?;
+    }
+    if let Some(new_name) = &edit.rename {
+        tx.execute(
+            "UPDATE snapshots SET name = ?1 WHERE id = ?2",
+            params![new_name, id],
+        )?;
+    }
+    tx.execute(
+        "UPDATE snapshots SET updated_at = ?1 WHERE id = ?2",
+        params![now, id],
+    )?;
+
+    tx.commit()?;
+    Ok(true)
+}
+
+/// Returns true if the error means another connection holds the lock.
+fn is_busy(err: &rusqlite::Error) -> bool {
+    matches!(
+        err.sqlite_error_code(),
+        Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+    )
+}
+
+/// Runs a database operation, retrying with backoff while the database is busy.
+///
+/// The busy timeout already makes `SQLite` wait for the lock; this covers the
+/// cases where it gives up early (e.g. WAL journal mode switches or lock
+/// upgrades) so callers see an error only after repeated contention.
+fn retry_on_busy<T>(mut op: impl FnMut() -> rusqlite::Result<T>) -> rusqlite::Result<T> {
+    let mut delay = BUSY_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Err(e) if is_busy(&e) && attempt < MAX_BUSY_RETRIES => {
+                warn!(
+                    attempt,
+                    max_attempts = MAX_BUSY_RETRIES,
+                    delay_ms = delay.as_millis(),
+                    "Snapshot database busy, retrying"
+                );
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
 /// Returns the default database path.
 ///
 /// Location: `~/.local/share/sd/snapshots/snapshots.db`
@@ -658,6 +817,29 @@ mod tests {
         assert_eq!(list.len(), 2);
     }
 
+    #[test]
+    fn test_list_snapshots_page() {
+        let mut db = SnapshotDb::in_memory().unwrap();
+        for i in 0..5 {
+            db.save_snapshot(&Snapshot::new(
+                format!("layout-{i}"),
+                "MK2".to_string(),
+                15,
+                72,
+                72,
+            ))
+            .unwrap();
+        }
+
+        assert_eq!(db.count_snapshots().unwrap(), 5);
+        let all = db.list_snapshots().unwrap();
+        let page = db.list_snapshots_page(Some(2), 1).unwrap();
+        let names: Vec<&str> = page.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, [all[1].name.as_str(), all[2].name.as_str()]);
+        assert_eq!(db.list_snapshots_page(None, 3).unwrap().len(), 2);
+        assert!(db.list_snapshots_page(Some(10), 5).unwrap().is_empty());
+    }
+
     #[test]
     fn test_delete_snapshot() {
         let mut db = SnapshotDb::in_memory().unwrap();
@@ -681,6 +863,43 @@ mod tests {
         assert!(!deleted);
     }
 
+    #[test]
+    fn test_edit_snapshot() {
+        let mut db = SnapshotDb::in_memory().unwrap();
+
+        let mut snap = Snapshot::new("work".to_string(), "XL".to_string(), 32, 96, 96)
+            .with_brightness(50)
+            .with_description("Wrok layout".to_string());
+        snap.add_key(SnapshotKey::color(1, "#ff0000".to_string()));
+        db.save_snapshot(&snap).unwrap();
+
+        let edit = SnapshotEdit {
+            description: Some("Work layout".to_string()),
+            brightness: Some(80),
+            rename: Some("work-2".to_string()),
+        };
+        assert!(db.edit_snapshot("work", &edit).unwrap());
+        assert!(!db.snapshot_exists("work").unwrap());
+
+        let loaded = db.load_snapshot("work-2").unwrap().unwrap();
+        assert_eq!(loaded.description.as_deref(), Some("Work layout"));
+        assert_eq!(loaded.brightness, Some(80));
+        assert_eq!(loaded.keys.len(), 1);
+        assert!(loaded.updated_at >= loaded.created_at);
+
+        // An empty description clears it; other fields stay
+        let clear = SnapshotEdit {
+            description: Some(String::new()),
+            ..SnapshotEdit::default()
+        };
+        assert!(db.edit_snapshot("work-2", &clear).unwrap());
+        let loaded = db.load_snapshot("work-2").unwrap().unwrap();
+        assert!(loaded.description.is_none());
+        assert_eq!(loaded.brightness, Some(80));
+
+        assert!(!db.edit_snapshot("missing", &edit).unwrap());
+    }
+
     #[test]
     fn test_image_cache() {
         let db = SnapshotDb::in_memory().unwrap();
@@ -702,6 +921,45 @@ mod tests {
         assert_eq!(loaded.format, "webp");
     }
 
+    #[test]
+    fn test_concurrent_writers() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("snapshots.db");
+        // Create the schema before the writers race
+        SnapshotDb::open(&path).unwrap();
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let mut db = SnapshotDb::open(&path)?;
+                    for j in 0..10 {
+                        let mut snap =
+                            Snapshot::new(format!("writer-{i}-{j}"), "XL".to_string(), 32, 96, 96);
+                        snap.add_key(SnapshotKey::color(0, "#ff0000".to_string()));
+                        db.save_snapshot(&snap)?;
+                        // Everyone also rewrites a shared snapshot to force contention
+                        db.save_snapshot(&Snapshot::new(
+                            "shared".to_string(),
+                            "XL".to_string(),
+                            32,
+                            96,
+                            96,
+                        ))?;
+                    }
+                    Ok::<_, SdError>(())
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap().unwrap();
+        }
+
+        let db = SnapshotDb::open(&path).unwrap();
+        assert_eq!(db.list_snapshots().unwrap().len(), 8 * 10 + 1);
+    }
+
     #[test]
     fn test_image_cache_path() {
         let path = image_cache_path("aabbccdd1234").unwrap();
@@ -0,0 +1,153 @@
+//! Key-by-key comparison of two snapshots.
+
+use serde::Serialize;
+
+use crate::error::{Result, SdError};
+
+use super::schema::{KeyState, Snapshot};
+
+/// How one key differs between two snapshots.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyDiff {
+    /// Key index (0-based).
+    pub key: u8,
+    /// True if both snapshots show the same content on this key.
+    pub same: bool,
+    /// State in the first snapshot, or `None` if it wasn't saved.
+    pub a: Option<KeyState>,
+    /// State in the second snapshot, or `None` if it wasn't saved.
+    pub b: Option<KeyState>,
+}
+
+/// The differences between two snapshots of the same layout.
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotDiff {
+    /// Name of the first snapshot.
+    pub a: String,
+    /// Name of the second snapshot.
+    pub b: String,
+    /// Brightness in the first snapshot, if captured.
+    pub brightness_a: Option<u8>,
+    /// Brightness in the second snapshot, if captured.
+    pub brightness_b: Option<u8>,
+    /// Every key, in index order.
+    pub keys: Vec<KeyDiff>,
+    /// Number of keys that differ.
+    pub different: usize,
+}
+
+impl SnapshotDiff {
+    /// Returns true if the snapshots show the same keys and brightness.
+    #[must_use]
+    pub fn is_identical(&self) -> bool {
+        self.different == 0 && self.brightness_a == self.brightness_b
+    }
+}
+
+/// Compare `a` and `b` key by key.
+///
+/// Images match by content hash, so the same picture saved from different
+/// paths counts as the same; colors match case-insensitively.
+///
+/// # Errors
+///
+/// Returns an error if the snapshots have different key counts, since their
+/// keys don't line up.
+pub fn compare(a: &Snapshot, b: &Snapshot) -> Result<SnapshotDiff> {
+    if a.key_count != b.key_count {
+        return Err(SdError::Other(format!(
+            "Snapshots '{}' ({}, {} keys) and '{}' ({}, {} keys) are for different layouts \
+             and can't be compared key by key",
+            a.name, a.device_model, a.key_count, b.name, b.device_model, b.key_count
+        )));
+    }
+
+    let state = |snapshot: &Snapshot, key: u8| {
+        snapshot
+            .keys
+            .iter()
+            .find(|k| k.key_index == key)
+            .map(|k| k.state.clone())
+    };
+    let keys: Vec<KeyDiff> = (0..a.key_count)
+        .map(|key| {
+            let (a, b) = (state(a, key), state(b, key));
+            let same = match (&a, &b) {
+                (Some(a), Some(b)) => same_content(a, b),
+                (None, None) => true,
+                _ => false,
+            };
+            KeyDiff { key, same, a, b }
+        })
+        .collect();
+    let different = keys.iter().filter(|k| !k.same).count();
+
+    Ok(SnapshotDiff {
+        a: a.name.clone(),
+        b: b.name.clone(),
+        brightness_a: a.brightness,
+        brightness_b: b.brightness,
+        keys,
+        different,
+    })
+}
+
+fn same_content(a: &KeyState, b: &KeyState) -> bool {
+    match (a, b) {
+        (KeyState::Image { image_hash: a, .. }, KeyState::Image { image_hash: b, .. }) => a == b,
+        (KeyState::Color { hex: a }, KeyState::Color { hex: b }) => a.eq_ignore_ascii_case(b),
+        (KeyState::Clear, KeyState::Clear) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::snapshot::SnapshotKey;
+
+    fn snapshot(name: &str, key_count: u8) -> Snapshot {
+        Snapshot::new(name.to_string(), "Mk2".to_string(), key_count, 72, 72)
+    }
+
+    #[test]
+    fn test_compare_reports_changed_keys() {
+        let mut a = snapshot("work", 15).with_brightness(80);
+        a.add_key(SnapshotKey::image(
+            0,
+            Some(PathBuf::from("a.png")),
+            "abc".to_string(),
+        ));
+        a.add_key(SnapshotKey::color(1, "#FF0000".to_string()));
+        a.add_key(SnapshotKey::cleared(2));
+
+        let mut b = snapshot("play", 15).with_brightness(80);
+        b.add_key(SnapshotKey::image(
+            0,
+            Some(PathBuf::from("elsewhere/a.png")),
+            "abc".to_string(),
+        ));
+        b.add_key(SnapshotKey::color(1, "#ff0000".to_string()));
+        b.add_key(SnapshotKey::color(2, "#000000".to_string()));
+        b.add_key(SnapshotKey::cleared(3));
+
+        let diff = compare(&a, &b).unwrap();
+        assert_eq!(diff.keys.len(), 15);
+        assert!(diff.keys[0].same && diff.keys[1].same);
+        assert!(!diff.keys[2].same);
+        // Saved in only one snapshot
+        assert!(!diff.keys[3].same);
+        assert!(diff.keys[3].a.is_none());
+        assert_eq!(diff.different, 2);
+        assert!(!diff.is_identical());
+        assert!(compare(&a, &a).unwrap().is_identical());
+    }
+
+    #[test]
+    fn test_compare_rejects_other_layouts() {
+        let err = compare(&snapshot("xl", 32), &snapshot("mini", 6)).unwrap_err();
+        assert!(err.to_string().contains("different layouts"), "{err}");
+    }
+}
@@ -40,8 +40,14 @@
 //! }
 //! ```
 
+mod archive;
 mod db;
+mod diff;
 mod schema;
 
+pub use archive::{
+    ARCHIVE_FORMAT_VERSION, ArchiveCompression, ArchiveManifest, SnapshotArchive, reencode_png,
+};
 pub use db::{SnapshotDb, default_db_path, default_image_cache_dir, image_cache_path};
-pub use schema::{CachedImage, KeyState, Snapshot, SnapshotKey, SnapshotSummary};
+pub use diff::{KeyDiff, SnapshotDiff, compare};
+pub use schema::{CachedImage, KeyState, Snapshot, SnapshotEdit, SnapshotKey, SnapshotSummary};
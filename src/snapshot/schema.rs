@@ -222,6 +222,25 @@ pub struct SnapshotSummary {
     pub updated_at: DateTime<Utc>,
 }
 
+/// Metadata changes for `snapshot edit`; unset fields are left as they are.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotEdit {
+    /// New description; an empty string clears it.
+    pub description: Option<String>,
+    /// New brightness level (0-100).
+    pub brightness: Option<u8>,
+    /// New name.
+    pub rename: Option<String>,
+}
+
+impl SnapshotEdit {
+    /// Returns true if the edit changes nothing.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.description.is_none() && self.brightness.is_none() && self.rename.is_none()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
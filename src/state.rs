@@ -118,6 +118,56 @@ impl SessionState {
             cleared_keys: cleared_count,
         }
     }
+
+    /// The tracked state with keys in order, for `sd state`.
+    #[must_use]
+    pub fn report(&self) -> StateReport {
+        let mut keys: Vec<KeyStateEntry> = self
+            .keys
+            .iter()
+            .map(|(key, state)| KeyStateEntry {
+                key: *key,
+                state: state.clone(),
+            })
+            .collect();
+        keys.sort_by_key(|entry| entry.key);
+        StateReport {
+            source: "session",
+            note: TRACKED_STATE_NOTE,
+            brightness: self.brightness,
+            keys,
+            summary: self.summary(),
+        }
+    }
+}
+
+/// Caveat shown with every state report.
+pub const TRACKED_STATE_NOTE: &str =
+    "Changes made through this sd process, not read back from the hardware";
+
+/// Tracked state as reported by `sd state`.
+#[derive(Debug, Clone, Serialize)]
+pub struct StateReport {
+    /// Where the state comes from (always `session`).
+    pub source: &'static str,
+    /// Reminder that this is tracked, not live, state.
+    pub note: &'static str,
+    /// Brightness if set.
+    pub brightness: Option<u8>,
+    /// Keys with tracked state, in key order.
+    pub keys: Vec<KeyStateEntry>,
+    /// Counts by state type.
+    pub summary: StateSummary,
+}
+
+/// One key's tracked state.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyStateEntry {
+    /// Key index.
+    pub key: u8,
+    /// What the key was last set to.
+    #[serde(flatten)]
+    pub state: KeyState,
 }
 
 /// Summary of session state for reporting.
@@ -159,6 +209,18 @@ pub fn session_state_mut() -> RwLockWriteGuard<'static, SessionState> {
     SESSION_STATE.write().expect("session state lock poisoned")
 }
 
+/// Forget everything tracked so far, returning how many keys were tracked.
+///
+/// Only sd's belief about the device changes; the device itself is
+/// untouched. Use it after the deck was changed outside sd so that
+/// `save --session-only` and skip-unchanged checks start from scratch.
+pub fn reset() -> usize {
+    let mut state = session_state_mut();
+    let keys = state.key_count();
+    state.reset();
+    keys
+}
+
 /// Record operations using the global state.
 ///
 /// These are convenience functions for recording state changes
@@ -301,4 +363,23 @@ mod tests {
         assert_eq!(summary.color_keys, 1);
         assert_eq!(summary.cleared_keys, 3);
     }
+
+    #[test]
+    fn test_report_orders_keys() {
+        let mut state = SessionState::new();
+        state.record_fill_key(9, "#00ff00".to_string());
+        state.record_set_key(2, PathBuf::from("/tmp/a.png"));
+        state.record_brightness(40);
+
+        let report = state.report();
+        let keys: Vec<u8> = report.keys.iter().map(|entry| entry.key).collect();
+        assert_eq!(keys, [2, 9]);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["source"], "session");
+        assert_eq!(json["brightness"], 40);
+        assert_eq!(json["keys"][1]["type"], "color");
+        assert_eq!(json["keys"][1]["hex"], "#00ff00");
+        assert_eq!(json["summary"]["total_keys"], 2);
+    }
 }
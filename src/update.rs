@@ -0,0 +1,210 @@
+//! Update checks for `sd version --check`.
+//!
+//! Compares the running version with the latest GitHub release. The check is
+//! opt-in, uses a short timeout and never fails the command: network or feed
+//! problems are reported alongside the current version instead.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::error::{Result, SdError};
+
+/// How long to wait for the release feed before giving up.
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The project's GitHub release feed.
+pub const RELEASE_FEED_URL: &str =
+    "https://api.github.com/repos/Dicklesworthstone/rust_stream_deck/releases/latest";
+
+/// Outcome of an update check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UpdateCheck {
+    /// Version of the running binary.
+    pub current: String,
+    /// Latest released version, if the feed was reached.
+    pub latest: Option<String>,
+    /// Whether `latest` is newer than `current`.
+    pub update_available: bool,
+    /// Release page for `latest`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_url: Option<String>,
+    /// Why the check couldn't complete (offline, timeout, bad feed).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl UpdateCheck {
+    /// A check that didn't reach the feed.
+    #[must_use]
+    pub fn unavailable(current: &str, reason: impl Into<String>) -> Self {
+        Self {
+            current: current.to_string(),
+            latest: None,
+            update_available: false,
+            release_url: None,
+            error: Some(reason.into()),
+        }
+    }
+
+    /// Compare `current` against a fetched release.
+    #[must_use]
+    pub fn from_release(current: &str, release: Release) -> Self {
+        let latest = release.tag_name.trim_start_matches('v').to_string();
+        Self {
+            current: current.to_string(),
+            update_available: is_newer(&latest, current),
+            latest: Some(latest),
+            release_url: Some(release.html_url),
+            error: None,
+        }
+    }
+}
+
+/// The fields we use from a GitHub release.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    /// Release tag, e.g. `v0.2.0`.
+    pub tag_name: String,
+    /// Release page URL.
+    pub html_url: String,
+}
+
+/// URL of the release feed.
+///
+/// `SD_UPDATE_URL` overrides [`RELEASE_FEED_URL`] (e.g. for a mirror).
+#[must_use]
+pub fn release_feed_url() -> String {
+    std::env::var("SD_UPDATE_URL").unwrap_or_else(|_| RELEASE_FEED_URL.to_string())
+}
+
+/// Fetch the latest release from `url`.
+///
+/// # Errors
+///
+/// Returns an error if the request fails, times out or the response isn't a
+/// release.
+pub fn fetch_latest_release(url: &str, timeout: Duration) -> Result<Release> {
+    debug!(
+        url,
+        timeout_ms = timeout.as_millis(),
+        "Fetching latest release"
+    );
+    let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+    let body = agent
+        .get(url)
+        .set("Accept", "application/vnd.github+json")
+        .set("User-Agent", concat!("sd/", env!("CARGO_PKG_VERSION")))
+        .call()
+        .map_err(|e| SdError::Other(format!("Failed to reach release feed: {e}")))?
+        .into_string()
+        .map_err(|e| SdError::Other(format!("Failed to read release feed: {e}")))?;
+    serde_json::from_str(&body)
+        .map_err(|e| SdError::Other(format!("Unexpected release feed response: {e}")))
+}
+
+/// Check `url` for a release newer than `current`, never failing.
+#[must_use]
+pub fn check_for_update(current: &str, url: &str, timeout: Duration) -> UpdateCheck {
+    check_with(current, || fetch_latest_release(url, timeout))
+}
+
+/// Check for a release newer than `current`, with `fetch` getting the latest
+/// release; a failed fetch is reported in the result.
+#[must_use]
+pub fn check_with(current: &str, fetch: impl FnOnce() -> Result<Release>) -> UpdateCheck {
+    match fetch() {
+        Ok(release) => UpdateCheck::from_release(current, release),
+        Err(e) => {
+            warn!(error = %e, "Update check failed");
+            UpdateCheck::unavailable(current, e.to_string())
+        }
+    }
+}
+
+/// Returns true if version `a` is newer than `b`.
+///
+/// Compares `major.minor.patch` numerically; a leading `v` and any
+/// pre-release/build suffix are ignored. Unparseable versions never count as
+/// newer.
+#[must_use]
+pub fn is_newer(a: &str, b: &str) -> bool {
+    match (parse_version(a), parse_version(b)) {
+        (Some(a), Some(b)) => a > b,
+        _ => false,
+    }
+}
+
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let core = version
+        .trim()
+        .trim_start_matches('v')
+        .split(['-', '+'])
+        .next()?;
+    let mut parts = core.split('.').map(str::parse::<u64>);
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    Some((major, minor, patch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("0.2.0", "0.1.0"));
+        assert!(is_newer("v1.0.0", "0.9.12"));
+        assert!(is_newer("0.1.10", "0.1.9"));
+        assert!(!is_newer("0.1.0", "0.1.0"));
+        assert!(!is_newer("0.1.0-beta.1", "0.1.0"));
+        assert!(!is_newer("0.0.9", "0.1.0"));
+        assert!(!is_newer("nightly", "0.1.0"));
+    }
+
+    #[test]
+    fn test_from_release() {
+        let release: Release = serde_json::from_str(
+            r#"{"tag_name": "v0.3.1", "html_url": "https://example.com/r/v0.3.1", "draft": false}"#,
+        )
+        .unwrap();
+        let check = UpdateCheck::from_release("0.1.0", release);
+        assert_eq!(check.latest.as_deref(), Some("0.3.1"));
+        assert!(check.update_available);
+        assert_eq!(
+            check.release_url.as_deref(),
+            Some("https://example.com/r/v0.3.1")
+        );
+
+        let json = serde_json::to_value(&check).unwrap();
+        assert_eq!(json["current"], "0.1.0");
+        assert!(json.get("error").is_none());
+    }
+
+    #[test]
+    fn test_unreachable_feed_is_graceful() {
+        let check = check_with("0.1.0", || {
+            Err(SdError::Other(
+                "Failed to reach release feed: offline".into(),
+            ))
+        });
+        assert!(!check.update_available);
+        assert!(check.latest.is_none());
+        assert!(check.error.as_deref().unwrap().contains("offline"));
+    }
+
+    #[test]
+    fn test_check_with_fetched_release() {
+        let check = check_with("0.1.0", || {
+            Ok(Release {
+                tag_name: "v0.1.0".into(),
+                html_url: "https://example.com/r/v0.1.0".into(),
+            })
+        });
+        assert!(!check.update_available);
+        assert_eq!(check.latest.as_deref(), Some("0.1.0"));
+        assert!(check.error.is_none());
+    }
+}
@@ -133,6 +133,35 @@ mod cli_args {
         let result = cli.run(&["set-keys", "--help"]);
         result.assert_stdout_contains("--start-key");
     }
+
+    #[test]
+    fn set_keys_accepts_allow_duplicates_flag() {
+        let cli = CliRunner::new();
+        let result = cli.run(&["set-keys", "--help"]);
+        result.assert_stdout_contains("--allow-duplicates");
+    }
+
+    #[test]
+    fn batch_commands_accept_chunking_flags() {
+        let cli = CliRunner::new();
+        for command in ["set-keys", "fill-keys", "clear-keys", "apply"] {
+            let result = cli.run(&[command, "--help"]);
+            result.assert_stdout_contains("--batch-size");
+            result.assert_stdout_contains("--batch-delay");
+        }
+        // --batch-delay without --batch-size is harmless
+        let batch_dir = fixtures_path("images/batch/complete-6");
+        cli.run_robot(&[
+            "set-keys",
+            batch_dir.to_str().unwrap(),
+            "--dry-run",
+            "--batch-size",
+            "4",
+            "--batch-delay",
+            "10",
+        ])
+        .assert_success();
+    }
 }
 
 // ============================================================================
@@ -170,6 +199,44 @@ mod error_handling {
         result.assert_failure();
     }
 
+    #[test]
+    fn fill_all_randomize_conflicts_with_color() {
+        let cli = CliRunner::new();
+        cli.run(&["fill-all", "ff0000", "--randomize"])
+            .assert_failure();
+        cli.run(&["fill-all", "ff0000", "--seed", "1"])
+            .assert_failure();
+    }
+
+    #[test]
+    fn set_keys_pattern_without_placeholder() {
+        let cli = CliRunner::new();
+        let batch_dir = fixtures_path("images/batch/complete-6");
+        // Rejected before scanning, even in a dry run
+        let result = cli.run_robot(&[
+            "set-keys",
+            batch_dir.to_str().unwrap(),
+            "--pattern",
+            "key-index.png",
+            "--dry-run",
+        ]);
+        result.assert_failure();
+        let json = result.json_stderr();
+        assert!(json["message"].as_str().unwrap().contains("key-index.png"));
+        assert!(json["suggestion"].as_str().unwrap().contains("{index:02d}"));
+    }
+
+    #[test]
+    fn fill_key_alpha_needs_over() {
+        let cli = CliRunner::new();
+        let result = cli.run_robot(&["fill-key", "0", "ff000080", "--dry-run"]);
+        result.assert_failure();
+        let json = result.json_stderr();
+        assert!(json["message"].as_str().unwrap().contains("--over"));
+        cli.run_robot(&["fill-key", "0", "ff000080", "--over", "--dry-run"])
+            .assert_success();
+    }
+
     #[test]
     fn fill_keys_missing_color_arg() {
         let cli = CliRunner::new();
@@ -232,6 +299,190 @@ mod robot_mode_dry_run_no_device {
         );
     }
 
+    #[test]
+    fn dry_run_reports_color_space_correction() {
+        let cli = CliRunner::new();
+        let result = cli.run_robot(&["fill-key", "0", "808080", "--dry-run"]);
+        result.assert_success();
+        assert!(parse_dry_run_json(&result)["details"]["upload_rgb"].is_null());
+
+        let result = cli.run_robot(&[
+            "fill-key",
+            "0",
+            "808080",
+            "--color-space",
+            "device",
+            "--dry-run",
+        ]);
+        result.assert_success();
+        let json = parse_dry_run_json(&result);
+        assert_eq!(json["details"]["rgb"], serde_json::json!([128, 128, 128]));
+        // The panel's steeper curve is pre-compensated by brightening
+        let upload = json["details"]["upload_rgb"][0].as_u64().unwrap();
+        assert!(upload > 128, "{json}");
+    }
+
+    #[test]
+    fn dry_run_reports_keys_in_user_numbering() {
+        let cli = CliRunner::new();
+        let result = cli.run_robot(&["--key-base", "1", "fill-key", "1", "ff0000", "--dry-run"]);
+        result.assert_success();
+        assert_eq!(parse_dry_run_json(&result)["details"]["key"], 1);
+
+        let result = cli.run_robot(&["--key-base", "1", "clear-key", "0", "--dry-run"]);
+        result.assert_failure();
+        let json = result.json_stderr();
+        assert!(json["message"].as_str().unwrap().contains("--key-base 1"));
+    }
+
+    #[test]
+    fn set_keys_scope_uses_user_numbering() {
+        let cli = CliRunner::new();
+        let batch_dir = fixtures_path("images/batch/complete-6");
+        let dir = batch_dir.to_str().unwrap();
+        let planned = |scope: &[&str]| {
+            let mut args = vec![
+                "--key-base",
+                "1",
+                "set-keys",
+                dir,
+                "--dry-run",
+                "--assume-model",
+                "mini",
+            ];
+            args.extend_from_slice(scope);
+            let result = cli.run_robot(&args);
+            result.assert_success();
+            parse_dry_run_json(&result)["details"]["operations"]
+                .as_array()
+                .expect("operations")
+                .iter()
+                .map(|op| op["key"].as_u64().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // Keys 2-3 are the files for native keys 1 and 2
+        assert_eq!(planned(&["--key-range", "2-3"]), [2, 3]);
+        assert_eq!(planned(&["--start-key", "5"]), [5, 6]);
+        assert_eq!(planned(&["--key-range", "-3", "--start-key", "2"]), [2, 3]);
+    }
+
+    #[test]
+    fn fill_palette_reads_user_numbering() {
+        let cli = CliRunner::new();
+        let dir = tempfile::tempdir().expect("tempdir");
+        let palette = dir.path().join("palette.txt");
+        std::fs::write(&palette, "ff0000\n8: 00ff00\n").expect("write palette");
+
+        let result = cli.run_robot(&[
+            "--key-base",
+            "1",
+            "fill-palette",
+            palette.to_str().unwrap(),
+            "--dry-run",
+        ]);
+        result.assert_success();
+        let keys: Vec<_> = parse_dry_run_json(&result)["details"]["operations"]
+            .as_array()
+            .expect("operations")
+            .iter()
+            .map(|op| op["key"].as_u64().unwrap())
+            .collect();
+        assert_eq!(keys, [1, 8]);
+
+        std::fs::write(&palette, "0: ff0000\n").expect("write palette");
+        let result = cli.run_robot(&[
+            "--key-base",
+            "1",
+            "fill-palette",
+            palette.to_str().unwrap(),
+            "--dry-run",
+        ]);
+        result.assert_failure();
+    }
+
+    #[test]
+    fn dry_run_resumes_from_checkpoint() {
+        let cli = CliRunner::new();
+        let batch_dir = fixtures_path("images/batch/complete-6");
+        let dir = batch_dir.to_str().unwrap();
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let checkpoint = tmp.path().join("layout.checkpoint");
+        let recorded = |layout: &str| {
+            let file = json!({
+                "source": std::fs::canonicalize(&batch_dir).expect("canonical dir"),
+                "serial": "CL0000001",
+                "layout": layout,
+                "completed": [0, 1],
+            });
+            std::fs::write(&checkpoint, file.to_string()).expect("write checkpoint");
+        };
+        let plan = || {
+            cli.run_robot(&[
+                "set-keys",
+                dir,
+                "--dry-run",
+                "--assume-model",
+                "mini",
+                "--checkpoint",
+                checkpoint.to_str().unwrap(),
+            ])
+        };
+
+        recorded("3x2");
+        let result = plan();
+        result.assert_success();
+        let json = parse_dry_run_json(&result);
+        assert_eq!(json["details"]["resumed"], json!([0, 1]));
+        let planned: Vec<u64> = json["details"]["operations"]
+            .as_array()
+            .expect("operations")
+            .iter()
+            .map(|op| op["key"].as_u64().unwrap())
+            .collect();
+        assert_eq!(planned, [2, 3, 4, 5]);
+        assert_eq!(json["details"]["summary"]["unmatched"], 0);
+        // Planning leaves the checkpoint alone
+        assert!(
+            std::fs::read_to_string(&checkpoint)
+                .unwrap()
+                .contains("CL0000001")
+        );
+
+        // A checkpoint from another layout is refused
+        recorded("8x4");
+        let result = plan();
+        result.assert_failure();
+        assert!(
+            result.stderr.contains("layout 8x4"),
+            "stderr: {}",
+            result.stderr
+        );
+    }
+
+    #[test]
+    fn dry_run_plans_for_assumed_model() {
+        let cli = CliRunner::new();
+        let batch_dir = fixtures_path("images/batch/complete-6");
+        let dir = batch_dir.to_str().unwrap();
+
+        let result = cli.run_robot(&["set-keys", dir, "--dry-run", "--assume-model", "mini"]);
+        result.assert_success();
+        let json = parse_dry_run_json(&result);
+        assert_eq!(json["device"]["model"], "Stream Deck Mini");
+        assert_eq!(json["device"]["key_count"], 6);
+        assert_eq!(
+            json["device"]["key_dimensions"],
+            serde_json::json!([72, 72])
+        );
+        assert_eq!(json["device"]["connected"], false);
+        assert_eq!(json["details"]["summary"]["matching_files"], 6);
+
+        // Real runs still need the device
+        cli.run_robot(&["set-keys", dir, "--assume-model", "mini"])
+            .assert_failure();
+    }
+
     #[test]
     fn dry_run_has_action_field() {
         let cli = CliRunner::new();
@@ -246,6 +497,70 @@ mod robot_mode_dry_run_no_device {
         );
     }
 
+    #[test]
+    fn overlay_key_dry_run_reports_the_box() {
+        let cli = CliRunner::new();
+        let badge = fixtures_path("images/valid/nonsquare-100x80.png");
+        let result = cli.run_robot(&[
+            "overlay-key",
+            "0",
+            badge.to_str().unwrap(),
+            "--position",
+            "top-left",
+            "--scale",
+            "0.5",
+            "--dry-run",
+        ]);
+        result.assert_success();
+
+        let json = parse_dry_run_json(&result);
+        assert_eq!(json["action"], "overlay_key");
+        // 100x80 fitted into half of the offline (XL) 96x96 key
+        let region = &json["details"]["box"];
+        assert_eq!(
+            (region["x"].as_u64(), region["y"].as_u64()),
+            (Some(0), Some(0))
+        );
+        assert_eq!(region["width"], 48);
+        assert_eq!(region["height"], 38);
+        assert_eq!(json["details"]["base_known"], false);
+    }
+
+    #[test]
+    fn reset_dry_run_reports_the_baseline() {
+        let cli = CliRunner::new();
+        let result = cli.run_robot(&["reset", "--brightness", "40", "--dry-run"]);
+        result.assert_success();
+
+        let json = parse_dry_run_json(&result);
+        assert_eq!(json["action"], "reset");
+        assert_eq!(json["details"]["brightness"], 40);
+
+        cli.run_robot(&["reset", "--brightness", "101", "--dry-run"])
+            .assert_failure();
+    }
+
+    #[test]
+    fn demo_dimmer_dry_run_reports_the_bindings() {
+        let cli = CliRunner::new();
+        let result = cli.run_robot(&["--key-base", "1", "demo", "dimmer", "--dry-run"]);
+        result.assert_success();
+
+        let json = parse_dry_run_json(&result);
+        assert_eq!(json["action"], "demo_dimmer");
+        assert_eq!(json["details"]["dim_key"], 1);
+        assert_eq!(json["details"]["brighten_key"], 2);
+        assert!(json["details"]["current_brightness"].is_null());
+        assert_eq!(json["device"]["connected"], false);
+
+        let result = cli.run(&["demo", "dimmer", "--step", "25", "--dry-run"]);
+        result.assert_success();
+        result.assert_stdout_contains(
+            "DRY RUN: Would dim with key 0 and brighten with key 1 by 25% per press",
+        );
+        result.assert_stdout_contains("Device: not connected");
+    }
+
     #[test]
     fn dry_run_has_validation_warnings() {
         let cli = CliRunner::new();
@@ -275,6 +590,31 @@ mod robot_mode_dry_run_no_device {
             "Device should be marked as disconnected"
         );
     }
+
+    #[test]
+    fn dry_run_randomize_lists_reproducible_colors() {
+        let cli = CliRunner::new();
+        let run = || {
+            let result = cli.run_robot(&["fill-all", "--randomize", "--seed", "7", "--dry-run"]);
+            result.assert_success();
+            parse_dry_run_json(&result)
+        };
+        let json = run();
+        assert_eq!(json["details"]["scheme"], "random");
+        assert_eq!(json["details"]["seed"], 7);
+        let operations = json["details"]["operations"]
+            .as_array()
+            .expect("Expected operations array");
+        assert!(!operations.is_empty());
+        assert!(operations.iter().all(|op| op["color"].is_string()));
+        assert_eq!(json["details"], run()["details"]);
+
+        let result = cli.run_robot(&["rainbow", "--dry-run"]);
+        result.assert_success();
+        let json = parse_dry_run_json(&result);
+        assert_eq!(json["details"]["scheme"], "rainbow");
+        assert_eq!(json["details"]["operations"][0]["color"], "#ff0000");
+    }
 }
 
 // ============================================================================
@@ -915,6 +1255,106 @@ mod dry_run_output {
         );
     }
 
+    #[test]
+    fn set_key_dry_run_no_resize_warning() {
+        let cli = CliRunner::new();
+        let images = TestImages::create_batch(1, 72);
+        let image = images.path().join("key-0.png");
+        let image = image.to_str().expect("utf-8 path");
+
+        // 72px image vs 96px default key size: resize warning by default
+        let result = cli.run_robot_dry_run(&["set-key", "0", image]);
+        result.assert_success();
+        let warnings = result.json()["validation"]["warnings"].to_string();
+        assert!(warnings.contains("will be resized"), "{warnings}");
+
+        let result = cli.run_robot_dry_run(&["set-key", "0", image, "--no-resize-warning"]);
+        result.assert_success();
+        let json = result.json();
+        assert!(
+            !json["validation"]["warnings"]
+                .to_string()
+                .contains("will be resized")
+        );
+        // The resize itself is still reported as part of the plan
+        assert_eq!(json["details"]["processing"]["resize_needed"], true);
+    }
+
+    #[test]
+    fn fill_palette_dry_run_lists_mapping() {
+        let cli = CliRunner::new();
+        let dir = tempfile::tempdir().expect("tempdir");
+        let palette = dir.path().join("palette.txt");
+        std::fs::write(&palette, "# warm\nff0000\nFF8000\n8: #00ff00\n").expect("write palette");
+
+        let result = cli.run_robot_dry_run(&["fill-palette", palette.to_str().unwrap()]);
+        result.assert_success();
+
+        let json = result.json();
+        assert_eq!(json["would_succeed"], true);
+        let ops = json["details"]["operations"]
+            .as_array()
+            .expect("operations");
+        let mapping: Vec<_> = ops
+            .iter()
+            .map(|op| (op["key"].as_u64().unwrap(), op["color"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            mapping,
+            vec![(0, "#ff0000"), (1, "#ff8000"), (8, "#00ff00")]
+        );
+    }
+
+    #[test]
+    fn fill_palette_dry_run_reports_invalid_color() {
+        let cli = CliRunner::new();
+        let dir = tempfile::tempdir().expect("tempdir");
+        let palette = dir.path().join("palette.txt");
+        std::fs::write(&palette, "ff0000\nnotacolor\n").expect("write palette");
+
+        let result = cli.run_robot_dry_run(&["fill-palette", palette.to_str().unwrap()]);
+        result.assert_success();
+
+        let json = result.json();
+        assert_eq!(json["would_succeed"], false);
+        assert_eq!(json["details"]["operations"][1]["would_succeed"], false);
+        assert_eq!(json["validation"]["errors"][0]["field"], "palette[line 2]");
+    }
+
+    #[test]
+    fn fill_from_image_dry_run_lists_colors() {
+        let cli = CliRunner::new();
+        let dir = tempfile::tempdir().expect("tempdir");
+        let source = dir.path().join("halves.png");
+        image::RgbImage::from_fn(80, 40, |x, _| {
+            if x < 40 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 0, 255])
+            }
+        })
+        .save(&source)
+        .expect("write image");
+
+        let result = cli.run_robot_dry_run(&[
+            "fill-from-image",
+            source.to_str().unwrap(),
+            "--mode",
+            "dominant",
+        ]);
+        result.assert_success();
+
+        let json = result.json();
+        assert_eq!(json["details"]["mode"], "dominant");
+        let ops = json["details"]["operations"]
+            .as_array()
+            .expect("operations");
+        // Offline, the XL's 8x4 grid is assumed
+        assert_eq!(ops.len(), 32);
+        assert_eq!(ops[0]["color"], "#ff0000");
+        assert_eq!(ops[7]["color"], "#0000ff");
+    }
+
     #[test]
     fn brightness_dry_run_shows_disconnected() {
         let cli = CliRunner::new();
@@ -930,4 +1370,134 @@ mod dry_run_output {
             "Dry run should indicate device is disconnected"
         );
     }
+
+    #[test]
+    fn dry_run_default_from_settings_file() {
+        let home = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(home.path().join("sd")).unwrap();
+        std::fs::write(
+            home.path().join("sd/config.yaml"),
+            "default_dry_run: true\n",
+        )
+        .unwrap();
+
+        let cli = CliRunner::new().with_env("XDG_CONFIG_HOME", home.path().to_str().unwrap());
+        let result = cli.run_robot(&["brightness", "50"]);
+        result.assert_success();
+        assert_eq!(result.json()["dry_run"], true);
+    }
+
+    #[test]
+    fn offline_key_size_from_default_model() {
+        let home = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(home.path().join("sd")).unwrap();
+        std::fs::write(
+            home.path().join("sd/config.yaml"),
+            "default_model: StreamDeckMK2\n",
+        )
+        .unwrap();
+        let images = TestImages::create_batch(1, 72);
+        let image = images.path().join("key-0.png");
+
+        let cli = CliRunner::new().with_env("XDG_CONFIG_HOME", home.path().to_str().unwrap());
+        let result = cli.run_robot_dry_run(&["set-key", "0", image.to_str().unwrap()]);
+        result.assert_success();
+        let json = result.json();
+        if json["device"]["connected"] == true {
+            return; // A real device's key size wins over the default model
+        }
+        assert_eq!(
+            json["details"]["processing"]["target_dimensions"],
+            serde_json::json!([72, 72])
+        );
+        assert_eq!(json["details"]["processing"]["resize_needed"], false);
+    }
+
+    /// A CLI whose settings file turns on `default_dry_run`; the home
+    /// directory is returned so it outlives the runner.
+    fn dry_run_by_default() -> (tempfile::TempDir, CliRunner) {
+        let home = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(home.path().join("config/sd")).unwrap();
+        std::fs::write(
+            home.path().join("config/sd/config.yaml"),
+            "default_dry_run: true\n",
+        )
+        .unwrap();
+        let cli = CliRunner::new()
+            .with_env(
+                "XDG_CONFIG_HOME",
+                home.path().join("config").to_str().unwrap(),
+            )
+            .with_env("XDG_DATA_HOME", home.path().join("data").to_str().unwrap());
+        (home, cli)
+    }
+
+    #[test]
+    fn fill_all_respects_default_dry_run() {
+        let (_home, cli) = dry_run_by_default();
+        let result = cli.run_robot(&["fill-all", "#ff0000"]);
+        result.assert_success();
+        let json = result.json();
+        assert_eq!(json["dry_run"], true);
+        assert_eq!(json["action"], "fill_all");
+        assert_eq!(json["details"]["color"], "#ff0000");
+    }
+
+    #[test]
+    fn fill_keys_respects_default_dry_run() {
+        let (_home, cli) = dry_run_by_default();
+        let result = cli.run_robot(&["fill-keys", "#00ff00", "--keys", "1", "2"]);
+        result.assert_success();
+        let json = result.json();
+        assert_eq!(json["dry_run"], true);
+        assert_eq!(json["action"], "fill_keys");
+        assert_eq!(json["details"]["keys"], json!([1, 2]));
+    }
+
+    #[test]
+    fn restore_respects_default_dry_run() {
+        use sd::snapshot::{Snapshot, SnapshotDb, SnapshotKey};
+
+        let (home, cli) = dry_run_by_default();
+        let mut db = SnapshotDb::open(home.path().join("data/sd/snapshots/snapshots.db")).unwrap();
+        let mut snap = Snapshot::new("desk".to_string(), "StreamDeckXL".to_string(), 32, 96, 96)
+            .with_brightness(40);
+        snap.add_key(SnapshotKey::color(3, "#0000ff".to_string()));
+        db.save_snapshot(&snap).unwrap();
+
+        let result = cli.run_robot(&["restore", "desk"]);
+        result.assert_success();
+        let json = result.json();
+        assert_eq!(json["dry_run"], true);
+        assert_eq!(json["action"], "restore");
+        assert_eq!(json["details"]["brightness"], 40);
+        assert_eq!(json["details"]["keys"][0]["key"], 3);
+        assert_eq!(json["details"]["keys"][0]["hex"], "#0000ff");
+    }
+
+    #[test]
+    fn demo_dimmer_respects_default_dry_run() {
+        let (_home, cli) = dry_run_by_default();
+        let result = cli.run_robot(&["demo", "dimmer", "--step", "20"]);
+        result.assert_success();
+        let json = result.json();
+        assert_eq!(json["dry_run"], true);
+        assert_eq!(json["action"], "demo_dimmer");
+        assert_eq!(json["details"]["step"], 20);
+    }
+
+    #[test]
+    fn dry_run_default_from_env() {
+        let cli = CliRunner::new().with_env("SD_DRY_RUN", "true");
+        let result = cli.run_robot(&["brightness", "50"]);
+        result.assert_success();
+        assert_eq!(result.json()["dry_run"], true);
+    }
+
+    #[test]
+    fn no_dry_run_flag_is_accepted() {
+        let cli = CliRunner::new();
+        let result = cli.run(&["--help"]);
+        result.assert_stdout_contains("--no-dry-run");
+    }
 }
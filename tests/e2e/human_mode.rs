@@ -41,3 +41,25 @@ fn no_color_disables_ansi_and_uses_ascii_box() {
     // NO_COLOR should force safe_box mode which uses ASCII boxes.
     assert_has_ascii_box(stdout);
 }
+
+#[test]
+fn width_override_wraps_error_panels() {
+    init_test_logging();
+    let cli = CliRunner::new()
+        .with_env("RUST_LOG", "off")
+        .with_env("NO_COLOR", "1");
+    let result = cli.run(&["--width", "40", "fill-key", "0", "nothex"]);
+    result.assert_failure();
+
+    let output = format!("{}{}", result.stdout, result.stderr);
+    assert_has_ascii_box(&output);
+    for line in output.lines() {
+        assert!(
+            line.chars().count() <= 40,
+            "line wider than --width 40: {line:?}"
+        );
+    }
+
+    // Too narrow for a panel's borders
+    cli.run(&["--width", "10", "version"]).assert_failure();
+}
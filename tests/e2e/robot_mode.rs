@@ -24,16 +24,407 @@ fn robot_quick_start_outputs_json() {
 }
 
 #[test]
-fn robot_list_outputs_json_array() {
+fn list_commands_matches_cli() {
+    init_test_logging();
+    let result = CliRunner::new().run(&["--list-commands"]);
+    result.assert_success();
+
+    let commands = parse_json(result.stdout.trim());
+    let names: Vec<&str> = commands
+        .as_array()
+        .expect("command array")
+        .iter()
+        .filter_map(|c| c["command"].as_str())
+        .collect();
+    assert!(names.contains(&"sd list"), "{names:?}");
+    assert!(names.contains(&"sd snapshot compare"), "{names:?}");
+    let brightness = commands
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["command"] == "sd brightness")
+        .expect("brightness command");
+    assert!(
+        brightness["args"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|a| a["positional"] == true)
+    );
+}
+
+#[test]
+fn robot_list_outputs_grouped_object() {
     init_test_logging();
     let cli = CliRunner::new();
     let result = cli.run_robot(&["list"]);
     result.assert_success();
 
+    let json = parse_json(result.stdout.trim());
+    let devices = json["devices"]
+        .as_array()
+        .expect("Expected devices array in device list");
+    assert_eq!(json["count"], devices.len());
+    if devices.len() == 1 {
+        assert_eq!(json["default"], devices[0]["serial"]);
+    } else {
+        assert!(json["default"].is_null());
+    }
+}
+
+#[test]
+fn robot_list_json_compat_v2_outputs_json_array() {
+    init_test_logging();
+    let cli = CliRunner::new();
+    let result = cli.run_robot(&["--json-compat", "v2", "list"]);
+    result.assert_success();
+
     let json = parse_json(result.stdout.trim());
     assert!(json.is_array(), "Expected JSON array for device list");
 }
 
+#[test]
+fn robot_list_with_timeout_is_stable() {
+    init_test_logging();
+    let cli = CliRunner::new();
+    let first = cli.run_robot(&["list", "--long", "--timeout", "5000"]);
+    first.assert_success();
+    let second = cli.run_robot(&["list", "--long", "--timeout", "5000"]);
+    second.assert_success();
+
+    let serials = |stdout: &str| -> Vec<String> {
+        parse_json(stdout.trim())["devices"]
+            .as_array()
+            .expect("Expected devices array in device list")
+            .iter()
+            .map(|d| d["serial"].as_str().unwrap_or_default().to_string())
+            .collect()
+    };
+    let order = serials(&first.stdout);
+    assert_eq!(order, serials(&second.stdout));
+    assert!(order.windows(2).all(|w| w[0] <= w[1]), "{order:?}");
+}
+
+#[test]
+fn robot_emit_complete_trails_the_result() {
+    init_test_logging();
+    let cli = CliRunner::new();
+    let result = cli.run_robot(&["list", "--emit-complete"]);
+    result.assert_success();
+    let last = parse_json(result.stdout.lines().last().unwrap_or_default());
+    assert_eq!(last["event"], "complete");
+    assert_eq!(last["command"], "list");
+    assert_eq!(last["ok"], true);
+    assert!(last["duration_ms"].is_u64());
+
+    let failed = cli.run_robot(&["fill-key", "0", "nothex", "--emit-complete"]);
+    failed.assert_failure();
+    let last = parse_json(failed.stdout.lines().last().unwrap_or_default());
+    assert_eq!(last["command"], "fill-key");
+    assert_eq!(last["ok"], false);
+
+    // Opt-in only
+    let plain = cli.run_robot(&["list"]);
+    assert!(!plain.stdout.contains("\"complete\""));
+}
+
+#[test]
+fn serve_stdio_speaks_json_rpc() {
+    init_test_logging();
+    let cli = CliRunner::new().with_stdin(concat!(
+        r#"{"jsonrpc": "2.0", "method": "version", "id": 1}"#,
+        "\n",
+        r#"[{"jsonrpc": "2.0", "method": "nope", "id": 2}, {"jsonrpc": "2.0", "method": "version"}]"#,
+        "\n",
+        r#"{"jsonrpc": "2.0", "method": "brightness", "params": {"level": "max"}, "id": 3}"#,
+        "\n",
+    ));
+    let result = cli.run(&["serve", "--stdio"]);
+    result.assert_success();
+
+    let lines: Vec<Value> = result.stdout.lines().map(parse_json).collect();
+    assert_eq!(lines.len(), 3, "{}", result.stdout);
+    assert_eq!(lines[0]["id"], 1);
+    assert!(lines[0]["result"]["version"].is_string());
+    assert_eq!(lines[1].as_array().map(Vec::len), Some(1));
+    assert_eq!(lines[1][0]["error"]["code"], -32601);
+    assert_eq!(lines[2]["error"]["code"], -32602);
+}
+
+#[test]
+fn serve_stdio_requires_auth_with_token() {
+    init_test_logging();
+    let cli = CliRunner::new()
+        .with_env("SD_SERVE_TOKEN", "s3cret")
+        .with_stdin(concat!(
+            r#"{"jsonrpc": "2.0", "method": "version", "id": 1}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "method": "auth", "params": {"token": "guess"}, "id": 2}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "method": "auth", "params": {"token": "s3cret"}, "id": 3}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "method": "version", "id": 4}"#,
+            "\n",
+        ));
+    let result = cli.run(&["serve", "--stdio"]);
+    result.assert_success();
+
+    let lines: Vec<Value> = result.stdout.lines().map(parse_json).collect();
+    assert_eq!(lines.len(), 4, "{}", result.stdout);
+    assert_eq!(lines[0]["error"]["code"], -32001);
+    assert_eq!(lines[1]["error"]["code"], -32001);
+    assert_eq!(lines[2]["result"]["authenticated"], true);
+    assert!(lines[3]["result"]["version"].is_string());
+}
+
+#[test]
+fn serve_rejects_an_empty_token() {
+    init_test_logging();
+    let cli = CliRunner::new();
+    for token in ["", "  "] {
+        let result = cli.run(&["serve", "--stdio", "--token", token]);
+        assert_eq!(result.exit_code, 2, "{token:?}: {}", result.stderr);
+        assert!(
+            result.stderr.contains("can't be empty"),
+            "{}",
+            result.stderr
+        );
+    }
+}
+
+#[test]
+fn serve_rpc_keys_follow_key_numbering() {
+    init_test_logging();
+    let result = CliRunner::new()
+        .with_env("SD_SERIAL", "no-such-deck")
+        .with_stdin(concat!(
+            r#"{"jsonrpc": "2.0", "method": "clear-key", "params": {"key": 0}, "id": 1}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "method": "fill-key", "params": {"key": 0, "color": "ff0000"}, "id": 2}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "method": "overlay-key", "params": {"key": 1, "image": "a.png", "position": "sideways"}, "id": 3}"#,
+            "\n",
+        ))
+        .run(&["--key-base", "1", "serve", "--stdio"]);
+    result.assert_success();
+
+    let lines: Vec<Value> = result.stdout.lines().map(parse_json).collect();
+    assert_eq!(lines.len(), 3, "{}", result.stdout);
+    for line in &lines[..2] {
+        let message = line["error"]["message"].as_str().unwrap_or_default();
+        assert!(message.contains("--key-base 1"), "{line}");
+    }
+    // Key 1 is valid with --key-base 1; the CLI's own parser rejects the position
+    assert_eq!(lines[2]["error"]["code"], -32602, "{}", lines[2]);
+    let message = lines[2]["error"]["message"].as_str().unwrap_or_default();
+    assert!(message.contains("sideways"), "{message}");
+}
+
+#[test]
+fn serve_hello_lists_answered_methods() {
+    init_test_logging();
+    let hello = CliRunner::new()
+        .with_stdin("{\"jsonrpc\": \"2.0\", \"method\": \"hello\", \"id\": 1}\n")
+        .run(&["serve", "--stdio"]);
+    hello.assert_success();
+    let result = parse_json(hello.stdout.trim())["result"].clone();
+    assert_eq!(result["protocol_version"], 1);
+    assert_eq!(result["notifications"], serde_json::json!(["context"]));
+
+    // Every advertised method is one the server actually answers
+    let methods = result["methods"].as_array().expect("methods");
+    let requests: String = methods
+        .iter()
+        .enumerate()
+        .map(|(id, method)| {
+            format!("{{\"jsonrpc\": \"2.0\", \"method\": {method}, \"id\": {id}}}\n")
+        })
+        .collect();
+    // A serial no deck has keeps device methods from touching real hardware
+    let calls = CliRunner::new()
+        .with_env("SD_SERIAL", "no-such-deck")
+        .with_stdin(&requests)
+        .run(&["serve", "--stdio"]);
+    let lines: Vec<Value> = calls.stdout.lines().map(parse_json).collect();
+    assert_eq!(lines.len(), methods.len(), "{}", calls.stdout);
+    for line in &lines {
+        assert_ne!(line["error"]["code"], -32601, "{line}");
+    }
+}
+
+#[test]
+fn state_reports_tracked_changes() {
+    init_test_logging();
+    let cli = CliRunner::new();
+    let result = cli.run_robot(&["state"]);
+    result.assert_success();
+    let json = parse_json(result.stdout.trim());
+    assert_eq!(json["source"], "session");
+    assert!(json["note"].as_str().unwrap().contains("not read back"));
+    assert!(json["brightness"].is_null());
+    assert_eq!(json["keys"], serde_json::json!([]));
+
+    let result = cli.run_robot(&["state", "reset"]);
+    result.assert_success();
+    assert!(
+        parse_json(result.stdout.trim())["message"]
+            .as_str()
+            .unwrap()
+            .contains("device is unchanged")
+    );
+
+    let cli = cli.with_stdin(concat!(
+        r#"{"jsonrpc": "2.0", "method": "state", "id": 1}"#,
+        "\n",
+        r#"{"jsonrpc": "2.0", "method": "reset-state", "id": 2}"#,
+        "\n",
+    ));
+    let result = cli.run(&["serve", "--stdio"]);
+    result.assert_success();
+    let lines: Vec<Value> = result.stdout.lines().map(parse_json).collect();
+    assert_eq!(lines[0]["result"]["summary"]["total_keys"], 0);
+    assert_eq!(lines[1]["result"]["reset"], true);
+}
+
+#[test]
+fn context_push_records_the_profile_for_serve() {
+    init_test_logging();
+    let home = tempfile::tempdir().expect("tempdir");
+    let config_home = home.path().join("config");
+    let data_home = home.path().join("data");
+    let cli = CliRunner::new()
+        .with_env("XDG_CONFIG_HOME", config_home.to_str().unwrap())
+        .with_env("XDG_DATA_HOME", data_home.to_str().unwrap());
+
+    let result = cli.run_robot(&["context", "firefox"]);
+    result.assert_failure();
+    result.assert_stderr_contains("contexts");
+
+    std::fs::create_dir_all(config_home.join("sd")).expect("create config dir");
+    std::fs::write(
+        config_home.join("sd/config.yaml"),
+        "contexts:\n  firefox: browser.yaml\n",
+    )
+    .expect("write settings");
+
+    let result = cli.run_robot(&["context", "code"]);
+    result.assert_failure();
+    result.assert_stderr_contains("firefox");
+
+    let result = cli.run_robot(&["context", "firefox"]);
+    result.assert_success();
+    let json = parse_json(result.stdout.trim());
+    assert_eq!(json["context"], "firefox");
+    assert!(json["profile"].as_str().unwrap().ends_with("browser.yaml"));
+
+    let pushed = std::fs::read_to_string(data_home.join("sd/context.json")).expect("context file");
+    assert_eq!(parse_json(&pushed)["name"], "firefox");
+}
+
+#[test]
+fn idle_reads_watch_activity() {
+    init_test_logging();
+    let home = tempfile::tempdir().expect("tempdir");
+    let cli = CliRunner::new().with_env("XDG_DATA_HOME", home.path().to_str().unwrap());
+
+    // No tracker running
+    let result = cli.run_robot(&["idle"]);
+    result.assert_failure();
+    result.assert_stderr_contains("sd watch");
+
+    let now = chrono::Utc::now();
+    let record = serde_json::json!({
+        "pid": 1,
+        "started_at": now - chrono::Duration::seconds(120),
+        "last_press_at": now - chrono::Duration::seconds(30),
+        "heartbeat_at": now,
+    });
+    let dir = home.path().join("sd");
+    std::fs::create_dir_all(&dir).expect("create data dir");
+    std::fs::write(dir.join("activity.json"), record.to_string()).expect("write activity");
+
+    let result = cli.run_robot(&["idle"]);
+    result.assert_success();
+    let idle = parse_json(result.stdout.trim())["idle_seconds"]
+        .as_u64()
+        .expect("idle_seconds");
+    assert!((30..40).contains(&idle), "{idle}");
+}
+
+#[test]
+fn explain_adds_explanation_to_errors() {
+    init_test_logging();
+    let cli = CliRunner::new();
+
+    let result = cli.run_robot(&["apply", "/nonexistent/sd-layout.yaml"]);
+    result.assert_failure();
+    assert!(result.json_stderr().get("explanation").is_none());
+
+    let result = cli.run_robot(&["apply", "/nonexistent/sd-layout.yaml", "--explain"]);
+    result.assert_failure();
+    let explanation = result.json_stderr()["explanation"]
+        .as_str()
+        .expect("explanation")
+        .to_string();
+    assert!(explanation.contains("sd init"), "{explanation}");
+}
+
+#[test]
+fn set_key_resolves_icon_names() {
+    init_test_logging();
+    let data = tempfile::tempdir().expect("tempdir");
+    let icon = data
+        .path()
+        .join("icons/hicolor/scalable/apps/sd-test-icon.svg");
+    std::fs::create_dir_all(icon.parent().unwrap()).expect("create theme dir");
+    std::fs::write(
+        &icon,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16"/>"#,
+    )
+    .expect("write icon");
+    let cli = CliRunner::new()
+        .with_env("XDG_DATA_DIRS", data.path().to_str().unwrap())
+        .with_env("SD_ICON_THEME", "sd-test-theme");
+
+    let result = cli.run_robot(&["set-key", "0", "icon:sd-test-icon", "--dry-run"]);
+    result.assert_success();
+    let json = parse_json(result.stdout.trim());
+    assert_eq!(
+        json["details"]["source"]["path"],
+        icon.display().to_string()
+    );
+
+    let result = cli.run_robot(&["set-key", "0", "icon:sd-no-such-icon", "--dry-run"]);
+    result.assert_failure();
+    result.assert_stderr_contains("sd-test-theme, hicolor");
+}
+
+#[test]
+fn set_key_accepts_data_uris() {
+    init_test_logging();
+    // A 3x2 red PNG
+    let uri = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAMAAAACCAIAAAASFvFNAAAAEElEQVR4nGP4z8AAQQxwFgBB0gX7h/C5SAAAAABJRU5ErkJggg==";
+    let cli = CliRunner::new();
+
+    let result = cli.run_robot(&["set-key", "0", uri, "--dry-run"]);
+    result.assert_success();
+    let json = parse_json(result.stdout.trim());
+    assert_eq!(json["details"]["source"]["path"], "data-uri");
+    assert_eq!(json["details"]["source"]["format"], "png");
+    assert_eq!(
+        json["details"]["source"]["dimensions"],
+        serde_json::json!([3, 2])
+    );
+
+    let result = cli.run_robot(&["set-key", "0", "data:text/plain;base64,aGk=", "--dry-run"]);
+    result.assert_failure();
+    result.assert_stderr_contains("not a supported image type");
+
+    let result = cli.run_robot(&["set-key", "0", "data:image/png;base64,aGk=", "--dry-run"]);
+    result.assert_failure();
+    result.assert_stderr_contains("Couldn't decode the data URI's png image");
+}
+
 #[test]
 fn robot_format_flag_outputs_json() {
     init_test_logging();
@@ -45,6 +436,30 @@ fn robot_format_flag_outputs_json() {
     assert!(json.get("version").is_some());
 }
 
+#[test]
+fn compact_flag_overrides_pretty_default() {
+    init_test_logging();
+    let cli = CliRunner::new();
+    let result = cli.run(&["version", "--format=json", "--compact"]);
+    result.assert_success();
+
+    let stdout = result.stdout.trim();
+    assert_eq!(stdout.lines().count(), 1, "Expected single-line JSON");
+    assert!(parse_json(stdout).get("version").is_some());
+}
+
+#[test]
+fn pretty_flag_overrides_json_compact_format() {
+    init_test_logging();
+    let cli = CliRunner::new();
+    let result = cli.run(&["version", "--format=json-compact", "--pretty"]);
+    result.assert_success();
+
+    let stdout = result.stdout.trim();
+    assert!(stdout.lines().count() > 1, "Expected multi-line JSON");
+    assert!(parse_json(stdout).get("version").is_some());
+}
+
 #[test]
 fn robot_error_includes_suggestion_or_device_info() {
     init_test_logging();
@@ -65,3 +480,710 @@ fn robot_error_includes_suggestion_or_device_info() {
     assert!(json.get("message").is_some());
     assert!(json.get("suggestion").is_some());
 }
+
+#[test]
+fn apply_export_images_without_device() {
+    init_test_logging();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let config = dir.path().join("layout.yaml");
+    std::fs::write(
+        &config,
+        "keys:\n  \"0\":\n    color: \"#ff0000\"\n  \"1\":\n    clear: true\n",
+    )
+    .expect("write config");
+    let out = dir.path().join("out");
+
+    let cli = CliRunner::new();
+    let result = cli.run_robot(&[
+        "apply",
+        config.to_str().unwrap(),
+        "--export-images",
+        out.to_str().unwrap(),
+        "--no-upload",
+        "--model",
+        "mini",
+    ]);
+    result.assert_success();
+
+    let json = parse_json(result.stdout.trim());
+    assert_eq!(json["uploaded"], false);
+    assert_eq!(json["exported"].as_array().map(Vec::len), Some(2));
+
+    let swatch = image::open(out.join("key-0.png")).expect("exported swatch");
+    assert_eq!((swatch.width(), swatch.height()), (72, 72));
+    assert_eq!(swatch.to_rgb8().get_pixel(0, 0).0, [255, 0, 0]);
+    assert!(out.join("key-1.png").exists());
+}
+
+#[test]
+fn apply_replace_dry_run_lists_uncovered_keys() {
+    init_test_logging();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let config = dir.path().join("layout.yaml");
+    std::fs::write(
+        &config,
+        "keys:\n  row-0:\n    color: \"#0000ff\"\n  \"4\":\n    clear: true\n",
+    )
+    .expect("write config");
+
+    let cli = CliRunner::new();
+    let result = cli.run_robot(&[
+        "apply",
+        config.to_str().unwrap(),
+        "--replace",
+        "--dry-run",
+        "--model",
+        "mini",
+    ]);
+    result.assert_success();
+
+    let json = parse_json(result.stdout.trim());
+    if !json["device"].is_null() {
+        // A connected device decides the layout instead of --model
+        return;
+    }
+    assert_eq!(json["replace"], true);
+    assert_eq!(json["would_clear"], serde_json::json!([3, 5]));
+}
+
+#[test]
+fn apply_to_mock_dumps_state() {
+    init_test_logging();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let config = dir.path().join("layout.yaml");
+    std::fs::write(
+        &config,
+        "brightness: 60\nkeys:\n  row-0:\n    color: \"#00ff00\"\n  \"4\":\n    color: \"#0000ff\"\n",
+    )
+    .expect("write config");
+    let state = dir.path().join("state.json");
+
+    let cli = CliRunner::new();
+    let result = cli.run_robot(&[
+        "apply",
+        config.to_str().unwrap(),
+        "--mock",
+        "mini",
+        "--dump-state",
+        state.to_str().unwrap(),
+    ]);
+    result.assert_success();
+    assert_eq!(parse_json(result.stdout.trim())["mock"], true);
+
+    let dump = parse_json(&std::fs::read_to_string(&state).expect("state file"));
+    assert_eq!(dump["brightness"], 60);
+    let states: Vec<&str> = dump["keys"]
+        .as_array()
+        .expect("keys")
+        .iter()
+        .map(|k| k["color"].as_str().unwrap_or("clear"))
+        .collect();
+    assert_eq!(
+        states,
+        ["#00ff00", "#00ff00", "#00ff00", "clear", "#0000ff", "clear"]
+    );
+}
+
+#[test]
+fn apply_reports_the_winning_selector() {
+    init_test_logging();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let config = dir.path().join("layout.yaml");
+    std::fs::write(
+        &config,
+        "keys:\n  default:\n    clear: true\n  row-0:\n    color: \"#00ff00\"\n  \"1\":\n    color: \"#ff0000\"\n",
+    )
+    .expect("write config");
+
+    let cli = CliRunner::new();
+    let result = cli.run_robot(&["apply", config.to_str().unwrap(), "--mock", "mini"]);
+    result.assert_success();
+    let json = parse_json(result.stdout.trim());
+    let results = json["results"].as_array().expect("results");
+    // One result per key, from its most specific selector
+    assert_eq!(results.len(), 6);
+    let selectors: Vec<&str> = results
+        .iter()
+        .map(|r| r["selector"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        selectors,
+        ["row-0", "1", "row-0", "default", "default", "default"]
+    );
+    assert_eq!(results[1]["color"], "#ff0000");
+}
+
+#[cfg(not(windows))]
+#[test]
+fn apply_hook_is_skipped_when_a_key_fails() {
+    init_test_logging();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let config = dir.path().join("layout.yaml");
+    std::fs::write(
+        &config,
+        "keys:\n  \"0\":\n    image: missing.png\n  \"1\":\n    color: \"#ff0000\"\n",
+    )
+    .expect("write config");
+    let marker = dir.path().join("hook-ran");
+    let after = format!("touch {}", marker.display());
+
+    let cli = CliRunner::new();
+    let result = cli.run_robot(&[
+        "apply",
+        config.to_str().unwrap(),
+        "--mock",
+        "mini",
+        "--after",
+        &after,
+    ]);
+    result.assert_failure();
+    let json = parse_json(result.stdout.trim());
+    assert_eq!(json["summary"]["failed"], 1, "{json}");
+    assert_eq!(json["hook"]["skipped"], "keys_failed");
+    assert!(!marker.exists(), "hook ran after a failed key");
+}
+
+#[test]
+fn apply_reports_write_verification() {
+    init_test_logging();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let config = dir.path().join("layout.yaml");
+    std::fs::write(&config, "keys:\n  \"1\":\n    color: \"#ff0000\"\n").expect("write config");
+    let report = dir.path().join("report.json");
+
+    let cli = CliRunner::new();
+    let result = cli.run_robot(&[
+        "apply",
+        config.to_str().unwrap(),
+        "--mock",
+        "mini",
+        "--verify-writes",
+        "--emit-complete",
+        "--report",
+        report.to_str().unwrap(),
+    ]);
+    result.assert_success();
+    assert!(
+        result.stdout.contains("--verify-writes has no effect"),
+        "{}",
+        result.stdout
+    );
+    // The mock, like every real deck, can't read images back
+    let last = parse_json(result.stdout.lines().last().unwrap_or_default());
+    let stats = &last["write_verification"];
+    assert_eq!(stats["supported"], false);
+    assert_eq!(stats["verified"], 0);
+    assert!(stats["writes"].as_u64().unwrap() > 0, "{stats}");
+    assert_eq!(stats["unverified"], stats["writes"]);
+
+    let report: Value =
+        serde_json::from_str(&std::fs::read_to_string(&report).expect("report written"))
+            .expect("report is JSON");
+    assert_eq!(&report["write_verification"], stats);
+
+    // Only reported when asked for
+    let plain = cli.run_robot(&[
+        "apply",
+        config.to_str().unwrap(),
+        "--mock",
+        "mini",
+        "--emit-complete",
+    ]);
+    let last = parse_json(plain.stdout.lines().last().unwrap_or_default());
+    assert!(last.get("write_verification").is_none());
+    assert!(!plain.stdout.contains("--verify-writes"));
+}
+
+#[test]
+fn apply_stream_emits_a_line_per_key() {
+    init_test_logging();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let config = dir.path().join("layout.yaml");
+    std::fs::write(
+        &config,
+        "keys:\n  default:\n    clear: true\n  \"1\":\n    color: \"#ff0000\"\n",
+    )
+    .expect("write config");
+
+    let cli = CliRunner::new();
+    let result = cli.run_robot(&[
+        "apply",
+        config.to_str().unwrap(),
+        "--mock",
+        "mini",
+        "--stream",
+        "--compact",
+    ]);
+    result.assert_success();
+    let lines: Vec<Value> = result.stdout.lines().map(parse_json).collect();
+    assert_eq!(lines.len(), 7, "{}", result.stdout);
+    // Most specific selector first, then the summary as usual
+    assert_eq!(lines[0]["event"], "key");
+    assert_eq!(lines[0]["command"], "apply");
+    assert_eq!(lines[0]["key"], 1);
+    assert_eq!(lines[0]["color"], "#ff0000");
+    assert!(lines[1..6].iter().all(|line| line["selector"] == "default"));
+    assert_eq!(lines[6]["command"], "apply");
+    assert_eq!(lines[6]["results"].as_array().map(Vec::len), Some(6));
+}
+
+#[test]
+fn apply_dedupe_images_renders_a_shared_icon_once() {
+    init_test_logging();
+    let dir = tempfile::tempdir().expect("tempdir");
+    image::RgbImage::from_pixel(100, 80, image::Rgb([30, 144, 255]))
+        .save(dir.path().join("icon.png"))
+        .expect("write icon");
+    let config = dir.path().join("layout.yaml");
+    std::fs::write(&config, "keys:\n  default:\n    image: icon.png\n").expect("write config");
+
+    let cli = CliRunner::new();
+    let result = cli.run_robot(&[
+        "--no-cache",
+        "apply",
+        config.to_str().unwrap(),
+        "--mock",
+        "mini",
+        "--dedupe-images",
+    ]);
+    result.assert_success();
+    let json = parse_json(&result.stdout);
+    assert_eq!(json["summary"]["success"], 6, "{json}");
+    assert_eq!(json["dedupe"]["unique_images"], 1);
+    assert_eq!(json["dedupe"]["reused"], 5);
+}
+
+#[test]
+fn apply_uses_the_variant_for_the_model() {
+    init_test_logging();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let config = dir.path().join("layout.yaml");
+    std::fs::write(
+        &config,
+        "keys:\n  \"0\":\n    color: \"#ff0000\"\n    variants:\n      mini:\n        color: \"#0000ff\"\n      xl:\n        color: \"#00ff00\"\n",
+    )
+    .expect("write config");
+
+    let cli = CliRunner::new();
+    for (model, color) in [("mini", "#0000ff"), ("mk2", "#ff0000")] {
+        let result = cli.run_robot(&["apply", config.to_str().unwrap(), "--mock", model]);
+        result.assert_success();
+        let json = parse_json(result.stdout.trim());
+        assert_eq!(json["results"][0]["color"], color, "{model}");
+    }
+}
+
+#[test]
+fn snapshot_on_apply_needs_a_real_device() {
+    init_test_logging();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let config = dir.path().join("layout.yaml");
+    std::fs::write(&config, "keys:\n  \"0\":\n    color: \"#00ff00\"\n").expect("write config");
+
+    // A mock has nothing worth backing up
+    let cli = CliRunner::new();
+    let result = cli.run(&[
+        "apply",
+        config.to_str().unwrap(),
+        "--mock",
+        "mini",
+        "--snapshot-on-apply",
+    ]);
+    result.assert_failure();
+    result.assert_stderr_contains("--snapshot-on-apply");
+}
+
+#[test]
+fn config_keys_resolves_most_specific_selector() {
+    init_test_logging();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let config = dir.path().join("layout.yaml");
+    std::fs::write(
+        &config,
+        "keys:\n  default:\n    clear: true\n  row-0:\n    color: \"#0000ff\"\n  \"1\":\n    color: \"#ff0000\"\n",
+    )
+    .expect("write config");
+
+    let cli = CliRunner::new();
+    let result = cli.run_robot(&[
+        "config",
+        "keys",
+        config.to_str().unwrap(),
+        "--model",
+        "mini",
+    ]);
+    result.assert_success();
+
+    let json = parse_json(result.stdout.trim());
+    let keys = json.as_array().expect("array of keys");
+    assert_eq!(keys.len(), 6);
+    assert_eq!(keys[0]["selector"], "row-0");
+    assert_eq!(keys[1]["selector"], "1");
+    assert_eq!(keys[1]["color"], "#ff0000");
+    assert_eq!(
+        keys[1]["overridden"],
+        serde_json::json!(["row-0", "default"])
+    );
+    assert_eq!(keys[3]["action"], "clear");
+}
+
+#[test]
+fn version_check_offline_reports_current_version() {
+    init_test_logging();
+    let cli = CliRunner::new();
+    let result = cli.run_robot(&["version", "--check", "--offline"]);
+    result.assert_success();
+
+    let json = parse_json(result.stdout.trim());
+    assert_eq!(json["current"], env!("CARGO_PKG_VERSION"));
+    assert!(json["latest"].is_null());
+    assert_eq!(json["update_available"], false);
+    assert!(json["error"].is_string());
+}
+
+#[test]
+fn unresolved_query_fails_after_writing_report() {
+    init_test_logging();
+    let cli = CliRunner::new();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let report = dir.path().join("report.json");
+
+    let result = cli.run_robot(&[
+        "--report",
+        report.to_str().unwrap(),
+        "--query",
+        "/no/such/field",
+        "version",
+    ]);
+    result.assert_failure();
+    assert!(result.stdout.trim().is_empty(), "stdout: {}", result.stdout);
+    assert!(
+        result.stderr.contains("\"error\""),
+        "stderr: {}",
+        result.stderr
+    );
+
+    let report: Value =
+        serde_json::from_str(&std::fs::read_to_string(&report).expect("report written"))
+            .expect("report is JSON");
+    assert_eq!(report["ok"], false);
+}
+
+#[test]
+fn raw_report_requires_confirmation_and_is_hidden() {
+    init_test_logging();
+    let cli = CliRunner::new();
+
+    let result = cli.run_robot(&["raw", "--report", "03 08 64"]);
+    result.assert_failure();
+    assert!(
+        format!("{}{}", result.stdout, result.stderr).contains("--i-know-what-im-doing"),
+        "error should name the confirmation flag"
+    );
+
+    cli.run(&["--help"])
+        .assert_stdout_not_contains("Send a raw HID report");
+}
+
+#[test]
+fn info_field_projects_selected_fields() {
+    init_test_logging();
+    let cli = CliRunner::new().with_env("RUST_LOG", "off");
+    let result = cli.run_robot(&["info", "--field", "serial", "--field", "keys"]);
+
+    if !result.success() {
+        // No device: the usual robot error goes to stderr
+        assert!(parse_json(result.stderr.trim()).get("error").is_some());
+        return;
+    }
+
+    let json = parse_json(result.stdout.trim());
+    let mut fields: Vec<&str> = json
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    fields.sort_unstable();
+    assert_eq!(fields, vec!["keys", "serial"]);
+    assert!(json["keys"].is_number());
+}
+
+#[test]
+fn snapshots_page_reports_the_total() {
+    init_test_logging();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let cli = CliRunner::new().with_env("XDG_DATA_HOME", dir.path().to_str().unwrap());
+
+    let result = cli.run_robot(&["snapshots", "--limit", "20", "--offset", "40"]);
+    result.assert_success();
+    let json = parse_json(result.stdout.trim());
+    assert_eq!(json["total"], 0);
+    assert_eq!(json["limit"], 20);
+    assert_eq!(json["offset"], 40);
+    assert_eq!(json["snapshots"].as_array().map(Vec::len), Some(0));
+
+    for bad in [["--limit", "0"], ["--offset", "-1"]] {
+        let result = cli.run(&["snapshots", bad[0], bad[1]]);
+        result.assert_failure();
+    }
+}
+
+/// Saves a `desk` snapshot under `data_home` with image keys 2 and 5. Only
+/// key 2's image is in the cache.
+fn save_image_snapshot(data_home: &std::path::Path) {
+    use sd::snapshot::{Snapshot, SnapshotDb, SnapshotKey};
+
+    let cached = "ab12cached";
+    let cache_dir = data_home.join("sd/snapshots/images/ab");
+    std::fs::create_dir_all(&cache_dir).expect("cache dir");
+    image::RgbImage::from_pixel(72, 72, image::Rgb([255, 0, 0]))
+        .save_with_format(
+            cache_dir.join(format!("{cached}.webp")),
+            image::ImageFormat::Png,
+        )
+        .expect("cached image");
+
+    let mut db = SnapshotDb::open(data_home.join("sd/snapshots/snapshots.db")).expect("db");
+    let mut snap = Snapshot::new("desk".to_string(), "StreamDeckMk2".to_string(), 15, 72, 72);
+    snap.add_key(SnapshotKey::image(2, None, cached.to_string()));
+    snap.add_key(SnapshotKey::image(
+        5,
+        Some("/icons/gone.png".into()),
+        "cd34missing".to_string(),
+    ));
+    snap.add_key(SnapshotKey::color(7, "#00ff00".to_string()));
+    db.save_snapshot(&snap).expect("save snapshot");
+}
+
+#[test]
+fn snapshot_show_images_exports_cached_keys() {
+    init_test_logging();
+    let home = tempfile::tempdir().expect("tempdir");
+    save_image_snapshot(home.path());
+    let out = home.path().join("out");
+    let cli = CliRunner::new().with_env("XDG_DATA_HOME", home.path().to_str().unwrap());
+
+    let result = cli.run_robot(&[
+        "snapshot",
+        "show",
+        "desk",
+        "--images",
+        out.to_str().unwrap(),
+    ]);
+    result.assert_success();
+    let json = parse_json(result.stdout.trim());
+    let exported = json["images"]["exported"].as_array().expect("exported");
+    assert_eq!(exported.len(), 1);
+    assert_eq!(exported[0]["key"], 2);
+    assert_eq!(exported[0]["path"].as_str(), out.join("key-2.png").to_str());
+
+    let img = image::open(out.join("key-2.png")).expect("exported image");
+    assert_eq!(img.to_rgb8().get_pixel(0, 0).0, [255, 0, 0]);
+    assert!(!out.join("key-5.png").exists());
+    assert!(!out.join("key-7.png").exists());
+}
+
+#[test]
+fn snapshot_show_images_lists_cache_misses() {
+    init_test_logging();
+    let home = tempfile::tempdir().expect("tempdir");
+    save_image_snapshot(home.path());
+    let out = home.path().join("out");
+    let cli = CliRunner::new().with_env("XDG_DATA_HOME", home.path().to_str().unwrap());
+
+    let result = cli.run_robot(&[
+        "snapshot",
+        "show",
+        "desk",
+        "--images",
+        out.to_str().unwrap(),
+    ]);
+    result.assert_success();
+    let json = parse_json(result.stdout.trim());
+    let missing = json["images"]["missing"].as_array().expect("missing");
+    assert_eq!(missing.len(), 1);
+    assert_eq!(missing[0]["key"], 5);
+    assert_eq!(missing[0]["hash"], "cd34missing");
+    assert_eq!(missing[0]["original_path"], "/icons/gone.png");
+    assert!(
+        missing[0]["error"]
+            .as_str()
+            .is_some_and(|e| e.contains("not in image cache")),
+        "{json}"
+    );
+}
+
+#[test]
+fn snapshot_show_images_overwrites_existing_files() {
+    init_test_logging();
+    let home = tempfile::tempdir().expect("tempdir");
+    save_image_snapshot(home.path());
+    let out = home.path().join("out");
+    std::fs::create_dir_all(&out).expect("out dir");
+    std::fs::write(out.join("key-2.png"), "stale").expect("stale file");
+    let cli = CliRunner::new().with_env("XDG_DATA_HOME", home.path().to_str().unwrap());
+
+    let result = cli.run_robot(&[
+        "snapshot",
+        "show",
+        "desk",
+        "--images",
+        out.to_str().unwrap(),
+    ]);
+    result.assert_success();
+    let img = image::open(out.join("key-2.png")).expect("overwritten image");
+    assert_eq!((img.width(), img.height()), (72, 72));
+}
+
+#[test]
+fn validate_all_checks_every_config_in_a_directory() {
+    init_test_logging();
+    let dir = tempfile::tempdir().expect("tempdir");
+    std::fs::write(
+        dir.path().join("good.yaml"),
+        "keys:\n  \"0\":\n    color: \"#00ff00\"\n",
+    )
+    .expect("write config");
+    std::fs::write(dir.path().join("bad.toml"), "keys = [").expect("write config");
+    std::fs::write(dir.path().join("notes.txt"), "not a config").expect("write notes");
+
+    let cli = CliRunner::new();
+    let result = cli.run_robot(&["validate", dir.path().to_str().unwrap(), "--all"]);
+    result.assert_failure();
+    let json = parse_json(result.stdout.trim());
+    assert_eq!(json["valid"], false);
+    assert_eq!(json["summary"]["files"], 2);
+    assert_eq!(json["summary"]["failed"], 1);
+    let results = json["results"].as_array().expect("results");
+    assert!(
+        results[0]["config_path"]
+            .as_str()
+            .unwrap()
+            .ends_with("bad.toml")
+    );
+    assert_eq!(results[0]["valid"], false);
+    assert_eq!(results[1]["valid"], true);
+}
+
+#[test]
+fn upgrade_config_rewrites_old_syntax() {
+    init_test_logging();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let config = dir.path().join("old.yaml");
+    let old = "keys:\n  row0:\n    fill: \"#00ff00\"\n";
+    std::fs::write(&config, old).expect("write config");
+
+    let cli = CliRunner::new();
+    let result = cli.run_robot(&["upgrade-config", config.to_str().unwrap(), "--dry-run"]);
+    result.assert_success();
+    let json = parse_json(result.stdout.trim());
+    assert_eq!(json["written"], false);
+    assert_eq!(json["changes"].as_array().expect("changes").len(), 2);
+    assert!(json["content"].as_str().unwrap().contains("row-0"));
+    assert_eq!(std::fs::read_to_string(&config).unwrap(), old);
+
+    let result = cli.run_robot(&["upgrade-config", config.to_str().unwrap()]);
+    result.assert_success();
+    assert_eq!(parse_json(result.stdout.trim())["written"], true);
+    cli.run_robot(&["validate", config.to_str().unwrap()])
+        .assert_success();
+}
+
+#[test]
+fn replay_reports_recorded_events_like_watch() {
+    init_test_logging();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let session = dir.path().join("session.jsonl");
+    std::fs::write(
+        &session,
+        "{\"key\":2,\"pressed\":true,\"timestamp_ms\":100}\n\
+         {\"key\":2,\"pressed\":true,\"timestamp_ms\":150}\n\
+         {\"key\":2,\"pressed\":false,\"timestamp_ms\":300}\n",
+    )
+    .expect("write recording");
+
+    let cli = CliRunner::new();
+    let result = cli.run_robot(&[
+        "replay",
+        session.to_str().unwrap(),
+        "--speed",
+        "100",
+        "--model",
+        "mini",
+    ]);
+    result.assert_success();
+    // The repeated press is dropped, as watch would
+    let events: Vec<serde_json::Value> = result.stdout.lines().map(parse_json).collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["pressed"], true);
+    assert_eq!(events[1]["pressed"], false);
+    assert_eq!(events[1]["timestamp_ms"], 300);
+
+    std::fs::write(
+        &session,
+        "{\"key\":6,\"pressed\":true,\"timestamp_ms\":0}\n",
+    )
+    .expect("write recording");
+    cli.run_robot(&["replay", session.to_str().unwrap(), "--model", "mini"])
+        .assert_failure();
+}
+
+#[test]
+fn also_json_logs_human_runs_as_json_lines() {
+    init_test_logging();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let config = dir.path().join("layout.yaml");
+    std::fs::write(&config, "keys:\n  \"0\":\n    color: \"#00ff00\"\n").expect("write config");
+    let log = dir.path().join("log.jsonl");
+
+    let cli = CliRunner::new();
+    let result = cli.run(&[
+        "validate",
+        config.to_str().unwrap(),
+        "--also-json",
+        log.to_str().unwrap(),
+    ]);
+    result.assert_success();
+    assert!(serde_json::from_str::<Value>(result.stdout.trim()).is_err());
+
+    let logged = std::fs::read_to_string(&log).expect("log file");
+    let lines: Vec<Value> = logged.lines().map(parse_json).collect();
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["valid"], true);
+}
+
+#[test]
+fn key_timeout_validates_duration_and_then() {
+    init_test_logging();
+    let cli = CliRunner::new();
+
+    // --then only makes sense with --for
+    cli.run(&["fill-key", "0", "ff0000", "--then", "clear"])
+        .assert_failure()
+        .assert_stderr_contains("--for");
+    cli.run(&["fill-key", "0", "ff0000", "--for", "0s"])
+        .assert_failure()
+        .assert_stderr_contains("greater than zero");
+    cli.run(&["set-key", "0", "icon.png", "--for", "5 fortnights"])
+        .assert_failure();
+}
+
+#[test]
+fn key_ranges_accept_open_ends() {
+    init_test_logging();
+    let cli = CliRunner::new();
+    // With no device connected, dry runs assume 32 keys
+    let keys = |range: &str| {
+        let result = cli.run_robot(&["clear-keys", "--range", range, "--dry-run"]);
+        result.assert_success();
+        parse_json(result.stdout.trim())["details"]["keys"]
+            .as_array()
+            .expect("keys")
+            .len()
+    };
+    assert_eq!(keys("28-"), 4);
+    assert_eq!(keys("-7"), 8);
+    assert_eq!(keys("-"), 32);
+    assert_eq!(keys("2-5"), 4);
+}
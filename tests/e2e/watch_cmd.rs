@@ -72,3 +72,29 @@ fn watch_once_flag_is_accepted() {
     // Clap uses exit code 2 for argument parsing errors.
     assert_ne!(result.exit_code, 2, "--once should be a valid flag");
 }
+
+#[test]
+fn watch_feedback_flags_are_validated() {
+    init_test_logging();
+    let cli = CliRunner::new().with_env("RUST_LOG", "off");
+
+    let result = cli.run_robot(&[
+        "watch",
+        "--timeout=1",
+        "--feedback",
+        "--feedback-color",
+        "#ff8800",
+        "--feedback-ms",
+        "100",
+    ]);
+    assert_ne!(result.exit_code, 2, "--feedback flags should be valid");
+
+    // Color options only make sense with --feedback
+    let result = cli.run_robot(&["watch", "--timeout=1", "--feedback-color", "ff0000"]);
+    assert_eq!(result.exit_code, 2);
+
+    // A bad color is rejected before looking for a device
+    let result = cli.run_robot(&["watch", "--feedback", "--feedback-color", "nope"]);
+    result.assert_failure();
+    assert!(result.stderr.contains("nope"), "{}", result.stderr);
+}
@@ -77,6 +77,17 @@ fn generate_valid_images(base: &Path) {
 
     // Grayscale
     create_grayscale_image(&valid_dir.join("grayscale.png"), 72, 72);
+
+    // Multi-size icon: one solid color per frame so tests can tell them apart
+    create_multi_size_ico(
+        &valid_dir.join("multi-size.ico"),
+        &[
+            (16, [255, 0, 0]),
+            (32, [0, 255, 0]),
+            (64, [0, 0, 255]),
+            (128, [255, 255, 255]),
+        ],
+    );
 }
 
 fn generate_invalid_images(base: &Path) {
@@ -225,6 +236,31 @@ fn create_solid_image(path: &Path, width: u32, height: u32, color: [u8; 3]) {
     img.save(path).expect("Failed to save image");
 }
 
+fn create_multi_size_ico(path: &Path, frames: &[(u32, [u8; 3])]) {
+    use image::ExtendedColorType;
+    use image::ImageEncoder;
+    use image::codecs::ico::{IcoEncoder, IcoFrame};
+    use image::codecs::png::PngEncoder;
+
+    let frames: Vec<IcoFrame> = frames
+        .iter()
+        .map(|&(size, color)| {
+            let img = RgbImage::from_pixel(size, size, Rgb(color));
+            let mut png = Vec::new();
+            PngEncoder::new(&mut png)
+                .write_image(img.as_raw(), size, size, ExtendedColorType::Rgb8)
+                .expect("Failed to encode icon frame");
+            IcoFrame::with_encoded(png, size, size, ExtendedColorType::Rgb8)
+                .expect("Failed to build icon frame")
+        })
+        .collect();
+
+    let file = fs::File::create(path).expect("Failed to create icon file");
+    IcoEncoder::new(file)
+        .encode_images(&frames)
+        .expect("Failed to save icon");
+}
+
 fn create_transparent_image(path: &Path, width: u32, height: u32) {
     let mut img = RgbaImage::new(width, height);
     for (x, y, pixel) in img.enumerate_pixels_mut() {
@@ -4,10 +4,12 @@
 //! including error handling, state tracking, and edge cases.
 
 use std::path::Path;
+use std::time::Duration;
 
-use sd::device::DeviceOperations;
 use sd::device::mock::{MockDevice, MockDeviceBuilder, Operation};
+use sd::device::{DeviceOperations, Dimmer, settle_button_states};
 use sd::error::SdError;
+use sd::image_cache::RenderedImages;
 use sd::image_ops::ResizeStrategy;
 
 /// Test that brightness bounds are enforced correctly.
@@ -217,6 +219,168 @@ fn test_button_state_queuing() {
     mock.assert_contains(&Operation::ReadButtonStates);
 }
 
+/// Test that settling stops once two consecutive readings agree.
+#[test]
+fn test_settle_button_states_agrees() {
+    let mock = MockDevice::xl();
+    mock.queue_reading(Some(&[3]));
+    mock.queue_reading(Some(&[3]));
+
+    let settle = settle_button_states(&mock, Duration::from_secs(5)).unwrap();
+    assert!(settle.settled);
+    assert_eq!(settle.polls, 2);
+    assert!(settle.states[3]);
+    assert!(!settle.states[0]);
+}
+
+/// Test that a changing reading restarts the streak, and a poll with no
+/// report counts as unchanged.
+#[test]
+fn test_settle_button_states_restarts_on_change() {
+    let mock = MockDevice::xl();
+    mock.queue_reading(Some(&[1]));
+    mock.queue_reading(Some(&[2]));
+    mock.queue_reading(None);
+
+    let settle = settle_button_states(&mock, Duration::from_secs(5)).unwrap();
+    assert!(settle.settled);
+    assert_eq!(settle.polls, 3);
+    assert!(settle.states[2]);
+    assert!(!settle.states[1]);
+}
+
+/// Test that an idle device, which sends no reports, settles with all keys
+/// released.
+#[test]
+fn test_settle_button_states_idle_device_settles() {
+    let mock = MockDevice::xl();
+    mock.queue_reading(None);
+    mock.queue_reading(None);
+
+    let settle = settle_button_states(&mock, Duration::ZERO).unwrap();
+    assert!(settle.settled);
+    assert_eq!(settle.polls, 2);
+    assert!(settle.states.iter().all(|pressed| !pressed));
+}
+
+/// Test that a failed poll ends the settle with an error.
+#[test]
+fn test_settle_button_states_propagates_read_errors() {
+    let mock = MockDevice::xl();
+    mock.queue_reading(Some(&[1]));
+    mock.inject_error(SdError::DeviceCommunication("unplugged".to_string()));
+
+    assert!(settle_button_states(&mock, Duration::from_secs(5)).is_err());
+}
+
+/// Test that the dimmer steps brightness once per press, clamped to 0-100.
+#[test]
+fn test_dimmer_brightness_sequence() {
+    let mock = MockDevice::xl();
+    let mut dimmer = Dimmer::new(0, 1, 30, 50, vec![false; 32]);
+    let readings: [Option<&[u8]>; 12] = [
+        Some(&[0]), // dim: 20
+        Some(&[0]), // held, no repeat
+        None,       // no report: released
+        Some(&[]),
+        Some(&[0]), // dim: 0 (clamped)
+        Some(&[1]), // brighten: 30
+        Some(&[]),
+        Some(&[1]), // brighten: 60
+        Some(&[]),
+        Some(&[1]), // brighten: 90
+        Some(&[]),
+        Some(&[1]), // brighten: 100 (clamped)
+    ];
+    for reading in readings {
+        mock.queue_reading(reading);
+    }
+
+    let levels: Vec<u8> = (0..12)
+        .filter_map(|_| dimmer.poll(&mock).unwrap())
+        .collect();
+    assert_eq!(levels, vec![20, 0, 30, 60, 90, 100]);
+    assert_eq!(dimmer.level(), 100);
+    assert_eq!(mock.get_brightness(), 100);
+    let sets = mock
+        .operations()
+        .into_iter()
+        .filter(|op| matches!(op, Operation::SetBrightness { .. }))
+        .count();
+    assert_eq!(sets, 6);
+}
+
+/// Test that a key already held when the dimmer starts doesn't count as a press.
+#[test]
+fn test_dimmer_ignores_keys_held_at_start() {
+    let mock = MockDevice::xl();
+    let mut held = vec![false; 32];
+    held[1] = true;
+    let mut dimmer = Dimmer::new(0, 1, 10, 50, held);
+    mock.queue_reading(Some(&[1]));
+
+    assert_eq!(dimmer.poll(&mock).unwrap(), None);
+    assert_eq!(dimmer.level(), 50);
+}
+
+/// Test that a failed brightness write surfaces from the dimmer.
+#[test]
+fn test_dimmer_propagates_write_errors() {
+    let mock = MockDevice::xl();
+    let mut dimmer = Dimmer::new(0, 1, 10, 50, vec![false; 32]);
+    mock.queue_reading(Some(&[0]));
+    mock.inject_error(SdError::DeviceCommunication("unplugged".to_string()));
+
+    assert!(dimmer.poll(&mock).is_err());
+    assert_eq!(dimmer.level(), 50);
+}
+
+/// Test that verified writes are counted when the image reads back intact.
+#[test]
+fn test_verify_writes_counts_verified_writes() {
+    let mock = MockDeviceBuilder::xl()
+        .with_read_back()
+        .build()
+        .with_verify_writes(true);
+    mock.set_key_image(0, Path::new("a.png"), ResizeStrategy::Fit)
+        .unwrap();
+    mock.fill_key_color(1, (255, 0, 0)).unwrap();
+
+    let stats = mock.write_verification().unwrap();
+    assert!(stats.supported);
+    assert_eq!((stats.writes, stats.verified, stats.mismatched), (2, 2, 0));
+}
+
+/// Test that a write that still reads back wrong after a retry fails.
+#[test]
+fn test_verify_writes_reports_mismatch() {
+    let mock = MockDeviceBuilder::xl()
+        .with_read_back()
+        .with_corrupt_keys(vec![4])
+        .build()
+        .with_verify_writes(true);
+    mock.fill_key_color(3, (0, 255, 0)).unwrap();
+    let err = mock.fill_key_color(4, (0, 255, 0)).unwrap_err();
+    assert!(err.to_string().contains("Key 4"), "{err}");
+
+    let stats = mock.write_verification().unwrap();
+    assert_eq!((stats.writes, stats.verified, stats.mismatched), (2, 1, 1));
+}
+
+/// Test that devices without read-back, like every real model, leave writes unverified.
+#[test]
+fn test_verify_writes_unsupported_device() {
+    let mock = MockDevice::xl().with_verify_writes(true);
+    mock.fill_key_color(0, (0, 0, 255)).unwrap();
+
+    let stats = mock.write_verification().unwrap();
+    assert!(!stats.supported);
+    assert_eq!((stats.writes, stats.verified, stats.unverified), (1, 0, 1));
+
+    // Nothing is tracked unless verification was asked for
+    assert_eq!(MockDevice::xl().write_verification(), None);
+}
+
 /// Test that operations are recorded correctly after clear.
 #[test]
 fn test_clear_operations_fresh_start() {
@@ -303,3 +467,45 @@ fn test_watch_buttons_recording() {
         timeout_secs: 5,
     });
 }
+
+/// Test that a shared icon is rendered once and uploads identical bytes.
+#[test]
+fn test_rendered_image_shared_across_keys() {
+    let dir = tempfile::tempdir().unwrap();
+    let icon = dir.path().join("icon.png");
+    image::RgbImage::from_pixel(100, 80, image::Rgb([30, 144, 255]))
+        .save(&icon)
+        .unwrap();
+
+    let mock = MockDevice::mini();
+    let size = mock.info().key_size();
+    let mut rendered = RenderedImages::new();
+    let mut renders = 0;
+    for key in 0..mock.info().key_count {
+        let image = rendered
+            .get_or_render(&icon, size, ResizeStrategy::Fit, || {
+                renders += 1;
+                sd::image_ops::process_for_key(&icon, size, ResizeStrategy::Fit)
+            })
+            .unwrap();
+        mock.set_key_rendered(key, image, &icon).unwrap();
+    }
+
+    assert_eq!(renders, 1);
+    assert_eq!(rendered.unique(), 1);
+    assert_eq!(rendered.reused(), 5);
+
+    let digests: Vec<String> = mock
+        .operations()
+        .into_iter()
+        .filter_map(|op| match op {
+            Operation::SetKeyRendered { digest, .. } => Some(digest),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(digests.len(), 6);
+    assert!(digests.iter().all(|d| *d == digests[0]));
+    for key in 0..6 {
+        mock.assert_key_has_image(key);
+    }
+}
@@ -7,7 +7,13 @@ use std::path::PathBuf;
 
 use image::GenericImageView;
 use sd::error::SdError;
-use sd::image_ops::{ResizeStrategy, load_and_resize};
+use sd::image_ops::{
+    ColorSpace, CropBox, KeySize, MIN_TEXT_CONTRAST, OverlayPosition, Padding, PaletteMode,
+    ResizeStrategy, auto_text_color, average_rgb, best_ico_frame, canvas_layout, composite_overlay,
+    contrast_ratio, dominant_rgb, grid_colors, ico_frames, load_and_resize, open_for_size,
+    overlay_box, overlay_color, process_for_key, resize_geometry, sharpen, simulate_lcd,
+    slice_for_deck, to_device_rgb,
+};
 
 /// Get the path to test fixtures directory.
 fn fixtures_dir() -> PathBuf {
@@ -321,3 +327,415 @@ fn test_various_target_sizes() {
     let img = load_and_resize(&path, 120, 120, ResizeStrategy::Fit).unwrap();
     assert_eq!(img.dimensions(), (120, 120));
 }
+
+/// Test parsing `--key-size` values.
+#[test]
+fn test_key_size_parse() {
+    assert_eq!("72x72".parse::<KeySize>().unwrap(), KeySize::new(72, 72));
+    assert_eq!("120X96".parse::<KeySize>().unwrap(), KeySize::new(120, 96));
+    assert_eq!(KeySize::new(96, 96).to_string(), "96x96");
+
+    for bad in ["", "72", "72x", "x72", "axb", "0x72", "72x0", "-1x72"] {
+        assert!(
+            bad.parse::<KeySize>().is_err(),
+            "'{bad}' should be rejected"
+        );
+    }
+}
+
+/// Test rendering at an explicit key size.
+#[test]
+fn test_process_for_key_override() {
+    let path = fixtures_dir().join("valid").join("exact-72x72.png");
+    if !path.exists() {
+        eprintln!("Skipping test: fixture not found at {:?}", path);
+        return;
+    }
+
+    let img = process_for_key(&path, KeySize::new(120, 100), ResizeStrategy::Stretch).unwrap();
+    assert_eq!(img.dimensions(), (120, 100));
+}
+
+/// Test canvas geometry for a Mini-sized grid.
+#[test]
+fn test_canvas_layout_no_gap() {
+    let layout = canvas_layout(2, 3, KeySize::new(72, 72), 0);
+
+    assert_eq!((layout.canvas_width, layout.canvas_height), (216, 144));
+    assert_eq!(layout.tiles.len(), 6);
+
+    let last = layout.tiles[5];
+    assert_eq!((last.key, last.row, last.col), (5, 1, 2));
+    assert_eq!((last.crop.x, last.crop.y), (144, 72));
+    assert_eq!((last.crop.width, last.crop.height), (72, 72));
+}
+
+/// Test that tile coordinates skip the gap between keys.
+#[test]
+fn test_canvas_layout_with_gap() {
+    // XL grid with the default 24px bezel gap
+    let layout = canvas_layout(4, 8, KeySize::new(96, 96), 24);
+
+    assert_eq!(layout.canvas_width, 8 * 96 + 7 * 24);
+    assert_eq!(layout.canvas_height, 4 * 96 + 3 * 24);
+
+    // Key 9 is row 1, col 1: offset by one key and one gap on each axis
+    let tile = layout.tiles[9];
+    assert_eq!((tile.row, tile.col), (1, 1));
+    assert_eq!((tile.crop.x, tile.crop.y), (120, 120));
+
+    // Last key ends exactly at the canvas edge
+    let last = layout.tiles[31];
+    assert_eq!(last.crop.x + last.crop.width, layout.canvas_width);
+    assert_eq!(last.crop.y + last.crop.height, layout.canvas_height);
+}
+
+/// Test slicing an image into one tile per key.
+#[test]
+fn test_slice_for_deck() {
+    // Left half red, right half blue
+    let img = image::RgbImage::from_fn(300, 100, |x, _| {
+        if x < 150 {
+            image::Rgb([255, 0, 0])
+        } else {
+            image::Rgb([0, 0, 255])
+        }
+    });
+    let img = image::DynamicImage::ImageRgb8(img);
+
+    let tiles = slice_for_deck(&img, 1, 2, KeySize::new(50, 50), 0);
+    assert_eq!(tiles.len(), 2);
+    assert!(tiles.iter().all(|t| t.dimensions() == (50, 50)));
+
+    assert_eq!(tiles[0].to_rgb8().get_pixel(10, 25).0, [255, 0, 0]);
+    assert_eq!(tiles[1].to_rgb8().get_pixel(40, 25).0, [0, 0, 255]);
+}
+
+/// Test the LCD display approximation is deterministic and plausible.
+#[test]
+fn test_simulate_lcd() {
+    // White stays white; black lifts slightly from backlight bleed
+    assert_eq!(simulate_lcd((255, 255, 255)), (255, 255, 255));
+    assert_eq!(simulate_lcd((0, 0, 0)), (18, 18, 18));
+
+    // Midtones darken but grays stay neutral
+    assert_eq!(simulate_lcd((128, 128, 128)), (117, 117, 117));
+
+    // Saturated colors are muted
+    let (r, g, b) = simulate_lcd((255, 0, 0));
+    assert!(r < 255 && g > 0 && g == b);
+
+    assert_eq!(simulate_lcd((40, 90, 200)), simulate_lcd((40, 90, 200)));
+}
+
+/// Test correcting fill colors for a color space.
+#[test]
+fn test_to_device_rgb() {
+    use sd::device::DeviceModel;
+
+    let gray = (128, 128, 128);
+    assert_eq!(
+        to_device_rgb(gray, ColorSpace::Srgb, Some(DeviceModel::Xl)),
+        gray
+    );
+    // Linear 0.5 is sRGB 188
+    assert_eq!(
+        to_device_rgb(gray, ColorSpace::Linear, None),
+        (188, 188, 188)
+    );
+
+    // Device correction undoes the panel curve that simulate_lcd models
+    let corrected = to_device_rgb(gray, ColorSpace::Device, Some(DeviceModel::Xl));
+    assert!(corrected.0 > 128);
+    assert!(simulate_lcd(corrected).0.abs_diff(128) <= 2);
+
+    // Extremes and displayless models are left alone
+    let white = (255, 255, 255);
+    assert_eq!(
+        to_device_rgb(white, ColorSpace::Device, Some(DeviceModel::Mini)),
+        white
+    );
+    assert_eq!(
+        to_device_rgb((0, 0, 0), ColorSpace::Device, Some(DeviceModel::Mini)),
+        (0, 0, 0)
+    );
+    assert_eq!(
+        to_device_rgb(gray, ColorSpace::Device, Some(DeviceModel::Pedal)),
+        gray
+    );
+}
+
+/// Test averaging an image's color.
+#[test]
+fn test_average_rgb() {
+    let img = image::RgbImage::from_fn(2, 1, |x, _| {
+        if x == 0 {
+            image::Rgb([200, 0, 100])
+        } else {
+            image::Rgb([0, 100, 100])
+        }
+    });
+    assert_eq!(
+        average_rgb(&image::DynamicImage::ImageRgb8(img)),
+        (100, 50, 100)
+    );
+}
+
+/// Test picking an image's most common color.
+#[test]
+fn test_dominant_rgb() {
+    // Three near-identical greens outvote one red
+    let img = image::RgbImage::from_fn(4, 1, |x, _| match x {
+        0 => image::Rgb([250, 0, 0]),
+        1 => image::Rgb([0, 200, 10]),
+        2 => image::Rgb([0, 202, 10]),
+        _ => image::Rgb([0, 204, 10]),
+    });
+    assert_eq!(
+        dominant_rgb(&image::DynamicImage::ImageRgb8(img)),
+        (0, 202, 10)
+    );
+}
+
+/// Test reducing an image to one color per key of a grid.
+#[test]
+fn test_grid_colors() {
+    // Left half red, right half blue, with a green speck in the top-left cell
+    let img = image::RgbImage::from_fn(40, 20, |x, y| {
+        if x == 0 && y == 0 {
+            image::Rgb([0, 255, 0])
+        } else if x < 20 {
+            image::Rgb([255, 0, 0])
+        } else {
+            image::Rgb([0, 0, 255])
+        }
+    });
+    let img = image::DynamicImage::ImageRgb8(img);
+
+    let dominant = grid_colors(&img, 2, 4, PaletteMode::Dominant);
+    assert_eq!(dominant.len(), 8);
+    assert_eq!(dominant[0], (255, 0, 0));
+    assert_eq!(dominant[3], (0, 0, 255));
+    assert_eq!(dominant[4], (255, 0, 0));
+
+    let average = grid_colors(&img, 2, 4, PaletteMode::Average);
+    assert_ne!(average[0], (255, 0, 0));
+    assert_eq!(average[1], (255, 0, 0));
+
+    // A grid finer than the image still gets a color for every key
+    let tiny =
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(1, 1, image::Rgb([9, 9, 9])));
+    assert_eq!(
+        grid_colors(&tiny, 3, 5, PaletteMode::Average),
+        vec![(9, 9, 9); 15]
+    );
+}
+
+/// Test blending a translucent color over an image.
+#[test]
+fn test_overlay_color() {
+    let img =
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(2, 2, image::Rgb([0, 0, 200])));
+    let half_red = overlay_color(&img, (255, 0, 0, 128)).to_rgb8();
+    assert_eq!(half_red.get_pixel(1, 1).0, [128, 0, 100]);
+    let opaque = overlay_color(&img, (255, 0, 0, 255)).to_rgb8();
+    assert_eq!(opaque.get_pixel(0, 0).0, [255, 0, 0]);
+    let clear = overlay_color(&img, (255, 0, 0, 0)).to_rgb8();
+    assert_eq!(clear.get_pixel(0, 0).0, [0, 0, 200]);
+}
+
+/// Test sharpening keeps the size and raises contrast across an edge.
+#[test]
+fn test_sharpen() {
+    // A soft ramp from dark to light, like a downscaled edge
+    let img = image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(12, 12, |x, _| {
+        let v = [60, 60, 60, 60, 80, 110, 140, 170, 190, 190, 190, 190][x as usize];
+        image::Rgb([v, v, v])
+    }));
+    let edge_contrast = |img: &image::DynamicImage| {
+        let rgb = img.to_rgb8();
+        i32::from(rgb.get_pixel(7, 6)[0]) - i32::from(rgb.get_pixel(4, 6)[0])
+    };
+
+    let sharp = sharpen(&img, 60);
+    assert_eq!(sharp.dimensions(), img.dimensions());
+    assert!(
+        edge_contrast(&sharp) > edge_contrast(&img),
+        "{} <= {}",
+        edge_contrast(&sharp),
+        edge_contrast(&img)
+    );
+    assert!(edge_contrast(&sharpen(&img, 100)) > edge_contrast(&sharp));
+    assert_eq!(sharpen(&img, 0).to_rgb8(), img.to_rgb8());
+}
+
+#[test]
+fn test_overlay_box_positions() {
+    let key = KeySize::new(96, 96);
+    let corner = overlay_box(key, (64, 32), OverlayPosition::BottomRight, 0.5).unwrap();
+    // Fits 48x48 keeping 2:1, pinned to the corner
+    assert_eq!(
+        corner,
+        CropBox {
+            x: 48,
+            y: 72,
+            width: 48,
+            height: 24
+        }
+    );
+    let center = overlay_box(key, (10, 10), OverlayPosition::Center, 0.25).unwrap();
+    assert_eq!((center.x, center.y, center.width), (36, 36, 24));
+    for scale in [0.0, -0.5, 1.5, f32::NAN] {
+        assert!(matches!(
+            overlay_box(key, (10, 10), OverlayPosition::Top, scale),
+            Err(SdError::ImageProcessing(_))
+        ));
+    }
+}
+
+#[test]
+fn test_composite_overlay_keeps_transparency() {
+    let base =
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(4, 4, image::Rgb([0, 0, 200])));
+    let mut badge = image::RgbaImage::from_pixel(2, 2, image::Rgba([255, 0, 0, 255]));
+    badge.put_pixel(0, 0, image::Rgba([0, 255, 0, 0]));
+    let region = CropBox {
+        x: 2,
+        y: 2,
+        width: 2,
+        height: 2,
+    };
+    let out = composite_overlay(&base, &image::DynamicImage::ImageRgba8(badge), region).to_rgb8();
+    assert_eq!(out.get_pixel(0, 0).0, [0, 0, 200]);
+    assert_eq!(out.get_pixel(2, 2).0, [0, 0, 200]);
+    assert_eq!(out.get_pixel(3, 3).0, [255, 0, 0]);
+}
+
+/// Test that multi-size icons decode the smallest frame covering the key.
+#[test]
+fn test_ico_best_frame_selection() {
+    let path = fixtures_dir().join("valid/multi-size.ico");
+    let bytes = std::fs::read(&path).expect("read icon fixture");
+
+    let frames = ico_frames(&bytes).expect("parse icon directory");
+    let sizes: Vec<_> = frames.iter().map(|f| (f.width, f.height)).collect();
+    assert_eq!(sizes, vec![(16, 16), (32, 32), (64, 64), (128, 128)]);
+
+    // 72px key: 128 is the smallest frame that doesn't need upscaling
+    let best = best_ico_frame(&frames, 72, 72).unwrap();
+    assert_eq!((best.width, best.height), (128, 128));
+    // Exact match wins
+    let best = best_ico_frame(&frames, 32, 32).unwrap();
+    assert_eq!((best.width, best.height), (32, 32));
+    // Nothing big enough: fall back to the largest
+    let best = best_ico_frame(&frames, 200, 200).unwrap();
+    assert_eq!((best.width, best.height), (128, 128));
+
+    // Decoding picks the chosen frame's pixels (64px frame is blue)
+    let img = open_for_size(&path, 60, 60).expect("decode icon");
+    assert_eq!(img.dimensions(), (64, 64));
+    assert_eq!(img.to_rgb8().get_pixel(10, 10).0, [0, 0, 255]);
+
+    // Full pipeline resizes from that frame
+    let img = load_and_resize(&path, 72, 72, ResizeStrategy::Fit).expect("load icon");
+    assert_eq!(img.dimensions(), (72, 72));
+    assert_eq!(img.to_rgb8().get_pixel(36, 36).0, [255, 255, 255]);
+}
+
+/// Test that SVGs are rasterized to fit the key, keeping their aspect ratio.
+#[test]
+fn test_svg_rasterized_to_key_size() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let path = dir.path().join("icon.svg");
+    std::fs::write(
+        &path,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10">
+            <rect width="20" height="10" fill="#ff0000"/>
+        </svg>"##,
+    )
+    .expect("write svg");
+
+    let img = open_for_size(&path, 72, 72).expect("rasterize svg");
+    assert_eq!(img.dimensions(), (72, 36));
+    assert_eq!(img.to_rgba8().get_pixel(36, 18).0, [255, 0, 0, 255]);
+
+    let img = load_and_resize(&path, 72, 72, ResizeStrategy::Fit).expect("load svg");
+    assert_eq!(img.dimensions(), (72, 72));
+
+    std::fs::write(&path, "<not svg").expect("write bad svg");
+    assert!(open_for_size(&path, 72, 72).is_err());
+}
+
+/// Test the shared crop/pad geometry for each resize strategy.
+#[test]
+fn test_resize_geometry() {
+    // 100x80 into a 72x72 key
+    let fit = resize_geometry((100, 80), 72, 72, ResizeStrategy::Fit);
+    assert_eq!((fit.scaled_width, fit.scaled_height), (72, 58));
+    assert_eq!(
+        fit.padding,
+        Some(Padding {
+            top: 7,
+            right: 0,
+            bottom: 7,
+            left: 0
+        })
+    );
+    assert_eq!(fit.crop, None);
+
+    let fill = resize_geometry((100, 80), 72, 72, ResizeStrategy::Fill);
+    assert_eq!((fill.scaled_width, fill.scaled_height), (90, 72));
+    assert_eq!(
+        fill.crop,
+        Some(CropBox {
+            x: 9,
+            y: 0,
+            width: 72,
+            height: 72
+        })
+    );
+
+    let stretch = resize_geometry((100, 80), 72, 72, ResizeStrategy::Stretch);
+    assert_eq!((stretch.scaled_width, stretch.scaled_height), (72, 72));
+    assert!(stretch.crop.is_none() && stretch.padding.is_none());
+
+    // The real resize produces exactly the reported framing
+    let path = fixtures_dir().join("valid/nonsquare-100x80.png");
+    let img = load_and_resize(&path, 72, 72, ResizeStrategy::Fit).unwrap();
+    let rgb = img.to_rgb8();
+    assert_eq!(rgb.get_pixel(36, 6).0, [0, 0, 0]); // inside top padding
+    assert_ne!(rgb.get_pixel(36, 7).0, [0, 0, 0]); // first image row
+}
+
+#[test]
+fn test_contrast_ratio_known_pairs() {
+    let close = |a: f32, b: f32| (a - b).abs() < 0.01;
+
+    // Reference values from the WCAG contrast formula
+    assert!(close(contrast_ratio((0, 0, 0), (255, 255, 255)), 21.0));
+    assert!(close(contrast_ratio((255, 255, 255), (0, 0, 0)), 21.0));
+    assert!(close(
+        contrast_ratio((0x77, 0x77, 0x77), (255, 255, 255)),
+        4.48
+    ));
+    assert!(close(contrast_ratio((255, 0, 0), (255, 255, 255)), 4.0));
+    assert!(close(contrast_ratio((0, 0, 255), (255, 255, 255)), 8.59));
+    assert!(close(contrast_ratio((12, 34, 56), (12, 34, 56)), 1.0));
+
+    // Yellow on white is unreadable; dark gray on black nearly so
+    assert!(contrast_ratio((255, 255, 0), (255, 255, 255)) < MIN_TEXT_CONTRAST);
+    assert!(contrast_ratio((40, 40, 40), (0, 0, 0)) < MIN_TEXT_CONTRAST);
+}
+
+#[test]
+fn test_auto_text_color() {
+    assert_eq!(auto_text_color((255, 255, 0)), (0, 0, 0));
+    assert_eq!(auto_text_color((0, 0, 128)), (255, 255, 255));
+    assert_eq!(auto_text_color((0, 0, 0)), (255, 255, 255));
+    for bg in [(255, 0, 0), (0, 128, 0), (128, 128, 128), (30, 144, 255)] {
+        assert!(
+            contrast_ratio(auto_text_color(bg), bg) >= MIN_TEXT_CONTRAST,
+            "{bg:?}"
+        );
+    }
+}
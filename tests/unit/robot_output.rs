@@ -17,9 +17,14 @@
 
 use std::path::Path;
 
-use sd::device::{ButtonEvent, DeviceInfo};
+use sd::cli::AfterTimeout;
+use sd::device::{BrightnessReading, ButtonEvent, DeviceInfo};
 use sd::error::SdError;
-use sd::output::{BatchKeyResult, BatchSummary, RobotFormat, RobotOutput};
+use sd::output::{
+    BatchKeyResult, BatchSummary, DeviceList, JsonVersion, KeyDisplayEvent, KeySetResult, Output,
+    RobotFormat, RobotOutput, TeeOutput, render_json, render_json_versioned, sort_batch_results,
+    visible_results,
+};
 
 /// Load a golden file from tests/golden/robot/.
 fn load_golden(name: &str) -> serde_json::Value {
@@ -155,6 +160,40 @@ fn device_list_empty() {
     assert_eq!(json.as_array().unwrap().len(), 0);
 }
 
+#[test]
+fn device_list_grouped_names_the_default() {
+    let single = vec![mock_device_xl()];
+    let json = serde_json::to_value(DeviceList::new(&single, &single)).expect("serialize list");
+    assert_eq!(json["count"], 1);
+    assert_eq!(json["default"], "AL12XL0001");
+    // Device objects are unchanged
+    assert_eq!(
+        json["devices"][0],
+        serde_json::to_value(&single[0]).unwrap()
+    );
+
+    let several = vec![mock_device_xl(), mock_device_mini()];
+    let json = serde_json::to_value(DeviceList::new(&several, &several)).expect("serialize list");
+    assert_eq!(json["count"], 2);
+    assert!(json["default"].is_null());
+
+    let none: Vec<DeviceInfo> = vec![];
+    let json = serde_json::to_value(DeviceList::new(&none, &none)).expect("serialize list");
+    assert_eq!(json["count"], 0);
+    assert!(json["default"].is_null());
+    assert!(json.get("timed_out").is_none());
+}
+
+#[test]
+fn device_list_reports_timeouts() {
+    let devices = vec![mock_device_xl(), mock_device_mini()];
+    let slow = vec!["AL12XL0001".to_string()];
+    let json = serde_json::to_value(DeviceList::new(&devices, &devices).with_timed_out(&slow))
+        .expect("serialize list");
+    assert_eq!(json["count"], 2);
+    assert_eq!(json["timed_out"], serde_json::json!(["AL12XL0001"]));
+}
+
 // =============================================================================
 // Button Event Serialization Tests
 // =============================================================================
@@ -420,6 +459,18 @@ fn batch_key_result_failure_structure() {
     assert_eq!(json["error"], "Failed");
 }
 
+#[test]
+fn batch_key_result_failure_hides_data_uri_payload() {
+    let uri = Path::new("data:image/png;base64,aGVsbG8=");
+    let json =
+        serde_json::to_value(BatchKeyResult::set_key_failure(2, uri, "Failed")).expect("serialize");
+    assert_eq!(
+        json["path"],
+        serde_json::to_value(BatchKeyResult::set_key_success(2, uri)).unwrap()["path"]
+    );
+    assert!(!json.to_string().contains("aGVsbG8"), "{json}");
+}
+
 #[test]
 fn batch_key_result_clear_success_structure() {
     let result = BatchKeyResult::clear_success(5);
@@ -441,6 +492,66 @@ fn batch_key_result_fill_success_structure() {
     assert_eq!(json["ok"], true);
 }
 
+#[test]
+fn batch_key_result_warnings_structure() {
+    let result = BatchKeyResult::set_key_success(0, Path::new("/path/to/image.png"));
+    let json = serde_json::to_value(&result).expect("serialize");
+    // Warnings are omitted when there are none
+    assert!(json.get("warnings").is_none());
+
+    let result = result.with_warnings(vec!["Image was resized from 512x512 to 96x96".into()]);
+    let json = serde_json::to_value(&result).expect("serialize");
+    assert_eq!(json["ok"], true);
+    assert_eq!(
+        json["warnings"][0],
+        "Image was resized from 512x512 to 96x96"
+    );
+}
+
+#[test]
+fn batch_results_sort_by_key_index() {
+    let mut results = vec![
+        BatchKeyResult::clear_success(12),
+        BatchKeyResult::set_key_failure(3, Path::new("/icons/key-3.png"), "Failed"),
+        BatchKeyResult::clear_success(0),
+        BatchKeyResult::fill_success(3, "#FF0000"),
+    ];
+    sort_batch_results(&mut results);
+
+    let keys: Vec<u8> = results.iter().map(|r| r.key).collect();
+    assert_eq!(keys, vec![0, 3, 3, 12]);
+    // Stable: the earlier operation on key 3 stays first
+    assert!(!results[1].ok);
+    assert!(results[2].ok);
+}
+
+#[test]
+fn only_errors_keeps_failed_results() {
+    let results = vec![
+        BatchKeyResult::fill_success(0, "#FF0000"),
+        BatchKeyResult::fill_failure(1, "#FF0000", "Failed"),
+        BatchKeyResult::fill_success(2, "#FF0000"),
+    ];
+    assert_eq!(visible_results(&results, false).len(), 3);
+    let failed = visible_results(&results, true);
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].key, 1);
+}
+
+#[test]
+fn key_set_result_matches_golden_shape() {
+    let golden = load_golden("key_set");
+    let result = KeySetResult::new(5, Path::new("/path/to/icon.png"));
+    let json = serde_json::to_value(&result).expect("serialize");
+
+    let keys: Vec<_> = json.as_object().unwrap().keys().collect();
+    let golden_keys: Vec<_> = golden.as_object().unwrap().keys().collect();
+    assert_eq!(keys, golden_keys);
+
+    let json = serde_json::to_value(result.with_warnings(vec!["resized".into()])).unwrap();
+    assert_eq!(json["warnings"], serde_json::json!(["resized"]));
+}
+
 #[test]
 fn batch_summary_structure() {
     let summary = BatchSummary::new(10, 8, 2);
@@ -523,6 +634,168 @@ fn robot_format_json_compact_available() {
     drop(output);
 }
 
+// =============================================================================
+// Query (JSON Pointer) Tests
+// =============================================================================
+
+#[test]
+fn query_extracts_nested_string_raw() {
+    let data = serde_json::json!({ "device": { "serial": "AL12XL0001" } });
+    let out = render_json(&data, false, Some("/device/serial")).unwrap();
+    assert_eq!(out, "AL12XL0001");
+}
+
+#[test]
+fn query_renders_non_string_as_json() {
+    let device = mock_device_xl();
+    assert_eq!(
+        render_json(&device, true, Some("/key_count")).unwrap(),
+        "32"
+    );
+
+    let data = serde_json::json!({ "keys": [1, 2] });
+    assert_eq!(render_json(&data, true, Some("/keys")).unwrap(), "[1,2]");
+}
+
+#[test]
+fn query_without_pointer_renders_whole_value() {
+    let data = serde_json::json!({ "a": 1 });
+    assert_eq!(render_json(&data, true, None).unwrap(), r#"{"a":1}"#);
+}
+
+#[test]
+fn query_unresolved_pointer_is_error() {
+    let data = serde_json::json!({ "a": 1 });
+    assert!(render_json(&data, true, Some("/missing")).is_err());
+    // Pointers must start with '/'
+    assert!(render_json(&data, true, Some("a")).is_err());
+}
+
+// =============================================================================
+// JSON Compat Version Tests
+// =============================================================================
+
+#[test]
+fn json_compat_parses_versions() {
+    assert_eq!("v1".parse::<JsonVersion>().unwrap(), JsonVersion::V1);
+    assert_eq!("1".parse::<JsonVersion>().unwrap(), JsonVersion::V1);
+    assert_eq!("v2".parse::<JsonVersion>().unwrap(), JsonVersion::V2);
+    assert_eq!(
+        "latest".parse::<JsonVersion>().unwrap(),
+        JsonVersion::LATEST
+    );
+    assert!("v9".parse::<JsonVersion>().is_err());
+}
+
+#[test]
+fn json_compat_v1_strips_key_result_warnings() {
+    let result = BatchKeyResult::set_key_success(0, Path::new("/icons/a.png"))
+        .with_warnings(vec!["Image was resized from 512x512 to 96x96".into()]);
+    let summary = BatchSummary::new(1, 1, 0);
+    let set_keys_at = |version: JsonVersion| {
+        let buffer = SharedBuffer::default();
+        RobotOutput::new(RobotFormat::Json)
+            .with_version(version)
+            .with_writer(Box::new(buffer.clone()))
+            .batch_set_keys(std::slice::from_ref(&result), &summary);
+        buffer.lines().remove(0)
+    };
+
+    let v1 = set_keys_at(JsonVersion::V1);
+    assert!(v1["results"][0].get("warnings").is_none());
+    assert_eq!(v1["results"][0]["ok"], true);
+
+    let latest = set_keys_at(JsonVersion::LATEST);
+    assert_eq!(
+        latest["results"][0]["warnings"].as_array().unwrap().len(),
+        1
+    );
+}
+
+#[test]
+fn json_compat_pin_applies_to_json_documents() {
+    let result = BatchKeyResult::set_key_success(0, Path::new("/icons/a.png"))
+        .with_warnings(vec!["resized".into()]);
+    let data = JsonVersion::V1.pinned(|| serde_json::json!({ "results": [&result] }));
+    assert!(data["results"][0].get("warnings").is_none());
+    let data = serde_json::json!({ "results": [&result] });
+    assert_eq!(data["results"][0]["warnings"][0], "resized");
+}
+
+#[test]
+fn json_compat_v3_strips_key_result_selector() {
+    let result =
+        BatchKeyResult::set_key_success(0, Path::new("/icons/a.png")).with_selector("row-0");
+    let data = [result];
+
+    let v3 = JsonVersion::V3.serialize(&data).unwrap();
+    assert!(v3[0].get("selector").is_none());
+    assert_eq!(v3[0]["key"], 0);
+    let latest = JsonVersion::LATEST.serialize(&data).unwrap();
+    assert_eq!(latest[0]["selector"], "row-0");
+}
+
+#[test]
+fn json_compat_v1_keeps_dry_run_validation_warnings() {
+    let data = serde_json::json!({
+        "dry_run": true,
+        "validation": { "inputs_valid": true, "errors": [], "warnings": ["Device not connected"] },
+    });
+    let v1 = JsonVersion::V1.serialize(&data).unwrap();
+    assert_eq!(v1, data);
+}
+
+#[test]
+fn json_compat_v1_matches_key_set_golden() {
+    let golden = load_golden("key_set");
+    let result = KeySetResult::new(5, Path::new("/home/user/icon.png"))
+        .with_warnings(vec!["resized".into()]);
+    assert_eq!(JsonVersion::V1.serialize(&result).unwrap(), golden);
+}
+
+#[test]
+fn json_compat_pinned_output_is_byte_identical_to_golden() {
+    // The golden file holds the bytes `set-key --robot` printed before versioning
+    let path = format!(
+        "{}/tests/golden/robot/key_set.json",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let golden = std::fs::read_to_string(path).unwrap();
+    let image = Path::new("/home/user/icon.png");
+    for (version, result) in [
+        (
+            JsonVersion::V1,
+            KeySetResult::new(5, image).with_warnings(vec!["resized".into()]),
+        ),
+        (JsonVersion::V2, KeySetResult::new(5, image)),
+        (JsonVersion::LATEST, KeySetResult::new(5, image)),
+    ] {
+        let printed = render_json_versioned(&result, version, None, false, None).unwrap();
+        assert_eq!(printed, golden.trim_end(), "{version}");
+    }
+}
+
+#[test]
+fn json_compat_v3_info_omits_current_brightness() {
+    let reading = BrightnessReading {
+        level: 60,
+        source: None,
+    };
+    let info_at = |version: JsonVersion| {
+        let buffer = SharedBuffer::default();
+        RobotOutput::new(RobotFormat::Json)
+            .with_version(version)
+            .with_writer(Box::new(buffer.clone()))
+            .device_info(&mock_device_xl(), Some(&reading));
+        buffer.lines().remove(0)
+    };
+
+    let v3 = info_at(JsonVersion::V3);
+    assert!(v3.get("current_brightness").is_none());
+    assert_eq!(v3, load_golden("device_info"));
+    assert_eq!(info_at(JsonVersion::LATEST)["current_brightness"], 60);
+}
+
 // =============================================================================
 // Field Naming Convention Tests
 // =============================================================================
@@ -591,6 +864,16 @@ fn boolean_values_are_booleans() {
     assert!(json["pressed"].is_boolean(), "pressed must be a boolean");
 }
 
+#[test]
+fn physical_dimensions_fields() {
+    let physical = mock_device_xl().physical().expect("XL has displays");
+    let json = serde_json::to_value(physical).expect("serialize");
+
+    assert!(json["physical_key_mm"].is_number());
+    assert!(json["gap_mm"].is_number());
+    assert!(json["dpi"].is_number());
+}
+
 #[test]
 fn counts_are_numbers() {
     let device = mock_device_xl();
@@ -602,3 +885,95 @@ fn counts_are_numbers() {
     assert!(json["rows"].is_number());
     assert!(json["cols"].is_number());
 }
+
+/// A writer the test can read back after the output is done with it.
+#[derive(Clone, Default)]
+struct SharedBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuffer {
+    fn lines(&self) -> Vec<serde_json::Value> {
+        let text = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+        text.lines()
+            .map(|line| serde_json::from_str(line).expect("one JSON value per line"))
+            .collect()
+    }
+}
+
+#[test]
+fn key_display_events_are_labeled_lines() {
+    let buffer = SharedBuffer::default();
+    let output = RobotOutput::new(RobotFormat::Json)
+        .with_label(Some("demo".into()))
+        .with_writer(Box::new(buffer.clone()));
+
+    output.key_display(&KeyDisplayEvent::Displayed {
+        for_ms: 5000,
+        key: 3,
+        shows: "#ff0000".into(),
+        warnings: Vec::new(),
+    });
+    output.key_display(&KeyDisplayEvent::Ended {
+        interrupted: true,
+        key: 3,
+        then: AfterTimeout::Clear,
+    });
+
+    let lines = buffer.lines();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["event"], "displayed");
+    assert_eq!(lines[0]["for_ms"], 5000);
+    assert!(lines[0].get("warnings").is_none());
+    assert_eq!(lines[1]["event"], "ended");
+    assert_eq!(lines[1]["then"], "clear");
+    assert_eq!(lines[1]["interrupted"], true);
+    for line in &lines {
+        assert_eq!(line["label"], "demo");
+        assert_eq!(line["key"], 3);
+    }
+}
+
+#[test]
+fn tee_output_reaches_every_sink() {
+    let (first, second) = (SharedBuffer::default(), SharedBuffer::default());
+    let sink = |buffer: &SharedBuffer| -> Box<dyn Output> {
+        Box::new(RobotOutput::new(RobotFormat::Json).with_writer(Box::new(buffer.clone())))
+    };
+    let tee = TeeOutput::new(vec![sink(&first), sink(&second)]);
+
+    tee.key_cleared(4);
+    tee.error(&SdError::NoDevicesFound);
+    for buffer in [&first, &second] {
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["key"], 4);
+        assert_eq!(lines[1]["error"], true);
+    }
+}
+
+#[test]
+fn label_is_added_to_objects_only() {
+    let data = serde_json::json!({ "command": "version" });
+    let labeled: serde_json::Value = serde_json::from_str(
+        &render_json_versioned(&data, JsonVersion::LATEST, Some("build-7"), true, None).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(labeled["label"], "build-7");
+    assert_eq!(labeled["command"], "version");
+
+    let unlabeled = render_json_versioned(&data, JsonVersion::LATEST, None, true, None).unwrap();
+    assert!(!unlabeled.contains("label"));
+
+    let array = render_json_versioned(&[1, 2], JsonVersion::LATEST, Some("x"), true, None).unwrap();
+    assert_eq!(array, "[1,2]");
+}